@@ -15,7 +15,7 @@ use crate::{
         memory_region::{non_overlapping_regions_from, MemoryRegion, MemoryRegionType},
         BootloaderAcpiArg, BootloaderFramebufferArg,
     },
-    vm::{paddr_to_vaddr, PHYS_MEM_BASE_VADDR},
+    vm::{paddr_to_vaddr, phys_mem_base_vaddr},
 };
 
 static BOOT_PARAMS: Once<BootParams> = Once::new();
@@ -73,7 +73,7 @@ fn init_initramfs(initramfs: &'static Once<&'static [u8]>) {
         return;
     }
     // We must return a slice composed by VA since kernel should read everything in VA.
-    let base_va = if ptr < PHYS_MEM_BASE_VADDR {
+    let base_va = if ptr < phys_mem_base_vaddr() {
         paddr_to_vaddr(ptr)
     } else {
         ptr
@@ -136,6 +136,9 @@ fn init_memory_regions(memory_regions: &'static Once<Vec<MemoryRegion>>) {
     // Add the kernel region.
     regions.push(MemoryRegion::kernel());
 
+    // Add the region where the boot code of the application processors is placed.
+    regions.push(crate::arch::smp::ap_boot_region());
+
     // Add the initramfs region.
     regions.push(MemoryRegion::new(
         boot_params.hdr.ramdisk_image as usize,
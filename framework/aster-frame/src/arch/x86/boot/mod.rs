@@ -25,3 +25,4 @@ mod multiboot2;
 use core::arch::global_asm;
 
 global_asm!(include_str!("boot.S"));
+global_asm!(include_str!("ap_boot.S"));
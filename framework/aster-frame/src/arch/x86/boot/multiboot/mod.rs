@@ -12,7 +12,7 @@ use crate::{
         memory_region::{non_overlapping_regions_from, MemoryRegion, MemoryRegionType},
         BootloaderAcpiArg, BootloaderFramebufferArg,
     },
-    vm::{paddr_to_vaddr, PHYS_MEM_BASE_VADDR},
+    vm::{paddr_to_vaddr, phys_mem_base_vaddr},
 };
 
 global_asm!(include_str!("header.S"));
@@ -76,7 +76,7 @@ fn init_initramfs(initramfs: &'static Once<&'static [u8]>) {
         )
     };
     // We must return a slice composed by VA since kernel should read every in VA.
-    let base_va = if start < PHYS_MEM_BASE_VADDR {
+    let base_va = if start < phys_mem_base_vaddr() {
         paddr_to_vaddr(start)
     } else {
         start
@@ -139,6 +139,9 @@ fn init_memory_regions(memory_regions: &'static Once<Vec<MemoryRegion>>) {
     // Add the kernel region.
     regions.push(MemoryRegion::kernel());
 
+    // Add the region where the boot code of the application processors is placed.
+    regions.push(crate::arch::smp::ap_boot_region());
+
     // Add the initramfs area.
     if info.mods_count != 0 {
         let modules_addr = info.mods_addr as usize;
@@ -325,13 +328,15 @@ struct MemoryEntry {
     memory_type: MemoryAreaType,
 }
 
-static MB1_INFO: Once<&'static MultibootLegacyInfo> = Once::new();
+/// The multiboot information, which is copied since the direct mapping of
+/// physical memory may be moved during the boot.
+static MB1_INFO: Once<MultibootLegacyInfo> = Once::new();
 
 /// The entry point of Rust code called by inline asm.
 #[no_mangle]
 unsafe extern "sysv64" fn __multiboot_entry(boot_magic: u32, boot_params: u64) -> ! {
     assert_eq!(boot_magic, MULTIBOOT_ENTRY_MAGIC);
-    MB1_INFO.call_once(|| &*(paddr_to_vaddr(boot_params as usize) as *const MultibootLegacyInfo));
+    MB1_INFO.call_once(|| *(paddr_to_vaddr(boot_params as usize) as *const MultibootLegacyInfo));
     crate::boot::register_boot_init_callbacks(
         init_bootloader_name,
         init_kernel_commandline,
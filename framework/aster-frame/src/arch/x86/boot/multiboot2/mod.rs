@@ -17,7 +17,7 @@ use crate::boot::{
 
 global_asm!(include_str!("header.S"));
 
-use crate::vm::{paddr_to_vaddr, PHYS_MEM_BASE_VADDR};
+use crate::vm::{paddr_to_vaddr, phys_mem_base_vaddr};
 
 pub(super) const MULTIBOOT2_ENTRY_MAGIC: u32 = 0x36d76289;
 
@@ -55,7 +55,7 @@ fn init_initramfs(initramfs: &'static Once<&'static [u8]>) {
     };
     let base_addr = mb2_module_tag.start_address() as usize;
     // We must return a slice composed by VA since kernel should read every in VA.
-    let base_va = if base_addr < PHYS_MEM_BASE_VADDR {
+    let base_va = if base_addr < phys_mem_base_vaddr() {
         paddr_to_vaddr(base_addr)
     } else {
         base_addr
@@ -142,6 +142,9 @@ fn init_memory_regions(memory_regions: &'static Once<Vec<MemoryRegion>>) {
     // Add the kernel region since Grub does not specify it.
     regions.push(MemoryRegion::kernel());
 
+    // Add the region where the boot code of the application processors is placed.
+    regions.push(crate::arch::smp::ap_boot_region());
+
     // Add the boot module region since Grub does not specify it.
     let mb2_module_tag = mb2_info.module_tags();
     for module in mb2_module_tag {
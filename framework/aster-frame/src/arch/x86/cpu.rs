@@ -4,8 +4,9 @@
 
 use alloc::vec::Vec;
 use core::{
-    arch::x86_64::{_fxrstor, _fxsave},
+    arch::x86_64::{__rdtscp, _fxrstor, _fxsave},
     fmt::Debug,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
 };
 
 use bitflags::bitflags;
@@ -17,28 +18,108 @@ use log::debug;
 #[cfg(feature = "intel_tdx")]
 use tdx_guest::tdcall;
 use trapframe::{GeneralRegs, UserContext as RawUserContext};
+use x86::{
+    cpuid::cpuid,
+    msr::{wrmsr, IA32_TSC_AUX},
+};
 use x86_64::registers::rflags::RFlags;
 
 #[cfg(feature = "intel_tdx")]
 use crate::arch::tdx_guest::{handle_virtual_exception, TdxTrapFrame};
 use crate::{
+    cpu::MAX_CPUS,
     trap::call_irq_callback_functions,
     user::{UserContextApi, UserContextApiInternal, UserEvent},
 };
 
+/// The number of CPUs that have been brought online.
+static NUM_CPUS: AtomicU32 = AtomicU32::new(1);
+
+/// Whether each CPU has recorded its ID with `set_this_cpu_id`.
+///
+/// Before the application processors are started, only the bootstrap
+/// processor is running, whose ID is always zero.
+static IS_CPU_ID_READY: AtomicBool = AtomicBool::new(false);
+
+/// Whether the `RDTSCP` instruction can be used to read the CPU ID.
+static IS_RDTSCP_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
+/// The local APIC IDs of the online CPUs, indexed by the CPU IDs.
+///
+/// It is used to identify the current CPU if `RDTSCP` is not supported.
+static CPU_APIC_IDS: [AtomicU32; MAX_CPUS] = {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const INIT: AtomicU32 = AtomicU32::new(u32::MAX);
+    [INIT; MAX_CPUS]
+};
+
 /// Returns the number of CPUs.
 pub fn num_cpus() -> u32 {
-    // FIXME: we only start one cpu now.
-    1
+    NUM_CPUS.load(Ordering::Acquire)
 }
 
 /// Returns the ID of this CPU.
 pub fn this_cpu() -> u32 {
-    // FIXME: we only start one cpu now.
-    0
+    if !IS_CPU_ID_READY.load(Ordering::Acquire) {
+        return 0;
+    }
+    if IS_RDTSCP_SUPPORTED.load(Ordering::Relaxed) {
+        let mut cpu_id = 0;
+        // Safety: `RDTSCP` only reads the TSC and the `IA32_TSC_AUX` MSR.
+        unsafe { __rdtscp(&mut cpu_id) };
+        return cpu_id;
+    }
+    let apic_id = current_apic_id();
+    CPU_APIC_IDS
+        .iter()
+        .position(|id| id.load(Ordering::Relaxed) == apic_id)
+        .unwrap() as u32
+}
+
+/// Records the ID of the current CPU.
+///
+/// It must be called by each CPU before it accesses any CPU-local object.
+pub(crate) fn set_this_cpu_id(cpu_id: u32) {
+    if IS_RDTSCP_SUPPORTED.load(Ordering::Relaxed) {
+        // Safety: `IA32_TSC_AUX` is reserved for the OS and is only read by `RDTSCP`.
+        unsafe { wrmsr(IA32_TSC_AUX, cpu_id as u64) };
+    }
+    CPU_APIC_IDS[cpu_id as usize].store(current_apic_id(), Ordering::Relaxed);
 }
 
-#[derive(Default)]
+/// Initializes the CPU identification on the bootstrap processor.
+pub(crate) fn init_bsp() {
+    const RDTSCP_SUPPORT: u32 = 1 << 27;
+    let cpuid = cpuid!(0x8000_0001);
+    IS_RDTSCP_SUPPORTED.store(cpuid.edx & RDTSCP_SUPPORT != 0, Ordering::Relaxed);
+    set_this_cpu_id(0);
+    IS_CPU_ID_READY.store(true, Ordering::Release);
+}
+
+/// Returns the local APIC ID of the given CPU.
+pub(crate) fn apic_id_of(cpu_id: u32) -> u32 {
+    CPU_APIC_IDS[cpu_id as usize].load(Ordering::Relaxed)
+}
+
+/// Marks one more CPU as online, returning the new number of CPUs.
+pub(crate) fn inc_num_cpus() -> u32 {
+    NUM_CPUS.fetch_add(1, Ordering::AcqRel) + 1
+}
+
+/// Returns the initial local APIC ID of the current CPU according to CPUID.
+///
+/// Unlike reading the ID from the local APIC, it works before the local APIC
+/// of the current CPU is enabled.
+fn current_apic_id() -> u32 {
+    const X2APIC_TOPOLOGY_LEAF: u32 = 0xb;
+    if cpuid!(0).eax >= X2APIC_TOPOLOGY_LEAF {
+        cpuid!(X2APIC_TOPOLOGY_LEAF).edx
+    } else {
+        cpuid!(1).ebx >> 24
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct CpuSet {
     bitset: BitVec,
 }
@@ -212,23 +293,19 @@ impl TdxTrapFrame for GeneralRegs {
     }
 }
 
-pub struct UserPreemption {
-    count: u32,
-}
+pub struct UserPreemption;
 
 impl UserPreemption {
-    const PREEMPTION_INTERVAL: u32 = 100;
-
     pub const fn new() -> Self {
-        UserPreemption { count: 0 }
+        UserPreemption
     }
 
+    /// Switches to other tasks if the scheduler asks the current CPU to reschedule,
+    /// e.g., when the current task has used up its time slice.
     pub fn might_preempt(&mut self) {
-        self.count = (self.count + 1) % Self::PREEMPTION_INTERVAL;
-
-        if self.count == 0 {
+        if crate::task::need_resched() {
             crate::arch::irq::enable_local();
-            crate::task::schedule();
+            crate::task::preempt();
             crate::arch::irq::disable_local();
         }
     }
@@ -290,7 +367,7 @@ impl UserContextApiInternal for UserContext {
                     }
                 }
             };
-            call_irq_callback_functions(&self.as_trap_frame());
+            call_irq_callback_functions(&self.as_trap_frame(), true);
 
             user_preemption.might_preempt();
         }
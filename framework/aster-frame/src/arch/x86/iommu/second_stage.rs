@@ -118,6 +118,11 @@ impl PageTableFlagsTrait for PageTableFlags {
         // FIXME: this is super bad
         self
     }
+
+    fn set_accessed(mut self, accessed: bool) -> Self {
+        self.set(Self::ACCESSED, accessed);
+        self
+    }
 }
 
 impl PageTableEntry {
@@ -39,6 +39,13 @@ pub(crate) fn disable_local() {
     x86_64::instructions::interrupts::disable();
 }
 
+/// Enables local IRQs and halts the current CPU until the next IRQ arrives.
+///
+/// The two steps are done atomically, so an IRQ arriving in between will not be missed.
+pub(crate) fn enable_local_and_halt() {
+    x86_64::instructions::interrupts::enable_and_hlt();
+}
+
 pub(crate) fn is_local_enabled() -> bool {
     x86_64::instructions::interrupts::are_enabled()
 }
@@ -14,12 +14,88 @@ pub mod xapic;
 pub static APIC_INSTANCE: Once<Arc<SpinLock<dyn Apic + 'static>>> = Once::new();
 
 pub trait Apic: ApicTimer + Sync + Send {
+    /// Enable the local APIC of the current CPU.
+    fn enable(&mut self);
+
     fn id(&self) -> u32;
 
     fn version(&self) -> u32;
 
     /// End of Interrupt, this function will inform APIC that this interrupt has been processed.
     fn eoi(&mut self);
+
+    /// Send an inter-processor interrupt (IPI) through the interrupt command register (ICR).
+    ///
+    /// This function returns after the IPI has been accepted by the local APIC.
+    fn send_ipi(&mut self, icr: Icr);
+}
+
+/// The value of the interrupt command register (ICR), which describes an
+/// inter-processor interrupt.
+///
+/// Bit 0-7:   The vector number of the interrupt.
+/// Bit 8-10:  Delivery Mode.
+/// Bit 11:    Destination Mode, 0 for Physical, 1 for Logical.
+/// Bit 12:    Delivery Status, 0 for Idle, 1 for Send Pending.
+/// Bit 14:    Level, 0 for De-assert, 1 for Assert.
+/// Bit 15:    Trigger Mode, 0 for Edge, 1 for Level.
+/// Bit 18-19: Destination Shorthand.
+/// Bit 32-63: Destination field. Only bit 56-63 are used in xAPIC mode.
+#[derive(Debug, Clone, Copy)]
+pub struct Icr(u64);
+
+impl Icr {
+    const LEVEL_ASSERT: u64 = 1 << 14;
+
+    pub fn new(destination: IpiDestination, delivery_mode: DeliveryMode, vector: u8) -> Self {
+        let (shorthand, apic_id) = match destination {
+            IpiDestination::Physical(apic_id) => (0b00, apic_id),
+            IpiDestination::AllExcludingSelf => (0b11, 0),
+        };
+        Self(
+            (apic_id as u64) << 32
+                | shorthand << 18
+                | Self::LEVEL_ASSERT
+                | (delivery_mode as u64) << 8
+                | vector as u64,
+        )
+    }
+
+    /// The upper 32 bits of the ICR, with the destination field in the xAPIC format.
+    pub fn upper_xapic(&self) -> u32 {
+        ((self.0 >> 32) as u32) << 24
+    }
+
+    /// The lower 32 bits of the ICR.
+    pub fn lower(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// The full ICR value in the x2APIC format.
+    pub fn as_x2apic(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The destination of an inter-processor interrupt.
+#[derive(Debug, Clone, Copy)]
+pub enum IpiDestination {
+    /// The CPU whose local APIC has the given ID.
+    Physical(u32),
+    /// All CPUs except the sender.
+    AllExcludingSelf,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(u64)]
+pub enum DeliveryMode {
+    /// Deliver the interrupt specified in the vector field.
+    Fixed = 0b000,
+    /// Deliver an INIT request, which resets the target processor.
+    Init = 0b101,
+    /// Deliver a start-up request, which makes the target processor start
+    /// executing at the page indicated by the vector field.
+    StartUp = 0b110,
 }
 
 pub trait ApicTimer: Sync + Send {
@@ -60,6 +136,24 @@ pub enum DivideConfig {
     Divide128 = 0b1010,
 }
 
+/// Send an inter-processor interrupt with the local APIC of the current CPU.
+pub fn send_ipi(destination: IpiDestination, delivery_mode: DeliveryMode, vector: u8) {
+    let icr = Icr::new(destination, delivery_mode, vector);
+    APIC_INSTANCE
+        .get()
+        .unwrap()
+        .lock_irq_disabled()
+        .send_ipi(icr);
+}
+
+/// Enable the local APIC of an application processor.
+///
+/// The APIC instance is shared by all CPUs, since the registers of
+/// the local APIC are always accessed on the current CPU.
+pub fn init_ap() {
+    APIC_INSTANCE.get().unwrap().lock_irq_disabled().enable();
+}
+
 pub fn init() -> Result<(), ApicInitError> {
     crate::arch::x86::kernel::pic::disable_temp();
     if let Some(mut x2apic) = x2apic::X2Apic::new() {
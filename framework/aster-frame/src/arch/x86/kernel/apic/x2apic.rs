@@ -2,8 +2,8 @@
 
 use x86::msr::{
     rdmsr, wrmsr, IA32_APIC_BASE, IA32_X2APIC_APICID, IA32_X2APIC_CUR_COUNT, IA32_X2APIC_DIV_CONF,
-    IA32_X2APIC_EOI, IA32_X2APIC_INIT_COUNT, IA32_X2APIC_LVT_TIMER, IA32_X2APIC_SIVR,
-    IA32_X2APIC_VERSION,
+    IA32_X2APIC_EOI, IA32_X2APIC_ICR, IA32_X2APIC_INIT_COUNT, IA32_X2APIC_LVT_TIMER,
+    IA32_X2APIC_SIVR, IA32_X2APIC_VERSION,
 };
 
 use super::ApicTimer;
@@ -23,8 +23,10 @@ impl X2Apic {
         let value = unsafe { core::arch::x86_64::__cpuid(1) };
         value.ecx & 0x20_0000 != 0
     }
+}
 
-    pub fn enable(&mut self) {
+impl super::Apic for X2Apic {
+    fn enable(&mut self) {
         const X2APIC_ENABLE_BITS: u64 = {
             // IA32_APIC_BASE MSR's EN bit: xAPIC global enable/disable
             const EN_BIT_IDX: u8 = 11;
@@ -50,9 +52,7 @@ impl X2Apic {
             wrmsr(IA32_X2APIC_SIVR, svr);
         }
     }
-}
 
-impl super::Apic for X2Apic {
     fn id(&self) -> u32 {
         unsafe { rdmsr(IA32_X2APIC_APICID) as u32 }
     }
@@ -66,6 +66,14 @@ impl super::Apic for X2Apic {
             wrmsr(IA32_X2APIC_EOI, 0);
         }
     }
+
+    fn send_ipi(&mut self, icr: super::Icr) {
+        // In x2APIC mode, writing the ICR sends the IPI immediately and
+        // there is no delivery status to wait for.
+        unsafe {
+            wrmsr(IA32_X2APIC_ICR, icr.as_x2apic());
+        }
+    }
 }
 
 impl ApicTimer for X2Apic {
@@ -45,7 +45,14 @@ impl XApic {
         unsafe { core::ptr::write_volatile(&mut self.mmio_region[index], val) }
     }
 
-    pub fn enable(&mut self) {
+    pub fn has_xapic() -> bool {
+        let value = unsafe { core::arch::x86_64::__cpuid(1) };
+        value.edx & 0x100 != 0
+    }
+}
+
+impl super::Apic for XApic {
+    fn enable(&mut self) {
         // Enable xAPIC
         set_apic_base_address(get_apic_base_address());
 
@@ -54,13 +61,6 @@ impl XApic {
         self.write(xapic::XAPIC_SVR, svr);
     }
 
-    pub fn has_xapic() -> bool {
-        let value = unsafe { core::arch::x86_64::__cpuid(1) };
-        value.edx & 0x100 != 0
-    }
-}
-
-impl super::Apic for XApic {
     fn id(&self) -> u32 {
         self.read(xapic::XAPIC_ID)
     }
@@ -72,6 +72,18 @@ impl super::Apic for XApic {
     fn eoi(&mut self) {
         self.write(xapic::XAPIC_EOI, 0);
     }
+
+    fn send_ipi(&mut self, icr: super::Icr) {
+        const ICR_DELIVERY_STATUS_PENDING: u32 = 1 << 12;
+
+        // The IPI is sent when the lower half of the ICR is written,
+        // so the destination must be written first.
+        self.write(xapic::XAPIC_ICR1, icr.upper_xapic());
+        self.write(xapic::XAPIC_ICR0, icr.lower());
+        while self.read(xapic::XAPIC_ICR0) & ICR_DELIVERY_STATUS_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+    }
 }
 
 impl ApicTimer for XApic {
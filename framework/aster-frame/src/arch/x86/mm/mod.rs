@@ -4,10 +4,15 @@ use alloc::{collections::BTreeMap, fmt};
 
 use pod::Pod;
 use spin::Once;
-use x86_64::{instructions::tlb, structures::paging::PhysFrame, VirtAddr};
+use x86_64::{
+    instructions::tlb,
+    registers::control::{Cr4, Cr4Flags},
+    structures::paging::PhysFrame,
+    VirtAddr,
+};
 
 use crate::vm::{
-    page_table::{table_of, PageTableEntryTrait, PageTableFlagsTrait},
+    page_table::{table_of, AddressWidth, PageTableEntryTrait, PageTableFlagsTrait},
     Paddr, Vaddr,
 };
 
@@ -50,16 +55,52 @@ pub fn tlb_flush(vaddr: Vaddr) {
     tlb::flush(VirtAddr::new(vaddr as u64));
 }
 
-pub const fn is_user_vaddr(vaddr: Vaddr) -> bool {
-    // FIXME: Support 3/5 level page table.
-    // 47 = 12(offset) + 4 * 9(index) - 1
-    (vaddr >> 47) == 0
+/// Flushes all the TLB entries of the current CPU, including the global ones.
+pub fn tlb_flush_all_including_global() {
+    let cr4 = Cr4::read();
+    if cr4.contains(Cr4Flags::PAGE_GLOBAL) {
+        // Toggling CR4.PGE invalidates all the TLB entries.
+        // Safety: The page table is not changed, so the memory safety is preserved.
+        unsafe {
+            Cr4::write(cr4 - Cr4Flags::PAGE_GLOBAL);
+            Cr4::write(cr4);
+        }
+    } else {
+        tlb::flush_all();
+    }
+}
+
+/// Returns whether the CPU supports 1 GiB pages.
+pub(crate) fn has_1gib_pages() -> bool {
+    x86::cpuid::CpuId::new()
+        .get_extended_processor_and_feature_identifiers()
+        .is_some_and(|info| info.has_1gib_pages())
+}
+
+/// Returns the paging level of the CPU.
+///
+/// The boot code enables 5-level paging if the CPU supports it. Otherwise,
+/// 4-level paging is used.
+pub fn paging_level() -> AddressWidth {
+    static PAGING_LEVEL: Once<AddressWidth> = Once::new();
+    *PAGING_LEVEL.call_once(|| {
+        if Cr4::read().contains(Cr4Flags::L5_PAGING) {
+            AddressWidth::Level5
+        } else {
+            AddressWidth::Level4
+        }
+    })
+}
+
+pub fn is_user_vaddr(vaddr: Vaddr) -> bool {
+    // The user space is the lower half of the canonical addresses.
+    (vaddr >> (paging_level().vaddr_bits() - 1)) == 0
 }
 
-pub const fn is_kernel_vaddr(vaddr: Vaddr) -> bool {
-    // FIXME: Support 3/5 level page table.
-    // 47 = 12(offset) + 4 * 9(index) - 1
-    ((vaddr >> 47) & 0x1) == 1
+pub fn is_kernel_vaddr(vaddr: Vaddr) -> bool {
+    // The kernel space is the higher half of the canonical addresses.
+    let shift = paging_level().vaddr_bits() - 1;
+    (vaddr >> shift) == (usize::MAX >> shift)
 }
 
 #[derive(Clone, Copy, Pod)]
@@ -84,20 +125,67 @@ pub(crate) fn init() {
     let page_directory_base = page_directory_base.start_address().as_u64() as usize;
 
     // Safety: page_directory_base is read from Cr3, the address is valid.
-    let p4 = unsafe { table_of::<PageTableEntry>(page_directory_base).unwrap() };
+    let root = unsafe { table_of::<PageTableEntry>(page_directory_base).unwrap() };
     // Cancel mapping in lowest addresses.
-    p4[0].clear();
+    root[0].clear();
+    if matches!(paging_level(), AddressWidth::Level5) {
+        // The boot code shares the level-4 page table of the kernel space with
+        // the lowest addresses, which maps the lowest addresses at its first entry.
+        // Safety: the address is read from a valid page table.
+        let p4 = unsafe { table_of::<PageTableEntry>(kernel_p4_paddr()).unwrap() };
+        p4[0].clear();
+    }
     INIT_MAPPED_PTE.call_once(|| {
         let mut mapped_pte = BTreeMap::new();
-        for (i, p4_i) in p4.iter().enumerate().take(512) {
-            if p4_i.flags().contains(PageTableFlags::PRESENT) {
-                mapped_pte.insert(i, *p4_i);
+        for (i, root_i) in root.iter().enumerate().take(512) {
+            if root_i.flags().contains(PageTableFlags::PRESENT) {
+                mapped_pte.insert(i, *root_i);
             }
         }
         mapped_pte
     });
 }
 
+/// Returns the physical address of the level-4 page table that maps the kernel
+/// space.
+///
+/// With 5-level paging, it is the table of the last entry in the root page table.
+/// Otherwise, it is the root page table itself.
+pub(crate) fn kernel_p4_paddr() -> Paddr {
+    let (page_directory_base, _) = x86_64::registers::control::Cr3::read();
+    let page_directory_base = page_directory_base.start_address().as_u64() as usize;
+    match paging_level() {
+        AddressWidth::Level5 => {
+            // Safety: page_directory_base is read from Cr3, the address is valid.
+            let p5 = unsafe { table_of::<PageTableEntry>(page_directory_base).unwrap() };
+            p5[NR_ENTRIES_PER_PAGE - 1].paddr()
+        }
+        _ => page_directory_base,
+    }
+}
+
+/// Moves the direct mapping of physical memory built by the boot code from
+/// `old_base` to `new_base`.
+///
+/// # Safety
+///
+/// Both addresses must be aligned to the span of a level-4 page table entry, and
+/// the mapping at the new address must not overlap with other mappings. No
+/// addresses in the old mapping may be used after the call.
+pub(crate) unsafe fn move_phys_mem_mapping(old_base: Vaddr, new_base: Vaddr) {
+    let old_index = (old_base >> 39) % NR_ENTRIES_PER_PAGE;
+    let new_index = (new_base >> 39) % NR_ENTRIES_PER_PAGE;
+    if old_index == new_index {
+        return;
+    }
+
+    // Safety: the address is read from a valid page table.
+    let p4 = unsafe { table_of::<PageTableEntry>(kernel_p4_paddr()).unwrap() };
+    p4[new_index] = p4[old_index];
+    p4[old_index].clear();
+    tlb_flush_all_including_global();
+}
+
 impl PageTableFlagsTrait for PageTableFlags {
     fn new() -> Self {
         Self::empty()
@@ -177,6 +265,11 @@ impl PageTableFlagsTrait for PageTableFlags {
         self.set(Self::HUGE, huge);
         self
     }
+
+    fn set_accessed(mut self, accessed: bool) -> Self {
+        self.set(Self::ACCESSED, accessed);
+        self
+    }
 }
 
 impl PageTableEntry {
@@ -10,6 +10,7 @@ pub(crate) mod kernel;
 pub(crate) mod mm;
 pub(crate) mod pci;
 pub mod qemu;
+pub(crate) mod smp;
 #[cfg(feature = "intel_tdx")]
 pub(crate) mod tdx_guest;
 pub(crate) mod timer;
@@ -23,6 +24,7 @@ use log::{info, warn};
 
 pub(crate) fn before_all_init() {
     enable_common_cpu_features();
+    cpu::init_bsp();
     console::init();
 }
 
@@ -75,6 +77,19 @@ pub fn read_tsc() -> u64 {
     unsafe { _rdtsc() }
 }
 
+/// Returns a random number from the hardware.
+///
+/// This is not a cryptographically secure source if `RDRAND` is not supported, in
+/// which case the time-stamp counter is used instead.
+pub(crate) fn read_random() -> u64 {
+    if let Some(rdrand) = x86_64::instructions::random::RdRand::new()
+        && let Some(random) = rdrand.get_u64()
+    {
+        return random;
+    }
+    read_tsc()
+}
+
 fn enable_common_cpu_features() {
     use x86_64::registers::{control::Cr4Flags, model_specific::EferFlags, xcontrol::XCr0Flags};
     let mut cr4 = x86_64::registers::control::Cr4::read();
@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Multiprocessor support.
+//!
+//! The application processors (APs) are listed in the MADT of the ACPI tables.
+//! They are started one by one by the bootstrap processor (BSP) with the
+//! INIT-SIPI-SIPI sequence, and then run the boot code in `ap_boot.S`.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use acpi::{platform::ProcessorState, PlatformInfo};
+use log::{info, warn};
+
+use super::{
+    cpu::{apic_id_of, inc_num_cpus, num_cpus, set_this_cpu_id},
+    kernel::{
+        acpi::ACPI_TABLES,
+        apic::{self, DeliveryMode, IpiDestination, APIC_INSTANCE},
+    },
+    mm::{paging_level, PageTableEntry, NR_ENTRIES_PER_PAGE},
+    read_tsc, tsc_freq,
+};
+use crate::{
+    boot::memory_region::{MemoryRegion, MemoryRegionType},
+    cpu::MAX_CPUS,
+    vm::{
+        paddr_to_vaddr,
+        page_table::{table_of, AddressWidth, PageTableEntryTrait, KERNEL_PAGE_TABLE},
+        phys_mem_base_vaddr, VmAllocOptions, PAGE_SIZE,
+    },
+};
+
+/// The physical address where the boot code of the APs is copied to.
+///
+/// The APs start in the real mode, so the address must be page-aligned and below 1 MiB.
+/// It must be consistent with `ap_boot.S`.
+const AP_BOOT_START_PA: usize = 0x8000;
+
+/// The physical address of the temporary page table of the APs.
+///
+/// It must be consistent with `ap_boot.S`.
+const AP_BOOT_PAGE_TABLE_PA: usize = 0x9000;
+
+/// The physical address of the temporary level-4 page table of the APs, which is
+/// only used with 5-level paging.
+const AP_BOOT_P4_PA: usize = 0xA000;
+
+/// The size of the boot stack of an AP, which is also the stack of its idle loop.
+const AP_BOOT_STACK_SIZE: usize = PAGE_SIZE * 64;
+
+/// Whether the AP that is being started has finished its initialization.
+static AP_ONLINE: AtomicBool = AtomicBool::new(false);
+
+/// Returns the memory region that is reserved for the boot code and the temporary
+/// page table of the APs.
+pub fn ap_boot_region() -> MemoryRegion {
+    MemoryRegion::new(
+        AP_BOOT_START_PA,
+        AP_BOOT_P4_PA + PAGE_SIZE - AP_BOOT_START_PA,
+        MemoryRegionType::Reserved,
+    )
+}
+
+/// Sends an IPI with the given vector to the given CPU.
+pub(crate) fn send_ipi(cpu_id: u32, vector: u8) {
+    apic::send_ipi(
+        IpiDestination::Physical(apic_id_of(cpu_id)),
+        DeliveryMode::Fixed,
+        vector,
+    );
+}
+
+/// Sends an IPI with the given vector to all the CPUs except the current one.
+pub(crate) fn broadcast_ipi(vector: u8) {
+    apic::send_ipi(
+        IpiDestination::AllExcludingSelf,
+        DeliveryMode::Fixed,
+        vector,
+    );
+}
+
+/// Starts all the APs listed in the ACPI tables.
+///
+/// This function must be called on the BSP after the local APIC and the timer are initialized.
+pub(crate) fn boot_aps() {
+    if !ACPI_TABLES.is_completed() || !APIC_INSTANCE.is_completed() {
+        return;
+    }
+
+    let ap_apic_ids: Vec<u32> = {
+        let table = ACPI_TABLES.get().unwrap().lock();
+        let Ok(platform_info) = PlatformInfo::new(&*table) else {
+            return;
+        };
+        let Some(processor_info) = platform_info.processor_info else {
+            return;
+        };
+        processor_info
+            .application_processors
+            .iter()
+            .filter(|processor| !matches!(processor.state, ProcessorState::Disabled))
+            .map(|processor| processor.local_apic_id)
+            .collect()
+    };
+    if ap_apic_ids.is_empty() {
+        return;
+    }
+
+    copy_ap_boot_code();
+    init_ap_boot_page_table();
+
+    for apic_id in ap_apic_ids {
+        let cpu_id = num_cpus();
+        if cpu_id as usize >= MAX_CPUS {
+            warn!(
+                "[SMP]: At most {} CPUs are supported, the remaining CPUs are ignored",
+                MAX_CPUS
+            );
+            break;
+        }
+        if boot_ap(cpu_id, apic_id) {
+            inc_num_cpus();
+        } else {
+            warn!("[SMP]: Failed to start the CPU with APIC ID {}", apic_id);
+        }
+    }
+    info!("[SMP]: {} CPUs are online", num_cpus());
+}
+
+/// Starts an AP and waits for it to finish its initialization.
+///
+/// Returns `false` if the AP does not respond in time.
+fn boot_ap(cpu_id: u32, apic_id: u32) -> bool {
+    const INIT_DELAY_US: u64 = 10_000;
+    const STARTUP_DELAY_US: u64 = 200;
+    const ONLINE_TIMEOUT_US: u64 = 1_000_000;
+
+    // The boot stack is used by the AP forever, so it is never freed.
+    let stack = VmAllocOptions::new(AP_BOOT_STACK_SIZE / PAGE_SIZE)
+        .is_contiguous(true)
+        .alloc_contiguous()
+        .expect("failed to allocate the boot stack of an AP");
+    let stack_top = paddr_to_vaddr(stack.end_paddr());
+    core::mem::forget(stack);
+
+    let page_table_paddr = KERNEL_PAGE_TABLE.get().unwrap().lock().root_paddr();
+    // Safety: The boot code of the APs has been copied and no AP is running it now.
+    unsafe {
+        write_ap_boot_data(ApBootData::StackTop, stack_top as u64);
+        write_ap_boot_data(ApBootData::PageTable, page_table_paddr as u64);
+        write_ap_boot_data(ApBootData::CpuId, cpu_id as u64);
+        write_ap_boot_data(
+            ApBootData::La57,
+            matches!(paging_level(), AddressWidth::Level5) as u64,
+        );
+    }
+    AP_ONLINE.store(false, Ordering::Release);
+
+    let destination = IpiDestination::Physical(apic_id);
+    apic::send_ipi(destination, DeliveryMode::Init, 0);
+    spin_wait_us(INIT_DELAY_US);
+    let start_page = (AP_BOOT_START_PA / PAGE_SIZE) as u8;
+    for _ in 0..2 {
+        apic::send_ipi(destination, DeliveryMode::StartUp, start_page);
+        spin_wait_us(STARTUP_DELAY_US);
+    }
+
+    let deadline = read_tsc() + tsc_freq() / 1_000_000 * ONLINE_TIMEOUT_US;
+    while !AP_ONLINE.load(Ordering::Acquire) {
+        if read_tsc() > deadline {
+            return false;
+        }
+        core::hint::spin_loop();
+    }
+    true
+}
+
+/// The entry of the Rust code on an AP, which is called by `ap_boot.S`.
+#[no_mangle]
+extern "sysv64" fn ap_early_entry(cpu_id: u32) -> ! {
+    set_this_cpu_id(cpu_id);
+    super::enable_common_cpu_features();
+    crate::trap::init();
+    apic::init_ap();
+    super::timer::init_ap();
+    info!("[SMP]: CPU {} is online", cpu_id);
+    AP_ONLINE.store(true, Ordering::Release);
+
+    crate::task::idle_loop()
+}
+
+extern "C" {
+    fn __ap_boot_start();
+    fn __ap_boot_end();
+    fn __ap_boot_stack_top();
+    fn __ap_boot_page_table();
+    fn __ap_boot_cpu_id();
+    fn __ap_boot_la57();
+}
+
+/// The data fields in the boot code, which are filled before starting each AP.
+enum ApBootData {
+    StackTop,
+    PageTable,
+    CpuId,
+    La57,
+}
+
+/// Writes a data field in the copy of the boot code.
+///
+/// # Safety
+///
+/// The boot code must have been copied, and no AP is reading the data fields.
+unsafe fn write_ap_boot_data(field: ApBootData, value: u64) {
+    let symbol = match field {
+        ApBootData::StackTop => __ap_boot_stack_top as usize,
+        ApBootData::PageTable => __ap_boot_page_table as usize,
+        ApBootData::CpuId => __ap_boot_cpu_id as usize,
+        ApBootData::La57 => __ap_boot_la57 as usize,
+    };
+    let offset = symbol - __ap_boot_start as usize;
+    let ptr = paddr_to_vaddr(AP_BOOT_START_PA + offset) as *mut u64;
+    ptr.write_volatile(value);
+}
+
+/// Copies the boot code of the APs to `AP_BOOT_START_PA`.
+fn copy_ap_boot_code() {
+    let len = __ap_boot_end as usize - __ap_boot_start as usize;
+    assert!(len <= AP_BOOT_PAGE_TABLE_PA - AP_BOOT_START_PA);
+    // Safety: The destination is reserved for the boot code of the APs and is not used
+    // by anyone else.
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            __ap_boot_start as *const u8,
+            paddr_to_vaddr(AP_BOOT_START_PA) as *mut u8,
+            len,
+        );
+    }
+}
+
+/// Initializes the temporary page table of the APs.
+///
+/// It is a copy of the root of the kernel page table, with the lowest 4 GiB mapped
+/// identically so that the APs can enable paging while running the boot code.
+/// With 5-level paging, the level-4 page table of the kernel space is copied as well.
+fn init_ap_boot_page_table() {
+    // The span of a level-4 page table entry.
+    const P4_ENTRY_SPAN: usize = PAGE_SIZE * NR_ENTRIES_PER_PAGE.pow(3);
+    let linear_mapping_index = (phys_mem_base_vaddr() / P4_ENTRY_SPAN) % NR_ENTRIES_PER_PAGE;
+
+    let kernel_pt_paddr = KERNEL_PAGE_TABLE.get().unwrap().lock().root_paddr();
+    // Safety: The destination is reserved for the temporary page table of the APs.
+    // The boot page table maps the lowest 4 GiB in the linear mapping, so
+    // reusing its entry gives the identical mapping.
+    unsafe {
+        let src = table_of::<PageTableEntry>(kernel_pt_paddr).unwrap();
+        let dst = table_of::<PageTableEntry>(AP_BOOT_PAGE_TABLE_PA).unwrap();
+        dst.copy_from_slice(src);
+
+        let p4 = match paging_level() {
+            AddressWidth::Level5 => {
+                let kernel_p4 = dst[NR_ENTRIES_PER_PAGE - 1];
+                let p4 = table_of::<PageTableEntry>(AP_BOOT_P4_PA).unwrap();
+                p4.copy_from_slice(table_of::<PageTableEntry>(kernel_p4.paddr()).unwrap());
+                dst[0] = PageTableEntry::new(AP_BOOT_P4_PA, kernel_p4.flags());
+                p4
+            }
+            _ => dst,
+        };
+        p4[0] = p4[linear_mapping_index];
+    }
+}
+
+/// Busy waits for the given number of microseconds.
+fn spin_wait_us(us: u64) {
+    let deadline = read_tsc() + tsc_freq() / 1_000_000 * us;
+    while read_tsc() < deadline {
+        core::hint::spin_loop();
+    }
+}
@@ -38,6 +38,27 @@ pub fn init() {
     }
 }
 
+/// Initializes the APIC timer of an application processor in the same mode as
+/// the bootstrap processor.
+pub(super) fn init_ap() {
+    let timer_irq_num = super::TIMER_IRQ_NUM.load(Ordering::Relaxed) as u64;
+    let mut apic_lock = APIC_INSTANCE.get().unwrap().lock_irq_disabled();
+    if is_tsc_deadline_mode_supported() {
+        apic_lock.set_lvt_timer(timer_irq_num | (1 << 18));
+        drop(apic_lock);
+        APIC_TIMER_CALLBACK.get().unwrap().call(());
+    } else {
+        // The APIC timers of all CPUs are assumed to run at the same frequency,
+        // so the count calibrated by the bootstrap processor is reused.
+        apic_lock.set_timer_div_config(DivideConfig::Divide64);
+        apic_lock.set_lvt_timer(timer_irq_num | (1 << 17));
+        apic_lock.set_timer_init_count(PERIODIC_INIT_COUNT.load(Ordering::Relaxed));
+    }
+}
+
+/// The initial count of the APIC timer in the periodic mode.
+static PERIODIC_INIT_COUNT: AtomicU64 = AtomicU64::new(0);
+
 pub(super) static APIC_TIMER_CALLBACK: Once<Arc<dyn Fn() + Sync + Send>> = Once::new();
 
 /// Determine if the current system supports tsc_deadline mode APIC timer
@@ -113,6 +134,7 @@ fn init_periodic_mode() {
         let ticks = (0xFFFF_FFFF - remain_ticks - APIC_FIRST_COUNT.load(Ordering::Relaxed))
             / CALLBACK_TIMES;
         apic_lock.set_timer_init_count(ticks);
+        PERIODIC_INIT_COUNT.store(ticks, Ordering::Relaxed);
         apic_lock.set_lvt_timer(super::TIMER_IRQ_NUM.load(Ordering::Relaxed) as u64 | (1 << 17));
         apic_lock.set_timer_div_config(DivideConfig::Divide64);
         info!(
@@ -14,7 +14,7 @@ use spin::Once;
 use trapframe::TrapFrame;
 
 use self::apic::APIC_TIMER_CALLBACK;
-use crate::{arch::x86::kernel, sync::SpinLock, trap::IrqLine};
+use crate::{arch::x86::kernel, cpu::this_cpu, sync::SpinLock, trap::IrqLine};
 
 /// The timer frequency (Hz). Here we choose 1000Hz since 1000Hz is easier for unit conversion and
 /// convenient for timer. What's more, the frequency cannot be set too high or too low, 1000Hz is
@@ -49,7 +49,26 @@ pub fn init() {
     TIMER_IRQ.call_once(|| timer_irq);
 }
 
+/// Initializes the timer of an application processor.
+pub(crate) fn init_ap() {
+    apic::init_ap();
+}
+
 fn timer_callback(trap_frame: &TrapFrame) {
+    // The timer interrupts arrive on every CPU. The system ticks and the timeout
+    // list are only driven by the bootstrap processor, while the running task is
+    // charged on each CPU so that its time slice expires wherever it runs.
+    if this_cpu() == 0 {
+        handle_ticks();
+    }
+    crate::task::scheduler_tick();
+
+    if APIC_TIMER_CALLBACK.is_completed() {
+        APIC_TIMER_CALLBACK.get().unwrap().call(());
+    }
+}
+
+fn handle_ticks() {
     let current_ticks = TICK.fetch_add(1, Ordering::SeqCst);
 
     let callbacks = {
@@ -72,10 +91,6 @@ fn timer_callback(trap_frame: &TrapFrame) {
     for callback in callbacks {
         (callback.callback)(&callback);
     }
-
-    if APIC_TIMER_CALLBACK.is_completed() {
-        APIC_TIMER_CALLBACK.get().unwrap().call(());
-    }
 }
 
 static TIMEOUT_LIST: Once<SpinLock<BinaryHeap<Arc<TimerCallback>>>> = Once::new();
@@ -9,7 +9,7 @@
 //!
 
 use alloc::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     ffi::CString,
     string::{String, ToString},
     vec,
@@ -31,11 +31,21 @@ pub enum ModuleArg {
     KeyVal(CString, CString),
 }
 
+/// The kernel options without values that are recognized by the kernel, which
+/// are not passed to the initprocess.
+const KERNEL_FLAGS: &[&str] = &[
+    // Disables the randomization of the kernel address space layout.
+    "nokaslr",
+    // Disables the randomization of the user address space layout.
+    "norandmaps",
+];
+
 /// The struct to store the parsed kernel command-line arguments.
 #[derive(Debug)]
 pub struct KCmdlineArg {
     initproc: InitprocArgs,
     module_args: BTreeMap<String, Vec<ModuleArg>>,
+    kernel_flags: BTreeSet<String>,
 }
 
 // Define get APIs.
@@ -56,6 +66,10 @@ impl KCmdlineArg {
     pub fn get_module_args(&self, module: &str) -> Option<&Vec<ModuleArg>> {
         self.module_args.get(module)
     }
+    /// Check whether a kernel option without value (e.g., `nokaslr`) is specified.
+    pub fn has_kernel_flag(&self, flag: &str) -> bool {
+        self.kernel_flags.contains(flag)
+    }
 }
 
 // Split the command line string by spaces but preserve
@@ -83,6 +97,7 @@ impl From<&str> for KCmdlineArg {
                 envp: Vec::new(),
             },
             module_args: BTreeMap::new(),
+            kernel_flags: BTreeSet::new(),
         };
 
         // Every thing after the "--" mark is the initproc arguments.
@@ -163,6 +178,10 @@ impl From<&str> for KCmdlineArg {
                 }
             } else {
                 // There is no value, the entry is only a option.
+                if KERNEL_FLAGS.contains(&option) {
+                    result.kernel_flags.insert(option.to_string());
+                    continue;
+                }
 
                 // If the option is not recognized, it is passed to the initproc.
                 // Pattern 'option' without value is treated as the init argument.
@@ -76,9 +76,14 @@ macro_rules! define_global_static_boot_arguments {
             });
         }
 
+        /// Calls the boot init callbacks whose boot information is not initialized yet.
         fn call_all_boot_init_callbacks() {
             let callbacks = &BOOT_INIT_CALLBACKS.get().unwrap();
-            $( (callbacks.$lower)(&$upper); )*
+            $(
+                if !$upper.is_completed() {
+                    (callbacks.$lower)(&$upper);
+                }
+            )*
         }
     };
 }
@@ -100,6 +105,14 @@ define_global_static_boot_arguments!(
 /// The initialization must be done after the heap is set and before physical
 /// mappings are cancelled.
 pub fn init() {
+    // The direct mapping of physical memory is randomized according to the kernel
+    // command line and the memory regions, before the other boot information (e.g.,
+    // the initramfs) is initialized with the addresses in the direct mapping.
+    let callbacks = &BOOT_INIT_CALLBACKS.get().unwrap();
+    (callbacks.kernel_cmdline)(&KERNEL_CMDLINE);
+    (callbacks.memory_regions)(&MEMORY_REGIONS);
+    crate::vm::randomize_phys_mem_base();
+
     call_all_boot_init_callbacks();
 }
 
@@ -4,7 +4,10 @@
 
 use core::{cell::UnsafeCell, ops::Deref};
 
-use crate::trap::disable_local;
+use crate::{
+    task::{disable_preempt, DisablePreemptGuard},
+    trap::disable_local,
+};
 
 cfg_if::cfg_if! {
     if #[cfg(target_arch = "x86_64")]{
@@ -13,6 +16,12 @@ cfg_if::cfg_if! {
     }
 }
 
+/// The maximum number of CPUs supported by the framework.
+///
+/// Each CPU-local variable reserves one slot for every possible CPU, so this
+/// value also bounds the memory footprint of CPU-local variables.
+pub const MAX_CPUS: usize = 64;
+
 /// Defines a CPU-local variable.
 ///
 /// # Example
@@ -39,47 +48,71 @@ macro_rules! cpu_local {
 
     // multiple declarations
     ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr; $($rest:tt)*) => {
-        $(#[$attr])* $vis static $name: $crate::CpuLocal<$t> = unsafe { $crate::CpuLocal::new($init) };
+        $crate::cpu_local!($(#[$attr])* $vis static $name: $t = $init);
         $crate::cpu_local!($($rest)*);
     };
 
     // single declaration
     ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr) => (
-        // TODO: reimplement cpu-local variable to support multi-core
-        $(#[$attr])* $vis static $name: $crate::CpuLocal<$t> = $crate::CpuLocal::new($init);
+        $(#[$attr])* $vis static $name: $crate::CpuLocal<$t> = {
+            // A constant item can be repeated in an array expression even if
+            // its type is not `Copy`, which gives every CPU its own copy.
+            #[allow(clippy::declare_interior_mutable_const)]
+            const INIT: $crate::cpu::CpuLocalSlot<$t> = $crate::cpu::CpuLocalSlot::new($init);
+            // Safety: The object is declared as a static variable and is only
+            // accessed through the methods of `CpuLocal`.
+            unsafe { $crate::CpuLocal::new([INIT; $crate::cpu::MAX_CPUS]) }
+        };
     );
 }
 
 /// CPU-local objects.
 ///
+/// Each CPU owns a separate instance of the underlying value, which is selected
+/// by the ID of the CPU that performs the access.
+///
 /// A CPU-local object only gives you immutable references to the underlying value.
 /// To mutate the value, one can use atomic values (e.g., `AtomicU32`) or internally mutable
 /// objects (e.g., `RefCell`).
 ///
-/// The `CpuLocal<T: Sync>` can be used directly.
+/// The `CpuLocal<T: Sync>` can be used through `CpuLocal::borrow`.
 /// Otherwise, the `CpuLocal<T>` must be used through `CpuLocal::borrow_with`.
+pub struct CpuLocal<T>([CpuLocalSlot<T>; MAX_CPUS]);
+
+/// The storage of a CPU-local object on a single CPU.
 ///
-/// TODO: re-implement `CpuLocal`
-pub struct CpuLocal<T>(UnsafeCell<T>);
+/// Slots are aligned to cache lines so that CPUs do not contend on the same
+/// cache line when they access their own instances.
+#[doc(hidden)]
+#[repr(align(64))]
+pub struct CpuLocalSlot<T>(UnsafeCell<T>);
 
-// Safety. At any given time, only one task can access the inner value T of a cpu-local variable.
+impl<T> CpuLocalSlot<T> {
+    #[doc(hidden)]
+    pub const fn new(val: T) -> Self {
+        Self(UnsafeCell::new(val))
+    }
+}
+
+// Safety. At any given time, only the tasks and IRQ handlers on one CPU can access
+// the instance of the inner value T that belongs to this CPU.
 unsafe impl<T> Sync for CpuLocal<T> {}
 
 impl<T> CpuLocal<T> {
     /// Initialize CPU-local object
     /// Developer cannot construct a valid CpuLocal object arbitrarily
     #[allow(clippy::missing_safety_doc)]
-    pub const unsafe fn new(val: T) -> Self {
-        Self(UnsafeCell::new(val))
+    pub const unsafe fn new(slots: [CpuLocalSlot<T>; MAX_CPUS]) -> Self {
+        Self(slots)
     }
 
     /// Borrow an immutable reference to the underlying value and feed it to a closure.
     ///
     /// During the execution of the closure, local IRQs are disabled. This ensures that
-    /// the CPU-local object is only accessed by the current task or IRQ handler.
+    /// the CPU-local object is only accessed by the current task or IRQ handler, and
+    /// that the current task cannot be migrated to another CPU.
     /// As local IRQs are disabled, one should keep the closure as short as possible.
     pub fn borrow_with<U, F: FnOnce(&T) -> U>(this: &Self, f: F) -> U {
-        // FIXME: implement disable preemption
         // Disable interrupts when accessing cpu-local variable
         let _guard = disable_local();
         // Safety. Now that the local IRQs are disabled, this CPU-local object can only be
@@ -89,15 +122,49 @@ impl<T> CpuLocal<T> {
         f(val_ref)
     }
 
+    /// Borrows an immutable reference to the instance of the current CPU.
+    ///
+    /// Preemption is disabled until the returned guard is dropped, so that the current
+    /// task cannot be migrated to another CPU while it holds the reference.
+    pub fn borrow(&self) -> CpuLocalGuard<'_, T>
+    where
+        T: Sync,
+    {
+        let guard = disable_preempt();
+        // Safety: The current task stays on this CPU while the guard is alive, and
+        // `T: Sync` allows the IRQ handlers to access the instance concurrently.
+        let val = unsafe { self.do_borrow() };
+        CpuLocalGuard { val, _guard: guard }
+    }
+
+    /// Returns the instance that belongs to the given CPU.
+    ///
+    /// Since the instance may be accessed by another CPU concurrently,
+    /// it requires `T: Sync`.
+    pub fn get_on_cpu(&self, cpu_id: u32) -> &T
+    where
+        T: Sync,
+    {
+        unsafe { &*self.0[cpu_id as usize].0.get() }
+    }
+
     unsafe fn do_borrow(&self) -> &T {
-        &*self.0.get()
+        &*self.0[this_cpu() as usize].0.get()
     }
 }
 
-impl<T: Sync> Deref for CpuLocal<T> {
+/// A reference to the instance of a CPU-local object on the current CPU.
+///
+/// See `CpuLocal::borrow` for more details.
+pub struct CpuLocalGuard<'a, T> {
+    val: &'a T,
+    _guard: DisablePreemptGuard,
+}
+
+impl<'a, T> Deref for CpuLocalGuard<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { &*self.0.get() }
+        self.val
     }
 }
@@ -36,6 +36,7 @@ pub mod io_mem;
 pub mod logger;
 pub mod panicking;
 pub mod prelude;
+pub mod smp;
 pub mod sync;
 pub mod task;
 pub mod timer;
@@ -65,6 +66,7 @@ pub fn init() {
     vm::init();
     trap::init();
     arch::after_all_init();
+    smp::init();
     bus::init();
     invoke_ffi_init_funcs();
 }
@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Symmetric multiprocessing (SMP).
+//!
+//! This module starts the application processors and provides the
+//! inter-processor interrupts (IPIs) that coordinate the CPUs.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use spin::Once;
+
+use crate::{
+    arch::{self, mm::tlb_flush_all_including_global},
+    cpu::{num_cpus, this_cpu},
+    sync::SpinLock,
+    task::{check_preempt, set_need_resched},
+    trap::IrqLine,
+};
+
+static RESCHEDULE_IRQ: Once<IrqLine> = Once::new();
+
+static TLB_SHOOTDOWN_IRQ: Once<IrqLine> = Once::new();
+
+/// Serializes the TLB shootdowns.
+static TLB_SHOOTDOWN_LOCK: SpinLock<()> = SpinLock::new(());
+
+/// The bitmap of the CPUs that have not flushed their TLBs for the ongoing TLB shootdown.
+static TLB_SHOOTDOWN_PENDING: AtomicU64 = AtomicU64::new(0);
+
+const_assert!(crate::cpu::MAX_CPUS <= u64::BITS as usize);
+
+pub(crate) fn init() {
+    let mut reschedule_irq = IrqLine::alloc().unwrap();
+    reschedule_irq.on_active(|_| check_preempt());
+    RESCHEDULE_IRQ.call_once(|| reschedule_irq);
+
+    let mut tlb_shootdown_irq = IrqLine::alloc().unwrap();
+    tlb_shootdown_irq.on_active(|_| handle_tlb_shootdown());
+    TLB_SHOOTDOWN_IRQ.call_once(|| tlb_shootdown_irq);
+
+    arch::smp::boot_aps();
+}
+
+/// Asks the given CPU to reschedule.
+///
+/// The CPU will switch to the next task in its run queue when it gets the
+/// chance to, e.g., when it is idle or is about to return to the user space.
+pub fn send_reschedule_ipi(cpu_id: u32) {
+    if cpu_id == this_cpu() {
+        set_need_resched();
+        return;
+    }
+    if let Some(irq) = RESCHEDULE_IRQ.get() {
+        arch::smp::send_ipi(cpu_id, irq.num());
+    }
+}
+
+/// Flushes the TLBs of all the other CPUs.
+///
+/// This function must be called after a mapping of the kernel space is changed.
+/// See `tlb_shootdown` for more details.
+pub(crate) fn tlb_shootdown_all() {
+    tlb_shootdown(u64::MAX);
+}
+
+/// Flushes the TLBs of the CPUs in the bitmap `cpus`, except the current CPU.
+///
+/// This function must be called after a mapping that may be cached by other CPUs
+/// is changed, and the TLB of the current CPU must be flushed by the caller.
+/// It returns after the target CPUs have flushed their TLBs.
+///
+/// The caller should not disable the local IRQs, since the target CPUs may be
+/// waiting for this CPU to respond to their own shootdowns or other IPIs.
+pub(crate) fn tlb_shootdown(cpus: u64) {
+    let this_cpu = this_cpu();
+    let online_cpus = (0..num_cpus()).fold(0u64, |bitmap, cpu_id| bitmap | 1 << cpu_id);
+    let target_cpus = cpus & online_cpus & !(1 << this_cpu);
+    if target_cpus == 0 {
+        return;
+    }
+    let Some(irq) = TLB_SHOOTDOWN_IRQ.get() else {
+        return;
+    };
+
+    let _guard = loop {
+        if let Some(guard) = TLB_SHOOTDOWN_LOCK.try_lock() {
+            break guard;
+        }
+        // The CPU holding the lock may be waiting for this CPU, whose local IRQs
+        // may be disabled. Respond to it here to avoid deadlocks.
+        handle_tlb_shootdown();
+        core::hint::spin_loop();
+    };
+
+    TLB_SHOOTDOWN_PENDING.store(target_cpus, Ordering::Release);
+    if target_cpus == online_cpus & !(1 << this_cpu) {
+        arch::smp::broadcast_ipi(irq.num());
+    } else {
+        (0..num_cpus())
+            .filter(|&cpu_id| target_cpus & 1 << cpu_id != 0)
+            .for_each(|cpu_id| arch::smp::send_ipi(cpu_id, irq.num()));
+    }
+    while TLB_SHOOTDOWN_PENDING.load(Ordering::Acquire) != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+fn handle_tlb_shootdown() {
+    let this_cpu_bit = 1u64 << this_cpu();
+    if TLB_SHOOTDOWN_PENDING.load(Ordering::Acquire) & this_cpu_bit != 0 {
+        tlb_flush_all_including_global();
+        TLB_SHOOTDOWN_PENDING.fetch_and(!this_cpu_bit, Ordering::Release);
+    }
+}
@@ -8,6 +8,9 @@ mod scheduler;
 #[allow(clippy::module_inception)]
 mod task;
 
+pub(crate) use self::processor::{
+    check_preempt, idle_loop, is_preemptible, need_resched, scheduler_tick, set_need_resched,
+};
 pub use self::{
     priority::Priority,
     processor::{current_task, disable_preempt, preempt, schedule, DisablePreemptGuard},
@@ -1,27 +1,41 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::sync::Arc;
-use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
-
-use lazy_static::lazy_static;
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering::Relaxed},
+};
 
 use super::{
-    scheduler::{fetch_task, GLOBAL_SCHEDULER},
-    task::{context_switch, TaskContext},
+    scheduler::{add_task, fetch_task, GLOBAL_SCHEDULER},
+    task::{context_switch, KernelStack, TaskContext},
     Task, TaskStatus,
 };
-use crate::{cpu_local, sync::Mutex};
+use crate::{
+    arch::irq,
+    cpu::{this_cpu, CpuLocal},
+    cpu_local,
+    vm::paddr_to_vaddr,
+};
 
 pub struct Processor {
     current: Option<Arc<Task>>,
+    /// The task that is switched out by the last context switch on this processor.
+    ///
+    /// It can only be handled in `finish_task_switch` after its context is saved.
+    prev: Option<Arc<Task>>,
     idle_task_cx: TaskContext,
+    /// The stack on which the idle loop runs.
+    idle_stack: Option<KernelStack>,
 }
 
 impl Processor {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             current: None,
-            idle_task_cx: TaskContext::default(),
+            prev: None,
+            idle_task_cx: TaskContext::new(),
+            idle_stack: None,
         }
     }
     fn get_idle_task_cx_ptr(&mut self) -> *mut TaskContext {
@@ -38,26 +52,77 @@ impl Processor {
     }
 }
 
-lazy_static! {
-    static ref PROCESSOR: Mutex<Processor> = Mutex::new(Processor::new());
+cpu_local! {
+    static PROCESSOR: RefCell<Processor> = RefCell::new(Processor::new());
+    static NEED_RESCHED: AtomicBool = AtomicBool::new(false);
+}
+
+fn with_processor<R, F: FnOnce(&mut Processor) -> R>(f: F) -> R {
+    CpuLocal::borrow_with(&PROCESSOR, |processor| f(&mut processor.borrow_mut()))
 }
 
 pub fn take_current_task() -> Option<Arc<Task>> {
-    PROCESSOR.lock().take_current()
+    with_processor(|processor| processor.take_current())
 }
 
 pub fn current_task() -> Option<Arc<Task>> {
-    PROCESSOR.lock().current()
+    with_processor(|processor| processor.current())
 }
 
 pub(crate) fn get_idle_task_cx_ptr() -> *mut TaskContext {
-    PROCESSOR.lock().get_idle_task_cx_ptr()
+    with_processor(|processor| processor.get_idle_task_cx_ptr())
+}
+
+/// Marks that the current CPU should reschedule as soon as possible.
+pub(crate) fn set_need_resched() {
+    NEED_RESCHED.borrow().store(true, Relaxed);
+}
+
+/// Returns whether the current CPU has been asked to reschedule.
+pub(crate) fn need_resched() -> bool {
+    NEED_RESCHED.borrow().load(Relaxed)
+}
+
+/// Charges a timer tick to the task running on the current CPU.
+///
+/// The current CPU is marked to reschedule if the scheduler decides that the
+/// task has run long enough.
+pub(crate) fn scheduler_tick() {
+    let Some(scheduler) = GLOBAL_SCHEDULER.get() else {
+        return;
+    };
+    let Some(curr_task) = current_task() else {
+        return;
+    };
+    if scheduler.tick(&curr_task) {
+        set_need_resched();
+    }
+}
+
+/// Marks the current CPU to reschedule if the task running on it should be preempted
+/// by the tasks in the run queue.
+pub(crate) fn check_preempt() {
+    let Some(scheduler) = GLOBAL_SCHEDULER.get() else {
+        return;
+    };
+    let Some(curr_task) = current_task() else {
+        return;
+    };
+    if scheduler.should_preempt(&curr_task) {
+        set_need_resched();
+    }
 }
 
 /// call this function to switch to other task by using GLOBAL_SCHEDULER
+///
+/// If there is no other task to run and the current task cannot continue to run,
+/// the current CPU will switch to its idle loop.
 pub fn schedule() {
+    NEED_RESCHED.borrow().store(false, Relaxed);
     if let Some(task) = fetch_task() {
         switch_to_task(task);
+    } else if current_task().is_some_and(|task| task.status() != TaskStatus::Runnable) {
+        switch_to(None);
     }
 }
 
@@ -67,17 +132,40 @@ pub fn preempt() {
     let Some(curr_task) = current_task() else {
         return;
     };
-    let mut scheduler = GLOBAL_SCHEDULER.lock_irq_disabled();
-    if !scheduler.should_preempt(&curr_task) {
+    let Some(scheduler) = GLOBAL_SCHEDULER.get() else {
+        return;
+    };
+    let need_resched = NEED_RESCHED.borrow().swap(false, Relaxed);
+    if !need_resched && !scheduler.should_preempt(&curr_task) {
         return;
     }
     let Some(next_task) = scheduler.dequeue() else {
         return;
     };
-    drop(scheduler);
+    drop(curr_task);
     switch_to_task(next_task);
 }
 
+/// The idle loop of the current CPU, which runs when there is no task to run.
+pub(crate) fn idle_loop() -> ! {
+    finish_task_switch();
+    loop {
+        // Disable local IRQs before checking the run queue, so that a wakeup IPI
+        // arriving after the check is not lost by the halt.
+        irq::disable_local();
+        if let Some(task) = fetch_task() {
+            irq::enable_local();
+            switch_to_task(task);
+        } else {
+            irq::enable_local_and_halt();
+        }
+    }
+}
+
+extern "sysv64" fn idle_loop_entry() -> ! {
+    idle_loop()
+}
+
 /// call this function to switch to other task
 ///
 /// if current task is none, then it will use the default task context and it will not return to this function again
@@ -86,41 +174,84 @@ pub fn preempt() {
 ///
 /// before context switch, current task will switch to the next task
 fn switch_to_task(next_task: Arc<Task>) {
-    if !PREEMPT_COUNT.is_preemptive() {
-        panic!(
-            "Calling schedule() while holding {} locks",
-            PREEMPT_COUNT.num_locks()
-        );
-    }
-
-    let current_task_cx_ptr = match current_task() {
-        None => PROCESSOR.lock().get_idle_task_cx_ptr(),
-        Some(current_task) => {
-            let mut task = current_task.inner_exclusive_access();
-
-            // FIXME: `task.ctx` should be put in a separate `UnsafeCell`, not as a part of
-            // `TaskInner`. Otherwise, it violates the sematics of `SpinLock` and Rust's memory
-            // model which requires that mutable references must be exclusive.
-            let cx_ptr = &mut task.ctx as *mut TaskContext;
-
-            debug_assert_ne!(task.task_status, TaskStatus::Sleeping);
-            if task.task_status == TaskStatus::Runnable {
-                drop(task);
-                GLOBAL_SCHEDULER.lock_irq_disabled().enqueue(current_task);
-            } else if task.task_status == TaskStatus::Sleepy {
-                task.task_status = TaskStatus::Sleeping;
-            }
+    switch_to(Some(next_task));
+}
 
-            cx_ptr
+/// Switches to the next task, or to the idle loop if `next_task` is `None`.
+///
+/// The current task is handled in `finish_task_switch` after the switch, since
+/// another CPU may pick it up as soon as it is put back into the run queue.
+fn switch_to(next_task: Option<Arc<Task>>) {
+    let num_locks = with_preempt_count(|preempt_count| preempt_count.num_locks());
+    if num_locks != 0 {
+        panic!("Calling schedule() while holding {} locks", num_locks);
+    }
+
+    let next_task_cx = match next_task {
+        Some(ref next_task) => next_task.inner_ctx(),
+        None => {
+            // The stack is allocated with the local IRQs enabled, since allocating
+            // memory may reclaim pages and shoot down the TLBs of other CPUs.
+            let new_idle_stack = with_processor(|processor| processor.idle_stack.is_none())
+                .then(|| KernelStack::new().unwrap());
+            with_processor(|processor| {
+                // The idle loop always starts over on its own stack.
+                if let Some(new_idle_stack) = new_idle_stack {
+                    processor.idle_stack = Some(new_idle_stack);
+                }
+                let idle_stack = processor.idle_stack.as_ref().unwrap();
+                let mut idle_task_cx = TaskContext::new();
+                idle_task_cx.rip = idle_loop_entry as usize;
+                idle_task_cx.regs.rsp = paddr_to_vaddr(idle_stack.end_paddr() - 16) as u64;
+                idle_task_cx
+            })
         }
     };
 
-    let next_task_cx_ptr = &next_task.inner_ctx() as *const TaskContext;
+    let current_task_cx_ptr = with_processor(|processor| {
+        let current_task_cx_ptr = match processor.current.take() {
+            None => processor.get_idle_task_cx_ptr(),
+            Some(current_task) => {
+                // FIXME: `task.ctx` should be put in a separate `UnsafeCell`, not as a part of
+                // `TaskInner`. Otherwise, it violates the sematics of `SpinLock` and Rust's memory
+                // model which requires that mutable references must be exclusive.
+                let cx_ptr = &mut current_task.inner_exclusive_access().ctx as *mut TaskContext;
+                processor.prev = Some(current_task);
+                cx_ptr
+            }
+        };
+        // change the current task to the next task
+        processor.current = next_task;
+        current_task_cx_ptr
+    });
 
-    // change the current task to the next task
-    PROCESSOR.lock().current = Some(next_task.clone());
     unsafe {
-        context_switch(current_task_cx_ptr, next_task_cx_ptr);
+        context_switch(current_task_cx_ptr, &next_task_cx as *const TaskContext);
+    }
+
+    finish_task_switch();
+}
+
+/// Handles the task that was switched out by the last context switch on the current CPU.
+///
+/// This function must be called right after a context switch completes, including
+/// when a new task or the idle loop starts running.
+pub(crate) fn finish_task_switch() {
+    let Some(prev_task) = with_processor(|processor| processor.prev.take()) else {
+        return;
+    };
+
+    let mut task = prev_task.inner_exclusive_access();
+    debug_assert_ne!(task.task_status, TaskStatus::Sleeping);
+    match task.task_status {
+        TaskStatus::Runnable => {
+            drop(task);
+            add_task(prev_task);
+        }
+        TaskStatus::Sleepy => {
+            task.task_status = TaskStatus::Sleeping;
+        }
+        _ => (),
     }
 }
 
@@ -128,6 +259,30 @@ cpu_local! {
     static PREEMPT_COUNT: PreemptInfo = PreemptInfo::new();
 }
 
+/// Accesses the preemption info of the current CPU with the local IRQs disabled.
+///
+/// It cannot be accessed with `CpuLocal::borrow` or `CpuLocal::borrow_with`, which
+/// disable preemption by themselves. Instead, the local IRQs are disabled directly,
+/// so that the current task is neither preempted nor migrated to another CPU
+/// between getting the CPU ID and accessing the info.
+fn with_preempt_count<R>(f: impl FnOnce(&PreemptInfo) -> R) -> R {
+    let was_enabled = irq::is_local_enabled();
+    if was_enabled {
+        irq::disable_local();
+    }
+    let res = f(PREEMPT_COUNT.get_on_cpu(this_cpu()));
+    if was_enabled {
+        irq::enable_local();
+    }
+    res
+}
+
+/// Returns whether the current task can be switched out, i.e., the current CPU
+/// holds no spin locks.
+pub(crate) fn is_preemptible() -> bool {
+    with_preempt_count(|preempt_count| preempt_count.is_preemptive())
+}
+
 /// Currently, ``PreemptInfo`` only holds the number of spin
 /// locks held by the current CPU. When it has a non-zero value,
 /// the CPU cannot call ``schedule()``.
@@ -169,7 +324,7 @@ impl !Send for DisablePreemptGuard {}
 
 impl DisablePreemptGuard {
     fn new() -> Self {
-        PREEMPT_COUNT.incease_num_locks();
+        with_preempt_count(|preempt_count| preempt_count.incease_num_locks());
         Self { private: () }
     }
 
@@ -182,7 +337,7 @@ impl DisablePreemptGuard {
 
 impl Drop for DisablePreemptGuard {
     fn drop(&mut self) {
-        PREEMPT_COUNT.decrease_num_locks();
+        with_preempt_count(|preempt_count| preempt_count.decrease_num_locks());
     }
 }
 
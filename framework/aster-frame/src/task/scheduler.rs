@@ -2,65 +2,58 @@
 
 use alloc::collections::VecDeque;
 
-use lazy_static::lazy_static;
+use spin::Once;
 
-use crate::{prelude::*, sync::SpinLock, task::Task};
+use crate::{cpu::this_cpu, prelude::*, smp::send_reschedule_ipi, sync::SpinLock, task::Task};
 
-lazy_static! {
-    pub(crate) static ref GLOBAL_SCHEDULER: SpinLock<GlobalScheduler> =
-        SpinLock::new(GlobalScheduler { scheduler: None });
-}
+pub(crate) static GLOBAL_SCHEDULER: Once<&'static dyn Scheduler> = Once::new();
 
 /// A scheduler for tasks.
 ///
 /// An implementation of scheduler can attach scheduler-related information
 /// with the `TypeMap` returned from `task.data()`.
+///
+/// The scheduler is shared by all CPUs. `dequeue` and `should_preempt` are
+/// always called on the CPU that is going to run the returned task or that is
+/// running the given task, so a scheduler may keep per-CPU run queues.
 pub trait Scheduler: Sync + Send {
-    fn enqueue(&self, task: Arc<Task>);
+    /// Enqueues a runnable task.
+    ///
+    /// If the task should preempt the task running on a specific CPU, or if that CPU may be
+    /// idle, returns the ID of that CPU so that it can be notified to reschedule.
+    fn enqueue(&self, task: Arc<Task>) -> Option<u32>;
 
+    /// Dequeues a task to run on the current CPU.
     fn dequeue(&self) -> Option<Arc<Task>>;
 
     /// Tells whether the given task should be preempted by other tasks in the queue.
     fn should_preempt(&self, task: &Arc<Task>) -> bool;
-}
 
-pub struct GlobalScheduler {
-    scheduler: Option<&'static dyn Scheduler>,
+    /// Charges a timer tick to the given task, which is running on the current CPU.
+    ///
+    /// Returns whether the task has used up its time slice and should be preempted.
+    /// This method is called in the interrupt context.
+    fn tick(&self, task: &Arc<Task>) -> bool;
 }
 
-impl GlobalScheduler {
-    pub fn new() -> Self {
-        Self { scheduler: None }
-    }
-
-    /// dequeue a task using scheduler
-    /// require the scheduler is not none
-    pub fn dequeue(&mut self) -> Option<Arc<Task>> {
-        self.scheduler.unwrap().dequeue()
-    }
-    /// enqueue a task using scheduler
-    /// require the scheduler is not none
-    pub fn enqueue(&mut self, task: Arc<Task>) {
-        self.scheduler.unwrap().enqueue(task)
-    }
-
-    pub fn should_preempt(&self, task: &Arc<Task>) -> bool {
-        self.scheduler.unwrap().should_preempt(task)
-    }
-}
 /// Set the global task scheduler.
 ///
 /// This must be called before invoking `Task::spawn`.
 pub fn set_scheduler(scheduler: &'static dyn Scheduler) {
-    GLOBAL_SCHEDULER.lock_irq_disabled().scheduler = Some(scheduler);
+    GLOBAL_SCHEDULER.call_once(|| scheduler);
 }
 
 pub fn fetch_task() -> Option<Arc<Task>> {
-    GLOBAL_SCHEDULER.lock_irq_disabled().dequeue()
+    GLOBAL_SCHEDULER.get()?.dequeue()
 }
 
 pub fn add_task(task: Arc<Task>) {
-    GLOBAL_SCHEDULER.lock_irq_disabled().enqueue(task);
+    let target_cpu = GLOBAL_SCHEDULER.get().unwrap().enqueue(task);
+    if let Some(target_cpu) = target_cpu
+        && target_cpu != this_cpu()
+    {
+        send_reschedule_ipi(target_cpu);
+    }
 }
 
 /// A simple FIFO (First-In-First-Out) task scheduler.
@@ -86,8 +79,11 @@ impl Default for FifoScheduler {
 
 impl Scheduler for FifoScheduler {
     /// Enqueues a task to the end of the queue.
-    fn enqueue(&self, task: Arc<Task>) {
+    ///
+    /// The queue is shared by all CPUs, so no CPU is specifically targeted.
+    fn enqueue(&self, task: Arc<Task>) -> Option<u32> {
         self.task_queue.lock_irq_disabled().push_back(task);
+        None
     }
     /// Dequeues a task from the front of the queue, if any.
     fn dequeue(&self) -> Option<Arc<Task>> {
@@ -98,4 +94,8 @@ impl Scheduler for FifoScheduler {
     fn should_preempt(&self, _task: &Arc<Task>) -> bool {
         false
     }
+    /// Tasks are not time-sliced, so the ticks are simply ignored.
+    fn tick(&self, _task: &Arc<Task>) -> bool {
+        false
+    }
 }
@@ -5,12 +5,13 @@ use intrusive_collections::{intrusive_adapter, LinkedListAtomicLink};
 use super::{
     add_task,
     priority::Priority,
-    processor::{current_task, schedule},
+    processor::{current_task, finish_task_switch, schedule},
 };
 use crate::{
     arch::mm::PageTableFlags,
     cpu::CpuSet,
     prelude::*,
+    smp::tlb_shootdown_all,
     sync::{SpinLock, SpinLockGuard},
     user::UserSpace,
     vm::{page_table::KERNEL_PAGE_TABLE, VmAllocOptions, VmSegment, PAGE_SIZE},
@@ -32,6 +33,20 @@ pub struct CalleeRegs {
     pub r15: u64,
 }
 
+impl CalleeRegs {
+    pub const fn new() -> Self {
+        Self {
+            rsp: 0,
+            rbx: 0,
+            rbp: 0,
+            r12: 0,
+            r13: 0,
+            r14: 0,
+            r15: 0,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 #[repr(C)]
 pub(crate) struct TaskContext {
@@ -39,6 +54,15 @@ pub(crate) struct TaskContext {
     pub rip: usize,
 }
 
+impl TaskContext {
+    pub const fn new() -> Self {
+        Self {
+            regs: CalleeRegs::new(),
+            rip: 0,
+        }
+    }
+}
+
 extern "C" {
     pub(crate) fn context_switch(cur: *mut TaskContext, nxt: *const TaskContext);
 }
@@ -87,7 +111,11 @@ impl KernelStack {
             crate::vm::paddr_to_vaddr(guard_page_paddr)
         };
         // Safety: The protected address must be the address of guard page hence it should be safe and valid.
-        unsafe { kernel_pt.protect(guard_page_vaddr, flags).unwrap() }
+        let old_flags = unsafe { kernel_pt.protect(guard_page_vaddr, flags).unwrap() };
+        drop(kernel_pt);
+        // The stale mapping of the guard page may be cached by other CPUs.
+        tlb_shootdown_all();
+        old_flags
     }
 }
 
@@ -114,7 +142,6 @@ pub struct Task {
     kstack: KernelStack,
     link: LinkedListAtomicLink,
     priority: Priority,
-    // TODO:: add multiprocessor support
     cpu_affinity: CpuSet,
 }
 
@@ -180,9 +207,19 @@ impl Task {
         unreachable!()
     }
 
+    /// Returns the priority that the task is created with.
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
     pub fn is_real_time(&self) -> bool {
         self.priority.is_real_time()
     }
+
+    /// Returns the set of CPUs that the task is allowed to run on.
+    pub fn cpu_affinity(&self) -> &CpuSet {
+        &self.cpu_affinity
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
@@ -261,6 +298,7 @@ impl TaskOptions {
         /// all task will entering this function
         /// this function is mean to executing the task_fn in Task
         extern "sysv64" fn kernel_task_entry() {
+            finish_task_switch();
             let current_task = current_task()
                 .expect("no current task, it should have current task in kernel task entry");
             current_task.func.call(());
@@ -4,9 +4,9 @@
 
 use core::{sync::atomic::Ordering, time::Duration};
 
-pub use crate::arch::timer::read_monotonic_milli_seconds;
+pub use crate::arch::timer::{read_monotonic_milli_seconds, TIMER_FREQ};
 use crate::{
-    arch::timer::{add_timeout_list, TimerCallback, TICK, TIMER_FREQ},
+    arch::timer::{add_timeout_list, TimerCallback, TICK},
     prelude::*,
     sync::SpinLock,
 };
@@ -1,7 +1,11 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
+use align_ext::AlignExt;
 use log::debug;
 #[cfg(feature = "intel_tdx")]
 use tdx_guest::tdcall;
@@ -19,9 +23,10 @@ use crate::{
         irq::IRQ_LIST,
         mm::{PageTableEntry, PageTableFlags},
     },
+    boot::memory_region::MemoryRegionType,
     cpu::{CpuException, PageFaultErrorCode, PAGE_FAULT},
     cpu_local,
-    vm::{PageTable, PHYS_MEM_BASE_VADDR, PHYS_MEM_VADDR_RANGE},
+    vm::{phys_mem_base_vaddr, phys_mem_vaddr_range, Paddr, PageSize, PageTable, MEMORY_REGIONS},
 };
 
 #[cfg(feature = "intel_tdx")]
@@ -145,16 +150,22 @@ extern "sysv64" fn trap_handler(f: &mut TrapFrame) {
             }
         }
     } else {
-        call_irq_callback_functions(f);
+        call_irq_callback_functions(f, false);
     }
 }
 
-pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame) {
+/// Calls the callbacks of the IRQ line of the trap.
+///
+/// `is_user_mode` tells whether the interrupt is taken in the user mode.
+pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame, is_user_mode: bool) {
     // For x86 CPUs, interrupts are not re-entrant. Local interrupts will be disabled when
     // an interrupt handler is called (Unless interrupts are re-enabled in an interrupt handler).
     //
     // FIXME: For arch that supports re-entrant interrupts, we may need to record nested level here.
-    IN_INTERRUPT_CONTEXT.store(true, Ordering::Release);
+    IN_INTERRUPT_CONTEXT.borrow().store(true, Ordering::Release);
+    INTERRUPTED_USER_MODE
+        .borrow()
+        .store(is_user_mode, Ordering::Relaxed);
 
     let irq_line = IRQ_LIST.get().unwrap().get(trap_frame.trap_num).unwrap();
     let callback_functions = irq_line.callback_list();
@@ -165,11 +176,14 @@ pub(crate) fn call_irq_callback_functions(trap_frame: &TrapFrame) {
         crate::arch::interrupts_ack();
     }
 
-    IN_INTERRUPT_CONTEXT.store(false, Ordering::Release);
+    IN_INTERRUPT_CONTEXT
+        .borrow()
+        .store(false, Ordering::Release);
 }
 
 cpu_local! {
     static IN_INTERRUPT_CONTEXT: AtomicBool = AtomicBool::new(false);
+    static INTERRUPTED_USER_MODE: AtomicBool = AtomicBool::new(false);
 }
 
 /// Returns whether we are in the interrupt context.
@@ -177,7 +191,15 @@ cpu_local! {
 /// FIXME: Here only hardware irq is taken into account. According to linux implementation, if
 /// we are in softirq context, or bottom half is disabled, this function also returns true.
 pub fn in_interrupt_context() -> bool {
-    IN_INTERRUPT_CONTEXT.load(Ordering::Acquire)
+    IN_INTERRUPT_CONTEXT.borrow().load(Ordering::Acquire)
+}
+
+/// Returns whether the interrupt being handled is taken in the user mode.
+///
+/// It is only meaningful in the interrupt context, e.g., to tell whether a timer
+/// tick should be charged to the user time or the system time of the current task.
+pub fn interrupted_user_mode() -> bool {
+    INTERRUPTED_USER_MODE.borrow().load(Ordering::Relaxed)
 }
 
 fn handle_kernel_page_fault(f: &TrapFrame) {
@@ -189,7 +211,7 @@ fn handle_kernel_page_fault(f: &TrapFrame) {
     );
 
     assert!(
-        PHYS_MEM_VADDR_RANGE.contains(&(page_fault_vaddr as usize)),
+        phys_mem_vaddr_range().contains(&(page_fault_vaddr as usize)),
         "kernel page fault: the address is outside the range of the direct mapping",
     );
 
@@ -214,17 +236,59 @@ fn handle_kernel_page_fault(f: &TrapFrame) {
     let mut page_table: PageTable<PageTableEntry, crate::vm::page_table::KernelMode> =
         unsafe { PageTable::from_root_register() };
 
-    let paddr = page_fault_vaddr as usize - PHYS_MEM_BASE_VADDR;
+    let vaddr = page_fault_vaddr as usize;
     let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
 
-    // SAFETY:
-    // 1. We have checked that the page fault address falls within the address range of the direct
-    //    mapping of physical memory.
-    // 2. We map the address to the correct physical page with the correct flags, where the
-    //    correctness follows the semantics of the direct mapping of physical memory.
-    unsafe {
-        page_table
-            .map(page_fault_vaddr as usize, paddr, flags)
-            .unwrap();
+    // Map the memory with the largest page possible to reduce the TLB pressure.
+    let mut is_mapped = false;
+    for page_size in [PageSize::Size1G, PageSize::Size2M] {
+        if !page_size.is_supported() {
+            continue;
+        }
+        let huge_vaddr = vaddr.align_down(page_size.nbytes());
+        let huge_paddr = huge_vaddr - phys_mem_base_vaddr();
+        if !is_ram(&(huge_paddr..huge_paddr + page_size.nbytes())) {
+            continue;
+        }
+        // SAFETY: The huge page only covers the RAM within the direct mapping of physical
+        // memory. Otherwise, the safety is the same as mapping a single page below.
+        if unsafe { page_table.map_huge(huge_vaddr, huge_paddr, flags, page_size) }.is_ok() {
+            is_mapped = true;
+            break;
+        }
+    }
+
+    if !is_mapped {
+        let paddr = vaddr - phys_mem_base_vaddr();
+        // SAFETY:
+        // 1. We have checked that the page fault address falls within the address range of the direct
+        //    mapping of physical memory.
+        // 2. We map the address to the correct physical page with the correct flags, where the
+        //    correctness follows the semantics of the direct mapping of physical memory.
+        unsafe {
+            page_table.map(vaddr, paddr, flags).unwrap();
+        }
     }
+
+    // The page tables created above are part of the kernel page table, so they must not
+    // be freed when the temporary `PageTable` is dropped.
+    core::mem::forget(page_table);
+}
+
+/// Returns whether the physical address range is in the RAM, which is safe to be mapped
+/// as cacheable huge pages, unlike the MMIO regions.
+fn is_ram(range: &Range<Paddr>) -> bool {
+    let Some(regions) = MEMORY_REGIONS.get() else {
+        return false;
+    };
+    regions.iter().any(|region| {
+        matches!(
+            region.typ(),
+            MemoryRegionType::Usable
+                | MemoryRegionType::Reclaimable
+                | MemoryRegionType::Kernel
+                | MemoryRegionType::Module
+        ) && region.base() <= range.start
+            && range.end <= region.base() + region.len()
+    })
 }
@@ -3,7 +3,7 @@
 mod handler;
 mod irq;
 
-pub use handler::in_interrupt_context;
+pub use handler::{in_interrupt_context, interrupted_user_mode};
 pub use trapframe::TrapFrame;
 
 pub(crate) use self::handler::call_irq_callback_functions;
@@ -226,6 +226,15 @@ impl VmFrame {
         (self.frame_index() + 1) * PAGE_SIZE
     }
 
+    /// Returns the number of `VmFrame`s that refer to the same page frame, including
+    /// this one.
+    ///
+    /// The page frame is not used by others (e.g., mapped in a page table) if this
+    /// is the only reference.
+    pub fn reference_count(&self) -> usize {
+        Arc::strong_count(&self.frame_index)
+    }
+
     fn need_dealloc(&self) -> bool {
         (*self.frame_index & VmFrameFlags::NEED_DEALLOC.bits()) != 0
     }
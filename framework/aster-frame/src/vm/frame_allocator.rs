@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::vec::Vec;
+use core::ops::Range;
 
 use align_ext::AlignExt;
 use buddy_system_allocator::FrameAllocator;
@@ -9,48 +10,235 @@ use spin::Once;
 
 use super::{frame::VmFrameFlags, VmFrame, VmFrameVec, VmSegment};
 use crate::{
+    arch::irq,
     boot::memory_region::{MemoryRegion, MemoryRegionType},
     sync::SpinLock,
+    task::{current_task, is_preemptible, Task},
     vm::PAGE_SIZE,
 };
 
-pub(super) static FRAME_ALLOCATOR: Once<SpinLock<FrameAllocator>> = Once::new();
+pub(super) static FRAME_ALLOCATOR: Once<SpinLock<CountingFrameAllocator>> = Once::new();
 
-pub(crate) fn alloc(nframes: usize, flags: VmFrameFlags) -> Option<VmFrameVec> {
-    FRAME_ALLOCATOR
-        .get()
-        .unwrap()
-        .lock()
-        .alloc(nframes)
-        .map(|start| {
-            let mut vector = Vec::new();
-            // Safety: The frame index is valid.
-            unsafe {
-                for i in 0..nframes {
-                    let frame = VmFrame::new(
-                        (start + i) * PAGE_SIZE,
-                        flags.union(VmFrameFlags::NEED_DEALLOC),
-                    );
-                    vector.push(frame);
-                }
+static WATERMARKS: Once<Watermarks> = Once::new();
+
+static LOW_MEMORY_HANDLER: Once<fn()> = Once::new();
+
+static OUT_OF_MEMORY_HANDLER: Once<fn() -> OutOfMemoryAction> = Once::new();
+
+/// The maximum number of times that a failed allocation is retried.
+pub(super) const MAX_OUT_OF_MEMORY_RETRIES: usize = 16;
+
+/// A frame allocator that counts the page frames in use.
+pub(super) struct CountingFrameAllocator {
+    allocator: FrameAllocator<32>,
+    total: usize,
+    allocated: usize,
+}
+
+impl CountingFrameAllocator {
+    fn new() -> Self {
+        Self {
+            allocator: FrameAllocator::new(),
+            total: 0,
+            allocated: 0,
+        }
+    }
+
+    fn add_frame(&mut self, range: Range<usize>) {
+        self.total += range.len();
+        self.allocator.add_frame(range.start, range.end);
+    }
+
+    /// Allocate the contiguous frames, returning the index of the first frame.
+    ///
+    /// The frames below the min watermark are reserved, which are only allocated if
+    /// `use_reserves` is true.
+    pub(super) fn alloc(&mut self, nframes: usize, use_reserves: bool) -> Option<usize> {
+        if !use_reserves && self.nr_free() < nframes + watermarks().min {
+            return None;
+        }
+        let start = self.allocator.alloc(nframes)?;
+        // The inner buddy allocator rounds the number of frames up to a power of two,
+        // so the frames beyond the requested ones are given back right away.
+        self.dealloc_range(start + nframes..start + nframes.next_power_of_two());
+        self.allocated += nframes;
+        Some(start)
+    }
+
+    fn dealloc(&mut self, start: usize, nframes: usize) {
+        self.dealloc_range(start..start + nframes);
+        self.allocated -= nframes;
+    }
+
+    /// Give the frames back to the inner buddy allocator, which only accepts aligned
+    /// blocks whose sizes are powers of two.
+    fn dealloc_range(&mut self, range: Range<usize>) {
+        let mut start = range.start;
+        while start < range.end {
+            let max_size = 1 << (usize::BITS - 1 - (range.end - start).leading_zeros());
+            let size = if start == 0 {
+                max_size
+            } else {
+                max_size.min(1 << start.trailing_zeros())
+            };
+            self.allocator.dealloc(start, size);
+            start += size;
+        }
+    }
+
+    fn nr_free(&self) -> usize {
+        self.total - self.allocated
+    }
+}
+
+/// The watermarks of the free page frames, which indicate the memory pressure.
+#[derive(Debug, Clone, Copy)]
+pub struct Watermarks {
+    /// The page frames below this watermark are reserved for the tasks that are exiting
+    /// to free memory, e.g., the victims of the out-of-memory handler.
+    pub min: usize,
+    /// When the free page frames drop below this watermark, the low memory handler is
+    /// invoked to reclaim memory.
+    pub low: usize,
+    /// The reclaiming is expected to stop when the free page frames reach this watermark.
+    pub high: usize,
+}
+
+/// Returns the number of the page frames managed by the frame allocator.
+pub fn total_frames() -> usize {
+    FRAME_ALLOCATOR.get().unwrap().lock().total
+}
+
+/// Returns the number of the free page frames.
+pub fn free_frames() -> usize {
+    FRAME_ALLOCATOR.get().unwrap().lock().nr_free()
+}
+
+/// Returns the watermarks of the free page frames.
+pub fn watermarks() -> Watermarks {
+    *WATERMARKS.get().unwrap()
+}
+
+/// Registers the handler that is invoked when the free page frames drop below the low
+/// watermark, or when an allocation fails.
+///
+/// The handler may be invoked in any context, so it should only notify others to
+/// reclaim memory, e.g., by waking up a kernel thread.
+pub fn register_low_memory_handler(handler: fn()) {
+    LOW_MEMORY_HANDLER.call_once(|| handler);
+}
+
+/// Checks the memory pressure after an allocation, which is made when `nr_free` frames
+/// are free.
+fn check_low_memory(nr_free: Option<usize>) {
+    let is_low = nr_free.map_or(true, |nr_free| nr_free < watermarks().low);
+    if is_low && let Some(handler) = LOW_MEMORY_HANDLER.get() {
+        handler();
+    }
+}
+
+/// The action to take after an allocation of the page frames or the kernel heap fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfMemoryAction {
+    /// Fails the allocation.
+    Fail,
+    /// Retries the allocation after others free memory.
+    Retry,
+    /// Retries the allocation with the reserved page frames (see `Watermarks::min`),
+    /// since the current task is going to exit and free its memory.
+    UseReserves,
+}
+
+/// Registers the handler that is invoked when an allocation of the page frames or
+/// the kernel heap fails.
+///
+/// The handler notifies others to free memory, e.g., by killing a process, and returns
+/// how the allocation should be handled. Like the low memory handler, it may be
+/// invoked with any locks held, so it should not wait for the memory to be freed.
+pub fn register_out_of_memory_handler(handler: fn() -> OutOfMemoryAction) {
+    OUT_OF_MEMORY_HANDLER.call_once(|| handler);
+}
+
+/// Handles a failed allocation with the out-of-memory handler, where `has_used_reserves`
+/// indicates whether the failed allocation has used the reserves.
+///
+/// Before the retry, the current task yields to the ones that free memory if it can
+/// sleep. Otherwise, the allocation is only retried if it can use the reserves for
+/// the first time, since retrying it again and again would never end.
+pub(super) fn handle_out_of_memory(has_used_reserves: bool) -> OutOfMemoryAction {
+    let Some(handler) = OUT_OF_MEMORY_HANDLER.get() else {
+        return OutOfMemoryAction::Fail;
+    };
+    if current_task().is_none() {
+        return OutOfMemoryAction::Fail;
+    }
+    let action = handler();
+    if action == OutOfMemoryAction::Fail {
+        return OutOfMemoryAction::Fail;
+    }
+    if irq::is_local_enabled() && is_preemptible() {
+        Task::yield_now();
+    } else if action == OutOfMemoryAction::Retry || has_used_reserves {
+        return OutOfMemoryAction::Fail;
+    }
+    action
+}
+
+/// Allocates the contiguous frames, returning the index of the first frame.
+///
+/// A failed allocation is retried if the out-of-memory handler is going to free memory,
+/// unless `no_retry` is true.
+fn alloc_frames(nframes: usize, no_retry: bool) -> Option<usize> {
+    let mut use_reserves = false;
+    let mut nr_retries = 0;
+    loop {
+        let (start, nr_free) = {
+            let mut allocator = FRAME_ALLOCATOR.get().unwrap().lock();
+            let start = allocator.alloc(nframes, use_reserves);
+            (start, allocator.nr_free())
+        };
+        check_low_memory(start.map(|_| nr_free));
+        if start.is_some() || no_retry || nr_retries == MAX_OUT_OF_MEMORY_RETRIES {
+            return start;
+        }
+        match handle_out_of_memory(use_reserves) {
+            OutOfMemoryAction::Fail => return None,
+            OutOfMemoryAction::Retry => (),
+            OutOfMemoryAction::UseReserves => use_reserves = true,
+        }
+        nr_retries += 1;
+    }
+}
+
+pub(crate) fn alloc(nframes: usize, flags: VmFrameFlags, no_retry: bool) -> Option<VmFrameVec> {
+    alloc_frames(nframes, no_retry).map(|start| {
+        let mut vector = Vec::new();
+        // Safety: The frame index is valid.
+        unsafe {
+            for i in 0..nframes {
+                let frame = VmFrame::new(
+                    (start + i) * PAGE_SIZE,
+                    flags.union(VmFrameFlags::NEED_DEALLOC),
+                );
+                vector.push(frame);
             }
-            VmFrameVec(vector)
-        })
+        }
+        VmFrameVec(vector)
+    })
 }
 
-pub(crate) fn alloc_single(flags: VmFrameFlags) -> Option<VmFrame> {
-    FRAME_ALLOCATOR.get().unwrap().lock().alloc(1).map(|idx|
+pub(crate) fn alloc_single(flags: VmFrameFlags, no_retry: bool) -> Option<VmFrame> {
+    alloc_frames(1, no_retry).map(|idx|
             // Safety: The frame index is valid.
             unsafe { VmFrame::new(idx * PAGE_SIZE, flags.union(VmFrameFlags::NEED_DEALLOC)) })
 }
 
-pub(crate) fn alloc_contiguous(nframes: usize, flags: VmFrameFlags) -> Option<VmSegment> {
-    FRAME_ALLOCATOR
-        .get()
-        .unwrap()
-        .lock()
-        .alloc(nframes)
-        .map(|start|
+pub(crate) fn alloc_contiguous(
+    nframes: usize,
+    flags: VmFrameFlags,
+    no_retry: bool,
+) -> Option<VmSegment> {
+    alloc_frames(nframes, no_retry).map(|start|
             // Safety: The range of page frames is contiguous and valid.
             unsafe {
             VmSegment::new(
@@ -86,7 +274,7 @@ pub(crate) unsafe fn dealloc_contiguous(start_index: usize, nframes: usize) {
 }
 
 pub(crate) fn init(regions: &[MemoryRegion]) {
-    let mut allocator = FrameAllocator::<32>::new();
+    let mut allocator = CountingFrameAllocator::new();
     for region in regions.iter() {
         if region.typ() == MemoryRegionType::Usable {
             // Make the memory region page-aligned, and skip if it is too small.
@@ -96,7 +284,7 @@ pub(crate) fn init(regions: &[MemoryRegion]) {
             if end <= start {
                 continue;
             }
-            allocator.add_frame(start, end);
+            allocator.add_frame(start..end);
             info!(
                 "Found usable region, start:{:x}, end:{:x}",
                 region.base(),
@@ -104,5 +292,13 @@ pub(crate) fn init(regions: &[MemoryRegion]) {
             );
         }
     }
+    // The low and high watermarks are about 1/128 and 1/64 of the total memory, which
+    // are sufficient to absorb bursts of allocations while the memory is being reclaimed.
+    let low = (allocator.total / 128).max(32);
+    WATERMARKS.call_once(|| Watermarks {
+        min: low / 2,
+        low,
+        high: low * 2,
+    });
     FRAME_ALLOCATOR.call_once(|| SpinLock::new(allocator));
 }
@@ -14,13 +14,24 @@ use crate::{
     prelude::*,
     sync::SpinLock,
     trap::disable_local,
-    vm::{frame_allocator::FRAME_ALLOCATOR, PAGE_SIZE},
+    vm::{
+        frame_allocator::{
+            handle_out_of_memory, OutOfMemoryAction, FRAME_ALLOCATOR, MAX_OUT_OF_MEMORY_RETRIES,
+        },
+        PAGE_SIZE,
+    },
     Error,
 };
 
 #[global_allocator]
 static HEAP_ALLOCATOR: LockedHeapWithRescue<32> = LockedHeapWithRescue::new(rescue);
 
+/// Handles the heap allocation error, which happens after the out-of-memory handler
+/// fails to free memory for the allocation (see `LockedHeapWithRescue::alloc`).
+///
+/// The small allocations only fail if they cannot wait for the memory to be freed.
+/// The large ones whose sizes are controlled by the users should be made with the
+/// fallible APIs instead, e.g., `Vec::try_reserve`.
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
@@ -28,6 +39,10 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 
 const INIT_KERNEL_HEAP_SIZE: usize = PAGE_SIZE * 256;
 
+/// The heap allocations up to this size are retried until they succeed as long as
+/// the out-of-memory handler is going to free memory.
+const MAX_SMALL_ALLOC_SIZE: usize = PAGE_SIZE * 8;
+
 static mut HEAP_SPACE: [u8; INIT_KERNEL_HEAP_SIZE] = [0; INIT_KERNEL_HEAP_SIZE];
 
 pub fn init() {
@@ -39,12 +54,12 @@ pub fn init() {
 
 struct LockedHeapWithRescue<const ORDER: usize> {
     heap: SpinLock<Heap<ORDER>>,
-    rescue: fn(&Self, &Layout) -> Result<()>,
+    rescue: fn(&Self, &Layout, bool) -> Result<()>,
 }
 
 impl<const ORDER: usize> LockedHeapWithRescue<ORDER> {
     /// Creates an new heap
-    pub const fn new(rescue: fn(&Self, &Layout) -> Result<()>) -> Self {
+    pub const fn new(rescue: fn(&Self, &Layout, bool) -> Result<()>) -> Self {
         Self {
             heap: SpinLock::new(Heap::<ORDER>::new()),
             rescue,
@@ -56,6 +71,26 @@ impl<const ORDER: usize> LockedHeapWithRescue<ORDER> {
         self.heap.lock_irq_disabled().init(start as usize, size);
     }
 
+    /// Allocates memory from the heap, which is enlarged if there is not enough memory.
+    ///
+    /// The heap is enlarged with the reserved page frames if `use_reserves` is true.
+    fn try_alloc(&self, layout: &Layout, use_reserves: bool) -> Option<*mut u8> {
+        let _guard = disable_local();
+
+        if let Ok(allocation) = self.heap.lock().alloc(*layout) {
+            return Some(allocation.as_ptr());
+        }
+
+        // Avoid locking self.heap when calling rescue.
+        (self.rescue)(self, layout, use_reserves).ok()?;
+
+        self.heap
+            .lock()
+            .alloc(*layout)
+            .ok()
+            .map(|allocation| allocation.as_ptr())
+    }
+
     /// Safety: The range [start, start + size) must be a valid memory region.
     unsafe fn add_to_heap(&self, start: usize, size: usize) {
         self.heap
@@ -66,23 +101,26 @@ impl<const ORDER: usize> LockedHeapWithRescue<ORDER> {
 
 unsafe impl<const ORDER: usize> GlobalAlloc for LockedHeapWithRescue<ORDER> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let _guard = disable_local();
-
-        if let Ok(allocation) = self.heap.lock().alloc(layout) {
-            return allocation.as_ptr();
-        }
-
-        // Avoid locking self.heap when calling rescue.
-        if (self.rescue)(self, &layout).is_err() {
-            return core::ptr::null_mut::<u8>();
+        let mut use_reserves = false;
+        let mut nr_retries = 0;
+        loop {
+            if let Some(allocation) = self.try_alloc(&layout, use_reserves) {
+                return allocation;
+            }
+            // Like Linux, the small allocations are too small to fail, since the kernel
+            // cannot handle the failures of most of them.
+            if nr_retries == MAX_OUT_OF_MEMORY_RETRIES && layout.size() > MAX_SMALL_ALLOC_SIZE {
+                return core::ptr::null_mut::<u8>();
+            }
+            // The local IRQs are enabled again, so that the current task can wait for
+            // the memory to be freed if it can sleep.
+            match handle_out_of_memory(use_reserves) {
+                OutOfMemoryAction::Fail => return core::ptr::null_mut::<u8>(),
+                OutOfMemoryAction::Retry => (),
+                OutOfMemoryAction::UseReserves => use_reserves = true,
+            }
+            nr_retries = (nr_retries + 1).min(MAX_OUT_OF_MEMORY_RETRIES);
         }
-
-        self.heap
-            .lock()
-            .alloc(layout)
-            .map_or(core::ptr::null_mut::<u8>(), |allocation| {
-                allocation.as_ptr()
-            })
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
@@ -93,7 +131,11 @@ unsafe impl<const ORDER: usize> GlobalAlloc for LockedHeapWithRescue<ORDER> {
     }
 }
 
-fn rescue<const ORDER: usize>(heap: &LockedHeapWithRescue<ORDER>, layout: &Layout) -> Result<()> {
+fn rescue<const ORDER: usize>(
+    heap: &LockedHeapWithRescue<ORDER>,
+    layout: &Layout,
+    use_reserves: bool,
+) -> Result<()> {
     const MIN_NUM_FRAMES: usize = 0x4000000 / PAGE_SIZE; // 64MB
 
     debug!("enlarge heap, layout = {:?}", layout);
@@ -106,11 +148,16 @@ fn rescue<const ORDER: usize>(heap: &LockedHeapWithRescue<ORDER>, layout: &Layou
 
     let allocation_start = {
         let mut frame_allocator = FRAME_ALLOCATOR.get().unwrap().lock();
-        if num_frames >= MIN_NUM_FRAMES {
-            frame_allocator.alloc(num_frames).ok_or(Error::NoMemory)?
+        // The reserves are precious, so only the frames needed are taken from them.
+        if num_frames >= MIN_NUM_FRAMES || use_reserves {
+            frame_allocator
+                .alloc(num_frames, use_reserves)
+                .ok_or(Error::NoMemory)?
         } else {
-            match frame_allocator.alloc(MIN_NUM_FRAMES) {
-                None => frame_allocator.alloc(num_frames).ok_or(Error::NoMemory)?,
+            match frame_allocator.alloc(MIN_NUM_FRAMES, false) {
+                None => frame_allocator
+                    .alloc(num_frames, false)
+                    .ok_or(Error::NoMemory)?,
                 Some(start) => {
                     num_frames = MIN_NUM_FRAMES;
                     start
@@ -1,15 +1,15 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use alloc::collections::{btree_map::Entry, BTreeMap};
-use core::fmt;
+use core::{fmt, ops::Range};
 
-use super::page_table::{PageTable, PageTableConfig, UserMode};
+use super::page_table::{PageSize, PageTable, PageTableConfig, UserMode};
 use crate::{
-    arch::mm::{PageTableEntry, PageTableFlags, INIT_MAPPED_PTE},
+    arch::mm::{paging_level, PageTableEntry, PageTableFlags, INIT_MAPPED_PTE},
     prelude::*,
     vm::{
-        is_page_aligned, VmAllocOptions, VmFrame, VmFrameVec, VmReader, VmWriter, PAGE_SIZE,
-        PHYS_MEM_BASE_VADDR,
+        is_page_aligned, phys_mem_base_vaddr, VmAllocOptions, VmFrame, VmFrameVec, VmReader,
+        VmWriter, PAGE_SIZE,
     },
     Error,
 };
@@ -20,6 +20,8 @@ pub struct MapArea {
     pub start_va: Vaddr,
     pub size: usize,
     pub mapper: BTreeMap<Vaddr, VmFrame>,
+    /// The size of the pages that map the area.
+    pub page_size: PageSize,
 }
 
 pub struct MemorySet {
@@ -51,6 +53,7 @@ impl MapArea {
             start_va,
             size,
             mapper: BTreeMap::new(),
+            page_size: PageSize::Size4K,
         };
         let mut current_va = start_va;
         let page_size = size / PAGE_SIZE;
@@ -65,6 +68,22 @@ impl MapArea {
         map_area
     }
 
+    /// This function will map the virtual address to the given physical frames as a
+    /// single huge page of `page_size`.
+    ///
+    /// The frames must be physically contiguous, and both the virtual address and
+    /// the physical address of the frames must be aligned to the page size.
+    pub fn new_huge(
+        start_va: Vaddr,
+        flags: PageTableFlags,
+        physical_frames: VmFrameVec,
+        page_size: PageSize,
+    ) -> Self {
+        let mut map_area = Self::new(start_va, page_size.nbytes(), flags, physical_frames);
+        map_area.page_size = page_size;
+        map_area
+    }
+
     pub fn map_with_physical_address(&mut self, va: Vaddr, pa: VmFrame) -> Paddr {
         assert!(is_page_aligned(va));
 
@@ -132,9 +151,16 @@ impl MemorySet {
             // TODO: check overlap
             if let Entry::Vacant(e) = self.areas.entry(area.start_va) {
                 let area = e.insert(area);
-                for (va, frame) in area.mapper.iter() {
-                    debug_assert!(frame.start_paddr() < PHYS_MEM_BASE_VADDR);
-                    self.pt.map(*va, frame, area.flags).unwrap();
+                if area.page_size == PageSize::Size4K {
+                    for (va, frame) in area.mapper.iter() {
+                        debug_assert!(frame.start_paddr() < phys_mem_base_vaddr());
+                        self.pt.map(*va, frame, area.flags).unwrap();
+                    }
+                } else {
+                    let frames = VmFrameVec(area.mapper.values().cloned().collect());
+                    self.pt
+                        .map_huge(area.start_va, &frames, area.flags, area.page_size)
+                        .unwrap();
                 }
             } else {
                 panic!(
@@ -156,9 +182,21 @@ impl MemorySet {
         self.pt.flags(vaddr)
     }
 
+    /// Return the number of the pages mapped in the memory set.
+    pub fn nr_mapped_pages(&self) -> usize {
+        self.areas.values().map(|area| area.mapper.len()).sum()
+    }
+
+    /// Clear the accessed bit of the page mapped at `vaddr`, returning whether the page
+    /// has been accessed since the bit was last cleared.
+    /// If the page is not mapped or is part of a huge page, return `None`.
+    pub fn clear_accessed(&mut self, vaddr: Vaddr) -> Option<bool> {
+        self.pt.clear_accessed(vaddr)
+    }
+
     pub fn new() -> Self {
         let mut page_table = PageTable::<PageTableEntry, UserMode>::new(PageTableConfig {
-            address_width: super::page_table::AddressWidth::Level4,
+            address_width: paging_level(),
         });
         let mapped_pte = INIT_MAPPED_PTE.get().unwrap();
         for (index, pte) in mapped_pte.iter() {
@@ -175,21 +213,71 @@ impl MemorySet {
 
     pub fn unmap(&mut self, va: Vaddr) -> Result<()> {
         if let Some(area) = self.areas.remove(&va) {
-            for (va, _) in area.mapper.iter() {
-                self.pt.unmap(*va).unwrap();
-            }
+            unmap_area(&mut self.pt, &area);
             Ok(())
         } else {
             Err(Error::PageFault)
         }
     }
 
-    pub fn clear(&mut self) {
-        for area in self.areas.values_mut() {
-            for (va, _) in area.mapper.iter() {
-                self.pt.unmap(*va).unwrap();
+    /// Unmap the pages within the range, which is allowed to contain gaps.
+    ///
+    /// A huge page that is partly in the range is split into 4 KiB pages first.
+    pub fn unmap_range(&mut self, range: &Range<Vaddr>) {
+        self.split_area_at(range.start);
+        self.split_area_at(range.end);
+        let area_vas: Vec<Vaddr> = self.areas.range(range.clone()).map(|(va, _)| *va).collect();
+        for va in area_vas {
+            let area = self.areas.remove(&va).unwrap();
+            unmap_area(&mut self.pt, &area);
+        }
+    }
+
+    /// Update the flags of the pages within the range, which is allowed to contain gaps.
+    ///
+    /// A huge page that is partly in the range is split into 4 KiB pages first.
+    pub fn protect_range(&mut self, range: &Range<Vaddr>, flags: PageTableFlags) {
+        self.split_area_at(range.start);
+        self.split_area_at(range.end);
+        for area in self.areas.range_mut(range.clone()).map(|(_, area)| area) {
+            area.flags = flags;
+            if area.page_size == PageSize::Size4K {
+                for va in area.mapper.keys() {
+                    self.pt.protect(*va, flags).unwrap();
+                }
+            } else {
+                self.pt.protect(area.start_va, flags).unwrap();
             }
         }
+    }
+
+    /// Split the huge-page area that contains `va` in its middle into the areas of 4 KiB pages,
+    /// so that `va` becomes a boundary of the areas.
+    fn split_area_at(&mut self, va: Vaddr) {
+        let Some((&area_va, area)) = self.areas.range(..va).next_back() else {
+            return;
+        };
+        if area.page_size == PageSize::Size4K || va >= area_va + area.size {
+            return;
+        }
+
+        let area = self.areas.remove(&area_va).unwrap();
+        unmap_area(&mut self.pt, &area);
+        let MapArea { flags, mapper, .. } = area;
+        for (va, frame) in mapper {
+            self.map(MapArea::new(
+                va,
+                PAGE_SIZE,
+                flags,
+                VmFrameVec::from_one_frame(frame),
+            ));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for area in self.areas.values() {
+            unmap_area(&mut self.pt, area);
+        }
         self.areas.clear();
     }
 
@@ -243,13 +331,18 @@ impl MemorySet {
     }
 
     pub fn protect(&mut self, addr: Vaddr, flags: PageTableFlags) {
-        let va = addr;
-        // Temporary solution, since the `MapArea` currently only represents
-        // a single `VmFrame`.
-        if let Some(areas) = self.areas.get_mut(&va) {
-            areas.flags = flags;
+        self.protect_range(&(addr..addr + PAGE_SIZE), flags);
+    }
+}
+
+/// Unmap the pages of the area from the page table.
+fn unmap_area(pt: &mut PageTable<PageTableEntry>, area: &MapArea) {
+    if area.page_size == PageSize::Size4K {
+        for va in area.mapper.keys() {
+            pt.unmap(*va).unwrap();
         }
-        self.pt.protect(va, flags).unwrap();
+    } else {
+        pt.unmap(area.start_va).unwrap();
     }
 }
 
@@ -276,3 +369,68 @@ impl fmt::Debug for MemorySet {
             .finish()
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+
+    const HUGE_PAGE_VADDR: Vaddr = 0x4000_0000;
+    const FLAGS: PageTableFlags = PageTableFlags::PRESENT
+        .union(PageTableFlags::WRITABLE)
+        .union(PageTableFlags::USER);
+
+    fn new_memory_set_with_huge_page() -> MemorySet {
+        let page_size = PageSize::Size2M;
+        let frames = VmAllocOptions::new(page_size.nframes())
+            .is_contiguous(true)
+            .alloc()
+            .unwrap();
+        let mut memory_set = MemorySet::new();
+        memory_set.map(MapArea::new_huge(HUGE_PAGE_VADDR, FLAGS, frames, page_size));
+        memory_set
+    }
+
+    #[ktest]
+    fn unmap_part_of_huge_page() {
+        let mut memory_set = new_memory_set_with_huge_page();
+        memory_set
+            .write_bytes(HUGE_PAGE_VADDR + 3 * PAGE_SIZE, &[42])
+            .unwrap();
+
+        memory_set.unmap_range(&(HUGE_PAGE_VADDR + PAGE_SIZE..HUGE_PAGE_VADDR + 2 * PAGE_SIZE));
+        assert!(memory_set.is_mapped(HUGE_PAGE_VADDR));
+        assert!(!memory_set.is_mapped(HUGE_PAGE_VADDR + PAGE_SIZE));
+        assert!(memory_set.is_mapped(HUGE_PAGE_VADDR + 2 * PAGE_SIZE));
+        assert_eq!(memory_set.nr_mapped_pages(), PageSize::Size2M.nframes() - 1);
+
+        // The split pages keep the contents of the huge page.
+        let mut buf = [0u8];
+        memory_set
+            .read_bytes(HUGE_PAGE_VADDR + 3 * PAGE_SIZE, &mut buf)
+            .unwrap();
+        assert_eq!(buf[0], 42);
+    }
+
+    #[ktest]
+    fn protect_part_of_huge_page() {
+        let mut memory_set = new_memory_set_with_huge_page();
+        let huge_page_end = HUGE_PAGE_VADDR + PageSize::Size2M.nbytes();
+
+        memory_set.protect_range(
+            &(huge_page_end - PAGE_SIZE..huge_page_end + PAGE_SIZE),
+            FLAGS - PageTableFlags::WRITABLE,
+        );
+        assert!(memory_set
+            .flags(HUGE_PAGE_VADDR)
+            .unwrap()
+            .contains(PageTableFlags::WRITABLE));
+        assert!(!memory_set
+            .flags(huge_page_end - PAGE_SIZE)
+            .unwrap()
+            .contains(PageTableFlags::WRITABLE));
+        assert_eq!(memory_set.nr_mapped_pages(), PageSize::Size2M.nframes());
+        assert!(memory_set
+            .write_bytes(huge_page_end - PAGE_SIZE, &[42])
+            .is_err());
+    }
+}
@@ -20,17 +20,25 @@ pub(crate) mod page_table;
 mod space;
 
 use alloc::{borrow::ToOwned, vec::Vec};
-use core::ops::Range;
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use align_ext::AlignExt;
 use spin::Once;
 
 pub use self::{
     dma::{Daddr, DmaCoherent, DmaDirection, DmaStream, DmaStreamSlice, HasDaddr},
     frame::{VmFrame, VmFrameVec, VmFrameVecIter, VmReader, VmSegment, VmWriter},
+    frame_allocator::{
+        free_frames, register_low_memory_handler, register_out_of_memory_handler, total_frames,
+        watermarks, OutOfMemoryAction, Watermarks,
+    },
     io::VmIo,
     memory_set::{MapArea, MemorySet},
     options::VmAllocOptions,
-    page_table::PageTable,
+    page_table::{PageSize, PageTable},
     space::{VmMapOptions, VmPerm, VmSpace},
 };
 use crate::boot::memory_region::{MemoryRegion, MemoryRegionType};
@@ -47,10 +55,35 @@ pub const PAGE_SIZE: usize = 0x1000;
 /// for some x86_64 CPUs' bugs. See
 /// <https://github.com/torvalds/linux/blob/480e035fc4c714fb5536e64ab9db04fedc89e910/arch/x86/include/asm/page_64.h#L68-L78>
 /// for the rationale.
+///
+/// With 5-level paging, the user space is larger (see [`max_userspace_vaddr`]).
+/// Like Linux, this remains the default limit of the user mappings since some
+/// programs assume that user pointers have at most 47 bits.
 pub const MAX_USERSPACE_VADDR: Vaddr = 0x0000_8000_0000_0000 - PAGE_SIZE;
 
+/// Returns the maximum virtual address of user space (non inclusive), which
+/// depends on the paging level.
+///
+/// The user space above [`MAX_USERSPACE_VADDR`] is only available with 5-level
+/// paging, where user programs can use 57-bit virtual addresses by asking for
+/// them explicitly (e.g., with the hint address of `mmap`).
+pub fn max_userspace_vaddr() -> Vaddr {
+    (1 << (crate::arch::mm::paging_level().vaddr_bits() - 1)) - PAGE_SIZE
+}
+
+/// The lowest base address of the direct mapping of physical memory.
+///
+/// The boot code maps the physical memory at this address. It remains the base
+/// address if the direct mapping is not randomized.
+const PHYS_MEM_BASE_VADDR_MIN: Vaddr = 0xffff_8000_0000_0000;
+
 /// The base address of the direct mapping of physical memory.
-pub(crate) const PHYS_MEM_BASE_VADDR: Vaddr = 0xffff_8000_0000_0000;
+///
+/// See [`randomize_phys_mem_base`] for how it is chosen.
+static PHYS_MEM_BASE_VADDR: AtomicUsize = AtomicUsize::new(PHYS_MEM_BASE_VADDR_MIN);
+
+/// The size of the direct mapping of physical memory.
+static PHYS_MEM_MAPPING_SIZE: AtomicUsize = AtomicUsize::new(PHYS_MEM_MAPPING_MAX_SIZE);
 
 /// The maximum size of the direct mapping of physical memory.
 ///
@@ -68,22 +101,77 @@ pub(crate) const PHYS_MEM_BASE_VADDR: Vaddr = 0xffff_8000_0000_0000;
 /// uses (e.g., the kernel code starting at [`kernel_loaded_offset()`]).
 pub(crate) const PHYS_MEM_MAPPING_MAX_SIZE: usize = 127 << 40;
 
-/// The address range of the direct mapping of physical memory.
+/// The alignment of the base address of the direct mapping of physical memory.
+///
+/// This is the span of a level-4 page table entry, so the direct mapping can be
+/// moved by moving the level-4 page table entries built by the boot code.
+const PHYS_MEM_BASE_VADDR_ALIGN: usize = 1 << 39;
+
+/// The padding of the direct mapping beyond the end of the physical memory when
+/// the direct mapping is randomized.
+///
+/// The direct mapping is still used to perform MMIO operations, so the padding
+/// keeps MMIO regions above the physical memory usable. The value is the same as
+/// the default of Linux's `CONFIG_RANDOMIZE_MEMORY_PHYSICAL_PADDING`.
+const PHYS_MEM_MAPPING_PADDING: usize = 10 << 40;
+
+/// Returns the base address of the direct mapping of physical memory.
+pub(crate) fn phys_mem_base_vaddr() -> Vaddr {
+    PHYS_MEM_BASE_VADDR.load(Ordering::Relaxed)
+}
+
+/// Returns the address range of the direct mapping of physical memory.
+pub(crate) fn phys_mem_vaddr_range() -> Range<Vaddr> {
+    let base = phys_mem_base_vaddr();
+    base..(base + PHYS_MEM_MAPPING_SIZE.load(Ordering::Relaxed))
+}
+
+/// Randomizes the base address of the direct mapping of physical memory.
+///
+/// The direct mapping is shrunk to cover the physical memory plus
+/// [`PHYS_MEM_MAPPING_PADDING`], and it is moved to a random slot within the
+/// [`PHYS_MEM_MAPPING_MAX_SIZE`] bytes above [`PHYS_MEM_BASE_VADDR_MIN`]. Like
+/// Linux, the randomization is disabled by the `nokaslr` kernel command-line option.
 ///
-/// This range is constructed based on [`PHYS_MEM_BASE_VADDR`] and
-/// [`PHYS_MEM_MAPPING_MAX_SIZE`].
-pub(crate) const PHYS_MEM_VADDR_RANGE: Range<Vaddr> =
-    PHYS_MEM_BASE_VADDR..(PHYS_MEM_BASE_VADDR + PHYS_MEM_MAPPING_MAX_SIZE);
+/// This must be called after the kernel command line and the memory regions are
+/// initialized, but before any other addresses in the direct mapping are in use.
+pub(crate) fn randomize_phys_mem_base() {
+    // TDX guests rely on the full direct mapping to access the VirtIO devices
+    // whose MMIO addresses are surprisingly high.
+    if cfg!(feature = "intel_tdx") || crate::boot::kernel_cmdline().has_kernel_flag("nokaslr") {
+        return;
+    }
+
+    let phys_mem_end = crate::boot::memory_regions()
+        .iter()
+        .map(|region| region.base() + region.len())
+        .max()
+        .unwrap_or(0);
+    let mapping_size = (phys_mem_end.align_up(PHYS_MEM_BASE_VADDR_ALIGN)
+        + PHYS_MEM_MAPPING_PADDING)
+        .min(PHYS_MEM_MAPPING_MAX_SIZE);
+    let nr_slots = (PHYS_MEM_MAPPING_MAX_SIZE - mapping_size) / PHYS_MEM_BASE_VADDR_ALIGN + 1;
+    let slot = crate::arch::read_random() as usize % nr_slots;
+    let base = PHYS_MEM_BASE_VADDR_MIN + slot * PHYS_MEM_BASE_VADDR_ALIGN;
+
+    // Safety: No addresses in the direct mapping are in use, as required by the
+    // caller. The new base address is aligned to the span of a level-4 page table
+    // entry, and the direct mapping at the new base address is still below the
+    // kernel code.
+    unsafe { crate::arch::mm::move_phys_mem_mapping(PHYS_MEM_BASE_VADDR_MIN, base) };
+    PHYS_MEM_MAPPING_SIZE.store(mapping_size, Ordering::Relaxed);
+    PHYS_MEM_BASE_VADDR.store(base, Ordering::Relaxed);
+}
 
 /// The kernel code is linear mapped to this address.
 ///
-/// FIXME: This offset should be randomly chosen by the loader or the
-/// boot compatibility layer. But we disabled it because the framework
-/// doesn't support relocatable kernel yet.
+/// The linux-bzimage setup loader may slide the kernel image, in which case both
+/// the virtual address and the physical address of the kernel code are moved by
+/// the same offset. So this offset remains unchanged.
 pub const fn kernel_loaded_offset() -> usize {
     0xffff_ffff_8000_0000
 }
-const_assert!(PHYS_MEM_VADDR_RANGE.end < kernel_loaded_offset());
+const_assert!(PHYS_MEM_BASE_VADDR_MIN + PHYS_MEM_MAPPING_MAX_SIZE < kernel_loaded_offset());
 
 /// Get physical address trait
 pub trait HasPaddr {
@@ -91,9 +179,9 @@ pub trait HasPaddr {
 }
 
 pub fn vaddr_to_paddr(va: Vaddr) -> Option<Paddr> {
-    if PHYS_MEM_VADDR_RANGE.contains(&va) {
+    if phys_mem_vaddr_range().contains(&va) {
         // can use offset to get the physical address
-        Some(va - PHYS_MEM_BASE_VADDR)
+        Some(va - phys_mem_base_vaddr())
     } else {
         page_table::vaddr_to_paddr(va)
     }
@@ -105,7 +193,7 @@ pub const fn is_page_aligned(p: usize) -> bool {
 
 /// Convert physical address to virtual address using offset, only available inside aster-frame
 pub(crate) fn paddr_to_vaddr(pa: usize) -> usize {
-    pa + PHYS_MEM_BASE_VADDR
+    pa + phys_mem_base_vaddr()
 }
 
 /// Only available inside aster-frame
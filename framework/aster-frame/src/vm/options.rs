@@ -14,6 +14,7 @@ pub struct VmAllocOptions {
     nframes: usize,
     is_contiguous: bool,
     uninit: bool,
+    no_retry: bool,
 }
 
 impl VmAllocOptions {
@@ -23,11 +24,16 @@ impl VmAllocOptions {
             nframes,
             is_contiguous: false,
             uninit: false,
+            no_retry: false,
         }
     }
 
     /// Sets whether the allocated frames should be contiguous.
     ///
+    /// The contiguous frames are aligned to their total size rounded up to a
+    /// power of two. So allocating `PageSize::nframes()` contiguous frames gives
+    /// the frames that can be mapped as a huge page.
+    ///
     /// The default value is `false`.
     pub fn is_contiguous(&mut self, is_contiguous: bool) -> &mut Self {
         self.is_contiguous = is_contiguous;
@@ -45,15 +51,29 @@ impl VmAllocOptions {
         self
     }
 
+    /// Sets whether the allocation fails right away if there are not enough free frames,
+    /// instead of waiting for others to free memory.
+    ///
+    /// It suits the allocations that can fall back to others, e.g., the huge pages that
+    /// are used transparently. Such allocations never invoke the out-of-memory handler.
+    ///
+    /// The default value is `false`.
+    pub fn no_retry(&mut self, no_retry: bool) -> &mut Self {
+        self.no_retry = no_retry;
+        self
+    }
+
     /// Allocate a collection of page frames according to the given options.
     pub fn alloc(&self) -> Result<VmFrameVec> {
         let flags = self.flags();
         let frames = if self.is_contiguous {
-            frame_allocator::alloc(self.nframes, flags).ok_or(Error::NoMemory)?
+            frame_allocator::alloc(self.nframes, flags, self.no_retry).ok_or(Error::NoMemory)?
         } else {
             let mut frame_list = Vec::new();
             for _ in 0..self.nframes {
-                frame_list.push(frame_allocator::alloc_single(flags).ok_or(Error::NoMemory)?);
+                frame_list.push(
+                    frame_allocator::alloc_single(flags, self.no_retry).ok_or(Error::NoMemory)?,
+                );
             }
             VmFrameVec(frame_list)
         };
@@ -72,7 +92,8 @@ impl VmAllocOptions {
             return Err(Error::InvalidArgs);
         }
 
-        let frame = frame_allocator::alloc_single(self.flags()).ok_or(Error::NoMemory)?;
+        let frame =
+            frame_allocator::alloc_single(self.flags(), self.no_retry).ok_or(Error::NoMemory)?;
         if !self.uninit {
             frame.writer().fill(0);
         }
@@ -88,8 +109,8 @@ impl VmAllocOptions {
             return Err(Error::InvalidArgs);
         }
 
-        let segment =
-            frame_allocator::alloc_contiguous(self.nframes, self.flags()).ok_or(Error::NoMemory)?;
+        let segment = frame_allocator::alloc_contiguous(self.nframes, self.flags(), self.no_retry)
+            .ok_or(Error::NoMemory)?;
         if !self.uninit {
             segment.writer().fill(0);
         }
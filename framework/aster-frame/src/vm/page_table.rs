@@ -9,9 +9,12 @@ use spin::Once;
 
 use super::{paddr_to_vaddr, Paddr, Vaddr, VmAllocOptions};
 use crate::{
-    arch::mm::{is_kernel_vaddr, is_user_vaddr, tlb_flush, PageTableEntry, NR_ENTRIES_PER_PAGE},
+    arch::mm::{
+        has_1gib_pages, is_kernel_vaddr, is_user_vaddr, paging_level, tlb_flush, PageTableEntry,
+        NR_ENTRIES_PER_PAGE,
+    },
     sync::SpinLock,
-    vm::{VmFrame, PAGE_SIZE},
+    vm::{VmFrame, VmFrameVec, PAGE_SIZE},
 };
 
 pub trait PageTableFlagsTrait: Clone + Copy + Sized + Pod + Debug {
@@ -29,6 +32,8 @@ pub trait PageTableFlagsTrait: Clone + Copy + Sized + Pod + Debug {
 
     fn set_huge(self, huge: bool) -> Self;
 
+    fn set_accessed(self, accessed: bool) -> Self;
+
     fn is_present(&self) -> bool;
 
     fn writable(&self) -> bool;
@@ -97,6 +102,63 @@ pub enum AddressWidth {
     Level5 = 5,
 }
 
+impl AddressWidth {
+    /// Returns the number of bits of the virtual addresses translated by the page table.
+    pub const fn vaddr_bits(self) -> usize {
+        PAGE_SIZE.trailing_zeros() as usize
+            + NR_ENTRIES_PER_PAGE.trailing_zeros() as usize * self as usize
+    }
+}
+
+/// The size of the page mapped by a leaf PTE, which is determined by the level of the PTE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    /// The 4 KiB page, which is mapped by a PTE in a level-1 page table.
+    Size4K,
+    /// The 2 MiB huge page, which is mapped by a PTE in a level-2 page table.
+    Size2M,
+    /// The 1 GiB huge page, which is mapped by a PTE in a level-3 page table.
+    Size1G,
+}
+
+impl PageSize {
+    /// Returns the level of the page table whose PTEs map the pages of this size.
+    pub const fn level(self) -> usize {
+        match self {
+            Self::Size4K => 1,
+            Self::Size2M => 2,
+            Self::Size1G => 3,
+        }
+    }
+
+    /// Returns the size of the page in bytes.
+    pub const fn nbytes(self) -> usize {
+        PAGE_SIZE * NR_ENTRIES_PER_PAGE.pow(self.level() as u32 - 1)
+    }
+
+    /// Returns the number of page frames in the page.
+    pub const fn nframes(self) -> usize {
+        self.nbytes() / PAGE_SIZE
+    }
+
+    /// Returns whether the CPU supports mapping the pages of this size.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Size4K | Self::Size2M => true,
+            Self::Size1G => has_1gib_pages(),
+        }
+    }
+
+    fn from_level(level: usize) -> Self {
+        match level {
+            1 => Self::Size4K,
+            2 => Self::Size2M,
+            3 => Self::Size1G,
+            _ => panic!("no page can be mapped in the level-{} page table", level),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PageTableError {
     /// Modifications to page tables (map, unmap, protect, etc.) are invalid for the following reasons:
@@ -104,8 +166,12 @@ pub enum PageTableError {
     /// 1. The mapping is present before map operation.
     /// 2. The mapping is already invalid before unmap operation.
     /// 3. The mapping is not exists before protect operation.
+    /// 4. The modification only covers part of a huge page.
     InvalidModification,
     InvalidVaddr,
+    /// The addresses or the frames do not fit the size of the page to map, e.g.,
+    /// the addresses are not aligned to the page size.
+    InvalidPageSize,
 }
 
 pub static KERNEL_PAGE_TABLE: Once<SpinLock<PageTable<PageTableEntry, KernelMode>>> = Once::new();
@@ -147,17 +213,48 @@ impl<T: PageTableEntryTrait> PageTable<T, UserMode> {
         frame: &VmFrame,
         flags: T::F,
     ) -> Result<(), PageTableError> {
-        if is_kernel_vaddr(vaddr) {
+        if !is_user_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
         // Safety:
         // 1. The vaddr belongs to user mode program and does not affect the kernel mapping.
         // 2. The area where the physical address islocated at untyped memory and does not affect kernel security.
-        unsafe { self.do_map(vaddr, frame.start_paddr(), flags) }
+        unsafe { self.do_map(vaddr, frame.start_paddr(), flags, PageSize::Size4K) }
+    }
+
+    /// Map the `frames` at `vaddr` as a single page of `page_size`.
+    ///
+    /// The frames must be physically contiguous and fill exactly one page of `page_size`.
+    /// Both `vaddr` and the physical address of the frames must be aligned to the page size.
+    pub fn map_huge(
+        &mut self,
+        vaddr: Vaddr,
+        frames: &VmFrameVec,
+        flags: T::F,
+        page_size: PageSize,
+    ) -> Result<(), PageTableError> {
+        if !is_user_vaddr(vaddr) {
+            return Err(PageTableError::InvalidVaddr);
+        }
+        let start_paddr = frames
+            .get(0)
+            .ok_or(PageTableError::InvalidPageSize)?
+            .start_paddr();
+        let is_contiguous = frames
+            .iter()
+            .enumerate()
+            .all(|(idx, frame)| frame.start_paddr() == start_paddr + idx * PAGE_SIZE);
+        if frames.len() != page_size.nframes() || !is_contiguous {
+            return Err(PageTableError::InvalidPageSize);
+        }
+        // Safety:
+        // 1. The vaddr belongs to user mode program and does not affect the kernel mapping.
+        // 2. The contiguous frames are untyped memory and do not affect kernel security.
+        unsafe { self.do_map(vaddr, start_paddr, flags, page_size) }
     }
 
     pub fn unmap(&mut self, vaddr: Vaddr) -> Result<(), PageTableError> {
-        if is_kernel_vaddr(vaddr) {
+        if !is_user_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
         // Safety: The vaddr belongs to user mode program and does not affect the kernel mapping.
@@ -165,13 +262,34 @@ impl<T: PageTableEntryTrait> PageTable<T, UserMode> {
     }
 
     pub fn protect(&mut self, vaddr: Vaddr, flags: T::F) -> Result<T::F, PageTableError> {
-        if is_kernel_vaddr(vaddr) {
+        if !is_user_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
         // Safety: The vaddr belongs to user mode program and does not affect the kernel mapping.
         unsafe { self.do_protect(vaddr, flags) }
     }
 
+    /// Clear the accessed bit of the PTE that maps `vaddr`, returning whether the page
+    /// has been accessed since the bit was last cleared.
+    ///
+    /// This function will return `None` if `vaddr` is not mapped, or is mapped with a huge page.
+    pub fn clear_accessed(&mut self, vaddr: Vaddr) -> Option<bool> {
+        if !is_user_vaddr(vaddr) {
+            return None;
+        }
+        let (last_entry, level) = self.do_page_walk_mut(vaddr, 1, false)?;
+        let flags = last_entry.flags();
+        if level != 1 || !last_entry.is_used() || !flags.is_present() {
+            return None;
+        }
+        if flags.has_accessed() {
+            // Clearing the accessed bit does not change the mapping.
+            last_entry.update(last_entry.paddr(), flags.set_accessed(false));
+            tlb_flush(vaddr);
+        }
+        Some(flags.has_accessed())
+    }
+
     /// Add a new mapping directly in the root page table.
     ///
     /// # Safety
@@ -199,23 +317,47 @@ impl<T: PageTableEntryTrait> PageTable<T, KernelMode> {
         paddr: Paddr,
         flags: T::F,
     ) -> Result<(), PageTableError> {
-        if is_user_vaddr(vaddr) {
+        if !is_kernel_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
-        self.do_map(vaddr, paddr, flags)
+        self.do_map(vaddr, paddr, flags, PageSize::Size4K)
+    }
+
+    /// Mapping `vaddr` to `paddr` with flags as a single page of `page_size`.
+    /// Both `vaddr` and `paddr` must be aligned to the page size.
+    ///
+    /// # Safety
+    ///
+    /// Modifying kernel mappings is considered unsafe, and incorrect operation may cause crashes.
+    /// User must take care of the consequences when using this API.
+    pub unsafe fn map_huge(
+        &mut self,
+        vaddr: Vaddr,
+        paddr: Paddr,
+        flags: T::F,
+        page_size: PageSize,
+    ) -> Result<(), PageTableError> {
+        if !is_kernel_vaddr(vaddr) {
+            return Err(PageTableError::InvalidVaddr);
+        }
+        self.do_map(vaddr, paddr, flags, page_size)
     }
 
     /// Unmap `vaddr`. The `vaddr` should not be at the low address
     ///  (memory belonging to the user mode program).
     ///
+    /// If `vaddr` is mapped by a huge page, the huge page is split so that
+    /// only the 4 KiB page at `vaddr` is unmapped.
+    ///
     /// # Safety
     ///
     /// Modifying kernel mappings is considered unsafe, and incorrect operation may cause crashes.
     /// User must take care of the consequences when using this API.
     pub unsafe fn unmap(&mut self, vaddr: Vaddr) -> Result<(), PageTableError> {
-        if is_user_vaddr(vaddr) {
+        if !is_kernel_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
+        self.split_huge_pages(vaddr);
         self.do_unmap(vaddr)
     }
 
@@ -223,16 +365,58 @@ impl<T: PageTableEntryTrait> PageTable<T, KernelMode> {
     ///  (memory belonging to the user mode program).
     /// If the modification succeeds, it will return the old flags of `vaddr`.
     ///
+    /// If `vaddr` is mapped by a huge page, the huge page is split so that
+    /// only the 4 KiB page at `vaddr` is modified.
+    ///
     /// # Safety
     ///
     /// Modifying kernel mappings is considered unsafe, and incorrect operation may cause crashes.
     /// User must take care of the consequences when using this API.
     pub unsafe fn protect(&mut self, vaddr: Vaddr, flags: T::F) -> Result<T::F, PageTableError> {
-        if is_user_vaddr(vaddr) {
+        if !is_kernel_vaddr(vaddr) {
             return Err(PageTableError::InvalidVaddr);
         }
+        self.split_huge_pages(vaddr);
         self.do_protect(vaddr, flags)
     }
+
+    /// Split the huge pages that contain `vaddr` until `vaddr` is mapped by a 4 KiB page.
+    ///
+    /// The split pages keep the same translations and flags as the huge page.
+    ///
+    /// # Safety
+    ///
+    /// The new page tables are not freed even if the pages are unmapped later, so
+    /// this is only used in the kernel page table that lives forever.
+    unsafe fn split_huge_pages(&mut self, vaddr: Vaddr) {
+        loop {
+            let Some((last_entry, level)) = self.do_page_walk_mut(vaddr, 1, false) else {
+                return;
+            };
+            let huge_flags = last_entry.flags();
+            if level == 1 || !huge_flags.is_present() || !huge_flags.is_huge() {
+                return;
+            }
+
+            let huge_paddr = last_entry.paddr();
+            let child_size = PageSize::from_level(level - 1).nbytes();
+            let child_flags = huge_flags.set_huge(level - 1 > 1);
+            let frame = VmAllocOptions::new(1).alloc_single().unwrap();
+            // Safety: The frame is newly allocated for the child page table.
+            let child_ptes: &mut [T] = table_of(frame.start_paddr()).unwrap();
+            for (idx, child_pte) in child_ptes.iter_mut().enumerate() {
+                *child_pte = T::new(huge_paddr + idx * child_size, child_flags);
+            }
+            let table_flags = T::F::new()
+                .set_present(true)
+                .set_accessible_by_user(true)
+                .set_readable(true)
+                .set_writable(true);
+            last_entry.update(frame.start_paddr(), table_flags);
+            tlb_flush(vaddr);
+            self.tables.push(frame);
+        }
+    }
 }
 
 impl<T: PageTableEntryTrait> PageTable<T, DeviceMode> {
@@ -258,7 +442,7 @@ impl<T: PageTableEntryTrait> PageTable<T, DeviceMode> {
         paddr: Paddr,
         flags: T::F,
     ) -> Result<(), PageTableError> {
-        self.do_map(vaddr, paddr, flags)
+        self.do_map(vaddr, paddr, flags, PageSize::Size4K)
     }
 
     pub fn unmap(&mut self, vaddr: Vaddr) -> Result<(), PageTableError> {
@@ -268,7 +452,7 @@ impl<T: PageTableEntryTrait> PageTable<T, DeviceMode> {
 }
 
 impl<T: PageTableEntryTrait, M> PageTable<T, M> {
-    /// Mapping `vaddr` to `paddr` with flags.
+    /// Mapping `vaddr` to `paddr` with flags as a single page of `page_size`.
     ///
     /// # Safety
     ///
@@ -279,34 +463,62 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
         vaddr: Vaddr,
         paddr: Paddr,
         flags: T::F,
+        page_size: PageSize,
     ) -> Result<(), PageTableError> {
-        let last_entry = self.do_page_walk_mut(vaddr, true).unwrap();
+        if vaddr % page_size.nbytes() != 0 || paddr % page_size.nbytes() != 0 {
+            return Err(PageTableError::InvalidPageSize);
+        }
+        let (last_entry, level) = self
+            .do_page_walk_mut(vaddr, page_size.level(), true)
+            .unwrap();
         trace!(
-            "Page Table: Map vaddr:{:x?}, paddr:{:x?}, flags:{:x?}",
+            "Page Table: Map vaddr:{:x?}, paddr:{:x?}, flags:{:x?}, page size:{:?}",
             vaddr,
             paddr,
-            flags
+            flags,
+            page_size
         );
-        if last_entry.is_used() && last_entry.flags().is_present() {
+        // The walk stops early if a huge page has been mapped in a higher level.
+        if level != page_size.level() {
             return Err(PageTableError::InvalidModification);
         }
-        last_entry.update(paddr, flags);
+        if last_entry.is_used() && last_entry.flags().is_present() {
+            // A page table that maps nothing can be replaced by a huge page. The table is
+            // still kept in `tables` since other CPUs may have cached it.
+            let is_empty_table = level > 1
+                && !last_entry.flags().is_huge()
+                && table_of::<T>(last_entry.paddr())
+                    .unwrap()
+                    .iter()
+                    .all(|pte| !pte.is_used());
+            if !is_empty_table {
+                return Err(PageTableError::InvalidModification);
+            }
+        }
+        last_entry.update(paddr, flags.set_huge(level > 1));
         tlb_flush(vaddr);
         Ok(())
     }
 
-    /// Find the last PTE and return its mutable reference.
+    /// Find the last PTE down to the page table of `target_level` and return its mutable
+    /// reference, as well as the level of the page table where the PTE is.
     ///
+    /// The walk stops at the PTE of a huge page, which may be in a level higher than `target_level`.
     /// If create is set, it will create the next table until the last PTE.
     /// If not, it will return `None` if it cannot reach the last PTE.
-    fn do_page_walk_mut(&mut self, vaddr: Vaddr, create: bool) -> Option<&mut T> {
+    fn do_page_walk_mut(
+        &mut self,
+        vaddr: Vaddr,
+        target_level: usize,
+        create: bool,
+    ) -> Option<(&mut T, usize)> {
         let mut level = self.config.address_width as usize;
         // Safety: The offset does not exceed the value of PAGE_SIZE.
         // It only change the memory controlled by page table.
         let mut current: &mut T =
             unsafe { &mut *(calculate_pte_vaddr::<T>(self.root_paddr, vaddr, level) as *mut T) };
 
-        while level > 1 {
+        while level > target_level {
             if !current.flags().is_present() {
                 if !create {
                     return None;
@@ -332,15 +544,16 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
                 &mut *(calculate_pte_vaddr::<T>(current.paddr(), vaddr, level) as *mut T)
             };
         }
-        Some(current)
+        Some((current, level))
     }
 
-    /// Find the last PTE and return its immutable reference.
+    /// Find the last PTE and return its immutable reference, as well as the level of
+    /// the page table where the PTE is.
     ///
     /// This function will return `None` if it cannot reach the last PTE.
     /// Note that finding an entry does not mean the corresponding virtual memory address is mapped
     /// since the entry may be empty.
-    fn do_page_walk(&self, vaddr: Vaddr) -> Option<&T> {
+    fn do_page_walk(&self, vaddr: Vaddr) -> Option<(&T, usize)> {
         let mut level = self.config.address_width as usize;
         // Safety: The offset does not exceed the value of PAGE_SIZE.
         // It only change the memory controlled by page table.
@@ -360,21 +573,27 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
             current =
                 unsafe { &*(calculate_pte_vaddr::<T>(current.paddr(), vaddr, level) as *const T) };
         }
-        Some(current)
+        Some((current, level))
     }
 
     /// Unmap `vaddr`.
     ///
+    /// If `vaddr` is mapped by a huge page, `vaddr` must be the start address of the huge page,
+    /// and the whole huge page is unmapped.
+    ///
     /// # Safety
     ///
     /// This function allows arbitrary modifications to the page table.
     /// Incorrect modifications may cause the kernel to crash (e.g., unmap the linear mapping.).
     unsafe fn do_unmap(&mut self, vaddr: Vaddr) -> Result<(), PageTableError> {
-        let last_entry = self
-            .do_page_walk_mut(vaddr, false)
+        let (last_entry, level) = self
+            .do_page_walk_mut(vaddr, 1, false)
             .ok_or(PageTableError::InvalidModification)?;
         trace!("Page Table: Unmap vaddr:{:x?}", vaddr);
-        if !last_entry.is_used() || !last_entry.flags().is_present() {
+        if !last_entry.is_used()
+            || !last_entry.flags().is_present()
+            || vaddr % PageSize::from_level(level).nbytes() != 0
+        {
             return Err(PageTableError::InvalidModification);
         }
         last_entry.clear();
@@ -385,14 +604,17 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
     /// Modify the flags mapped at `vaddr`.
     /// If the modification succeeds, it will return the old flags of `vaddr`.
     ///
+    /// If `vaddr` is mapped by a huge page, `vaddr` must be the start address of the huge page,
+    /// and the flags of the whole huge page are modified.
+    ///
     /// # Safety
     ///
     /// This function allows arbitrary modifications to the page table.
     /// Incorrect modifications may cause the kernel to crash
     /// (e.g., make the linear mapping visible to the user mode applications.).
     unsafe fn do_protect(&mut self, vaddr: Vaddr, new_flags: T::F) -> Result<T::F, PageTableError> {
-        let last_entry = self
-            .do_page_walk_mut(vaddr, false)
+        let (last_entry, level) = self
+            .do_page_walk_mut(vaddr, 1, false)
             .ok_or(PageTableError::InvalidModification)?;
         let old_flags = last_entry.flags();
         trace!(
@@ -400,10 +622,13 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
             vaddr,
             new_flags
         );
-        if !last_entry.is_used() || !old_flags.is_present() {
+        if !last_entry.is_used()
+            || !old_flags.is_present()
+            || vaddr % PageSize::from_level(level).nbytes() != 0
+        {
             return Err(PageTableError::InvalidModification);
         }
-        last_entry.update(last_entry.paddr(), new_flags);
+        last_entry.update(last_entry.paddr(), new_flags.set_huge(level > 1));
         tlb_flush(vaddr);
         Ok(old_flags)
     }
@@ -421,7 +646,7 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
             root_paddr: page_directory_base.start_address().as_u64() as usize,
             tables: Vec::new(),
             config: PageTableConfig {
-                address_width: AddressWidth::Level4,
+                address_width: paging_level(),
             },
             _phantom: PhantomData,
         }
@@ -430,7 +655,7 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
     /// Return the flags of the PTE for the target virtual memory address.
     /// If the PTE does not exist, return `None`.
     pub fn flags(&self, vaddr: Vaddr) -> Option<T::F> {
-        self.do_page_walk(vaddr).map(|entry| entry.flags())
+        self.do_page_walk(vaddr).map(|(entry, _)| entry.flags())
     }
 
     /// Return the root physical address of current `PageTable`.
@@ -438,10 +663,15 @@ impl<T: PageTableEntryTrait, M> PageTable<T, M> {
         self.root_paddr
     }
 
+    /// Return the paging level of current `PageTable`.
+    pub fn address_width(&self) -> AddressWidth {
+        self.config.address_width
+    }
+
     /// Determine whether the target virtual memory address is mapped.
     pub fn is_mapped(&self, vaddr: Vaddr) -> bool {
         self.do_page_walk(vaddr)
-            .is_some_and(|last_entry| last_entry.is_used() && last_entry.flags().is_present())
+            .is_some_and(|(last_entry, _)| last_entry.is_used() && last_entry.flags().is_present())
     }
 }
 
@@ -464,9 +694,9 @@ pub fn vaddr_to_paddr(vaddr: Vaddr) -> Option<Paddr> {
     let page_table = KERNEL_PAGE_TABLE.get().unwrap().lock();
     // Although we bypass the unsafe APIs provided by KernelMode, the purpose here is
     // only to obtain the corresponding physical address according to the mapping.
-    let last_entry = page_table.do_page_walk(vaddr)?;
-    // FIXME: Support huge page
-    Some(last_entry.paddr() + (vaddr & (PAGE_SIZE - 1)))
+    let (last_entry, level) = page_table.do_page_walk(vaddr)?;
+    let page_size = PageSize::from_level(level).nbytes();
+    Some(last_entry.paddr() + (vaddr & (page_size - 1)))
 }
 
 fn calculate_pte_vaddr<T: PageTableEntryTrait>(
@@ -485,3 +715,45 @@ pub fn init() {
         SpinLock::new(unsafe { PageTable::from_root_register() })
     });
 }
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+
+    #[ktest]
+    fn split_huge_page_in_kernel_page_table() {
+        let page_size = PageSize::Size2M;
+        // The frames are zeroed via the direct mapping, so they are mapped in the kernel
+        // page table, probably with huge pages.
+        let frames = VmAllocOptions::new(page_size.nframes())
+            .is_contiguous(true)
+            .alloc()
+            .unwrap();
+        let paddr = frames.get(0).unwrap().start_paddr();
+        let vaddr = paddr_to_vaddr(paddr);
+
+        let mut page_table = KERNEL_PAGE_TABLE.get().unwrap().lock();
+        let flags = page_table.flags(vaddr).unwrap().set_huge(false);
+        // Safety: The page belongs to the frames allocated above, and it is only made
+        // read-only temporarily.
+        unsafe {
+            page_table
+                .protect(vaddr + PAGE_SIZE, flags.set_writable(false))
+                .unwrap();
+        }
+
+        // The split pages keep the same translations.
+        for offset in [0, PAGE_SIZE, page_size.nbytes() - PAGE_SIZE] {
+            let (last_entry, level) = page_table.do_page_walk(vaddr + offset).unwrap();
+            assert_eq!(level, 1);
+            assert_eq!(last_entry.paddr(), paddr + offset);
+        }
+        assert!(page_table.flags(vaddr).unwrap().writable());
+        assert!(!page_table.flags(vaddr + PAGE_SIZE).unwrap().writable());
+
+        // Safety: The page is made writable again as it was.
+        unsafe {
+            page_table.protect(vaddr + PAGE_SIZE, flags).unwrap();
+        }
+    }
+}
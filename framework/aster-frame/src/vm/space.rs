@@ -1,11 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::ops::Range;
+use core::{
+    cell::RefCell,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use bitflags::bitflags;
 
-use super::{is_page_aligned, MapArea, MemorySet, VmFrameVec, VmIo};
-use crate::{arch::mm::PageTableFlags, prelude::*, sync::Mutex, vm::PAGE_SIZE, Error};
+use super::{is_page_aligned, MapArea, MemorySet, PageSize, VmFrameVec, VmIo};
+use crate::{
+    arch::mm::PageTableFlags,
+    cpu::{this_cpu, CpuLocal},
+    cpu_local,
+    prelude::*,
+    smp::tlb_shootdown,
+    sync::Mutex,
+    vm::PAGE_SIZE,
+    Error,
+};
 
 /// Virtual memory space.
 ///
@@ -22,6 +35,14 @@ use crate::{arch::mm::PageTableFlags, prelude::*, sync::Mutex, vm::PAGE_SIZE, Er
 #[derive(Debug, Clone)]
 pub struct VmSpace {
     memory_set: Arc<Mutex<MemorySet>>,
+    /// The bitmap of the CPUs on which the VM space is active, whose TLBs may
+    /// cache the mappings of the VM space.
+    active_cpus: Arc<AtomicU64>,
+}
+
+cpu_local! {
+    /// The active CPU bitmap of the VM space that is last activated on each CPU.
+    static LAST_ACTIVE_CPUS: RefCell<Option<Arc<AtomicU64>>> = RefCell::new(None);
 }
 
 impl VmSpace {
@@ -29,17 +50,39 @@ impl VmSpace {
     pub fn new() -> Self {
         Self {
             memory_set: Arc::new(Mutex::new(MemorySet::new())),
+            active_cpus: Arc::new(AtomicU64::new(0)),
         }
     }
 
     /// Activate the page table, load root physical address to cr3
     #[allow(clippy::missing_safety_doc)]
     pub unsafe fn activate(&self) {
+        let this_cpu_bit = 1u64 << this_cpu();
+        // The CPU is marked before loading the page table, so that any later change
+        // to the mappings is followed by a shootdown on this CPU.
+        self.active_cpus.fetch_or(this_cpu_bit, Ordering::SeqCst);
+
         #[cfg(target_arch = "x86_64")]
         crate::arch::x86::mm::activate_page_table(
             self.memory_set.lock().pt.root_paddr(),
             x86_64::registers::control::Cr3Flags::PAGE_LEVEL_CACHE_DISABLE,
         );
+
+        // Loading the page table flushes the mappings of the last VM space, so the
+        // CPU no longer needs to be shot down for it.
+        let last_active_cpus = CpuLocal::borrow_with(&LAST_ACTIVE_CPUS, |last_active_cpus| {
+            last_active_cpus.replace(Some(self.active_cpus.clone()))
+        });
+        if let Some(last_active_cpus) = last_active_cpus
+            && !Arc::ptr_eq(&last_active_cpus, &self.active_cpus)
+        {
+            last_active_cpus.fetch_and(!this_cpu_bit, Ordering::SeqCst);
+        }
+    }
+
+    /// Flushes the TLBs of the other CPUs on which the VM space is active.
+    fn tlb_shootdown(&self) {
+        tlb_shootdown(self.active_cpus.load(Ordering::SeqCst));
     }
 
     /// Maps some physical memory pages into the VM space according to the given
@@ -53,6 +96,12 @@ impl VmSpace {
         if options.addr.is_none() {
             return Err(Error::InvalidArgs);
         }
+        let page_size = options.page_size;
+        if page_size != PageSize::Size4K
+            && !fit_huge_pages(&frames, options.addr.unwrap(), page_size)
+        {
+            return Err(Error::InvalidArgs);
+        }
 
         // if can overwrite, the old mapping should be unmapped.
         if options.can_overwrite {
@@ -72,10 +121,18 @@ impl VmSpace {
         base_addr
             .checked_add(frames.len() * PAGE_SIZE)
             .ok_or(Error::Overflow)?;
-        for (idx, frame) in frames.into_iter().enumerate() {
-            let addr = base_addr + idx * PAGE_SIZE;
-            let frames = VmFrameVec::from_one_frame(frame);
-            memory_set.map(MapArea::new(addr, PAGE_SIZE, flags, frames));
+        if page_size == PageSize::Size4K {
+            for (idx, frame) in frames.into_iter().enumerate() {
+                let addr = base_addr + idx * PAGE_SIZE;
+                let frames = VmFrameVec::from_one_frame(frame);
+                memory_set.map(MapArea::new(addr, PAGE_SIZE, flags, frames));
+            }
+        } else {
+            for (idx, huge_frames) in frames.0.chunks(page_size.nframes()).enumerate() {
+                let addr = base_addr + idx * page_size.nbytes();
+                let frames = VmFrameVec(huge_frames.to_vec());
+                memory_set.map(MapArea::new_huge(addr, flags, frames, page_size));
+            }
         }
 
         Ok(base_addr)
@@ -101,22 +158,33 @@ impl VmSpace {
         flags.is_some_and(|flags| !flags.contains(PageTableFlags::NO_EXECUTE))
     }
 
+    /// Returns the number of the physical memory pages that are mapped, i.e., the
+    /// resident set size in pages.
+    pub fn nr_mapped_pages(&self) -> usize {
+        self.memory_set.lock().nr_mapped_pages()
+    }
+
+    /// Clears the accessed bit of the page mapped at `vaddr`, returning whether the
+    /// page has been accessed since the bit was last cleared.
+    ///
+    /// The accessed bits help to find the pages that are not recently used. If the
+    /// page is not mapped, or is part of a huge page, `None` is returned.
+    pub fn clear_accessed(&self, vaddr: Vaddr) -> Option<bool> {
+        self.memory_set.lock().clear_accessed(vaddr)
+    }
+
     /// Unmaps the physical memory pages within the VM address range.
     ///
     /// The range is allowed to contain gaps, where no physical memory pages
     /// are mapped.
+    ///
+    /// The huge pages that are partly in the range are split, so that the pages
+    /// outside the range stay mapped.
     pub fn unmap(&self, range: &Range<Vaddr>) -> Result<()> {
         assert!(is_page_aligned(range.start) && is_page_aligned(range.end));
-        let mut start_va = range.start;
-        let num_pages = (range.end - range.start) / PAGE_SIZE;
-        let mut inner = self.memory_set.lock();
-        start_va
-            .checked_add(PAGE_SIZE * num_pages)
-            .ok_or(Error::Overflow)?;
-        for i in 0..num_pages {
-            inner.unmap(start_va)?;
-            start_va += PAGE_SIZE;
-        }
+        self.memory_set.lock().unmap_range(range);
+        // The VM space may be active on other CPUs.
+        self.tlb_shootdown();
         Ok(())
     }
 
@@ -125,22 +193,20 @@ impl VmSpace {
         self.memory_set.lock().clear();
         #[cfg(target_arch = "x86_64")]
         x86_64::instructions::tlb::flush_all();
+        self.tlb_shootdown();
     }
 
     /// Update the VM protection permissions within the VM address range.
     ///
-    /// The entire specified VM range must have been mapped with physical
-    /// memory pages.
+    /// The range is allowed to contain gaps, where no physical memory pages
+    /// are mapped. The huge pages that are partly in the range are split, so
+    /// that the pages outside the range keep their permissions.
     pub fn protect(&self, range: &Range<Vaddr>, perm: VmPerm) -> Result<()> {
         debug_assert!(range.start % PAGE_SIZE == 0);
         debug_assert!(range.end % PAGE_SIZE == 0);
-        let start_page = range.start / PAGE_SIZE;
-        let end_page = range.end / PAGE_SIZE;
         let flags = PageTableFlags::from(perm);
-        for page_idx in start_page..end_page {
-            let addr = page_idx * PAGE_SIZE;
-            self.memory_set.lock().protect(addr, flags)
-        }
+        self.memory_set.lock().protect_range(range, flags);
+        self.tlb_shootdown();
         Ok(())
     }
 
@@ -151,6 +217,7 @@ impl VmSpace {
     pub fn deep_copy(&self) -> Self {
         Self {
             memory_set: Arc::new(Mutex::new(self.memory_set.lock().clone())),
+            active_cpus: Arc::new(AtomicU64::new(0)),
         }
     }
 }
@@ -161,6 +228,25 @@ impl Default for VmSpace {
     }
 }
 
+/// Check whether the frames can be mapped at `addr` as huge pages of `page_size`, i.e.,
+/// each group of frames for a huge page is physically contiguous and aligned.
+fn fit_huge_pages(frames: &VmFrameVec, addr: Vaddr, page_size: PageSize) -> bool {
+    if !page_size.is_supported()
+        || addr % page_size.nbytes() != 0
+        || frames.len() % page_size.nframes() != 0
+    {
+        return false;
+    }
+    frames.0.chunks(page_size.nframes()).all(|huge_frames| {
+        let start_paddr = huge_frames[0].start_paddr();
+        start_paddr % page_size.nbytes() == 0
+            && huge_frames
+                .iter()
+                .enumerate()
+                .all(|(idx, frame)| frame.start_paddr() == start_paddr + idx * PAGE_SIZE)
+    })
+}
+
 impl VmIo for VmSpace {
     fn read_bytes(&self, vaddr: usize, buf: &mut [u8]) -> Result<()> {
         self.memory_set.lock().read_bytes(vaddr, buf)
@@ -183,6 +269,8 @@ pub struct VmMapOptions {
     perm: VmPerm,
     /// can overwrite
     can_overwrite: bool,
+    /// page size
+    page_size: PageSize,
 }
 
 impl VmMapOptions {
@@ -193,6 +281,7 @@ impl VmMapOptions {
             align: PAGE_SIZE,
             perm: VmPerm::empty(),
             can_overwrite: false,
+            page_size: PageSize::Size4K,
         }
     }
 
@@ -236,6 +325,19 @@ impl VmMapOptions {
         self.can_overwrite = can_overwrite;
         self
     }
+
+    /// Sets the size of the pages that map the frames.
+    ///
+    /// If the page size is larger than 4 KiB, every `page_size.nframes()` frames
+    /// are mapped as a huge page. So the frames of each huge page must be
+    /// physically contiguous, and both the address and the physical address of
+    /// the frames must be aligned to the page size.
+    ///
+    /// The default value of this option is 4 KiB.
+    pub fn page_size(&mut self, page_size: PageSize) -> &mut Self {
+        self.page_size = page_size;
+        self
+    }
 }
 
 impl Default for VmMapOptions {
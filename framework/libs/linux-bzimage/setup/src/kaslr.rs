@@ -0,0 +1,131 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Kernel address space layout randomization (KASLR).
+//!
+//! The kernel is linked at fixed addresses, with the relocations kept in the ELF
+//! file. The setup moves the kernel image by a random offset (the slide), which
+//! applies to both the physical and virtual addresses of the kernel image. So the
+//! boot page tables of the kernel, which map the kernel code with a fixed offset
+//! from its physical address, still work. Like Linux, the randomization is
+//! disabled by the `nokaslr` kernel command-line option.
+
+use core::ops::Range;
+
+use linux_boot_params::BootParams;
+
+use crate::loader::{image_paddr_range, is_relocatable};
+
+/// The alignment of the slide, which keeps the kernel image aligned to huge pages.
+const SLIDE_ALIGN: u64 = 0x200000;
+
+/// The end of the physical memory where the kernel image can be loaded.
+///
+/// The kernel code is mapped in the highest 2 GiB of the virtual address space,
+/// and the boot page tables only map the lowest 2 GiB of the physical memory there.
+/// Like Linux, we limit the kernel image to the lowest 1 GiB.
+const IMAGE_END_MAX: u64 = 0x4000_0000;
+
+/// Chooses a random slide of the kernel image in `payload`.
+///
+/// `is_usable` returns whether the physical memory range can hold the kernel
+/// image. The slide is zero if the randomization is disabled, or if the kernel
+/// ELF is not relocatable, or if no usable memory is found.
+pub fn choose_slide(
+    boot_params: &BootParams,
+    payload: &[u8],
+    mut is_usable: impl FnMut(Range<u64>) -> bool,
+) -> u64 {
+    if has_cmdline_option(boot_params, b"nokaslr") || !is_relocatable(payload) {
+        return 0;
+    }
+
+    let image_range = image_paddr_range(payload);
+    let image_start = *image_range.start();
+    let image_end = *image_range.end();
+    if image_end > IMAGE_END_MAX {
+        return 0;
+    }
+    let nr_slots = (IMAGE_END_MAX - image_end) / SLIDE_ALIGN + 1;
+
+    let payload_range = {
+        let start = payload.as_ptr() as u64;
+        start..start + payload.len() as u64
+    };
+    let ramdisk_range = {
+        let start = boot_params.hdr.ramdisk_image as u64;
+        start..start + boot_params.hdr.ramdisk_size as u64
+    };
+    let overlaps = |a: &Range<u64>, b: &Range<u64>| a.start < b.end && b.start < a.end;
+
+    // Probe the slots from a random one, so that the slide falls back to the
+    // nearby slots if the randomly chosen one is not usable.
+    let first_slot = read_random() as u64 % nr_slots;
+    for i in 0..nr_slots {
+        let slide = (first_slot + i) % nr_slots * SLIDE_ALIGN;
+        let range = image_start + slide..image_end + slide;
+        if overlaps(&range, &payload_range) || overlaps(&range, &ramdisk_range) {
+            continue;
+        }
+        if is_usable(range) {
+            return slide;
+        }
+    }
+    0
+}
+
+/// Returns whether the physical memory range is usable RAM according to the E820
+/// table provided by the bootloader.
+pub fn is_e820_ram(boot_params: &BootParams, range: &Range<u64>) -> bool {
+    let nr_entries = boot_params.e820_entries as usize;
+    boot_params.e820_table[..nr_entries].iter().any(|entry| {
+        let (addr, size, typ) = (entry.addr, entry.size, entry.typ);
+        matches!(typ, linux_boot_params::E820Type::Ram)
+            && addr <= range.start
+            && range.end <= addr + size
+    })
+}
+
+fn has_cmdline_option(boot_params: &BootParams, option: &[u8]) -> bool {
+    let cmdline_ptr = boot_params.hdr.cmd_line_ptr as usize as *const u8;
+    if cmdline_ptr.is_null() {
+        return false;
+    }
+    // Safety: the command line is a C-style zero-terminated string provided by
+    // the bootloader.
+    let cmdline = unsafe {
+        let mut len = 0;
+        while *cmdline_ptr.add(len) != 0 {
+            len += 1;
+        }
+        core::slice::from_raw_parts(cmdline_ptr, len)
+    };
+    cmdline
+        .split(|c| c.is_ascii_whitespace())
+        .any(|arg| arg == option)
+}
+
+/// Returns a random number from the hardware, or from the time-stamp counter if
+/// `RDRAND` is not supported.
+fn read_random() -> u32 {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__cpuid, _rdrand32_step, _rdtsc};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__cpuid, _rdrand32_step, _rdtsc};
+
+    #[target_feature(enable = "rdrand")]
+    unsafe fn rdrand32(random: &mut u32) -> bool {
+        _rdrand32_step(random) == 1
+    }
+
+    const CPUID_ECX_RDRAND: u32 = 1 << 30;
+    // Safety: CPUID is supported by all processors running the setup.
+    let has_rdrand = unsafe { __cpuid(1) }.ecx & CPUID_ECX_RDRAND != 0;
+    let mut random = 0;
+    // Safety: RDRAND is supported.
+    if has_rdrand && unsafe { rdrand32(&mut random) } {
+        return random;
+    }
+    // Safety: reading the time-stamp counter has no side effects.
+    let tsc = unsafe { _rdtsc() };
+    (tsc ^ (tsc >> 32)) as u32
+}
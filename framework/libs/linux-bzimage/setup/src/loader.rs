@@ -1,10 +1,24 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use xmas_elf::program::{ProgramHeader, SegmentData};
+use core::ops::RangeInclusive;
+
+use xmas_elf::{
+    program::{ProgramHeader, SegmentData},
+    sections::{SectionData, ShType, SHF_ALLOC},
+    ElfFile,
+};
+
+const R_X86_64_64: u32 = 1;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
 
 /// Load the kernel ELF payload to memory.
-pub fn load_elf(file: &[u8]) {
-    let elf = xmas_elf::ElfFile::new(file).unwrap();
+///
+/// Both the physical and virtual addresses of the kernel image are moved by
+/// `slide` bytes. A nonzero `slide` requires the kernel ELF to be relocatable
+/// (see [`is_relocatable`]).
+pub fn load_elf(file: &[u8], slide: u64) {
+    let elf = ElfFile::new(file).unwrap();
 
     for ph in elf.program_iter() {
         let ProgramHeader::Ph64(program) = ph else {
@@ -13,29 +27,148 @@ pub fn load_elf(file: &[u8]) {
             );
         };
         if program.get_type().unwrap() == xmas_elf::program::Type::Load {
-            load_segment(&elf, program);
+            load_segment(&elf, program, slide);
+        }
+    }
+
+    if slide != 0 {
+        relocate(&elf, slide);
+    }
+}
+
+/// Returns the range of the physical addresses where the kernel image is loaded
+/// without being moved.
+pub fn image_paddr_range(file: &[u8]) -> RangeInclusive<u64> {
+    let elf = ElfFile::new(file).unwrap();
+    let mut start = u64::MAX;
+    let mut end = 0;
+    for_each_load_segment(&elf, |program| {
+        start = start.min(program.physical_addr);
+        end = end.max(program.physical_addr + program.mem_size);
+    });
+    start..=end
+}
+
+/// Returns whether the kernel ELF keeps the relocations of its loaded sections,
+/// which are emitted by the linker with `--emit-relocs`.
+pub fn is_relocatable(file: &[u8]) -> bool {
+    let elf = ElfFile::new(file).unwrap();
+    elf.section_iter()
+        .any(|section| is_loaded_rela_section(&elf, &section))
+}
+
+fn for_each_load_segment(file: &ElfFile, mut f: impl FnMut(&xmas_elf::program::ProgramHeader64)) {
+    for ph in file.program_iter() {
+        if let ProgramHeader::Ph64(program) = ph {
+            if matches!(program.get_type(), Ok(xmas_elf::program::Type::Load)) {
+                f(program);
+            }
         }
     }
 }
 
-fn load_segment(file: &xmas_elf::ElfFile, program: &xmas_elf::program::ProgramHeader64) {
+fn is_loaded_rela_section(file: &ElfFile, section: &xmas_elf::sections::SectionHeader) -> bool {
+    if !matches!(section.get_type(), Ok(ShType::Rela)) {
+        return false;
+    }
+    file.section_header(section.info() as u16)
+        .is_ok_and(|target| target.flags() & SHF_ALLOC != 0)
+}
+
+/// Applies the relocations of the loaded sections to the kernel image moved by
+/// `slide` bytes.
+///
+/// The kernel is linked at fixed addresses, so only the absolute relocations
+/// need to be fixed. The relocated values are already in the image, and they
+/// are moved if they refer to the kernel image. The PC-relative relocations are
+/// kept untouched since the whole kernel image is moved together.
+fn relocate(file: &ElfFile, slide: u64) {
+    let paddr_range = image_paddr_range(file.input);
+    let mut vaddr_start = u64::MAX;
+    let mut vaddr_end = 0;
+    for_each_load_segment(file, |program| {
+        vaddr_start = vaddr_start.min(program.virtual_addr);
+        vaddr_end = vaddr_end.max(program.virtual_addr + program.mem_size);
+    });
+    let vaddr_range = vaddr_start..=vaddr_end;
+    let refers_to_image = |value: u64| paddr_range.contains(&value) || vaddr_range.contains(&value);
+
+    for section in file.section_iter() {
+        if !is_loaded_rela_section(file, &section) {
+            continue;
+        }
+        let Ok(SectionData::Rela64(relas)) = section.get_data(file) else {
+            panic!("[setup] Unexpected relocation section data type!");
+        };
+        for rela in relas {
+            let Some(paddr) = loaded_paddr_of(file, rela.get_offset()) else {
+                continue;
+            };
+            let ptr = (paddr + slide) as usize;
+            // Safety: the place to relocate is within the kernel image that has been
+            // loaded to memory.
+            unsafe {
+                match rela.get_type() {
+                    R_X86_64_64 => {
+                        let value = core::ptr::read_unaligned(ptr as *const u64);
+                        if refers_to_image(value) {
+                            core::ptr::write_unaligned(ptr as *mut u64, value + slide);
+                        }
+                    }
+                    R_X86_64_32 => {
+                        let value = core::ptr::read_unaligned(ptr as *const u32) as u64;
+                        if refers_to_image(value) {
+                            core::ptr::write_unaligned(ptr as *mut u32, (value + slide) as u32);
+                        }
+                    }
+                    R_X86_64_32S => {
+                        let value = core::ptr::read_unaligned(ptr as *const i32) as i64 as u64;
+                        if refers_to_image(value) {
+                            core::ptr::write_unaligned(
+                                ptr as *mut u32,
+                                value.wrapping_add(slide) as u32,
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Returns the physical address where the virtual address `vaddr` of the kernel
+/// image is loaded without being moved.
+fn loaded_paddr_of(file: &ElfFile, vaddr: u64) -> Option<u64> {
+    let mut paddr = None;
+    for_each_load_segment(file, |program| {
+        if (program.virtual_addr..program.virtual_addr + program.file_size).contains(&vaddr) {
+            paddr = Some(program.physical_addr + (vaddr - program.virtual_addr));
+        }
+    });
+    paddr
+}
+
+fn load_segment(file: &ElfFile, program: &xmas_elf::program::ProgramHeader64, slide: u64) {
     let SegmentData::Undefined(header_data) = program.get_data(file).unwrap() else {
         panic!("[setup] Unexpected segment data type!");
     };
-    // Safety: the physical address from the ELF file is valid
+    let physical_addr = program.physical_addr + slide;
+    // Safety: the physical address from the ELF file is valid, and the caller
+    // ensures that the moved image is in usable memory.
     let dst_slice = unsafe {
-        core::slice::from_raw_parts_mut(program.physical_addr as *mut u8, program.mem_size as usize)
+        core::slice::from_raw_parts_mut(physical_addr as *mut u8, program.mem_size as usize)
     };
     /* crate::println!(
         "[setup loader debug] loading ELF segment at {:#x}, size = {:#x}",
-        program.physical_addr,
+        physical_addr,
         program.mem_size,
     ); */
     #[cfg(feature = "debug_print")]
     unsafe {
         use crate::console::{print_hex, print_str};
         print_str("[setup loader debug] loading ELF segment at ");
-        print_hex(program.physical_addr as u64);
+        print_hex(physical_addr);
         print_str(", size = ");
         print_hex(program.mem_size as u64);
         print_str("\n");
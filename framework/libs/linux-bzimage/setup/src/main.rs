@@ -25,6 +25,7 @@
 use linux_boot_params::BootParams;
 
 mod console;
+mod kaslr;
 mod loader;
 
 // Unfortunately, the entrypoint is not defined here in the main.rs file.
@@ -4,7 +4,10 @@ use linux_boot_params::BootParams;
 use uefi::{
     data_types::Handle,
     proto::loaded_image::LoadedImage,
-    table::{boot::MemoryMap, Boot, Runtime, SystemTable},
+    table::{
+        boot::{AllocateType, MemoryMap, MemoryType},
+        Boot, Runtime, SystemTable,
+    },
 };
 
 use super::{
@@ -57,7 +60,20 @@ fn efi_phase_boot(
 
     uefi_services::println!("[EFI stub] Loading payload.");
     let payload = unsafe { crate::get_payload(&*boot_params_ptr) };
-    crate::loader::load_elf(payload);
+    // The kernel image is only moved to the memory that can be allocated from the
+    // firmware, so that the firmware does not use the memory.
+    let slide = crate::kaslr::choose_slide(unsafe { &*boot_params_ptr }, payload, |range| {
+        let nr_pages = (range.end - range.start).div_ceil(4096) as usize;
+        system_table
+            .boot_services()
+            .allocate_pages(
+                AllocateType::Address(range.start),
+                MemoryType::LOADER_DATA,
+                nr_pages,
+            )
+            .is_ok()
+    });
+    crate::loader::load_elf(payload, slide);
 
     uefi_services::println!("[EFI stub] Exiting EFI boot services.");
     let memory_type = {
@@ -69,13 +85,14 @@ fn efi_phase_boot(
     };
     let (system_table, memory_map) = system_table.exit_boot_services(memory_type);
 
-    efi_phase_runtime(system_table, memory_map, boot_params_ptr);
+    efi_phase_runtime(system_table, memory_map, boot_params_ptr, slide);
 }
 
 fn efi_phase_runtime(
     _system_table: SystemTable<Runtime>,
     memory_map: MemoryMap<'static>,
     boot_params_ptr: *mut BootParams,
+    slide: u64,
 ) -> ! {
     unsafe {
         crate::console::print_str("[EFI stub] Entered runtime services.\n");
@@ -196,5 +213,10 @@ fn efi_phase_runtime(
         print_str("\n");
     }
 
-    unsafe { super::call_aster_entrypoint(super::ASTER_ENTRY_POINT as u64, boot_params_ptr as u64) }
+    unsafe {
+        super::call_aster_entrypoint(
+            super::ASTER_ENTRY_POINT as u64 + slide,
+            boot_params_ptr as u64,
+        )
+    }
 }
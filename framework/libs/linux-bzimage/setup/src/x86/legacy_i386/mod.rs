@@ -28,10 +28,18 @@ extern "cdecl" fn bzimage_entry(boot_params_ptr: u32) -> ! {
     let boot_params = unsafe { &*(boot_params_ptr as *const BootParams) };
     // Safety: the payload_offset and payload_length is valid.
     let payload = crate::get_payload(boot_params);
-    crate::loader::load_elf(payload);
+    let slide = crate::kaslr::choose_slide(boot_params, payload, |range| {
+        crate::kaslr::is_e820_ram(boot_params, &range)
+    });
+    crate::loader::load_elf(payload, slide);
 
     // Safety: the entrypoint and the ptr is valid.
-    unsafe { call_aster_entrypoint(ASTER_ENTRY_POINT, boot_params_ptr.try_into().unwrap()) };
+    unsafe {
+        call_aster_entrypoint(
+            ASTER_ENTRY_POINT + slide as u32,
+            boot_params_ptr.try_into().unwrap(),
+        )
+    };
 }
 
 unsafe fn call_aster_entrypoint(entrypoint: u32, boot_params_ptr: u32) -> ! {
@@ -4,7 +4,7 @@ use crate::{
     fs::{
         devpts::DevPts,
         fs_resolver::{FsPath, FsResolver},
-        utils::{Dentry, Inode, InodeMode, InodeType},
+        utils::{Dentry, Inode, InodeMode, InodeType, MountFlags},
     },
     prelude::*,
 };
@@ -23,7 +23,7 @@ pub fn init() -> Result<()> {
     let dev = fs.lookup(&FsPath::try_from("/dev")?)?;
     // Create the "pts" directory and mount devpts on it.
     let devpts = dev.create("pts", InodeType::Dir, InodeMode::from_bits_truncate(0o755))?;
-    devpts.mount(DevPts::new())?;
+    devpts.mount(DevPts::new(), "devpts", MountFlags::empty())?;
 
     DEV_PTS.call_once(|| devpts);
 
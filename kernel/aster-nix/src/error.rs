@@ -236,6 +236,12 @@ impl From<alloc::string::FromUtf8Error> for Error {
     }
 }
 
+impl From<alloc::collections::TryReserveError> for Error {
+    fn from(_: alloc::collections::TryReserveError) -> Self {
+        Error::with_message(Errno::ENOMEM, "cannot allocate memory")
+    }
+}
+
 impl From<core::ffi::FromBytesUntilNulError> for Error {
     fn from(_: core::ffi::FromBytesUntilNulError) -> Self {
         Error::with_message(Errno::E2BIG, "Cannot find null in cstring")
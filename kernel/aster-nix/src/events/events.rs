@@ -2,12 +2,15 @@
 
 /// A trait to represent any events.
 ///
+/// Events are usually small and copyable, but they may also carry data that
+/// is only cloneable, e.g., the name of a file.
+///
 /// # The unit event
 ///
 /// The unit type `()` can serve as a unit event.
 /// It can be used if there is only one kind of event
 /// and the event carries no additional information.
-pub trait Events: Copy + Clone + Send + Sync + 'static {}
+pub trait Events: Clone + Send + Sync + 'static {}
 
 impl Events for () {}
 
@@ -60,6 +60,11 @@ impl<E: Events, F: EventsFilter<E>> Subject<E, F> {
         observer
     }
 
+    /// Returns whether there are any registered observers.
+    pub fn has_observers(&self) -> bool {
+        self.num_observers.load(Ordering::Relaxed) != 0
+    }
+
     /// Notify events to all registered observers.
     ///
     /// It will remove the observers which have been freed.
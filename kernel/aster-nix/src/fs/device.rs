@@ -74,6 +74,12 @@ impl Debug for DeviceId {
     }
 }
 
+impl From<u64> for DeviceId {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
 impl From<DeviceId> for u64 {
     fn from(value: DeviceId) -> Self {
         value.0
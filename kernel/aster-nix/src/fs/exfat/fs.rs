@@ -387,6 +387,10 @@ impl PageCacheBackend for ExfatFS {
 }
 
 impl FileSystem for ExfatFS {
+    fn name(&self) -> &'static str {
+        "exfat"
+    }
+
     fn sync(&self) -> Result<()> {
         for inode in self.inodes.read().values() {
             inode.sync()?;
@@ -400,7 +404,12 @@ impl FileSystem for ExfatFS {
     }
 
     fn sb(&self) -> SuperBlock {
-        SuperBlock::new(BOOT_SIGNATURE as u64, self.sector_size(), MAX_NAME_LENGTH)
+        // Like Linux, the blocks are counted in clusters.
+        let mut sb = SuperBlock::new(EXFAT_MAGIC, self.cluster_size(), MAX_NAME_LENGTH);
+        sb.blocks = (self.super_block.num_clusters - EXFAT_RESERVED_CLUSTERS) as usize;
+        sb.bfree = self.num_free_clusters() as usize;
+        sb.bavail = sb.bfree;
+        sb
     }
 
     fn flags(&self) -> FsFlags {
@@ -434,3 +443,36 @@ pub struct ExfatMountOptions {
     pub(super) time_offset: i32,
     pub(super) zero_size_dir: bool,
 }
+
+impl ExfatMountOptions {
+    /// Parses the comma-separated mount options, e.g., "uid=1000,gid=1000,umask=022".
+    ///
+    /// The options that are not supported are ignored.
+    pub fn parse(options: &str) -> Result<Self> {
+        let mut mount_options = Self::default();
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').unwrap_or((option, ""));
+            match key {
+                "uid" => mount_options.fs_uid = parse_option_value(value, 10)?,
+                "gid" => mount_options.fs_gid = parse_option_value(value, 10)?,
+                "umask" => {
+                    mount_options.fs_fmask = parse_option_value(value, 8)?;
+                    mount_options.fs_dmask = mount_options.fs_fmask;
+                }
+                "fmask" => mount_options.fs_fmask = parse_option_value(value, 8)?,
+                "dmask" => mount_options.fs_dmask = parse_option_value(value, 8)?,
+                "keep_last_dots" => mount_options.keep_last_dots = true,
+                "zero_size_dir" => mount_options.zero_size_dir = true,
+                _ => warn!("unsupported exfat mount option: {}", option),
+            }
+        }
+        Ok(mount_options)
+    }
+}
+
+fn parse_option_value<T: TryFrom<u32>>(value: &str, radix: u32) -> Result<T> {
+    u32::from_str_radix(value, radix)
+        .ok()
+        .and_then(|value| T::try_from(value).ok())
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid exfat mount option value"))
+}
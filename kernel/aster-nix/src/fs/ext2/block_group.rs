@@ -41,10 +41,24 @@ impl BlockGroup {
                 let descriptor = {
                     // Read the block group descriptor
                     // TODO: if the main is corrupted, should we load the backup?
-                    let offset = idx * core::mem::size_of::<RawGroupDescriptor>();
+                    let offset = idx * super_block.group_descriptor_size();
                     let raw_descriptor = group_descriptors_segment
                         .read_val::<RawGroupDescriptor>(offset)
                         .unwrap();
+                    if super_block.group_descriptor_size() >= RAW_GROUP_DESCRIPTOR_64BIT_SIZE {
+                        // The upper parts are never written, so they must be zeros.
+                        let raw_descriptor_high = group_descriptors_segment
+                            .read_val::<RawGroupDescriptorHigh>(
+                                offset + core::mem::size_of::<RawGroupDescriptor>(),
+                            )
+                            .unwrap();
+                        if !raw_descriptor_high.is_zero() {
+                            return_errno_with_message!(
+                                Errno::EFBIG,
+                                "the block group is out of range"
+                            );
+                        }
+                    }
                     GroupDescriptor::from(raw_descriptor)
                 };
 
@@ -232,14 +246,14 @@ impl BlockGroup {
         let mut bio_waiter = BioWaiter::new();
         // Writes back the inode bitmap.
         let inode_bitmap_bid = Bid::new(inner.metadata.descriptor.inode_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
+        bio_waiter.concat(fs.write_metadata_bytes_async(
             inode_bitmap_bid.to_offset(),
             inner.metadata.inode_bitmap.as_bytes(),
         )?);
 
         // Writes back the block bitmap.
         let block_bitmap_bid = Bid::new(inner.metadata.descriptor.block_bitmap_bid as u64);
-        bio_waiter.concat(fs.block_device().write_bytes_async(
+        bio_waiter.concat(fs.write_metadata_bytes_async(
             block_bitmap_bid.to_offset(),
             inner.metadata.block_bitmap.as_bytes(),
         )?);
@@ -325,7 +339,10 @@ impl PageCacheBackend for BlockGroupImpl {
 
     fn write_page(&self, idx: usize, frame: &VmFrame) -> Result<BioWaiter> {
         let bid = self.inode_table_bid + idx as Ext2Bid;
-        self.fs.upgrade().unwrap().write_block_async(bid, frame)
+        self.fs
+            .upgrade()
+            .unwrap()
+            .write_metadata_block_async(bid, frame)
     }
 
     fn npages(&self) -> usize {
@@ -453,6 +470,10 @@ struct GroupDescriptor {
     free_inodes_count: u16,
     /// Number of directories in group
     dirs_count: u16,
+    /// Flags of the group used by Ext4
+    flags: u16,
+    /// The remaining fields used by Ext4
+    reserved: [u32; 3],
 }
 
 impl From<RawGroupDescriptor> for GroupDescriptor {
@@ -464,12 +485,18 @@ impl From<RawGroupDescriptor> for GroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
+            flags: desc.flags,
+            reserved: desc.reserved,
         }
     }
 }
 
 const_assert!(core::mem::size_of::<RawGroupDescriptor>() == 32);
 
+/// The minimum size of the group descriptor if the 64-bit feature is enabled.
+pub(super) const RAW_GROUP_DESCRIPTOR_64BIT_SIZE: usize =
+    core::mem::size_of::<RawGroupDescriptor>() + core::mem::size_of::<RawGroupDescriptorHigh>();
+
 /// The raw block group descriptor.
 ///
 /// The table starts on the first block following the superblock.
@@ -482,7 +509,7 @@ pub(super) struct RawGroupDescriptor {
     pub free_blocks_count: u16,
     pub free_inodes_count: u16,
     pub dirs_count: u16,
-    pad: u16,
+    flags: u16,
     reserved: [u32; 3],
 }
 
@@ -495,8 +522,38 @@ impl From<&GroupDescriptor> for RawGroupDescriptor {
             free_blocks_count: desc.free_blocks_count,
             free_inodes_count: desc.free_inodes_count,
             dirs_count: desc.dirs_count,
-            pad: 0u16,
-            reserved: [0u32; 3],
+            flags: desc.flags,
+            reserved: desc.reserved,
         }
     }
 }
+
+/// The upper parts of the raw block group descriptor, which exist only
+/// if the 64-bit feature is enabled.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawGroupDescriptorHigh {
+    block_bitmap_high: u32,
+    inode_bitmap_high: u32,
+    inode_table_high: u32,
+    free_blocks_count_high: u16,
+    free_inodes_count_high: u16,
+    dirs_count_high: u16,
+    itable_unused_high: u16,
+    exclude_bitmap_high: u32,
+    block_bitmap_csum_high: u16,
+    inode_bitmap_csum_high: u16,
+    reserved: u32,
+}
+
+impl RawGroupDescriptorHigh {
+    /// Returns whether the upper parts of the locations and counts are zeros.
+    fn is_zero(&self) -> bool {
+        self.block_bitmap_high == 0
+            && self.inode_bitmap_high == 0
+            && self.inode_table_high == 0
+            && self.free_blocks_count_high == 0
+            && self.free_inodes_count_high == 0
+            && self.dirs_count_high == 0
+    }
+}
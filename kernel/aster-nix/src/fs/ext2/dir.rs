@@ -45,6 +45,21 @@ impl DirEntry {
         Self::new(parent_ino, "..", FileType::Dir)
     }
 
+    /// Constructs an unused `DirEntry` which occupies `record_len` bytes.
+    ///
+    /// Its inode number is zero, so it is skipped by the readers.
+    pub(super) fn unused(record_len: usize) -> Self {
+        Self {
+            header: DirEntryHeader {
+                ino: 0,
+                record_len: record_len as u16,
+                name_len: 0,
+                file_type: DirEntryFileType::Unknown as _,
+            },
+            name: CStr256::from(""),
+        }
+    }
+
     /// Returns a reference to the header.
     fn header(&self) -> &DirEntryHeader {
         &self.header
@@ -95,6 +110,17 @@ impl DirEntry {
     pub(super) fn gap_len(&self) -> usize {
         self.record_len() - self.actual_len()
     }
+
+    /// Returns the length of the space that can be used by a new entry.
+    ///
+    /// The whole record of an unused entry can be reused.
+    pub(super) fn free_len(&self) -> usize {
+        if self.ino() == 0 {
+            self.record_len()
+        } else {
+            self.gap_len()
+        }
+    }
 }
 
 /// The header of `DirEntry`.
@@ -169,14 +195,32 @@ impl<'a> DirEntryReader<'a> {
         }
     }
 
-    /// Reads one `DirEntry` from the current offset.
+    /// Reads one `DirEntry` from the current offset, the unused entries are skipped.
     pub fn read_entry(&mut self) -> Result<DirEntry> {
+        loop {
+            let entry = self.read_raw_entry()?;
+            if entry.ino() != 0 {
+                return Ok(entry);
+            }
+        }
+    }
+
+    /// Reads one `DirEntry` from the current offset, including the unused one.
+    pub(super) fn read_raw_entry(&mut self) -> Result<DirEntry> {
+        if self.offset >= self.page_cache.pages().size() {
+            return_errno!(Errno::ENOENT);
+        }
+
         let header = self
             .page_cache
             .pages()
             .read_val::<DirEntryHeader>(self.offset)?;
-        if header.ino == 0 {
-            return_errno!(Errno::ENOENT);
+        let record_len = header.record_len as usize;
+        if record_len < DirEntry::header_len() + header.name_len as usize
+            || record_len % 4 != 0
+            || (self.offset % BLOCK_SIZE) + record_len > BLOCK_SIZE
+        {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid dir entry");
         }
 
         let mut name = vec![0u8; header.name_len as _];
@@ -197,15 +241,19 @@ impl<'a> Iterator for DirEntryReader<'a> {
     type Item = (usize, DirEntry);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let offset = self.offset;
-        let entry = match self.read_entry() {
-            Ok(entry) => entry,
-            Err(_) => {
-                return None;
+        loop {
+            let offset = self.offset;
+            let entry = match self.read_raw_entry() {
+                Ok(entry) => entry,
+                Err(_) => {
+                    return None;
+                }
+            };
+
+            if entry.ino() != 0 {
+                return Some((offset, entry));
             }
-        };
-
-        Some((offset, entry))
+        }
     }
 }
 
@@ -242,59 +290,139 @@ impl<'a> DirEntryWriter<'a> {
     /// If there is a gap between existing entries, inserts the new entry into the gap；
     /// If there is no available space, expands the size and appends the new entry at the end.
     pub fn append_entry(&mut self, mut new_entry: DirEntry) -> Result<()> {
-        let Some((offset, mut entry)) = DirEntryReader::new(self.page_cache, self.offset)
-            .find(|(_, entry)| entry.gap_len() >= new_entry.record_len())
-        else {
-            // Resize and append it at the new block.
-            let old_size = self.page_cache.pages().size();
-            let new_size = old_size + BLOCK_SIZE;
-            self.page_cache.pages().resize(new_size)?;
-            new_entry.set_record_len(BLOCK_SIZE);
-            self.offset = old_size;
+        let mut reader = DirEntryReader::new(self.page_cache, self.offset);
+        while let Ok(entry) = reader.read_raw_entry() {
+            if entry.free_len() >= new_entry.record_len() {
+                let offset = reader.offset - entry.record_len();
+                return self.insert_entry_at(offset, entry, new_entry);
+            }
+        }
+
+        // Resize and append it at the new block.
+        let old_size = self.page_cache.pages().size();
+        let new_size = old_size + BLOCK_SIZE;
+        self.page_cache.pages().resize(new_size)?;
+        new_entry.set_record_len(BLOCK_SIZE);
+        self.offset = old_size;
+        self.write_entry(&new_entry)?;
+        Ok(())
+    }
+
+    /// Inserts a new `DirEntry` into the block starting at `block_offset`.
+    ///
+    /// Returns `false` if there is no available space in the block.
+    pub(super) fn insert_entry_in_block(
+        &mut self,
+        block_offset: usize,
+        new_entry: DirEntry,
+    ) -> Result<bool> {
+        let mut reader = DirEntryReader::new(self.page_cache, block_offset);
+        while reader.offset < block_offset + BLOCK_SIZE {
+            let entry = reader.read_raw_entry()?;
+            if entry.free_len() >= new_entry.record_len() {
+                let offset = reader.offset - entry.record_len();
+                self.insert_entry_at(offset, entry, new_entry)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Inserts a new `DirEntry` into the free space of the `entry` at `offset`.
+    fn insert_entry_at(
+        &mut self,
+        offset: usize,
+        mut entry: DirEntry,
+        mut new_entry: DirEntry,
+    ) -> Result<()> {
+        self.offset = offset;
+        if entry.ino() == 0 {
+            // Reuse the record of the unused entry.
+            new_entry.set_record_len(entry.record_len());
             self.write_entry(&new_entry)?;
-            return Ok(());
+        } else {
+            // Write in the gap between existing entries.
+            new_entry.set_record_len(entry.gap_len());
+            entry.set_record_len(entry.actual_len());
+            self.write_entry(&entry)?;
+            self.write_entry(&new_entry)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the `entries` into the block starting at `block_offset` compactly,
+    /// the last entry occupies the rest of the block.
+    pub(super) fn write_block(&mut self, block_offset: usize, entries: &[DirEntry]) -> Result<()> {
+        self.offset = block_offset;
+        let Some((last_entry, entries)) = entries.split_last() else {
+            return self.write_entry(&DirEntry::unused(BLOCK_SIZE));
         };
 
-        // Write in the gap between existing entries.
-        new_entry.set_record_len(entry.gap_len());
-        entry.set_record_len(entry.actual_len());
-        self.offset = offset;
-        self.write_entry(&entry)?;
-        self.write_entry(&new_entry)?;
+        for entry in entries {
+            let mut entry = entry.clone();
+            entry.set_record_len(entry.actual_len());
+            self.write_entry(&entry)?;
+        }
+        let mut last_entry = last_entry.clone();
+        last_entry.set_record_len(block_offset + BLOCK_SIZE - self.offset);
+        self.write_entry(&last_entry)?;
         Ok(())
     }
 
     /// Removes and returns an existing `DirEntry` indicated by `name`.
     pub fn remove_entry(&mut self, name: &str) -> Result<DirEntry> {
-        let self_entry_record_len = DirEntry::self_entry(0).record_len();
-        let reader = DirEntryReader::new(self.page_cache, 0);
-        let next_reader = DirEntryReader::new(self.page_cache, self_entry_record_len);
-        let Some(((pre_offset, mut pre_entry), (offset, entry))) = reader
-            .zip(next_reader)
-            .find(|((offset, _), (_, dir_entry))| dir_entry.name() == name)
+        let Some((offset, entry)) =
+            DirEntryReader::new(self.page_cache, 0).find(|(_, entry)| entry.name() == name)
         else {
             return_errno!(Errno::ENOENT);
         };
 
-        if DirEntryReader::new(self.page_cache, offset)
-            .next()
-            .is_none()
-            && Bid::from_offset(pre_offset) != Bid::from_offset(offset)
-        {
-            // Shrink the size.
-            let new_size = pre_offset.align_up(BLOCK_SIZE);
-            self.page_cache.pages().resize(new_size)?;
-            pre_entry.set_record_len(new_size - pre_offset);
-            self.offset = pre_offset;
-            self.write_entry(&pre_entry)?;
-        } else {
-            // Update the previous entry.
-            pre_entry.set_record_len(pre_entry.record_len() + entry.record_len());
-            self.offset = pre_offset;
-            self.write_entry(&pre_entry)?;
+        self.remove_entry_at(offset, &entry, true)?;
+        Ok(entry)
+    }
+
+    /// Removes the `entry` at `offset`.
+    ///
+    /// The size is shrunk if the entry is the only one in the last block and
+    /// `can_shrink` is true.
+    pub(super) fn remove_entry_at(
+        &mut self,
+        offset: usize,
+        entry: &DirEntry,
+        can_shrink: bool,
+    ) -> Result<()> {
+        let block_offset = offset.align_down(BLOCK_SIZE);
+        if offset == block_offset {
+            if can_shrink
+                && offset != 0
+                && offset + entry.record_len() == self.page_cache.pages().size()
+            {
+                // Shrink the size.
+                self.page_cache.pages().resize(offset)?;
+            } else {
+                // The first entry in a block can not be merged, so it is marked as unused.
+                self.offset = offset;
+                self.write_entry(&DirEntry::unused(entry.record_len()))?;
+            }
+            return Ok(());
         }
 
-        Ok(entry)
+        // Update the previous entry in the same block.
+        let mut reader = DirEntryReader::new(self.page_cache, block_offset);
+        let (pre_offset, mut pre_entry) = loop {
+            let pre_offset = reader.offset;
+            let pre_entry = reader.read_raw_entry()?;
+            if reader.offset == offset {
+                break (pre_offset, pre_entry);
+            }
+            if reader.offset > offset {
+                return_errno_with_message!(Errno::EUCLEAN, "invalid dir entry");
+            }
+        };
+        pre_entry.set_record_len(pre_entry.record_len() + entry.record_len());
+        self.offset = pre_offset;
+        self.write_entry(&pre_entry)?;
+        Ok(())
     }
 
     /// Renames the `DirEntry` from `old_name` to the `new_name` from the current offset.
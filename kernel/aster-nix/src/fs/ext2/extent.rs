@@ -0,0 +1,721 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The extent tree of Ext4, which is an alternative to the indirect block pointers.
+//!
+//! An extent maps a range of consecutive file blocks to a range of consecutive
+//! device blocks. The extents of an inode are organized as a B+ tree whose root
+//! node is stored in the block pointers of the inode. Each node starts with a header,
+//! followed by index entries pointing to the lower nodes, or by extents in the leaves.
+//!
+//! The tree is loaded into memory as a sorted list of extents when it is used for the
+//! first time, and the nodes are rebuilt from the list when the tree is written back.
+
+use super::{
+    block_ptr::{BlockPtrs, Ext2Bid},
+    fs::Ext2,
+    prelude::*,
+};
+
+/// The magic number of the extent tree nodes.
+const EXTENT_MAGIC: u16 = 0xf30a;
+
+/// The maximum length of an initialized extent.
+const MAX_INIT_LEN: Ext2Bid = 32768;
+
+/// The maximum length of an uninitialized extent.
+const MAX_UNINIT_LEN: Ext2Bid = MAX_INIT_LEN - 1;
+
+/// The maximum depth of the tree.
+const MAX_DEPTH: u16 = 5;
+
+const HEADER_SIZE: usize = core::mem::size_of::<RawExtentHeader>();
+
+const ENTRY_SIZE: usize = core::mem::size_of::<RawExtent>();
+
+/// The maximum number of entries in the root node.
+const ROOT_MAX_ENTRIES: usize = (core::mem::size_of::<BlockPtrs>() - HEADER_SIZE) / ENTRY_SIZE;
+
+/// The maximum number of entries in a node stored in a block.
+const NODE_MAX_ENTRIES: usize = (BLOCK_SIZE - HEADER_SIZE) / ENTRY_SIZE;
+
+/// The extent tree of an inode.
+pub(super) struct ExtentTree {
+    /// The root node stored in the block pointers of the inode.
+    root: BlockPtrs,
+    /// The extents sorted by the file block IDs, which is `None` if the tree is not loaded.
+    extents: Option<Vec<Extent>>,
+    /// The device block IDs of the nodes except the root.
+    node_bids: Vec<Ext2Bid>,
+    is_dirty: bool,
+    fs: Weak<Ext2>,
+}
+
+/// The mapping of the file blocks in the extent tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) enum ExtentMapping {
+    /// The blocks are mapped to the device range.
+    Mapped(Range<Ext2Bid>),
+    /// The blocks are mapped to the device range, but their contents are zeros
+    /// until they are written.
+    Uninit(Range<Ext2Bid>),
+    /// The number of blocks that are not mapped.
+    Unmapped(Ext2Bid),
+}
+
+impl ExtentTree {
+    /// Creates an extent tree whose root node is stored in `root`.
+    ///
+    /// The tree is loaded lazily.
+    pub fn new(root: &BlockPtrs, fs: Weak<Ext2>) -> Self {
+        Self {
+            root: *root,
+            extents: None,
+            node_bids: Vec::new(),
+            is_dirty: false,
+            fs,
+        }
+    }
+
+    /// Initializes `root` as the root node of an empty tree.
+    pub fn init_root(root: &mut BlockPtrs) {
+        *root = BlockPtrs::default();
+        root.as_bytes_mut()[..HEADER_SIZE]
+            .copy_from_slice(RawExtentHeader::new(0, ROOT_MAX_ENTRIES, 0).as_bytes());
+    }
+
+    /// Returns whether the tree has been modified since it was written back.
+    pub fn is_dirty(&self) -> bool {
+        self.is_dirty
+    }
+
+    /// Returns the mapping of the blocks from the beginning of the `range`.
+    ///
+    /// Note that the mapped range may be smaller than the requested range.
+    pub fn read(&mut self, range: Range<Ext2Bid>) -> Result<ExtentMapping> {
+        let extents = self.extents()?;
+        let max_cnt = range.len() as Ext2Bid;
+        let idx = extents.partition_point(|extent| extent.end() <= range.start);
+        let mapping = match extents.get(idx) {
+            Some(extent) if extent.start <= range.start => {
+                let device_start = extent.device_start + (range.start - extent.start);
+                let cnt = (extent.end() - range.start).min(max_cnt);
+                let device_range = device_start..device_start + cnt;
+                if extent.is_uninit {
+                    ExtentMapping::Uninit(device_range)
+                } else {
+                    ExtentMapping::Mapped(device_range)
+                }
+            }
+            Some(extent) => ExtentMapping::Unmapped((extent.start - range.start).min(max_cnt)),
+            None => ExtentMapping::Unmapped(max_cnt),
+        };
+        Ok(mapping)
+    }
+
+    /// Maps the unmapped file blocks starting from `bid` to the `device_range`.
+    pub fn insert(&mut self, bid: Ext2Bid, device_range: Range<Ext2Bid>) -> Result<()> {
+        let extents = self.extents()?;
+        let mut idx = extents.partition_point(|extent| extent.start < bid);
+        let mut bid = bid;
+        let mut device_range = device_range;
+
+        // Merges the blocks into the previous extent if possible.
+        if let Some(prev) = idx.checked_sub(1).map(|prev_idx| &mut extents[prev_idx])
+            && !prev.is_uninit
+            && prev.end() == bid
+            && prev.device_end() == device_range.start
+        {
+            let cnt = (MAX_INIT_LEN - prev.len).min(device_range.len() as Ext2Bid);
+            prev.len += cnt;
+            bid += cnt;
+            device_range.start += cnt;
+        }
+
+        while !device_range.is_empty() {
+            let cnt = (device_range.len() as Ext2Bid).min(MAX_INIT_LEN);
+            extents.insert(
+                idx,
+                Extent {
+                    start: bid,
+                    len: cnt,
+                    device_start: device_range.start,
+                    is_uninit: false,
+                },
+            );
+            idx += 1;
+            bid += cnt;
+            device_range.start += cnt;
+        }
+
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Marks the block `bid` in an uninitialized extent as initialized, since it
+    /// has been written.
+    pub fn set_initialized(&mut self, bid: Ext2Bid) -> Result<()> {
+        let extents = self.extents()?;
+        let idx = extents.partition_point(|extent| extent.end() <= bid);
+        let Some(extent) = extents.get(idx).copied() else {
+            return Ok(());
+        };
+        if !extent.is_uninit || extent.start > bid {
+            return Ok(());
+        }
+
+        // Splits the extent into at most three ones.
+        let offset = bid - extent.start;
+        let mut new_extents = Vec::with_capacity(3);
+        if offset > 0 {
+            new_extents.push(Extent {
+                len: offset,
+                ..extent
+            });
+        }
+        new_extents.push(Extent {
+            start: bid,
+            len: 1,
+            device_start: extent.device_start + offset,
+            is_uninit: false,
+        });
+        if offset + 1 < extent.len {
+            new_extents.push(Extent {
+                start: bid + 1,
+                len: extent.len - offset - 1,
+                device_start: extent.device_start + offset + 1,
+                is_uninit: true,
+            });
+        }
+        let initialized_idx = idx + usize::from(offset > 0);
+        extents.splice(idx..idx + 1, new_extents);
+
+        // Merges the initialized block into the previous extent if possible.
+        if initialized_idx > 0 {
+            let prev = extents[initialized_idx - 1];
+            if !prev.is_uninit
+                && prev.end() == bid
+                && prev.device_end() == extents[initialized_idx].device_start
+                && prev.len < MAX_INIT_LEN
+            {
+                extents[initialized_idx - 1].len += 1;
+                extents.remove(initialized_idx);
+            }
+        }
+
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Removes the mappings of the file blocks from `bid` on, and returns the
+    /// device ranges which are no longer used.
+    pub fn truncate(&mut self, bid: Ext2Bid) -> Result<Vec<Range<Ext2Bid>>> {
+        let extents = self.extents()?;
+        let mut freed_ranges = Vec::new();
+        while let Some(extent) = extents.last_mut() {
+            if extent.end() <= bid {
+                break;
+            }
+
+            if extent.start >= bid {
+                freed_ranges.push(extent.device_start..extent.device_end());
+                extents.pop();
+            } else {
+                let new_len = bid - extent.start;
+                freed_ranges.push(extent.device_start + new_len..extent.device_end());
+                extent.len = new_len;
+            }
+        }
+
+        if !freed_ranges.is_empty() {
+            self.is_dirty = true;
+        }
+        Ok(freed_ranges)
+    }
+
+    /// Rebuilds the nodes of the tree and writes them back.
+    ///
+    /// The node blocks are allocated from the `block_group_idx` group first.
+    /// Returns the new root node if the tree is dirty.
+    pub fn sync(&mut self, block_group_idx: usize) -> Result<Option<BlockPtrs>> {
+        if !self.is_dirty {
+            return Ok(None);
+        }
+        let fs = self.fs.upgrade().unwrap();
+        let extents = self.extents.as_ref().unwrap();
+
+        // Calculates the number of nodes in each level, from the leaves to the root.
+        let mut level_sizes = Vec::new();
+        let mut nentries = extents.len();
+        while nentries > ROOT_MAX_ENTRIES {
+            nentries = nentries.div_ceil(NODE_MAX_ENTRIES);
+            level_sizes.push(nentries);
+        }
+        if level_sizes.len() > MAX_DEPTH as usize {
+            return_errno_with_message!(Errno::EFBIG, "too many extents");
+        }
+
+        // Allocates or frees the node blocks.
+        let nnodes: usize = level_sizes.iter().sum();
+        while self.node_bids.len() < nnodes {
+            let cnt = (nnodes - self.node_bids.len()) as Ext2Bid;
+            let bids = fs
+                .alloc_blocks(block_group_idx, cnt)
+                .ok_or_else(|| Error::with_message(Errno::ENOSPC, "no space for extent nodes"))?;
+            self.node_bids.extend(bids);
+        }
+        for bid in self.node_bids.split_off(nnodes) {
+            fs.free_blocks(bid..bid + 1)?;
+        }
+
+        // Builds the nodes from the leaves to the root.
+        let mut entries: Vec<(Ext2Bid, [u8; ENTRY_SIZE])> = extents
+            .iter()
+            .map(|extent| (extent.start, RawExtent::from(extent).to_array()))
+            .collect();
+        let mut node_bids = self.node_bids.iter();
+        let mut bio_waiter = BioWaiter::new();
+        for (depth, level_size) in level_sizes.iter().enumerate() {
+            let mut upper_entries = Vec::with_capacity(*level_size);
+            for chunk in entries.chunks(NODE_MAX_ENTRIES) {
+                let bid = *node_bids.next().unwrap();
+                let mut buf = vec![0u8; BLOCK_SIZE];
+                write_node(&mut buf, chunk, NODE_MAX_ENTRIES, depth as u16);
+                let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+                frame.write_bytes(0, &buf)?;
+                bio_waiter.concat(fs.write_metadata_block_async(bid, &frame)?);
+
+                let index = RawExtentIndex::new(chunk[0].0, bid);
+                upper_entries.push((chunk[0].0, index.to_array()));
+            }
+            entries = upper_entries;
+        }
+        bio_waiter.wait().ok_or_else(|| {
+            Error::with_message(Errno::EIO, "failed to write the extent tree nodes")
+        })?;
+
+        let mut root = BlockPtrs::default();
+        write_node(
+            root.as_bytes_mut(),
+            &entries,
+            ROOT_MAX_ENTRIES,
+            level_sizes.len() as u16,
+        );
+        self.root = root;
+        self.is_dirty = false;
+        Ok(Some(root))
+    }
+
+    /// Returns the extents, the tree is loaded if necessary.
+    fn extents(&mut self) -> Result<&mut Vec<Extent>> {
+        if self.extents.is_none() {
+            let fs = self.fs.upgrade().unwrap();
+            let mut extents = Vec::new();
+            load_extents(
+                self.root.as_bytes(),
+                &|bid, frame| fs.read_block(bid, frame),
+                &mut extents,
+                &mut self.node_bids,
+            )?;
+            self.extents = Some(extents);
+        }
+        Ok(self.extents.as_mut().unwrap())
+    }
+}
+
+impl Debug for ExtentTree {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ExtentTree")
+            .field("extents", &self.extents)
+            .field("node_bids", &self.node_bids)
+            .field("is_dirty", &self.is_dirty)
+            .finish()
+    }
+}
+
+/// An extent maps the consecutive file blocks to the consecutive device blocks.
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Extent {
+    /// The first file block ID.
+    pub start: Ext2Bid,
+    /// The number of blocks.
+    pub len: Ext2Bid,
+    /// The first device block ID.
+    pub device_start: Ext2Bid,
+    /// Whether the blocks are allocated but not initialized.
+    pub is_uninit: bool,
+}
+
+impl Extent {
+    pub fn end(&self) -> Ext2Bid {
+        self.start + self.len
+    }
+
+    pub fn device_end(&self) -> Ext2Bid {
+        self.device_start + self.len
+    }
+}
+
+/// Loads the extents of the tree whose root node is `root` into `extents`,
+/// and the device block IDs of the other nodes into `node_bids`.
+///
+/// The nodes are read by the `read_block`.
+pub(super) fn load_extents(
+    root: &[u8],
+    read_block: &dyn Fn(Ext2Bid, &VmFrame) -> Result<()>,
+    extents: &mut Vec<Extent>,
+    node_bids: &mut Vec<Ext2Bid>,
+) -> Result<()> {
+    load_node(root, None, read_block, extents, node_bids)
+}
+
+fn load_node(
+    node: &[u8],
+    expected_depth: Option<u16>,
+    read_block: &dyn Fn(Ext2Bid, &VmFrame) -> Result<()>,
+    extents: &mut Vec<Extent>,
+    node_bids: &mut Vec<Ext2Bid>,
+) -> Result<()> {
+    let header = RawExtentHeader::from_bytes(&node[..HEADER_SIZE]);
+    if header.magic != EXTENT_MAGIC
+        || header.entries > header.max
+        || HEADER_SIZE + (header.max as usize) * ENTRY_SIZE > node.len()
+        || header.depth > MAX_DEPTH
+        || expected_depth.is_some_and(|depth| depth != header.depth)
+    {
+        return_errno_with_message!(Errno::EINVAL, "invalid extent tree node");
+    }
+
+    for idx in 0..header.entries as usize {
+        let entry = &node[HEADER_SIZE + idx * ENTRY_SIZE..HEADER_SIZE + (idx + 1) * ENTRY_SIZE];
+        if header.depth > 0 {
+            let index = RawExtentIndex::from_bytes(entry);
+            if index.leaf_high != 0 {
+                return_errno_with_message!(Errno::EINVAL, "the extent node is out of range");
+            }
+
+            let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+            read_block(index.leaf_low, &frame)?;
+            let mut buf = vec![0u8; BLOCK_SIZE];
+            frame.read_bytes(0, &mut buf)?;
+            node_bids.push(index.leaf_low);
+            load_node(&buf, Some(header.depth - 1), read_block, extents, node_bids)?;
+        } else {
+            let raw_extent = RawExtent::from_bytes(entry);
+            if raw_extent.start_high != 0 {
+                return_errno_with_message!(Errno::EINVAL, "the extent is out of range");
+            }
+            let extent = Extent::try_from(raw_extent)?;
+            if extents.last().is_some_and(|last| last.end() > extent.start) {
+                return_errno_with_message!(Errno::EINVAL, "the extents overlap");
+            }
+            extents.push(extent);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a node with the `entries` into `buf`.
+fn write_node(buf: &mut [u8], entries: &[(Ext2Bid, [u8; ENTRY_SIZE])], max: usize, depth: u16) {
+    buf[..HEADER_SIZE].copy_from_slice(RawExtentHeader::new(entries.len(), max, depth).as_bytes());
+    for (idx, (_, entry)) in entries.iter().enumerate() {
+        let offset = HEADER_SIZE + idx * ENTRY_SIZE;
+        buf[offset..offset + ENTRY_SIZE].copy_from_slice(entry);
+    }
+}
+
+/// The header of the extent tree nodes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtentHeader {
+    magic: u16,
+    /// Number of valid entries.
+    entries: u16,
+    /// Capacity of entries.
+    max: u16,
+    /// The depth of the node, zero for the leaves.
+    depth: u16,
+    generation: u32,
+}
+
+impl RawExtentHeader {
+    fn new(entries: usize, max: usize, depth: u16) -> Self {
+        Self {
+            magic: EXTENT_MAGIC,
+            entries: entries as u16,
+            max: max as u16,
+            depth,
+            generation: 0,
+        }
+    }
+}
+
+/// The index entry in the internal nodes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtentIndex {
+    /// The first file block ID covered by the lower node.
+    block: u32,
+    /// The lower 32 bits of the lower node's device block ID.
+    leaf_low: u32,
+    /// The upper 16 bits of the lower node's device block ID.
+    leaf_high: u16,
+    unused: u16,
+}
+
+impl RawExtentIndex {
+    fn new(block: Ext2Bid, leaf: Ext2Bid) -> Self {
+        Self {
+            block,
+            leaf_low: leaf,
+            leaf_high: 0,
+            unused: 0,
+        }
+    }
+
+    fn to_array(self) -> [u8; ENTRY_SIZE] {
+        self.as_bytes().try_into().unwrap()
+    }
+}
+
+/// The extent entry in the leaves.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawExtent {
+    /// The first file block ID.
+    block: u32,
+    /// The number of blocks, which is greater than `MAX_INIT_LEN` if uninitialized.
+    len: u16,
+    /// The upper 16 bits of the first device block ID.
+    start_high: u16,
+    /// The lower 32 bits of the first device block ID.
+    start_low: u32,
+}
+
+impl RawExtent {
+    fn to_array(self) -> [u8; ENTRY_SIZE] {
+        self.as_bytes().try_into().unwrap()
+    }
+}
+
+impl TryFrom<RawExtent> for Extent {
+    type Error = crate::error::Error;
+
+    fn try_from(raw_extent: RawExtent) -> Result<Self> {
+        let raw_len = raw_extent.len as Ext2Bid;
+        let (len, is_uninit) = if raw_len > MAX_INIT_LEN {
+            (raw_len - MAX_INIT_LEN, true)
+        } else {
+            (raw_len, false)
+        };
+        if len == 0 {
+            return_errno_with_message!(Errno::EINVAL, "invalid extent length");
+        }
+        Ok(Self {
+            start: raw_extent.block,
+            len,
+            device_start: raw_extent.start_low,
+            is_uninit,
+        })
+    }
+}
+
+impl From<&Extent> for RawExtent {
+    fn from(extent: &Extent) -> Self {
+        debug_assert!(
+            extent.len
+                <= if extent.is_uninit {
+                    MAX_UNINIT_LEN
+                } else {
+                    MAX_INIT_LEN
+                }
+        );
+        Self {
+            block: extent.start,
+            len: if extent.is_uninit {
+                (extent.len + MAX_INIT_LEN) as u16
+            } else {
+                extent.len as u16
+            },
+            start_high: 0,
+            start_low: extent.device_start,
+        }
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+
+    /// Builds a node header, whose layout is `ext4_extent_header` in Linux.
+    fn header(entries: u16, max: u16, depth: u16) -> Vec<u8> {
+        [0xf30au16, entries, max, depth]
+            .iter()
+            .flat_map(|field| field.to_le_bytes())
+            .chain(0u32.to_le_bytes())
+            .collect()
+    }
+
+    /// Builds an index entry, whose layout is `ext4_extent_idx` in Linux.
+    fn index(block: u32, leaf: u32) -> Vec<u8> {
+        [block.to_le_bytes(), leaf.to_le_bytes(), [0; 4]].concat()
+    }
+
+    /// Builds an extent, whose layout is `ext4_extent` in Linux.
+    fn extent(block: u32, len: u16, start: u32) -> Vec<u8> {
+        [
+            &block.to_le_bytes()[..],
+            &len.to_le_bytes()[..],
+            &0u16.to_le_bytes()[..],
+            &start.to_le_bytes()[..],
+        ]
+        .concat()
+    }
+
+    /// Builds a node of `size` bytes.
+    fn node(header: Vec<u8>, entries: &[Vec<u8>], size: usize) -> Vec<u8> {
+        let mut node = [header, entries.concat()].concat();
+        node.resize(size, 0);
+        node
+    }
+
+    /// Loads the tree whose root is `root` and whose leaf in the block 100 is `leaf`.
+    fn load(root: &[u8], leaf: &[u8]) -> Result<(Vec<Extent>, Vec<Ext2Bid>)> {
+        let read_block = |bid: Ext2Bid, frame: &VmFrame| -> Result<()> {
+            assert_eq!(bid, 100);
+            frame.write_bytes(0, leaf)?;
+            Ok(())
+        };
+        let mut extents = Vec::new();
+        let mut node_bids = Vec::new();
+        load_extents(root, &read_block, &mut extents, &mut node_bids)?;
+        Ok((extents, node_bids))
+    }
+
+    /// A tree of depth one, whose only leaf is in the block 100. The file blocks
+    /// `4..6` are uninitialized and `6..10` are a hole.
+    ///
+    /// The device blocks of the file blocks `0..6` are consecutive.
+    fn two_level_tree() -> (Vec<u8>, Vec<u8>) {
+        let root = node(header(1, 4, 1), &[index(0, 100)], 60);
+        let leaf = node(
+            header(3, 340, 0),
+            &[
+                extent(0, 4, 1996),
+                extent(4, 0x8000 + 2, 2000),
+                extent(10, 3, 3000),
+            ],
+            BLOCK_SIZE,
+        );
+        (root, leaf)
+    }
+
+    fn tree_of(extents: Vec<Extent>) -> ExtentTree {
+        ExtentTree {
+            root: BlockPtrs::default(),
+            extents: Some(extents),
+            node_bids: Vec::new(),
+            is_dirty: false,
+            fs: Weak::new(),
+        }
+    }
+
+    #[ktest]
+    fn layout() {
+        assert_eq!(HEADER_SIZE, 12);
+        assert_eq!(ENTRY_SIZE, 12);
+        assert_eq!(ROOT_MAX_ENTRIES, 4);
+    }
+
+    #[ktest]
+    fn load_two_level_tree() {
+        let (root, leaf) = two_level_tree();
+        let (extents, node_bids) = load(&root, &leaf).unwrap();
+        assert_eq!(node_bids, [100]);
+        let extents: Vec<_> = extents
+            .iter()
+            .map(|extent| {
+                (
+                    extent.start,
+                    extent.len,
+                    extent.device_start,
+                    extent.is_uninit,
+                )
+            })
+            .collect();
+        assert_eq!(
+            extents,
+            [
+                (0, 4, 1996, false),
+                (4, 2, 2000, true),
+                (10, 3, 3000, false)
+            ]
+        );
+    }
+
+    #[ktest]
+    fn read_mappings() {
+        let (root, leaf) = two_level_tree();
+        let mut tree = tree_of(load(&root, &leaf).unwrap().0);
+        assert_eq!(tree.read(1..3).unwrap(), ExtentMapping::Mapped(1997..1999));
+        assert_eq!(tree.read(2..20).unwrap(), ExtentMapping::Mapped(1998..2000));
+        assert_eq!(tree.read(5..20).unwrap(), ExtentMapping::Uninit(2001..2002));
+        assert_eq!(tree.read(6..20).unwrap(), ExtentMapping::Unmapped(4));
+        assert_eq!(tree.read(7..8).unwrap(), ExtentMapping::Unmapped(1));
+        assert_eq!(
+            tree.read(12..20).unwrap(),
+            ExtentMapping::Mapped(3002..3003)
+        );
+        assert_eq!(tree.read(13..20).unwrap(), ExtentMapping::Unmapped(7));
+    }
+
+    #[ktest]
+    fn modify_extents() {
+        let (root, leaf) = two_level_tree();
+        let mut tree = tree_of(load(&root, &leaf).unwrap().0);
+
+        // Merged into the previous extent.
+        tree.insert(13, 3003..3005).unwrap();
+        assert_eq!(
+            tree.read(10..20).unwrap(),
+            ExtentMapping::Mapped(3000..3005)
+        );
+
+        // Split into an initialized and an uninitialized extents, and the first
+        // one is merged into the previous extent.
+        tree.set_initialized(4).unwrap();
+        assert_eq!(tree.read(0..20).unwrap(), ExtentMapping::Mapped(1996..2001));
+        assert_eq!(tree.read(4..20).unwrap(), ExtentMapping::Mapped(2000..2001));
+        assert_eq!(tree.read(5..20).unwrap(), ExtentMapping::Uninit(2001..2002));
+
+        let freed_ranges = tree.truncate(5).unwrap();
+        assert_eq!(freed_ranges, [3000..3005, 2001..2002]);
+        assert_eq!(tree.read(4..20).unwrap(), ExtentMapping::Mapped(2000..2001));
+        assert_eq!(tree.read(5..20).unwrap(), ExtentMapping::Unmapped(15));
+        assert!(tree.is_dirty());
+    }
+
+    #[ktest]
+    fn reject_invalid_nodes() {
+        let (root, leaf) = two_level_tree();
+
+        let mut bad_magic = root.clone();
+        bad_magic[0] = 0;
+        assert!(load(&bad_magic, &leaf).is_err());
+
+        let too_many_entries = node(header(5, 4, 0), &[], 60);
+        assert!(load(&too_many_entries, &leaf).is_err());
+
+        let bad_depth = node(header(1, 4, 2), &[index(0, 100)], 60);
+        assert!(load(&bad_depth, &leaf).is_err());
+
+        let overlapped = node(
+            header(2, 340, 0),
+            &[extent(0, 4, 1000), extent(3, 1, 2000)],
+            BLOCK_SIZE,
+        );
+        assert!(load(&root, &overlapped).is_err());
+
+        let zero_len = node(header(1, 4, 0), &[extent(0, 0, 1000)], 60);
+        assert!(load(&zero_len, &leaf).is_err());
+    }
+}
@@ -1,16 +1,31 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use aster_frame::sync::WaitQueue;
+
 use super::{
     block_group::{BlockGroup, RawGroupDescriptor},
     block_ptr::Ext2Bid,
     inode::{FilePerm, FileType, Inode, InodeDesc, RawInode},
+    journal::Journal,
     prelude::*,
-    super_block::{RawSuperBlock, SuperBlock, SUPER_BLOCK_OFFSET},
+    super_block::{
+        FeatureCompatSet, FeatureInCompatSet, RawSuperBlock, SuperBlock, SUPER_BLOCK_OFFSET,
+    },
+};
+use crate::{
+    fs::utils::FileSystem,
+    thread::{
+        kernel_thread::{KernelThreadExt, ThreadOptions},
+        Thread,
+    },
 };
 
 /// The root inode number.
 const ROOT_INO: u32 = 2;
 
+/// The interval to commit the journal, which is the default one of Ext4.
+const JOURNAL_COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+
 /// The Ext2 filesystem.
 #[derive(Debug)]
 pub struct Ext2 {
@@ -21,7 +36,12 @@ pub struct Ext2 {
     blocks_per_group: Ext2Bid,
     inode_size: usize,
     block_size: usize,
+    group_descriptor_size: usize,
     group_descriptors_segment: VmSegment,
+    journal: Option<Journal>,
+    /// Whether the Ext2 is read-only since it has the features not supported for
+    /// writing (see `SuperBlock::is_read_only`).
+    is_read_only: bool,
     self_ref: Weak<Self>,
 }
 
@@ -30,15 +50,18 @@ impl Ext2 {
     pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Self>> {
         // Load the superblock
         // TODO: if the main superblock is corrupted, should we load the backup?
-        let super_block = {
+        let load_super_block = |block_device: &dyn BlockDevice| -> Result<SuperBlock> {
             let raw_super_block = block_device.read_val::<RawSuperBlock>(SUPER_BLOCK_OFFSET)?;
-            SuperBlock::try_from(raw_super_block)?
+            SuperBlock::try_from(raw_super_block)
         };
+        let mut super_block = load_super_block(block_device.as_ref())?;
         assert!(super_block.block_size() == BLOCK_SIZE);
 
-        let group_descriptors_segment = {
+        let load_group_descriptors = |block_device: &dyn BlockDevice,
+                                      super_block: &SuperBlock|
+         -> Result<VmSegment> {
             let npages = ((super_block.block_groups_count() as usize)
-                * core::mem::size_of::<RawGroupDescriptor>())
+                * super_block.group_descriptor_size())
             .div_ceil(BLOCK_SIZE);
             let segment = VmAllocOptions::new(npages)
                 .uninit(true)
@@ -50,7 +73,42 @@ impl Ext2 {
                     return Err(Error::from(err_status));
                 }
             }
-            segment
+            Ok(segment)
+        };
+        let mut group_descriptors_segment =
+            load_group_descriptors(block_device.as_ref(), &super_block)?;
+
+        // Open the journal before loading the other metadata, which may be
+        // updated by replaying the journal.
+        //
+        // A read-only Ext2 is never written, so its journal is ignored if it is clean.
+        let is_read_only = super_block.is_read_only();
+        if is_read_only && super_block.needs_recovery() {
+            return_errno_with_message!(
+                Errno::EROFS,
+                "the journal cannot be replayed on a read-only filesystem"
+            );
+        }
+        let journal = if !is_read_only
+            && super_block
+                .feature_compat()
+                .contains(FeatureCompatSet::HAS_JOURNAL)
+        {
+            let journal =
+                Self::open_journal(&block_device, &super_block, &group_descriptors_segment)?;
+            super_block = load_super_block(block_device.as_ref())?;
+            group_descriptors_segment =
+                load_group_descriptors(block_device.as_ref(), &super_block)?;
+
+            // Mark the journal as needing recovery while the filesystem is in use.
+            super_block.set_needs_recovery(true);
+            block_device.write_bytes(
+                SUPER_BLOCK_OFFSET,
+                RawSuperBlock::from(&super_block).as_bytes(),
+            )?;
+            Some(journal)
+        } else {
+            None
         };
 
         // Load the block groups information
@@ -78,6 +136,7 @@ impl Ext2 {
             blocks_per_group: super_block.blocks_per_group(),
             inode_size: super_block.inode_size(),
             block_size: super_block.block_size(),
+            group_descriptor_size: super_block.group_descriptor_size(),
             block_groups: load_block_groups(
                 weak_ref.clone(),
                 block_device.as_ref(),
@@ -87,11 +146,75 @@ impl Ext2 {
             block_device,
             super_block: RwMutex::new(Dirty::new(super_block)),
             group_descriptors_segment,
+            journal,
+            is_read_only,
             self_ref: weak_ref.clone(),
         });
+        if ext2.journal.is_some() {
+            Self::spawn_journal_committer(Arc::downgrade(&ext2));
+        }
         Ok(ext2)
     }
 
+    /// Spawns a kernel thread to commit the journal periodically, until the Ext2
+    /// is dropped.
+    ///
+    /// The dirty metadata is written to the journal before each commit, so that
+    /// the committed transaction is consistent.
+    fn spawn_journal_committer(fs: Weak<Self>) {
+        let task_fn = move || {
+            let wait_queue = WaitQueue::new();
+            loop {
+                wait_queue.wait_until_or_timeout(|| None::<()>, &JOURNAL_COMMIT_INTERVAL);
+                let Some(fs) = fs.upgrade() else {
+                    return;
+                };
+                if let Err(err) = fs.sync() {
+                    warn!("ext2: failed to commit the journal: {:?}", err);
+                }
+            }
+        };
+        Thread::spawn_kernel_thread(ThreadOptions::new(task_fn));
+    }
+
+    /// Opens the journal stored in the journal inode, which is read directly
+    /// from the inode table.
+    fn open_journal(
+        block_device: &Arc<dyn BlockDevice>,
+        super_block: &SuperBlock,
+        group_descriptors_segment: &VmSegment,
+    ) -> Result<Journal> {
+        let journal_ino = super_block.journal_ino();
+        if super_block
+            .feature_incompat()
+            .contains(FeatureInCompatSet::JOURNAL_DEV)
+            || journal_ino == 0
+        {
+            return_errno_with_message!(Errno::EINVAL, "not supported external journal");
+        }
+
+        let block_group_idx = ((journal_ino - 1) / super_block.inodes_per_group()) as usize;
+        let inode_idx = ((journal_ino - 1) % super_block.inodes_per_group()) as usize;
+        if block_group_idx >= super_block.block_groups_count() as usize {
+            return_errno_with_message!(Errno::EINVAL, "invalid journal inode");
+        }
+        let raw_descriptor = group_descriptors_segment.read_val::<RawGroupDescriptor>(
+            block_group_idx * super_block.group_descriptor_size(),
+        )?;
+        let offset = (raw_descriptor.inode_table as usize) * BLOCK_SIZE
+            + inode_idx * super_block.inode_size();
+
+        let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        match block_device.read_block_sync(Bid::from_offset(offset), &frame)? {
+            BioStatus::Complete => (),
+            err_status => {
+                return Err(Error::from(err_status));
+            }
+        }
+        let journal_inode = frame.read_val::<RawInode>(offset % BLOCK_SIZE)?;
+        Journal::open(block_device.clone(), &journal_inode)
+    }
+
     /// Returns the block device.
     pub fn block_device(&self) -> &dyn BlockDevice {
         self.block_device.as_ref()
@@ -144,7 +267,16 @@ impl Ext2 {
         let (block_group_idx, ino) =
             self.alloc_ino(dir_block_group_idx, file_type == FileType::Dir)?;
         let inode = {
-            let inode_desc = InodeDesc::new(file_type, file_perm);
+            let mut inode_desc = InodeDesc::new(file_type, file_perm);
+            if matches!(file_type, FileType::File | FileType::Dir)
+                && self
+                    .super_block
+                    .read()
+                    .feature_incompat()
+                    .contains(FeatureInCompatSet::EXTENTS)
+            {
+                inode_desc.enable_extents();
+            }
             Inode::new(ino, block_group_idx, inode_desc, self.self_ref.clone())
         };
         let block_group = &self.block_groups[block_group_idx];
@@ -203,7 +335,7 @@ impl Ext2 {
         block_group_idx: usize,
         raw_descriptor: &RawGroupDescriptor,
     ) -> Result<()> {
-        let offset = block_group_idx * core::mem::size_of::<RawGroupDescriptor>();
+        let offset = block_group_idx * self.group_descriptor_size;
         self.group_descriptors_segment
             .write_val(offset, raw_descriptor)?;
         Ok(())
@@ -221,6 +353,8 @@ impl Ext2 {
         mut block_group_idx: usize,
         count: Ext2Bid,
     ) -> Option<Range<Ext2Bid>> {
+        // The running transaction may have been committed since the last allocation.
+        self.release_committed_blocks();
         if count > self.super_block.read().free_blocks_count() {
             return None;
         }
@@ -270,7 +404,36 @@ impl Ext2 {
     }
 
     /// Frees a range of blocks.
+    ///
+    /// If the Ext2 has a journal, the blocks are not reused until the running
+    /// transaction of the journal is committed.
     pub(super) fn free_blocks(&self, range: Range<Ext2Bid>) -> Result<()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+        self.block_group_of_bid(range.end - 1)?;
+        if let Some(journal) = self.journal.as_ref() {
+            journal.defer_free(range);
+            return Ok(());
+        }
+        self.release_blocks(range)
+    }
+
+    /// Returns the blocks freed in the committed transactions of the journal to
+    /// the block groups.
+    fn release_committed_blocks(&self) {
+        let Some(journal) = self.journal.as_ref() else {
+            return;
+        };
+        for range in journal.take_releasable_blocks() {
+            if let Err(err) = self.release_blocks(range.clone()) {
+                warn!("ext2: failed to release blocks {:?}: {:?}", range, err);
+            }
+        }
+    }
+
+    /// Returns a range of blocks to the block groups, after which they can be reused.
+    fn release_blocks(&self, range: Range<Ext2Bid>) -> Result<()> {
         let mut current_range = range.clone();
         while !current_range.is_empty() {
             let (_, block_group) = self.block_group_of_bid(current_range.start)?;
@@ -293,6 +456,10 @@ impl Ext2 {
 
     /// Reads contiguous blocks starting from the `bid` synchronously.
     pub(super) fn read_blocks(&self, bid: Ext2Bid, segment: &VmSegment) -> Result<()> {
+        if let Some(journal) = self.journal.as_ref() {
+            return journal.read_blocks(bid, segment);
+        }
+
         let status = self
             .block_device
             .read_blocks_sync(Bid::new(bid as u64), segment)?;
@@ -304,6 +471,10 @@ impl Ext2 {
 
     /// Reads one block indicated by the `bid` synchronously.
     pub(super) fn read_block(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<()> {
+        if let Some(journal) = self.journal.as_ref() {
+            return journal.read_block(bid, frame);
+        }
+
         let status = self
             .block_device
             .read_block_sync(Bid::new(bid as u64), frame)?;
@@ -315,12 +486,18 @@ impl Ext2 {
 
     /// Reads one block indicated by the `bid` asynchronously.
     pub(super) fn read_block_async(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<BioWaiter> {
+        if let Some(journal) = self.journal.as_ref() {
+            return journal.read_block_async(bid, frame);
+        }
+
         let waiter = self.block_device.read_block(Bid::new(bid as u64), frame)?;
         Ok(waiter)
     }
 
     /// Writes contiguous blocks starting from the `bid` synchronously.
     pub(super) fn write_blocks(&self, bid: Ext2Bid, segment: &VmSegment) -> Result<()> {
+        self.check_writable()?;
+        self.forget_metadata(bid..bid + segment.nframes() as Ext2Bid);
         let status = self
             .block_device
             .write_blocks_sync(Bid::new(bid as u64), segment)?;
@@ -332,6 +509,8 @@ impl Ext2 {
 
     /// Writes one block indicated by the `bid` synchronously.
     pub(super) fn write_block(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<()> {
+        self.check_writable()?;
+        self.forget_metadata(bid..bid + 1);
         let status = self
             .block_device
             .write_block_sync(Bid::new(bid as u64), frame)?;
@@ -343,10 +522,98 @@ impl Ext2 {
 
     /// Writes one block indicated by the `bid` asynchronously.
     pub(super) fn write_block_async(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<BioWaiter> {
+        self.check_writable()?;
+        self.forget_metadata(bid..bid + 1);
         let waiter = self.block_device.write_block(Bid::new(bid as u64), frame)?;
         Ok(waiter)
     }
 
+    /// Writes one metadata block indicated by the `bid` asynchronously.
+    ///
+    /// If the Ext2 has a journal, the block is written to the running transaction
+    /// of the journal, and will be written in place when the transaction is committed.
+    pub(super) fn write_metadata_block_async(
+        &self,
+        bid: Ext2Bid,
+        frame: &VmFrame,
+    ) -> Result<BioWaiter> {
+        self.check_writable()?;
+        if let Some(journal) = self.journal.as_ref() {
+            journal.write_block(bid, frame)?;
+            return Ok(BioWaiter::new());
+        }
+
+        self.write_block_async(bid, frame)
+    }
+
+    /// Writes contiguous metadata blocks starting from the `bid` asynchronously.
+    pub(super) fn write_metadata_blocks_async(
+        &self,
+        bid: Ext2Bid,
+        segment: &VmSegment,
+    ) -> Result<BioWaiter> {
+        self.check_writable()?;
+        if let Some(journal) = self.journal.as_ref() {
+            let mut buf = vec![0u8; segment.nbytes()];
+            segment.read_bytes(0, &mut buf)?;
+            journal.write_bytes(bid as usize * BLOCK_SIZE, &buf)?;
+            return Ok(BioWaiter::new());
+        }
+
+        let waiter = self
+            .block_device
+            .write_blocks(Bid::new(bid as u64), segment)?;
+        Ok(waiter)
+    }
+
+    /// Writes the metadata bytes at the `offset` of the block device asynchronously.
+    pub(super) fn write_metadata_bytes_async(
+        &self,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<BioWaiter> {
+        self.check_writable()?;
+        if let Some(journal) = self.journal.as_ref() {
+            journal.write_bytes(offset, buf)?;
+            return Ok(BioWaiter::new());
+        }
+
+        let waiter = self.block_device.write_bytes_async(offset, buf)?;
+        Ok(waiter)
+    }
+
+    /// Returns whether the Ext2 is read-only, since it has the features not supported
+    /// for writing, e.g., the metadata checksums.
+    pub fn is_read_only(&self) -> bool {
+        self.is_read_only
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_read_only {
+            return_errno_with_message!(Errno::EROFS, "the filesystem is read-only");
+        }
+        Ok(())
+    }
+
+    /// Removes the blocks in the `range` from the running transaction of the journal,
+    /// since they are written in place as file data.
+    fn forget_metadata(&self, range: Range<Ext2Bid>) {
+        if let Some(journal) = self.journal.as_ref() {
+            journal.forget(range);
+        }
+    }
+
+    /// Commits the metadata written to the journal.
+    ///
+    /// It does nothing if the Ext2 has no journal.
+    pub fn commit_journal(&self) -> Result<()> {
+        if let Some(journal) = self.journal.as_ref() {
+            journal.commit()?;
+        }
+        self.release_committed_blocks();
+        Ok(())
+    }
+
     /// Writes back the metadata to the block device.
     pub fn sync_metadata(&self) -> Result<()> {
         // If the superblock is clean, the block groups must be clean.
@@ -364,11 +631,10 @@ impl Ext2 {
         let mut bio_waiter = BioWaiter::new();
         let raw_super_block = RawSuperBlock::from((*super_block).deref());
         bio_waiter.concat(
-            self.block_device
-                .write_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?,
+            self.write_metadata_bytes_async(SUPER_BLOCK_OFFSET, raw_super_block.as_bytes())?,
         );
-        bio_waiter.concat(self.block_device.write_blocks(
-            super_block.group_descriptors_bid(0),
+        bio_waiter.concat(self.write_metadata_blocks_async(
+            super_block.group_descriptors_bid(0).to_raw() as Ext2Bid,
             &self.group_descriptors_segment,
         )?);
         bio_waiter
@@ -382,12 +648,12 @@ impl Ext2 {
             if super_block.is_backup_group(idx as usize) {
                 let mut bio_waiter = BioWaiter::new();
                 raw_super_block_backup.block_group_idx = idx as u16;
-                bio_waiter.concat(self.block_device.write_bytes_async(
+                bio_waiter.concat(self.write_metadata_bytes_async(
                     super_block.bid(idx as usize).to_offset(),
                     raw_super_block_backup.as_bytes(),
                 )?);
-                bio_waiter.concat(self.block_device.write_blocks(
-                    super_block.group_descriptors_bid(idx as usize),
+                bio_waiter.concat(self.write_metadata_blocks_async(
+                    super_block.group_descriptors_bid(idx as usize).to_raw() as Ext2Bid,
                     &self.group_descriptors_segment,
                 )?);
                 bio_waiter.wait().ok_or_else(|| {
@@ -0,0 +1,824 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The hashed directory index, which is known as the htree.
+//!
+//! The entries of an indexed directory are distributed into the leaf blocks by the
+//! hashes of their names, and the index maps the hash ranges to the leaf blocks.
+//! The first block of the directory is the root of the index, it starts with the
+//! "." and ".." entries, the latter occupies the rest of the block so that the index
+//! is invisible to the implementations which do not support it. The root may point to
+//! the internal index nodes, which look like blocks with a single unused entry.
+//!
+//! The index is at most two levels, and the leaf blocks are never merged after removals,
+//! which keeps the same limitations as Linux without the `largedir` feature.
+
+use super::{
+    dir::{DirEntry, DirEntryReader, DirEntryWriter},
+    prelude::*,
+    super_block::SuperBlock,
+};
+
+/// The offset of the index info in the root block, which follows the "." and ".." entries.
+const ROOT_INFO_OFFSET: usize = 24;
+
+/// The offset of the index entries in the root block.
+const ROOT_ENTRIES_OFFSET: usize = ROOT_INFO_OFFSET + core::mem::size_of::<RawDxRootInfo>();
+
+/// The offset of the index entries in the internal node, which follows the fake entry.
+const NODE_ENTRIES_OFFSET: usize = 8;
+
+const DX_ENTRY_SIZE: usize = core::mem::size_of::<RawDxEntry>();
+
+/// The maximum number of the levels of the internal nodes.
+const MAX_INDIRECT_LEVELS: u8 = 1;
+
+/// The flag of the unsupported features in the `unused_flags` of the root.
+const DX_FLAG_INCOMPAT: u8 = 1 << 0;
+
+/// The hash of the end of the directory, which can not be used by the names.
+const HTREE_EOF_32BIT: u32 = 0x7fff_ffff;
+
+/// The hashed index of a directory, which is stored in the page cache of the directory.
+pub(super) struct HTree<'a> {
+    page_cache: &'a PageCache,
+    hasher: DirHasher,
+}
+
+impl<'a> HTree<'a> {
+    /// Constructs the index of the directory with the given page cache.
+    pub fn new(page_cache: &'a PageCache, super_block: &SuperBlock) -> Self {
+        Self {
+            page_cache,
+            hasher: DirHasher::new(super_block),
+        }
+    }
+
+    /// Builds the index for a directory with a single block.
+    ///
+    /// The entries except "." and ".." are moved into a new leaf block, and the first
+    /// block becomes the root of the index.
+    pub fn build(&self) -> Result<()> {
+        if self.page_cache.pages().size() != BLOCK_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "the directory has more than one block");
+        }
+
+        let mut entries: Vec<DirEntry> = DirEntryReader::new(self.page_cache, 0)
+            .map(|(_, entry)| entry)
+            .collect();
+        if entries.len() < 2 || entries[0].name() != "." || entries[1].name() != ".." {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid dot entries");
+        }
+        let leaf_entries = entries.split_off(2);
+
+        let leaf_bid = self.append_block()?;
+        let mut writer = DirEntryWriter::new(self.page_cache, 0);
+        writer.write_block(leaf_bid as usize * BLOCK_SIZE, &leaf_entries)?;
+
+        // Writes the root, the ".." entry covers the index.
+        let pages = self.page_cache.pages();
+        pages.write_bytes(0, &vec![0u8; BLOCK_SIZE])?;
+        writer.write_block(0, &entries)?;
+        pages.write_val(
+            ROOT_INFO_OFFSET,
+            &RawDxRootInfo {
+                reserved_zero: 0,
+                hash_version: self.hasher.default_version,
+                info_len: core::mem::size_of::<RawDxRootInfo>() as u8,
+                indirect_levels: 0,
+                unused_flags: 0,
+            },
+        )?;
+        let root = DxFrame {
+            entries_offset: ROOT_ENTRIES_OFFSET,
+            count: 1,
+            limit: dx_limit(ROOT_ENTRIES_OFFSET),
+            at: 0,
+        };
+        root.write_count_limit(self.page_cache)?;
+        root.write_entry(self.page_cache, 0, 0, leaf_bid)?;
+        Ok(())
+    }
+
+    /// Looks up the entry with the `name` through the index.
+    ///
+    /// Returns the offset and the entry if found.
+    pub fn lookup(&self, name: &str) -> Result<Option<(usize, DirEntry)>> {
+        let mut path = self.probe(name)?;
+        loop {
+            let block_offset = path.leaf_bid(self.page_cache)? as usize * BLOCK_SIZE;
+            let found = self
+                .block_entries(block_offset)?
+                .into_iter()
+                .find(|(_, entry)| entry.name() == name);
+            if found.is_some() {
+                return Ok(found);
+            }
+
+            if !self.next_leaf(&mut path)? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Inserts a new entry into the leaf block indicated by the index.
+    ///
+    /// The leaf block is split if it is full.
+    pub fn insert(&self, new_entry: DirEntry) -> Result<()> {
+        let mut path = self.probe(new_entry.name())?;
+        let leaf_bid = path.leaf_bid(self.page_cache)?;
+        let mut writer = DirEntryWriter::new(self.page_cache, 0);
+        if writer.insert_entry_in_block(leaf_bid as usize * BLOCK_SIZE, new_entry.clone())? {
+            return Ok(());
+        }
+
+        // Makes room in the index for the new leaf block, then splits the leaf block.
+        self.reserve_index_entry(&mut path)?;
+        let (split_hash, new_leaf_bid) = self.split_leaf(leaf_bid, path.version)?;
+        let frame = path.frames.last_mut().unwrap();
+        frame.insert_entry(self.page_cache, split_hash, new_leaf_bid)?;
+
+        let target_bid = if path.hash >= split_hash & !1 {
+            new_leaf_bid
+        } else {
+            leaf_bid
+        };
+        if !writer.insert_entry_in_block(target_bid as usize * BLOCK_SIZE, new_entry)? {
+            return_errno_with_message!(Errno::ENOSPC, "no space in the split leaf block");
+        }
+        Ok(())
+    }
+
+    /// Removes and returns the entry with the `name`.
+    pub fn remove(&self, name: &str) -> Result<DirEntry> {
+        let (offset, entry) = self.lookup(name)?.ok_or(Error::new(Errno::ENOENT))?;
+        DirEntryWriter::new(self.page_cache, offset).remove_entry_at(offset, &entry, false)?;
+        Ok(entry)
+    }
+
+    /// Finds the path from the root to the leaf block which may contain the `name`.
+    fn probe(&self, name: &str) -> Result<DxPath> {
+        let pages = self.page_cache.pages();
+        let root_info = pages.read_val::<RawDxRootInfo>(ROOT_INFO_OFFSET)?;
+        if root_info.reserved_zero != 0
+            || root_info.info_len as usize != core::mem::size_of::<RawDxRootInfo>()
+            || root_info.indirect_levels > MAX_INDIRECT_LEVELS
+            || root_info.unused_flags & DX_FLAG_INCOMPAT != 0
+        {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid htree root");
+        }
+        let version = self.hasher.version(root_info.hash_version)?;
+        let hash = self.hasher.hash(name.as_bytes(), version);
+
+        let mut path = DxPath {
+            frames: Vec::with_capacity(root_info.indirect_levels as usize + 1),
+            hash,
+            version,
+        };
+        let mut frame = DxFrame::load(self.page_cache, ROOT_ENTRIES_OFFSET)?;
+        loop {
+            frame.at = frame.search(self.page_cache, hash)?;
+            let bid = frame.entry_bid(self.page_cache, frame.at)?;
+            path.frames.push(frame);
+            if path.frames.len() > root_info.indirect_levels as usize {
+                break;
+            }
+            frame = self.load_node(bid)?;
+        }
+        Ok(path)
+    }
+
+    /// Moves the path to the next leaf block if it may contain the names with
+    /// the same hash.
+    ///
+    /// Returns `false` if there is no such a leaf block.
+    fn next_leaf(&self, path: &mut DxPath) -> Result<bool> {
+        let Some(level) = path
+            .frames
+            .iter()
+            .rposition(|frame| frame.at + 1 < frame.count)
+        else {
+            return Ok(false);
+        };
+
+        let frame = &mut path.frames[level];
+        frame.at += 1;
+        // The collision bit is set if the names with the same hash span two blocks.
+        let (next_hash, mut bid) = frame.entry(self.page_cache, frame.at)?;
+        if next_hash & !1 != path.hash {
+            return Ok(false);
+        }
+
+        for lower_level in level + 1..path.frames.len() {
+            let mut frame = self.load_node(bid)?;
+            frame.at = 0;
+            bid = frame.entry_bid(self.page_cache, 0)?;
+            path.frames[lower_level] = frame;
+        }
+        Ok(true)
+    }
+
+    /// Makes sure that there is room for a new entry in the last frame of the `path`,
+    /// by splitting the internal node or adding a new level to the index.
+    fn reserve_index_entry(&self, path: &mut DxPath) -> Result<()> {
+        let frame = path.frames.last().unwrap();
+        if frame.count < frame.limit {
+            return Ok(());
+        }
+
+        if path.frames.len() == 1 {
+            // Moves the entries of the root into a new node.
+            let root = &mut path.frames[0];
+            let node_bid = self.append_block()?;
+            let mut node = self.new_node(node_bid)?;
+            node.count = root.count;
+            node.at = root.at;
+            self.copy_entries(root, 0, &node, 0, root.count)?;
+            node.write_count_limit(self.page_cache)?;
+
+            root.count = 1;
+            root.at = 0;
+            root.write_count_limit(self.page_cache)?;
+            root.write_entry(self.page_cache, 0, 0, node_bid)?;
+            self.set_indirect_levels(1)?;
+            path.frames.push(node);
+            return Ok(());
+        }
+
+        // Splits the node into two.
+        let (root, node) = path.frames.split_at_mut(1);
+        let (root, node) = (&mut root[0], &mut node[0]);
+        if root.count >= root.limit {
+            return_errno_with_message!(Errno::ENOSPC, "the directory index is full");
+        }
+        let new_node_bid = self.append_block()?;
+        let mut new_node = self.new_node(new_node_bid)?;
+        let split = node.count / 2;
+        let (split_hash, _) = node.entry(self.page_cache, split)?;
+        new_node.count = node.count - split;
+        self.copy_entries(node, split, &new_node, 0, new_node.count)?;
+        new_node.write_count_limit(self.page_cache)?;
+        node.count = split;
+        node.write_count_limit(self.page_cache)?;
+
+        root.insert_entry(self.page_cache, split_hash, new_node_bid)?;
+        if node.at >= split {
+            new_node.at = node.at - split;
+            root.at += 1;
+            *node = new_node;
+        }
+        Ok(())
+    }
+
+    /// Splits the leaf block into two by the hashes of the entries.
+    ///
+    /// Returns the lowest hash in the new leaf block and its block ID. The hash has
+    /// the collision bit set if the names with the same hash span the two blocks.
+    fn split_leaf(&self, leaf_bid: u32, version: HashVersion) -> Result<(u32, u32)> {
+        let block_offset = leaf_bid as usize * BLOCK_SIZE;
+        let mut entries: Vec<(u32, DirEntry)> = self
+            .block_entries(block_offset)?
+            .into_iter()
+            .map(|(_, entry)| (self.hasher.hash(entry.name().as_bytes(), version), entry))
+            .collect();
+        if entries.len() < 2 {
+            return_errno_with_message!(Errno::ENOSPC, "can not split the leaf block");
+        }
+        entries.sort_by_key(|(hash, _)| *hash);
+
+        // Moves the upper half of the entries by size into the new block.
+        let total_len: usize = entries.iter().map(|(_, entry)| entry.actual_len()).sum();
+        let mut moved_len = 0;
+        let mut split = entries.len();
+        while split > 1 && moved_len < total_len / 2 {
+            split -= 1;
+            moved_len += entries[split].1.actual_len();
+        }
+        let split_hash = entries[split].0;
+        let is_continued = split_hash == entries[split - 1].0;
+
+        let new_leaf_bid = self.append_block()?;
+        let mut lower: Vec<DirEntry> = entries.into_iter().map(|(_, entry)| entry).collect();
+        let upper = lower.split_off(split);
+        let mut writer = DirEntryWriter::new(self.page_cache, 0);
+        writer.write_block(block_offset, &lower)?;
+        writer.write_block(new_leaf_bid as usize * BLOCK_SIZE, &upper)?;
+
+        Ok((split_hash | is_continued as u32, new_leaf_bid))
+    }
+
+    /// Returns the entries in the block starting at `block_offset` with their offsets.
+    fn block_entries(&self, block_offset: usize) -> Result<Vec<(usize, DirEntry)>> {
+        let mut reader = DirEntryReader::new(self.page_cache, block_offset);
+        let mut entries = Vec::new();
+        let mut offset = block_offset;
+        while offset < block_offset + BLOCK_SIZE {
+            let entry = reader.read_raw_entry()?;
+            let record_len = entry.record_len();
+            if entry.ino() != 0 {
+                entries.push((offset, entry));
+            }
+            offset += record_len;
+        }
+        Ok(entries)
+    }
+
+    /// Loads the internal node in the block `bid`.
+    fn load_node(&self, bid: u32) -> Result<DxFrame> {
+        check_bid(self.page_cache, bid)?;
+        let block_offset = bid as usize * BLOCK_SIZE;
+        let fake_entry = DirEntryReader::new(self.page_cache, block_offset).read_raw_entry()?;
+        if fake_entry.ino() != 0 || fake_entry.record_len() != BLOCK_SIZE {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid htree node");
+        }
+        DxFrame::load(self.page_cache, block_offset + NODE_ENTRIES_OFFSET)
+    }
+
+    /// Initializes an empty internal node in the block `bid`.
+    fn new_node(&self, bid: u32) -> Result<DxFrame> {
+        let block_offset = bid as usize * BLOCK_SIZE;
+        DirEntryWriter::new(self.page_cache, block_offset)
+            .write_entry(&DirEntry::unused(BLOCK_SIZE))?;
+        Ok(DxFrame {
+            entries_offset: block_offset + NODE_ENTRIES_OFFSET,
+            count: 0,
+            limit: dx_limit(NODE_ENTRIES_OFFSET),
+            at: 0,
+        })
+    }
+
+    /// Copies `count` index entries from `src` starting at `src_idx` to `dst` starting at `dst_idx`.
+    ///
+    /// The count and limit of `dst` should be written after copying.
+    fn copy_entries(
+        &self,
+        src: &DxFrame,
+        src_idx: usize,
+        dst: &DxFrame,
+        dst_idx: usize,
+        count: usize,
+    ) -> Result<()> {
+        let mut buf = vec![0u8; count * DX_ENTRY_SIZE];
+        let pages = self.page_cache.pages();
+        pages.read_bytes(src.entry_offset(src_idx), &mut buf)?;
+        pages.write_bytes(dst.entry_offset(dst_idx), &buf)?;
+        Ok(())
+    }
+
+    fn set_indirect_levels(&self, indirect_levels: u8) -> Result<()> {
+        let pages = self.page_cache.pages();
+        let mut root_info = pages.read_val::<RawDxRootInfo>(ROOT_INFO_OFFSET)?;
+        root_info.indirect_levels = indirect_levels;
+        pages.write_val(ROOT_INFO_OFFSET, &root_info)?;
+        Ok(())
+    }
+
+    /// Appends a new block to the directory and returns its block ID.
+    fn append_block(&self) -> Result<u32> {
+        let old_size = self.page_cache.pages().size();
+        self.page_cache.pages().resize(old_size + BLOCK_SIZE)?;
+        Ok((old_size / BLOCK_SIZE) as u32)
+    }
+}
+
+/// The path from the root to a leaf block in the index.
+struct DxPath {
+    /// The frames of the root and the internal nodes.
+    frames: Vec<DxFrame>,
+    /// The hash of the name.
+    hash: u32,
+    version: HashVersion,
+}
+
+impl DxPath {
+    /// Returns the block ID of the leaf block.
+    fn leaf_bid(&self, page_cache: &PageCache) -> Result<u32> {
+        let frame = self.frames.last().unwrap();
+        frame.entry_bid(page_cache, frame.at)
+    }
+}
+
+/// A block of the index entries, which is either the root or an internal node.
+#[derive(Clone, Copy, Debug)]
+struct DxFrame {
+    /// The offset of the index entries in the directory.
+    entries_offset: usize,
+    count: usize,
+    limit: usize,
+    /// The index of the current entry.
+    at: usize,
+}
+
+impl DxFrame {
+    /// Loads the frame whose index entries start at `entries_offset`.
+    fn load(page_cache: &PageCache, entries_offset: usize) -> Result<Self> {
+        let count_limit = page_cache
+            .pages()
+            .read_val::<RawDxCountLimit>(entries_offset)?;
+        let count = count_limit.count as usize;
+        let limit = count_limit.limit as usize;
+        if limit != dx_limit(entries_offset % BLOCK_SIZE) || count == 0 || count > limit {
+            return_errno_with_message!(Errno::EUCLEAN, "invalid htree count or limit");
+        }
+        Ok(Self {
+            entries_offset,
+            count,
+            limit,
+            at: 0,
+        })
+    }
+
+    /// Returns the index of the last entry whose hash is not greater than `hash`.
+    ///
+    /// The first entry has no hash, and it covers the lowest hashes.
+    fn search(&self, page_cache: &PageCache, hash: u32) -> Result<usize> {
+        let (mut low, mut high) = (1, self.count);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (mid_hash, _) = self.entry(page_cache, mid)?;
+            if mid_hash > hash {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        Ok(low - 1)
+    }
+
+    /// Returns the hash and the block ID of the entry at `idx`.
+    fn entry(&self, page_cache: &PageCache, idx: usize) -> Result<(u32, u32)> {
+        let raw_entry = page_cache
+            .pages()
+            .read_val::<RawDxEntry>(self.entry_offset(idx))?;
+        let hash = if idx == 0 { 0 } else { raw_entry.hash };
+        Ok((hash, raw_entry.bid))
+    }
+
+    /// Returns the block ID of the entry at `idx`, which is checked to be in the directory.
+    fn entry_bid(&self, page_cache: &PageCache, idx: usize) -> Result<u32> {
+        let (_, bid) = self.entry(page_cache, idx)?;
+        check_bid(page_cache, bid)?;
+        Ok(bid)
+    }
+
+    /// Inserts an entry after the current entry.
+    fn insert_entry(&mut self, page_cache: &PageCache, hash: u32, bid: u32) -> Result<()> {
+        debug_assert!(self.count < self.limit);
+        let pages = page_cache.pages();
+        let idx = self.at + 1;
+        let mut buf = vec![0u8; (self.count - idx) * DX_ENTRY_SIZE];
+        pages.read_bytes(self.entry_offset(idx), &mut buf)?;
+        pages.write_bytes(self.entry_offset(idx + 1), &buf)?;
+        self.write_entry(page_cache, idx, hash, bid)?;
+        self.count += 1;
+        self.write_count_limit(page_cache)
+    }
+
+    fn write_entry(&self, page_cache: &PageCache, idx: usize, hash: u32, bid: u32) -> Result<()> {
+        let pages = page_cache.pages();
+        if idx == 0 {
+            // The first entry stores the count and limit instead of the hash.
+            pages.write_val(self.entry_offset(0) + 4, &bid)?;
+        } else {
+            pages.write_val(self.entry_offset(idx), &RawDxEntry { hash, bid })?;
+        }
+        Ok(())
+    }
+
+    fn write_count_limit(&self, page_cache: &PageCache) -> Result<()> {
+        page_cache.pages().write_val(
+            self.entries_offset,
+            &RawDxCountLimit {
+                limit: self.limit as u16,
+                count: self.count as u16,
+            },
+        )?;
+        Ok(())
+    }
+
+    fn entry_offset(&self, idx: usize) -> usize {
+        self.entries_offset + idx * DX_ENTRY_SIZE
+    }
+}
+
+/// Checks that the block `bid` pointed by the index is a block of the directory other than the root.
+fn check_bid(page_cache: &PageCache, bid: u32) -> Result<()> {
+    if bid == 0 || bid as usize >= page_cache.pages().size() / BLOCK_SIZE {
+        return_errno_with_message!(Errno::EUCLEAN, "invalid htree block");
+    }
+    Ok(())
+}
+
+/// Returns the maximum number of the index entries starting at `offset` in a block.
+fn dx_limit(offset: usize) -> usize {
+    (BLOCK_SIZE - offset) / DX_ENTRY_SIZE
+}
+
+/// The info of the index in the root block.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDxRootInfo {
+    reserved_zero: u32,
+    hash_version: u8,
+    /// The length of the info, which is 8.
+    info_len: u8,
+    /// The number of the levels of the internal nodes.
+    indirect_levels: u8,
+    unused_flags: u8,
+}
+
+/// The header of the index entries, which overlaps the hash of the first entry.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDxCountLimit {
+    limit: u16,
+    count: u16,
+}
+
+/// The index entry, which maps the hashes from `hash` on to the block `bid`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawDxEntry {
+    hash: u32,
+    bid: u32,
+}
+
+/// The hash algorithm of the directory index.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum HashVersion {
+    Legacy,
+    HalfMd4,
+    Tea,
+    LegacyUnsigned,
+    HalfMd4Unsigned,
+    TeaUnsigned,
+}
+
+/// Computes the hashes of the names in the directory index.
+struct DirHasher {
+    seed: [u32; 4],
+    is_unsigned: bool,
+    /// The hash version used by the new index.
+    default_version: u8,
+}
+
+impl DirHasher {
+    fn new(super_block: &SuperBlock) -> Self {
+        Self {
+            seed: *super_block.hash_seed(),
+            is_unsigned: super_block.is_hash_unsigned(),
+            default_version: super_block.def_hash_version(),
+        }
+    }
+
+    /// Returns the hash algorithm indicated by the version stored in the root.
+    ///
+    /// Whether the chars are signed is decided by the superblock.
+    fn version(&self, raw_version: u8) -> Result<HashVersion> {
+        let version = match (raw_version, self.is_unsigned) {
+            (0, false) => HashVersion::Legacy,
+            (1, false) => HashVersion::HalfMd4,
+            (2, false) => HashVersion::Tea,
+            (0, true) => HashVersion::LegacyUnsigned,
+            (1, true) => HashVersion::HalfMd4Unsigned,
+            (2, true) => HashVersion::TeaUnsigned,
+            _ => return_errno_with_message!(Errno::EUCLEAN, "not supported htree hash version"),
+        };
+        Ok(version)
+    }
+
+    /// Computes the major hash of the `name`, whose lowest bit is always zero.
+    fn hash(&self, name: &[u8], version: HashVersion) -> u32 {
+        let mut buf = if self.seed.iter().any(|word| *word != 0) {
+            self.seed
+        } else {
+            [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]
+        };
+
+        let hash = match version {
+            HashVersion::Legacy => legacy_hash(name, false),
+            HashVersion::LegacyUnsigned => legacy_hash(name, true),
+            HashVersion::HalfMd4 | HashVersion::HalfMd4Unsigned => {
+                let is_unsigned = version == HashVersion::HalfMd4Unsigned;
+                for (idx, chunk) in name.chunks(32).enumerate() {
+                    let mut input = [0u32; 8];
+                    str_to_hash_buf(chunk, name.len() - idx * 32, is_unsigned, &mut input);
+                    half_md4_transform(&mut buf, &input);
+                }
+                buf[1]
+            }
+            HashVersion::Tea | HashVersion::TeaUnsigned => {
+                let is_unsigned = version == HashVersion::TeaUnsigned;
+                for (idx, chunk) in name.chunks(16).enumerate() {
+                    let mut input = [0u32; 4];
+                    str_to_hash_buf(chunk, name.len() - idx * 16, is_unsigned, &mut input);
+                    tea_transform(&mut buf, &input);
+                }
+                buf[0]
+            }
+        };
+
+        let hash = hash & !1;
+        if hash == HTREE_EOF_32BIT << 1 {
+            (HTREE_EOF_32BIT - 1) << 1
+        } else {
+            hash
+        }
+    }
+}
+
+/// Converts the char to an integer as the signed or unsigned char in C.
+fn char_to_u32(c: u8, is_unsigned: bool) -> u32 {
+    if is_unsigned {
+        c as u32
+    } else {
+        c as i8 as i32 as u32
+    }
+}
+
+/// The legacy hash used by the early versions of the directory index.
+fn legacy_hash(name: &[u8], is_unsigned: bool) -> u32 {
+    let (mut hash0, mut hash1): (u32, u32) = (0x12a3fe2d, 0x37abe8f9);
+    for c in name {
+        let mut hash =
+            hash1.wrapping_add(hash0 ^ char_to_u32(*c, is_unsigned).wrapping_mul(7152373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
+
+/// Packs the `chunk` of a name into the words of `buf`, padded with the length of
+/// the rest of the name.
+fn str_to_hash_buf(chunk: &[u8], rest_len: usize, is_unsigned: bool, buf: &mut [u32]) {
+    let len = rest_len as u32;
+    let pad = {
+        let pad = len | (len << 8);
+        pad | (pad << 16)
+    };
+
+    let mut words = buf.iter_mut();
+    let mut val = pad;
+    for (idx, c) in chunk.iter().enumerate() {
+        val = char_to_u32(*c, is_unsigned).wrapping_add(val << 8);
+        if idx % 4 == 3 {
+            *words.next().unwrap() = val;
+            val = pad;
+        }
+    }
+    if chunk.len() % 4 != 0 {
+        *words.next().unwrap() = val;
+    }
+    for word in words {
+        *word = pad;
+    }
+}
+
+/// The transform of the Tiny Encryption Algorithm.
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e3779b9;
+
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum: u32 = 0;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+/// The cut-down version of the MD4 transform, which has 3 rounds of 8 steps.
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K1: u32 = 0;
+    const K2: u32 = 0o13240474631;
+    const K3: u32 = 0o15666365641;
+
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let round =
+        |func: &dyn Fn(u32, u32, u32) -> u32, a: u32, b: u32, c: u32, d: u32, x: u32, s: u32| {
+            a.wrapping_add(func(b, c, d)).wrapping_add(x).rotate_left(s)
+        };
+
+    let [mut a, mut b, mut c, mut d] = *buf;
+
+    // Round 1
+    for i in [0, 4] {
+        a = round(&f, a, b, c, d, input[i].wrapping_add(K1), 3);
+        d = round(&f, d, a, b, c, input[i + 1].wrapping_add(K1), 7);
+        c = round(&f, c, d, a, b, input[i + 2].wrapping_add(K1), 11);
+        b = round(&f, b, c, d, a, input[i + 3].wrapping_add(K1), 19);
+    }
+
+    // Round 2
+    for i in [1, 0] {
+        a = round(&g, a, b, c, d, input[i].wrapping_add(K2), 3);
+        d = round(&g, d, a, b, c, input[i + 2].wrapping_add(K2), 5);
+        c = round(&g, c, d, a, b, input[i + 4].wrapping_add(K2), 9);
+        b = round(&g, b, c, d, a, input[i + 6].wrapping_add(K2), 13);
+    }
+
+    // Round 3
+    for i in [3, 1] {
+        a = round(&h, a, b, c, d, input[i].wrapping_add(K3), 3);
+        d = round(&h, d, a, b, c, input[i + 4].wrapping_add(K3), 9);
+        c = round(&h, c, d, a, b, input[i - 1].wrapping_add(K3), 11);
+        b = round(&h, b, c, d, a, input[i + 3].wrapping_add(K3), 15);
+    }
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+#[cfg(ktest)]
+mod test {
+    use super::*;
+
+    /// The names and their expected hashes, which are computed by `debugfs dx_hash`
+    /// of e2fsprogs with the default seed.
+    ///
+    /// The hashes are in the order of legacy, half MD4 and TEA, and the names contain
+    /// non-ASCII chars to tell apart the signed and the unsigned versions.
+    const SIGNED_HASHES: [(&str, [u32; 3]); 5] = [
+        (".", [0x71d73e48, 0x3df9c490, 0x31fd669c]),
+        ("lost+found", [0x5e2aba24, 0x591de422, 0x2dbf9e80]),
+        ("hello.txt", [0x65a05776, 0xa26e1d86, 0x5107c3f2]),
+        ("café.txt", [0x8be18dee, 0x1851ccc4, 0x625de47c]),
+        (
+            "abcdefghijklmnopqrstuvwxyz0123456789_ABCDEFGHIJKLMNOP",
+            [0xdaa7ce5e, 0x570930f6, 0x35dfb8de],
+        ),
+    ];
+    const UNSIGNED_HASHES: [(&str, [u32; 3]); 2] = [
+        ("hello.txt", [0x65a05776, 0xa26e1d86, 0x5107c3f2]),
+        ("café.txt", [0x0bff8f8c, 0x109eec0e, 0xa7497840]),
+    ];
+
+    fn new_hasher(seed: [u32; 4], is_unsigned: bool) -> DirHasher {
+        DirHasher {
+            seed,
+            is_unsigned,
+            default_version: 1,
+        }
+    }
+
+    fn check_hashes(hasher: &DirHasher, expected: &[(&str, [u32; 3])]) {
+        for (name, hashes) in expected {
+            for (raw_version, hash) in hashes.iter().enumerate() {
+                let version = hasher.version(raw_version as u8).unwrap();
+                assert_eq!(
+                    hasher.hash(name.as_bytes(), version),
+                    *hash,
+                    "{:?} of {}",
+                    version,
+                    name
+                );
+            }
+        }
+    }
+
+    #[ktest]
+    fn signed_hashes() {
+        check_hashes(&new_hasher([0; 4], false), &SIGNED_HASHES);
+    }
+
+    #[ktest]
+    fn unsigned_hashes() {
+        check_hashes(&new_hasher([0; 4], true), &UNSIGNED_HASHES);
+    }
+
+    #[ktest]
+    fn seeded_hashes() {
+        // Dumped by `debugfs htree_dump` from a file system made with
+        // `mke2fs -E hash_seed=11223344-5566-7788-99aa-bbccddeeff00`.
+        let hasher = new_hasher([0x44332211, 0x88776655, 0xccbbaa99, 0x00ffeedd], false);
+        let expected = [
+            ("file_2", 0x5b3780d4),
+            ("file_4", 0xa1cfb48e),
+            ("file_8", 0xd9486b1c),
+            ("file_119", 0xe3cc89e6),
+        ];
+        for (name, hash) in expected {
+            assert_eq!(hasher.hash(name.as_bytes(), HashVersion::HalfMd4), hash);
+        }
+    }
+
+    #[ktest]
+    fn unsupported_hash_version() {
+        let hasher = new_hasher([0; 4], false);
+        assert!(hasher.version(3).is_err());
+    }
+}
@@ -11,9 +11,18 @@ use crate::{
 };
 
 impl FileSystem for Ext2 {
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
+
     fn sync(&self) -> Result<()> {
+        // Nothing is written back to a read-only Ext2, e.g., the access times.
+        if self.is_read_only() {
+            return Ok(());
+        }
         self.sync_all_inodes()?;
         self.sync_metadata()?;
+        self.commit_journal()?;
         Ok(())
     }
 
@@ -26,7 +35,11 @@ impl FileSystem for Ext2 {
     }
 
     fn flags(&self) -> FsFlags {
-        FsFlags::empty()
+        if self.is_read_only() {
+            FsFlags::RDONLY
+        } else {
+            FsFlags::empty()
+        }
     }
 }
 
@@ -37,13 +50,23 @@ impl From<RwMutexReadGuard<'_, Dirty<Ext2SuperBlock>>> for SuperBlock {
             bsize: ext2_sb.block_size(),
             blocks: ext2_sb.total_blocks() as _,
             bfree: ext2_sb.free_blocks_count() as _,
-            bavail: ext2_sb.free_blocks_count() as _,
+            bavail: ext2_sb
+                .free_blocks_count()
+                .saturating_sub(ext2_sb.reserved_blocks_count()) as _,
             files: ext2_sb.total_inodes() as _,
             ffree: ext2_sb.free_inodes_count() as _,
-            fsid: 0, // TODO
+            fsid: fsid_from_uuid(ext2_sb.uuid()),
             namelen: NAME_MAX,
             frsize: ext2_sb.fragment_size(),
             flags: 0, // TODO
         }
     }
 }
+
+/// Folds the uuid of the volume into the filesystem ID, in the same way as Linux.
+fn fsid_from_uuid(uuid: &[u8; 16]) -> u64 {
+    let word = |offset: usize| u32::from_le_bytes(uuid[offset..offset + 4].try_into().unwrap());
+    let low = word(0) ^ word(8);
+    let high = word(4) ^ word(12);
+    ((high as u64) << 32) | low as u64
+}
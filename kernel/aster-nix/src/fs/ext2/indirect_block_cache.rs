@@ -104,7 +104,7 @@ impl IndirectBlockCache {
         for _ in 0..num {
             let (bid, block) = self.cache.pop_lru().unwrap();
             if block.is_dirty() {
-                bio_waiter.concat(self.fs().write_block_async(bid, &block.frame)?);
+                bio_waiter.concat(self.fs().write_metadata_block_async(bid, &block.frame)?);
             }
         }
 
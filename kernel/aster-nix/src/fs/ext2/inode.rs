@@ -6,9 +6,12 @@ use super::{
     block_ptr::{BidPath, BlockPtrs, Ext2Bid, BID_SIZE, MAX_BLOCK_PTRS},
     blocks_hole::BlocksHoleDesc,
     dir::{DirEntry, DirEntryReader, DirEntryWriter},
+    extent::{ExtentMapping, ExtentTree},
     fs::Ext2,
+    htree::HTree,
     indirect_block_cache::{IndirectBlock, IndirectBlockCache},
     prelude::*,
+    super_block::FeatureCompatSet,
 };
 
 /// Max length of file name.
@@ -409,14 +412,14 @@ impl Inode {
 
         let try_readdir = |offset: &mut usize, visitor: &mut dyn DirentVisitor| -> Result<()> {
             let dir_entry_reader = DirEntryReader::new(&inner.page_cache, *offset);
-            for (_, dir_entry) in dir_entry_reader {
+            for (entry_offset, dir_entry) in dir_entry_reader {
                 visitor.visit(
                     dir_entry.name(),
                     dir_entry.ino() as u64,
                     InodeType::from(dir_entry.type_()),
                     dir_entry.record_len(),
                 )?;
-                *offset += dir_entry.record_len();
+                *offset = entry_offset + dir_entry.record_len();
             }
 
             Ok(())
@@ -596,6 +599,7 @@ impl Inner {
     pub fn gid(&self) -> u32;
     pub fn set_gid(&mut self, gid: u32);
     pub fn file_flags(&self) -> FileFlags;
+    pub fn set_file_flags(&mut self, flags: FileFlags);
     pub fn hard_links(&self) -> u16;
     pub fn inc_hard_links(&mut self);
     pub fn dec_hard_links(&mut self);
@@ -609,6 +613,7 @@ impl Inner {
     pub fn set_device_id(&mut self, device_id: u64);
     pub fn device_id(&self) -> u64;
     pub fn sync_metadata(&self) -> Result<()>;
+    pub fn fs(&self) -> Arc<Ext2>;
 }
 
 impl Inner {
@@ -742,6 +747,15 @@ impl Inner {
     }
 
     pub fn get_entry(&self, name: &str, offset: usize) -> Option<(usize, DirEntry)> {
+        // The "." and ".." entries are not indexed.
+        if self.is_indexed_dir()
+            && name != "."
+            && name != ".."
+            && let Ok(found) = self.htree().lookup(name)
+        {
+            return found;
+        }
+
         DirEntryReader::new(&self.page_cache, offset).find(|(offset, entry)| entry.name() == name)
     }
 
@@ -753,7 +767,7 @@ impl Inner {
         let is_dir = entry.type_() == FileType::Dir;
         let is_parent = entry.name() == "..";
 
-        DirEntryWriter::new(&self.page_cache, offset).append_entry(entry)?;
+        self.insert_entry(entry, offset)?;
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size > file_size {
@@ -766,7 +780,7 @@ impl Inner {
     }
 
     pub fn remove_entry(&mut self, name: &str, offset: usize) -> Result<()> {
-        let entry = DirEntryWriter::new(&self.page_cache, offset).remove_entry(name)?;
+        let entry = self.take_entry(name, offset)?;
         let is_dir = entry.type_() == FileType::Dir;
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
@@ -780,7 +794,17 @@ impl Inner {
     }
 
     pub fn rename_entry(&mut self, old_name: &str, new_name: &str, offset: usize) -> Result<()> {
-        DirEntryWriter::new(&self.page_cache, offset).rename_entry(old_name, new_name)?;
+        if self.is_indexed_dir() {
+            // The entry is moved to the leaf block indicated by the hash of the new name.
+            let (_, entry) = self
+                .get_entry(old_name, offset)
+                .ok_or(Error::new(Errno::ENOENT))?;
+            self.insert_entry(DirEntry::new(entry.ino(), new_name, entry.type_()), offset)?;
+            self.take_entry(old_name, offset)?;
+        } else {
+            self.clear_index();
+            DirEntryWriter::new(&self.page_cache, offset).rename_entry(old_name, new_name)?;
+        }
         let file_size = self.inode_impl.file_size();
         let page_cache_size = self.page_cache.pages().size();
         if page_cache_size != file_size {
@@ -789,6 +813,68 @@ impl Inner {
         Ok(())
     }
 
+    /// Inserts the entry through the index if the directory is indexed.
+    ///
+    /// A full directory with a single block is converted into an indexed one
+    /// if the `dir_index` feature is enabled.
+    fn insert_entry(&mut self, entry: DirEntry, offset: usize) -> Result<()> {
+        if self.is_indexed_dir() {
+            match self.htree().insert(entry.clone()) {
+                // Falls back to the linear directory if the index is corrupted.
+                Err(e) if e.error() == Errno::EUCLEAN => (),
+                result => return result,
+            }
+        }
+        self.clear_index();
+
+        if self.page_cache.pages().size() == BLOCK_SIZE && self.has_dir_index() {
+            if DirEntryWriter::new(&self.page_cache, 0).insert_entry_in_block(0, entry.clone())? {
+                return Ok(());
+            }
+            if self.htree().build().is_ok() {
+                self.set_file_flags(self.file_flags() | FileFlags::INDEX_DIR);
+                return self.htree().insert(entry);
+            }
+        }
+        DirEntryWriter::new(&self.page_cache, offset).append_entry(entry)
+    }
+
+    /// Removes and returns the entry through the index if the directory is indexed.
+    fn take_entry(&mut self, name: &str, offset: usize) -> Result<DirEntry> {
+        if self.is_indexed_dir() {
+            match self.htree().remove(name) {
+                // Falls back to the linear directory if the index is corrupted.
+                Err(e) if e.error() == Errno::EUCLEAN => (),
+                result => return result,
+            }
+        }
+        self.clear_index();
+        DirEntryWriter::new(&self.page_cache, offset).remove_entry(name)
+    }
+
+    fn htree(&self) -> HTree<'_> {
+        HTree::new(&self.page_cache, &self.fs().super_block())
+    }
+
+    fn has_dir_index(&self) -> bool {
+        self.fs()
+            .super_block()
+            .feature_compat()
+            .contains(FeatureCompatSet::DIR_INDEX)
+    }
+
+    fn is_indexed_dir(&self) -> bool {
+        self.file_flags().contains(FileFlags::INDEX_DIR) && self.has_dir_index()
+    }
+
+    /// Clears the index flag since the index is outdated by the linear modifications.
+    fn clear_index(&mut self) {
+        let flags = self.file_flags();
+        if flags.contains(FileFlags::INDEX_DIR) {
+            self.set_file_flags(flags - FileFlags::INDEX_DIR);
+        }
+    }
+
     pub fn set_parent_ino(&mut self, parent_ino: u32) -> Result<()> {
         let (offset, mut entry) = self.get_entry("..", 0).unwrap();
         entry.set_ino(parent_ino);
@@ -813,6 +899,7 @@ struct InodeImpl_ {
     desc: Dirty<InodeDesc>,
     blocks_hole_desc: RwLock<BlocksHoleDesc>,
     indirect_blocks: RwMutex<IndirectBlockCache>,
+    extent_tree: RwMutex<ExtentTree>,
     is_freed: bool,
     last_alloc_device_bid: Option<Ext2Bid>,
     weak_self: Weak<Inode>,
@@ -822,6 +909,7 @@ impl InodeImpl_ {
     pub fn new(desc: Dirty<InodeDesc>, weak_self: Weak<Inode>, fs: Weak<Ext2>) -> Self {
         Self {
             blocks_hole_desc: RwLock::new(BlocksHoleDesc::new(desc.blocks_count() as usize)),
+            extent_tree: RwMutex::new(ExtentTree::new(&desc.block_ptrs, fs.clone())),
             desc,
             indirect_blocks: RwMutex::new(IndirectBlockCache::new(fs)),
             is_freed: false,
@@ -838,6 +926,16 @@ impl InodeImpl_ {
         self.inode().fs()
     }
 
+    /// Returns whether the inode or its extent tree need to be written back.
+    fn is_metadata_dirty(&self) -> bool {
+        self.desc.is_dirty() || self.extent_tree.read().is_dirty()
+    }
+
+    /// Returns whether the blocks are mapped by the extent tree.
+    fn has_extents(&self) -> bool {
+        self.desc.flags.contains(FileFlags::EXTENTS)
+    }
+
     pub fn read_block_async(&self, bid: Ext2Bid, block: &VmFrame) -> Result<BioWaiter> {
         if bid >= self.desc.blocks_count() {
             return_errno!(Errno::EINVAL);
@@ -848,8 +946,19 @@ impl InodeImpl_ {
             return Ok(BioWaiter::new());
         }
 
-        let device_range = DeviceRangeReader::new(self, bid..bid + 1)?.read()?;
-        self.fs().read_block_async(device_range.start, block)
+        let device_bid = if self.has_extents() {
+            match self.extent_tree.write().read(bid..bid + 1)? {
+                ExtentMapping::Mapped(device_range) => device_range.start,
+                // The unmapped and uninitialized blocks are read as zeros.
+                ExtentMapping::Uninit(_) | ExtentMapping::Unmapped(_) => {
+                    block.writer().fill(0);
+                    return Ok(BioWaiter::new());
+                }
+            }
+        } else {
+            DeviceRangeReader::new(self, bid..bid + 1)?.read()?.start
+        };
+        self.fs().read_block_async(device_bid, block)
     }
 
     pub fn read_block_sync(&self, bid: Ext2Bid, block: &VmFrame) -> Result<()> {
@@ -864,8 +973,18 @@ impl InodeImpl_ {
             return_errno!(Errno::EINVAL);
         }
 
-        let device_range = DeviceRangeReader::new(self, bid..bid + 1)?.read()?;
-        let waiter = self.fs().write_block_async(device_range.start, block)?;
+        let device_bid = if self.has_extents() {
+            self.map_extent_block_for_write(bid)?
+        } else {
+            DeviceRangeReader::new(self, bid..bid + 1)?.read()?.start
+        };
+        let waiter = match self.desc.type_ {
+            // The blocks of directories and symlinks are metadata.
+            FileType::Dir | FileType::Symlink => {
+                self.fs().write_metadata_block_async(device_bid, block)?
+            }
+            _ => self.fs().write_block_async(device_bid, block)?,
+        };
 
         // FIXME: Unset the block hole in the callback function of bio.
         self.blocks_hole_desc.write().unset(bid as usize);
@@ -879,12 +998,40 @@ impl InodeImpl_ {
         }
     }
 
+    /// Returns the device block ID to write the block `bid` in the extent tree.
+    ///
+    /// The block is allocated if it is not mapped, e.g., the file is created
+    /// sparsely by other implementations.
+    fn map_extent_block_for_write(&self, bid: Ext2Bid) -> Result<Ext2Bid> {
+        let mut extent_tree = self.extent_tree.write();
+        let device_bid = match extent_tree.read(bid..bid + 1)? {
+            ExtentMapping::Mapped(device_range) => device_range.start,
+            ExtentMapping::Uninit(device_range) => {
+                // FIXME: Set the block as initialized in the callback function of bio.
+                extent_tree.set_initialized(bid)?;
+                device_range.start
+            }
+            ExtentMapping::Unmapped(_) => {
+                let fs = self.fs();
+                let device_range = fs
+                    .alloc_blocks(self.inode().block_group_idx, 1)
+                    .ok_or_else(|| Error::new(Errno::ENOSPC))?;
+                if let Err(e) = extent_tree.insert(bid, device_range.clone()) {
+                    fs.free_blocks(device_range).unwrap();
+                    return Err(e);
+                }
+                device_range.start
+            }
+        };
+        Ok(device_bid)
+    }
+
     pub fn resize(&mut self, new_size: usize) -> Result<()> {
         let old_size = self.desc.size;
         if new_size > old_size {
             self.expand(new_size)?;
         } else {
-            self.shrink(new_size);
+            self.shrink(new_size)?;
         }
         Ok(())
     }
@@ -918,7 +1065,7 @@ impl InodeImpl_ {
         let mut current_range = range.clone();
         while !current_range.is_empty() {
             let Ok(expand_cnt) = self.try_expand_blocks(current_range.clone()) else {
-                self.shrink_blocks(range.start..current_range.start);
+                self.shrink_blocks(range.start..current_range.start)?;
                 return_errno_with_message!(Errno::ENOSPC, "can not allocate blocks");
             };
             current_range.start += expand_cnt;
@@ -934,6 +1081,10 @@ impl InodeImpl_ {
     /// isn't enough consecutive space available or if there is a necessity to allocate
     /// indirect blocks.
     fn try_expand_blocks(&mut self, range: Range<Ext2Bid>) -> Result<Ext2Bid> {
+        if self.has_extents() {
+            return self.try_expand_extent_blocks(range);
+        }
+
         // Calculates the maximum number of consecutive blocks that can be allocated in
         // this round, as well as the number of additional indirect blocks required for
         // the allocation.
@@ -1024,6 +1175,42 @@ impl InodeImpl_ {
         Ok(device_range.len() as Ext2Bid)
     }
 
+    /// Attempts to expand a range of blocks mapped by the extent tree, and returns
+    /// the number of consecutive blocks successfully expanded.
+    ///
+    /// The blocks which have already been mapped, e.g., preallocated beyond the end
+    /// of the file by other implementations, are reused.
+    fn try_expand_extent_blocks(&mut self, range: Range<Ext2Bid>) -> Result<Ext2Bid> {
+        let fs = self.fs();
+        let block_group_idx = self
+            .last_alloc_device_bid
+            .map_or(self.inode().block_group_idx, |id| {
+                ((id + 1) / fs.blocks_per_group()) as usize
+            });
+
+        let mut extent_tree = self.extent_tree.write();
+        let expand_cnt = match extent_tree.read(range.clone())? {
+            ExtentMapping::Mapped(device_range) | ExtentMapping::Uninit(device_range) => {
+                device_range.len() as Ext2Bid
+            }
+            ExtentMapping::Unmapped(max_cnt) => {
+                let device_range = fs
+                    .alloc_blocks(block_group_idx, max_cnt)
+                    .ok_or_else(|| Error::new(Errno::ENOSPC))?;
+                if let Err(e) = extent_tree.insert(range.start, device_range.clone()) {
+                    fs.free_blocks(device_range).unwrap();
+                    return Err(e);
+                }
+                self.last_alloc_device_bid = Some(device_range.end - 1);
+                device_range.len() as Ext2Bid
+            }
+        };
+        drop(extent_tree);
+
+        self.desc.blocks_count = range.start + expand_cnt;
+        Ok(expand_cnt)
+    }
+
     /// Sets the device block IDs for a specified range.
     ///
     /// It updates the mapping between the file's block IDs and the device's block IDs
@@ -1145,42 +1332,60 @@ impl InodeImpl_ {
     ///
     /// After the reduction, the size will be shrinked to `new_size`,
     /// which may result in an decreased block count.
-    fn shrink(&mut self, new_size: usize) {
+    fn shrink(&mut self, new_size: usize) -> Result<()> {
         let new_blocks = self.desc.size_to_blocks(new_size);
         let old_blocks = self.desc.blocks_count();
 
         // Shrinks block count if necessary
         if new_blocks < old_blocks {
-            self.shrink_blocks(new_blocks..old_blocks);
+            self.shrink_blocks(new_blocks..old_blocks)?;
             self.blocks_hole_desc.write().resize(new_blocks as usize);
         }
 
         // Shrinks the size
         self.desc.size = new_size;
+        Ok(())
     }
 
     /// Shrinks inode blocks.
     ///
     /// After the reduction, the block count will be decreased to `range.start`.
-    fn shrink_blocks(&mut self, range: Range<Ext2Bid>) {
+    /// If an error occurs, the block count is decreased to the blocks that are not freed.
+    fn shrink_blocks(&mut self, range: Range<Ext2Bid>) -> Result<()> {
         let mut current_range = range.clone();
         while !current_range.is_empty() {
-            let free_cnt = self.try_shrink_blocks(current_range.clone());
+            let free_cnt = match self.try_shrink_blocks(current_range.clone()) {
+                Ok(free_cnt) => free_cnt,
+                Err(err) => {
+                    self.desc.blocks_count = current_range.end;
+                    return Err(err);
+                }
+            };
             current_range.end -= free_cnt;
         }
 
         self.desc.blocks_count = range.start;
         self.last_alloc_device_bid = if range.start == 0 {
             None
+        } else if self.has_extents() {
+            match self
+                .extent_tree
+                .write()
+                .read((range.start - 1)..range.start)?
+            {
+                ExtentMapping::Mapped(device_range) | ExtentMapping::Uninit(device_range) => {
+                    Some(device_range.start)
+                }
+                ExtentMapping::Unmapped(_) => None,
+            }
         } else {
             Some(
-                DeviceRangeReader::new(self, (range.start - 1)..range.start)
-                    .unwrap()
-                    .read()
-                    .unwrap()
+                DeviceRangeReader::new(self, (range.start - 1)..range.start)?
+                    .read()?
                     .start,
             )
         };
+        Ok(())
     }
 
     /// Attempts to shrink a range of blocks and returns the number of blocks
@@ -1188,7 +1393,18 @@ impl InodeImpl_ {
     ///
     /// Note that the returned number may be less than the requested range if needs
     /// to free the indirect blocks that are no longer required.
-    fn try_shrink_blocks(&mut self, range: Range<Ext2Bid>) -> Ext2Bid {
+    fn try_shrink_blocks(&mut self, range: Range<Ext2Bid>) -> Result<Ext2Bid> {
+        if self.has_extents() {
+            // The extent tree frees all the blocks at once, including the ones
+            // preallocated beyond the end of the file.
+            let fs = self.fs();
+            let freed_ranges = self.extent_tree.write().truncate(range.start)?;
+            for device_range in freed_ranges {
+                fs.free_blocks(device_range)?;
+            }
+            return Ok(range.len() as Ext2Bid);
+        }
+
         // Calculates the maximum range of blocks that can be freed in this round.
         let range = {
             let max_cnt = (range.len() as Ext2Bid)
@@ -1197,13 +1413,13 @@ impl InodeImpl_ {
         };
 
         let fs = self.fs();
-        let device_range_reader = DeviceRangeReader::new(self, range.clone()).unwrap();
+        let device_range_reader = DeviceRangeReader::new(self, range.clone())?;
         for device_range in device_range_reader {
-            fs.free_blocks(device_range.clone()).unwrap();
+            fs.free_blocks(device_range.clone())?;
         }
 
-        self.free_indirect_blocks_required_by(range.start).unwrap();
-        range.len() as Ext2Bid
+        self.free_indirect_blocks_required_by(range.start)?;
+        Ok(range.len() as Ext2Bid)
     }
 
     /// Frees the indirect blocks required by the specified block ID.
@@ -1437,6 +1653,10 @@ impl InodeImpl {
         Arc::new(Self(RwMutex::new(inner)))
     }
 
+    pub fn fs(&self) -> Arc<Ext2> {
+        self.0.read().fs()
+    }
+
     pub fn file_size(&self) -> usize {
         self.0.read().desc.size
     }
@@ -1480,6 +1700,11 @@ impl InodeImpl {
         self.0.read().desc.flags
     }
 
+    pub fn set_file_flags(&self, flags: FileFlags) {
+        let mut inner = self.0.write();
+        inner.desc.flags = flags;
+    }
+
     pub fn hard_links(&self) -> u16 {
         self.0.read().desc.hard_links
     }
@@ -1583,12 +1808,12 @@ impl InodeImpl {
     }
 
     pub fn sync_metadata(&self) -> Result<()> {
-        if !self.0.read().desc.is_dirty() {
+        if !self.0.read().is_metadata_dirty() {
             return Ok(());
         }
 
         let mut inner = self.0.write();
-        if !inner.desc.is_dirty() {
+        if !inner.is_metadata_dirty() {
             return Ok(());
         }
 
@@ -1605,6 +1830,10 @@ impl InodeImpl {
         }
 
         inner.indirect_blocks.write().evict_all()?;
+        let extent_root = inner.extent_tree.write().sync(inode.block_group_idx)?;
+        if let Some(extent_root) = extent_root {
+            inner.desc.block_ptrs = extent_root;
+        }
         inode.fs().sync_inode(inode.ino(), &inner.desc)?;
         inner.desc.clear_dirty();
         Ok(())
@@ -1671,22 +1900,35 @@ impl TryFrom<RawInode> for InodeDesc {
 
     fn try_from(inode: RawInode) -> Result<Self> {
         let file_type = FileType::from_raw_mode(inode.mode)?;
+        let size = if file_type == FileType::File {
+            (inode.size_high as usize) << 32 | inode.size_low as usize
+        } else {
+            inode.size_low as usize
+        };
         Ok(Self {
             type_: file_type,
             perm: FilePerm::from_raw_mode(inode.mode)?,
             uid: (inode.os_dependent_2.uid_high as u32) << 16 | inode.uid as u32,
             gid: (inode.os_dependent_2.gid_high as u32) << 16 | inode.gid as u32,
-            size: if file_type == FileType::File {
-                (inode.size_high as usize) << 32 | inode.size_low as usize
-            } else {
-                inode.size_low as usize
-            },
+            size,
             atime: Duration::from(inode.atime),
             ctime: Duration::from(inode.ctime),
             mtime: Duration::from(inode.mtime),
             dtime: Duration::from(inode.dtime),
             hard_links: inode.hard_links,
-            blocks_count: inode.blocks_count,
+            blocks_count: {
+                let raw_blocks_count =
+                    (inode.os_dependent_2.blocks_high as u64) << 32 | inode.blocks_count as u64;
+                let blocks_count = if inode.flags & FileFlags::HUGE_FILE.bits() != 0 {
+                    raw_blocks_count
+                } else {
+                    raw_blocks_count / (BLOCK_SIZE / SECTOR_SIZE) as u64
+                };
+                // The blocks of sparse files may be less than the size.
+                blocks_count
+                    .max(size.div_ceil(BLOCK_SIZE) as u64)
+                    .min(Ext2Bid::MAX as u64) as Ext2Bid
+            },
             flags: FileFlags::from_bits(inode.flags)
                 .ok_or(Error::with_message(Errno::EINVAL, "invalid file flags"))?,
             block_ptrs: inode.block_ptrs,
@@ -1722,6 +1964,12 @@ impl InodeDesc {
         })
     }
 
+    /// Makes the blocks of the inode mapped by an empty extent tree.
+    pub fn enable_extents(&mut self) {
+        self.flags.insert(FileFlags::EXTENTS);
+        ExtentTree::init_root(&mut self.block_ptrs);
+    }
+
     pub fn num_page_bytes(&self) -> usize {
         (self.blocks_count() as usize) * BLOCK_SIZE
     }
@@ -1846,6 +2094,20 @@ bitflags! {
         const DIR_SYNC = 1 << 16;
         /// Top of directory hierarchies.
         const TOP_DIR = 1 << 17;
+        /// The number of blocks is in the unit of filesystem blocks.
+        const HUGE_FILE = 1 << 18;
+        /// Inode uses extents.
+        const EXTENTS = 1 << 19;
+        /// Verity protected inode.
+        const VERITY = 1 << 20;
+        /// Inode stores a large extended attribute.
+        const EA_INODE = 1 << 21;
+        /// Inode has inline data.
+        const INLINE_DATA = 1 << 28;
+        /// Create with parent's project id.
+        const PROJECT_INHERIT = 1 << 29;
+        /// Casefolded directory.
+        const CASEFOLD = 1 << 30;
         /// Reserved for ext2 lib.
         const RESERVED = 1 << 31;
     }
@@ -1898,6 +2160,7 @@ pub(super) struct RawInode {
 
 impl From<&InodeDesc> for RawInode {
     fn from(inode: &InodeDesc) -> Self {
+        let raw_blocks_count = inode.blocks_count as u64 * (BLOCK_SIZE / SECTOR_SIZE) as u64;
         Self {
             mode: inode.type_ as u16 | inode.perm.bits(),
             uid: inode.uid as u16,
@@ -1908,8 +2171,9 @@ impl From<&InodeDesc> for RawInode {
             dtime: UnixTime::from(inode.dtime),
             gid: inode.gid as u16,
             hard_links: inode.hard_links,
-            blocks_count: inode.blocks_count,
-            flags: inode.flags.bits(),
+            blocks_count: raw_blocks_count as u32,
+            // The number of blocks is always stored in sectors.
+            flags: (inode.flags - FileFlags::HUGE_FILE).bits(),
             block_ptrs: inode.block_ptrs,
             file_acl: match inode.acl {
                 Some(acl) if inode.type_ == FileType::File => acl.to_raw() as u32,
@@ -1917,9 +2181,11 @@ impl From<&InodeDesc> for RawInode {
             },
             size_high: match inode.acl {
                 Some(acl) if inode.type_ == FileType::Dir => acl.to_raw() as u32,
+                _ if inode.type_ == FileType::File => (inode.size >> 32) as u32,
                 _ => Default::default(),
             },
             os_dependent_2: Osd2 {
+                blocks_high: (raw_blocks_count >> 32) as u16,
                 uid_high: (inode.uid >> 16) as u16,
                 gid_high: (inode.gid >> 16) as u16,
                 ..Default::default()
@@ -1933,16 +2199,17 @@ impl From<&InodeDesc> for RawInode {
 #[repr(C)]
 #[derive(Clone, Copy, Default, Debug, Pod)]
 pub(super) struct Osd2 {
-    /// Fragment number.
-    pub frag_num: u8,
-    /// Fragment size.
-    pub frag_size: u8,
-    pad1: u16,
+    /// High 16 bits of the number of blocks, used by Ext4.
+    pub blocks_high: u16,
+    /// High 16 bits of File ACL, used by Ext4.
+    file_acl_high: u16,
     /// High 16 bits of User Id.
     pub uid_high: u16,
     /// High 16 bits of Group Id.
     pub gid_high: u16,
-    reserved2: u32,
+    /// Low 16 bits of the inode checksum, used by Ext4.
+    checksum_low: u16,
+    reserved: u16,
 }
 
 fn is_block_aligned(offset: usize) -> bool {
@@ -0,0 +1,819 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The journal of Ext3 and Ext4, whose on-disk format is known as JBD2.
+//!
+//! The journal is a circular log stored in a reserved inode. Instead of being
+//! written in place, the metadata blocks are collected into the running transaction.
+//! When the transaction is committed, the blocks are written to the log after a
+//! descriptor block which records their locations, followed by a commit block.
+//! Then the blocks are written in place, which is called checkpointing.
+//!
+//! If the system crashes before a committed transaction is checkpointed, the
+//! transaction is replayed from the log when the filesystem is opened again,
+//! so the metadata is never left half-updated.
+//!
+//! The blocks freed in the running transaction are not reused until the transaction
+//! is committed. Otherwise, they could be overwritten with file data in place while
+//! the metadata that still refers to them is not committed yet.
+//!
+//! For simplicity, each transaction is checkpointed right after it is committed,
+//! so the log holds at most one transaction and never wraps around. Only the internal
+//! journal without checksums is supported.
+//!
+//! Note that the commit block is written only after the other log blocks complete, and
+//! the blocks are checkpointed only after the commit block completes. But the block
+//! device is never asked to flush its volatile write cache, since the virtio block
+//! device does not support `Flush` yet, so the order in which the blocks reach the
+//! persistent storage is not guaranteed on a power loss.
+
+use super::{
+    block_ptr::{Ext2Bid, BID_SIZE, DIRECT_RANGE},
+    extent::load_extents,
+    inode::{FileFlags, RawInode},
+    prelude::*,
+};
+
+/// The magic number of the journal blocks.
+const JBD2_MAGIC: u32 = 0xc03b_3998;
+
+/// The minimal number of blocks in the journal.
+const JBD2_MIN_JOURNAL_BLOCKS: usize = 1024;
+
+const HEADER_SIZE: usize = core::mem::size_of::<RawHeader>();
+
+const UUID_SIZE: usize = 16;
+
+/// The Ext2 view of the journal.
+pub(super) struct Journal {
+    block_device: Arc<dyn BlockDevice>,
+    /// The device block IDs of the journal blocks, indexed by their numbers in the journal.
+    blocks: Vec<Ext2Bid>,
+    /// The number of the first block of the log.
+    first: u32,
+    /// The block of the journal superblock.
+    ///
+    /// The whole block is kept so that the unparsed fields are written back as they are.
+    super_block_frame: VmFrame,
+    /// The 128-bit uuid of the journal.
+    uuid: [u8; UUID_SIZE],
+    /// Whether the block numbers in the log are 64 bits.
+    is_64bit: bool,
+    inner: Mutex<JournalInner>,
+}
+
+struct JournalInner {
+    /// The sequence number of the running transaction.
+    sequence: u32,
+    /// The metadata blocks of the running transaction, indexed by their device block IDs.
+    blocks: BTreeMap<Ext2Bid, VmFrame>,
+    /// The blocks freed in the running transaction.
+    freed_blocks: Vec<Range<Ext2Bid>>,
+    /// The blocks freed in the committed transactions, which can be reused.
+    releasable_blocks: Vec<Range<Ext2Bid>>,
+}
+
+impl Journal {
+    /// Opens the journal stored in the `journal_inode`.
+    ///
+    /// If the filesystem was not unmounted cleanly, the committed transactions in
+    /// the log are replayed.
+    pub fn open(block_device: Arc<dyn BlockDevice>, journal_inode: &RawInode) -> Result<Self> {
+        let blocks = map_journal_blocks(block_device.as_ref(), journal_inode)?;
+
+        let super_block_frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        read_block(block_device.as_ref(), blocks[0], &super_block_frame)?;
+        let super_block = super_block_frame.read_val::<RawJournalSuperBlock>(0)?;
+        let is_v2 = match super_block.header.block_type() {
+            Some(BlockType::SuperBlockV1) => false,
+            Some(BlockType::SuperBlockV2) => true,
+            _ => return_errno_with_message!(Errno::EINVAL, "bad journal superblock"),
+        };
+        if u32::from_be(super_block.block_size) as usize != BLOCK_SIZE {
+            return_errno_with_message!(Errno::EINVAL, "not supported journal block size");
+        }
+        if super_block.errno != 0 {
+            warn!("ext2: the journal has been aborted with an error");
+        }
+        let max_len = u32::from_be(super_block.max_len) as usize;
+        let first = u32::from_be(super_block.first);
+        if max_len > blocks.len() || first == 0 || first as usize >= max_len {
+            return_errno_with_message!(Errno::EINVAL, "invalid journal layout");
+        }
+
+        let incompat = if is_v2 {
+            if super_block.feature_compat != 0 || super_block.feature_ro_compat != 0 {
+                return_errno_with_message!(Errno::EINVAL, "not supported journal features");
+            }
+            JournalFeatureInCompatSet::from_bits(u32::from_be(super_block.feature_incompat))
+                .filter(|features| JournalFeatureInCompatSet::SUPPORTED.contains(*features))
+                .ok_or(Error::with_message(
+                    Errno::EINVAL,
+                    "not supported journal features",
+                ))?
+        } else {
+            JournalFeatureInCompatSet::empty()
+        };
+
+        let journal = Self {
+            block_device,
+            blocks: blocks[..max_len].to_vec(),
+            first,
+            super_block_frame,
+            uuid: super_block.uuid,
+            is_64bit: incompat.contains(JournalFeatureInCompatSet::BIT64),
+            inner: Mutex::new(JournalInner {
+                sequence: u32::from_be(super_block.sequence),
+                blocks: BTreeMap::new(),
+                freed_blocks: Vec::new(),
+                releasable_blocks: Vec::new(),
+            }),
+        };
+
+        let start = u32::from_be(super_block.start);
+        if start != 0 {
+            journal.recover(start)?;
+        }
+        Ok(journal)
+    }
+
+    /// Reads one block indicated by the `bid` synchronously.
+    pub fn read_block(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<()> {
+        match self.read_block_async(bid, frame)?.wait() {
+            Some(BioStatus::Complete) => Ok(()),
+            _ => return_errno!(Errno::EIO),
+        }
+    }
+
+    /// Reads one block indicated by the `bid` asynchronously.
+    ///
+    /// If the block has been written in the running transaction, it is read from
+    /// the transaction instead of the block device.
+    pub fn read_block_async(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<BioWaiter> {
+        let inner = self.inner.lock();
+        if let Some(block) = inner.blocks.get(&bid) {
+            frame.copy_from_frame(block);
+            return Ok(BioWaiter::new());
+        }
+        let waiter = self.block_device.read_block(Bid::new(bid as u64), frame)?;
+        Ok(waiter)
+    }
+
+    /// Reads contiguous blocks starting from the `bid` synchronously.
+    pub fn read_blocks(&self, bid: Ext2Bid, segment: &VmSegment) -> Result<()> {
+        let inner = self.inner.lock();
+        match self
+            .block_device
+            .read_blocks_sync(Bid::new(bid as u64), segment)?
+        {
+            BioStatus::Complete => (),
+            err_status => return Err(Error::from(err_status)),
+        }
+
+        let end_bid = bid + segment.nframes() as Ext2Bid;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        for (block_bid, block) in inner.blocks.range(bid..end_bid) {
+            block.read_bytes(0, &mut buf)?;
+            segment.write_bytes((block_bid - bid) as usize * BLOCK_SIZE, &buf)?;
+        }
+        Ok(())
+    }
+
+    /// Writes one metadata block indicated by the `bid` to the running transaction.
+    pub fn write_block(&self, bid: Ext2Bid, frame: &VmFrame) -> Result<()> {
+        let mut inner = self.inner.lock();
+        if let Some(block) = inner.blocks.get(&bid) {
+            block.copy_from_frame(frame);
+            return Ok(());
+        }
+
+        self.reserve_block(&mut inner)?;
+        let block = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        block.copy_from_frame(frame);
+        inner.blocks.insert(bid, block);
+        Ok(())
+    }
+
+    /// Writes the metadata bytes at the `offset` of the block device to the running
+    /// transaction.
+    ///
+    /// The bytes may cover partial blocks, whose remaining parts are read from
+    /// the block device.
+    pub fn write_bytes(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let mut buf_offset = 0;
+        while buf_offset < buf.len() {
+            let bid = ((offset + buf_offset) / BLOCK_SIZE) as Ext2Bid;
+            let block_offset = (offset + buf_offset) % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - block_offset).min(buf.len() - buf_offset);
+
+            let block = match inner.blocks.get(&bid) {
+                Some(block) => block.clone(),
+                None => {
+                    self.reserve_block(&mut inner)?;
+                    let block = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+                    if len != BLOCK_SIZE {
+                        read_block(self.block_device.as_ref(), bid, &block)?;
+                    }
+                    inner.blocks.insert(bid, block.clone());
+                    block
+                }
+            };
+            block.write_bytes(block_offset, &buf[buf_offset..buf_offset + len])?;
+            buf_offset += len;
+        }
+        Ok(())
+    }
+
+    /// Removes the blocks in the `range` from the running transaction.
+    ///
+    /// It must be called before the blocks are reused to store file data, which
+    /// is written in place, otherwise the stale metadata would overwrite the data
+    /// when the transaction is checkpointed.
+    pub fn forget(&self, range: Range<Ext2Bid>) {
+        let mut inner = self.inner.lock();
+        inner.blocks.retain(|bid, _| !range.contains(bid));
+    }
+
+    /// Records the blocks in the `range` as freed in the running transaction.
+    ///
+    /// The blocks can be reused after the transaction is committed, when they are
+    /// returned by `take_releasable_blocks`.
+    pub fn defer_free(&self, range: Range<Ext2Bid>) {
+        self.inner.lock().freed_blocks.push(range);
+    }
+
+    /// Takes the blocks freed in the committed transactions, which can be reused now.
+    pub fn take_releasable_blocks(&self) -> Vec<Range<Ext2Bid>> {
+        core::mem::take(&mut self.inner.lock().releasable_blocks)
+    }
+
+    /// Commits the running transaction to the log, and then checkpoints it.
+    pub fn commit(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        self.commit_transaction(&mut inner)
+    }
+
+    /// Makes room in the running transaction for a new block.
+    ///
+    /// If the log cannot hold one more block, the running transaction is committed.
+    fn reserve_block(&self, inner: &mut JournalInner) -> Result<()> {
+        let nblocks = inner.blocks.len() + 1;
+        let nlog_blocks = nblocks + nblocks.div_ceil(self.tags_per_descriptor()) + 1;
+        if nlog_blocks > self.log_len() {
+            self.commit_transaction(inner)?;
+        }
+        Ok(())
+    }
+
+    fn commit_transaction(&self, inner: &mut JournalInner) -> Result<()> {
+        if inner.blocks.is_empty() {
+            let freed_blocks = core::mem::take(&mut inner.freed_blocks);
+            inner.releasable_blocks.extend(freed_blocks);
+            return Ok(());
+        }
+        let sequence = inner.sequence;
+
+        // Writes the descriptor blocks and the metadata blocks to the log.
+        let mut bio_waiter = BioWaiter::new();
+        let mut log_idx = self.first;
+        let blocks: Vec<(&Ext2Bid, &VmFrame)> = inner.blocks.iter().collect();
+        for chunk in blocks.chunks(self.tags_per_descriptor()) {
+            let descriptor_idx = log_idx;
+            log_idx += 1;
+
+            let mut descriptor = vec![0u8; BLOCK_SIZE];
+            descriptor[..HEADER_SIZE]
+                .copy_from_slice(RawHeader::new(BlockType::Descriptor, sequence).as_bytes());
+            let mut tag_offset = HEADER_SIZE;
+            for (idx, (bid, block)) in chunk.iter().enumerate() {
+                let mut flags = TagFlags::empty();
+                if idx != 0 {
+                    flags |= TagFlags::SAME_UUID;
+                }
+                if idx == chunk.len() - 1 {
+                    flags |= TagFlags::LAST_TAG;
+                }
+
+                // A block that starts with the magic number would be mistaken for a
+                // journal block, so its magic number is cleared in the log.
+                let log_block = if u32::from_be(block.read_val::<u32>(0)?) == JBD2_MAGIC {
+                    flags |= TagFlags::ESCAPE;
+                    let escaped_block = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+                    escaped_block.copy_from_frame(block);
+                    escaped_block.write_val(0, &0u32)?;
+                    escaped_block
+                } else {
+                    (*block).clone()
+                };
+                bio_waiter.concat(self.write_log_block_async(log_idx, &log_block)?);
+                log_idx += 1;
+
+                let tag = &mut descriptor[tag_offset..tag_offset + self.tag_size()];
+                tag[0..4].copy_from_slice(&bid.to_be_bytes());
+                tag[6..8].copy_from_slice(&flags.bits().to_be_bytes());
+                tag_offset += self.tag_size();
+                if idx == 0 {
+                    descriptor[tag_offset..tag_offset + UUID_SIZE].copy_from_slice(&self.uuid);
+                    tag_offset += UUID_SIZE;
+                }
+            }
+
+            let descriptor_block = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+            descriptor_block.write_bytes(0, &descriptor)?;
+            bio_waiter.concat(self.write_log_block_async(descriptor_idx, &descriptor_block)?);
+        }
+        bio_waiter
+            .wait()
+            .ok_or_else(|| Error::with_message(Errno::EIO, "failed to write the journal log"))?;
+        drop(bio_waiter);
+
+        // Writes the commit block after all the other blocks are in the log, so
+        // that a transaction with a commit block is always complete.
+        let commit_block = VmAllocOptions::new(1).alloc_single()?;
+        commit_block.write_val(0, &RawHeader::new(BlockType::Commit, sequence))?;
+        match self.write_log_block_async(log_idx, &commit_block)?.wait() {
+            Some(BioStatus::Complete) => (),
+            _ => return_errno_with_message!(Errno::EIO, "failed to write the commit block"),
+        }
+
+        // Marks the log as not empty, then the transaction will be replayed if the
+        // checkpoint is interrupted.
+        self.write_super_block(self.first, sequence)?;
+
+        // Checkpoints the transaction.
+        let mut bio_waiter = BioWaiter::new();
+        for (bid, block) in inner.blocks.iter() {
+            bio_waiter.concat(
+                self.block_device
+                    .write_block(Bid::new(*bid as u64), block)?,
+            );
+        }
+        bio_waiter.wait().ok_or_else(|| {
+            Error::with_message(Errno::EIO, "failed to checkpoint the transaction")
+        })?;
+
+        inner.sequence = sequence.wrapping_add(1);
+        inner.blocks.clear();
+        let freed_blocks = core::mem::take(&mut inner.freed_blocks);
+        inner.releasable_blocks.extend(freed_blocks);
+        self.write_super_block(0, inner.sequence)
+    }
+
+    /// Replays the committed transactions in the log starting from the `start` block.
+    fn recover(&self, start: u32) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let transactions = self.scan_log(start, inner.sequence)?;
+
+        // A block revoked by a transaction must not be replayed from the
+        // transactions up to and including it.
+        let mut revoked_blocks: BTreeMap<Ext2Bid, u32> = BTreeMap::new();
+        for transaction in &transactions {
+            for bid in &transaction.revoked_blocks {
+                revoked_blocks.insert(*bid, transaction.sequence);
+            }
+        }
+
+        let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        let mut nreplayed = 0;
+        for transaction in &transactions {
+            for logged_block in &transaction.blocks {
+                if revoked_blocks
+                    .get(&logged_block.bid)
+                    .is_some_and(|sequence| tid_geq(*sequence, transaction.sequence))
+                {
+                    continue;
+                }
+
+                self.read_log_block(logged_block.log_idx, &frame)?;
+                if logged_block.is_escaped {
+                    frame.write_val(0, &JBD2_MAGIC.to_be())?;
+                }
+                match self
+                    .block_device
+                    .write_block_sync(Bid::new(logged_block.bid as u64), &frame)?
+                {
+                    BioStatus::Complete => (),
+                    err_status => return Err(Error::from(err_status)),
+                }
+                nreplayed += 1;
+            }
+        }
+        info!(
+            "ext2: replayed {} transactions with {} blocks from the journal",
+            transactions.len(),
+            nreplayed
+        );
+
+        if let Some(transaction) = transactions.last() {
+            inner.sequence = transaction.sequence.wrapping_add(1);
+        }
+        self.write_super_block(0, inner.sequence)
+    }
+
+    /// Scans the log starting from the `start` block, and returns the complete
+    /// transactions whose sequence numbers start from the `sequence`.
+    fn scan_log(&self, start: u32, mut sequence: u32) -> Result<Vec<Transaction>> {
+        if start < self.first || start as usize >= self.blocks.len() {
+            return_errno_with_message!(Errno::EINVAL, "invalid start of the journal log");
+        }
+
+        let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut transactions = Vec::new();
+        let mut running_transaction = Transaction::new(sequence);
+        let mut log_idx = start;
+        let mut nscanned = 0;
+        while nscanned < self.log_len() {
+            self.read_log_block(log_idx, &frame)?;
+            frame.read_bytes(0, &mut buf)?;
+            let header = frame.read_val::<RawHeader>(0)?;
+            if u32::from_be(header.sequence) != sequence {
+                break;
+            }
+
+            match header.block_type() {
+                Some(BlockType::Descriptor) => {
+                    for (bid, flags) in self.parse_tags(&buf)? {
+                        log_idx = self.next_log_idx(log_idx);
+                        nscanned += 1;
+                        running_transaction.blocks.push(LoggedBlock {
+                            bid,
+                            log_idx,
+                            is_escaped: flags.contains(TagFlags::ESCAPE),
+                        });
+                    }
+                }
+                Some(BlockType::Revoke) => {
+                    let revoked_blocks = self.parse_revoke_records(&buf)?;
+                    running_transaction.revoked_blocks.extend(revoked_blocks);
+                }
+                Some(BlockType::Commit) => {
+                    sequence = sequence.wrapping_add(1);
+                    transactions.push(core::mem::replace(
+                        &mut running_transaction,
+                        Transaction::new(sequence),
+                    ));
+                }
+                _ => break,
+            }
+            log_idx = self.next_log_idx(log_idx);
+            nscanned += 1;
+        }
+        Ok(transactions)
+    }
+
+    /// Parses the tags of a descriptor block, which map the following log blocks
+    /// to their device block IDs.
+    fn parse_tags(&self, buf: &[u8]) -> Result<Vec<(Ext2Bid, TagFlags)>> {
+        let mut tags = Vec::new();
+        let mut offset = HEADER_SIZE;
+        while offset + self.tag_size() <= BLOCK_SIZE {
+            let bid = read_be_u32(buf, offset);
+            let flags = TagFlags::from_bits_truncate(u16::from_be_bytes([
+                buf[offset + 6],
+                buf[offset + 7],
+            ]));
+            if self.is_64bit && read_be_u32(buf, offset + 8) != 0 {
+                return_errno_with_message!(Errno::EINVAL, "the journal block is out of range");
+            }
+            tags.push((bid, flags));
+
+            offset += self.tag_size();
+            if !flags.contains(TagFlags::SAME_UUID) {
+                offset += UUID_SIZE;
+            }
+            if flags.contains(TagFlags::LAST_TAG) {
+                break;
+            }
+        }
+        Ok(tags)
+    }
+
+    /// Parses the records of a revoke block.
+    fn parse_revoke_records(&self, buf: &[u8]) -> Result<Vec<Ext2Bid>> {
+        // The number of bytes used in the block, including the header.
+        let nbytes = (read_be_u32(buf, HEADER_SIZE) as usize).min(BLOCK_SIZE);
+        let record_size = if self.is_64bit { 8 } else { 4 };
+
+        let mut revoked_blocks = Vec::new();
+        let mut offset = HEADER_SIZE + 4;
+        while offset + record_size <= nbytes {
+            let bid = if self.is_64bit {
+                if read_be_u32(buf, offset) != 0 {
+                    return_errno_with_message!(Errno::EINVAL, "the journal block is out of range");
+                }
+                read_be_u32(buf, offset + 4)
+            } else {
+                read_be_u32(buf, offset)
+            };
+            revoked_blocks.push(bid);
+            offset += record_size;
+        }
+        Ok(revoked_blocks)
+    }
+
+    /// Updates the start and the sequence of the log in the journal superblock,
+    /// and writes it back synchronously.
+    ///
+    /// A zero `start` means the log is empty.
+    fn write_super_block(&self, start: u32, sequence: u32) -> Result<()> {
+        let mut super_block = self.super_block_frame.read_val::<RawJournalSuperBlock>(0)?;
+        super_block.start = start.to_be();
+        super_block.sequence = sequence.to_be();
+        self.super_block_frame.write_val(0, &super_block)?;
+        match self
+            .write_log_block_async(0, &self.super_block_frame)?
+            .wait()
+        {
+            Some(BioStatus::Complete) => Ok(()),
+            _ => return_errno_with_message!(Errno::EIO, "failed to write the journal superblock"),
+        }
+    }
+
+    fn read_log_block(&self, log_idx: u32, frame: &VmFrame) -> Result<()> {
+        read_block(
+            self.block_device.as_ref(),
+            self.blocks[log_idx as usize],
+            frame,
+        )
+    }
+
+    fn write_log_block_async(&self, log_idx: u32, frame: &VmFrame) -> Result<BioWaiter> {
+        let bid = self.blocks[log_idx as usize];
+        let waiter = self.block_device.write_block(Bid::new(bid as u64), frame)?;
+        Ok(waiter)
+    }
+
+    fn next_log_idx(&self, log_idx: u32) -> u32 {
+        if log_idx as usize + 1 >= self.blocks.len() {
+            self.first
+        } else {
+            log_idx + 1
+        }
+    }
+
+    /// Returns the number of blocks in the log.
+    fn log_len(&self) -> usize {
+        self.blocks.len() - self.first as usize
+    }
+
+    fn tag_size(&self) -> usize {
+        if self.is_64bit {
+            12
+        } else {
+            8
+        }
+    }
+
+    /// Returns the number of tags that a descriptor block can hold at least.
+    fn tags_per_descriptor(&self) -> usize {
+        (BLOCK_SIZE - HEADER_SIZE - UUID_SIZE) / self.tag_size()
+    }
+}
+
+impl Debug for Journal {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Journal")
+            .field("len", &self.blocks.len())
+            .field("first", &self.first)
+            .field("is_64bit", &self.is_64bit)
+            .finish()
+    }
+}
+
+/// A committed transaction found in the log.
+struct Transaction {
+    sequence: u32,
+    blocks: Vec<LoggedBlock>,
+    revoked_blocks: Vec<Ext2Bid>,
+}
+
+impl Transaction {
+    fn new(sequence: u32) -> Self {
+        Self {
+            sequence,
+            blocks: Vec::new(),
+            revoked_blocks: Vec::new(),
+        }
+    }
+}
+
+/// A metadata block stored in the log.
+struct LoggedBlock {
+    /// The device block ID where the block is replayed.
+    bid: Ext2Bid,
+    /// The number of the journal block where the block is stored.
+    log_idx: u32,
+    /// Whether the magic number at the beginning of the block is cleared.
+    is_escaped: bool,
+}
+
+/// Maps the blocks of the journal inode to the device block IDs.
+fn map_journal_blocks(
+    block_device: &dyn BlockDevice,
+    journal_inode: &RawInode,
+) -> Result<Vec<Ext2Bid>> {
+    let nblocks =
+        (((journal_inode.size_high as usize) << 32) | journal_inode.size_low as usize) / BLOCK_SIZE;
+    if nblocks < JBD2_MIN_JOURNAL_BLOCKS {
+        return_errno_with_message!(Errno::EINVAL, "the journal is too small");
+    }
+
+    if FileFlags::from_bits_truncate(journal_inode.flags).contains(FileFlags::EXTENTS) {
+        let mut extents = Vec::new();
+        load_extents(
+            journal_inode.block_ptrs.as_bytes(),
+            &|bid, frame| read_block(block_device, bid, frame),
+            &mut extents,
+            &mut Vec::new(),
+        )?;
+
+        let mut blocks = Vec::with_capacity(nblocks);
+        for extent in extents.iter() {
+            if extent.start as usize != blocks.len() || extent.is_uninit {
+                return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+            }
+            blocks.extend(extent.device_start..extent.device_end());
+        }
+        if blocks.len() < nblocks {
+            return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+        }
+        blocks.truncate(nblocks);
+        return Ok(blocks);
+    }
+
+    let block_ptrs = &journal_inode.block_ptrs;
+    let mut blocks = Vec::with_capacity(nblocks);
+    for idx in DIRECT_RANGE {
+        blocks.push(block_ptrs.direct(idx));
+    }
+    map_indirect_blocks(block_device, block_ptrs.indirect(), 1, nblocks, &mut blocks)?;
+    map_indirect_blocks(
+        block_device,
+        block_ptrs.db_indirect(),
+        2,
+        nblocks,
+        &mut blocks,
+    )?;
+    map_indirect_blocks(
+        block_device,
+        block_ptrs.tb_indirect(),
+        3,
+        nblocks,
+        &mut blocks,
+    )?;
+
+    blocks.truncate(nblocks);
+    if blocks.len() < nblocks || blocks.contains(&0) {
+        return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+    }
+    Ok(blocks)
+}
+
+/// Appends the blocks pointed by the indirect block at the `level` to `blocks`,
+/// until there are `nblocks` blocks.
+fn map_indirect_blocks(
+    block_device: &dyn BlockDevice,
+    bid: Ext2Bid,
+    level: u32,
+    nblocks: usize,
+    blocks: &mut Vec<Ext2Bid>,
+) -> Result<()> {
+    if blocks.len() >= nblocks {
+        return Ok(());
+    }
+    if bid == 0 {
+        return_errno_with_message!(Errno::EINVAL, "the journal has holes");
+    }
+
+    let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+    read_block(block_device, bid, &frame)?;
+    for idx in 0..BLOCK_SIZE / BID_SIZE {
+        if blocks.len() >= nblocks {
+            break;
+        }
+        let child_bid = frame.read_val::<Ext2Bid>(idx * BID_SIZE)?;
+        if level == 1 {
+            blocks.push(child_bid);
+        } else {
+            map_indirect_blocks(block_device, child_bid, level - 1, nblocks, blocks)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_block(block_device: &dyn BlockDevice, bid: Ext2Bid, frame: &VmFrame) -> Result<()> {
+    match block_device.read_block_sync(Bid::new(bid as u64), frame)? {
+        BioStatus::Complete => Ok(()),
+        err_status => Err(Error::from(err_status)),
+    }
+}
+
+fn read_be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Checks if the sequence number `a` is greater than or equal to `b`,
+/// taking the wrapping around into account.
+fn tid_geq(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) >= 0
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, TryFromInt)]
+enum BlockType {
+    Descriptor = 1,
+    Commit = 2,
+    SuperBlockV1 = 3,
+    SuperBlockV2 = 4,
+    Revoke = 5,
+}
+
+bitflags! {
+    /// Incompatible feature set of the journal.
+    struct JournalFeatureInCompatSet: u32 {
+        /// The log has revoke blocks
+        const REVOKE = 1 << 0;
+        /// The block numbers in the log are 64 bits
+        const BIT64 = 1 << 1;
+        /// The commit blocks may be written before the other blocks
+        const ASYNC_COMMIT = 1 << 2;
+        /// The log blocks have checksums of version 2
+        const CSUM_V2 = 1 << 3;
+        /// The log blocks have checksums of version 3
+        const CSUM_V3 = 1 << 4;
+        /// The log has fast commit blocks
+        const FAST_COMMIT = 1 << 5;
+        /// The features supported by this implementation
+        const SUPPORTED = Self::REVOKE.bits | Self::BIT64.bits | Self::ASYNC_COMMIT.bits;
+    }
+}
+
+bitflags! {
+    /// The flags of a tag in the descriptor block.
+    struct TagFlags: u16 {
+        /// The magic number of the block is cleared in the log
+        const ESCAPE = 1 << 0;
+        /// The tag is not followed by an uuid
+        const SAME_UUID = 1 << 1;
+        /// The block was deleted by this transaction
+        const DELETED = 1 << 2;
+        /// The tag is the last one in the descriptor block
+        const LAST_TAG = 1 << 3;
+    }
+}
+
+/// The header of the journal blocks.
+///
+/// All the fields of the journal are stored in big-endian order.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawHeader {
+    magic: u32,
+    block_type: u32,
+    sequence: u32,
+}
+
+impl RawHeader {
+    fn new(block_type: BlockType, sequence: u32) -> Self {
+        Self {
+            magic: JBD2_MAGIC.to_be(),
+            block_type: (block_type as u32).to_be(),
+            sequence: sequence.to_be(),
+        }
+    }
+
+    /// Returns the block type if the header is valid.
+    fn block_type(&self) -> Option<BlockType> {
+        if u32::from_be(self.magic) != JBD2_MAGIC {
+            return None;
+        }
+        BlockType::try_from(u32::from_be(self.block_type)).ok()
+    }
+}
+
+/// The leading fields of the journal superblock.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod)]
+struct RawJournalSuperBlock {
+    header: RawHeader,
+    /// Block size of the journal.
+    block_size: u32,
+    /// Total number of blocks in the journal.
+    max_len: u32,
+    /// The first block of the log.
+    first: u32,
+    /// The sequence number of the first transaction in the log.
+    sequence: u32,
+    /// The block where the log starts, zero if the log is empty.
+    start: u32,
+    /// Error number of the aborted journal.
+    errno: u32,
+    //
+    // These fields are valid for the version 2 superblock only.
+    //
+    feature_compat: u32,
+    feature_incompat: u32,
+    feature_ro_compat: u32,
+    uuid: [u8; UUID_SIZE],
+}
@@ -14,6 +14,15 @@
 //!    stored in PageCache, which accelerates the performance of data access.
 //! 3. Compatible with queue-based block device. The filesystem can submits multiple
 //!    BIO requests to be block device at once, thereby enhancing I/O performance.
+//! 4. Compatible with the Ext3 journal. If the filesystem has a journal, the metadata
+//!    is written through the journal, and the journal is replayed when the
+//!    filesystem is opened after a crash.
+//! 5. Compatible with the common Ext4 features. The files can be mapped by extents,
+//!    and the `64bit`, `flex_bg` and `huge_file` features are supported, so the
+//!    disks formatted by `mkfs.ext4 -O ^metadata_csum,^uninit_bg` can be mounted.
+//! 6. Hashed directory index. If the `dir_index` feature is enabled, the large
+//!    directories are indexed by the hashes of the names, so the entries can be
+//!    found without scanning the whole directory.
 //!
 //! # Example
 //!
@@ -35,6 +44,8 @@
 //! Here we summarizes the features that need to be implemented in the future.
 //! 1. Supports merging small read/write operations.
 //! 2. Handles the intermediate failure status correctly.
+//! 3. Supports the external journal and the journal checksums.
+//! 4. Supports the metadata checksums and the block numbers larger than 2^32.
 
 pub use fs::Ext2;
 pub use inode::{FilePerm, FileType, Inode};
@@ -44,10 +55,13 @@ mod block_group;
 mod block_ptr;
 mod blocks_hole;
 mod dir;
+mod extent;
 mod fs;
+mod htree;
 mod impl_for_vfs;
 mod indirect_block_cache;
 mod inode;
+mod journal;
 mod prelude;
 mod super_block;
 mod utils;
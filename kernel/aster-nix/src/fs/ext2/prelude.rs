@@ -9,7 +9,7 @@ pub(super) use align_ext::AlignExt;
 pub(super) use aster_block::{
     bio::{BioStatus, BioWaiter},
     id::Bid,
-    BlockDevice, BLOCK_SIZE,
+    BlockDevice, BLOCK_SIZE, SECTOR_SIZE,
 };
 pub(super) use aster_frame::{
     sync::{RwMutex, RwMutexReadGuard, RwMutexWriteGuard},
@@ -1,6 +1,10 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{inode::RawInode, prelude::*};
+use super::{
+    block_group::{RawGroupDescriptor, RAW_GROUP_DESCRIPTOR_64BIT_SIZE},
+    inode::RawInode,
+    prelude::*,
+};
 
 /// The magic number of Ext2.
 pub const MAGIC_NUM: u16 = 0xef53;
@@ -10,6 +14,9 @@ pub const SUPER_BLOCK_OFFSET: usize = 1024;
 
 const SUPER_BLOCK_SIZE: usize = 1024;
 
+/// The flag indicates that the directory hash treats the names as unsigned chars.
+const FLAGS_UNSIGNED_HASH: u32 = 1 << 1;
+
 /// The in-memory rust superblock.
 ///
 /// It contains all information about the layout of the Ext2.
@@ -91,17 +98,68 @@ pub struct SuperBlock {
     prealloc_file_blocks: u8,
     /// Number of blocks to preallocate for directories.
     prealloc_dir_blocks: u8,
+    //
+    // These fields are used by the journal and other extended features. They are
+    // kept as they are so that the superblock can be written back without loss.
+    //
+    /// Compression algorithms used.
+    algorithm_usage_bitmap: u32,
+    /// Uuid of journal superblock.
+    journal_uuid: [u8; 16],
+    /// Inode number of journal file.
+    journal_ino: u32,
+    /// Device number of journal file.
+    journal_dev: u32,
+    /// Start of list of inodes to delete.
+    last_orphan: u32,
+    /// HTREE hash seed.
+    hash_seed: [u32; 4],
+    /// Default hash version to use.
+    def_hash_version: u8,
+    /// Default mount options.
+    default_mount_opts: u32,
+    /// First metablock block group.
+    first_meta_bg: u32,
+    /// The type of the backup of the journal inode's block pointers.
+    journal_backup_type: u8,
+    /// Size of the group descriptor.
+    desc_size: u16,
+    /// When the filesystem was created.
+    mkfs_time: UnixTime,
+    /// Backup of the journal inode's block pointers and size.
+    journal_blocks: [u32; 17],
+    /// All inodes have at least these extra bytes.
+    min_extra_isize: u16,
+    /// New inodes should reserve these extra bytes.
+    want_extra_isize: u16,
+    /// Miscellaneous flags.
+    flags: u32,
+    /// The remaining fields.
+    reserved: Reserved,
 }
 
 impl TryFrom<RawSuperBlock> for SuperBlock {
     type Error = crate::error::Error;
 
     fn try_from(sb: RawSuperBlock) -> Result<Self> {
+        // The upper halves of the block counts are only valid with the 64-bit feature.
+        // Since the block IDs are 32 bits, the counts must fit in their lower halves.
+        let is_64bit = sb.feature_incompat & FeatureInCompatSet::IS_64BIT.bits() != 0;
+        let blocks_count_of = |count_low: u32, count_high: u32| -> Result<u32> {
+            if is_64bit && count_high != 0 {
+                return_errno_with_message!(Errno::EFBIG, "the filesystem is too large");
+            }
+            Ok(count_low)
+        };
+
         Ok(Self {
             inodes_count: sb.inodes_count,
-            blocks_count: sb.blocks_count,
-            reserved_blocks_count: sb.reserved_blocks_count,
-            free_blocks_count: sb.free_blocks_count,
+            blocks_count: blocks_count_of(sb.blocks_count, sb.blocks_count_high)?,
+            reserved_blocks_count: blocks_count_of(
+                sb.reserved_blocks_count,
+                sb.reserved_blocks_count_high,
+            )?,
+            free_blocks_count: blocks_count_of(sb.free_blocks_count, sb.free_blocks_count_high)?,
             free_inodes_count: sb.free_inodes_count,
             first_data_block: Bid::new(sb.first_data_block as _),
             block_size: 1024 << sb.log_block_size,
@@ -161,17 +219,62 @@ impl TryFrom<RawSuperBlock> for SuperBlock {
             feature_compat: FeatureCompatSet::from_bits(sb.feature_compat).ok_or(
                 Error::with_message(Errno::EINVAL, "invalid feature compat set"),
             )?,
-            feature_incompat: FeatureInCompatSet::from_bits(sb.feature_incompat).ok_or(
-                Error::with_message(Errno::EINVAL, "invalid feature incompat set"),
-            )?,
-            feature_ro_compat: FeatureRoCompatSet::from_bits(sb.feature_ro_compat).ok_or(
-                Error::with_message(Errno::EINVAL, "invalid feature ro compat set"),
-            )?,
+            feature_incompat: {
+                let feature_incompat = FeatureInCompatSet::from_bits(sb.feature_incompat).ok_or(
+                    Error::with_message(Errno::EINVAL, "invalid feature incompat set"),
+                )?;
+                if !FeatureInCompatSet::SUPPORTED.contains(feature_incompat) {
+                    return_errno_with_message!(Errno::EINVAL, "not supported feature incompat set");
+                }
+                feature_incompat
+            },
+            feature_ro_compat: {
+                let feature_ro_compat = FeatureRoCompatSet::from_bits(sb.feature_ro_compat).ok_or(
+                    Error::with_message(Errno::EINVAL, "invalid feature ro compat set"),
+                )?;
+                if !(FeatureRoCompatSet::SUPPORTED | FeatureRoCompatSet::SUPPORTED_READ_ONLY)
+                    .contains(feature_ro_compat)
+                {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "not supported feature ro compat set"
+                    );
+                }
+                feature_ro_compat
+            },
             uuid: sb.uuid,
             volume_name: sb.volume_name,
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
+            algorithm_usage_bitmap: sb.algorithm_usage_bitmap,
+            journal_uuid: sb.journal_uuid,
+            journal_ino: sb.journal_ino,
+            journal_dev: sb.journal_dev,
+            last_orphan: sb.last_orphan,
+            hash_seed: sb.hash_seed,
+            def_hash_version: sb.def_hash_version,
+            default_mount_opts: sb.default_mount_opts,
+            first_meta_bg: sb.first_meta_bg,
+            journal_backup_type: sb.journal_backup_type,
+            desc_size: {
+                if is_64bit {
+                    let desc_size = sb.desc_size as usize;
+                    if desc_size < RAW_GROUP_DESCRIPTOR_64BIT_SIZE
+                        || !desc_size.is_power_of_two()
+                        || desc_size > BLOCK_SIZE
+                    {
+                        return_errno_with_message!(Errno::EINVAL, "invalid group descriptor size");
+                    }
+                }
+                sb.desc_size
+            },
+            mkfs_time: sb.mkfs_time,
+            journal_blocks: sb.journal_blocks,
+            min_extra_isize: sb.min_extra_isize,
+            want_extra_isize: sb.want_extra_isize,
+            flags: sb.flags,
+            reserved: sb.reserved,
         })
     }
 }
@@ -214,7 +317,16 @@ impl SuperBlock {
 
     /// Returns the number of block groups.
     pub fn block_groups_count(&self) -> u32 {
-        self.blocks_count / self.blocks_per_group
+        (self.blocks_count - self.first_data_block.to_raw() as u32).div_ceil(self.blocks_per_group)
+    }
+
+    /// Returns the size of the group descriptor.
+    pub fn group_descriptor_size(&self) -> usize {
+        if self.feature_incompat.contains(FeatureInCompatSet::IS_64BIT) {
+            self.desc_size as usize
+        } else {
+            core::mem::size_of::<RawGroupDescriptor>()
+        }
     }
 
     /// Returns the filesystem state.
@@ -242,6 +354,59 @@ impl SuperBlock {
         self.feature_ro_compat
     }
 
+    /// Returns whether the filesystem can only be used as read-only, since it has
+    /// the features that are not supported for writing.
+    pub fn is_read_only(&self) -> bool {
+        self.feature_ro_compat
+            .intersects(FeatureRoCompatSet::SUPPORTED_READ_ONLY)
+    }
+
+    /// Returns whether the journal needs to be replayed.
+    pub fn needs_recovery(&self) -> bool {
+        self.feature_incompat.contains(FeatureInCompatSet::RECOVER)
+    }
+
+    /// Returns the number of blocks reserved for the privileged users.
+    pub fn reserved_blocks_count(&self) -> u32 {
+        self.reserved_blocks_count
+    }
+
+    /// Returns the 128-bit uuid of the volume.
+    pub fn uuid(&self) -> &[u8; 16] {
+        &self.uuid
+    }
+
+    /// Returns the inode number of the journal file.
+    ///
+    /// It is valid only if the `FeatureCompatSet::HAS_JOURNAL` is set.
+    pub fn journal_ino(&self) -> u32 {
+        self.journal_ino
+    }
+
+    /// Returns the seed used by the hash of the directory index.
+    pub fn hash_seed(&self) -> &[u32; 4] {
+        &self.hash_seed
+    }
+
+    /// Returns the default hash version used by the directory index.
+    pub fn def_hash_version(&self) -> u8 {
+        self.def_hash_version
+    }
+
+    /// Returns whether the hash of the directory index treats the names as unsigned chars.
+    pub fn is_hash_unsigned(&self) -> bool {
+        self.flags & FLAGS_UNSIGNED_HASH != 0
+    }
+
+    /// Marks that the journal of the filesystem needs to be recovered.
+    ///
+    /// The flag is set while the filesystem is mounted with a journal, so that
+    /// the journal will be replayed if the filesystem is not unmounted cleanly.
+    pub(super) fn set_needs_recovery(&mut self, needs_recovery: bool) {
+        self.feature_incompat
+            .set(FeatureInCompatSet::RECOVER, needs_recovery);
+    }
+
     /// Returns the number of free blocks.
     pub fn free_blocks_count(&self) -> u32 {
         self.free_blocks_count
@@ -337,6 +502,20 @@ bitflags! {
         const RESIZE_INO = 1 << 4;
         /// Directories use hash index
         const DIR_INDEX = 1 << 5;
+        /// Block groups are lazily initialized
+        const LAZY_BG = 1 << 6;
+        /// Snapshots exclude inode
+        const EXCLUDE_INODE = 1 << 7;
+        /// Snapshots exclude bitmap
+        const EXCLUDE_BITMAP = 1 << 8;
+        /// At most two backup superblocks are kept
+        const SPARSE_SUPER2 = 1 << 9;
+        /// File system supports fast commits in the journal
+        const FAST_COMMIT = 1 << 10;
+        /// Inode numbers are never changed
+        const STABLE_INODES = 1 << 11;
+        /// File system has an orphan file
+        const ORPHAN_FILE = 1 << 12;
     }
 }
 
@@ -353,6 +532,35 @@ bitflags! {
         const JOURNAL_DEV = 1 << 3;
         /// Metablock block group
         const META_BG = 1 << 4;
+        /// Files use extents
+        const EXTENTS = 1 << 6;
+        /// File system supports 64-bit block numbers
+        const IS_64BIT = 1 << 7;
+        /// Multiple mount protection
+        const MMP = 1 << 8;
+        /// The metadata of block groups are packed together
+        const FLEX_BG = 1 << 9;
+        /// Inodes can be used to store large extended attributes
+        const EA_INODE = 1 << 10;
+        /// Directory entries contain extra data
+        const DIRDATA = 1 << 12;
+        /// Metadata checksum seed is stored in the superblock
+        const CSUM_SEED = 1 << 13;
+        /// Directories can be larger than 2GB or use a 3-level htree
+        const LARGEDIR = 1 << 14;
+        /// Data can be stored in the inode
+        const INLINE_DATA = 1 << 15;
+        /// Encrypted inodes are present
+        const ENCRYPT = 1 << 16;
+        /// Directories can be case-insensitive
+        const CASEFOLD = 1 << 17;
+
+        /// The features supported by this implementation.
+        const SUPPORTED = Self::FILETYPE.bits
+            | Self::RECOVER.bits
+            | Self::EXTENTS.bits
+            | Self::IS_64BIT.bits
+            | Self::FLEX_BG.bits;
     }
 }
 
@@ -365,6 +573,41 @@ bitflags! {
         const LARGE_FILE = 1 << 1;
         /// Directory contents are stored in the form of a Binary Tree
         const BTREE_DIR = 1 << 2;
+        /// The number of blocks of files can be larger than 2^32 sectors
+        const HUGE_FILE = 1 << 3;
+        /// Group descriptors have checksums
+        const GDT_CSUM = 1 << 4;
+        /// Directories can have more than 65000 subdirectories
+        const DIR_NLINK = 1 << 5;
+        /// Inodes have extra space after the original structure
+        const EXTRA_ISIZE = 1 << 6;
+        /// Quota is stored in hidden inodes
+        const QUOTA = 1 << 8;
+        /// Blocks are allocated in clusters
+        const BIGALLOC = 1 << 9;
+        /// Metadata have checksums
+        const METADATA_CSUM = 1 << 10;
+        /// File system can only be mounted as readonly
+        const READONLY = 1 << 12;
+        /// Project quota is supported
+        const PROJECT = 1 << 13;
+        /// Verity inodes are present
+        const VERITY = 1 << 15;
+        /// The orphan file may be non-empty
+        const ORPHAN_PRESENT = 1 << 16;
+
+        /// The features supported by this implementation.
+        const SUPPORTED = Self::SPARSE_SUPER.bits
+            | Self::LARGE_FILE.bits
+            | Self::BTREE_DIR.bits
+            | Self::HUGE_FILE.bits
+            | Self::DIR_NLINK.bits
+            | Self::EXTRA_ISIZE.bits;
+        /// The features supported by this implementation only if the filesystem
+        /// is read-only, since the checksums are not updated on writes.
+        const SUPPORTED_READ_ONLY = Self::GDT_CSUM.bits
+            | Self::METADATA_CSUM.bits
+            | Self::READONLY.bits;
     }
 }
 
@@ -479,12 +722,27 @@ pub(super) struct RawSuperBlock {
     pub hash_seed: [u32; 4],
     /// Default hash version to use
     pub def_hash_version: u8,
-    reserved_char_pad: u8,
-    reserved_word_pad: u16,
+    pub journal_backup_type: u8,
+    /// Size of the group descriptor if the 64-bit feature is enabled.
+    pub desc_size: u16,
     /// Default mount options.
     pub default_mount_opts: u32,
     /// First metablock block group.
     pub first_meta_bg: u32,
+    ///
+    /// These fields are for the extended features in Ext4.
+    ///
+    /// When the filesystem was created.
+    pub mkfs_time: UnixTime,
+    /// Backup of the journal inode's block pointers and size.
+    pub journal_blocks: [u32; 17],
+    pub blocks_count_high: u32,
+    pub reserved_blocks_count_high: u32,
+    pub free_blocks_count_high: u32,
+    pub min_extra_isize: u16,
+    pub want_extra_isize: u16,
+    /// Miscellaneous flags.
+    pub flags: u32,
     reserved: Reserved,
 }
 
@@ -526,6 +784,23 @@ impl From<&SuperBlock> for RawSuperBlock {
             last_mounted_dir: sb.last_mounted_dir,
             prealloc_file_blocks: sb.prealloc_file_blocks,
             prealloc_dir_blocks: sb.prealloc_dir_blocks,
+            algorithm_usage_bitmap: sb.algorithm_usage_bitmap,
+            journal_uuid: sb.journal_uuid,
+            journal_ino: sb.journal_ino,
+            journal_dev: sb.journal_dev,
+            last_orphan: sb.last_orphan,
+            hash_seed: sb.hash_seed,
+            def_hash_version: sb.def_hash_version,
+            default_mount_opts: sb.default_mount_opts,
+            first_meta_bg: sb.first_meta_bg,
+            journal_backup_type: sb.journal_backup_type,
+            desc_size: sb.desc_size,
+            mkfs_time: sb.mkfs_time,
+            journal_blocks: sb.journal_blocks,
+            min_extra_isize: sb.min_extra_isize,
+            want_extra_isize: sb.want_extra_isize,
+            flags: sb.flags,
+            reserved: sb.reserved,
             ..Default::default()
         }
     }
@@ -533,10 +808,10 @@ impl From<&SuperBlock> for RawSuperBlock {
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod)]
-struct Reserved([u32; 190]);
+struct Reserved([u32; 167]);
 
 impl Default for Reserved {
     fn default() -> Self {
-        Self([0u32; 190])
+        Self([0u32; 167])
     }
 }
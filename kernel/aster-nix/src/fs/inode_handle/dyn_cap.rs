@@ -22,6 +22,10 @@ impl InodeHandle<Rights> {
         if access_mode.is_writable() && inode.type_() == InodeType::Dir {
             return_errno_with_message!(Errno::EISDIR, "Directory cannot open to write");
         }
+        // The device files can be written to even if they are on a read-only mount.
+        if access_mode.is_writable() && inode.type_() == InodeType::File {
+            dentry.check_writable_mount()?;
+        }
 
         let file_io = if let Some(device) = inode.as_device() {
             device.open()?
@@ -29,6 +33,7 @@ impl InodeHandle<Rights> {
             None
         };
 
+        dentry.notify_fs_events(FsEvents::OPEN);
         let inner = Arc::new(InodeHandle_ {
             dentry,
             file_io,
@@ -16,8 +16,8 @@ use crate::{
         device::Device,
         file_handle::FileLike,
         utils::{
-            AccessMode, Dentry, DirentVisitor, InodeMode, InodeType, IoctlCmd, Metadata, SeekFrom,
-            StatusFlags,
+            AccessMode, Dentry, DirentVisitor, FsEvents, InodeMode, InodeType, IoctlCmd, Metadata,
+            SeekFrom, StatusFlags,
         },
     },
     prelude::*,
@@ -53,6 +53,9 @@ impl InodeHandle_ {
         };
 
         *offset += len;
+        if len > 0 {
+            self.dentry.notify_fs_events(FsEvents::ACCESS);
+        }
         Ok(len)
     }
 
@@ -73,6 +76,9 @@ impl InodeHandle_ {
         };
 
         *offset += len;
+        if len > 0 {
+            self.dentry.notify_fs_events(FsEvents::MODIFY);
+        }
         Ok(len)
     }
 
@@ -180,6 +186,17 @@ impl InodeHandle_ {
     pub fn set_group(&self, gid: Gid) -> Result<()>;
 }
 
+impl Drop for InodeHandle_ {
+    fn drop(&mut self) {
+        let events = if self.access_mode.is_writable() {
+            FsEvents::CLOSE_WRITE
+        } else {
+            FsEvents::CLOSE_NOWRITE
+        };
+        self.dentry.notify_fs_events(events);
+    }
+}
+
 impl Debug for InodeHandle_ {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("InodeHandle_")
@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The inotify file.
+//!
+//! An inotify file watches inodes for file system changes. Each watch observes
+//! the events of an inode, no matter through which hard link it is reached. The
+//! events are queued in the file and read from it as `inotify_event` structures.
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use align_ext::AlignExt;
+
+use super::{
+    file_handle::FileLike,
+    utils::{Dentry, FsEvent, FsEvents, InodeMode, IoctlCmd, Metadata, StatusFlags},
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::signal::{Pollee, Poller},
+    util::write_val_to_user,
+};
+
+/// The maximum number of the queued events, which is the default value of Linux.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// The watch descriptor.
+pub type WatchDescriptor = i32;
+
+bitflags! {
+    /// The flags of `inotify_add_watch`, besides the events to watch.
+    pub struct InotifyFlags: u32 {
+        /// Watches the path only if it is a directory.
+        const IN_ONLYDIR       = 0x0100_0000;
+        /// Does not follow the path if it is a symbolic link.
+        const IN_DONT_FOLLOW   = 0x0200_0000;
+        /// Does not report the events of the children after they are unlinked.
+        ///
+        /// The events of a child are only reported to its directory while it is
+        /// linked, so this is always in effect.
+        const IN_EXCL_UNLINK   = 0x0400_0000;
+        /// Fails if the path is already watched.
+        const IN_MASK_CREATE   = 0x1000_0000;
+        /// Adds the events to the existing watch instead of replacing them.
+        const IN_MASK_ADD      = 0x2000_0000;
+        /// Removes the watch after the first event.
+        const IN_ONESHOT       = 0x8000_0000;
+    }
+}
+
+/// The events that can be watched.
+const WATCHABLE_EVENTS: FsEvents = FsEvents::ACCESS
+    .union(FsEvents::MODIFY)
+    .union(FsEvents::ATTRIB)
+    .union(FsEvents::CLOSE)
+    .union(FsEvents::OPEN)
+    .union(FsEvents::MOVE)
+    .union(FsEvents::CREATE)
+    .union(FsEvents::DELETE)
+    .union(FsEvents::DELETE_SELF)
+    .union(FsEvents::MOVE_SELF);
+
+pub struct InotifyFile {
+    inner: Mutex<InotifyInner>,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+    weak_self: Weak<Self>,
+}
+
+struct InotifyInner {
+    watches: BTreeMap<WatchDescriptor, Arc<Watch>>,
+    next_wd: WatchDescriptor,
+    events: VecDeque<InotifyEvent>,
+}
+
+impl InotifyFile {
+    pub fn new(is_nonblocking: bool) -> Arc<Self> {
+        Arc::new_cyclic(|weak_self| Self {
+            inner: Mutex::new(InotifyInner {
+                watches: BTreeMap::new(),
+                next_wd: 1,
+                events: VecDeque::new(),
+            }),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee: Pollee::new(IoEvents::empty()),
+            weak_self: weak_self.clone(),
+        })
+    }
+
+    /// Watches the events of the inode of the dentry, or modifies the existing watch
+    /// of the inode.
+    ///
+    /// Returns the descriptor of the watch.
+    pub fn add_watch(
+        &self,
+        dentry: Arc<Dentry>,
+        events: FsEvents,
+        flags: InotifyFlags,
+    ) -> Result<WatchDescriptor> {
+        let events = events & WATCHABLE_EVENTS;
+        if events.is_empty() {
+            return_errno_with_message!(Errno::EINVAL, "no event to watch");
+        }
+
+        let mut inner = self.inner.lock();
+        if let Some(watch) = inner
+            .watches
+            .values()
+            .find(|watch| watch.dentry.inode().is_same_inode(dentry.inode().as_ref()))
+        {
+            if flags.contains(InotifyFlags::IN_MASK_CREATE) {
+                return_errno_with_message!(Errno::EEXIST, "the path is already watched");
+            }
+            let (old_events, _) = watch.mask();
+            let events = if flags.contains(InotifyFlags::IN_MASK_ADD) {
+                old_events | events
+            } else {
+                events
+            };
+            watch.set_mask(events, flags);
+            return Ok(watch.wd);
+        }
+
+        let wd = inner.next_wd;
+        inner.next_wd = inner
+            .next_wd
+            .checked_add(1)
+            .ok_or_else(|| Error::with_message(Errno::ENOSPC, "too many watches"))?;
+        let watch = Arc::new(Watch {
+            wd,
+            dentry: dentry.clone(),
+            mask: AtomicU32::new(0),
+            inotify: self.weak_self.clone(),
+        });
+        watch.set_mask(events, flags);
+        inner.watches.insert(wd, watch.clone());
+        drop(inner);
+
+        // The subject of the inode is not locked with the inner lock held,
+        // since the inner lock is acquired when the subject notifies the watch.
+        dentry.register_fs_event_observer(Arc::downgrade(&watch) as _);
+        Ok(wd)
+    }
+
+    /// Removes the watch, for which an `IGNORED` event is queued.
+    pub fn remove_watch(&self, wd: WatchDescriptor) -> Result<()> {
+        let watch = {
+            let mut inner = self.inner.lock();
+            let watch = inner
+                .watches
+                .remove(&wd)
+                .ok_or_else(|| Error::with_message(Errno::EINVAL, "the watch does not exist"))?;
+            inner.queue_event(InotifyEvent::new(wd, FsEvents::IGNORED, 0, None));
+            watch
+        };
+        self.pollee.add_events(IoEvents::IN);
+
+        let observer = Arc::downgrade(&watch) as _;
+        watch.dentry.unregister_fs_event_observer(&observer);
+        Ok(())
+    }
+
+    fn handle_event(&self, watch: &Watch, event: &FsEvent) {
+        let (watched_events, flags) = watch.mask();
+        let events = event.events();
+        let reported_events = events & watched_events;
+        // The watch is removed automatically if the watched inode is deleted.
+        let is_deleted = events.contains(FsEvents::DELETE_SELF);
+
+        let mut inner = self.inner.lock();
+        if !inner.watches.contains_key(&watch.wd) {
+            return;
+        }
+        if !reported_events.is_empty() {
+            let events = reported_events | (events & FsEvents::ISDIR);
+            let name = event.name().map(String::from);
+            inner.queue_event(InotifyEvent::new(watch.wd, events, event.cookie(), name));
+        }
+        let is_oneshot = flags.contains(InotifyFlags::IN_ONESHOT) && !reported_events.is_empty();
+        if is_deleted || is_oneshot {
+            // The watch cannot be unregistered from the inode here, since the
+            // subject of the inode is being locked. It is unregistered when the
+            // subject finds the watch freed.
+            inner.watches.remove(&watch.wd);
+            inner.queue_event(InotifyEvent::new(watch.wd, FsEvents::IGNORED, 0, None));
+        }
+        let has_events = !inner.events.is_empty();
+        drop(inner);
+
+        if has_events {
+            self.pollee.add_events(IoEvents::IN);
+        }
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn try_read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut inner = self.inner.lock();
+        if inner.events.is_empty() {
+            return_errno_with_message!(Errno::EAGAIN, "no event is queued");
+        }
+
+        let mut read_len = 0;
+        while let Some(event) = inner.events.front() {
+            let event_len = event.len();
+            if read_len + event_len > buf.len() {
+                break;
+            }
+            event.write_to(&mut buf[read_len..read_len + event_len]);
+            read_len += event_len;
+            inner.events.pop_front();
+        }
+        if inner.events.is_empty() {
+            self.pollee.del_events(IoEvents::IN);
+        }
+
+        if read_len == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+        Ok(read_len)
+    }
+}
+
+impl InotifyInner {
+    fn queue_event(&mut self, event: InotifyEvent) {
+        // Like Linux, an event identical to the last one is merged into it.
+        if self.events.back() == Some(&event) {
+            return;
+        }
+
+        if self.events.len() >= MAX_QUEUED_EVENTS {
+            let overflow = InotifyEvent::new(-1, FsEvents::Q_OVERFLOW, 0, None);
+            if self.events.back() != Some(&overflow) {
+                self.events.push_back(overflow);
+            }
+            return;
+        }
+        self.events.push_back(event);
+    }
+}
+
+impl FileLike for InotifyFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        if self.is_nonblocking() {
+            return self.try_read(buf);
+        }
+
+        let poller = Poller::new();
+        loop {
+            match self.try_read(buf) {
+                Err(err) if err.error() == Errno::EAGAIN => (),
+                result => return result,
+            };
+
+            let events = self.poll(IoEvents::IN, Some(&poller));
+            if !events.is_empty() {
+                continue;
+            }
+
+            poller.wait()?;
+        }
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        match cmd {
+            IoctlCmd::FIONREAD => {
+                let len: usize = self
+                    .inner
+                    .lock()
+                    .events
+                    .iter()
+                    .map(|event| event.len())
+                    .sum();
+                write_val_to_user(arg, &(len as i32))?;
+                Ok(0)
+            }
+            _ => return_errno_with_message!(Errno::EINVAL, "ioctl is not supported"),
+        }
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.pollee.poll(mask, poller)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking() {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata::new_anonymous(InodeMode::from_bits_truncate(0o600))
+    }
+
+    fn register_observer(
+        &self,
+        observer: Weak<dyn Observer<IoEvents>>,
+        mask: IoEvents,
+    ) -> Result<()> {
+        self.pollee.register_observer(observer, mask);
+        Ok(())
+    }
+
+    fn unregister_observer(
+        &self,
+        observer: &Weak<dyn Observer<IoEvents>>,
+    ) -> Result<Weak<dyn Observer<IoEvents>>> {
+        self.pollee
+            .unregister_observer(observer)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "observer is not registered"))
+    }
+}
+
+impl Drop for InotifyFile {
+    fn drop(&mut self) {
+        let watches = core::mem::take(&mut self.inner.lock().watches);
+        for watch in watches.values() {
+            let observer = Arc::downgrade(watch) as _;
+            watch.dentry.unregister_fs_event_observer(&observer);
+        }
+    }
+}
+
+/// A watch of the events on the inode of a dentry.
+struct Watch {
+    wd: WatchDescriptor,
+    dentry: Arc<Dentry>,
+    /// The bits of the watched events and the flags.
+    mask: AtomicU32,
+    inotify: Weak<InotifyFile>,
+}
+
+impl Watch {
+    fn mask(&self) -> (FsEvents, InotifyFlags) {
+        let mask = self.mask.load(Ordering::Relaxed);
+        (
+            FsEvents::from_bits_truncate(mask),
+            InotifyFlags::from_bits_truncate(mask),
+        )
+    }
+
+    fn set_mask(&self, events: FsEvents, flags: InotifyFlags) {
+        self.mask
+            .store(events.bits() | flags.bits(), Ordering::Relaxed);
+    }
+}
+
+impl Observer<FsEvent> for Watch {
+    fn on_events(&self, event: &FsEvent) {
+        if let Some(inotify) = self.inotify.upgrade() {
+            inotify.handle_event(self, event);
+        }
+    }
+}
+
+/// A queued event.
+#[derive(Debug, PartialEq, Eq)]
+struct InotifyEvent {
+    wd: WatchDescriptor,
+    events: FsEvents,
+    cookie: u32,
+    name: Option<String>,
+}
+
+/// The `inotify_event` structure of Linux, which is followed by the name.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct c_inotify_event {
+    wd: i32,
+    mask: u32,
+    cookie: u32,
+    len: u32,
+}
+
+impl InotifyEvent {
+    fn new(wd: WatchDescriptor, events: FsEvents, cookie: u32, name: Option<String>) -> Self {
+        Self {
+            wd,
+            events,
+            cookie,
+            name,
+        }
+    }
+
+    /// Returns the length of the name field, which is null-terminated and padded
+    /// to the size of the header.
+    fn name_len(&self) -> usize {
+        let align = core::mem::size_of::<c_inotify_event>();
+        match &self.name {
+            Some(name) => (name.len() + 1).align_up(align),
+            None => 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        core::mem::size_of::<c_inotify_event>() + self.name_len()
+    }
+
+    fn write_to(&self, buf: &mut [u8]) {
+        let header = c_inotify_event {
+            wd: self.wd,
+            mask: self.events.bits(),
+            cookie: self.cookie,
+            len: self.name_len() as u32,
+        };
+        let header_len = core::mem::size_of::<c_inotify_event>();
+        buf[..header_len].copy_from_slice(header.as_bytes());
+
+        let name_buf = &mut buf[header_len..];
+        name_buf.fill(0);
+        if let Some(name) = &self.name {
+            name_buf[..name.len()].copy_from_slice(name.as_bytes());
+        }
+    }
+}
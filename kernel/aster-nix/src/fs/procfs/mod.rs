@@ -3,6 +3,7 @@
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use self::{
+    mounts::MountsSymOps,
     pid::PidDirOps,
     self_::SelfSymOps,
     template::{DirOps, ProcDir, ProcDirBuilder, ProcSymBuilder, SymOps},
@@ -14,6 +15,7 @@ use crate::{
     process::{process_table, process_table::PidEvent, Pid},
 };
 
+mod mounts;
 mod pid;
 mod self_;
 mod template;
@@ -53,6 +55,10 @@ impl ProcFS {
 }
 
 impl FileSystem for ProcFS {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+
     fn sync(&self) -> Result<()> {
         Ok(())
     }
@@ -94,6 +100,8 @@ impl DirOps for RootDirOps {
     fn lookup_child(&self, this_ptr: Weak<dyn Inode>, name: &str) -> Result<Arc<dyn Inode>> {
         let child = if name == "self" {
             SelfSymOps::new_inode(this_ptr.clone())
+        } else if name == "mounts" {
+            MountsSymOps::new_inode(this_ptr.clone())
         } else if let Ok(pid) = name.parse::<Pid>() {
             let process_ref =
                 process_table::get_process(&pid).ok_or_else(|| Error::new(Errno::ENOENT))?;
@@ -111,6 +119,8 @@ impl DirOps for RootDirOps {
         };
         let mut cached_children = this.cached_children().write();
         cached_children.put_entry_if_not_found("self", || SelfSymOps::new_inode(this_ptr.clone()));
+        cached_children
+            .put_entry_if_not_found("mounts", || MountsSymOps::new_inode(this_ptr.clone()));
 
         for process in process_table::process_table().iter() {
             let pid = process.pid().to_string();
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::*;
+
+/// Represents the inode at `/proc/mounts`, which is a symlink to `self/mounts`
+/// like Linux.
+pub struct MountsSymOps;
+
+impl MountsSymOps {
+    pub fn new_inode(parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcSymBuilder::new(Self).parent(parent).build().unwrap()
+    }
+}
+
+impl SymOps for MountsSymOps {
+    fn read_link(&self) -> Result<String> {
+        Ok(String::from("self/mounts"))
+    }
+}
@@ -1,6 +1,12 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use self::{comm::CommFileOps, exe::ExeSymOps, fd::FdDirOps};
+use self::{
+    comm::CommFileOps,
+    exe::ExeSymOps,
+    fd::FdDirOps,
+    mounts::{MountInfoFileOps, MountsFileOps},
+    oom_score::{OomScoreAdjFileOps, OomScoreFileOps},
+};
 use super::template::{
     DirOps, FileOps, ProcDir, ProcDirBuilder, ProcFileBuilder, ProcSymBuilder, SymOps,
 };
@@ -17,6 +23,8 @@ use crate::{
 mod comm;
 mod exe;
 mod fd;
+mod mounts;
+mod oom_score;
 
 /// Represents the inode at `/proc/[pid]`.
 pub struct PidDirOps(Arc<Process>);
@@ -51,6 +59,10 @@ impl DirOps for PidDirOps {
             "exe" => ExeSymOps::new_inode(self.0.clone(), this_ptr.clone()),
             "comm" => CommFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             "fd" => FdDirOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "mounts" => MountsFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "mountinfo" => MountInfoFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "oom_score" => OomScoreFileOps::new_inode(self.0.clone(), this_ptr.clone()),
+            "oom_score_adj" => OomScoreAdjFileOps::new_inode(self.0.clone(), this_ptr.clone()),
             _ => return_errno!(Errno::ENOENT),
         };
         Ok(inode)
@@ -70,6 +82,18 @@ impl DirOps for PidDirOps {
         });
         cached_children.put_entry_if_not_found("fd", || {
             FdDirOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("mounts", || {
+            MountsFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("mountinfo", || {
+            MountInfoFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("oom_score", || {
+            OomScoreFileOps::new_inode(self.0.clone(), this_ptr.clone())
+        });
+        cached_children.put_entry_if_not_found("oom_score_adj", || {
+            OomScoreAdjFileOps::new_inode(self.0.clone(), this_ptr.clone())
         })
     }
 }
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::fmt::Write;
+
+use super::*;
+use crate::fs::{
+    device::DeviceId,
+    rootfs::root_mount,
+    utils::{MountFlags, MountNode},
+};
+
+/// Represents the inode at `/proc/[pid]/mounts`.
+pub struct MountsFileOps(Arc<Process>);
+
+impl MountsFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MountsFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for (mount_node, mount_path) in visible_mounts(&self.0) {
+            writeln!(
+                output,
+                "{} {} {} {} 0 0",
+                escape(mount_node.source()),
+                escape(&mount_path),
+                mount_node.fs().name(),
+                mount_options(mount_node.flags()),
+            )
+            .unwrap();
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+/// Represents the inode at `/proc/[pid]/mountinfo`.
+pub struct MountInfoFileOps(Arc<Process>);
+
+impl MountInfoFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for MountInfoFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let mut output = String::new();
+        for (mount_node, mount_path) in visible_mounts(&self.0) {
+            // The root mount has no parent, so its own ID is shown like Linux.
+            let parent_id = mount_node
+                .parent()
+                .map_or(mount_node.id(), |parent| parent.id());
+            let device_id = DeviceId::from(mount_node.root_dentry().metadata().dev);
+            writeln!(
+                output,
+                "{} {} {}:{} {} {} {} - {} {} rw",
+                mount_node.id(),
+                parent_id,
+                device_id.major(),
+                device_id.minor(),
+                escape(mount_node.root_path()),
+                escape(&mount_path),
+                mount_options(mount_node.flags()),
+                mount_node.fs().name(),
+                escape(mount_node.source()),
+            )
+            .unwrap();
+        }
+        Ok(output.into_bytes())
+    }
+}
+
+/// Returns the mounts that are visible to the process, with their paths relative to
+/// the root directory of the process.
+///
+/// The mounts are listed in the preorder of the mount tree, so that a mount always
+/// appears before the mounts on its dentries.
+fn visible_mounts(process: &Process) -> Vec<(Arc<MountNode>, String)> {
+    let root_path = process.fs().read().root().abs_path();
+
+    let mut mounts = Vec::new();
+    let mut stack = vec![root_mount().clone()];
+    while let Some(mount_node) = stack.pop() {
+        let abs_path = mount_node.root_dentry().abs_path();
+        let mount_path = if root_path == "/" {
+            Some(abs_path)
+        } else if abs_path == root_path {
+            Some(String::from("/"))
+        } else {
+            abs_path
+                .strip_prefix(&root_path)
+                .filter(|path| path.starts_with('/'))
+                .map(String::from)
+        };
+        stack.extend(mount_node.children().into_iter().rev());
+        if let Some(mount_path) = mount_path {
+            mounts.push((mount_node, mount_path));
+        }
+    }
+    mounts
+}
+
+fn mount_options(flags: MountFlags) -> String {
+    let mut options = String::from(if flags.contains(MountFlags::RDONLY) {
+        "ro"
+    } else {
+        "rw"
+    });
+    if flags.contains(MountFlags::NOSUID) {
+        options.push_str(",nosuid");
+    }
+    if flags.contains(MountFlags::NOEXEC) {
+        options.push_str(",noexec");
+    }
+    options
+}
+
+/// Escapes the whitespaces and backslashes with octal sequences, like Linux.
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => write!(escaped, "\\{:03o}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,103 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use alloc::format;
+use core::sync::atomic::Ordering;
+
+use super::*;
+use crate::{
+    process::{credentials, posix_thread::PosixThreadExt},
+    vm::oom::{oom_score, OOM_SCORE_ADJ_MAX, OOM_SCORE_ADJ_MIN},
+};
+
+/// Represents the inode at `/proc/[pid]/oom_score`.
+pub struct OomScoreFileOps(Arc<Process>);
+
+impl OomScoreFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for OomScoreFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(format!("{}\n", oom_score(&self.0)).into_bytes())
+    }
+}
+
+/// Represents the inode at `/proc/[pid]/oom_score_adj`.
+pub struct OomScoreAdjFileOps(Arc<Process>);
+
+impl OomScoreAdjFileOps {
+    pub fn new_inode(process_ref: Arc<Process>, parent: Weak<dyn Inode>) -> Arc<dyn Inode> {
+        ProcFileBuilder::new(Self(process_ref))
+            .parent(parent)
+            .build()
+            .unwrap()
+    }
+}
+
+impl FileOps for OomScoreAdjFileOps {
+    fn data(&self) -> Result<Vec<u8>> {
+        let oom_score_adj = self.0.oom_score_adj().load(Ordering::Relaxed);
+        Ok(format!("{}\n", oom_score_adj).into_bytes())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        check_access_perm(&self.0)?;
+
+        let oom_score_adj = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|value| value.trim().parse::<i16>().ok())
+            .filter(|value| (OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(value))
+            .ok_or(Error::with_message(
+                Errno::EINVAL,
+                "invalid oom_score_adj value",
+            ))?;
+
+        // Only root can make the process less likely to be killed.
+        let old_oom_score_adj = self.0.oom_score_adj().load(Ordering::Relaxed);
+        if oom_score_adj < old_oom_score_adj && !credentials().euid().is_root() {
+            return_errno_with_message!(Errno::EACCES, "cannot decrease oom_score_adj");
+        }
+        self.0
+            .oom_score_adj()
+            .store(oom_score_adj, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+}
+
+/// Checks whether the current process is allowed to change the attributes of the process.
+///
+/// Like the ptrace access check in Linux, the current process must either be privileged,
+/// or its effective user ID must equal the real, effective and saved user IDs of the process.
+fn check_access_perm(process: &Process) -> Result<()> {
+    let euid = credentials().euid();
+    if euid.is_root() {
+        return Ok(());
+    }
+
+    let main_thread = process
+        .main_thread()
+        .ok_or(Error::with_message(Errno::ESRCH, "the process has exited"))?;
+    if let Some(posix_thread) = main_thread.as_posix_thread() {
+        let target_credentials = posix_thread.credentials();
+        if euid == target_credentials.ruid()
+            && euid == target_credentials.euid()
+            && euid == target_credentials.suid()
+        {
+            return Ok(());
+        }
+    }
+
+    return_errno_with_message!(
+        Errno::EACCES,
+        "changing the attributes of the process is not allowed"
+    );
+}
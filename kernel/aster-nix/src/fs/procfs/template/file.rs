@@ -20,9 +20,10 @@ impl<F: FileOps> ProcFile<F> {
     pub fn new(file: F, fs: Arc<dyn FileSystem>, is_volatile: bool) -> Arc<Self> {
         let common = {
             let procfs = fs.downcast_ref::<ProcFS>().unwrap();
+            let mode = if file.is_writable() { 0o644 } else { 0o444 };
             let metadata = Metadata::new_file(
                 procfs.alloc_id(),
-                InodeMode::from_bits_truncate(0o444),
+                InodeMode::from_bits_truncate(mode),
                 &fs.sb(),
             );
             Common::new(metadata, Arc::downgrade(&fs), is_volatile)
@@ -52,6 +53,10 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
     fn fs(&self) -> Arc<dyn FileSystem>;
 
     fn resize(&self, _new_size: usize) -> Result<()> {
+        // Opening a writable file with `O_TRUNC` should succeed.
+        if self.inner.is_writable() {
+            return Ok(());
+        }
         Err(Error::new(Errno::EPERM))
     }
 
@@ -72,12 +77,13 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
         self.read_at(offset, buf)
     }
 
-    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        // The data is always written as a whole.
+        self.inner.write(buf)
     }
 
-    fn write_direct_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
-        Err(Error::new(Errno::EPERM))
+    fn write_direct_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.write_at(offset, buf)
     }
 
     fn read_link(&self) -> Result<String> {
@@ -103,4 +109,14 @@ impl<F: FileOps + 'static> Inode for ProcFile<F> {
 
 pub trait FileOps: Sync + Send {
     fn data(&self) -> Result<Vec<u8>>;
+
+    /// Updates the data with the written buffer, returning the number of bytes written.
+    fn write(&self, _buf: &[u8]) -> Result<usize> {
+        Err(Error::new(Errno::EPERM))
+    }
+
+    /// Returns whether the data can be written, i.e., `write` is implemented.
+    fn is_writable(&self) -> bool {
+        false
+    }
 }
@@ -19,8 +19,8 @@ use crate::{
     fs::{
         device::Device,
         utils::{
-            CStr256, DirentVisitor, FileSystem, FsFlags, Inode, InodeMode, InodeType, IoctlCmd,
-            Metadata, PageCache, PageCacheBackend, SuperBlock,
+            CStr256, DirentVisitor, FileSeals, FileSystem, FsFlags, Inode, InodeMode, InodeType,
+            IoctlCmd, Metadata, PageCache, PageCacheBackend, SuperBlock,
         },
     },
     prelude::*,
@@ -67,6 +67,10 @@ impl RamFS {
 }
 
 impl FileSystem for RamFS {
+    fn name(&self) -> &'static str {
+        "ramfs"
+    }
+
     fn sync(&self) -> Result<()> {
         // do nothing
         Ok(())
@@ -85,13 +89,16 @@ impl FileSystem for RamFS {
     }
 }
 
-struct RamInode(RwMutex<Inode_>);
+pub(super) struct RamInode(RwMutex<Inode_>);
 
 struct Inode_ {
     inner: Inner,
     metadata: Metadata,
     this: Weak<RamInode>,
     fs: Weak<RamFS>,
+    /// The seals of the file. Sealing is not allowed unless the file is created
+    /// by `memfd_create` with `MFD_ALLOW_SEALING`.
+    seals: FileSeals,
 }
 
 impl Inode_ {
@@ -101,6 +108,7 @@ impl Inode_ {
             metadata: Metadata::new_dir(ino, mode, sb),
             this: Weak::default(),
             fs: Weak::default(),
+            seals: FileSeals::F_SEAL_SEAL,
         }
     }
 
@@ -115,6 +123,7 @@ impl Inode_ {
             metadata: Metadata::new_file(ino, mode, sb),
             this: Weak::default(),
             fs: Weak::default(),
+            seals: FileSeals::F_SEAL_SEAL,
         }
     }
 
@@ -124,6 +133,7 @@ impl Inode_ {
             metadata: Metadata::new_symlink(ino, mode, sb),
             this: Weak::default(),
             fs: Weak::default(),
+            seals: FileSeals::F_SEAL_SEAL,
         }
     }
 
@@ -133,6 +143,7 @@ impl Inode_ {
             metadata: Metadata::new_socket(ino, mode, sb),
             this: Weak::default(),
             fs: Weak::default(),
+            seals: FileSeals::F_SEAL_SEAL,
         }
     }
 
@@ -147,6 +158,7 @@ impl Inode_ {
             inner: Inner::Device(device),
             this: Weak::default(),
             fs: Weak::default(),
+            seals: FileSeals::F_SEAL_SEAL,
         }
     }
 
@@ -405,6 +417,11 @@ impl RamInode {
         })
     }
 
+    /// Allows the file to be sealed, which is used by the files created by `memfd_create`.
+    pub(super) fn allow_sealing(&self) {
+        self.0.write().seals.remove(FileSeals::F_SEAL_SEAL);
+    }
+
     fn new_device(fs: &Arc<RamFS>, mode: InodeMode, device: Arc<dyn Device>) -> Arc<Self> {
         Arc::new_cyclic(|weak_self| {
             let inode = RamInode(RwMutex::new(Inode_::new_device(
@@ -480,9 +497,16 @@ impl Inode for RamInode {
         let Some(page_cache) = self_inode.inner.as_file() else {
             return_errno_with_message!(Errno::EISDIR, "write is not supported");
         };
+        let seals = self_inode.seals;
+        if seals.intersects(FileSeals::F_SEAL_WRITE | FileSeals::F_SEAL_FUTURE_WRITE) {
+            return_errno_with_message!(Errno::EPERM, "the file is sealed against writing");
+        }
         let file_size = self_inode.metadata.size;
         let new_size = offset + buf.len();
         let should_expand_size = new_size > file_size;
+        if should_expand_size && seals.contains(FileSeals::F_SEAL_GROW) {
+            return_errno_with_message!(Errno::EPERM, "the file is sealed against growing");
+        }
         if should_expand_size {
             page_cache.pages().resize(new_size)?;
         }
@@ -513,6 +537,13 @@ impl Inode for RamInode {
         if file_size == new_size {
             return Ok(());
         }
+        let seals = self_inode.seals;
+        if new_size < file_size && seals.contains(FileSeals::F_SEAL_SHRINK) {
+            return_errno_with_message!(Errno::EPERM, "the file is sealed against shrinking");
+        }
+        if new_size > file_size && seals.contains(FileSeals::F_SEAL_GROW) {
+            return_errno_with_message!(Errno::EPERM, "the file is sealed against growing");
+        }
 
         let mut self_inode = self_inode.upgrade();
         self_inode.resize(new_size);
@@ -919,6 +950,29 @@ impl Inode for RamInode {
         Weak::upgrade(&self.0.read().fs).unwrap()
     }
 
+    fn seals(&self) -> Result<FileSeals> {
+        let self_inode = self.0.read();
+        if self_inode.inner.as_file().is_none() {
+            return_errno_with_message!(Errno::EINVAL, "only regular files can be sealed");
+        }
+        Ok(self_inode.seals)
+    }
+
+    fn add_seals(&self, seals: FileSeals, check: &dyn Fn(FileSeals) -> Result<()>) -> Result<()> {
+        // The upgradeable lock keeps out the other sealing operations during the check,
+        // but still allows the readers of the inode (e.g., the page faults).
+        let self_inode = self.0.upread();
+        if self_inode.inner.as_file().is_none() {
+            return_errno_with_message!(Errno::EINVAL, "only regular files can be sealed");
+        }
+        if self_inode.seals.contains(FileSeals::F_SEAL_SEAL) {
+            return_errno_with_message!(Errno::EPERM, "the file is sealed against sealing");
+        }
+        check(self_inode.seals)?;
+        self_inode.upgrade().seals |= seals;
+        Ok(())
+    }
+
     fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
         if let Some(device) = self.0.read().inner.as_device() {
             return device.ioctl(cmd, arg);
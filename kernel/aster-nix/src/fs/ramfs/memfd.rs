@@ -0,0 +1,55 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Anonymous files created by `memfd_create`.
+//!
+//! A memfd is a regular file in an internal RamFS, which is unlinked as soon as
+//! it is created. So it behaves like a RamFS file (e.g., it can be mapped with
+//! its page cache), but it can only be accessed through its file descriptors.
+
+use spin::Once;
+
+use super::{fs::RamInode, RamFS};
+use crate::{
+    fs::{
+        inode_handle::InodeHandle,
+        utils::{AccessMode, InodeMode, InodeType, MountNode, StatusFlags},
+    },
+    prelude::*,
+};
+
+/// The prefix of the names of memfds, which is shown in `/proc/[pid]/fd`.
+const MEMFD_NAME_PREFIX: &str = "memfd:";
+
+/// The internal RamFS that holds the memfds.
+static MEMFD_MOUNT: Once<Arc<MountNode>> = Once::new();
+/// The lock to create a memfd and unlink it atomically, so that the memfds with
+/// the same name never collide.
+static MEMFD_CREATION_LOCK: Mutex<()> = Mutex::new(());
+
+/// Creates a memfd with the name, returning the opened file.
+///
+/// If `allow_sealing` is false, the memfd is sealed with `F_SEAL_SEAL`, so that
+/// no seals can be added.
+pub fn create_memfd(name: &str, allow_sealing: bool) -> Result<InodeHandle> {
+    let root_dentry = MEMFD_MOUNT
+        .call_once(|| MountNode::new_root(RamFS::new()))
+        .root_dentry();
+    let name = String::from(MEMFD_NAME_PREFIX) + name;
+
+    let dentry = {
+        let _guard = MEMFD_CREATION_LOCK.lock();
+        let dentry =
+            root_dentry.create(&name, InodeType::File, InodeMode::from_bits_truncate(0o777))?;
+        root_dentry.unlink(&name)?;
+        dentry
+    };
+    if allow_sealing {
+        dentry
+            .inode()
+            .downcast_ref::<RamInode>()
+            .unwrap()
+            .allow_sealing();
+    }
+
+    InodeHandle::new(dentry, AccessMode::O_RDWR, StatusFlags::empty())
+}
@@ -3,8 +3,10 @@
 //! Ramfs based on PageCache
 
 pub use fs::RamFS;
+pub use memfd::create_memfd;
 
 mod fs;
+mod memfd;
 
 const RAMFS_MAGIC: u64 = 0x0102_1994;
 const BLOCK_SIZE: usize = 4096;
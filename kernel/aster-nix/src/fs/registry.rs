@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The registry of file system types.
+//!
+//! A file system type maps a name (e.g., "ext2") to a constructor, which creates
+//! a file system from a block device and a mount option string. The `mount`
+//! system call looks up the file system types here, so the user space decides
+//! which file systems are mounted.
+//!
+//! Like Linux, a block device holds at most one file system at a time. Mounting
+//! the device again shares the file system, since two instances with their own
+//! caches and allocators would corrupt the device.
+
+use aster_block::BlockDevice;
+use aster_virtio::device::block::device::BlockDevice as VirtIoBlockDevice;
+
+use super::{
+    devpts::DevPts,
+    exfat::{ExfatFS, ExfatMountOptions},
+    ext2::Ext2,
+    procfs::ProcFS,
+    ramfs::RamFS,
+    utils::FileSystem,
+};
+use crate::{prelude::*, thread::kernel_thread::KernelThreadExt};
+
+/// The constructor of a file system type.
+///
+/// The block device is `Some` if and only if the file system type requires a device.
+pub type FsCreator = fn(Option<Arc<dyn BlockDevice>>, &str) -> Result<Arc<dyn FileSystem>>;
+
+struct FsType {
+    requires_device: bool,
+    creator: FsCreator,
+}
+
+static FS_TYPES: RwLock<BTreeMap<&'static str, FsType>> = RwLock::new(BTreeMap::new());
+
+/// The names of the block devices whose request handling threads have been started.
+static STARTED_DEVICES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The file systems created on the block devices, keyed by the device names.
+static DEVICE_FILE_SYSTEMS: Mutex<BTreeMap<String, DeviceFs>> = Mutex::new(BTreeMap::new());
+
+struct DeviceFs {
+    fs_type: String,
+    fs: Weak<dyn FileSystem>,
+}
+
+/// Registers a file system type.
+///
+/// If `requires_device` is true, the file system is created on the block device
+/// specified by the source of the mount.
+pub fn register_fs_type(
+    name: &'static str,
+    requires_device: bool,
+    creator: FsCreator,
+) -> Result<()> {
+    let mut fs_types = FS_TYPES.write();
+    if fs_types.contains_key(name) {
+        return_errno_with_message!(Errno::EBUSY, "the file system type has been registered");
+    }
+    fs_types.insert(
+        name,
+        FsType {
+            requires_device,
+            creator,
+        },
+    );
+    Ok(())
+}
+
+/// Creates a file system of the type from the source and the mount options.
+///
+/// If the file system type requires a device and the device already holds a file
+/// system of the same type, the existing file system is returned.
+pub fn create_fs(fs_type: &str, source: &str, options: &str) -> Result<Arc<dyn FileSystem>> {
+    let (requires_device, creator) = {
+        let fs_types = FS_TYPES.read();
+        let fs_type = fs_types
+            .get(fs_type)
+            .ok_or_else(|| Error::with_message(Errno::ENODEV, "unknown file system type"))?;
+        (fs_type.requires_device, fs_type.creator)
+    };
+    if !requires_device {
+        return creator(None, options);
+    }
+
+    let device_name = device_name_of(source)?;
+    let mut device_file_systems = DEVICE_FILE_SYSTEMS.lock();
+    device_file_systems.retain(|_, device_fs| device_fs.fs.strong_count() > 0);
+    if let Some(device_fs) = device_file_systems.get(device_name)
+        && let Some(fs) = device_fs.fs.upgrade()
+    {
+        if device_fs.fs_type != fs_type {
+            return_errno_with_message!(
+                Errno::EBUSY,
+                "the block device holds a file system of another type"
+            );
+        }
+        return Ok(fs);
+    }
+
+    let fs = creator(Some(open_block_device(source)?), options)?;
+    device_file_systems.insert(
+        String::from(device_name),
+        DeviceFs {
+            fs_type: String::from(fs_type),
+            fs: Arc::downgrade(&fs),
+        },
+    );
+    Ok(fs)
+}
+
+/// Returns the name of the block device, which may be prefixed with "/dev/".
+fn device_name_of(source: &str) -> Result<&str> {
+    // The device name is specified in the QEMU arguments as `serial={device_name}`.
+    let device_name = source.strip_prefix("/dev/").unwrap_or(source);
+    if device_name.is_empty() {
+        return_errno_with_message!(Errno::ENOTBLK, "the source is not a block device");
+    }
+    Ok(device_name)
+}
+
+/// Opens the block device by its name, which may be prefixed with "/dev/".
+///
+/// The request handling thread of the device is started when the device is
+/// opened for the first time.
+pub(crate) fn open_block_device(source: &str) -> Result<Arc<dyn BlockDevice>> {
+    let device_name = device_name_of(source)?;
+    let Some(device) = aster_block::get_device(device_name) else {
+        return_errno_with_message!(Errno::ENXIO, "the block device does not exist");
+    };
+
+    let mut started_devices = STARTED_DEVICES.lock();
+    if !started_devices.contains(device_name) {
+        let cloned_device = device.clone();
+        let task_fn = move || {
+            info!("spawn the virt-io-block thread");
+            let virtio_block_device = cloned_device.downcast_ref::<VirtIoBlockDevice>().unwrap();
+            loop {
+                virtio_block_device.handle_requests();
+            }
+        };
+        crate::Thread::spawn_kernel_thread(crate::ThreadOptions::new(task_fn));
+        started_devices.insert(String::from(device_name));
+    }
+    Ok(device)
+}
+
+/// Registers the built-in file system types.
+pub fn init() {
+    register_fs_type("ext2", true, |device, _| Ok(Ext2::open(device.unwrap())?)).unwrap();
+    register_fs_type("exfat", true, |device, options| {
+        Ok(ExfatFS::open(
+            device.unwrap(),
+            ExfatMountOptions::parse(options)?,
+        )?)
+    })
+    .unwrap();
+    register_fs_type("ramfs", false, |_, _| Ok(RamFS::new())).unwrap();
+    register_fs_type("tmpfs", false, |_, _| Ok(RamFS::new())).unwrap();
+    register_fs_type("proc", false, |_, _| Ok(ProcFS::new())).unwrap();
+    register_fs_type("devpts", false, |_, _| Ok(DevPts::new())).unwrap();
+}
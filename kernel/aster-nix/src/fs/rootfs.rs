@@ -9,7 +9,7 @@ use super::{
     fs_resolver::{FsPath, FsResolver},
     procfs::ProcFS,
     ramfs::RamFS,
-    utils::{FileSystem, InodeMode, InodeType, MountNode},
+    utils::{FileSystem, InodeMode, InodeType, MountFlags, MountNode},
 };
 use crate::prelude::*;
 
@@ -76,10 +76,17 @@ pub fn init(initramfs_buf: &[u8]) -> Result<()> {
     }
     // Mount ProcFS
     let proc_dentry = fs.lookup(&FsPath::try_from("/proc")?)?;
-    proc_dentry.mount(ProcFS::new())?;
+    proc_dentry.mount(ProcFS::new(), "proc", MountFlags::empty())?;
     // Mount DevFS
     let dev_dentry = fs.lookup(&FsPath::try_from("/dev")?)?;
-    dev_dentry.mount(RamFS::new())?;
+    dev_dentry.mount(RamFS::new(), "dev", MountFlags::empty())?;
+    // Mount the tmpfs for POSIX shared memory
+    let shm_dentry = fs.lookup(&FsPath::try_from("/dev")?)?.create(
+        "shm",
+        InodeType::Dir,
+        InodeMode::from_bits_truncate(0o1777),
+    )?;
+    shm_dentry.mount(RamFS::new(), "shm", MountFlags::empty())?;
 
     println!("[kernel] rootfs is ready");
 
@@ -88,7 +95,8 @@ pub fn init(initramfs_buf: &[u8]) -> Result<()> {
 
 pub fn mount_fs_at(fs: Arc<dyn FileSystem>, fs_path: &FsPath) -> Result<()> {
     let target_dentry = FsResolver::new().lookup(fs_path)?;
-    target_dentry.mount(fs)?;
+    let source = String::from(fs.name());
+    target_dentry.mount(fs, &source, MountFlags::empty())?;
     Ok(())
 }
 
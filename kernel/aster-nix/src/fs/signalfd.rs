@@ -0,0 +1,289 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The signalfd file.
+//!
+//! A signalfd file accepts the signals in its mask, which are read from the file
+//! as `signalfd_siginfo` structures instead of being delivered to the handlers.
+//! The signals are usually blocked so that they stay pending until being read.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{
+    file_handle::FileLike,
+    utils::{InodeMode, Metadata, StatusFlags},
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::{
+        posix_thread::PosixThreadExt,
+        signal::{
+            c_types::siginfo_t,
+            constants::{SIGBUS, SIGCHLD, SIGFPE, SIGILL, SIGSEGV, SIGTRAP, SI_TIMER},
+            sig_mask::SigMask,
+            sig_num::SigNum,
+            Pollee, Poller, SigEvents, SigEventsFilter,
+        },
+    },
+    thread::Thread,
+};
+
+pub struct SignalFile {
+    mask: Mutex<SigMask>,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+    /// The threads whose signal queues notify the file of new signals.
+    ///
+    /// Like Linux, the file reports the signals of the thread that polls or reads it,
+    /// so every such thread is observed.
+    threads: Mutex<Vec<Weak<Thread>>>,
+    weak_self: Weak<Self>,
+}
+
+impl SignalFile {
+    /// Creates a signalfd file that accepts the signals in the mask.
+    pub fn new(mask: SigMask, is_nonblocking: bool) -> Arc<Self> {
+        let file = Arc::new_cyclic(|weak_self| Self {
+            mask: Mutex::new(mask),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee: Pollee::new(IoEvents::empty()),
+            threads: Mutex::new(Vec::new()),
+            weak_self: weak_self.clone(),
+        });
+        file.observe_current_thread();
+        file
+    }
+
+    /// Replaces the mask of the signals to be accepted.
+    pub fn set_mask(&self, mask: SigMask) {
+        *self.mask.lock() = mask;
+        let mut threads = self.threads.lock();
+        threads.retain(|thread| thread.strong_count() > 0);
+        for thread in threads.iter().filter_map(Weak::upgrade) {
+            self.register_to_thread(&thread, mask);
+        }
+        drop(threads);
+        self.update_io_events();
+    }
+
+    fn mask(&self) -> SigMask {
+        *self.mask.lock()
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    /// Observes the signal queue of the current thread if it is not observed yet.
+    fn observe_current_thread(&self) {
+        let current_thread = current_thread!();
+        let mut threads = self.threads.lock();
+        if threads
+            .iter()
+            .any(|thread| thread.as_ptr() == Arc::as_ptr(&current_thread))
+        {
+            return;
+        }
+        threads.retain(|thread| thread.strong_count() > 0);
+        self.register_to_thread(&current_thread, self.mask());
+        threads.push(Arc::downgrade(&current_thread));
+    }
+
+    fn register_to_thread(&self, thread: &Thread, mask: SigMask) {
+        let posix_thread = thread.as_posix_thread().unwrap();
+        // The filter takes the signals to be ignored.
+        let filter = SigEventsFilter::new(SigMask::from(!mask.as_u64()));
+        posix_thread.register_sigqueue_observer(self.weak_self.clone() as _, filter);
+    }
+
+    /// Updates the events according to the pending signals of the current thread.
+    fn update_io_events(&self) {
+        let current_thread = current_thread!();
+        let posix_thread = current_thread.as_posix_thread().unwrap();
+        if posix_thread.has_pending_signal_in(&self.mask()) {
+            self.pollee.add_events(IoEvents::IN);
+        } else {
+            self.pollee.del_events(IoEvents::IN);
+        }
+    }
+
+    /// Reads the pending signals in the mask of the current thread.
+    fn try_read(&self, buf: &mut [u8]) -> Result<usize> {
+        let current_thread = current_thread!();
+        let posix_thread = current_thread.as_posix_thread().unwrap();
+        let blocked = SigMask::from(!self.mask().as_u64());
+
+        let info_len = core::mem::size_of::<signalfd_siginfo>();
+        let mut read_len = 0;
+        for info_buf in buf.chunks_exact_mut(info_len) {
+            let Some(signal) = posix_thread.dequeue_signal(&blocked) else {
+                break;
+            };
+            let info = signalfd_siginfo::from_siginfo(signal.num(), &signal.to_info());
+            info_buf.copy_from_slice(info.as_bytes());
+            read_len += info_len;
+        }
+        self.update_io_events();
+
+        if read_len == 0 {
+            return_errno_with_message!(Errno::EAGAIN, "no signal is pending");
+        }
+        Ok(read_len)
+    }
+}
+
+impl Observer<SigEvents> for SignalFile {
+    fn on_events(&self, _events: &SigEvents) {
+        self.pollee.add_events(IoEvents::IN);
+    }
+}
+
+impl FileLike for SignalFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        if buf.len() < core::mem::size_of::<signalfd_siginfo>() {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+
+        if self.is_nonblocking() {
+            return self.try_read(buf);
+        }
+
+        let poller = Poller::new();
+        loop {
+            match self.try_read(buf) {
+                Err(err) if err.error() == Errno::EAGAIN => (),
+                result => return result,
+            };
+
+            let events = self.poll(IoEvents::IN, Some(&poller));
+            if !events.is_empty() {
+                continue;
+            }
+
+            poller.wait()?;
+        }
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.observe_current_thread();
+        self.update_io_events();
+        self.pollee.poll(mask, poller)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking() {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata::new_anonymous(InodeMode::from_bits_truncate(0o600))
+    }
+
+    fn register_observer(
+        &self,
+        observer: Weak<dyn Observer<IoEvents>>,
+        mask: IoEvents,
+    ) -> Result<()> {
+        self.pollee.register_observer(observer, mask);
+        Ok(())
+    }
+
+    fn unregister_observer(
+        &self,
+        observer: &Weak<dyn Observer<IoEvents>>,
+    ) -> Result<Weak<dyn Observer<IoEvents>>> {
+        self.pollee
+            .unregister_observer(observer)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "observer is not registered"))
+    }
+}
+
+impl Drop for SignalFile {
+    fn drop(&mut self) {
+        let observer = self.weak_self.clone() as _;
+        for thread in self.threads.lock().iter().filter_map(Weak::upgrade) {
+            let posix_thread = thread.as_posix_thread().unwrap();
+            posix_thread.unregiser_sigqueue_observer(&observer);
+        }
+    }
+}
+
+/// The `signalfd_siginfo` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct signalfd_siginfo {
+    pub ssi_signo: u32,
+    pub ssi_errno: i32,
+    pub ssi_code: i32,
+    pub ssi_pid: u32,
+    pub ssi_uid: u32,
+    pub ssi_fd: i32,
+    pub ssi_tid: u32,
+    pub ssi_band: u32,
+    pub ssi_overrun: u32,
+    pub ssi_trapno: u32,
+    pub ssi_status: i32,
+    pub ssi_int: i32,
+    pub ssi_ptr: u64,
+    pub ssi_utime: u64,
+    pub ssi_stime: u64,
+    pub ssi_addr: u64,
+    pub ssi_addr_lsb: u16,
+    pub pad2: u16,
+    pub ssi_syscall: i32,
+    pub ssi_call_addr: u64,
+    pub ssi_arch: u32,
+    pub pad: [u8; 28],
+}
+
+impl signalfd_siginfo {
+    /// Converts a `siginfo_t`, whose fields are interpreted according to the signal
+    /// and the code, as Linux does.
+    fn from_siginfo(signum: SigNum, siginfo: &siginfo_t) -> Self {
+        // The offsets are those of the union fields of Linux's `siginfo_t`.
+        let bytes = siginfo.as_bytes();
+        let read_u32 =
+            |offset: usize| u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let read_u64 =
+            |offset: usize| u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        let mut info = Self::new_zeroed();
+        info.ssi_signo = siginfo.si_signo as u32;
+        info.ssi_errno = siginfo.si_errno;
+        info.ssi_code = siginfo.si_code;
+
+        let is_fault = matches!(signum, SIGSEGV | SIGBUS | SIGILL | SIGFPE | SIGTRAP);
+        if is_fault && siginfo.si_code > 0 {
+            info.ssi_addr = read_u64(16);
+        } else if signum == SIGCHLD {
+            info.ssi_pid = read_u32(16);
+            info.ssi_uid = read_u32(20);
+            info.ssi_status = read_u32(24) as i32;
+            info.ssi_utime = read_u64(32);
+            info.ssi_stime = read_u64(40);
+        } else if siginfo.si_code == SI_TIMER {
+            info.ssi_tid = read_u32(16);
+            info.ssi_overrun = read_u32(20);
+            info.ssi_int = read_u32(24) as i32;
+            info.ssi_ptr = read_u64(24);
+        } else {
+            info.ssi_pid = read_u32(16);
+            info.ssi_uid = read_u32(20);
+            info.ssi_int = read_u32(24) as i32;
+            info.ssi_ptr = read_u64(24);
+        }
+        info
+    }
+}
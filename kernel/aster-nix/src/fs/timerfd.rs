@@ -0,0 +1,238 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The timerfd file.
+//!
+//! A timerfd file is a timer whose expirations are read from the file as a 64-bit
+//! counter, so that the timer can be waited on together with other files.
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_frame::timer::Timer;
+
+use super::{
+    file_handle::FileLike,
+    utils::{InodeMode, Metadata, StatusFlags},
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::signal::{Pollee, Poller},
+    thread::work_queue::{submit_work_func, WorkPriority},
+    time::{now_as_duration, ClockID},
+};
+
+pub struct TimerFile {
+    clock_id: ClockID,
+    timer: Arc<Timer>,
+    state: Arc<TimerState>,
+    is_nonblocking: AtomicBool,
+    pollee: Pollee,
+}
+
+/// The state shared with the timer callback, which runs in the interrupt context
+/// and therefore only touches atomics.
+struct TimerState {
+    /// The number of expirations since the last read or the last setting.
+    expirations: AtomicU64,
+    /// The time of the next expiration on the clock in nanoseconds, or zero if disarmed.
+    deadline: AtomicU64,
+    /// The interval of a periodic timer in nanoseconds, or zero if one-shot.
+    interval: AtomicU64,
+}
+
+impl TimerFile {
+    pub fn new(clock_id: ClockID, is_nonblocking: bool) -> Result<Self> {
+        if !matches!(
+            clock_id,
+            ClockID::CLOCK_REALTIME | ClockID::CLOCK_MONOTONIC | ClockID::CLOCK_BOOTTIME
+        ) {
+            return_errno_with_message!(Errno::EINVAL, "the clock is not supported by timerfd");
+        }
+
+        let state = Arc::new(TimerState {
+            expirations: AtomicU64::new(0),
+            deadline: AtomicU64::new(0),
+            interval: AtomicU64::new(0),
+        });
+        let pollee = Pollee::new(IoEvents::empty());
+        let timer = {
+            let state = state.clone();
+            let pollee = pollee.clone();
+            Timer::new(move |timer| {
+                state.expirations.fetch_add(1, Ordering::Relaxed);
+                let interval = state.interval.load(Ordering::Relaxed);
+                if interval != 0 {
+                    state.deadline.fetch_add(interval, Ordering::Relaxed);
+                    timer.set(Duration::from_nanos(interval));
+                } else {
+                    state.deadline.store(0, Ordering::Relaxed);
+                }
+                // Notifying the pollers may sleep, which is not allowed in the interrupt context.
+                let pollee = pollee.clone();
+                submit_work_func(move || pollee.add_events(IoEvents::IN), WorkPriority::High);
+            })?
+        };
+
+        Ok(Self {
+            clock_id,
+            timer,
+            state,
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            pollee,
+        })
+    }
+
+    /// Arms the timer to expire after `value` and then every `interval`, or disarms
+    /// the timer if `value` is zero. If `is_absolute` is true, `value` is the time
+    /// on the clock rather than relative to now.
+    ///
+    /// Returns the old setting, as `get_time` does.
+    pub fn set_time(
+        &self,
+        value: Duration,
+        interval: Duration,
+        is_absolute: bool,
+    ) -> Result<(Duration, Duration)> {
+        let old_setting = self.get_time()?;
+
+        self.timer.clear();
+        self.state.expirations.store(0, Ordering::Relaxed);
+        self.state.deadline.store(0, Ordering::Relaxed);
+        self.state.interval.store(0, Ordering::Relaxed);
+        self.pollee.del_events(IoEvents::IN);
+        if value.is_zero() {
+            return Ok(old_setting);
+        }
+
+        let now = now_as_duration(&self.clock_id)?;
+        let (deadline, timeout) = if is_absolute {
+            (value, value.saturating_sub(now))
+        } else {
+            (now + value, value)
+        };
+        self.state
+            .deadline
+            .store(deadline.as_nanos() as u64, Ordering::Relaxed);
+        self.state
+            .interval
+            .store(interval.as_nanos() as u64, Ordering::Relaxed);
+        self.timer.set(timeout);
+        Ok(old_setting)
+    }
+
+    /// Returns the remaining time until the next expiration and the interval.
+    ///
+    /// The remaining time is zero if the timer is disarmed.
+    pub fn get_time(&self) -> Result<(Duration, Duration)> {
+        let deadline = self.state.deadline.load(Ordering::Relaxed);
+        let interval = Duration::from_nanos(self.state.interval.load(Ordering::Relaxed));
+        if deadline == 0 {
+            return Ok((Duration::ZERO, interval));
+        }
+
+        let now = now_as_duration(&self.clock_id)?;
+        let remain = Duration::from_nanos(deadline).saturating_sub(now);
+        Ok((remain, interval))
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn try_read(&self) -> Result<u64> {
+        let expirations = self.state.expirations.swap(0, Ordering::Relaxed);
+        self.pollee.del_events(IoEvents::IN);
+        if expirations == 0 {
+            return_errno_with_message!(Errno::EAGAIN, "the timer has not expired");
+        }
+        Ok(expirations)
+    }
+
+    fn update_io_events(&self) {
+        if self.state.expirations.load(Ordering::Relaxed) > 0 {
+            self.pollee.add_events(IoEvents::IN);
+        }
+    }
+}
+
+impl FileLike for TimerFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = core::mem::size_of::<u64>();
+        if buf.len() < len {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+
+        let expirations = if self.is_nonblocking() {
+            self.try_read()?
+        } else {
+            let poller = Poller::new();
+            loop {
+                match self.try_read() {
+                    Err(err) if err.error() == Errno::EAGAIN => (),
+                    result => break result?,
+                };
+
+                let events = self.poll(IoEvents::IN, Some(&poller));
+                if !events.is_empty() {
+                    continue;
+                }
+
+                poller.wait()?;
+            }
+        };
+        buf[..len].copy_from_slice(&expirations.to_ne_bytes());
+        Ok(len)
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.update_io_events();
+        self.pollee.poll(mask, poller)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking() {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata::new_anonymous(InodeMode::from_bits_truncate(0o600))
+    }
+
+    fn register_observer(
+        &self,
+        observer: Weak<dyn Observer<IoEvents>>,
+        mask: IoEvents,
+    ) -> Result<()> {
+        self.pollee.register_observer(observer, mask);
+        Ok(())
+    }
+
+    fn unregister_observer(
+        &self,
+        observer: &Weak<dyn Observer<IoEvents>>,
+    ) -> Result<Weak<dyn Observer<IoEvents>>> {
+        self.pollee
+            .unregister_observer(observer)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "observer is not registered"))
+    }
+}
+
+impl Drop for TimerFile {
+    fn drop(&mut self) {
+        self.timer.clear();
+    }
+}
@@ -0,0 +1,675 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The userfaultfd file.
+//!
+//! A userfaultfd file allows the page faults within the registered ranges of an
+//! address space to be handled in user space. A faulting thread is blocked, and the
+//! fault is reported to the handler that reads the file. The handler resolves the
+//! fault, e.g., by filling the missing page with `UFFDIO_COPY` or by removing the
+//! write protection with `UFFDIO_WRITEPROTECT`, and then the thread retries the access.
+
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use aster_frame::{sync::WaitQueue, vm::max_userspace_vaddr};
+use aster_rights::Full;
+
+use super::{
+    file_handle::FileLike,
+    utils::{InodeMode, IoctlCmd, Metadata, StatusFlags},
+};
+use crate::{
+    events::{IoEvents, Observer},
+    prelude::*,
+    process::signal::{Pollee, Poller},
+    thread::Tid,
+    util::{read_bytes_from_user, read_val_from_user, write_val_to_user},
+    vm::vmar::Vmar,
+};
+
+/// The version of the userfaultfd API.
+const UFFD_API: u64 = 0xAA;
+
+/// The ioctls that are supported after `UFFDIO_API`, as a bitmask of their numbers.
+const UFFD_API_IOCTLS: u64 = 1 << 0x00 | 1 << 0x01 | 1 << 0x3F;
+/// The ioctls that are supported on a registered range.
+const UFFD_API_RANGE_IOCTLS: u64 = 1 << 0x02 | 1 << 0x03 | 1 << 0x04;
+/// The ioctl that is supported on a range registered in the write-protect mode.
+const UFFD_API_WP_IOCTLS: u64 = 1 << 0x06;
+
+/// The event of a page fault in `UffdMsg`.
+const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+
+pub struct UserfaultFile {
+    ctx: Arc<UserfaultCtx>,
+    is_nonblocking: AtomicBool,
+}
+
+impl UserfaultFile {
+    /// Creates a userfaultfd file for the address space of the current process.
+    pub fn new(is_nonblocking: bool) -> Result<Self> {
+        let vmar = current!().root_vmar().dup()?;
+        Ok(Self {
+            ctx: Arc::new(UserfaultCtx::new(vmar)),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+        })
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    fn wait_events<F, R>(&self, mask: IoEvents, mut cond: F) -> Result<R>
+    where
+        F: FnMut() -> Result<R>,
+    {
+        let poller = Poller::new();
+
+        loop {
+            match cond() {
+                Err(err) if err.error() == Errno::EAGAIN => (),
+                result => return result,
+            };
+
+            let events = self.poll(mask, Some(&poller));
+            if !events.is_empty() {
+                continue;
+            }
+
+            poller.wait()?;
+        }
+    }
+
+    fn api(&self, arg: usize) -> Result<()> {
+        let mut uffdio_api: UffdioApi = read_val_from_user(arg)?;
+        if uffdio_api.api != UFFD_API {
+            return_errno_with_message!(Errno::EINVAL, "the API version is not supported");
+        }
+        let features = UffdFeatures::from_bits(uffdio_api.features)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown userfaultfd features"))?;
+
+        {
+            let mut ctx_features = self.ctx.features.lock();
+            if ctx_features.is_some() {
+                return_errno_with_message!(Errno::EINVAL, "the API handshake is already done");
+            }
+            *ctx_features = Some(features);
+        }
+
+        uffdio_api.features = UffdFeatures::all().bits();
+        uffdio_api.ioctls = UFFD_API_IOCTLS;
+        write_val_to_user(arg, &uffdio_api)
+    }
+
+    fn register(&self, arg: usize) -> Result<()> {
+        let mut uffdio_register: UffdioRegister = read_val_from_user(arg)?;
+        let range = uffdio_register.range.to_range()?;
+        let mode = UffdRegisterMode::from_bits(uffdio_register.mode)
+            .filter(|mode| !mode.is_empty())
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid registration mode"))?;
+
+        let registration = UserfaultRegistration {
+            ctx: Arc::downgrade(&self.ctx),
+            mode,
+        };
+        self.ctx.vmar.register_userfault(range, registration)?;
+
+        uffdio_register.ioctls = if mode.contains(UffdRegisterMode::WP) {
+            UFFD_API_RANGE_IOCTLS | UFFD_API_WP_IOCTLS
+        } else {
+            UFFD_API_RANGE_IOCTLS
+        };
+        write_val_to_user(arg, &uffdio_register)
+    }
+
+    fn unregister(&self, arg: usize) -> Result<()> {
+        let uffdio_range: UffdioRange = read_val_from_user(arg)?;
+        let range = uffdio_range.to_range()?;
+
+        self.ctx
+            .vmar
+            .unregister_userfault(range.clone(), &self.ctx)?;
+        self.ctx
+            .wp_pages
+            .lock()
+            .retain(|page_addr| !range.contains(page_addr));
+        self.ctx.wake(&range);
+        Ok(())
+    }
+
+    fn wake(&self, arg: usize) -> Result<()> {
+        let uffdio_range: UffdioRange = read_val_from_user(arg)?;
+        let range = uffdio_range.to_range()?;
+
+        self.ctx.wake(&range);
+        Ok(())
+    }
+
+    fn copy(&self, arg: usize) -> Result<()> {
+        let mut uffdio_copy: UffdioCopy = read_val_from_user(arg)?;
+        let range = UffdioRange {
+            start: uffdio_copy.dst,
+            len: uffdio_copy.len,
+        }
+        .to_range()?;
+        let mode = UffdioCopyMode::from_bits(uffdio_copy.mode)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown copy mode"))?;
+        let src = uffdio_copy.src as Vaddr;
+        if src % PAGE_SIZE != 0
+            || src.checked_add(range.len()).is_none()
+            || (src < range.end && range.start < src + range.len())
+        {
+            return_errno_with_message!(Errno::EINVAL, "invalid source address");
+        }
+
+        let (nr_copied, result) = self.ctx.fill_pages(
+            range.clone(),
+            |offset, buf| read_bytes_from_user(src + offset, buf),
+            mode.contains(UffdioCopyMode::WP),
+        );
+        uffdio_copy.copy = nr_filled_or_errno(nr_copied, &result);
+        write_val_to_user(arg, &uffdio_copy)?;
+
+        self.finish_filling(
+            range,
+            nr_copied,
+            result,
+            mode.contains(UffdioCopyMode::DONTWAKE),
+        )
+    }
+
+    fn zeropage(&self, arg: usize) -> Result<()> {
+        let mut uffdio_zeropage: UffdioZeropage = read_val_from_user(arg)?;
+        let range = uffdio_zeropage.range.to_range()?;
+        let mode = UffdioZeropageMode::from_bits(uffdio_zeropage.mode)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown zeropage mode"))?;
+
+        let (nr_zeroed, result) = self.ctx.fill_pages(
+            range.clone(),
+            |_, buf| {
+                buf.fill(0);
+                Ok(())
+            },
+            false,
+        );
+        uffdio_zeropage.zeropage = nr_filled_or_errno(nr_zeroed, &result);
+        write_val_to_user(arg, &uffdio_zeropage)?;
+
+        self.finish_filling(
+            range,
+            nr_zeroed,
+            result,
+            mode.contains(UffdioZeropageMode::DONTWAKE),
+        )
+    }
+
+    /// Wakes the faults on the filled pages and reports the error of filling, if any.
+    ///
+    /// Like Linux, `EAGAIN` is returned if only part of the pages are filled.
+    fn finish_filling(
+        &self,
+        range: Range<Vaddr>,
+        nr_filled: usize,
+        result: Result<()>,
+        dont_wake: bool,
+    ) -> Result<()> {
+        if nr_filled == 0 {
+            return result;
+        }
+        if !dont_wake {
+            self.ctx.wake(&(range.start..range.start + nr_filled));
+        }
+        if nr_filled < range.len() {
+            return_errno_with_message!(Errno::EAGAIN, "only part of the pages are filled");
+        }
+        Ok(())
+    }
+
+    fn write_protect(&self, arg: usize) -> Result<()> {
+        let uffdio_writeprotect: UffdioWriteprotect = read_val_from_user(arg)?;
+        let range = uffdio_writeprotect.range.to_range()?;
+        let mode = UffdioWriteprotectMode::from_bits(uffdio_writeprotect.mode)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown write-protect mode"))?;
+        if mode.contains(UffdioWriteprotectMode::WP | UffdioWriteprotectMode::DONTWAKE) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the faults are never woken after protecting"
+            );
+        }
+
+        let vmar = &self.ctx.vmar;
+        vmar.check_userfault(&range, &self.ctx, UffdRegisterMode::WP)?;
+        if mode.contains(UffdioWriteprotectMode::WP) {
+            self.ctx
+                .wp_pages
+                .lock()
+                .extend(range.clone().step_by(PAGE_SIZE));
+            vmar.write_protect(range)?;
+        } else {
+            // The pages stay readonly in the page table, and they are remapped as
+            // writable on the next write faults.
+            self.ctx
+                .wp_pages
+                .lock()
+                .retain(|page_addr| !range.contains(page_addr));
+            if !mode.contains(UffdioWriteprotectMode::DONTWAKE) {
+                self.ctx.wake(&range);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UserfaultFile {
+    fn drop(&mut self) {
+        self.ctx.release();
+    }
+}
+
+impl FileLike for UserfaultFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let msg_len = core::mem::size_of::<UffdMsg>();
+        let max_msgs = buf.len() / msg_len;
+        if max_msgs == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the buffer is too small");
+        }
+
+        let msgs = if self.is_nonblocking() {
+            self.ctx.try_read(max_msgs)?
+        } else {
+            self.wait_events(IoEvents::IN, || self.ctx.try_read(max_msgs))?
+        };
+        for (msg, msg_buf) in msgs.iter().zip(buf.chunks_exact_mut(msg_len)) {
+            msg_buf.copy_from_slice(msg.as_bytes());
+        }
+        Ok(msgs.len() * msg_len)
+    }
+
+    fn ioctl(&self, cmd: IoctlCmd, arg: usize) -> Result<i32> {
+        if !matches!(cmd, IoctlCmd::UFFDIO_API) && self.ctx.features.lock().is_none() {
+            return_errno_with_message!(Errno::EINVAL, "the API handshake is not done");
+        }
+
+        match cmd {
+            IoctlCmd::UFFDIO_API => self.api(arg)?,
+            IoctlCmd::UFFDIO_REGISTER => self.register(arg)?,
+            IoctlCmd::UFFDIO_UNREGISTER => self.unregister(arg)?,
+            IoctlCmd::UFFDIO_WAKE => self.wake(arg)?,
+            IoctlCmd::UFFDIO_COPY => self.copy(arg)?,
+            IoctlCmd::UFFDIO_ZEROPAGE => self.zeropage(arg)?,
+            IoctlCmd::UFFDIO_WRITEPROTECT => self.write_protect(arg)?,
+            _ => return_errno_with_message!(Errno::EINVAL, "the ioctl is not supported"),
+        }
+        Ok(0)
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&Poller>) -> IoEvents {
+        self.ctx.pollee.poll(mask, poller)
+    }
+
+    fn status_flags(&self) -> StatusFlags {
+        if self.is_nonblocking() {
+            StatusFlags::O_NONBLOCK
+        } else {
+            StatusFlags::empty()
+        }
+    }
+
+    fn set_status_flags(&self, new_flags: StatusFlags) -> Result<()> {
+        self.is_nonblocking.store(
+            new_flags.contains(StatusFlags::O_NONBLOCK),
+            Ordering::Relaxed,
+        );
+        Ok(())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata::new_anonymous(InodeMode::from_bits_truncate(0o600))
+    }
+
+    fn register_observer(
+        &self,
+        observer: Weak<dyn Observer<IoEvents>>,
+        mask: IoEvents,
+    ) -> Result<()> {
+        self.ctx.pollee.register_observer(observer, mask);
+        Ok(())
+    }
+
+    fn unregister_observer(
+        &self,
+        observer: &Weak<dyn Observer<IoEvents>>,
+    ) -> Result<Weak<dyn Observer<IoEvents>>> {
+        self.ctx
+            .pollee
+            .unregister_observer(observer)
+            .ok_or_else(|| Error::with_message(Errno::ENOENT, "observer is not registered"))
+    }
+}
+
+/// The context of a userfaultfd file, which is shared with the faulting threads.
+pub struct UserfaultCtx {
+    /// The address space whose faults are handled.
+    vmar: Vmar<Full>,
+    /// The features enabled by `UFFDIO_API`, or `None` before the API handshake.
+    features: Mutex<Option<UffdFeatures>>,
+    /// The faults that are waiting to be resolved.
+    faults: Mutex<Vec<PendingFault>>,
+    /// The addresses of the write-protected pages.
+    wp_pages: Mutex<BTreeSet<Vaddr>>,
+    /// Whether the file is closed, after which the faults are handled by the kernel.
+    is_released: AtomicBool,
+    next_fault_id: AtomicU64,
+    pollee: Pollee,
+    wait_queue: WaitQueue,
+}
+
+struct PendingFault {
+    id: u64,
+    /// The address of the faulting page.
+    addr: Vaddr,
+    flags: PagefaultFlags,
+    tid: Tid,
+    /// Whether the fault has been read by the handler.
+    is_read: bool,
+}
+
+impl UserfaultCtx {
+    fn new(vmar: Vmar<Full>) -> Self {
+        Self {
+            vmar,
+            features: Mutex::new(None),
+            faults: Mutex::new(Vec::new()),
+            wp_pages: Mutex::new(BTreeSet::new()),
+            is_released: AtomicBool::new(false),
+            next_fault_id: AtomicU64::new(0),
+            pollee: Pollee::new(IoEvents::empty()),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+
+    fn is_released(&self) -> bool {
+        self.is_released.load(Ordering::Acquire)
+    }
+
+    /// Returns whether the page at the address is write-protected.
+    pub fn is_write_protected(&self, page_addr: Vaddr) -> bool {
+        self.wp_pages.lock().contains(&page_addr)
+    }
+
+    /// Reports the fault on the page to the handler, and waits until the fault is
+    /// resolved. Then the faulting access should be retried.
+    pub fn handle_fault(&self, page_addr: Vaddr, write: bool, wp: bool) -> Result<()> {
+        let mut flags = PagefaultFlags::empty();
+        flags.set(PagefaultFlags::WRITE, write);
+        flags.set(PagefaultFlags::WP, wp);
+
+        let id = self.next_fault_id.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut faults = self.faults.lock();
+            // The faults are no longer cleared after the file is released.
+            if self.is_released() {
+                return Ok(());
+            }
+            faults.push(PendingFault {
+                id,
+                addr: page_addr,
+                flags,
+                tid: current_thread!().tid(),
+                is_read: false,
+            });
+            self.update_io_events(&faults);
+        }
+
+        self.wait_queue.wait_until(|| {
+            let faults = self.faults.lock();
+            (!faults.iter().any(|fault| fault.id == id)).then_some(())
+        });
+        Ok(())
+    }
+
+    /// Wakes the faulting threads on the pages within the range.
+    fn wake(&self, range: &Range<Vaddr>) {
+        let mut faults = self.faults.lock();
+        faults.retain(|fault| !range.contains(&fault.addr));
+        self.update_io_events(&faults);
+        drop(faults);
+        self.wait_queue.wake_all();
+    }
+
+    /// Wakes all the faulting threads, after which the faults are handled by the kernel.
+    fn release(&self) {
+        let mut faults = self.faults.lock();
+        self.is_released.store(true, Ordering::Release);
+        faults.clear();
+        drop(faults);
+        self.wp_pages.lock().clear();
+        self.wait_queue.wake_all();
+    }
+
+    /// Takes at most `max_msgs` faults that have not been read.
+    fn try_read(&self, max_msgs: usize) -> Result<Vec<UffdMsg>> {
+        let with_thread_id = self
+            .features
+            .lock()
+            .is_some_and(|features| features.contains(UffdFeatures::THREAD_ID));
+
+        let mut faults = self.faults.lock();
+        let msgs: Vec<UffdMsg> = faults
+            .iter_mut()
+            .filter(|fault| !fault.is_read)
+            .take(max_msgs)
+            .map(|fault| {
+                fault.is_read = true;
+                UffdMsg {
+                    event: UFFD_EVENT_PAGEFAULT,
+                    flags: fault.flags.bits(),
+                    address: fault.addr as u64,
+                    ptid: if with_thread_id { fault.tid } else { 0 },
+                    ..Default::default()
+                }
+            })
+            .collect();
+        if msgs.is_empty() {
+            return_errno_with_message!(Errno::EAGAIN, "no page faults are pending");
+        }
+        self.update_io_events(&faults);
+        Ok(msgs)
+    }
+
+    /// Fills the missing pages within the range with the content read by `read_page`,
+    /// which is called with the offset of each page in the range.
+    ///
+    /// Returns the number of bytes filled, and the error that stops filling, if any.
+    fn fill_pages(
+        &self,
+        range: Range<Vaddr>,
+        mut read_page: impl FnMut(usize, &mut [u8]) -> Result<()>,
+        write_protect: bool,
+    ) -> (usize, Result<()>) {
+        let mut buf = vec![0u8; PAGE_SIZE];
+        for page_addr in range.clone().step_by(PAGE_SIZE) {
+            let offset = page_addr - range.start;
+            if let Err(err) = read_page(offset, &mut buf) {
+                return (offset, Err(err));
+            }
+            // The page is protected before it is filled, so that it is never mapped
+            // as writable in between.
+            if write_protect {
+                self.wp_pages.lock().insert(page_addr);
+            }
+            if let Err(err) = self.vmar.fill_userfault_page(page_addr, self, &buf) {
+                if write_protect {
+                    self.wp_pages.lock().remove(&page_addr);
+                }
+                return (offset, Err(err));
+            }
+        }
+        (range.len(), Ok(()))
+    }
+
+    fn update_io_events(&self, faults: &[PendingFault]) {
+        if faults.iter().any(|fault| !fault.is_read) {
+            self.pollee.add_events(IoEvents::IN);
+        } else {
+            self.pollee.del_events(IoEvents::IN);
+        }
+    }
+}
+
+/// The registration of a range of memory with a userfaultfd.
+#[derive(Clone)]
+pub struct UserfaultRegistration {
+    ctx: Weak<UserfaultCtx>,
+    mode: UffdRegisterMode,
+}
+
+impl UserfaultRegistration {
+    /// Returns the context to handle the faults, or `None` if the file is closed.
+    pub fn ctx(&self) -> Option<Arc<UserfaultCtx>> {
+        self.ctx.upgrade().filter(|ctx| !ctx.is_released())
+    }
+
+    pub fn mode(&self) -> UffdRegisterMode {
+        self.mode
+    }
+
+    /// Returns whether the range is registered with the context.
+    pub fn is_registered_with(&self, ctx: &UserfaultCtx) -> bool {
+        core::ptr::eq(self.ctx.as_ptr(), ctx)
+    }
+}
+
+bitflags! {
+    /// The faults that are handled in user space for a registered range.
+    pub struct UffdRegisterMode: u64 {
+        /// The faults on the missing pages.
+        const MISSING = 1 << 0;
+        /// The write faults on the write-protected pages.
+        const WP      = 1 << 1;
+    }
+}
+
+bitflags! {
+    struct UffdFeatures: u64 {
+        const PAGEFAULT_FLAG_WP = 1 << 0;
+        const THREAD_ID         = 1 << 8;
+    }
+}
+
+bitflags! {
+    struct PagefaultFlags: u64 {
+        const WRITE = 1 << 0;
+        const WP    = 1 << 1;
+    }
+}
+
+bitflags! {
+    struct UffdioCopyMode: u64 {
+        const DONTWAKE = 1 << 0;
+        const WP       = 1 << 1;
+    }
+}
+
+bitflags! {
+    struct UffdioZeropageMode: u64 {
+        const DONTWAKE = 1 << 0;
+    }
+}
+
+bitflags! {
+    struct UffdioWriteprotectMode: u64 {
+        const WP       = 1 << 0;
+        const DONTWAKE = 1 << 1;
+    }
+}
+
+/// The message of a page fault read from the file.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, Pod)]
+struct UffdMsg {
+    event: u8,
+    reserved1: u8,
+    reserved2: u16,
+    reserved3: u32,
+    flags: u64,
+    address: u64,
+    ptid: u32,
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioApi {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioRange {
+    start: u64,
+    len: u64,
+}
+
+impl UffdioRange {
+    /// Checks that the range is page-aligned, non-empty and in user space.
+    fn to_range(self) -> Result<Range<Vaddr>> {
+        let start = self.start as Vaddr;
+        let len = self.len as usize;
+        if start % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 || len == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the range is not page-aligned");
+        }
+        if start.checked_add(len).is_none() || start + len > max_userspace_vaddr() {
+            return_errno_with_message!(Errno::EINVAL, "the range is not in user space");
+        }
+        Ok(start..start + len)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioRegister {
+    range: UffdioRange,
+    mode: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioCopy {
+    dst: u64,
+    src: u64,
+    len: u64,
+    mode: u64,
+    /// The number of bytes copied, or the negated error number.
+    copy: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioZeropage {
+    range: UffdioRange,
+    mode: u64,
+    /// The number of bytes zeroed, or the negated error number.
+    zeropage: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+struct UffdioWriteprotect {
+    range: UffdioRange,
+    mode: u64,
+}
+
+/// Returns the number of bytes filled, or the negated error number if no bytes
+/// are filled, which is reported to the user by `UFFDIO_COPY` and `UFFDIO_ZEROPAGE`.
+fn nr_filled_or_errno(nr_filled: usize, result: &Result<()>) -> i64 {
+    match result {
+        Err(err) if nr_filled == 0 => -(err.error() as i64),
+        _ => nr_filled as i64,
+    }
+}
@@ -7,13 +7,20 @@ use core::{
 
 use inherit_methods_macro::inherit_methods;
 
-use super::{FileSystem, Inode, InodeMode, InodeType, Metadata, MountNode, NAME_MAX};
+use super::{
+    FileSystem, FsEvent, FsEvents, Inode, InodeMode, InodeType, Metadata, MountFlags, MountNode,
+    NAME_MAX,
+};
 use crate::{
+    events::Observer,
     fs::device::Device,
     prelude::*,
     process::{Gid, Uid},
 };
 
+/// The cookie of the next rename, which associates its `MOVED_FROM` and `MOVED_TO` events.
+static NEXT_RENAME_COOKIE: AtomicU32 = AtomicU32::new(1);
+
 lazy_static! {
     static ref DCACHE: Mutex<BTreeMap<DentryKey, Arc<Dentry>>> = Mutex::new(BTreeMap::new());
 }
@@ -161,8 +168,8 @@ impl Dentry {
             .fetch_and(!(DentryFlags::MOUNTED.bits()), Ordering::Release);
     }
 
-    /// Currently, the root dentry of a fs is the root of a mount.
-    fn is_root_of_mount(&self) -> bool {
+    /// Returns whether the dentry is the root dentry of a mount.
+    pub fn is_root_of_mount(&self) -> bool {
         self.name_and_parent.read().as_ref().is_none()
     }
 
@@ -171,11 +178,25 @@ impl Dentry {
         self.mount_node.upgrade().unwrap()
     }
 
+    /// Returns whether the dentry belongs to the mount.
+    pub fn is_in_mount(&self, mount_node: &Arc<MountNode>) -> bool {
+        Weak::as_ptr(&self.mount_node) == Arc::as_ptr(mount_node)
+    }
+
+    /// Checks that the mount which the dentry belongs to is not read-only.
+    pub fn check_writable_mount(&self) -> Result<()> {
+        if self.mount_node().flags().contains(MountFlags::RDONLY) {
+            return_errno_with_message!(Errno::EROFS, "the mount is read-only");
+        }
+        Ok(())
+    }
+
     /// Create a dentry by making inode.
     pub fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<Self>> {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
         let mut children = self.children.lock();
         if children.find_dentry(name).is_some() {
             return_errno!(Errno::EEXIST);
@@ -190,6 +211,9 @@ impl Dentry {
             children.insert_dentry(&dentry);
             dentry
         };
+        drop(children);
+
+        self.notify_child_fs_events(FsEvents::CREATE, type_ == InodeType::Dir, 0, name);
         Ok(child)
     }
 
@@ -198,6 +222,7 @@ impl Dentry {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
         let mut children = self.children.lock();
         if children.find_dentry(name).is_some() {
             return_errno!(Errno::EEXIST);
@@ -212,6 +237,9 @@ impl Dentry {
             children.insert_dentry(&dentry);
             dentry
         };
+        drop(children);
+
+        self.notify_child_fs_events(FsEvents::CREATE, false, 0, name);
         Ok(child)
     }
 
@@ -254,6 +282,7 @@ impl Dentry {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
         let mut children = self.children.lock();
         if children.find_dentry(name).is_some() {
             return_errno!(Errno::EEXIST);
@@ -268,6 +297,11 @@ impl Dentry {
             DentryOptions::Leaf((String::from(name), self.this())),
         );
         children.insert_dentry(&dentry);
+        drop(children);
+
+        // The link count of the inode is changed.
+        old.notify_self_fs_events(FsEvents::ATTRIB);
+        self.notify_child_fs_events(FsEvents::CREATE, false, 0, name);
         Ok(())
     }
 
@@ -276,10 +310,17 @@ impl Dentry {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
         let mut children = self.children.lock();
         let _ = children.find_dentry_with_checking_mountpoint(name)?;
         self.inode.unlink(name)?;
-        children.delete_dentry(name);
+        let child = children.delete_dentry(name);
+        drop(children);
+
+        self.notify_child_fs_events(FsEvents::DELETE, false, 0, name);
+        if let Some(child) = child {
+            child.notify_unlinked();
+        }
         Ok(())
     }
 
@@ -288,10 +329,17 @@ impl Dentry {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
         let mut children = self.children.lock();
         let _ = children.find_dentry_with_checking_mountpoint(name)?;
         self.inode.rmdir(name)?;
-        children.delete_dentry(name);
+        let child = children.delete_dentry(name);
+        drop(children);
+
+        self.notify_child_fs_events(FsEvents::DELETE, true, 0, name);
+        if let Some(child) = child {
+            child.notify_self_fs_events(FsEvents::DELETE_SELF);
+        }
         Ok(())
     }
 
@@ -303,15 +351,17 @@ impl Dentry {
         if self.inode.type_() != InodeType::Dir || new_dir.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
+        self.check_writable_mount()?;
+        new_dir.check_writable_mount()?;
 
         // Self and new_dir are same Dentry, just modify name
-        if Arc::ptr_eq(&self.this(), new_dir) {
+        let (old_dentry, replaced_dentry) = if Arc::ptr_eq(&self.this(), new_dir) {
             if old_name == new_name {
                 return Ok(());
             }
             let mut children = self.children.lock();
             let old_dentry = children.find_dentry_with_checking_mountpoint(old_name)?;
-            let _ = children.find_dentry_with_checking_mountpoint(new_name)?;
+            let replaced_dentry = children.find_dentry_with_checking_mountpoint(new_name)?;
             self.inode.rename(old_name, &self.inode, new_name)?;
             match old_dentry.as_ref() {
                 Some(dentry) => {
@@ -323,6 +373,7 @@ impl Dentry {
                     children.delete_dentry(new_name);
                 }
             }
+            (old_dentry, replaced_dentry)
         } else {
             // Self and new_dir are different Dentry
             if !Arc::ptr_eq(&self.mount_node(), &new_dir.mount_node()) {
@@ -331,7 +382,8 @@ impl Dentry {
             let (mut self_children, mut new_dir_children) =
                 write_lock_children_on_two_dentries(self, new_dir);
             let old_dentry = self_children.find_dentry_with_checking_mountpoint(old_name)?;
-            let _ = new_dir_children.find_dentry_with_checking_mountpoint(new_name)?;
+            let replaced_dentry =
+                new_dir_children.find_dentry_with_checking_mountpoint(new_name)?;
             self.inode.rename(old_name, &new_dir.inode, new_name)?;
             match old_dentry.as_ref() {
                 Some(dentry) => {
@@ -343,6 +395,24 @@ impl Dentry {
                     new_dir_children.delete_dentry(new_name);
                 }
             }
+            (old_dentry, replaced_dentry)
+        };
+
+        let is_dir = match old_dentry.as_ref() {
+            Some(dentry) => dentry.type_() == InodeType::Dir,
+            None => new_dir
+                .inode
+                .lookup(new_name)
+                .is_ok_and(|inode| inode.type_() == InodeType::Dir),
+        };
+        let cookie = NEXT_RENAME_COOKIE.fetch_add(1, Ordering::Relaxed);
+        self.notify_child_fs_events(FsEvents::MOVED_FROM, is_dir, cookie, old_name);
+        new_dir.notify_child_fs_events(FsEvents::MOVED_TO, is_dir, cookie, new_name);
+        if let Some(dentry) = old_dentry {
+            dentry.notify_self_fs_events(FsEvents::MOVE_SELF);
+        }
+        if let Some(dentry) = replaced_dentry {
+            dentry.notify_unlinked();
         }
         Ok(())
     }
@@ -354,17 +424,39 @@ impl Dentry {
     /// The root dentry cannot be mounted.
     ///
     /// Return the mounted child mount.
-    pub fn mount(&self, fs: Arc<dyn FileSystem>) -> Result<Arc<MountNode>> {
+    pub fn mount(
+        &self,
+        fs: Arc<dyn FileSystem>,
+        source: &str,
+        flags: MountFlags,
+    ) -> Result<Arc<MountNode>> {
+        self.check_mountpoint()?;
+
+        let child_mount = self.mount_node().mount(fs, source, &self.this(), flags)?;
+        self.set_mountpoint();
+        Ok(child_mount)
+    }
+
+    /// Bind the directory tree of the source dentry on this dentry. It will make this
+    /// dentry to be a mountpoint.
+    ///
+    /// Return the mounted child mount.
+    pub fn bind_mount(&self, source: &Dentry, flags: MountFlags) -> Result<Arc<MountNode>> {
+        self.check_mountpoint()?;
+
+        let child_mount = self.mount_node().bind(source, &self.this(), flags)?;
+        self.set_mountpoint();
+        Ok(child_mount)
+    }
+
+    fn check_mountpoint(&self) -> Result<()> {
         if self.inode.type_() != InodeType::Dir {
             return_errno!(Errno::ENOTDIR);
         }
         if self.effective_parent().is_none() {
             return_errno_with_message!(Errno::EINVAL, "can not mount on root");
         }
-
-        let child_mount = self.mount_node().mount(fs, &self.this())?;
-        self.set_mountpoint();
-        Ok(child_mount)
+        Ok(())
     }
 
     /// Unmount and return the mounted child mount.
@@ -412,6 +504,72 @@ impl Dentry {
         debug_assert!(path.starts_with('/'));
         path
     }
+
+    /// Registers an observer of the file system events on the inode of the dentry and,
+    /// if it is a directory, on its children.
+    ///
+    /// The observer also observes the events through the other hard links of the inode.
+    pub fn register_fs_event_observer(&self, observer: Weak<dyn Observer<FsEvent>>) {
+        self.inode.register_fs_event_observer(observer);
+    }
+
+    /// Unregisters an observer of the file system events.
+    pub fn unregister_fs_event_observer(
+        &self,
+        observer: &Weak<dyn Observer<FsEvent>>,
+    ) -> Option<Weak<dyn Observer<FsEvent>>> {
+        self.inode.unregister_fs_event_observer(observer)
+    }
+
+    /// Notifies the observers of the inode and those of the parent directory of the events.
+    pub fn notify_fs_events(&self, events: FsEvents) {
+        self.notify_self_fs_events(events);
+
+        let Some(parent) = self.parent() else {
+            return;
+        };
+        parent.notify_child_fs_events(events, self.type_() == InodeType::Dir, 0, &self.name());
+    }
+
+    /// Notifies the observers of the inode only, which is the case for the events
+    /// that are not reported to the parent, e.g., `DELETE_SELF`.
+    fn notify_self_fs_events(&self, events: FsEvents) {
+        if !self.inode.has_fs_event_observers() {
+            return;
+        }
+
+        let events = if self.type_() == InodeType::Dir {
+            events | FsEvents::ISDIR
+        } else {
+            events
+        };
+        self.inode.notify_fs_event(&FsEvent::new(events, 0, None));
+    }
+
+    /// Notifies the observers of the directory of the events on a child.
+    fn notify_child_fs_events(&self, events: FsEvents, is_dir: bool, cookie: u32, name: &str) {
+        if !self.inode.has_fs_event_observers() {
+            return;
+        }
+
+        let events = if is_dir {
+            events | FsEvents::ISDIR
+        } else {
+            events
+        };
+        self.inode
+            .notify_fs_event(&FsEvent::new(events, cookie, Some(String::from(name))));
+    }
+
+    /// Notifies that a name of the dentry is removed, which deletes the file if
+    /// it is the last link, or changes its link count otherwise.
+    fn notify_unlinked(&self) {
+        if self.metadata().nlinks == 0 {
+            self.notify_self_fs_events(FsEvents::DELETE_SELF);
+        } else {
+            self.notify_self_fs_events(FsEvents::ATTRIB);
+        }
+    }
 }
 
 #[inherit_methods(from = "self.inode")]
@@ -421,17 +579,52 @@ impl Dentry {
     pub fn metadata(&self) -> Metadata;
     pub fn type_(&self) -> InodeType;
     pub fn mode(&self) -> Result<InodeMode>;
-    pub fn set_mode(&self, mode: InodeMode) -> Result<()>;
     pub fn size(&self) -> usize;
-    pub fn resize(&self, size: usize) -> Result<()>;
     pub fn owner(&self) -> Result<Uid>;
-    pub fn set_owner(&self, uid: Uid) -> Result<()>;
     pub fn group(&self) -> Result<Gid>;
-    pub fn set_group(&self, gid: Gid) -> Result<()>;
     pub fn atime(&self) -> Duration;
-    pub fn set_atime(&self, time: Duration);
     pub fn mtime(&self) -> Duration;
-    pub fn set_mtime(&self, time: Duration);
+}
+
+/// The methods that change the inode and notify the observers of the changes.
+impl Dentry {
+    pub fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inode.set_mode(mode)?;
+        self.notify_fs_events(FsEvents::ATTRIB);
+        Ok(())
+    }
+
+    pub fn resize(&self, size: usize) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inode.resize(size)?;
+        self.notify_fs_events(FsEvents::MODIFY);
+        Ok(())
+    }
+
+    pub fn set_owner(&self, uid: Uid) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inode.set_owner(uid)?;
+        self.notify_fs_events(FsEvents::ATTRIB);
+        Ok(())
+    }
+
+    pub fn set_group(&self, gid: Gid) -> Result<()> {
+        self.check_writable_mount()?;
+        self.inode.set_group(gid)?;
+        self.notify_fs_events(FsEvents::ATTRIB);
+        Ok(())
+    }
+
+    pub fn set_atime(&self, time: Duration) {
+        self.inode.set_atime(time);
+        self.notify_fs_events(FsEvents::ATTRIB);
+    }
+
+    pub fn set_mtime(&self, time: Duration) {
+        self.inode.set_mtime(time);
+        self.notify_fs_events(FsEvents::ATTRIB);
+    }
 }
 
 impl Debug for Dentry {
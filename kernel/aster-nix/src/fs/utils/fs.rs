@@ -40,10 +40,15 @@ bitflags! {
     pub struct FsFlags: u32 {
         /// Dentry cannot be evicted.
         const DENTRY_UNEVICTABLE = 1 << 1;
+        /// The FS can only be mounted as read-only.
+        const RDONLY = 1 << 2;
     }
 }
 
 pub trait FileSystem: Any + Sync + Send {
+    /// Returns the name of the file system type, e.g., "ext2".
+    fn name(&self) -> &'static str;
+
     fn sync(&self) -> Result<()>;
 
     fn root_inode(&self) -> Arc<dyn Inode>;
@@ -62,6 +67,7 @@ impl dyn FileSystem {
 impl Debug for dyn FileSystem {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("FileSystem")
+            .field("name", &self.name())
             .field("super_block", &self.sb())
             .field("flags", &self.flags())
             .finish()
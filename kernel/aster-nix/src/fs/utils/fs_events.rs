@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::Inode;
+use crate::{
+    events::{Events, Observer, Subject},
+    prelude::*,
+};
+
+lazy_static! {
+    /// The subjects of the file system events on the inodes.
+    ///
+    /// The subjects are kept per inode instead of per dentry, so that the events
+    /// on a file are observed no matter through which hard link the file is reached.
+    static ref FS_EVENT_SUBJECTS: Mutex<BTreeMap<InodeKey, Subject<FsEvent>>> =
+        Mutex::new(BTreeMap::new());
+}
+
+/// The number of the subjects in `FS_EVENT_SUBJECTS`, which allows the events
+/// to be dropped without locking the subjects if no inode is observed.
+static NUM_FS_EVENT_SUBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+bitflags! {
+    /// The events of file system changes, whose values are the same as those of inotify.
+    pub struct FsEvents: u32 {
+        /// The file is accessed, e.g., read.
+        const ACCESS        = 0x0000_0001;
+        /// The file is modified, e.g., written or truncated.
+        const MODIFY        = 0x0000_0002;
+        /// The metadata is changed, e.g., the permissions, the timestamps or the link count.
+        const ATTRIB        = 0x0000_0004;
+        /// The file opened for writing is closed.
+        const CLOSE_WRITE   = 0x0000_0008;
+        /// The file not opened for writing is closed.
+        const CLOSE_NOWRITE = 0x0000_0010;
+        /// The file is opened.
+        const OPEN          = 0x0000_0020;
+        /// A file is moved out of the directory.
+        const MOVED_FROM    = 0x0000_0040;
+        /// A file is moved into the directory.
+        const MOVED_TO      = 0x0000_0080;
+        /// A file is created in the directory.
+        const CREATE        = 0x0000_0100;
+        /// A file is deleted from the directory.
+        const DELETE        = 0x0000_0200;
+        /// The file itself is deleted.
+        const DELETE_SELF   = 0x0000_0400;
+        /// The file itself is moved.
+        const MOVE_SELF     = 0x0000_0800;
+        /// The file system containing the file is unmounted.
+        const UNMOUNT       = 0x0000_2000;
+        /// The event queue overflows.
+        const Q_OVERFLOW    = 0x0000_4000;
+        /// The watch is removed.
+        const IGNORED       = 0x0000_8000;
+        /// The subject of the event is a directory.
+        const ISDIR         = 0x4000_0000;
+
+        const CLOSE = Self::CLOSE_WRITE.bits | Self::CLOSE_NOWRITE.bits;
+        const MOVE = Self::MOVED_FROM.bits | Self::MOVED_TO.bits;
+    }
+}
+
+/// An event of file system changes on a dentry.
+#[derive(Debug, Clone)]
+pub struct FsEvent {
+    events: FsEvents,
+    /// The cookie that associates the `MOVED_FROM` and the `MOVED_TO` events of a rename.
+    cookie: u32,
+    /// The name of the child if the event happens on a child of the directory.
+    name: Option<String>,
+}
+
+impl FsEvent {
+    pub fn new(events: FsEvents, cookie: u32, name: Option<String>) -> Self {
+        Self {
+            events,
+            cookie,
+            name,
+        }
+    }
+
+    pub fn events(&self) -> FsEvents {
+        self.events
+    }
+
+    pub fn cookie(&self) -> u32 {
+        self.cookie
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl Events for FsEvent {}
+
+/// The key that identifies an inode among all the file systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct InodeKey {
+    fs_ptr: usize,
+    ino: u64,
+}
+
+impl InodeKey {
+    fn new(inode: &dyn Inode) -> Self {
+        Self {
+            fs_ptr: Arc::as_ptr(&inode.fs()) as *const () as usize,
+            ino: inode.ino(),
+        }
+    }
+}
+
+impl dyn Inode {
+    /// Registers an observer of the file system events on the inode and,
+    /// if it is a directory, on its children.
+    pub fn register_fs_event_observer(&self, observer: Weak<dyn Observer<FsEvent>>) {
+        let mut subjects = FS_EVENT_SUBJECTS.lock();
+        subjects
+            .entry(InodeKey::new(self))
+            .or_insert_with(Subject::new)
+            .register_observer(observer, ());
+        NUM_FS_EVENT_SUBJECTS.store(subjects.len(), Ordering::Relaxed);
+    }
+
+    /// Unregisters an observer of the file system events on the inode.
+    pub fn unregister_fs_event_observer(
+        &self,
+        observer: &Weak<dyn Observer<FsEvent>>,
+    ) -> Option<Weak<dyn Observer<FsEvent>>> {
+        let mut subjects = FS_EVENT_SUBJECTS.lock();
+        let key = InodeKey::new(self);
+        let subject = subjects.get(&key)?;
+        let observer = subject.unregister_observer(observer);
+        if !subject.has_observers() {
+            subjects.remove(&key);
+            NUM_FS_EVENT_SUBJECTS.store(subjects.len(), Ordering::Relaxed);
+        }
+        observer
+    }
+
+    /// Returns whether the two inodes are the same one, which may be reached through
+    /// different hard links.
+    pub fn is_same_inode(&self, other: &dyn Inode) -> bool {
+        InodeKey::new(self) == InodeKey::new(other)
+    }
+
+    /// Returns whether the file system events on the inode are observed.
+    pub fn has_fs_event_observers(&self) -> bool {
+        if NUM_FS_EVENT_SUBJECTS.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        FS_EVENT_SUBJECTS.lock().contains_key(&InodeKey::new(self))
+    }
+
+    /// Notifies the observers of the inode of the event.
+    pub fn notify_fs_event(&self, event: &FsEvent) {
+        if NUM_FS_EVENT_SUBJECTS.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut subjects = FS_EVENT_SUBJECTS.lock();
+        let key = InodeKey::new(self);
+        let Some(subject) = subjects.get(&key) else {
+            return;
+        };
+        subject.notify_observers(event);
+        // The freed observers, e.g., the one-shot watches that have been triggered,
+        // are dropped from the subject by the notification.
+        if !subject.has_observers() {
+            subjects.remove(&key);
+            NUM_FS_EVENT_SUBJECTS.store(subjects.len(), Ordering::Relaxed);
+        }
+    }
+}
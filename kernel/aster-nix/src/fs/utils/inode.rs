@@ -115,6 +115,25 @@ impl InodeMode {
     }
 }
 
+bitflags! {
+    /// The seals of a file, which restrict the operations allowed on the file.
+    ///
+    /// The seals can only be added to the files that support sealing, e.g., the ones
+    /// created by `memfd_create`. Once added, a seal cannot be removed.
+    pub struct FileSeals: u32 {
+        /// No more seals can be added.
+        const F_SEAL_SEAL         = 0x0001;
+        /// The file cannot shrink.
+        const F_SEAL_SHRINK       = 0x0002;
+        /// The file cannot grow.
+        const F_SEAL_GROW         = 0x0004;
+        /// The content of the file cannot be modified.
+        const F_SEAL_WRITE        = 0x0008;
+        /// Like `F_SEAL_WRITE`, but the existing shared writable mappings are kept.
+        const F_SEAL_FUTURE_WRITE = 0x0010;
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Metadata {
     pub dev: u64,
@@ -227,6 +246,27 @@ impl Metadata {
             rdev: 0,
         }
     }
+
+    /// Creates the metadata of a file that is not backed by any file system,
+    /// e.g., an eventfd, a signalfd or a timerfd.
+    pub fn new_anonymous(mode: InodeMode) -> Metadata {
+        Self {
+            dev: 0,
+            ino: 0,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: Default::default(),
+            mtime: Default::default(),
+            ctime: Default::default(),
+            type_: InodeType::File,
+            mode,
+            nlinks: 1,
+            uid: Uid::new_root(),
+            gid: Gid::new_root(),
+            rdev: 0,
+        }
+    }
 }
 
 pub trait Inode: Any + Sync + Send {
@@ -328,6 +368,31 @@ pub trait Inode: Any + Sync + Send {
         Err(Error::new(Errno::EISDIR))
     }
 
+    /// Returns the seals of the file.
+    ///
+    /// An error is returned if the file does not support sealing.
+    fn seals(&self) -> Result<FileSeals> {
+        Err(Error::with_message(
+            Errno::EINVAL,
+            "the file does not support sealing",
+        ))
+    }
+
+    /// Adds the seals to the file.
+    ///
+    /// `check` is called with the current seals before the seals are added, and no
+    /// seals can be added by others in the meantime. The seals are not added if it
+    /// fails.
+    ///
+    /// An error is returned if the file does not support sealing, or if it is
+    /// sealed with `F_SEAL_SEAL`.
+    fn add_seals(&self, seals: FileSeals, check: &dyn Fn(FileSeals) -> Result<()>) -> Result<()> {
+        Err(Error::with_message(
+            Errno::EINVAL,
+            "the file does not support sealing",
+        ))
+    }
+
     fn sync(&self) -> Result<()> {
         Ok(())
     }
@@ -33,4 +33,18 @@ pub enum IoctlCmd {
     TIOCGPTPEER = 0x40045441,
     /// Get tdx report using TDCALL
     TDXGETREPORT = 0xc4405401,
+    /// Enable the userfaultfd API
+    UFFDIO_API = 0xc018aa3f,
+    /// Register a memory range with the userfaultfd
+    UFFDIO_REGISTER = 0xc020aa00,
+    /// Unregister a memory range from the userfaultfd
+    UFFDIO_UNREGISTER = 0x8010aa01,
+    /// Wake the threads that fault in a memory range
+    UFFDIO_WAKE = 0x8010aa02,
+    /// Copy data into the missing pages
+    UFFDIO_COPY = 0xc028aa03,
+    /// Fill the missing pages with zeros
+    UFFDIO_ZEROPAGE = 0xc020aa04,
+    /// Write-protect or unprotect the pages
+    UFFDIO_WRITEPROTECT = 0xc018aa06,
 }
@@ -10,9 +10,10 @@ pub use dirent_visitor::DirentVisitor;
 pub use direntry_vec::DirEntryVecExt;
 pub use file_creation_mask::FileCreationMask;
 pub use fs::{FileSystem, FsFlags, SuperBlock};
-pub use inode::{Inode, InodeMode, InodeType, Metadata};
+pub use fs_events::{FsEvent, FsEvents};
+pub use inode::{FileSeals, Inode, InodeMode, InodeType, Metadata};
 pub use ioctl::IoctlCmd;
-pub use mount::MountNode;
+pub use mount::{MountFlags, MountNode};
 pub use page_cache::{PageCache, PageCacheBackend};
 pub use random_test::{generate_random_operation, new_fs_in_memory};
 pub use status_flags::StatusFlags;
@@ -25,6 +26,7 @@ mod dirent_visitor;
 mod direntry_vec;
 mod file_creation_mask;
 mod fs;
+mod fs_events;
 mod inode;
 mod ioctl;
 mod mount;
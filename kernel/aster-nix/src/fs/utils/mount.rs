@@ -1,10 +1,21 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{Dentry, DentryKey, FileSystem, InodeType};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use super::{Dentry, DentryKey, FileSystem, FsFlags, Inode, InodeType};
 use crate::prelude::*;
 
+/// The ID of the next mount node, which is shown in `/proc/[pid]/mountinfo`.
+static NEXT_MOUNT_ID: AtomicUsize = AtomicUsize::new(1);
+
 /// The MountNode can form a mount tree to maintain the mount information.
 pub struct MountNode {
+    /// The unique ID.
+    id: usize,
+    /// The source of the mount, e.g., the name of the block device.
+    source: String,
+    /// The path of the root dentry in the FS, which is not "/" for bind mounts.
+    root_path: String,
     /// Root dentry.
     root_dentry: Arc<Dentry>,
     /// Mountpoint dentry. A mount node can be mounted on one dentry of another mount node,
@@ -12,6 +23,8 @@ pub struct MountNode {
     mountpoint_dentry: Option<Arc<Dentry>>,
     /// The associated FS.
     fs: Arc<dyn FileSystem>,
+    /// The per-mount flags.
+    flags: AtomicU32,
     /// Child mount nodes which are mounted on one dentry of self.
     children: Mutex<BTreeMap<DentryKey, Arc<Self>>>,
     /// Reference to self.
@@ -27,18 +40,41 @@ impl MountNode {
     /// It is allowed to create a mount node even if the fs has been provided to another
     /// mount node. It is the fs's responsibility to ensure the data consistency.
     pub fn new_root(fs: Arc<dyn FileSystem>) -> Arc<Self> {
-        Self::new(fs, None)
+        let root_inode = fs.root_inode();
+        let source = String::from(fs.name());
+        Self::new(
+            fs,
+            root_inode,
+            source,
+            String::from("/"),
+            None,
+            MountFlags::empty(),
+        )
     }
 
     /// The internal constructor.
     ///
     /// Root mount node has no mountpoint which other mount nodes must have mountpoint.
-    fn new(fs: Arc<dyn FileSystem>, mountpoint: Option<Arc<Dentry>>) -> Arc<Self> {
+    /// The root inode is the root inode of the fs, or any directory inode of it for
+    /// bind mounts.
+    fn new(
+        fs: Arc<dyn FileSystem>,
+        root_inode: Arc<dyn Inode>,
+        source: String,
+        root_path: String,
+        mountpoint: Option<Arc<Dentry>>,
+        flags: MountFlags,
+    ) -> Arc<Self> {
+        let flags = required_flags(fs.as_ref(), flags);
         Arc::new_cyclic(|weak_self| Self {
-            root_dentry: Dentry::new_root(fs.root_inode(), weak_self.clone()),
+            id: NEXT_MOUNT_ID.fetch_add(1, Ordering::Relaxed),
+            source,
+            root_path,
+            root_dentry: Dentry::new_root(root_inode, weak_self.clone()),
             mountpoint_dentry: mountpoint,
             children: Mutex::new(BTreeMap::new()),
             fs,
+            flags: AtomicU32::new(flags.bits()),
             this: weak_self.clone(),
         })
     }
@@ -54,7 +90,77 @@ impl MountNode {
     /// mountpoint. It is the fs's responsibility to ensure the data consistency.
     ///
     /// Return the mounted child mount.
-    pub fn mount(&self, fs: Arc<dyn FileSystem>, mountpoint: &Arc<Dentry>) -> Result<Arc<Self>> {
+    pub fn mount(
+        &self,
+        fs: Arc<dyn FileSystem>,
+        source: &str,
+        mountpoint: &Arc<Dentry>,
+        flags: MountFlags,
+    ) -> Result<Arc<Self>> {
+        let root_inode = fs.root_inode();
+        self.mount_inode(
+            fs,
+            root_inode,
+            String::from(source),
+            String::from("/"),
+            mountpoint,
+            flags,
+        )
+    }
+
+    /// Bind the directory tree of the source dentry on the mountpoint, it will create a
+    /// new child mount node, which shares the fs with the mount of the source dentry.
+    ///
+    /// The mountpoint should belong to this mount node, or an error is returned.
+    ///
+    /// Return the mounted child mount.
+    pub fn bind(
+        &self,
+        source: &Dentry,
+        mountpoint: &Arc<Dentry>,
+        flags: MountFlags,
+    ) -> Result<Arc<Self>> {
+        if source.type_() != InodeType::Dir {
+            return_errno!(Errno::ENOTDIR);
+        }
+
+        // The path of the source dentry in the FS is its path in the source mount,
+        // appended to the path of the root dentry of the source mount in the FS.
+        let source_mount = source.mount_node();
+        let root_path = {
+            let path = source.abs_path();
+            let mount_path = source_mount.root_dentry().abs_path();
+            let relative_path = if mount_path == "/" {
+                path.as_str()
+            } else {
+                path.strip_prefix(&mount_path).unwrap_or(&path)
+            };
+            match (source_mount.root_path(), relative_path) {
+                (root_path, "") => String::from(root_path),
+                ("/", relative_path) => String::from(relative_path),
+                (root_path, relative_path) => String::from(root_path) + relative_path,
+            }
+        };
+
+        self.mount_inode(
+            source.fs(),
+            source.inode().clone(),
+            String::from(source_mount.source()),
+            root_path,
+            mountpoint,
+            flags,
+        )
+    }
+
+    fn mount_inode(
+        &self,
+        fs: Arc<dyn FileSystem>,
+        root_inode: Arc<dyn Inode>,
+        source: String,
+        root_path: String,
+        mountpoint: &Arc<Dentry>,
+        flags: MountFlags,
+    ) -> Result<Arc<Self>> {
         if !Arc::ptr_eq(&mountpoint.mount_node(), &self.this()) {
             return_errno_with_message!(Errno::EINVAL, "mountpoint not belongs to this");
         }
@@ -63,7 +169,14 @@ impl MountNode {
         }
 
         let key = mountpoint.key();
-        let child_mount = Self::new(fs, Some(mountpoint.clone()));
+        let child_mount = Self::new(
+            fs,
+            root_inode,
+            source,
+            root_path,
+            Some(mountpoint.clone()),
+            flags,
+        );
         self.children.lock().insert(key, child_mount.clone());
         Ok(child_mount)
     }
@@ -92,6 +205,33 @@ impl MountNode {
         self.children.lock().get(&mountpoint.key()).cloned()
     }
 
+    /// Returns whether any mount node is mounted on the dentries of this mount node.
+    pub fn has_children(&self) -> bool {
+        !self.children.lock().is_empty()
+    }
+
+    /// Returns the child mount nodes, in the order of their IDs.
+    pub fn children(&self) -> Vec<Arc<Self>> {
+        let mut children: Vec<_> = self.children.lock().values().cloned().collect();
+        children.sort_by_key(|child| child.id);
+        children
+    }
+
+    /// Get the unique ID of this mount node.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Get the source of the mount.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Get the path of the root dentry in the associated fs.
+    pub fn root_path(&self) -> &str {
+        &self.root_path
+    }
+
     /// Get the root dentry of this mount node.
     pub fn root_dentry(&self) -> &Arc<Dentry> {
         &self.root_dentry
@@ -130,14 +270,51 @@ impl MountNode {
     pub fn fs(&self) -> &Arc<dyn FileSystem> {
         &self.fs
     }
+
+    /// Get the per-mount flags.
+    pub fn flags(&self) -> MountFlags {
+        MountFlags::from_bits_truncate(self.flags.load(Ordering::Relaxed))
+    }
+
+    /// Set the per-mount flags, which is used to remount.
+    pub fn set_flags(&self, flags: MountFlags) {
+        let flags = required_flags(self.fs.as_ref(), flags);
+        self.flags.store(flags.bits(), Ordering::Relaxed);
+    }
+}
+
+/// Adds the per-mount flags that are required by the FS to the `flags`.
+fn required_flags(fs: &dyn FileSystem, flags: MountFlags) -> MountFlags {
+    if fs.flags().contains(FsFlags::RDONLY) {
+        flags | MountFlags::RDONLY
+    } else {
+        flags
+    }
 }
 
 impl Debug for MountNode {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("MountNode")
+            .field("id", &self.id)
+            .field("source", &self.source)
             .field("root", &self.root_dentry)
             .field("mountpoint", &self.mountpoint_dentry)
             .field("fs", &self.fs)
+            .field("flags", &self.flags())
             .finish()
     }
 }
+
+bitflags! {
+    /// The per-mount flags.
+    ///
+    /// The values are the same as the corresponding `MS_*` flags of `mount(2)`.
+    pub struct MountFlags: u32 {
+        /// Disallow writing to the files and the directories.
+        const RDONLY = 1 << 0;
+        /// Ignore the set-user-ID and set-group-ID bits when executing programs.
+        const NOSUID = 1 << 1;
+        /// Disallow executing programs.
+        const NOEXEC = 1 << 3;
+    }
+}
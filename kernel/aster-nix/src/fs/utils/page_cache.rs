@@ -1,15 +1,23 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use core::ops::Range;
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use aster_block::bio::{BioStatus, BioWaiter};
 use aster_frame::vm::{VmAllocOptions, VmFrame};
 use aster_rights::Full;
 use lru::LruCache;
+use spin::Once;
 
 use crate::{
     prelude::*,
-    vm::vmo::{get_page_idx_range, Pager, Vmo, VmoFlags, VmoOptions},
+    thread::work_queue::{submit_work_func, WorkPriority},
+    vm::{
+        reclaim::{register_shrinker, Shrinker},
+        vmo::{get_page_idx_range, Pager, Vmo, VmoFlags, VmoOptions, WeakVmo},
+    },
 };
 
 pub struct PageCache {
@@ -25,6 +33,7 @@ impl PageCache {
             .flags(VmoFlags::RESIZABLE)
             .pager(manager.clone())
             .alloc()?;
+        manager.init_vmo(&pages);
         Ok(Self { pages, manager })
     }
 
@@ -38,6 +47,7 @@ impl PageCache {
             .flags(VmoFlags::RESIZABLE)
             .pager(manager.clone())
             .alloc()?;
+        manager.init_vmo(&pages);
         Ok(Self { pages, manager })
     }
 
@@ -86,18 +96,34 @@ impl Debug for PageCache {
 }
 
 struct PageCacheManager {
-    pages: Mutex<LruCache<usize, Page>>,
+    pages: Arc<Mutex<LruCache<usize, Page>>>,
+    /// The number of times that pages are removed from `pages`, which is only
+    /// updated with `pages` locked.
+    removals: Arc<AtomicUsize>,
     backend: Weak<dyn PageCacheBackend>,
+    /// The VMO that shares the pages, which are reclaimed only if they are not
+    /// committed in it.
+    vmo: Once<WeakVmo<Full>>,
 }
 
 impl PageCacheManager {
     pub fn new(backend: Weak<dyn PageCacheBackend>) -> Self {
         Self {
-            pages: Mutex::new(LruCache::unbounded()),
+            pages: Arc::new(Mutex::new(LruCache::unbounded())),
+            removals: Arc::new(AtomicUsize::new(0)),
             backend,
+            vmo: Once::new(),
         }
     }
 
+    /// Sets the VMO that is attached to the manager, and starts reclaiming the pages
+    /// under memory pressure.
+    fn init_vmo(self: &Arc<Self>, vmo: &Vmo<Full>) {
+        self.vmo.call_once(|| vmo.downgrade());
+        let shrinker: Weak<dyn Shrinker> = Arc::downgrade(self) as _;
+        register_shrinker(shrinker);
+    }
+
     pub fn backend(&self) -> Arc<dyn PageCacheBackend> {
         self.backend.upgrade().unwrap()
     }
@@ -106,42 +132,130 @@ impl PageCacheManager {
     pub fn discard_range(&self, range: Range<usize>) {
         let page_idx_range = get_page_idx_range(&range);
         for idx in page_idx_range {
-            self.pages.lock().pop(&idx);
+            let mut pages = self.pages.lock();
+            if pages.pop(&idx).is_some() {
+                self.removals.fetch_add(1, Ordering::Relaxed);
+            }
         }
     }
 
     pub fn evict_range(&self, range: Range<usize>) -> Result<()> {
         let page_idx_range = get_page_idx_range(&range);
 
+        // The mapped pages are write-protected first, so that the writes to them during
+        // or after the writeback fault and mark them dirty again.
+        if let Some(vmo) = self.vmo.get().and_then(WeakVmo::upgrade) {
+            vmo.notify_writeback(page_idx_range.clone());
+        }
+
         //TODO: When there are many pages, we should submit them in batches of folios rather than all at once.
         let mut indices_and_waiters: Vec<(usize, BioWaiter)> = Vec::new();
+        let mut is_failed = false;
 
         for idx in page_idx_range {
             if let Some(page) = self.pages.lock().get_mut(&idx) {
                 if let PageState::Dirty = page.state() {
                     let backend = self.backend();
                     if idx < backend.npages() {
-                        indices_and_waiters.push((idx, backend.write_page(idx, page.frame())?));
+                        // The page is cleaned before it is written, so that the updates
+                        // made while it is being written are not lost.
+                        page.set_state(PageState::UpToDate);
+                        match backend.write_page(idx, page.frame()) {
+                            Ok(waiter) => indices_and_waiters.push((idx, waiter)),
+                            Err(_) => {
+                                page.set_state(PageState::Dirty);
+                                is_failed = true;
+                            }
+                        }
                     }
                 }
             }
         }
 
         for (idx, waiter) in indices_and_waiters.iter() {
-            if matches!(waiter.wait(), Some(BioStatus::Complete)) {
+            if !matches!(waiter.wait(), Some(BioStatus::Complete)) {
+                // The page stays dirty, so that it will be written back again.
                 if let Some(page) = self.pages.lock().get_mut(idx) {
-                    page.set_state(PageState::UpToDate)
+                    page.set_state(PageState::Dirty)
                 }
-            } else {
-                // TODO: We may need an error handler here.
-                return_errno!(Errno::EIO)
+                is_failed = true;
             }
         }
+        if is_failed {
+            return_errno!(Errno::EIO);
+        }
 
         Ok(())
     }
 }
 
+impl Shrinker for PageCacheManager {
+    fn shrink(&self, nr_to_scan: usize) -> usize {
+        // The candidates are the least recently used pages that are not mapped. Such a
+        // page is referenced by the LRU list and possibly by the VMO.
+        let candidates: Vec<(usize, bool)> = self
+            .pages
+            .lock()
+            .iter()
+            .rev()
+            .take(nr_to_scan)
+            .filter(|(_, page)| page.frame().reference_count() <= 2)
+            .map(|(idx, page)| (*idx, matches!(page.state(), PageState::Dirty)))
+            .collect();
+        let Some(backend) = self.backend.upgrade() else {
+            return 0;
+        };
+
+        // Write back the dirty candidates in a batch.
+        let mut indices_and_waiters = Vec::new();
+        for (idx, _) in candidates.iter().filter(|(_, is_dirty)| *is_dirty) {
+            if *idx >= backend.npages() {
+                continue;
+            }
+            let Some(frame) = self.pages.lock().peek(idx).map(|page| page.frame().clone()) else {
+                continue;
+            };
+            if let Ok(waiter) = backend.write_page(*idx, &frame) {
+                indices_and_waiters.push((*idx, waiter));
+            }
+        }
+        for (idx, waiter) in indices_and_waiters.iter() {
+            if matches!(waiter.wait(), Some(BioStatus::Complete))
+                && let Some(page) = self.pages.lock().peek_mut(idx)
+            {
+                page.set_state(PageState::UpToDate);
+            }
+        }
+
+        let vmo = self.vmo.get().and_then(WeakVmo::upgrade);
+        let mut nr_reclaimed = 0;
+        for (idx, _) in candidates {
+            // The page that is only in the LRU list is dropped if it is clean.
+            let is_dropped = {
+                let mut pages = self.pages.lock();
+                let is_unused = pages.peek(&idx).is_some_and(|page| {
+                    page.frame().reference_count() == 1 && !matches!(page.state(), PageState::Dirty)
+                });
+                if is_unused {
+                    pages.pop(&idx);
+                    self.removals.fetch_add(1, Ordering::Relaxed);
+                }
+                is_unused
+            };
+            // Otherwise, the page is decommitted from the VMO if it is not mapped. A page
+            // that is dirty again is written back synchronously when it is decommitted.
+            if is_dropped
+                || vmo
+                    .as_ref()
+                    .is_some_and(|vmo| vmo.decommit_unused_page(idx).unwrap_or(false))
+            {
+                nr_reclaimed += 1;
+            }
+        }
+        nr_reclaimed
+    }
+}
+
 impl Debug for PageCacheManager {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("PageCacheManager")
@@ -184,7 +298,14 @@ impl Pager for PageCacheManager {
     }
 
     fn decommit_page(&self, idx: usize) -> Result<()> {
-        let page_result = self.pages.lock().pop(&idx);
+        let page_result = {
+            let mut pages = self.pages.lock();
+            let page_result = pages.pop(&idx);
+            if page_result.is_some() {
+                self.removals.fetch_add(1, Ordering::Relaxed);
+            }
+            page_result
+        };
         if let Some(page) = page_result {
             if let PageState::Dirty = page.state() {
                 let Some(backend) = self.backend.upgrade() else {
@@ -198,6 +319,72 @@ impl Pager for PageCacheManager {
 
         Ok(())
     }
+
+    fn flush_pages(&self, idx_range: Range<usize>) -> Result<()> {
+        self.evict_range((idx_range.start * PAGE_SIZE)..(idx_range.end * PAGE_SIZE))
+    }
+
+    fn readahead(&self, idx_range: Range<usize>) -> Result<()> {
+        let backend = self.backend();
+        let idx_range = idx_range.start..idx_range.end.min(backend.npages());
+        let removals_before = self.removals.load(Ordering::Relaxed);
+
+        let mut pages_and_waiters = Vec::new();
+        for idx in idx_range {
+            if self.pages.lock().contains(&idx) {
+                continue;
+            }
+            let page = Page::alloc()?;
+            let waiter = backend.read_page(idx, page.frame())?;
+            pages_and_waiters.push((idx, page, waiter));
+        }
+        if pages_and_waiters.is_empty() {
+            return Ok(());
+        }
+
+        // Wait for the reads in the work queue. A page is cached only if it is still absent.
+        // If any page has been removed in the meantime, it may have been written back after
+        // being read, so none of the pages are cached to avoid caching stale data.
+        let pages = self.pages.clone();
+        let removals = self.removals.clone();
+        let pages_and_waiters = Mutex::new(Some(pages_and_waiters));
+        submit_work_func(
+            move || {
+                let Some(pages_and_waiters) = pages_and_waiters.lock().take() else {
+                    return;
+                };
+                let read_pages: Vec<(usize, Page)> = pages_and_waiters
+                    .into_iter()
+                    .filter_map(|(idx, mut page, waiter)| {
+                        if !matches!(waiter.wait(), Some(BioStatus::Complete)) {
+                            return None;
+                        }
+                        page.set_state(PageState::UpToDate);
+                        Some((idx, page))
+                    })
+                    .collect();
+
+                let mut pages = pages.lock();
+                if removals.load(Ordering::Relaxed) != removals_before {
+                    return;
+                }
+                for (idx, page) in read_pages {
+                    if !pages.contains(&idx) {
+                        pages.put(idx, page);
+                    }
+                }
+            },
+            WorkPriority::Normal,
+        );
+        Ok(())
+    }
+
+    fn deactivate_pages(&self, idx_range: Range<usize>) {
+        let mut pages = self.pages.lock();
+        for idx in idx_range {
+            pages.demote(&idx);
+        }
+    }
 }
 
 #[derive(Debug)]
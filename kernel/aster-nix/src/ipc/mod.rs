@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V inter-process communication (IPC).
+//!
+//! An IPC object is identified by an ID, and can be looked up by a key so that
+//! unrelated processes can agree on the same object. The access to an IPC object
+//! is controlled by the permission bits and the owner of the object, in a way
+//! similar to that of a file.
+
+use core::time::Duration;
+
+use crate::{
+    prelude::*,
+    process::{credentials, signal::Pauser, Gid, Uid},
+    time::{now_as_duration, ClockID},
+};
+
+pub mod msg;
+pub mod sem;
+pub mod shm;
+
+/// The key of an IPC object.
+pub type IpcKey = i32;
+
+/// The ID of an IPC object.
+pub type IpcId = i32;
+
+/// The key with which a new IPC object is always created.
+pub const IPC_PRIVATE: IpcKey = 0;
+
+/// The maximum ID of an IPC object.
+const IPC_ID_MAX: IpcId = i32::MAX;
+
+bitflags! {
+    /// The flags of the `*get` syscalls.
+    ///
+    /// The lowest 9 bits are the permission bits, which are not included.
+    pub struct IpcFlags: u32 {
+        /// Creates the object if the key does not exist.
+        const IPC_CREAT = 0o1000;
+        /// Fails if the key exists.
+        const IPC_EXCL = 0o2000;
+        /// Returns an error instead of waiting.
+        const IPC_NOWAIT = 0o4000;
+    }
+}
+
+/// The permission bits of an IPC object.
+const IPC_MODE_MASK: u16 = 0o777;
+
+/// The ownership and the permission of an IPC object.
+#[derive(Debug, Clone, Copy)]
+pub struct IpcPermission {
+    key: IpcKey,
+    uid: Uid,
+    gid: Gid,
+    cuid: Uid,
+    cgid: Gid,
+    mode: u16,
+}
+
+impl IpcPermission {
+    /// Creates the permission of a new object, which is owned by the current process.
+    pub fn new(key: IpcKey, mode: u16) -> Self {
+        let credentials = credentials();
+        let uid = credentials.euid();
+        let gid = credentials.egid();
+        Self {
+            key,
+            uid,
+            gid,
+            cuid: uid,
+            cgid: gid,
+            mode: mode & IPC_MODE_MASK,
+        }
+    }
+
+    pub fn key(&self) -> IpcKey {
+        self.key
+    }
+
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    /// Checks whether the current process has the requested permission bits.
+    ///
+    /// The requested bits are given for all of the owner, the group and others,
+    /// e.g., `0o444` for read and `0o222` for write, as Linux does.
+    pub fn check(&self, requested: u16) -> Result<()> {
+        let credentials = credentials();
+        let euid = credentials.euid();
+        if euid.is_root() {
+            return Ok(());
+        }
+
+        let granted = if euid == self.uid || euid == self.cuid {
+            self.mode >> 6
+        } else if self.is_in_group(credentials.egid(), &credentials.groups()) {
+            self.mode >> 3
+        } else {
+            self.mode
+        };
+        let requested = (requested | requested >> 3 | requested >> 6) & 0o7;
+        if requested & !granted != 0 {
+            return_errno_with_message!(Errno::EACCES, "the IPC object is not accessible");
+        }
+        Ok(())
+    }
+
+    /// Checks whether the current process is the owner or the creator of the object,
+    /// which is required to change or to remove the object.
+    pub fn check_owner(&self) -> Result<()> {
+        let euid = credentials().euid();
+        if euid.is_root() || euid == self.uid || euid == self.cuid {
+            return Ok(());
+        }
+        return_errno_with_message!(
+            Errno::EPERM,
+            "the process is not the owner of the IPC object"
+        );
+    }
+
+    /// Changes the owner and the permission bits, as `IPC_SET` does.
+    pub fn set(&mut self, uid: Uid, gid: Gid, mode: u16) -> Result<()> {
+        self.check_owner()?;
+        self.uid = uid;
+        self.gid = gid;
+        self.mode = (self.mode & !IPC_MODE_MASK) | (mode & IPC_MODE_MASK);
+        Ok(())
+    }
+
+    /// Marks the object as removed, which can no longer be looked up by the key.
+    pub fn mark_removed(&mut self) {
+        self.key = IPC_PRIVATE;
+    }
+
+    fn is_in_group(&self, egid: Gid, groups: &BTreeSet<Gid>) -> bool {
+        egid == self.gid || egid == self.cgid || groups.contains(&self.gid)
+    }
+
+    pub fn to_c(&self, seq: u16) -> c_ipc_perm {
+        c_ipc_perm {
+            key: self.key,
+            uid: self.uid.as_u32(),
+            gid: self.gid.as_u32(),
+            cuid: self.cuid.as_u32(),
+            cgid: self.cgid.as_u32(),
+            mode: self.mode,
+            pad1: 0,
+            seq,
+            pad2: 0,
+            unused1: 0,
+            unused2: 0,
+        }
+    }
+}
+
+/// The `ipc64_perm` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct c_ipc_perm {
+    pub key: i32,
+    pub uid: u32,
+    pub gid: u32,
+    pub cuid: u32,
+    pub cgid: u32,
+    pub mode: u16,
+    pub pad1: u16,
+    pub seq: u16,
+    pub pad2: u16,
+    pub unused1: u64,
+    pub unused2: u64,
+}
+
+/// An object managed by an `IpcTable`.
+pub trait IpcObject: Send + Sync {
+    fn permission(&self) -> IpcPermission;
+}
+
+/// A table of the IPC objects of the same kind.
+pub struct IpcTable<T> {
+    inner: Mutex<IpcTableInner<T>>,
+}
+
+struct IpcTableInner<T> {
+    objects: BTreeMap<IpcId, Arc<T>>,
+    next_id: IpcId,
+}
+
+impl<T: IpcObject> IpcTable<T> {
+    pub const fn new() -> Self {
+        Self {
+            inner: Mutex::new(IpcTableInner {
+                objects: BTreeMap::new(),
+                next_id: 0,
+            }),
+        }
+    }
+
+    /// Gets the object with the ID.
+    pub fn get(&self, id: IpcId) -> Result<Arc<T>> {
+        self.inner
+            .lock()
+            .objects
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the IPC object does not exist"))
+    }
+
+    /// Returns all the objects in the table.
+    pub fn objects(&self) -> Vec<Arc<T>> {
+        self.inner.lock().objects.values().cloned().collect()
+    }
+
+    /// Gets the ID of the object with the key, or creates a new object if required.
+    ///
+    /// If the object exists, `check` is called on it after the permission check.
+    /// Otherwise, `new_object` is called with the ID and the permission of the new object.
+    pub fn get_or_create(
+        &self,
+        key: IpcKey,
+        flags: IpcFlags,
+        mode: u16,
+        check: impl FnOnce(&T) -> Result<()>,
+        new_object: impl FnOnce(IpcId, IpcPermission) -> Result<T>,
+    ) -> Result<IpcId> {
+        let mut inner = self.inner.lock();
+        if key != IPC_PRIVATE
+            && let Some((id, object)) = inner
+                .objects
+                .iter()
+                .find(|(_, object)| object.permission().key() == key)
+        {
+            if flags.contains(IpcFlags::IPC_CREAT | IpcFlags::IPC_EXCL) {
+                return_errno_with_message!(Errno::EEXIST, "the IPC key exists");
+            }
+            object.permission().check(mode)?;
+            check(object)?;
+            return Ok(*id);
+        }
+
+        if key != IPC_PRIVATE && !flags.contains(IpcFlags::IPC_CREAT) {
+            return_errno_with_message!(Errno::ENOENT, "the IPC key does not exist");
+        }
+        let id = inner.alloc_id()?;
+        let object = new_object(id, IpcPermission::new(key, mode))?;
+        inner.objects.insert(id, Arc::new(object));
+        Ok(id)
+    }
+
+    /// Removes the object with the ID from the table.
+    pub fn remove(&self, id: IpcId) -> Option<Arc<T>> {
+        self.inner.lock().objects.remove(&id)
+    }
+}
+
+impl<T> IpcTableInner<T> {
+    fn alloc_id(&mut self) -> Result<IpcId> {
+        for _ in 0..=self.objects.len() {
+            let id = self.next_id;
+            self.next_id = if id == IPC_ID_MAX { 0 } else { id + 1 };
+            if !self.objects.contains_key(&id) {
+                return Ok(id);
+            }
+        }
+        return_errno_with_message!(Errno::ENOSPC, "too many IPC objects");
+    }
+}
+
+/// The threads waiting on an IPC object.
+///
+/// Each thread waits with its own `Pauser`, so that a signal only interrupts the
+/// thread that it is delivered to.
+pub(super) struct IpcWaiters {
+    pausers: Mutex<Vec<Arc<Pauser>>>,
+}
+
+impl IpcWaiters {
+    pub(super) const fn new() -> Self {
+        Self {
+            pausers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Waits until `cond` returns `Some(_)`, the current thread is interrupted by
+    /// a signal, or the timeout (if any) expires.
+    ///
+    /// The condition is checked again whenever the waiters are woken up.
+    pub(super) fn wait_until<F, R>(&self, cond: F, timeout: Option<&Duration>) -> Result<R>
+    where
+        F: FnMut() -> Option<R>,
+    {
+        let pauser = Pauser::new();
+        self.pausers.lock().push(pauser.clone());
+        let res = match timeout {
+            Some(timeout) => pauser.pause_until_or_timeout(cond, timeout),
+            None => pauser.pause_until(cond),
+        };
+        self.pausers
+            .lock()
+            .retain(|waiter| !Arc::ptr_eq(waiter, &pauser));
+        res
+    }
+
+    /// Wakes up all the waiters to check their conditions.
+    pub(super) fn wake_all(&self) {
+        for pauser in self.pausers.lock().iter() {
+            pauser.resume_all();
+        }
+    }
+}
+
+/// Returns the current time in seconds since the Epoch, which is used as
+/// the timestamps of IPC objects.
+fn now_as_secs() -> i64 {
+    now_as_duration(&ClockID::CLOCK_REALTIME)
+        .unwrap_or(Duration::ZERO)
+        .as_secs() as i64
+}
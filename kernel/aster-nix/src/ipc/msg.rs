@@ -0,0 +1,351 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V message queues.
+//!
+//! A message queue holds typed messages. A sender waits if the queue is full,
+//! and a receiver waits until a message of the requested type arrives.
+
+use super::{
+    c_ipc_perm, now_as_secs, IpcFlags, IpcId, IpcKey, IpcObject, IpcPermission, IpcTable,
+    IpcWaiters,
+};
+use crate::{
+    prelude::*,
+    process::{credentials, Gid, Pid, Uid},
+};
+
+/// The maximum size of a message.
+pub const MSGMAX: usize = 8192;
+/// The default maximum number of bytes in a queue.
+pub const MSGMNB: usize = 16384;
+
+static MSG_TABLE: IpcTable<MessageQueue> = IpcTable::new();
+
+bitflags! {
+    /// The flags of `msgsnd` and `msgrcv`.
+    pub struct MsgFlags: u32 {
+        /// Returns an error instead of waiting.
+        const IPC_NOWAIT = 0o4000;
+        /// Truncates the message if it is longer than the buffer.
+        const MSG_NOERROR = 0o10000;
+        /// Receives the first message whose type is not the requested one.
+        const MSG_EXCEPT = 0o20000;
+        /// Copies the message at the position without removing it.
+        const MSG_COPY = 0o40000;
+    }
+}
+
+/// A message.
+pub struct Message {
+    mtype: i64,
+    text: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(mtype: i64, text: Vec<u8>) -> Result<Self> {
+        if mtype <= 0 {
+            return_errno_with_message!(Errno::EINVAL, "the message type must be positive");
+        }
+        if text.len() > MSGMAX {
+            return_errno_with_message!(Errno::EINVAL, "the message is too long");
+        }
+        Ok(Self { mtype, text })
+    }
+
+    pub fn mtype(&self) -> i64 {
+        self.mtype
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+}
+
+/// A System V message queue.
+pub struct MessageQueue {
+    id: IpcId,
+    inner: Mutex<MessageQueueInner>,
+    /// The senders and the receivers waiting on the queue.
+    waiters: IpcWaiters,
+}
+
+struct MessageQueueInner {
+    permission: IpcPermission,
+    is_removed: bool,
+    messages: VecDeque<Message>,
+    /// The number of bytes of the messages in the queue.
+    cbytes: usize,
+    /// The maximum number of bytes in the queue.
+    qbytes: usize,
+    /// The time of the last `msgsnd`.
+    stime: i64,
+    /// The time of the last `msgrcv`.
+    rtime: i64,
+    /// The time of the creation or the last change by `IPC_SET`.
+    ctime: i64,
+    /// The last process that sends a message.
+    lspid: Pid,
+    /// The last process that receives a message.
+    lrpid: Pid,
+}
+
+impl IpcObject for MessageQueue {
+    fn permission(&self) -> IpcPermission {
+        self.inner.lock().permission
+    }
+}
+
+impl MessageQueue {
+    fn new(id: IpcId, permission: IpcPermission) -> Self {
+        let inner = MessageQueueInner {
+            permission,
+            is_removed: false,
+            messages: VecDeque::new(),
+            cbytes: 0,
+            qbytes: MSGMNB,
+            stime: 0,
+            rtime: 0,
+            ctime: now_as_secs(),
+            lspid: 0,
+            lrpid: 0,
+        };
+        Self {
+            id,
+            inner: Mutex::new(inner),
+            waiters: IpcWaiters::new(),
+        }
+    }
+
+    pub fn id(&self) -> IpcId {
+        self.id
+    }
+
+    /// Sends a message, waiting until the queue has enough space.
+    pub fn send(&self, message: Message, flags: MsgFlags) -> Result<()> {
+        self.permission().check(0o222)?;
+
+        let pid = current!().pid();
+        let mut message = Some(message);
+        let try_send = || {
+            let mut inner = self.inner.lock();
+            if inner.is_removed {
+                return Some(Err(Error::with_message(
+                    Errno::EIDRM,
+                    "the message queue is removed",
+                )));
+            }
+            let len = message.as_ref().unwrap().text.len();
+            // Like Linux, the number of messages is also limited by the maximum
+            // number of bytes, so that zero-length messages cannot fill up the memory.
+            if inner.cbytes + len > inner.qbytes || inner.messages.len() + 1 > inner.qbytes {
+                if flags.contains(MsgFlags::IPC_NOWAIT) {
+                    return Some(Err(Error::with_message(
+                        Errno::EAGAIN,
+                        "the message queue is full",
+                    )));
+                }
+                return None;
+            }
+
+            inner.messages.push_back(message.take().unwrap());
+            inner.cbytes += len;
+            inner.stime = now_as_secs();
+            inner.lspid = pid;
+            self.waiters.wake_all();
+            Some(Ok(()))
+        };
+
+        self.waiters.wait_until(try_send, None)?
+    }
+
+    /// Receives a message of the requested type, waiting until such a message arrives.
+    ///
+    /// If `msgtyp` is zero, the first message is received. If `msgtyp` is positive,
+    /// the first message of the type is received, or of any other type with `MSG_EXCEPT`.
+    /// If `msgtyp` is negative, the first message of the lowest type that is less than
+    /// or equal to the absolute value of `msgtyp` is received.
+    ///
+    /// With `MSG_COPY`, `msgtyp` is the position of the message to copy instead.
+    pub fn receive(&self, max_len: usize, msgtyp: i64, flags: MsgFlags) -> Result<Message> {
+        if flags.contains(MsgFlags::MSG_COPY) {
+            return self.copy(max_len, msgtyp, flags);
+        }
+        self.permission().check(0o444)?;
+
+        let pid = current!().pid();
+        let try_receive = || {
+            let mut inner = self.inner.lock();
+            if inner.is_removed {
+                return Some(Err(Error::with_message(
+                    Errno::EIDRM,
+                    "the message queue is removed",
+                )));
+            }
+            let Some(index) = find_message(&inner.messages, msgtyp, flags) else {
+                if flags.contains(MsgFlags::IPC_NOWAIT) {
+                    return Some(Err(Error::with_message(
+                        Errno::ENOMSG,
+                        "no message of the requested type",
+                    )));
+                }
+                return None;
+            };
+            if inner.messages[index].text.len() > max_len && !flags.contains(MsgFlags::MSG_NOERROR)
+            {
+                return Some(Err(Error::with_message(
+                    Errno::E2BIG,
+                    "the message is longer than the buffer",
+                )));
+            }
+
+            let mut message = inner.messages.remove(index).unwrap();
+            inner.cbytes -= message.text.len();
+            inner.rtime = now_as_secs();
+            inner.lrpid = pid;
+            self.waiters.wake_all();
+            message.text.truncate(max_len);
+            Some(Ok(message))
+        };
+
+        self.waiters.wait_until(try_receive, None)?
+    }
+
+    /// Copies the message at the position without removing it, as `msgrcv` does
+    /// with `MSG_COPY`.
+    ///
+    /// Like Linux, `MSG_COPY` must be used with `IPC_NOWAIT` and without `MSG_EXCEPT`.
+    fn copy(&self, max_len: usize, index: i64, flags: MsgFlags) -> Result<Message> {
+        if !flags.contains(MsgFlags::IPC_NOWAIT) || flags.contains(MsgFlags::MSG_EXCEPT) {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "MSG_COPY requires IPC_NOWAIT and excludes MSG_EXCEPT"
+            );
+        }
+
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        if inner.is_removed {
+            return_errno_with_message!(Errno::EIDRM, "the message queue is removed");
+        }
+        let message = usize::try_from(index)
+            .ok()
+            .and_then(|index| inner.messages.get(index))
+            .ok_or_else(|| Error::with_message(Errno::ENOMSG, "no message at the position"))?;
+        if message.text.len() > max_len && !flags.contains(MsgFlags::MSG_NOERROR) {
+            return_errno_with_message!(Errno::E2BIG, "the message is longer than the buffer");
+        }
+        let len = message.text.len().min(max_len);
+        Ok(Message {
+            mtype: message.mtype,
+            text: message.text[..len].to_vec(),
+        })
+    }
+
+    /// Returns the `msqid_ds` of the queue, as `IPC_STAT` does.
+    pub fn stat(&self) -> Result<c_msqid_ds> {
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(c_msqid_ds {
+            msg_perm: inner.permission.to_c(0),
+            msg_stime: inner.stime,
+            msg_rtime: inner.rtime,
+            msg_ctime: inner.ctime,
+            msg_cbytes: inner.cbytes as u64,
+            msg_qnum: inner.messages.len() as u64,
+            msg_qbytes: inner.qbytes as u64,
+            msg_lspid: inner.lspid,
+            msg_lrpid: inner.lrpid,
+            unused4: 0,
+            unused5: 0,
+        })
+    }
+
+    /// Changes the owner, the permission bits and the maximum number of bytes
+    /// of the queue, as `IPC_SET` does.
+    ///
+    /// Only a privileged process can raise the maximum number of bytes beyond `MSGMNB`.
+    pub fn set(&self, msqid_ds: &c_msqid_ds) -> Result<()> {
+        let qbytes = msqid_ds.msg_qbytes as usize;
+        let mut inner = self.inner.lock();
+        inner.permission.check_owner()?;
+        if qbytes > MSGMNB && qbytes > inner.qbytes && !credentials().euid().is_root() {
+            return_errno_with_message!(Errno::EPERM, "raising the queue size is not allowed");
+        }
+        let msg_perm = &msqid_ds.msg_perm;
+        inner.permission.set(
+            Uid::new(msg_perm.uid),
+            Gid::new(msg_perm.gid),
+            msg_perm.mode,
+        )?;
+        inner.qbytes = qbytes;
+        inner.ctime = now_as_secs();
+        drop(inner);
+
+        // The senders may be able to proceed with the new size.
+        self.waiters.wake_all();
+        Ok(())
+    }
+
+    /// Removes the queue, as `IPC_RMID` does.
+    ///
+    /// The processes waiting on the queue are woken up with `EIDRM`.
+    pub fn remove(&self) -> Result<()> {
+        {
+            let mut inner = self.inner.lock();
+            inner.permission.check_owner()?;
+            inner.permission.mark_removed();
+            inner.is_removed = true;
+        }
+        MSG_TABLE.remove(self.id);
+        self.waiters.wake_all();
+        Ok(())
+    }
+}
+
+fn find_message(messages: &VecDeque<Message>, msgtyp: i64, flags: MsgFlags) -> Option<usize> {
+    if msgtyp == 0 {
+        return if messages.is_empty() { None } else { Some(0) };
+    }
+    if msgtyp > 0 {
+        let is_except = flags.contains(MsgFlags::MSG_EXCEPT);
+        return messages
+            .iter()
+            .position(|message| (message.mtype == msgtyp) != is_except);
+    }
+    let max_type = msgtyp.unsigned_abs();
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.mtype as u64 <= max_type)
+        .min_by_key(|(index, message)| (message.mtype, *index))
+        .map(|(index, _)| index)
+}
+
+/// Gets the ID of the queue with the key, or creates a new queue if required.
+pub fn msgget(key: IpcKey, flags: IpcFlags, mode: u16) -> Result<IpcId> {
+    let new_queue = |id, permission| Ok(MessageQueue::new(id, permission));
+    MSG_TABLE.get_or_create(key, flags, mode, |_| Ok(()), new_queue)
+}
+
+/// Gets the queue with the ID.
+pub fn get_message_queue(id: IpcId) -> Result<Arc<MessageQueue>> {
+    MSG_TABLE.get(id)
+}
+
+/// The `msqid64_ds` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct c_msqid_ds {
+    pub msg_perm: c_ipc_perm,
+    pub msg_stime: i64,
+    pub msg_rtime: i64,
+    pub msg_ctime: i64,
+    pub msg_cbytes: u64,
+    pub msg_qnum: u64,
+    pub msg_qbytes: u64,
+    pub msg_lspid: Pid,
+    pub msg_lrpid: Pid,
+    pub unused4: u64,
+    pub unused5: u64,
+}
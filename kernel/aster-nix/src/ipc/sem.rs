@@ -0,0 +1,419 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V semaphores.
+//!
+//! A semaphore set contains an array of semaphores, on which a process can
+//! perform an array of operations atomically. If the operations cannot be
+//! performed at once, the process waits until they can.
+
+use core::time::Duration;
+
+use super::{
+    c_ipc_perm, now_as_secs, IpcFlags, IpcId, IpcKey, IpcObject, IpcPermission, IpcTable,
+    IpcWaiters,
+};
+use crate::{
+    prelude::*,
+    process::{Gid, Pid, Uid},
+};
+
+/// The maximum number of semaphores in a set.
+pub const SEMMSL: usize = 32000;
+/// The maximum number of operations in a `semop` call.
+pub const SEMOPM: usize = 500;
+/// The maximum value of a semaphore.
+pub const SEMVMX: i32 = 32767;
+
+static SEM_TABLE: IpcTable<SemaphoreSet> = IpcTable::new();
+
+/// Returns an error instead of waiting.
+const IPC_NOWAIT: i16 = 0o4000;
+/// Undoes the operation when the process exits.
+const SEM_UNDO: i16 = 0x1000;
+
+/// An operation on a semaphore, i.e., the `sembuf` structure of Linux.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct SemBuf {
+    pub sem_num: u16,
+    pub sem_op: i16,
+    pub sem_flg: i16,
+}
+
+/// A System V semaphore set.
+pub struct SemaphoreSet {
+    id: IpcId,
+    nsems: usize,
+    inner: Mutex<SemaphoreSetInner>,
+    /// The processes waiting for the operations on the set.
+    waiters: IpcWaiters,
+}
+
+struct SemaphoreSetInner {
+    permission: IpcPermission,
+    is_removed: bool,
+    semaphores: Vec<Semaphore>,
+    /// The time of the last `semop`.
+    otime: i64,
+    /// The time of the creation or the last change by `semctl`.
+    ctime: i64,
+    /// The adjustments to be applied when the processes exit, recorded by `SEM_UNDO`.
+    adjustments: BTreeMap<Pid, Vec<i32>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Semaphore {
+    value: i32,
+    /// The last process that operates on the semaphore.
+    pid: Pid,
+    /// The number of processes waiting for the value to increase.
+    ncnt: usize,
+    /// The number of processes waiting for the value to become zero.
+    zcnt: usize,
+}
+
+impl IpcObject for SemaphoreSet {
+    fn permission(&self) -> IpcPermission {
+        self.inner.lock().permission
+    }
+}
+
+impl SemaphoreSet {
+    fn new(id: IpcId, permission: IpcPermission, nsems: usize) -> Self {
+        let inner = SemaphoreSetInner {
+            permission,
+            is_removed: false,
+            semaphores: vec![Semaphore::default(); nsems],
+            otime: 0,
+            ctime: now_as_secs(),
+            adjustments: BTreeMap::new(),
+        };
+        Self {
+            id,
+            nsems,
+            inner: Mutex::new(inner),
+            waiters: IpcWaiters::new(),
+        }
+    }
+
+    pub fn id(&self) -> IpcId {
+        self.id
+    }
+
+    pub fn nsems(&self) -> usize {
+        self.nsems
+    }
+
+    /// Performs the operations atomically, waiting until they can be performed
+    /// or the timeout expires.
+    pub fn semop(&self, ops: &[SemBuf], timeout: Option<&Duration>) -> Result<()> {
+        if ops.len() > SEMOPM {
+            return_errno_with_message!(Errno::E2BIG, "too many operations");
+        }
+        if ops.iter().any(|op| op.sem_num as usize >= self.nsems) {
+            return_errno_with_message!(Errno::EFBIG, "the semaphore does not exist");
+        }
+        let alters = ops.iter().any(|op| op.sem_op != 0);
+        self.permission()
+            .check(if alters { 0o222 } else { 0o444 })?;
+
+        let pid = current!().pid();
+        if let Some(res) = self.try_semop(ops, pid) {
+            return res;
+        }
+
+        self.update_wait_counts(ops, true);
+        let res = self
+            .waiters
+            .wait_until(|| self.try_semop(ops, pid), timeout);
+        self.update_wait_counts(ops, false);
+
+        match res {
+            Ok(res) => res,
+            Err(err) if err.error() == Errno::ETIME => {
+                return_errno_with_message!(Errno::EAGAIN, "the timeout expires")
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Tries to perform the operations atomically.
+    ///
+    /// Returns `None` if the operations cannot be performed without waiting.
+    fn try_semop(&self, ops: &[SemBuf], pid: Pid) -> Option<Result<()>> {
+        let mut inner = self.inner.lock();
+        if inner.is_removed {
+            return Some(Err(Error::with_message(
+                Errno::EIDRM,
+                "the semaphore set is removed",
+            )));
+        }
+
+        let mut values: Vec<i32> = inner.semaphores.iter().map(|sem| sem.value).collect();
+        for op in ops {
+            let value = &mut values[op.sem_num as usize];
+            let sem_op = op.sem_op as i32;
+            if (sem_op == 0 && *value != 0) || *value + sem_op < 0 {
+                if op.sem_flg & IPC_NOWAIT != 0 {
+                    return Some(Err(Error::with_message(
+                        Errno::EAGAIN,
+                        "the operations cannot be performed immediately",
+                    )));
+                }
+                return None;
+            }
+            if *value + sem_op > SEMVMX {
+                return Some(Err(Error::with_message(
+                    Errno::ERANGE,
+                    "the value of the semaphore is too large",
+                )));
+            }
+            *value += sem_op;
+        }
+
+        let inner = &mut *inner;
+        for (semaphore, value) in inner.semaphores.iter_mut().zip(values) {
+            semaphore.value = value;
+        }
+        for op in ops {
+            let num = op.sem_num as usize;
+            inner.semaphores[num].pid = pid;
+            if op.sem_flg & SEM_UNDO != 0 && op.sem_op != 0 {
+                let adjustments = inner
+                    .adjustments
+                    .entry(pid)
+                    .or_insert_with(|| vec![0; self.nsems]);
+                adjustments[num] -= op.sem_op as i32;
+            }
+        }
+        inner.otime = now_as_secs();
+
+        self.waiters.wake_all();
+        Some(Ok(()))
+    }
+
+    fn update_wait_counts(&self, ops: &[SemBuf], is_waiting: bool) {
+        let mut inner = self.inner.lock();
+        for op in ops {
+            let semaphore = &mut inner.semaphores[op.sem_num as usize];
+            let count = match op.sem_op {
+                0 => &mut semaphore.zcnt,
+                sem_op if sem_op < 0 => &mut semaphore.ncnt,
+                _ => continue,
+            };
+            if is_waiting {
+                *count += 1;
+            } else {
+                *count -= 1;
+            }
+        }
+    }
+
+    fn check_num(&self, num: usize) -> Result<()> {
+        if num >= self.nsems {
+            return_errno_with_message!(Errno::EINVAL, "the semaphore does not exist");
+        }
+        Ok(())
+    }
+
+    /// Returns the value of a semaphore, as `GETVAL` does.
+    pub fn value(&self, num: usize) -> Result<i32> {
+        self.check_num(num)?;
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(inner.semaphores[num].value)
+    }
+
+    /// Returns the values of all the semaphores, as `GETALL` does.
+    pub fn values(&self) -> Result<Vec<u16>> {
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(inner
+            .semaphores
+            .iter()
+            .map(|semaphore| semaphore.value as u16)
+            .collect())
+    }
+
+    /// Returns the last process that operates on a semaphore, as `GETPID` does.
+    pub fn last_pid(&self, num: usize) -> Result<Pid> {
+        self.check_num(num)?;
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(inner.semaphores[num].pid)
+    }
+
+    /// Returns the number of the processes waiting for a semaphore to increase,
+    /// as `GETNCNT` does.
+    pub fn ncnt(&self, num: usize) -> Result<usize> {
+        self.check_num(num)?;
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(inner.semaphores[num].ncnt)
+    }
+
+    /// Returns the number of the processes waiting for a semaphore to become zero,
+    /// as `GETZCNT` does.
+    pub fn zcnt(&self, num: usize) -> Result<usize> {
+        self.check_num(num)?;
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(inner.semaphores[num].zcnt)
+    }
+
+    /// Sets the value of a semaphore, as `SETVAL` does.
+    ///
+    /// The adjustments of the semaphore recorded by `SEM_UNDO` are cleared.
+    pub fn set_value(&self, num: usize, value: i32) -> Result<()> {
+        self.check_num(num)?;
+        if !(0..=SEMVMX).contains(&value) {
+            return_errno_with_message!(Errno::ERANGE, "the value is out of range");
+        }
+        let mut inner = self.inner.lock();
+        inner.permission.check(0o222)?;
+        let pid = current!().pid();
+        inner.semaphores[num].value = value;
+        inner.semaphores[num].pid = pid;
+        for adjustments in inner.adjustments.values_mut() {
+            adjustments[num] = 0;
+        }
+        inner.ctime = now_as_secs();
+        drop(inner);
+
+        self.waiters.wake_all();
+        Ok(())
+    }
+
+    /// Sets the values of all the semaphores, as `SETALL` does.
+    ///
+    /// All the adjustments recorded by `SEM_UNDO` are cleared.
+    pub fn set_values(&self, values: &[u16]) -> Result<()> {
+        debug_assert_eq!(values.len(), self.nsems);
+        if values.iter().any(|value| *value as i32 > SEMVMX) {
+            return_errno_with_message!(Errno::ERANGE, "the value is out of range");
+        }
+        let mut inner = self.inner.lock();
+        inner.permission.check(0o222)?;
+        let pid = current!().pid();
+        for (semaphore, value) in inner.semaphores.iter_mut().zip(values) {
+            semaphore.value = *value as i32;
+            semaphore.pid = pid;
+        }
+        inner.adjustments.clear();
+        inner.ctime = now_as_secs();
+        drop(inner);
+
+        self.waiters.wake_all();
+        Ok(())
+    }
+
+    /// Returns the `semid_ds` of the set, as `IPC_STAT` does.
+    pub fn stat(&self) -> Result<c_semid_ds> {
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(c_semid_ds {
+            sem_perm: inner.permission.to_c(0),
+            sem_otime: inner.otime,
+            unused1: 0,
+            sem_ctime: inner.ctime,
+            unused2: 0,
+            sem_nsems: self.nsems as u64,
+            unused3: 0,
+            unused4: 0,
+        })
+    }
+
+    /// Changes the owner and the permission bits, as `IPC_SET` does.
+    pub fn set(&self, semid_ds: &c_semid_ds) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let sem_perm = &semid_ds.sem_perm;
+        inner.permission.set(
+            Uid::new(sem_perm.uid),
+            Gid::new(sem_perm.gid),
+            sem_perm.mode,
+        )?;
+        inner.ctime = now_as_secs();
+        Ok(())
+    }
+
+    /// Removes the set, as `IPC_RMID` does.
+    ///
+    /// The processes waiting on the set are woken up with `EIDRM`.
+    pub fn remove(&self) -> Result<()> {
+        {
+            let mut inner = self.inner.lock();
+            inner.permission.check_owner()?;
+            inner.permission.mark_removed();
+            inner.is_removed = true;
+        }
+        SEM_TABLE.remove(self.id);
+        self.waiters.wake_all();
+        Ok(())
+    }
+
+    /// Applies the adjustments of the process, which is exiting.
+    fn undo(&self, pid: Pid) {
+        let mut inner = self.inner.lock();
+        let Some(adjustments) = inner.adjustments.remove(&pid) else {
+            return;
+        };
+        for (semaphore, adjustment) in inner.semaphores.iter_mut().zip(adjustments) {
+            if adjustment == 0 {
+                continue;
+            }
+            // The result is clamped, as Linux does.
+            semaphore.value = (semaphore.value + adjustment).clamp(0, SEMVMX);
+            semaphore.pid = pid;
+        }
+        drop(inner);
+
+        self.waiters.wake_all();
+    }
+}
+
+/// Gets the ID of the set with the key, or creates a new set if required.
+pub fn semget(key: IpcKey, nsems: usize, flags: IpcFlags, mode: u16) -> Result<IpcId> {
+    let check_nsems = |set: &SemaphoreSet| {
+        if nsems > set.nsems() {
+            return_errno_with_message!(
+                Errno::EINVAL,
+                "the number of semaphores exceeds that of the set"
+            );
+        }
+        Ok(())
+    };
+    let new_set = |id, permission| {
+        if nsems == 0 || nsems > SEMMSL {
+            return_errno_with_message!(Errno::EINVAL, "invalid number of semaphores");
+        }
+        Ok(SemaphoreSet::new(id, permission, nsems))
+    };
+    SEM_TABLE.get_or_create(key, flags, mode, check_nsems, new_set)
+}
+
+/// Gets the set with the ID.
+pub fn get_semaphore_set(id: IpcId) -> Result<Arc<SemaphoreSet>> {
+    SEM_TABLE.get(id)
+}
+
+/// Applies the adjustments recorded by `SEM_UNDO` for the exiting process.
+pub fn exit_sem(pid: Pid) {
+    for set in SEM_TABLE.objects() {
+        set.undo(pid);
+    }
+}
+
+/// The `semid64_ds` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct c_semid_ds {
+    pub sem_perm: c_ipc_perm,
+    pub sem_otime: i64,
+    pub unused1: u64,
+    pub sem_ctime: i64,
+    pub unused2: u64,
+    pub sem_nsems: u64,
+    pub unused3: u64,
+    pub unused4: u64,
+}
@@ -0,0 +1,313 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! System V shared memory.
+//!
+//! A shared memory segment is backed by a VMO, which is mapped into the address
+//! space of every process that attaches the segment. The mappings are shared, so
+//! they are inherited rather than copied-on-write by the child of `fork`.
+//!
+//! Each mapping of a segment holds a `ShmAttachment`, which counts the attachments
+//! of the segment. As in Linux, the mappings that are split from a mapping or that
+//! are inherited by the child count as separate attachments.
+
+use align_ext::AlignExt;
+use aster_rights::Rights;
+
+use super::{c_ipc_perm, now_as_secs, IpcFlags, IpcId, IpcKey, IpcObject, IpcPermission, IpcTable};
+use crate::{
+    prelude::*,
+    process::{Gid, Pid, Uid},
+    vm::{
+        perms::VmPerms,
+        vmo::{Vmo, VmoOptions},
+    },
+};
+
+/// The minimum size of a segment.
+pub const SHMMIN: usize = 1;
+/// The maximum size of a segment, which is the same as the default value of Linux.
+pub const SHMMAX: usize = usize::MAX - (1 << 24);
+
+static SHM_TABLE: IpcTable<ShmSegment> = IpcTable::new();
+
+bitflags! {
+    /// The flags of `shmat`.
+    pub struct ShmFlags: u32 {
+        /// Attaches the segment for read-only access.
+        const SHM_RDONLY = 0o10000;
+        /// Rounds the attach address down to a multiple of `SHMLBA`.
+        const SHM_RND = 0o20000;
+        /// Replaces the existing mappings in the range of the segment.
+        const SHM_REMAP = 0o40000;
+        /// Attaches the segment for execute access.
+        const SHM_EXEC = 0o100000;
+    }
+}
+
+/// The alignment of the attach address.
+const SHMLBA: usize = PAGE_SIZE;
+
+/// A System V shared memory segment.
+pub struct ShmSegment {
+    id: IpcId,
+    /// The size requested on creation, which may not be page-aligned.
+    size: usize,
+    vmo: Vmo<Rights>,
+    inner: Mutex<ShmSegmentInner>,
+}
+
+struct ShmSegmentInner {
+    permission: IpcPermission,
+    is_removed: bool,
+    /// The time of the last `shmat`.
+    atime: i64,
+    /// The time of the last `shmdt`.
+    dtime: i64,
+    /// The time of the creation or the last change by `IPC_SET`.
+    ctime: i64,
+    /// The creator.
+    cpid: Pid,
+    /// The last process that attaches or detaches the segment.
+    lpid: Pid,
+    /// The number of the attachments.
+    nattch: usize,
+}
+
+impl IpcObject for ShmSegment {
+    fn permission(&self) -> IpcPermission {
+        self.inner.lock().permission
+    }
+}
+
+impl ShmSegment {
+    fn new(id: IpcId, permission: IpcPermission, size: usize) -> Result<Self> {
+        let vmo = VmoOptions::<Rights>::new(size.align_up(PAGE_SIZE)).alloc()?;
+        let inner = ShmSegmentInner {
+            permission,
+            is_removed: false,
+            atime: 0,
+            dtime: 0,
+            ctime: now_as_secs(),
+            cpid: current!().pid(),
+            lpid: 0,
+            nattch: 0,
+        };
+        Ok(Self {
+            id,
+            size,
+            vmo,
+            inner: Mutex::new(inner),
+        })
+    }
+
+    pub fn id(&self) -> IpcId {
+        self.id
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of the attachments.
+    pub fn nattch(&self) -> usize {
+        self.inner.lock().nattch
+    }
+
+    /// Attaches the segment to the address space of the current process.
+    ///
+    /// If `addr` is zero, the address is chosen automatically.
+    pub fn attach(self: &Arc<Self>, addr: Vaddr, flags: ShmFlags) -> Result<Vaddr> {
+        let mut perms = VmPerms::READ;
+        let mut requested = 0o444;
+        if !flags.contains(ShmFlags::SHM_RDONLY) {
+            perms |= VmPerms::WRITE;
+            requested |= 0o222;
+        }
+        if flags.contains(ShmFlags::SHM_EXEC) {
+            perms |= VmPerms::EXEC;
+            requested |= 0o111;
+        }
+        self.permission().check(requested)?;
+
+        let addr = if flags.contains(ShmFlags::SHM_RND) {
+            addr.align_down(SHMLBA)
+        } else {
+            addr
+        };
+        if addr % SHMLBA != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the attach address is not aligned");
+        }
+        if addr == 0 && flags.contains(ShmFlags::SHM_REMAP) {
+            return_errno_with_message!(Errno::EINVAL, "SHM_REMAP requires an attach address");
+        }
+
+        let current = current!();
+        let root_vmar = current.root_vmar();
+        let mut options = root_vmar
+            .new_map(self.vmo.dup()?, perms)?
+            .is_shared(true)
+            .shm_attachment(ShmAttachment::new(self.clone()));
+        if addr != 0 {
+            options = options
+                .offset(addr)
+                .can_overwrite(flags.contains(ShmFlags::SHM_REMAP));
+        }
+        let addr = options.build()?;
+
+        let mut inner = self.inner.lock();
+        inner.atime = now_as_secs();
+        inner.lpid = current.pid();
+        Ok(addr)
+    }
+
+    /// Returns the `shmid_ds` of the segment, as `IPC_STAT` does.
+    pub fn stat(&self) -> Result<c_shmid_ds> {
+        let inner = self.inner.lock();
+        inner.permission.check(0o444)?;
+        Ok(c_shmid_ds {
+            shm_perm: inner.permission.to_c(0),
+            shm_segsz: self.size as u64,
+            shm_atime: inner.atime,
+            shm_dtime: inner.dtime,
+            shm_ctime: inner.ctime,
+            shm_cpid: inner.cpid,
+            shm_lpid: inner.lpid,
+            shm_nattch: self.nattch() as u64,
+            unused4: 0,
+            unused5: 0,
+        })
+    }
+
+    /// Changes the owner and the permission bits, as `IPC_SET` does.
+    pub fn set(&self, shmid_ds: &c_shmid_ds) -> Result<()> {
+        let mut inner = self.inner.lock();
+        let shm_perm = &shmid_ds.shm_perm;
+        inner.permission.set(
+            Uid::new(shm_perm.uid),
+            Gid::new(shm_perm.gid),
+            shm_perm.mode,
+        )?;
+        inner.ctime = now_as_secs();
+        Ok(())
+    }
+
+    /// Marks the segment to be removed, as `IPC_RMID` does.
+    ///
+    /// The segment can no longer be found by its key. It is removed when it is
+    /// detached by all the processes.
+    pub fn remove(&self) -> Result<()> {
+        let mut inner = self.inner.lock();
+        inner.permission.check_owner()?;
+        inner.permission.mark_removed();
+        inner.is_removed = true;
+        let is_detached = inner.nattch == 0;
+        // The table is locked before the segments when it is searched.
+        drop(inner);
+        if is_detached {
+            SHM_TABLE.remove(self.id);
+        }
+        Ok(())
+    }
+}
+
+/// An attachment of a segment, which is held by a mapping of the segment.
+pub struct ShmAttachment {
+    segment: Arc<ShmSegment>,
+}
+
+impl ShmAttachment {
+    fn new(segment: Arc<ShmSegment>) -> Self {
+        segment.inner.lock().nattch += 1;
+        Self { segment }
+    }
+
+    pub fn segment(&self) -> &Arc<ShmSegment> {
+        &self.segment
+    }
+}
+
+impl Clone for ShmAttachment {
+    fn clone(&self) -> Self {
+        Self::new(self.segment.clone())
+    }
+}
+
+impl Drop for ShmAttachment {
+    /// Detaches the segment, which happens when the mapping is unmapped, e.g., by
+    /// `shmdt`, `munmap` or the exit of the process.
+    fn drop(&mut self) {
+        let mut inner = self.segment.inner.lock();
+        inner.nattch -= 1;
+        inner.dtime = now_as_secs();
+        let should_remove = inner.is_removed && inner.nattch == 0;
+        drop(inner);
+        if should_remove {
+            SHM_TABLE.remove(self.segment.id);
+        }
+    }
+}
+
+/// Gets the ID of the segment with the key, or creates a new segment if required.
+pub fn shmget(key: IpcKey, size: usize, flags: IpcFlags, mode: u16) -> Result<IpcId> {
+    let check_size = |segment: &ShmSegment| {
+        if size > segment.size() {
+            return_errno_with_message!(Errno::EINVAL, "the size exceeds that of the segment");
+        }
+        Ok(())
+    };
+    let new_segment = |id, permission| {
+        if !(SHMMIN..=SHMMAX).contains(&size) {
+            return_errno_with_message!(Errno::EINVAL, "invalid segment size");
+        }
+        ShmSegment::new(id, permission, size)
+    };
+    SHM_TABLE.get_or_create(key, flags, mode, check_size, new_segment)
+}
+
+/// Gets the segment with the ID.
+pub fn get_shm_segment(id: IpcId) -> Result<Arc<ShmSegment>> {
+    SHM_TABLE.get(id)
+}
+
+/// Detaches the segment that is attached at the address of the current process.
+pub fn shmdt(addr: Vaddr) -> Result<()> {
+    if addr % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the address is not aligned");
+    }
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    let vm_mapping = root_vmar.get_vm_mapping(addr)?;
+    if vm_mapping.map_to_addr() != addr {
+        return_errno_with_message!(Errno::EINVAL, "no segment is attached at the address");
+    }
+    let segment = vm_mapping
+        .shm_attachment()
+        .map(|attachment| attachment.segment().clone())
+        .ok_or_else(|| {
+            Error::with_message(Errno::EINVAL, "no segment is attached at the address")
+        })?;
+    let range = addr..addr + vm_mapping.map_size();
+    drop(vm_mapping);
+    root_vmar.destroy(range)?;
+
+    segment.inner.lock().lpid = current.pid();
+    Ok(())
+}
+
+/// The `shmid64_ds` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct c_shmid_ds {
+    pub shm_perm: c_ipc_perm,
+    pub shm_segsz: u64,
+    pub shm_atime: i64,
+    pub shm_dtime: i64,
+    pub shm_ctime: i64,
+    pub shm_cpid: Pid,
+    pub shm_lpid: Pid,
+    pub shm_nattch: u64,
+    pub unused4: u64,
+    pub unused5: u64,
+}
@@ -55,6 +55,7 @@ pub mod driver;
 pub mod error;
 pub mod events;
 pub mod fs;
+mod ipc;
 pub mod net;
 pub mod prelude;
 mod process;
@@ -98,6 +99,8 @@ fn init_thread() {
         thread.tid()
     );
     thread::work_queue::init();
+    vm::reclaim::init();
+    vm::oom::init();
 
     print_banner();
 
@@ -10,6 +10,7 @@ use super::{
     posix_thread::{PosixThread, PosixThreadBuilder, PosixThreadExt, ThreadName},
     process_table,
     process_vm::ProcessVm,
+    ptrace::ptrace_clone_event,
     signal::sig_disposition::SigDispositions,
     Credentials, Process, ProcessBuilder,
 };
@@ -107,7 +108,9 @@ impl CloneFlags {
             | CloneFlags::CLONE_SETTLS
             | CloneFlags::CLONE_PARENT_SETTID
             | CloneFlags::CLONE_CHILD_SETTID
-            | CloneFlags::CLONE_CHILD_CLEARTID;
+            | CloneFlags::CLONE_CHILD_CLEARTID
+            | CloneFlags::CLONE_PTRACE
+            | CloneFlags::CLONE_UNTRACED;
         let unsupported_flags = *self - supported_flags;
         if !unsupported_flags.is_empty() {
             panic!("contains unsupported clone flags: {:?}", unsupported_flags);
@@ -127,6 +130,7 @@ pub fn clone_child(parent_context: UserContext, clone_args: CloneArgs) -> Result
             current_thread!().tid(),
             child_tid
         );
+        ptrace_clone_event(&child_thread, clone_args.clone_flags);
         child_thread.run();
         debug!(
             "*********return to parent thread, current tid = {}, child pid = {}*********",
@@ -142,6 +146,9 @@ pub fn clone_child(parent_context: UserContext, clone_args: CloneArgs) -> Result
             current!().pid(),
             child_pid
         );
+        if let Some(child_thread) = child_process.main_thread() {
+            ptrace_clone_event(&child_thread, clone_args.clone_flags);
+        }
         child_process.run();
         debug!(
             "*********return to parent process, current pid = {}, child pid = {}*********",
@@ -180,6 +187,15 @@ fn clone_child_thread(parent_context: UserContext, clone_args: CloneArgs) -> Res
         *sigmask
     };
 
+    // Inherit scheduling policy and CPU affinity from current thread
+    let sched_policy = current_thread!().sched_entity().policy();
+    let cpu_affinity = {
+        let current_thread = current_thread!();
+        let current_posix_thread = current_thread.as_posix_thread().unwrap();
+        let cpu_affinity = current_posix_thread.cpu_affinity().lock_irq_disabled();
+        cpu_affinity.clone()
+    };
+
     let child_tid = allocate_tid();
     let child_thread = {
         let is_main_thread = child_tid == current.pid();
@@ -192,6 +208,8 @@ fn clone_child_thread(parent_context: UserContext, clone_args: CloneArgs) -> Res
         let thread_builder = PosixThreadBuilder::new(child_tid, child_user_space, credentials)
             .process(Arc::downgrade(&current))
             .sig_mask(sig_mask)
+            .sched_policy(sched_policy)
+            .cpu_affinity(cpu_affinity)
             .is_main_thread(is_main_thread);
         thread_builder.build()
     };
@@ -265,6 +283,21 @@ fn clone_child_process(parent_context: UserContext, clone_args: CloneArgs) -> Re
     // inherit parent's nice value
     let child_nice = current.nice().load(Ordering::Relaxed);
 
+    // inherit parent's OOM score adjustment
+    let child_oom_score_adj = current.oom_score_adj().load(Ordering::Relaxed);
+
+    // inherit parent's personality
+    let child_personality = current.personality().load(Ordering::Relaxed);
+
+    // inherit parent's scheduling policy and CPU affinity
+    let child_sched_policy = current_thread!().sched_entity().policy();
+    let child_cpu_affinity = {
+        let current_thread = current_thread!();
+        let posix_thread = current_thread.as_posix_thread().unwrap();
+        let cpu_affinity = posix_thread.cpu_affinity().lock_irq_disabled();
+        cpu_affinity.clone()
+    };
+
     let child_tid = allocate_tid();
 
     let child = {
@@ -280,6 +313,8 @@ fn clone_child_process(parent_context: UserContext, clone_args: CloneArgs) -> Re
             PosixThreadBuilder::new(child_tid, child_user_space, credentials)
                 .thread_name(Some(child_thread_name))
                 .sig_mask(child_sig_mask)
+                .sched_policy(child_sched_policy)
+                .cpu_affinity(child_cpu_affinity)
         };
 
         let mut process_builder =
@@ -292,7 +327,9 @@ fn clone_child_process(parent_context: UserContext, clone_args: CloneArgs) -> Re
             .fs(child_fs)
             .umask(child_umask)
             .sig_dispositions(child_sig_dispositions)
-            .nice(child_nice);
+            .nice(child_nice)
+            .oom_score_adj(child_oom_score_adj)
+            .personality(child_personality);
 
         process_builder.build()?
     };
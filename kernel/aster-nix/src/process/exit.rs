@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{process_table, Pid, Process, TermStatus};
+use super::{process_table, ptrace, Pid, Process, TermStatus};
 use crate::{
+    ipc::sem::exit_sem,
     prelude::*,
     process::{
         posix_thread::PosixThreadExt,
@@ -30,15 +31,31 @@ pub fn do_exit_group(term_status: TermStatus) {
             if let Err(e) = posix_thread.exit(tid, term_status) {
                 debug!("Ignore error when call exit: {:?}", e);
             }
+            ptrace::notify_tracer_of_exit(posix_thread);
         }
     }
 
+    // Detach all threads traced by the process
+    ptrace::detach_all_tracees(&current);
+
+    // Undo the semaphore operations with `SEM_UNDO`
+    exit_sem(current.pid());
+
+    // Delete all timers so that no more signals are sent to the process
+    current.timers().clear();
+
     // Close all files then exit the process
     let files = current.file_table().lock().close_all();
     for file in files {
         let _ = file.clean_for_close();
     }
 
+    // Release the memory now, since the process may not be reaped soon. The memory
+    // shared with other processes (e.g., the parent of `vfork`) is kept.
+    if current.root_vmar().num_refs() == 1 {
+        let _ = current.root_vmar().clear();
+    }
+
     // Move children to the init process
     if !is_init_process(&current) {
         if let Some(init_process) = get_init_process() {
@@ -4,6 +4,7 @@ mod clone;
 mod credentials;
 mod exit;
 mod kill;
+mod personality;
 pub mod posix_thread;
 #[allow(clippy::module_inception)]
 mod process;
@@ -11,16 +12,19 @@ mod process_filter;
 pub mod process_table;
 mod process_vm;
 mod program_loader;
+pub mod ptrace;
 mod rlimit;
 pub mod signal;
 mod status;
 mod term_status;
+mod timer;
 mod wait;
 
 pub use clone::{clone_child, CloneArgs, CloneFlags};
 pub use credentials::{credentials, credentials_mut, Credentials, Gid, Uid};
 pub use exit::do_exit_group;
 pub use kill::{kill, kill_all, kill_group, tgkill};
+pub use personality::PersonalityFlags;
 pub use process::{
     current, ExitCode, JobControl, Pgid, Pid, Process, ProcessBuilder, ProcessGroup, Session, Sid,
     Terminal,
@@ -30,4 +34,5 @@ pub use process_vm::{MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN}
 pub use program_loader::{check_executable_file, load_program_to_vm};
 pub use rlimit::ResourceType;
 pub use term_status::TermStatus;
+pub use timer::{ITimerKind, PosixTimer, ProcessTimers, TimerClock, TimerId, TimerNotify};
 pub use wait::{wait_child_exit, WaitOptions};
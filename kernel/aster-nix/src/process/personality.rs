@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The personality of a process, which is set by the `personality` system call.
+//!
+//! A personality consists of an execution domain in its lowest byte and the flags
+//! in the other bits. Only the Linux execution domain (`PER_LINUX`, which is zero)
+//! is supported, so the personality only matters for its flags.
+
+use crate::prelude::*;
+
+bitflags! {
+    /// The flags of a personality.
+    pub struct PersonalityFlags: u32 {
+        const UNAME26            = 0x0020000;
+        /// Disables the address space layout randomization.
+        const ADDR_NO_RANDOMIZE  = 0x0040000;
+        const FDPIC_FUNCPTRS     = 0x0080000;
+        const MMAP_PAGE_ZERO     = 0x0100000;
+        const ADDR_COMPAT_LAYOUT = 0x0200000;
+        const READ_IMPLIES_EXEC  = 0x0400000;
+        const ADDR_LIMIT_32BIT   = 0x0800000;
+        const SHORT_INODE        = 0x1000000;
+        const WHOLE_SECONDS      = 0x2000000;
+        const STICKY_TIMEOUTS    = 0x4000000;
+        const ADDR_LIMIT_3GB     = 0x8000000;
+    }
+}
+
+impl PersonalityFlags {
+    /// The flags that are cleared when executing a set-user-ID or set-group-ID
+    /// program, since they may weaken the security of the program.
+    pub const CLEAR_ON_SETID: Self = Self::READ_IMPLIES_EXEC
+        .union(Self::ADDR_NO_RANDOMIZE)
+        .union(Self::ADDR_COMPAT_LAYOUT)
+        .union(Self::MMAP_PAGE_ZERO);
+}
@@ -1,18 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_frame::user::UserSpace;
+use core::sync::atomic::Ordering;
 
-use super::{PosixThread, PosixThreadExt, RealTimer};
+use aster_frame::{cpu::CpuSet, user::UserSpace};
+
+use super::PosixThread;
 use crate::{
     prelude::*,
     process::{
         posix_thread::name::ThreadName,
-        signal::{
-            constants::SIGALRM, sig_mask::SigMask, sig_queues::SigQueues,
-            signals::kernel::KernelSignal,
-        },
+        signal::{sig_mask::SigMask, sig_queues::SigQueues},
         Credentials, Process,
     },
+    sched::policy::SchedPolicy,
     thread::{status::ThreadStatus, task, thread_table, Thread, Tid},
 };
 
@@ -30,6 +30,8 @@ pub struct PosixThreadBuilder {
     clear_child_tid: Vaddr,
     sig_mask: SigMask,
     sig_queues: SigQueues,
+    sched_policy: SchedPolicy,
+    cpu_affinity: CpuSet,
     is_main_thread: bool,
 }
 
@@ -45,6 +47,8 @@ impl PosixThreadBuilder {
             clear_child_tid: 0,
             sig_mask: SigMask::new_empty(),
             sig_queues: SigQueues::new(),
+            sched_policy: SchedPolicy::default(),
+            cpu_affinity: CpuSet::new_full(),
             is_main_thread: true,
         }
     }
@@ -80,6 +84,16 @@ impl PosixThreadBuilder {
         self
     }
 
+    pub fn sched_policy(mut self, sched_policy: SchedPolicy) -> Self {
+        self.sched_policy = sched_policy;
+        self
+    }
+
+    pub fn cpu_affinity(mut self, cpu_affinity: CpuSet) -> Self {
+        self.cpu_affinity = cpu_affinity;
+        self
+    }
+
     pub fn build(self) -> Arc<Thread> {
         let Self {
             tid,
@@ -91,22 +105,16 @@ impl PosixThreadBuilder {
             clear_child_tid,
             sig_mask,
             sig_queues,
+            sched_policy,
+            cpu_affinity,
             is_main_thread,
         } = self;
 
-        let real_timer = RealTimer::new(move || {
-            let process = {
-                let Some(current_thread) = thread_table::get_thread(tid) else {
-                    return;
-                };
-                let posix_thread = current_thread.as_posix_thread().unwrap();
-                posix_thread.process()
-            };
-
-            let signal = KernelSignal::new(SIGALRM);
-            process.enqueue_signal(signal);
-        })
-        .unwrap();
+        // The threads of a process share the nice value of the process.
+        let nice = process
+            .upgrade()
+            .map(|process| process.nice().load(Ordering::Relaxed))
+            .unwrap_or_default();
 
         let thread = Arc::new_cyclic(|thread_ref| {
             let task = task::create_new_user_task(user_space, thread_ref.clone());
@@ -118,16 +126,19 @@ impl PosixThreadBuilder {
                 set_child_tid: Mutex::new(set_child_tid),
                 clear_child_tid: Mutex::new(clear_child_tid),
                 credentials,
-                real_timer: Mutex::new(real_timer),
                 sig_mask: Mutex::new(sig_mask),
                 sig_queues: Mutex::new(sig_queues),
                 sig_context: Mutex::new(None),
                 sig_stack: Mutex::new(None),
                 robust_list: Mutex::new(None),
+                cpu_affinity: SpinLock::new(cpu_affinity),
+                ptrace: Mutex::new(None),
             };
 
             Thread::new(tid, task, posix_thread, status)
         });
+        thread.sched_entity().set_policy(sched_policy);
+        thread.sched_entity().set_nice(nice);
         thread_table::add_thread(thread.clone());
         thread
     }
@@ -1,5 +1,8 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::time::Duration;
+
+use aster_frame::cpu::CpuSet;
 use aster_rights::{ReadOp, WriteOp};
 use futex::futex_wake;
 use robust_list::wake_robust_futex;
@@ -7,6 +10,7 @@ use robust_list::wake_robust_futex;
 use super::{
     do_exit_group,
     kill::SignalSenderIds,
+    ptrace::PtraceState,
     signal::{
         sig_mask::SigMask, sig_num::SigNum, sig_queues::SigQueues, signals::Signal, SigEvents,
         SigEventsFilter, SigStack,
@@ -26,13 +30,11 @@ pub mod futex;
 mod name;
 mod posix_thread_ext;
 mod robust_list;
-mod timer;
 
 pub use builder::PosixThreadBuilder;
 pub use name::{ThreadName, MAX_THREAD_NAME_LEN};
 pub use posix_thread_ext::PosixThreadExt;
 pub use robust_list::RobustListHead;
-pub use timer::RealTimer;
 
 pub struct PosixThread {
     // Immutable part
@@ -52,9 +54,6 @@ pub struct PosixThread {
     /// Process credentials. At the kernel level, credentials are a per-thread attribute.
     credentials: Credentials,
 
-    /// The timer counts down in real (i.e., wall clock) time
-    real_timer: Mutex<RealTimer>,
-
     // Signal
     /// Blocked signals
     sig_mask: Mutex<SigMask>,
@@ -64,6 +63,14 @@ pub struct PosixThread {
     /// FIXME: This field may be removed. For glibc applications with RESTORER flag set, the sig_context is always equals with rsp.
     sig_context: Mutex<Option<Vaddr>>,
     sig_stack: Mutex<Option<SigStack>>,
+
+    /// The CPUs that the thread is allowed to run on.
+    ///
+    /// It is a spin lock since the scheduler reads it with the local IRQs disabled.
+    cpu_affinity: SpinLock<CpuSet>,
+
+    /// The ptrace state, which exists if the thread is traced.
+    ptrace: Mutex<Option<PtraceState>>,
 }
 
 impl PosixThread {
@@ -91,6 +98,16 @@ impl PosixThread {
         !self.sig_queues.lock().is_empty()
     }
 
+    /// Returns whether the signal is pending, no matter whether it is blocked.
+    pub(in crate::process) fn has_pending_signal_of(&self, signum: SigNum) -> bool {
+        self.sig_queues.lock().has_pending(signum)
+    }
+
+    /// Returns whether any signal in the mask is pending, no matter whether it is blocked.
+    pub fn has_pending_signal_in(&self, mask: &SigMask) -> bool {
+        self.sig_queues.lock().has_pending_in(mask)
+    }
+
     /// Returns whether the signal is blocked by the thread.
     pub(in crate::process) fn has_signal_blocked(&self, signal: &dyn Signal) -> bool {
         let mask = self.sig_mask.lock();
@@ -146,8 +163,23 @@ impl PosixThread {
         return_errno_with_message!(Errno::EPERM, "sending signal to the thread is not allowed.");
     }
 
-    pub fn real_timer(&self) -> &Mutex<RealTimer> {
-        &self.real_timer
+    /// Charges `delta` of CPU time, which makes the CPU time of the thread
+    /// `thread_cpu_time`, to the process. `is_user_time` tells whether the time
+    /// is spent in the user mode.
+    ///
+    /// This method is called in the interrupt context on timer ticks.
+    pub fn charge_cpu_time(
+        &self,
+        tid: Tid,
+        thread_cpu_time: Duration,
+        delta: Duration,
+        is_user_time: bool,
+    ) {
+        if let Some(process) = self.process.upgrade() {
+            process
+                .timers()
+                .charge_cpu_time(tid, thread_cpu_time, delta, is_user_time);
+        }
     }
 
     pub(in crate::process) fn enqueue_signal(&self, signal: Box<dyn Signal>) {
@@ -155,7 +187,9 @@ impl PosixThread {
     }
 
     pub fn dequeue_signal(&self, mask: &SigMask) -> Option<Box<dyn Signal>> {
-        self.sig_queues.lock().dequeue(mask)
+        let signal = self.sig_queues.lock().dequeue(mask)?;
+        signal.on_dequeue();
+        Some(signal)
     }
 
     pub fn register_sigqueue_observer(
@@ -178,6 +212,14 @@ impl PosixThread {
         &self.sig_stack
     }
 
+    pub fn cpu_affinity(&self) -> &SpinLock<CpuSet> {
+        &self.cpu_affinity
+    }
+
+    pub(in crate::process) fn ptrace(&self) -> &Mutex<Option<PtraceState>> {
+        &self.ptrace
+    }
+
     pub fn robust_list(&self) -> &Mutex<Option<RobustListHead>> {
         &self.robust_list
     }
@@ -242,8 +284,6 @@ impl PosixThread {
             thread_table::remove_thread(tid);
         }
 
-        self.real_timer.lock().clear();
-
         if self.is_main_thread() || self.is_last_thread() {
             // exit current process.
             debug!("self is main thread or last thread");
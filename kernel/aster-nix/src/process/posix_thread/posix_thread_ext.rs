@@ -6,7 +6,10 @@ use super::{builder::PosixThreadBuilder, name::ThreadName, PosixThread};
 use crate::{
     fs::fs_resolver::{FsPath, FsResolver, AT_FDCWD},
     prelude::*,
-    process::{process_vm::ProcessVm, program_loader::load_program_to_vm, Credentials, Process},
+    process::{
+        process_vm::ProcessVm, program_loader::load_program_to_vm, Credentials, PersonalityFlags,
+        Process,
+    },
     thread::{Thread, Tid},
 };
 pub trait PosixThreadExt {
@@ -40,8 +43,15 @@ impl PosixThreadExt for Thread {
             let fs_path = FsPath::new(AT_FDCWD, executable_path)?;
             fs_resolver.lookup(&fs_path)?
         };
-        let (_, elf_load_info) =
-            load_program_to_vm(process_vm, elf_file, argv, envp, fs_resolver, 1)?;
+        let (_, elf_load_info) = load_program_to_vm(
+            process_vm,
+            elf_file,
+            argv,
+            envp,
+            fs_resolver,
+            PersonalityFlags::empty(),
+            1,
+        )?;
 
         let vm_space = process_vm.root_vmar().vm_space().clone();
         let mut cpu_ctx = UserContext::default();
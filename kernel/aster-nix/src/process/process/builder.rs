@@ -33,6 +33,8 @@ pub struct ProcessBuilder<'a> {
     sig_dispositions: Option<Arc<Mutex<SigDispositions>>>,
     credentials: Option<Credentials>,
     nice: Option<Nice>,
+    oom_score_adj: Option<i16>,
+    personality: Option<u32>,
 }
 
 impl<'a> ProcessBuilder<'a> {
@@ -52,6 +54,8 @@ impl<'a> ProcessBuilder<'a> {
             sig_dispositions: None,
             credentials: None,
             nice: None,
+            oom_score_adj: None,
+            personality: None,
         }
     }
 
@@ -110,6 +114,16 @@ impl<'a> ProcessBuilder<'a> {
         self
     }
 
+    pub fn oom_score_adj(&mut self, oom_score_adj: i16) -> &mut Self {
+        self.oom_score_adj = Some(oom_score_adj);
+        self
+    }
+
+    pub fn personality(&mut self, personality: u32) -> &mut Self {
+        self.personality = Some(personality);
+        self
+    }
+
     fn check_build(&self) -> Result<()> {
         if self.main_thread_builder.is_some() {
             debug_assert!(self.parent.upgrade().is_some());
@@ -145,6 +159,8 @@ impl<'a> ProcessBuilder<'a> {
             sig_dispositions,
             credentials,
             nice,
+            oom_score_adj,
+            personality,
         } = self;
 
         let process_vm = process_vm.or_else(|| Some(ProcessVm::alloc())).unwrap();
@@ -171,6 +187,10 @@ impl<'a> ProcessBuilder<'a> {
 
         let nice = nice.or_else(|| Some(Nice::default())).unwrap();
 
+        let oom_score_adj = oom_score_adj.unwrap_or(0);
+
+        let personality = personality.unwrap_or(0);
+
         let process = {
             let threads = Vec::new();
             Arc::new(Process::new(
@@ -185,6 +205,8 @@ impl<'a> ProcessBuilder<'a> {
                 sig_dispositions,
                 resource_limits,
                 nice,
+                oom_score_adj,
+                personality,
             ))
         };
 
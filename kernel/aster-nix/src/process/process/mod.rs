@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::{AtomicI16, AtomicU32};
+
 use super::{
     posix_thread::PosixThreadExt,
     process_table,
@@ -10,14 +12,14 @@ use super::{
         Pauser,
     },
     status::ProcessStatus,
-    Credentials, TermStatus,
+    Credentials, ProcessTimers, TermStatus,
 };
 use crate::{
     device::tty::open_ntty_as_controlling_terminal,
     fs::{file_table::FileTable, fs_resolver::FsResolver, utils::FileCreationMask},
     prelude::*,
     sched::nice::Nice,
-    thread::{allocate_tid, Thread},
+    thread::{allocate_tid, Thread, Tid},
     vm::vmar::Vmar,
 };
 
@@ -64,6 +66,8 @@ pub struct Process {
     pub(super) parent: Mutex<Weak<Process>>,
     /// Children processes
     children: Mutex<BTreeMap<Pid, Arc<Process>>>,
+    /// Threads traced by the process
+    tracees: Mutex<BTreeMap<Tid, Arc<Thread>>>,
     /// Process group
     pub(super) process_group: Mutex<Weak<ProcessGroup>>,
     /// File table
@@ -78,6 +82,13 @@ pub struct Process {
     /// According to POSIX.1, the nice value is a per-process attribute,
     /// the threads in a process should share a nice value.
     nice: Atomic<Nice>,
+    /// The adjustment of the badness score for the OOM killer, which is in the range
+    /// of [-1000, 1000].
+    oom_score_adj: AtomicI16,
+    /// The personality, which is set by the `personality` system call
+    personality: AtomicU32,
+    /// The interval timers and POSIX timers
+    timers: ProcessTimers,
 
     // Signal
     /// Sig dispositions
@@ -98,6 +109,8 @@ impl Process {
         sig_dispositions: Arc<Mutex<SigDispositions>>,
         resource_limits: ResourceLimits,
         nice: Nice,
+        oom_score_adj: i16,
+        personality: u32,
     ) -> Self {
         let children_pauser = {
             // SIGCHID does not interrupt pauser. Child process will
@@ -115,6 +128,7 @@ impl Process {
             status: Mutex::new(ProcessStatus::Uninit),
             parent: Mutex::new(parent),
             children: Mutex::new(BTreeMap::new()),
+            tracees: Mutex::new(BTreeMap::new()),
             process_group: Mutex::new(Weak::new()),
             file_table,
             fs,
@@ -122,6 +136,9 @@ impl Process {
             sig_dispositions,
             resource_limits: Mutex::new(resource_limits),
             nice: Atomic::new(nice),
+            oom_score_adj: AtomicI16::new(oom_score_adj),
+            personality: AtomicU32::new(personality),
+            timers: ProcessTimers::new(),
         }
     }
 
@@ -218,6 +235,22 @@ impl Process {
         &self.nice
     }
 
+    /// Returns the adjustment of the badness score for the OOM killer.
+    pub fn oom_score_adj(&self) -> &AtomicI16 {
+        &self.oom_score_adj
+    }
+
+    /// Returns the personality, whose flags are [`PersonalityFlags`].
+    ///
+    /// [`PersonalityFlags`]: super::PersonalityFlags
+    pub fn personality(&self) -> &AtomicU32 {
+        &self.personality
+    }
+
+    pub fn timers(&self) -> &ProcessTimers {
+        &self.timers
+    }
+
     pub fn main_thread(&self) -> Option<Arc<Thread>> {
         self.threads
             .lock()
@@ -247,6 +280,10 @@ impl Process {
         &self.children_pauser
     }
 
+    pub(in crate::process) fn tracees(&self) -> &Mutex<BTreeMap<Tid, Arc<Thread>>> {
+        &self.tracees
+    }
+
     // *********** Process group & Session***********
 
     /// Returns the process group id of the process.
@@ -537,7 +574,7 @@ impl Process {
     /// chooses an arbitrary thread to which to deliver the signal.
     ///
     /// TODO: restrict these method with access control tool.
-    pub fn enqueue_signal(&self, signal: impl Signal + Clone + 'static) {
+    pub fn enqueue_signal(&self, signal: impl Signal + 'static) {
         if self.is_zombie() {
             return;
         }
@@ -619,6 +656,8 @@ mod test {
             Arc::new(Mutex::new(SigDispositions::default())),
             ResourceLimits::default(),
             Nice::default(),
+            0,
+            0,
         ))
     }
 
@@ -14,7 +14,7 @@ use crate::{
     },
 };
 
-/// The base address of user heap
+/// The base address of user heap if it is not randomized
 pub const USER_HEAP_BASE: Vaddr = 0x0000_0000_1000_0000;
 /// The max allowed size of user heap
 pub const USER_HEAP_SIZE_LIMIT: usize = PAGE_SIZE * 1000; // 4MB
@@ -22,7 +22,7 @@ pub const USER_HEAP_SIZE_LIMIT: usize = PAGE_SIZE * 1000; // 4MB
 #[derive(Debug)]
 pub struct Heap {
     /// The lowest address of the heap
-    base: Vaddr,
+    base: AtomicUsize,
     /// The heap size limit
     limit: usize,
     /// The current heap highest address
@@ -32,12 +32,21 @@ pub struct Heap {
 impl Heap {
     pub const fn new() -> Self {
         Heap {
-            base: USER_HEAP_BASE,
+            base: AtomicUsize::new(USER_HEAP_BASE),
             limit: USER_HEAP_SIZE_LIMIT,
             current_heap_end: AtomicUsize::new(USER_HEAP_BASE),
         }
     }
 
+    /// Moves the lowest address of the heap up by `padding` bytes from
+    /// [`USER_HEAP_BASE`].
+    ///
+    /// This method should be called before the heap vmo is mapped.
+    pub(super) fn set_base_padding(&self, padding: usize) {
+        debug_assert!(padding % PAGE_SIZE == 0);
+        self.base.store(USER_HEAP_BASE + padding, Ordering::Relaxed);
+    }
+
     /// Inits and maps the heap Vmo
     pub(super) fn alloc_and_map_vmo(&self, root_vmar: &Vmar<Full>) -> Result<()> {
         let heap_vmo = {
@@ -49,7 +58,7 @@ impl Heap {
             root_vmar
                 .new_map(heap_vmo, perms)
                 .unwrap()
-                .offset(self.base)
+                .offset(self.base())
                 .size(self.limit)
         };
         vmar_map_options.build()?;
@@ -64,7 +73,8 @@ impl Heap {
         match new_heap_end {
             None => Ok(self.current_heap_end.load(Ordering::Relaxed)),
             Some(new_heap_end) => {
-                if new_heap_end > self.base + self.limit {
+                let base = self.base();
+                if new_heap_end > base + self.limit {
                     return_errno_with_message!(Errno::ENOMEM, "heap size limit was met.");
                 }
                 let current_heap_end = self.current_heap_end.load(Ordering::Acquire);
@@ -72,8 +82,8 @@ impl Heap {
                     // FIXME: should we allow shrink current user heap?
                     return Ok(current_heap_end);
                 }
-                let new_size = (new_heap_end - self.base).align_up(PAGE_SIZE);
-                let heap_mapping = root_vmar.get_vm_mapping(USER_HEAP_BASE)?;
+                let new_size = (new_heap_end - base).align_up(PAGE_SIZE);
+                let heap_mapping = root_vmar.get_vm_mapping(base)?;
                 let heap_vmo = heap_mapping.vmo();
                 heap_vmo.resize(new_size)?;
                 self.current_heap_end.store(new_heap_end, Ordering::Release);
@@ -83,7 +93,11 @@ impl Heap {
     }
 
     pub(super) fn set_uninitialized(&self) {
-        self.current_heap_end.store(self.base, Ordering::Relaxed);
+        self.current_heap_end.store(self.base(), Ordering::Relaxed);
+    }
+
+    fn base(&self) -> Vaddr {
+        self.base.load(Ordering::Relaxed)
     }
 }
 
@@ -91,7 +105,7 @@ impl Clone for Heap {
     fn clone(&self) -> Self {
         let current_heap_end = self.current_heap_end.load(Ordering::Relaxed);
         Self {
-            base: self.base,
+            base: AtomicUsize::new(self.base()),
             limit: self.limit,
             current_heap_end: AtomicUsize::new(current_heap_end),
         }
@@ -89,11 +89,11 @@ pub const MAX_ENV_LEN: usize = 128;
  */
 
 /// The initial portion of the main stack of a process.
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct InitStack {
     /// The initial highest address.
     /// The stack grows down from this address
-    initial_top: Vaddr,
+    initial_top: AtomicUsize,
     /// The max allowed stack size
     max_size: usize,
     /// The current stack pointer.
@@ -105,20 +105,25 @@ pub struct InitStack {
 
 impl InitStack {
     pub(super) fn new() -> Self {
-        let nr_pages_padding = {
-            let mut random_nr_pages_padding: u8 = 0;
-            getrandom::getrandom(random_nr_pages_padding.as_bytes_mut()).unwrap();
-            random_nr_pages_padding as usize
-        };
-        let initial_top = MAX_USERSPACE_VADDR - PAGE_SIZE * nr_pages_padding;
+        let initial_top = MAX_USERSPACE_VADDR;
         let max_size = INIT_STACK_SIZE;
         Self {
-            initial_top,
+            initial_top: AtomicUsize::new(initial_top),
             max_size,
             pos: Arc::new(AtomicUsize::new(initial_top)),
         }
     }
 
+    /// Moves the initial highest address down by `padding` bytes from the
+    /// highest address of the user space.
+    ///
+    /// This method should be called before the vmo of the init stack is mapped.
+    pub(super) fn set_top_padding(&self, padding: usize) {
+        debug_assert!(padding % PAGE_SIZE == 0);
+        self.initial_top
+            .store(MAX_USERSPACE_VADDR - padding, Ordering::Relaxed);
+    }
+
     /// Init and map the vmo for init stack
     pub(super) fn alloc_and_map_vmo(&self, root_vmar: &Vmar<Full>) -> Result<()> {
         let vmo = {
@@ -128,7 +133,7 @@ impl InitStack {
 
         let vmar_map_options = {
             let perms = VmPerms::READ | VmPerms::WRITE;
-            let map_addr = self.initial_top - self.max_size;
+            let map_addr = self.initial_top() - self.max_size;
             debug_assert!(map_addr % PAGE_SIZE == 0);
             root_vmar.new_map(vmo, perms)?.offset(map_addr)
         };
@@ -177,11 +182,15 @@ impl InitStack {
     }
 
     fn is_initialized(&self) -> bool {
-        self.pos() != self.initial_top
+        self.pos() != self.initial_top()
     }
 
     fn set_uninitialized(&self) {
-        self.pos.store(self.initial_top, Ordering::Relaxed);
+        self.pos.store(self.initial_top(), Ordering::Relaxed);
+    }
+
+    fn initial_top(&self) -> Vaddr {
+        self.initial_top.load(Ordering::Relaxed)
     }
 
     fn pos(&self) -> Vaddr {
@@ -189,6 +198,16 @@ impl InitStack {
     }
 }
 
+impl Clone for InitStack {
+    fn clone(&self) -> Self {
+        Self {
+            initial_top: AtomicUsize::new(self.initial_top()),
+            max_size: self.max_size,
+            pos: Arc::new(AtomicUsize::new(self.pos())),
+        }
+    }
+}
+
 /// A writer to initialize the content of an `InitStack`.
 pub struct InitStackWriter<'a> {
     pos: Arc<AtomicUsize>,
@@ -12,6 +12,7 @@
 mod heap;
 mod init_stack;
 
+use aster_frame::vm::MAX_USERSPACE_VADDR;
 use aster_rights::Full;
 pub use heap::Heap;
 
@@ -28,7 +29,7 @@ use crate::{prelude::*, vm::vmar::Vmar};
 /*
  * The user's virtual memory space layout looks like below.
  * TODO: The layout of the userheap does not match the current implementation,
- * And currently the initial program break is a fixed value.
+ * And currently the initial program break is a fixed value plus a random padding.
  *
  *  (high address)
  *  +---------------------+ <------+ The top of Vmar, which is the highest address usable
@@ -61,6 +62,20 @@ use crate::{prelude::*, vm::vmar::Vmar};
  *  (low address)
  */
 
+/// The range of the random padding below the top of the user stack, which is
+/// the same as Linux's `STACK_RND_MASK` (16 GiB) for 64-bit processes.
+const STACK_RANDOM_RANGE: usize = 0x40_0000 * PAGE_SIZE;
+/// The range of the random padding above the base of the user heap, which is
+/// the same as Linux's `brk` randomization (32 MiB).
+const HEAP_RANDOM_RANGE: usize = 0x200_0000;
+/// The mmap base if the address space layout is randomized, which is the same
+/// as Linux's `TASK_UNMAPPED_BASE` (a third of the user space).
+const RANDOM_MMAP_BASE: Vaddr = (MAX_USERSPACE_VADDR / 3) & !(PAGE_SIZE - 1);
+/// The range of the random padding above the mmap base and the load address of
+/// position-independent executables, which is the same as Linux's
+/// `mmap_rnd_bits` (28 bits of pages) for 64-bit processes.
+pub(super) const MMAP_RANDOM_RANGE: usize = (1 << 28) * PAGE_SIZE;
+
 // The process user space virtual memory
 pub struct ProcessVm {
     root_vmar: Vmar<Full>,
@@ -129,9 +144,30 @@ impl ProcessVm {
     }
 
     /// Clears existing mappings and then maps stack and heap vmo.
-    pub(super) fn clear_and_map(&self) {
+    ///
+    /// If `randomize_layout` is true, the stack top, the heap base and the mmap
+    /// base are randomized.
+    pub(super) fn clear_and_map(&self, randomize_layout: bool) {
         self.root_vmar.clear().unwrap();
+        if randomize_layout {
+            self.init_stack
+                .set_top_padding(random_page_offset(STACK_RANDOM_RANGE));
+            self.heap
+                .set_base_padding(random_page_offset(HEAP_RANDOM_RANGE));
+            self.root_vmar
+                .set_mmap_base(RANDOM_MMAP_BASE + random_page_offset(MMAP_RANDOM_RANGE));
+        } else {
+            self.init_stack.set_top_padding(0);
+            self.heap.set_base_padding(0);
+        }
         self.init_stack.alloc_and_map_vmo(&self.root_vmar).unwrap();
         self.heap.alloc_and_map_vmo(&self.root_vmar).unwrap();
     }
 }
+
+/// Returns a random page-aligned offset that is less than `range`.
+pub(super) fn random_page_offset(range: usize) -> usize {
+    let mut random: usize = 0;
+    getrandom::getrandom(random.as_bytes_mut()).unwrap();
+    random % (range / PAGE_SIZE) * PAGE_SIZE
+}
@@ -20,7 +20,7 @@ use crate::{
     prelude::*,
     process::{
         do_exit_group,
-        process_vm::{AuxKey, AuxVec, ProcessVm},
+        process_vm::{random_page_offset, AuxKey, AuxVec, ProcessVm, MMAP_RANDOM_RANGE},
         TermStatus,
     },
     vdso::vdso_vmo,
@@ -31,10 +31,17 @@ use crate::{
     },
 };
 
+/// The base address of position-independent executables loaded by the dynamic
+/// linker, which is the same as Linux's `ELF_ET_DYN_BASE` on x86-64.
+const ELF_ET_DYN_BASE: Vaddr = 0x5555_5555_4000;
+
 /// Loads elf to the process vm.   
 ///
 /// This function will map elf segments and
 /// initialize process init stack.
+///
+/// If `randomize_layout` is true, position-independent executables are loaded
+/// at a random address.
 pub fn load_elf_to_vm(
     process_vm: &ProcessVm,
     file_header: &[u8],
@@ -42,6 +49,7 @@ pub fn load_elf_to_vm(
     fs_resolver: &FsResolver,
     argv: Vec<CString>,
     envp: Vec<CString>,
+    randomize_layout: bool,
 ) -> Result<ElfLoadInfo> {
     let parsed_elf = Elf::parse_elf(file_header)?;
 
@@ -55,7 +63,7 @@ pub fn load_elf_to_vm(
         None
     };
 
-    match init_and_map_vmos(process_vm, ldso, &parsed_elf, &elf_file) {
+    match init_and_map_vmos(process_vm, ldso, &parsed_elf, &elf_file, randomize_layout) {
         Ok((entry_point, mut aux_vec)) => {
             // Map and set vdso entry.
             // Since vdso does not require being mapped to any specific address,
@@ -112,7 +120,7 @@ fn lookup_and_parse_ldso(
 }
 
 fn load_ldso(root_vmar: &Vmar<Full>, ldso_file: &Dentry, ldso_elf: &Elf) -> Result<LdsoLoadInfo> {
-    let map_addr = map_segment_vmos(ldso_elf, root_vmar, ldso_file)?;
+    let map_addr = map_segment_vmos(ldso_elf, root_vmar, ldso_file, None)?;
     Ok(LdsoLoadInfo::new(
         ldso_elf.entry_point() + map_addr,
         map_addr,
@@ -124,6 +132,7 @@ fn init_and_map_vmos(
     ldso: Option<(Arc<Dentry>, Elf)>,
     parsed_elf: &Elf,
     elf_file: &Dentry,
+    randomize_layout: bool,
 ) -> Result<(Vaddr, AuxVec)> {
     let root_vmar = process_vm.root_vmar();
 
@@ -134,7 +143,20 @@ fn init_and_map_vmos(
         None
     };
 
-    let elf_map_addr = map_segment_vmos(parsed_elf, root_vmar, elf_file)?;
+    // Like Linux, a position-independent executable with a dynamic linker is
+    // loaded at `ELF_ET_DYN_BASE`, while the dynamic linker itself (and a static
+    // position-independent executable) is loaded in the mmap region.
+    let elf_base_addr = if ldso_load_info.is_some() {
+        let random_offset = if randomize_layout {
+            random_page_offset(MMAP_RANDOM_RANGE)
+        } else {
+            0
+        };
+        Some(ELF_ET_DYN_BASE + random_offset)
+    } else {
+        None
+    };
+    let elf_map_addr = map_segment_vmos(parsed_elf, root_vmar, elf_file, elf_base_addr)?;
 
     let aux_vec = {
         let ldso_base = ldso_load_info
@@ -202,11 +224,18 @@ impl ElfLoadInfo {
 }
 
 /// init vmo for each segment and then map segment to root vmar
-pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) -> Result<Vaddr> {
+///
+/// A shared object is mapped at `preferred_base` if it is given and free.
+pub fn map_segment_vmos(
+    elf: &Elf,
+    root_vmar: &Vmar<Full>,
+    elf_file: &Dentry,
+    preferred_base: Option<Vaddr>,
+) -> Result<Vaddr> {
     // all segments of the shared object must be mapped to a continuous vm range
     // to ensure the relative offset of each segment not changed.
     let base_addr = if elf.is_shared_object() {
-        base_map_addr(elf, root_vmar)?
+        base_map_addr(elf, root_vmar, preferred_base)?
     } else {
         0
     };
@@ -229,7 +258,11 @@ pub fn map_segment_vmos(elf: &Elf, root_vmar: &Vmar<Full>, elf_file: &Dentry) ->
     Ok(base_addr)
 }
 
-fn base_map_addr(elf: &Elf, root_vmar: &Vmar<Full>) -> Result<Vaddr> {
+fn base_map_addr(
+    elf: &Elf,
+    root_vmar: &Vmar<Full>,
+    preferred_base: Option<Vaddr>,
+) -> Result<Vaddr> {
     let elf_size = elf
         .program_headers
         .iter()
@@ -250,6 +283,15 @@ fn base_map_addr(elf: &Elf, root_vmar: &Vmar<Full>) -> Result<Vaddr> {
         ))?;
     let map_size = elf_size.align_up(PAGE_SIZE);
     let vmo = VmoOptions::<Rights>::new(0).alloc()?;
+    if let Some(preferred_base) = preferred_base {
+        let vmar_map_options = root_vmar
+            .new_map(vmo.dup()?, VmPerms::empty())?
+            .size(map_size)
+            .offset(preferred_base);
+        if let Ok(base_addr) = vmar_map_options.build() {
+            return Ok(base_addr);
+        }
+    }
     let vmar_map_options = root_vmar.new_map(vmo, VmPerms::empty())?.size(map_size);
     vmar_map_options.build()
 }
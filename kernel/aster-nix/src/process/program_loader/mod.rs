@@ -7,11 +7,11 @@ use self::{
     elf::{load_elf_to_vm, ElfLoadInfo},
     shebang::parse_shebang_line,
 };
-use super::process_vm::ProcessVm;
+use super::{process_vm::ProcessVm, PersonalityFlags};
 use crate::{
     fs::{
         fs_resolver::{FsPath, FsResolver, AT_FDCWD},
-        utils::Dentry,
+        utils::{Dentry, MountFlags},
     },
     prelude::*,
 };
@@ -23,12 +23,15 @@ use crate::{
 /// then it will trigger recursion. We will try to setup root vmar for the interpreter.
 /// I guess for most cases, setting the recursion_limit as 1 should be enough.
 /// because the interpreter is usually an elf binary(e.g., /bin/bash)
+/// About personality: the address space layout is randomized unless the personality
+/// contains `ADDR_NO_RANDOMIZE` or the kernel command line contains `norandmaps`.
 pub fn load_program_to_vm(
     process_vm: &ProcessVm,
     elf_file: Arc<Dentry>,
     argv: Vec<CString>,
     envp: Vec<CString>,
     fs_resolver: &FsResolver,
+    personality: PersonalityFlags,
     recursion_limit: usize,
 ) -> Result<(String, ElfLoadInfo)> {
     let abs_path = elf_file.abs_path();
@@ -56,14 +59,24 @@ pub fn load_program_to_vm(
             new_argv,
             envp,
             fs_resolver,
+            personality,
             recursion_limit - 1,
         );
     }
 
-    process_vm.clear_and_map();
+    let randomize_layout = !personality.contains(PersonalityFlags::ADDR_NO_RANDOMIZE)
+        && !aster_frame::boot::kernel_cmdline().has_kernel_flag("norandmaps");
+    process_vm.clear_and_map(randomize_layout);
 
-    let elf_load_info =
-        load_elf_to_vm(process_vm, &*file_header, elf_file, fs_resolver, argv, envp)?;
+    let elf_load_info = load_elf_to_vm(
+        process_vm,
+        &*file_header,
+        elf_file,
+        fs_resolver,
+        argv,
+        envp,
+        randomize_layout,
+    )?;
 
     Ok((abs_path, elf_load_info))
 }
@@ -81,5 +94,9 @@ pub fn check_executable_file(dentry: &Arc<Dentry>) -> Result<()> {
         return_errno_with_message!(Errno::EACCES, "the dentry is not executable");
     }
 
+    if dentry.mount_node().flags().contains(MountFlags::NOEXEC) {
+        return_errno_with_message!(Errno::EACCES, "the dentry is on a noexec mount");
+    }
+
     Ok(())
 }
@@ -0,0 +1,900 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Process tracing.
+//!
+//! A tracer is a process that observes and controls the execution of some threads,
+//! which are called its tracees. A tracee stops in a ptrace-stop when it enters or
+//! exits a syscall, is about to receive a signal, or hits an event that its tracer
+//! is interested in. The tracer is notified as if the tracee were a stopped child,
+//! and it can then inspect and modify the tracee before resuming it.
+//!
+//! The tracee waits in a ptrace-stop by the same means as a job-control stop, i.e.,
+//! its thread status is set to stopped until the tracer sets it back to running.
+
+use aster_frame::cpu::UserContext;
+
+use super::{
+    credentials,
+    posix_thread::{PosixThread, PosixThreadExt},
+    process_filter::ProcessFilter,
+    signal::{
+        c_types::siginfo_t,
+        constants::{SIGCHLD, SIGKILL, SIGSTOP, SIGTRAP},
+        sig_num::SigNum,
+        signals::{kernel::KernelSignal, Signal},
+    },
+    CloneFlags, Process, TermStatus, WaitOptions,
+};
+use crate::{
+    prelude::*,
+    thread::{Thread, Tid},
+};
+
+bitflags! {
+    /// The options of a tracee, which are set by `PTRACE_SETOPTIONS` or `PTRACE_SEIZE`.
+    pub struct PtraceOptions: u32 {
+        const PTRACE_O_TRACESYSGOOD = 1;
+        const PTRACE_O_TRACEFORK = 1 << PtraceEvent::Fork as u32;
+        const PTRACE_O_TRACEVFORK = 1 << PtraceEvent::Vfork as u32;
+        const PTRACE_O_TRACECLONE = 1 << PtraceEvent::Clone as u32;
+        const PTRACE_O_TRACEEXEC = 1 << PtraceEvent::Exec as u32;
+        const PTRACE_O_TRACEVFORKDONE = 1 << PtraceEvent::VforkDone as u32;
+        const PTRACE_O_TRACEEXIT = 1 << PtraceEvent::Exit as u32;
+        const PTRACE_O_TRACESECCOMP = 1 << PtraceEvent::Seccomp as u32;
+        const PTRACE_O_EXITKILL = 1 << 20;
+        const PTRACE_O_SUSPEND_SECCOMP = 1 << 21;
+    }
+}
+
+/// The events that a tracee reports with a `PTRACE_EVENT_*` stop.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceEvent {
+    Fork = 1,
+    Vfork = 2,
+    Clone = 3,
+    Exec = 4,
+    VforkDone = 5,
+    Exit = 6,
+    Seccomp = 7,
+    /// The stop of a tracee that is attached by `PTRACE_SEIZE`, which is caused by
+    /// `PTRACE_INTERRUPT` or the auto-attaching of a new child.
+    Stop = 128,
+}
+
+impl PtraceEvent {
+    /// Returns the option that enables the event, or `None` if the event is always enabled.
+    fn option(&self) -> Option<PtraceOptions> {
+        match self {
+            Self::Stop => None,
+            event => Some(PtraceOptions::from_bits_truncate(1 << *event as u32)),
+        }
+    }
+}
+
+/// How a tracee runs after it is resumed by its tracer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PtraceResumeMode {
+    /// Runs until the next signal or event, i.e., `PTRACE_CONT`.
+    Continue,
+    /// Also stops at the entry and the exit of the next syscall, i.e., `PTRACE_SYSCALL`.
+    Syscall,
+    /// Also stops after executing one instruction, i.e., `PTRACE_SINGLESTEP`.
+    SingleStep,
+}
+
+/// The reason of a ptrace-stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PtraceStop {
+    /// A signal-delivery-stop.
+    Signal(SigNum),
+    /// A syscall-enter-stop or a syscall-exit-stop.
+    Syscall,
+    /// A `PTRACE_EVENT_*` stop.
+    Event(PtraceEvent),
+}
+
+/// The ptrace state of a traced thread.
+pub(super) struct PtraceState {
+    tracer: Weak<Process>,
+    options: PtraceOptions,
+    /// Whether the thread is attached by `PTRACE_SEIZE`.
+    is_seized: bool,
+    /// Whether the thread is detached while it is in a ptrace-stop.
+    ///
+    /// The state is dropped by the thread itself when it leaves the stop.
+    is_detached: bool,
+    resume_mode: PtraceResumeMode,
+    /// The signal to be delivered when the thread is resumed.
+    resume_signal: Option<SigNum>,
+    /// The current ptrace-stop, which is cleared when the tracer resumes the thread.
+    stop: Option<PtraceStop>,
+    /// Whether the current ptrace-stop has been reported to the tracer by `wait`.
+    is_stop_reported: bool,
+    /// The signal information of the current ptrace-stop.
+    siginfo: Option<siginfo_t>,
+    /// The user context of the thread in the current ptrace-stop, which is
+    /// modified by the tracer and restored when the thread is resumed.
+    context: Option<UserContext>,
+    /// The number of the syscall that the thread is running, or `usize::MAX`.
+    orig_rax: usize,
+    /// The event that the thread should stop for at the next chance.
+    pending_event: Option<PtraceEvent>,
+    /// The message of the last event, which is read by `PTRACE_GETEVENTMSG`.
+    event_msg: u64,
+}
+
+impl PtraceState {
+    fn new(tracer: &Arc<Process>, options: PtraceOptions, is_seized: bool) -> Self {
+        Self {
+            tracer: Arc::downgrade(tracer),
+            options,
+            is_seized,
+            is_detached: false,
+            resume_mode: PtraceResumeMode::Continue,
+            resume_signal: None,
+            stop: None,
+            is_stop_reported: false,
+            siginfo: None,
+            context: None,
+            orig_rax: usize::MAX,
+            pending_event: None,
+            event_msg: 0,
+        }
+    }
+
+    fn tracer(&self) -> Option<Arc<Process>> {
+        self.tracer.upgrade()
+    }
+
+    fn is_traced_by(&self, process: &Arc<Process>) -> bool {
+        !self.is_detached && Weak::ptr_eq(&self.tracer, &Arc::downgrade(process))
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.is_some()
+    }
+
+    /// Returns the status of the current ptrace-stop, which is encoded as specified
+    /// in the wait(2) man page.
+    fn stop_status(&self) -> Option<u32> {
+        Some(self.stop_status_of(self.stop?))
+    }
+
+    fn stop_status_of(&self, stop: PtraceStop) -> u32 {
+        let stop_sig = match stop {
+            PtraceStop::Signal(sig_num) => sig_num.as_u8() as u32,
+            PtraceStop::Syscall if self.options.contains(PtraceOptions::PTRACE_O_TRACESYSGOOD) => {
+                SIGTRAP.as_u8() as u32 | 0x80
+            }
+            PtraceStop::Syscall => SIGTRAP.as_u8() as u32,
+            PtraceStop::Event(event) => SIGTRAP.as_u8() as u32 | (event as u32) << 8,
+        };
+        stop_sig << 8 | 0x7f
+    }
+
+    /// Resumes the thread, which must be in a ptrace-stop.
+    fn resume(&mut self, mode: PtraceResumeMode, signal: Option<SigNum>, thread: &Thread) {
+        debug_assert!(self.is_stopped());
+        self.stop = None;
+        self.resume_mode = mode;
+        self.resume_signal = signal;
+        let mut status = thread.status().lock();
+        if status.is_stopped() {
+            status.set_running();
+        }
+    }
+}
+
+/// The general-purpose registers of a tracee, which are read and written by the tracer.
+///
+/// The layout is the same as `struct user_regs_struct` of Linux.
+#[derive(Debug, Default, Clone, Copy, Pod)]
+#[repr(C)]
+pub struct PtraceRegs {
+    pub r15: usize,
+    pub r14: usize,
+    pub r13: usize,
+    pub r12: usize,
+    pub rbp: usize,
+    pub rbx: usize,
+    pub r11: usize,
+    pub r10: usize,
+    pub r9: usize,
+    pub r8: usize,
+    pub rax: usize,
+    pub rcx: usize,
+    pub rdx: usize,
+    pub rsi: usize,
+    pub rdi: usize,
+    pub orig_rax: usize,
+    pub rip: usize,
+    pub cs: usize,
+    pub rflags: usize,
+    pub rsp: usize,
+    pub ss: usize,
+    pub fs_base: usize,
+    pub gs_base: usize,
+    pub ds: usize,
+    pub es: usize,
+    pub fs: usize,
+    pub gs: usize,
+}
+
+/// The user code segment selector that is seen by the tracer.
+const USER_CS: usize = 0x33;
+/// The user stack segment selector that is seen by the tracer.
+const USER_SS: usize = 0x2b;
+
+/// The flags in RFLAGS that the tracer is allowed to change.
+const USER_RFLAGS_MASK: usize = 0x54dd5;
+/// The trap flag in RFLAGS, which enables single-stepping.
+const RFLAGS_TF: usize = 1 << 8;
+
+impl PtraceRegs {
+    fn from_context(context: &UserContext, orig_rax: usize) -> Self {
+        Self {
+            r15: context.r15(),
+            r14: context.r14(),
+            r13: context.r13(),
+            r12: context.r12(),
+            rbp: context.rbp(),
+            rbx: context.rbx(),
+            r11: context.r11(),
+            r10: context.r10(),
+            r9: context.r9(),
+            r8: context.r8(),
+            rax: context.rax(),
+            rcx: context.rcx(),
+            rdx: context.rdx(),
+            rsi: context.rsi(),
+            rdi: context.rdi(),
+            orig_rax,
+            rip: context.rip(),
+            cs: USER_CS,
+            rflags: context.rflags(),
+            rsp: context.rsp(),
+            ss: USER_SS,
+            fs_base: context.fsbase(),
+            gs_base: context.gsbase(),
+            ds: 0,
+            es: 0,
+            fs: 0,
+            gs: 0,
+        }
+    }
+
+    /// Writes the registers to the context. The segment selectors cannot be changed.
+    fn write_to_context(&self, context: &mut UserContext) {
+        context.set_r15(self.r15);
+        context.set_r14(self.r14);
+        context.set_r13(self.r13);
+        context.set_r12(self.r12);
+        context.set_rbp(self.rbp);
+        context.set_rbx(self.rbx);
+        context.set_r11(self.r11);
+        context.set_r10(self.r10);
+        context.set_r9(self.r9);
+        context.set_r8(self.r8);
+        context.set_rax(self.rax);
+        context.set_rcx(self.rcx);
+        context.set_rdx(self.rdx);
+        context.set_rsi(self.rsi);
+        context.set_rdi(self.rdi);
+        context.set_rip(self.rip);
+        let rflags = (context.rflags() & !USER_RFLAGS_MASK) | (self.rflags & USER_RFLAGS_MASK);
+        context.set_rflags(rflags);
+        context.set_rsp(self.rsp);
+        context.set_fsbase(self.fs_base);
+        context.set_gsbase(self.gs_base);
+    }
+}
+
+// ****************** Tracer ******************
+
+/// Makes the current thread traced by the parent process, i.e., `PTRACE_TRACEME`.
+pub fn ptrace_traceme() -> Result<()> {
+    let current = current!();
+    let Some(parent) = current.parent() else {
+        return_errno_with_message!(Errno::EPERM, "the process has no parent to trace it");
+    };
+    attach(&parent, current_thread!(), PtraceOptions::empty(), false)
+}
+
+/// Attaches the current process to the thread as its tracer, i.e., `PTRACE_ATTACH`
+/// or `PTRACE_SEIZE`.
+///
+/// A thread attached by `PTRACE_ATTACH` is sent a `SIGSTOP`, while a thread
+/// attached by `PTRACE_SEIZE` keeps running.
+pub fn ptrace_attach(tracee: Arc<Thread>, options: PtraceOptions, is_seized: bool) -> Result<()> {
+    let current = current!();
+    let Some(posix_thread) = tracee.as_posix_thread() else {
+        return_errno_with_message!(Errno::EPERM, "kernel threads cannot be traced");
+    };
+    if Arc::ptr_eq(&posix_thread.process(), &current) {
+        return_errno_with_message!(Errno::EPERM, "a process cannot trace itself");
+    }
+    check_attach_perm(posix_thread)?;
+
+    attach(&current, tracee.clone(), options, is_seized)?;
+    if !is_seized {
+        posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGSTOP)));
+    }
+    Ok(())
+}
+
+/// Checks whether the current thread is allowed to trace the thread.
+///
+/// The tracer must either be privileged, or have the same user IDs as the tracee.
+fn check_attach_perm(tracee: &PosixThread) -> Result<()> {
+    let credentials = credentials();
+    if credentials.euid().is_root() {
+        return Ok(());
+    }
+
+    let tracee_credentials = tracee.credentials();
+    let uid = credentials.ruid();
+    if uid == tracee_credentials.ruid()
+        && uid == tracee_credentials.euid()
+        && uid == tracee_credentials.suid()
+    {
+        return Ok(());
+    }
+
+    return_errno_with_message!(Errno::EPERM, "tracing the thread is not allowed");
+}
+
+fn attach(
+    tracer: &Arc<Process>,
+    tracee: Arc<Thread>,
+    options: PtraceOptions,
+    is_seized: bool,
+) -> Result<()> {
+    // Lock order: tracees of tracer -> ptrace state of tracee
+    let mut tracees = tracer.tracees().lock();
+    let posix_thread = tracee.as_posix_thread().unwrap();
+    let mut ptrace = posix_thread.ptrace().lock();
+    if ptrace.is_some() {
+        return_errno_with_message!(Errno::EPERM, "the thread is already traced");
+    }
+    *ptrace = Some(PtraceState::new(tracer, options, is_seized));
+    tracees.insert(tracee.tid(), tracee.clone());
+    Ok(())
+}
+
+/// Detaches the thread from the current process, i.e., `PTRACE_DETACH`.
+///
+/// The thread must be in a ptrace-stop. It is resumed with the signal.
+pub fn ptrace_detach(tracee: &Thread, signal: Option<SigNum>) -> Result<()> {
+    let current = current!();
+    let mut tracees = current.tracees().lock();
+    with_stopped_tracee(&current, tracee, |state| {
+        state.is_detached = true;
+        state.resume(PtraceResumeMode::Continue, signal, tracee);
+    })?;
+    tracees.remove(&tracee.tid());
+    Ok(())
+}
+
+/// Resumes the thread, which must be in a ptrace-stop, i.e., `PTRACE_CONT`,
+/// `PTRACE_SYSCALL` or `PTRACE_SINGLESTEP`.
+pub fn ptrace_resume(
+    tracee: &Thread,
+    mode: PtraceResumeMode,
+    signal: Option<SigNum>,
+) -> Result<()> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| {
+        state.resume(mode, signal, tracee);
+    })
+}
+
+/// Kills the thread, i.e., `PTRACE_KILL`.
+pub fn ptrace_kill(tracee: &Thread) -> Result<()> {
+    let current = current!();
+    with_tracee(&current, tracee, |state| {
+        if state.is_stopped() {
+            state.resume(PtraceResumeMode::Continue, None, tracee);
+        }
+    })?;
+    let posix_thread = tracee.as_posix_thread().unwrap();
+    posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGKILL)));
+    Ok(())
+}
+
+/// Asks the thread, which must be attached by `PTRACE_SEIZE`, to stop, i.e., `PTRACE_INTERRUPT`.
+///
+/// The thread stops with `PTRACE_EVENT_STOP` the next time it returns to the user space.
+pub fn ptrace_interrupt(tracee: &Thread) -> Result<()> {
+    let current = current!();
+    with_tracee(&current, tracee, |state| {
+        if !state.is_seized {
+            return_errno_with_message!(Errno::EIO, "the thread is not attached by PTRACE_SEIZE");
+        }
+        if !state.is_stopped() {
+            state.pending_event = Some(PtraceEvent::Stop);
+        }
+        Ok(())
+    })?
+}
+
+/// Sets the options of the thread, which must be in a ptrace-stop.
+pub fn ptrace_set_options(tracee: &Thread, options: PtraceOptions) -> Result<()> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| state.options = options)
+}
+
+/// Returns the message of the last event of the thread, which must be in a ptrace-stop.
+pub fn ptrace_event_msg(tracee: &Thread) -> Result<u64> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| state.event_msg)
+}
+
+/// Returns the signal information of the current ptrace-stop of the thread.
+pub fn ptrace_siginfo(tracee: &Thread) -> Result<siginfo_t> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| state.siginfo)?
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the stop has no signal information"))
+}
+
+/// Returns the registers of the thread, which must be in a ptrace-stop.
+pub fn ptrace_regs(tracee: &Thread) -> Result<PtraceRegs> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| {
+        let context = state.context.as_ref().unwrap();
+        PtraceRegs::from_context(context, state.orig_rax)
+    })
+}
+
+/// Sets the registers of the thread, which must be in a ptrace-stop.
+pub fn ptrace_set_regs(tracee: &Thread, regs: &PtraceRegs) -> Result<()> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |state| {
+        let context = state.context.as_mut().unwrap();
+        regs.write_to_context(context);
+        state.orig_rax = regs.orig_rax;
+    })
+}
+
+/// Returns the process of the thread, which must be in a ptrace-stop, to access its memory.
+pub fn ptrace_stopped_process(tracee: &Thread) -> Result<Arc<Process>> {
+    let current = current!();
+    with_stopped_tracee(&current, tracee, |_| ())?;
+    Ok(tracee.as_posix_thread().unwrap().process())
+}
+
+fn with_tracee<R>(
+    tracer: &Arc<Process>,
+    tracee: &Thread,
+    f: impl FnOnce(&mut PtraceState) -> R,
+) -> Result<R> {
+    let Some(posix_thread) = tracee.as_posix_thread() else {
+        return_errno_with_message!(Errno::ESRCH, "the thread is not traced");
+    };
+    let mut ptrace = posix_thread.ptrace().lock();
+    match ptrace.as_mut() {
+        Some(state) if state.is_traced_by(tracer) => Ok(f(state)),
+        _ => return_errno_with_message!(Errno::ESRCH, "the thread is not traced by the process"),
+    }
+}
+
+fn with_stopped_tracee<R>(
+    tracer: &Arc<Process>,
+    tracee: &Thread,
+    f: impl FnOnce(&mut PtraceState) -> R,
+) -> Result<R> {
+    with_tracee(tracer, tracee, |state| {
+        if !state.is_stopped() {
+            return_errno_with_message!(Errno::ESRCH, "the thread is not in a ptrace-stop");
+        }
+        Ok(f(state))
+    })?
+}
+
+/// Returns whether the tracer has any tracee that matches the filter.
+pub(super) fn has_tracees(tracer: &Process, filter: ProcessFilter) -> bool {
+    tracer
+        .tracees()
+        .lock()
+        .values()
+        .any(|tracee| matches_filter(tracee, filter))
+}
+
+/// Reports a tracee that matches the filter and is in a ptrace-stop that is not
+/// reported yet, or has exited while it is not a child of the tracer.
+///
+/// Returns the thread ID and the status encoded as specified in the wait(2) man page.
+pub(super) fn wait_tracee(
+    tracer: &Process,
+    filter: ProcessFilter,
+    wait_options: WaitOptions,
+) -> Option<(Tid, u32)> {
+    let mut tracees = tracer.tracees().lock();
+    let mut exited_tracees = Vec::new();
+    let mut result = None;
+    for (&tid, tracee) in tracees.iter() {
+        if !matches_filter(tracee, filter) {
+            continue;
+        }
+
+        let posix_thread = tracee.as_posix_thread().unwrap();
+        if tracee.is_exited() {
+            exited_tracees.push(tid);
+            // The exit of a child is reported in the same way as an untraced child.
+            let process = posix_thread.process();
+            if posix_thread.is_main_thread() && !tracer.has_child(&process.pid()) {
+                if let Some(exit_code) = process.exit_code() {
+                    result = Some((tid, exit_code));
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let mut ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_mut() else {
+            continue;
+        };
+        if state.is_stop_reported {
+            continue;
+        }
+        if let Some(status) = state.stop_status() {
+            if !wait_options.contains(WaitOptions::WNOWAIT) {
+                state.is_stop_reported = true;
+            }
+            result = Some((tid, status));
+            break;
+        }
+    }
+
+    if !wait_options.contains(WaitOptions::WNOWAIT) {
+        for tid in exited_tracees {
+            tracees.remove(&tid);
+        }
+    }
+    result
+}
+
+fn matches_filter(tracee: &Thread, filter: ProcessFilter) -> bool {
+    match filter {
+        ProcessFilter::Any => true,
+        ProcessFilter::WithPid(pid) => tracee.tid() == pid,
+        ProcessFilter::WithPgid(pgid) => {
+            let posix_thread = tracee.as_posix_thread().unwrap();
+            posix_thread.process().pgid() == pgid
+        }
+    }
+}
+
+/// Detaches all the tracees of the exiting tracer.
+///
+/// The tracees are killed instead if they have the `PTRACE_O_EXITKILL` option.
+pub(super) fn detach_all_tracees(tracer: &Process) {
+    let tracees = core::mem::take(&mut *tracer.tracees().lock());
+    for tracee in tracees.values() {
+        let posix_thread = tracee.as_posix_thread().unwrap();
+        let mut ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_mut() else {
+            continue;
+        };
+        if state.options.contains(PtraceOptions::PTRACE_O_EXITKILL) {
+            posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGKILL)));
+        }
+        if state.is_stopped() {
+            state.is_detached = true;
+            state.resume(PtraceResumeMode::Continue, None, tracee);
+        } else {
+            *ptrace = None;
+        }
+    }
+}
+
+/// Notifies the tracer of the thread, if any, that the thread has exited.
+pub(super) fn notify_tracer_of_exit(posix_thread: &PosixThread) {
+    let tracer = {
+        let ptrace = posix_thread.ptrace().lock();
+        ptrace.as_ref().and_then(|state| state.tracer())
+    };
+    if let Some(tracer) = tracer {
+        notify_tracer(&tracer);
+    }
+}
+
+fn notify_tracer(tracer: &Process) {
+    tracer.enqueue_signal(KernelSignal::new(SIGCHLD));
+    tracer.children_pauser().resume_all();
+}
+
+// ****************** Tracee ******************
+
+/// Stops the current thread in a ptrace-stop until its tracer resumes it.
+///
+/// Returns the signal that the tracer asks to deliver.
+fn ptrace_stop(stop: PtraceStop, context: &mut UserContext) -> Option<SigNum> {
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+
+    let tracer = {
+        let mut ptrace = posix_thread.ptrace().lock();
+        let state = ptrace.as_mut()?;
+        let Some(tracer) = state.tracer() else {
+            // The tracer has gone. Continue as if the thread were detached.
+            *ptrace = None;
+            return match stop {
+                PtraceStop::Signal(sig_num) => Some(sig_num),
+                _ => None,
+            };
+        };
+
+        let siginfo = match stop {
+            PtraceStop::Signal(_) => state.siginfo,
+            // The signal code is the same as the stop signal in the status reported by `wait`.
+            _ => {
+                let code = (state.stop_status_of(stop) >> 8) as i32;
+                Some(siginfo_t::new(SIGTRAP, code))
+            }
+        };
+        state.stop = Some(stop);
+        state.is_stop_reported = false;
+        state.siginfo = siginfo;
+        state.context = Some(*context);
+        current_thread.status().lock().set_stopped();
+        tracer
+    };
+    notify_tracer(&tracer);
+
+    // Wait in the same way as a job-control stop, except that the pending signals
+    // are not handled. Only `SIGKILL` can end a ptrace-stop without the tracer.
+    while current_thread.status().lock().is_stopped() {
+        if posix_thread.has_pending_signal_of(SIGKILL) {
+            break;
+        }
+        Thread::yield_now();
+    }
+
+    let mut ptrace = posix_thread.ptrace().lock();
+    let state = ptrace.as_mut()?;
+    if state.stop.take().is_some() {
+        // The thread is woken up by `SIGKILL`.
+        let mut status = current_thread.status().lock();
+        if status.is_stopped() {
+            status.set_running();
+        }
+    }
+    if let Some(new_context) = state.context.take() {
+        *context = new_context;
+    }
+    if state.resume_mode == PtraceResumeMode::SingleStep {
+        context.set_rflags(context.rflags() | RFLAGS_TF);
+    } else {
+        context.set_rflags(context.rflags() & !RFLAGS_TF);
+    }
+    let signal = state.resume_signal.take();
+    if state.is_detached {
+        *ptrace = None;
+    }
+    signal
+}
+
+/// Stops the current thread for the event, if the thread is traced and the tracer
+/// is interested in the event.
+fn ptrace_event_stop(event: PtraceEvent, context: &mut UserContext) {
+    if let Some(sig_num) = ptrace_stop(PtraceStop::Event(event), context) {
+        enqueue_signal_to_current(sig_num);
+    }
+}
+
+fn enqueue_signal_to_current(sig_num: SigNum) {
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    posix_thread.enqueue_signal(Box::new(KernelSignal::new(sig_num)));
+}
+
+/// Runs the ptrace-stops of the current thread before a signal is delivered.
+///
+/// The tracer may change the signal or suppress it, in which case `None` is returned.
+pub fn ptrace_signal_stop(
+    signal: Box<dyn Signal>,
+    context: &mut UserContext,
+) -> Option<Box<dyn Signal>> {
+    let sig_num = signal.num();
+    if sig_num == SIGKILL {
+        return Some(signal);
+    }
+
+    {
+        let current_thread = current_thread!();
+        let posix_thread = current_thread.as_posix_thread().unwrap();
+        let mut ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_mut() else {
+            return Some(signal);
+        };
+        state.siginfo = Some(signal.to_info());
+    }
+
+    let new_sig_num = ptrace_stop(PtraceStop::Signal(sig_num), context)?;
+    if new_sig_num == sig_num {
+        Some(signal)
+    } else {
+        Some(Box::new(KernelSignal::new(new_sig_num)))
+    }
+}
+
+/// Runs the pending event stop of the current thread, if any.
+pub fn ptrace_pending_event_stop(context: &mut UserContext) {
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    let event = {
+        let mut ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_mut() else {
+            return;
+        };
+        state.pending_event.take()
+    };
+    if let Some(event) = event {
+        ptrace_event_stop(event, context);
+    }
+}
+
+/// Runs the syscall-enter-stop of the current thread, if it is asked to.
+///
+/// Returns the number of the syscall to run, which may be changed by the tracer,
+/// or `None` if the tracer asks to skip the syscall.
+pub fn ptrace_syscall_enter(context: &mut UserContext) -> Option<usize> {
+    let syscall_number = context.rax();
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+
+    let should_stop = {
+        let mut ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_mut() else {
+            return Some(syscall_number);
+        };
+        state.orig_rax = syscall_number;
+        state.resume_mode == PtraceResumeMode::Syscall
+    };
+    if !should_stop {
+        return Some(syscall_number);
+    }
+
+    // The syscall fails with `ENOSYS` if it is skipped by the tracer.
+    context.set_rax(-(Errno::ENOSYS as i32) as usize);
+    if let Some(sig_num) = ptrace_stop(PtraceStop::Syscall, context) {
+        enqueue_signal_to_current(sig_num);
+    }
+
+    let syscall_number = {
+        let ptrace = posix_thread.ptrace().lock();
+        ptrace
+            .as_ref()
+            .map_or(syscall_number, |state| state.orig_rax)
+    };
+    if syscall_number as isize == -1 {
+        return None;
+    }
+    context.set_rax(syscall_number);
+    Some(syscall_number)
+}
+
+/// Runs the event stop and the syscall-exit-stop of the current thread, if it is asked to.
+pub fn ptrace_syscall_exit(context: &mut UserContext) {
+    ptrace_pending_event_stop(context);
+
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    let should_stop = {
+        let ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_ref() else {
+            return;
+        };
+        state.resume_mode == PtraceResumeMode::Syscall
+    };
+    if should_stop && let Some(sig_num) = ptrace_stop(PtraceStop::Syscall, context) {
+        enqueue_signal_to_current(sig_num);
+    }
+
+    if let Some(state) = posix_thread.ptrace().lock().as_mut() {
+        state.orig_rax = usize::MAX;
+    }
+}
+
+/// Runs the `PTRACE_EVENT_EXIT` stop of the current thread, if it is asked to.
+pub fn ptrace_exit_stop(term_status: TermStatus, context: &mut UserContext) {
+    if !has_event_enabled(PtraceEvent::Exit, term_status.as_u32() as u64) {
+        return;
+    }
+    // The thread is going to exit, so any signal from the tracer is useless.
+    let _ = ptrace_stop(PtraceStop::Event(PtraceEvent::Exit), context);
+}
+
+/// Records the successful `execve` of the current thread.
+///
+/// The thread will stop with `PTRACE_EVENT_EXEC` at the exit of the syscall if the
+/// tracer is interested in it. Otherwise, a thread that is not attached by
+/// `PTRACE_SEIZE` receives a `SIGTRAP`.
+pub fn ptrace_exec_event() {
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    let mut ptrace = posix_thread.ptrace().lock();
+    let Some(state) = ptrace.as_mut() else {
+        return;
+    };
+    if state.options.contains(PtraceOptions::PTRACE_O_TRACEEXEC) {
+        state.pending_event = Some(PtraceEvent::Exec);
+        state.event_msg = current_thread.tid() as u64;
+    } else if !state.is_seized {
+        drop(ptrace);
+        enqueue_signal_to_current(SIGTRAP);
+    }
+}
+
+/// Attaches the new child of the current thread to the tracer of the current thread,
+/// if the tracer is interested in the creation of the child.
+///
+/// The child stops before it runs any user code. The current thread will stop with
+/// the corresponding event at the exit of the syscall.
+pub(super) fn ptrace_clone_event(child: &Arc<Thread>, clone_flags: CloneFlags) {
+    let event = if clone_flags.contains(CloneFlags::CLONE_VFORK) {
+        PtraceEvent::Vfork
+    } else if clone_flags.contains(CloneFlags::CLONE_THREAD) {
+        PtraceEvent::Clone
+    } else {
+        PtraceEvent::Fork
+    };
+
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    let (tracer, options, is_seized) = {
+        let ptrace = posix_thread.ptrace().lock();
+        let Some(state) = ptrace.as_ref() else {
+            return;
+        };
+        let Some(tracer) = state.tracer() else {
+            return;
+        };
+        (tracer, state.options, state.is_seized)
+    };
+
+    let reports_event = event
+        .option()
+        .is_some_and(|option| options.contains(option));
+    let is_forced = clone_flags.contains(CloneFlags::CLONE_PTRACE);
+    if clone_flags.contains(CloneFlags::CLONE_UNTRACED) || !(reports_event || is_forced) {
+        return;
+    }
+
+    if attach(&tracer, child.clone(), options, is_seized).is_err() {
+        return;
+    }
+    let child_posix_thread = child.as_posix_thread().unwrap();
+    if is_seized {
+        let mut ptrace = child_posix_thread.ptrace().lock();
+        ptrace.as_mut().unwrap().pending_event = Some(PtraceEvent::Stop);
+    } else {
+        child_posix_thread.enqueue_signal(Box::new(KernelSignal::new(SIGSTOP)));
+    }
+
+    if reports_event && let Some(state) = posix_thread.ptrace().lock().as_mut() {
+        state.pending_event = Some(event);
+        state.event_msg = child.tid() as u64;
+    }
+}
+
+/// Returns whether the current thread is traced and its tracer is interested in the event.
+///
+/// If so, the message of the event is recorded.
+fn has_event_enabled(event: PtraceEvent, event_msg: u64) -> bool {
+    let current_thread = current_thread!();
+    let posix_thread = current_thread.as_posix_thread().unwrap();
+    let mut ptrace = posix_thread.ptrace().lock();
+    let Some(state) = ptrace.as_mut() else {
+        return false;
+    };
+    let is_enabled = event
+        .option()
+        .map_or(true, |option| state.options.contains(option));
+    if is_enabled {
+        state.event_msg = event_msg;
+    }
+    is_enabled
+}
@@ -56,6 +56,26 @@ impl siginfo_t {
         // let siginfo = *self;
         read_union_fields!(self.siginfo_fields.sigfault.addr)
     }
+
+    /// Sets the process ID and the status of the child for `SIGCHLD`.
+    pub fn set_sigchld_fields(&mut self, pid: Pid, status: i32) {
+        // The layout is the same as the `_sigchld` field of Linux's `siginfo_t`,
+        // whose `si_uid` field is left zero.
+        let mut bytes = [0; 128 - mem::size_of::<i32>() * 4];
+        bytes[0..4].copy_from_slice(&pid.to_ne_bytes());
+        bytes[8..12].copy_from_slice(&status.to_ne_bytes());
+        self.siginfo_fields = siginfo_fields_t { bytes };
+    }
+
+    /// Sets the timer ID, the overrun count and the value of the timer for `SI_TIMER`.
+    pub fn set_timer_fields(&mut self, timer_id: i32, overrun: i32, value: u64) {
+        // The layout is the same as the `_timer` field of Linux's `siginfo_t`.
+        let mut bytes = [0; 128 - mem::size_of::<i32>() * 4];
+        bytes[0..4].copy_from_slice(&timer_id.to_ne_bytes());
+        bytes[4..8].copy_from_slice(&overrun.to_ne_bytes());
+        bytes[8..16].copy_from_slice(&value.to_ne_bytes());
+        self.siginfo_fields = siginfo_fields_t { bytes };
+    }
 }
 
 #[derive(Clone, Copy, Pod)]
@@ -18,6 +18,7 @@ use core::mem;
 use align_ext::AlignExt;
 use aster_frame::{cpu::UserContext, task::Task};
 use c_types::{siginfo_t, ucontext_t};
+use constants::SIGKILL;
 pub use events::{SigEvents, SigEventsFilter};
 pub use pauser::Pauser;
 pub use poll::{Pollee, Poller};
@@ -26,7 +27,10 @@ use sig_mask::SigMask;
 use sig_num::SigNum;
 pub use sig_stack::{SigStack, SigStackFlags};
 
-use super::posix_thread::{PosixThread, PosixThreadExt};
+use super::{
+    posix_thread::{PosixThread, PosixThreadExt},
+    ptrace::{ptrace_exit_stop, ptrace_pending_event_stop, ptrace_signal_stop},
+};
 use crate::{
     prelude::*,
     process::{do_exit_group, TermStatus},
@@ -38,6 +42,9 @@ pub fn handle_pending_signal(context: &mut UserContext) -> Result<()> {
     let current = current!();
     let current_thread = current_thread!();
 
+    // A traced thread may be asked to stop by its tracer without any signal.
+    ptrace_pending_event_stop(context);
+
     // We first deal with signal in current thread, then signal in current process.
     let signal = {
         let posix_thread = current_thread.as_posix_thread().unwrap();
@@ -49,6 +56,11 @@ pub fn handle_pending_signal(context: &mut UserContext) -> Result<()> {
         }
     };
 
+    // The tracer of a traced thread may change or suppress the signal.
+    let Some(signal) = ptrace_signal_stop(signal, context) else {
+        return Ok(());
+    };
+
     let sig_num = signal.num();
     trace!("sig_num = {:?}, sig_name = {}", sig_num, sig_num.sig_name());
     let sig_action = current.sig_dispositions().lock().get(sig_num);
@@ -81,7 +93,11 @@ pub fn handle_pending_signal(context: &mut UserContext) -> Result<()> {
                         current.executable_path(),
                         sig_num.sig_name()
                     );
-                    do_exit_group(TermStatus::Killed(sig_num));
+                    let term_status = TermStatus::Killed(sig_num);
+                    if sig_num != SIGKILL {
+                        ptrace_exit_stop(term_status, context);
+                    }
+                    do_exit_group(term_status);
                     // We should exit current here, since we cannot restore a valid status from trap now.
                     Task::current().exit();
                 }
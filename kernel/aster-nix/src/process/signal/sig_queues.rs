@@ -131,6 +131,25 @@ impl SigQueues {
         None
     }
 
+    /// Returns whether the signal is pending, no matter whether it is blocked.
+    pub fn has_pending(&self, signum: SigNum) -> bool {
+        if signum.is_std() {
+            let idx = (signum.as_u8() - MIN_STD_SIG_NUM) as usize;
+            self.std_queues[idx].is_some()
+        } else {
+            let idx = (signum.as_u8() - MIN_RT_SIG_NUM) as usize;
+            !self.rt_queues[idx].is_empty()
+        }
+    }
+
+    /// Returns whether any signal in the mask is pending.
+    pub fn has_pending_in(&self, mask: &SigMask) -> bool {
+        (MIN_STD_SIG_NUM..=MAX_RT_SIG_NUM).any(|num| {
+            let signum = SigNum::try_from(num).unwrap();
+            mask.contains(signum) && self.has_pending(signum)
+        })
+    }
+
     fn get_std_queue_mut(&mut self, signum: SigNum) -> &mut Option<Box<dyn Signal>> {
         debug_assert!(signum.is_std());
         let idx = (signum.as_u8() - MIN_STD_SIG_NUM) as usize;
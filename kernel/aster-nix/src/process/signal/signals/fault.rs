@@ -1,9 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
 use aster_frame::cpu::{
-    CpuException, CpuExceptionInfo, ALIGNMENT_CHECK, BOUND_RANGE_EXCEEDED, DIVIDE_BY_ZERO,
-    GENERAL_PROTECTION_FAULT, INVALID_OPCODE, PAGE_FAULT, SIMD_FLOATING_POINT_EXCEPTION,
-    X87_FLOATING_POINT_EXCEPTION,
+    CpuException, CpuExceptionInfo, ALIGNMENT_CHECK, BOUND_RANGE_EXCEEDED, BREAKPOINT, DEBUG,
+    DIVIDE_BY_ZERO, GENERAL_PROTECTION_FAULT, INVALID_OPCODE, PAGE_FAULT,
+    SIMD_FLOATING_POINT_EXCEPTION, X87_FLOATING_POINT_EXCEPTION,
 };
 
 use super::Signal;
@@ -24,6 +24,8 @@ impl FaultSignal {
         let exception = CpuException::to_cpu_exception(trap_info.id as u16).unwrap();
         let (num, code, addr) = match *exception {
             DIVIDE_BY_ZERO => (SIGFPE, FPE_INTDIV, None),
+            DEBUG => (SIGTRAP, TRAP_TRACE, None),
+            BREAKPOINT => (SIGTRAP, TRAP_BRKPT, None),
             X87_FLOATING_POINT_EXCEPTION | SIMD_FLOATING_POINT_EXCEPTION => {
                 (SIGFPE, FPE_FLTDIV, None)
             }
@@ -13,4 +13,6 @@ pub trait Signal: Send + Sync + Debug + Any {
     fn num(&self) -> SigNum;
     /// Returns the siginfo_t that gives more details about a signal.
     fn to_info(&self) -> siginfo_t;
+    /// Called when the signal is dequeued to be delivered or to be read.
+    fn on_dequeue(&self) {}
 }
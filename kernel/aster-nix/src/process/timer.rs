@@ -0,0 +1,578 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The interval timers of processes.
+//!
+//! A process has the three interval timers of `setitimer`, which count down in real
+//! time (`ITIMER_REAL`) or in the CPU time of the process (`ITIMER_VIRTUAL` and
+//! `ITIMER_PROF`), and the POSIX timers created by `timer_create`, which may count
+//! on a wall clock or on the CPU time of the process or of a thread.
+//!
+//! The timers on the wall clocks are driven by the timers of `aster-frame`, while the
+//! timers on the CPU-time clocks are checked when a timer tick is charged to a thread.
+//! Both happen in the interrupt context, so the expirations only touch atomics and the
+//! signals are sent in the work queue.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicI32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_frame::timer::Timer;
+
+use super::{
+    posix_thread::PosixThreadExt,
+    signal::{
+        c_types::siginfo_t,
+        constants::{SIGALRM, SIGPROF, SIGVTALRM, SI_TIMER},
+        sig_num::SigNum,
+        signals::{kernel::KernelSignal, Signal},
+    },
+    Process,
+};
+use crate::{
+    prelude::*,
+    thread::{
+        thread_table,
+        work_queue::{submit_work_func, WorkPriority},
+        Tid,
+    },
+    time::{now_as_duration, ClockID},
+};
+
+/// The ID of a POSIX timer, which is unique in its process.
+pub type TimerId = i32;
+
+/// The interval timers of `setitimer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[repr(i32)]
+pub enum ITimerKind {
+    /// Counts down in real time and sends `SIGALRM`.
+    Real = 0,
+    /// Counts down in the user CPU time of the process and sends `SIGVTALRM`.
+    Virtual = 1,
+    /// Counts down in the CPU time of the process and sends `SIGPROF`.
+    Prof = 2,
+}
+
+/// The clock that a timer counts on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerClock {
+    /// A wall clock, which is `CLOCK_REALTIME`, `CLOCK_MONOTONIC` or `CLOCK_BOOTTIME`.
+    Wall(ClockID),
+    /// The CPU time of the process.
+    ProcessCpu,
+    /// The user CPU time of the process, on which `ITIMER_VIRTUAL` counts.
+    ProcessUserCpu,
+    /// The CPU time of a thread of the process.
+    ThreadCpu(Tid),
+}
+
+/// How a timer notifies its expirations.
+#[derive(Debug, Clone, Copy)]
+pub enum TimerNotify {
+    /// No notification (`SIGEV_NONE`). The timer can only be observed with `timer_gettime`.
+    None,
+    /// A kernel signal to the process, which is sent by the interval timers of `setitimer`.
+    Kernel(SigNum),
+    /// A timer signal to the process (`SIGEV_SIGNAL`) or to a thread of it (`SIGEV_THREAD_ID`),
+    /// which carries the value given by the user.
+    Signal {
+        signum: SigNum,
+        tid: Option<Tid>,
+        value: u64,
+    },
+}
+
+/// A timer of a process.
+pub struct PosixTimer {
+    clock: TimerClock,
+    /// The timer of `aster-frame`, which exists if the timer counts on a wall clock.
+    wall_timer: Option<Arc<Timer>>,
+    state: Arc<TimerState>,
+}
+
+/// The state shared with the timer callbacks and the timer signals.
+struct TimerState {
+    id: TimerId,
+    notify: TimerNotify,
+    process: Weak<Process>,
+    /// The time of the next expiration on the clock in nanoseconds, or zero if disarmed.
+    deadline: AtomicU64,
+    /// The interval of a periodic timer in nanoseconds, or zero if one-shot.
+    interval: AtomicU64,
+    /// The sequence number of the timer signal that is sent but not dequeued yet, or zero.
+    queued_signal: AtomicU64,
+    /// The number of expirations that are not notified since the last signal is sent.
+    overrun: AtomicU64,
+    /// The overrun count of the last dequeued signal, which is returned by `timer_getoverrun`.
+    last_overrun: AtomicI32,
+}
+
+/// The sequence number of the next timer signal, which tells the signals of a timer apart.
+static NEXT_SIGNAL_SEQ: AtomicU64 = AtomicU64::new(1);
+
+impl PosixTimer {
+    fn new(
+        id: TimerId,
+        clock: TimerClock,
+        notify: TimerNotify,
+        process: Weak<Process>,
+    ) -> Result<Arc<Self>> {
+        let state = Arc::new(TimerState {
+            id,
+            notify,
+            process,
+            deadline: AtomicU64::new(0),
+            interval: AtomicU64::new(0),
+            queued_signal: AtomicU64::new(0),
+            overrun: AtomicU64::new(0),
+            last_overrun: AtomicI32::new(0),
+        });
+
+        let wall_timer = match clock {
+            TimerClock::Wall(_) => {
+                let state = state.clone();
+                Some(Timer::new(move |timer| {
+                    let interval = state.interval.load(Ordering::Relaxed);
+                    if interval != 0 {
+                        state.deadline.fetch_add(interval, Ordering::Relaxed);
+                        timer.set(Duration::from_nanos(interval));
+                    } else {
+                        state.deadline.store(0, Ordering::Relaxed);
+                    }
+                    state.expire(1);
+                })?)
+            }
+            TimerClock::ProcessCpu | TimerClock::ProcessUserCpu | TimerClock::ThreadCpu(_) => None,
+        };
+
+        Ok(Arc::new(Self {
+            clock,
+            wall_timer,
+            state,
+        }))
+    }
+
+    /// Arms the timer to expire after `value` and then every `interval`, or disarms
+    /// the timer if `value` is zero. If `is_absolute` is true, `value` is the time
+    /// on the clock rather than relative to now.
+    ///
+    /// Returns the old setting, as `get_time` does.
+    pub fn set_time(
+        &self,
+        value: Duration,
+        interval: Duration,
+        is_absolute: bool,
+    ) -> Result<(Duration, Duration)> {
+        let old_setting = self.get_time()?;
+
+        self.disarm();
+        if value.is_zero() {
+            return Ok(old_setting);
+        }
+
+        let now = self.now()?;
+        let (deadline, timeout) = if is_absolute {
+            (value, value.saturating_sub(now))
+        } else {
+            (now + value, value)
+        };
+        self.state
+            .interval
+            .store(interval.as_nanos() as u64, Ordering::Relaxed);
+        self.state
+            .deadline
+            .store(deadline.as_nanos() as u64, Ordering::Relaxed);
+        if let Some(wall_timer) = &self.wall_timer {
+            wall_timer.set(timeout);
+        }
+        Ok(old_setting)
+    }
+
+    /// Returns the remaining time until the next expiration and the interval.
+    ///
+    /// The remaining time is zero if the timer is disarmed.
+    pub fn get_time(&self) -> Result<(Duration, Duration)> {
+        let deadline = self.state.deadline.load(Ordering::Relaxed);
+        let interval = Duration::from_nanos(self.state.interval.load(Ordering::Relaxed));
+        if deadline == 0 {
+            return Ok((Duration::ZERO, interval));
+        }
+
+        let remain = Duration::from_nanos(deadline).saturating_sub(self.now()?);
+        Ok((remain, interval))
+    }
+
+    /// Returns the number of the expirations that are not notified before the
+    /// last signal of the timer is dequeued.
+    pub fn overrun(&self) -> i32 {
+        self.state.last_overrun.load(Ordering::Relaxed)
+    }
+
+    /// Disarms the timer and resets the overrun counts.
+    fn disarm(&self) {
+        if let Some(wall_timer) = &self.wall_timer {
+            wall_timer.clear();
+        }
+        self.state.deadline.store(0, Ordering::Relaxed);
+        self.state.interval.store(0, Ordering::Relaxed);
+        self.state.overrun.store(0, Ordering::Relaxed);
+        self.state.last_overrun.store(0, Ordering::Relaxed);
+    }
+
+    fn now(&self) -> Result<Duration> {
+        match self.clock {
+            TimerClock::Wall(clock_id) => now_as_duration(&clock_id),
+            TimerClock::ProcessCpu => {
+                let process =
+                    self.state.process.upgrade().ok_or_else(|| {
+                        Error::with_message(Errno::ESRCH, "the process has exited")
+                    })?;
+                Ok(process.timers().cpu_time())
+            }
+            TimerClock::ProcessUserCpu => {
+                let process =
+                    self.state.process.upgrade().ok_or_else(|| {
+                        Error::with_message(Errno::ESRCH, "the process has exited")
+                    })?;
+                Ok(process.timers().user_cpu_time())
+            }
+            TimerClock::ThreadCpu(tid) => {
+                let thread = thread_table::get_thread(tid)
+                    .ok_or_else(|| Error::with_message(Errno::ESRCH, "the thread has exited"))?;
+                Ok(thread.sched_entity().runtime())
+            }
+        }
+    }
+
+    /// Checks whether a timer on a CPU-time clock expires at the CPU time `now`.
+    fn check_cpu_time(&self, now: Duration) {
+        let now = now.as_nanos() as u64;
+        let deadline = self.state.deadline.load(Ordering::Relaxed);
+        if deadline == 0 || now < deadline {
+            return;
+        }
+
+        // A tick may cover multiple periods, each of which counts as an expiration.
+        let interval = self.state.interval.load(Ordering::Relaxed);
+        let (new_deadline, count) = if interval == 0 {
+            (0, 1)
+        } else {
+            let count = (now - deadline) / interval + 1;
+            (deadline + count * interval, count)
+        };
+        // The ticks of the threads on other CPUs may race to expire the timer.
+        if self
+            .state
+            .deadline
+            .compare_exchange(deadline, new_deadline, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.state.expire(count);
+        }
+    }
+}
+
+impl Drop for PosixTimer {
+    fn drop(&mut self) {
+        if let Some(wall_timer) = &self.wall_timer {
+            wall_timer.clear();
+        }
+    }
+}
+
+impl TimerState {
+    /// Notifies `count` expirations of the timer.
+    ///
+    /// This method is called in the interrupt context.
+    fn expire(self: &Arc<Self>, count: u64) {
+        match self.notify {
+            TimerNotify::None => (),
+            TimerNotify::Kernel(signum) => {
+                let process = self.process.clone();
+                submit_work_func(
+                    move || {
+                        if let Some(process) = process.upgrade() {
+                            process.enqueue_signal(KernelSignal::new(signum));
+                        }
+                    },
+                    WorkPriority::High,
+                );
+            }
+            TimerNotify::Signal { .. } => {
+                let seq = NEXT_SIGNAL_SEQ.fetch_add(1, Ordering::Relaxed);
+                if self
+                    .queued_signal
+                    .compare_exchange(0, seq, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_err()
+                {
+                    // The last signal has not been dequeued, so the expirations are overruns.
+                    self.overrun.fetch_add(count, Ordering::Relaxed);
+                    return;
+                }
+                self.overrun.fetch_add(count - 1, Ordering::Relaxed);
+
+                let state = self.clone();
+                submit_work_func(move || state.send_signal(seq), WorkPriority::High);
+            }
+        }
+    }
+
+    fn send_signal(self: &Arc<Self>, seq: u64) {
+        let TimerNotify::Signal { signum, tid, value } = self.notify else {
+            unreachable!("the timer does not notify with signals");
+        };
+        let signal = TimerSignal {
+            num: signum,
+            value,
+            seq,
+            overrun: AtomicI32::new(0),
+            timer: self.clone(),
+        };
+
+        // If the signal is not enqueued, dropping it allows the timer to send the next one.
+        match tid {
+            Some(tid) => {
+                if let Some(thread) = thread_table::get_thread(tid)
+                    && let Some(posix_thread) = thread.as_posix_thread()
+                {
+                    posix_thread.enqueue_signal(Box::new(signal));
+                }
+            }
+            None => {
+                if let Some(process) = self.process.upgrade() {
+                    process.enqueue_signal(signal);
+                }
+            }
+        }
+    }
+}
+
+/// The signal sent by a POSIX timer.
+///
+/// A timer has at most one signal queued at a time. The expirations before the
+/// signal is dequeued are counted as overruns, which the signal carries.
+pub struct TimerSignal {
+    num: SigNum,
+    value: u64,
+    seq: u64,
+    /// The overrun count, which is settled when the signal is dequeued.
+    overrun: AtomicI32,
+    timer: Arc<TimerState>,
+}
+
+impl Signal for TimerSignal {
+    fn num(&self) -> SigNum {
+        self.num
+    }
+
+    fn to_info(&self) -> siginfo_t {
+        let mut info = siginfo_t::new(self.num, SI_TIMER);
+        info.set_timer_fields(
+            self.timer.id,
+            self.overrun.load(Ordering::Relaxed),
+            self.value,
+        );
+        info
+    }
+
+    fn on_dequeue(&self) {
+        if self.timer.queued_signal.load(Ordering::Relaxed) != self.seq {
+            return;
+        }
+
+        let overrun = self.timer.overrun.swap(0, Ordering::Relaxed);
+        // Like `DELAYTIMER_MAX` of Linux, the overrun count saturates at `i32::MAX`.
+        let overrun = overrun.min(i32::MAX as u64) as i32;
+        self.overrun.store(overrun, Ordering::Relaxed);
+        self.timer.last_overrun.store(overrun, Ordering::Relaxed);
+        self.release();
+    }
+}
+
+impl TimerSignal {
+    /// Allows the timer to send the next signal.
+    fn release(&self) {
+        let _ = self.timer.queued_signal.compare_exchange(
+            self.seq,
+            0,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl Drop for TimerSignal {
+    fn drop(&mut self) {
+        // The signal may be discarded without being dequeued, e.g., if a standard
+        // signal of the same number is already pending.
+        self.release();
+    }
+}
+
+impl fmt::Debug for TimerSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimerSignal")
+            .field("num", &self.num)
+            .field("timer_id", &self.timer.id)
+            .field("overrun", &self.overrun)
+            .finish()
+    }
+}
+
+/// The timers of a process.
+///
+/// The timers are protected by spin locks, since they are checked in the interrupt
+/// context on timer ticks.
+pub struct ProcessTimers {
+    /// The CPU time of the process in nanoseconds, including that of the exited threads.
+    cpu_time: AtomicU64,
+    /// The part of `cpu_time` that is spent in the user mode, in nanoseconds.
+    user_cpu_time: AtomicU64,
+    /// The interval timers of `setitimer`, which are created on first use.
+    itimers: SpinLock<[Option<Arc<PosixTimer>>; 3]>,
+    /// The POSIX timers created by `timer_create`.
+    posix_timers: SpinLock<BTreeMap<TimerId, Arc<PosixTimer>>>,
+}
+
+impl ProcessTimers {
+    pub fn new() -> Self {
+        Self {
+            cpu_time: AtomicU64::new(0),
+            user_cpu_time: AtomicU64::new(0),
+            itimers: SpinLock::new([None, None, None]),
+            posix_timers: SpinLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the CPU time of the process.
+    pub fn cpu_time(&self) -> Duration {
+        Duration::from_nanos(self.cpu_time.load(Ordering::Relaxed))
+    }
+
+    /// Returns the user CPU time of the process.
+    pub fn user_cpu_time(&self) -> Duration {
+        Duration::from_nanos(self.user_cpu_time.load(Ordering::Relaxed))
+    }
+
+    /// Returns the interval timer of `kind`, which is created on first use.
+    pub fn itimer(&self, kind: ITimerKind, process: &Arc<Process>) -> Result<Arc<PosixTimer>> {
+        let mut itimers = self.itimers.lock_irq_disabled();
+        if let Some(itimer) = &itimers[kind as usize] {
+            return Ok(itimer.clone());
+        }
+
+        // Like Linux, `ITIMER_REAL` counts on the monotonic clock.
+        let (clock, signum) = match kind {
+            ITimerKind::Real => (TimerClock::Wall(ClockID::CLOCK_MONOTONIC), SIGALRM),
+            ITimerKind::Virtual => (TimerClock::ProcessUserCpu, SIGVTALRM),
+            ITimerKind::Prof => (TimerClock::ProcessCpu, SIGPROF),
+        };
+        let itimer = PosixTimer::new(
+            0,
+            clock,
+            TimerNotify::Kernel(signum),
+            Arc::downgrade(process),
+        )?;
+        itimers[kind as usize] = Some(itimer.clone());
+        Ok(itimer)
+    }
+
+    /// Creates a POSIX timer, whose notification is made from its ID by `notify_fn`.
+    pub fn create_timer(
+        &self,
+        clock: TimerClock,
+        notify_fn: impl FnOnce(TimerId) -> TimerNotify,
+        process: &Arc<Process>,
+    ) -> Result<TimerId> {
+        let mut posix_timers = self.posix_timers.lock_irq_disabled();
+        let id = (0..TimerId::MAX)
+            .find(|id| !posix_timers.contains_key(id))
+            .ok_or_else(|| Error::with_message(Errno::EAGAIN, "too many timers"))?;
+        let timer = PosixTimer::new(id, clock, notify_fn(id), Arc::downgrade(process))?;
+        posix_timers.insert(id, timer);
+        Ok(id)
+    }
+
+    pub fn get_timer(&self, id: TimerId) -> Result<Arc<PosixTimer>> {
+        self.posix_timers
+            .lock_irq_disabled()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "the timer does not exist"))
+    }
+
+    pub fn delete_timer(&self, id: TimerId) -> Result<()> {
+        let timer = self.posix_timers.lock_irq_disabled().remove(&id);
+        if timer.is_none() {
+            return_errno_with_message!(Errno::EINVAL, "the timer does not exist");
+        }
+        Ok(())
+    }
+
+    /// Deletes all the POSIX timers, which are not preserved across `execve`.
+    pub fn delete_posix_timers(&self) {
+        let posix_timers = core::mem::take(&mut *self.posix_timers.lock_irq_disabled());
+        drop(posix_timers);
+    }
+
+    /// Deletes all the timers when the process exits.
+    pub fn clear(&self) {
+        let itimers = core::mem::take(&mut *self.itimers.lock_irq_disabled());
+        drop(itimers);
+        self.delete_posix_timers();
+    }
+
+    /// Charges `delta` of CPU time to the thread `tid`, whose CPU time becomes
+    /// `thread_cpu_time`, and expires the timers on the CPU-time clocks.
+    ///
+    /// The user CPU time is only charged if `is_user_time` is true.
+    ///
+    /// This method is called in the interrupt context on timer ticks.
+    pub(crate) fn charge_cpu_time(
+        &self,
+        tid: Tid,
+        thread_cpu_time: Duration,
+        delta: Duration,
+        is_user_time: bool,
+    ) {
+        let delta = delta.as_nanos() as u64;
+        let cpu_time =
+            Duration::from_nanos(self.cpu_time.fetch_add(delta, Ordering::Relaxed) + delta);
+        let user_cpu_time = is_user_time.then(|| {
+            Duration::from_nanos(self.user_cpu_time.fetch_add(delta, Ordering::Relaxed) + delta)
+        });
+
+        let itimers = self.itimers.lock_irq_disabled();
+        for itimer in itimers.iter().flatten() {
+            match itimer.clock {
+                TimerClock::ProcessCpu => itimer.check_cpu_time(cpu_time),
+                TimerClock::ProcessUserCpu => {
+                    if let Some(user_cpu_time) = user_cpu_time {
+                        itimer.check_cpu_time(user_cpu_time);
+                    }
+                }
+                _ => (),
+            }
+        }
+        drop(itimers);
+
+        let posix_timers = self.posix_timers.lock_irq_disabled();
+        for timer in posix_timers.values() {
+            match timer.clock {
+                TimerClock::ProcessCpu => timer.check_cpu_time(cpu_time),
+                TimerClock::ThreadCpu(timer_tid) if timer_tid == tid => {
+                    timer.check_cpu_time(thread_cpu_time)
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Default for ProcessTimers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
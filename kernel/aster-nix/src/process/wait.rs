@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use super::{process_filter::ProcessFilter, ExitCode, Pid, Process};
+use super::{process_filter::ProcessFilter, ptrace, ExitCode, Pid, Process};
 use crate::{prelude::*, process::process_table, thread::thread_table};
 
 // The definition of WaitOptions is from Occlum
@@ -40,13 +40,19 @@ pub fn wait_child_exit(
             .cloned()
             .collect::<Vec<_>>();
 
-        if unwaited_children.is_empty() {
+        // A tracer also waits for its tracees, which may not be its children.
+        if unwaited_children.is_empty() && !ptrace::has_tracees(&current, child_filter) {
             return Some(Err(Error::with_message(
                 Errno::ECHILD,
                 "the process has no child to wait",
             )));
         }
 
+        // Ptrace-stops are reported no matter whether `WSTOPPED` is specified.
+        if let Some((tid, status)) = ptrace::wait_tracee(&current, child_filter, wait_options) {
+            return Some(Ok((tid, status as ExitCode)));
+        }
+
         // return immediately if we find a zombie child
         let zombie_child = unwaited_children.iter().find(|child| child.is_zombie());
 
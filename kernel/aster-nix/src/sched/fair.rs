@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The fair scheduling class for normal tasks.
+//!
+//! Like the Completely Fair Scheduler (CFS) of Linux, each task keeps a virtual
+//! runtime, which is its real runtime weighted by its nice value. A task with a
+//! smaller nice value has a greater weight, so its virtual runtime grows slower
+//! and it gets more CPU time. The task with the smallest virtual runtime is always
+//! picked to run next.
+
+use aster_frame::task::Task;
+
+use super::{
+    nice::NICE_0_WEIGHT,
+    policy::SchedPolicy,
+    sched_entity::{thread_of, TICK_NS},
+};
+use crate::prelude::*;
+
+/// The period in which every runnable task is expected to run once, in nanoseconds.
+const SCHED_LATENCY_NS: u64 = 6_000_000;
+
+/// The minimum time slice of a task, in nanoseconds.
+const MIN_GRANULARITY_NS: u64 = 750_000;
+
+/// The virtual runtime by which a waiting task must lead the current task to preempt it,
+/// in nanoseconds.
+const WAKEUP_GRANULARITY_NS: u64 = 1_000_000;
+
+/// The fair run queue of a CPU.
+///
+/// The tasks are ordered by their virtual runtimes in a balanced tree.
+/// The task that is running is not in the queue.
+pub(super) struct FairRunQueue {
+    /// The tasks and their weights when enqueued, which are keyed by their virtual
+    /// runtimes and the sequence numbers of enqueuing that break ties.
+    tasks: BTreeMap<(u64, u64), (Arc<Task>, u64)>,
+    /// The total weight of the tasks in the queue.
+    load: u64,
+    /// The monotonically increasing lower bound of the virtual runtimes in the queue.
+    min_vruntime: u64,
+    /// The sequence number of the next enqueuing.
+    next_seq: u64,
+}
+
+impl FairRunQueue {
+    pub(super) const fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+            load: 0,
+            min_vruntime: 0,
+            next_seq: 0,
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub(super) fn push(&mut self, task: Arc<Task>) {
+        let (vruntime, weight) = match thread_of(&task) {
+            Some(thread) => {
+                let entity = thread.sched_entity();
+                // A task that has slept for a long time should not monopolize the CPU,
+                // so it only gets a bounded bonus over the tasks in the queue.
+                let vruntime = entity
+                    .vruntime()
+                    .max(self.min_vruntime.saturating_sub(SCHED_LATENCY_NS / 2));
+                entity.set_vruntime(vruntime);
+                (vruntime, entity.weight())
+            }
+            None => (self.min_vruntime, NICE_0_WEIGHT as u64),
+        };
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.tasks.insert((vruntime, seq), (task, weight));
+        self.load += weight;
+    }
+
+    /// Removes the task with the smallest virtual runtime.
+    pub(super) fn pop(&mut self) -> Option<Arc<Task>> {
+        let (&key, _) = self.tasks.first_key_value()?;
+        Some(self.remove(key))
+    }
+
+    /// Removes the task with the smallest virtual runtime among the tasks that satisfy `f`.
+    pub(super) fn pop_if(&mut self, f: impl Fn(&Task) -> bool) -> Option<Arc<Task>> {
+        let key = self
+            .tasks
+            .iter()
+            .find(|(_, (task, _))| f(task))
+            .map(|(&key, _)| key)?;
+        Some(self.remove(key))
+    }
+
+    /// Returns the lower bound of the virtual runtimes in the queue.
+    ///
+    /// The virtual runtimes of different queues are not comparable, so a task that moves
+    /// to another CPU only keeps its lead over this value.
+    pub(super) fn min_vruntime(&self) -> u64 {
+        self.min_vruntime
+    }
+
+    fn remove(&mut self, key: (u64, u64)) -> Arc<Task> {
+        let (task, weight) = self.tasks.remove(&key).unwrap();
+        self.load -= weight;
+        if let Some(thread) = thread_of(&task) {
+            // The task starts a new time slice.
+            thread.sched_entity().reset_slice_runtime();
+        }
+        self.update_min_vruntime(key.0);
+        task
+    }
+
+    /// Charges a timer tick to the given task, which is running on the current CPU.
+    ///
+    /// Returns whether the task has used up its time slice.
+    pub(super) fn tick(&mut self, task: &Task) -> bool {
+        let Some(thread) = thread_of(task) else {
+            return !self.is_empty();
+        };
+        let entity = thread.sched_entity();
+        entity.charge(TICK_NS);
+        self.update_min_vruntime(entity.vruntime());
+
+        if self.is_empty() {
+            return false;
+        }
+        let weight = entity.weight();
+        let time_slice = (SCHED_LATENCY_NS * weight / (self.load + weight)).max(MIN_GRANULARITY_NS);
+        entity.slice_runtime() >= time_slice
+    }
+
+    /// Tells whether the given task, which is running on the current CPU, should be
+    /// preempted by the task with the smallest virtual runtime in the queue.
+    pub(super) fn should_preempt(&self, task: &Task) -> bool {
+        let Some((&(min_vruntime, _), (first_task, _))) = self.tasks.first_key_value() else {
+            return false;
+        };
+        let Some(thread) = thread_of(task) else {
+            return false;
+        };
+        let entity = thread.sched_entity();
+        match thread_of(first_task).map(|thread| thread.sched_entity().policy()) {
+            // Batch and idle tasks never preempt others.
+            Some(SchedPolicy::Batch | SchedPolicy::Idle) => false,
+            // Idle tasks are preempted by any other task.
+            _ if entity.policy() == SchedPolicy::Idle => true,
+            _ => min_vruntime + WAKEUP_GRANULARITY_NS < entity.vruntime(),
+        }
+    }
+
+    /// Advances `min_vruntime` towards the smaller of `vruntime` and
+    /// the smallest virtual runtime in the queue.
+    fn update_min_vruntime(&mut self, vruntime: u64) {
+        let vruntime = match self.tasks.first_key_value() {
+            Some((&(first_vruntime, _), _)) => vruntime.min(first_vruntime),
+            None => vruntime,
+        };
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+    }
+}
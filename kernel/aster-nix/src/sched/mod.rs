@@ -1,8 +1,14 @@
 // SPDX-License-Identifier: MPL-2.0
 
+mod fair;
 pub mod nice;
+pub mod policy;
 mod priority_scheduler;
+mod real_time;
+mod sched_entity;
 
 // There may be multiple scheduling policies in the system,
 // and subsequent schedulers can be placed under this module.
-pub use self::priority_scheduler::init;
+pub use self::{
+    priority_scheduler::init, real_time::ROUND_ROBIN_TIME_SLICE_NS, sched_entity::SchedEntity,
+};
@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_frame::task::Priority as TaskPriority;
+
+/// The scheduling policy of a thread.
+///
+/// Real-time threads always run before normal threads. Among real-time threads,
+/// the ones with higher real-time priorities run first. Normal threads share
+/// the remaining CPU time according to their nice values.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SchedPolicy {
+    /// A real-time policy, with which a thread runs until it blocks, yields,
+    /// or is preempted by a thread with a higher real-time priority.
+    Fifo(RealTimePriority),
+    /// A real-time policy like `Fifo`, except that the threads with the same
+    /// real-time priority take turns to run in fixed time slices.
+    RoundRobin(RealTimePriority),
+    /// The default policy for normal threads.
+    #[default]
+    Normal,
+    /// A policy for CPU-intensive normal threads, which never preempt other
+    /// threads on wakeup.
+    Batch,
+    /// A policy for normal threads that run only when the CPU is otherwise idle.
+    Idle,
+}
+
+impl SchedPolicy {
+    pub fn is_real_time(&self) -> bool {
+        matches!(self, Self::Fifo(_) | Self::RoundRobin(_))
+    }
+
+    /// Returns the real-time priority, or `None` if the policy is not a real-time one.
+    pub fn real_time_priority(&self) -> Option<RealTimePriority> {
+        match self {
+            Self::Fifo(priority) | Self::RoundRobin(priority) => Some(*priority),
+            _ => None,
+        }
+    }
+}
+
+impl From<TaskPriority> for SchedPolicy {
+    /// Converts the priority that a task is created with, where a smaller value
+    /// represents a higher priority and the values below 100 are real-time ones.
+    fn from(priority: TaskPriority) -> Self {
+        if priority.is_real_time() {
+            let raw = RealTimePriority::MAX.to_raw() as u16 - priority.get();
+            Self::Fifo(RealTimePriority::new(raw as u8))
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// The real-time priority of a thread.
+///
+/// It is a value in the range 1 to 99, with 99 being the highest priority.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RealTimePriority {
+    value: u8,
+}
+
+impl RealTimePriority {
+    /// The minimum real-time priority, whose value is 1.
+    pub const MIN: Self = Self { value: 1 };
+
+    /// The maximum real-time priority, whose value is 99.
+    pub const MAX: Self = Self { value: 99 };
+
+    /// Creates a new `RealTimePriority` from the raw value.
+    ///
+    /// Values given beyond the permissible range are automatically adjusted
+    /// to the nearest boundary value.
+    pub fn new(raw: u8) -> Self {
+        if raw > Self::MAX.to_raw() {
+            Self::MAX
+        } else if raw < Self::MIN.to_raw() {
+            Self::MIN
+        } else {
+            Self { value: raw }
+        }
+    }
+
+    /// Converts to the raw value.
+    pub const fn to_raw(self) -> u8 {
+        self.value
+    }
+}
@@ -1,8 +1,18 @@
 // SPDX-License-Identifier: MPL-2.0
 
-use aster_frame::task::{set_scheduler, Scheduler, Task, TaskAdapter};
-use intrusive_collections::LinkedList;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use aster_frame::{
+    cpu::{num_cpus, this_cpu},
+    smp::send_reschedule_ipi,
+    task::{set_scheduler, Scheduler, Task},
+};
+
+use super::{
+    fair::FairRunQueue,
+    real_time::RealTimeRunQueue,
+    sched_entity::{charge_cpu_time, is_allowed_on, policy_of, thread_of, with_cpu_affinity},
+};
 use crate::prelude::*;
 
 pub fn init() {
@@ -13,44 +23,230 @@ pub fn init() {
 
 /// The preempt scheduler
 ///
-/// Real-time tasks are placed in the `real_time_tasks` queue and
-/// are always prioritized during scheduling.
-/// Normal tasks are placed in the `normal_tasks` queue and are only
-/// scheduled for execution when there are no real-time tasks.
+/// Each CPU has its own run queue. A task is enqueued to the least loaded
+/// CPU among the CPUs that it is allowed to run on, and an idle CPU steals
+/// tasks from the run queues of other CPUs.
+///
+/// Within a run queue, real-time tasks are always prioritized during scheduling
+/// in the order of their real-time priorities (see the `real_time` module), and normal
+/// tasks share the rest of the CPU time fairly according to their nice values (see the
+/// `fair` module).
 struct PreemptScheduler {
-    /// Tasks with a priority of less than 100 are regarded as real-time tasks.
-    real_time_tasks: SpinLock<LinkedList<TaskAdapter>>,
-    /// Tasks with a priority greater than or equal to 100 are regarded as normal tasks.
-    normal_tasks: SpinLock<LinkedList<TaskAdapter>>,
+    /// The run queues of the CPUs, indexed by the CPU IDs.
+    run_queues: Vec<RunQueue>,
 }
 
 impl PreemptScheduler {
     pub fn new() -> Self {
-        Self {
-            real_time_tasks: SpinLock::new(LinkedList::new(TaskAdapter::new())),
-            normal_tasks: SpinLock::new(LinkedList::new(TaskAdapter::new())),
+        let run_queues = (0..num_cpus()).map(|_| RunQueue::new()).collect();
+        Self { run_queues }
+    }
+
+    /// Selects the CPU to run the task.
+    ///
+    /// The current CPU is preferred if it is among the least loaded ones.
+    fn select_cpu(&self, task: &Arc<Task>) -> u32 {
+        let this_cpu = this_cpu();
+        let mut selected = None;
+        let mut min_len = usize::MAX;
+        with_cpu_affinity(task, |cpu_affinity| {
+            for cpu_id in cpu_affinity.iter() {
+                let Some(run_queue) = self.run_queues.get(cpu_id) else {
+                    continue;
+                };
+                let len = run_queue.len();
+                if len < min_len || (len == min_len && cpu_id as u32 == this_cpu) {
+                    selected = Some(cpu_id as u32);
+                    min_len = len;
+                }
+            }
+        });
+        // Fall back to the current CPU if the task is not allowed to run on any online CPU.
+        selected.unwrap_or(this_cpu)
+    }
+
+    /// Makes the virtual runtime of a normal task relative to the run queue of `cpu_id`,
+    /// if it was last relative to the run queue of another CPU.
+    ///
+    /// This must be done before the task is pushed to or runs on the CPU.
+    fn move_to(&self, task: &Task, cpu_id: u32) {
+        if policy_of(task).is_real_time() {
+            return;
+        }
+        let Some(thread) = thread_of(task) else {
+            return;
+        };
+        let entity = thread.sched_entity();
+        let prev_cpu = entity.swap_cpu(cpu_id);
+        if prev_cpu == cpu_id {
+            return;
         }
+        let Some(prev_queue) = self.run_queues.get(prev_cpu as usize) else {
+            return;
+        };
+        let lead = entity
+            .vruntime()
+            .saturating_sub(prev_queue.fair_tasks.lock_irq_disabled().min_vruntime());
+        let min_vruntime = self.run_queues[cpu_id as usize]
+            .fair_tasks
+            .lock_irq_disabled()
+            .min_vruntime();
+        entity.set_vruntime(lead + min_vruntime);
+    }
+
+    fn push_to(&self, task: Arc<Task>, cpu_id: u32) {
+        self.move_to(&task, cpu_id);
+        self.run_queues[cpu_id as usize].push(task);
+    }
+
+    /// Tells whether the given task, which is running on the given CPU, should be
+    /// preempted by the tasks in the run queue of that CPU.
+    fn should_preempt_on(&self, cpu_id: u32, task: &Task) -> bool {
+        let run_queue = &self.run_queues[cpu_id as usize];
+        let real_time_tasks = run_queue.real_time_tasks.lock_irq_disabled();
+        if policy_of(task).is_real_time() {
+            return real_time_tasks.should_preempt(task);
+        }
+        !real_time_tasks.is_empty()
+            || run_queue
+                .fair_tasks
+                .lock_irq_disabled()
+                .should_preempt(task)
+    }
+
+    fn dequeue_task(&self, this_cpu: u32) -> Option<Arc<Task>> {
+        while let Some(task) = self.run_queues[this_cpu as usize].pop() {
+            if is_allowed_on(&task, this_cpu) {
+                return Some(task);
+            }
+            // The CPU affinity of the task has been changed since it was enqueued,
+            // so move it to a CPU that it is allowed to run on.
+            let cpu_id = self.select_cpu(&task);
+            if cpu_id == this_cpu {
+                return Some(task);
+            }
+            self.push_to(task, cpu_id);
+            send_reschedule_ipi(cpu_id);
+        }
+
+        // Steal a task from the busiest CPU.
+        let busiest_queue = self
+            .run_queues
+            .iter()
+            .enumerate()
+            .filter(|(cpu_id, _)| *cpu_id as u32 != this_cpu)
+            .max_by_key(|(_, run_queue)| run_queue.len())
+            .map(|(_, run_queue)| run_queue)?;
+        let task = busiest_queue.steal(this_cpu)?;
+        self.move_to(&task, this_cpu);
+        Some(task)
     }
 }
 
 impl Scheduler for PreemptScheduler {
-    fn enqueue(&self, task: Arc<Task>) {
-        if task.is_real_time() {
-            self.real_time_tasks.lock_irq_disabled().push_back(task);
-        } else {
-            self.normal_tasks.lock_irq_disabled().push_back(task);
+    fn enqueue(&self, task: Arc<Task>) -> Option<u32> {
+        let cpu_id = self.select_cpu(&task);
+        self.push_to(task, cpu_id);
+        // The CPU does not need to be notified if the task running on it goes on running.
+        match self.run_queues[cpu_id as usize].current() {
+            Some(curr_task) if !self.should_preempt_on(cpu_id, &curr_task) => None,
+            _ => Some(cpu_id),
         }
     }
 
     fn dequeue(&self) -> Option<Arc<Task>> {
-        if !self.real_time_tasks.lock_irq_disabled().is_empty() {
-            self.real_time_tasks.lock_irq_disabled().pop_front()
+        let this_cpu = this_cpu();
+        let task = self.dequeue_task(this_cpu);
+        // If no task is dequeued, the current task may go on running. It is only
+        // recorded again in the next tick, since the CPU may become idle as well.
+        self.run_queues[this_cpu as usize].set_current(task.as_ref());
+        task
+    }
+
+    fn should_preempt(&self, task: &Arc<Task>) -> bool {
+        self.should_preempt_on(this_cpu(), task)
+    }
+
+    fn tick(&self, task: &Arc<Task>) -> bool {
+        charge_cpu_time(task);
+
+        let run_queue = &self.run_queues[this_cpu() as usize];
+        run_queue.set_current(Some(task));
+        if policy_of(task).is_real_time() {
+            return run_queue.real_time_tasks.lock_irq_disabled().tick(task);
+        }
+        // The tick must be charged even if the task is to be preempted anyway.
+        let slice_used_up = run_queue.fair_tasks.lock_irq_disabled().tick(task);
+        slice_used_up || run_queue.has_real_time_tasks()
+    }
+}
+
+/// The run queue of a CPU.
+struct RunQueue {
+    /// Tasks with real-time policies.
+    real_time_tasks: SpinLock<RealTimeRunQueue>,
+    /// Tasks with normal policies.
+    fair_tasks: SpinLock<FairRunQueue>,
+    /// The number of tasks in the run queue.
+    len: AtomicUsize,
+    /// The task that is known to be running on the CPU, or `None` if the CPU may be idle.
+    current: SpinLock<Option<Weak<Task>>>,
+}
+
+impl RunQueue {
+    fn new() -> Self {
+        Self {
+            real_time_tasks: SpinLock::new(RealTimeRunQueue::new()),
+            fair_tasks: SpinLock::new(FairRunQueue::new()),
+            len: AtomicUsize::new(0),
+            current: SpinLock::new(None),
+        }
+    }
+
+    fn current(&self) -> Option<Arc<Task>> {
+        self.current.lock_irq_disabled().as_ref()?.upgrade()
+    }
+
+    fn set_current(&self, task: Option<&Arc<Task>>) {
+        *self.current.lock_irq_disabled() = task.map(Arc::downgrade);
+    }
+
+    fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    fn has_real_time_tasks(&self) -> bool {
+        !self.real_time_tasks.lock_irq_disabled().is_empty()
+    }
+
+    fn push(&self, task: Arc<Task>) {
+        if policy_of(&task).is_real_time() {
+            self.real_time_tasks.lock_irq_disabled().push(task);
         } else {
-            self.normal_tasks.lock_irq_disabled().pop_front()
+            self.fair_tasks.lock_irq_disabled().push(task);
         }
+        self.len.fetch_add(1, Ordering::Relaxed);
     }
 
-    fn should_preempt(&self, task: &Arc<Task>) -> bool {
-        !task.is_real_time() && !self.real_time_tasks.lock_irq_disabled().is_empty()
+    fn pop(&self) -> Option<Arc<Task>> {
+        let task = self
+            .real_time_tasks
+            .lock_irq_disabled()
+            .pop()
+            .or_else(|| self.fair_tasks.lock_irq_disabled().pop())?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(task)
+    }
+
+    /// Removes the first task that is allowed to run on the given CPU.
+    fn steal(&self, cpu_id: u32) -> Option<Arc<Task>> {
+        let is_allowed = |task: &Task| is_allowed_on(task, cpu_id);
+        let task = self
+            .real_time_tasks
+            .lock_irq_disabled()
+            .pop_if(is_allowed)
+            .or_else(|| self.fair_tasks.lock_irq_disabled().pop_if(is_allowed))?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(task)
     }
 }
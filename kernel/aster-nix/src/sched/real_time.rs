@@ -0,0 +1,126 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The real-time scheduling class.
+//!
+//! A real-time task always runs before the tasks with lower real-time priorities
+//! and the normal tasks. Tasks with the same real-time priority run in FIFO order,
+//! and the ones with the `RoundRobin` policy are rotated when their time slices
+//! are used up.
+
+use aster_frame::task::{Task, TaskAdapter};
+use intrusive_collections::LinkedList;
+
+use super::{
+    policy::{RealTimePriority, SchedPolicy},
+    sched_entity::{policy_of, thread_of, TICK_NS},
+};
+use crate::prelude::*;
+
+/// The time slice of a task with the `RoundRobin` policy, in nanoseconds.
+pub const ROUND_ROBIN_TIME_SLICE_NS: u64 = 100_000_000;
+
+const NR_PRIORITIES: usize = RealTimePriority::MAX.to_raw() as usize + 1;
+
+/// The real-time run queue of a CPU.
+///
+/// The task that is running is not in the queue.
+pub(super) struct RealTimeRunQueue {
+    /// The FIFO lists of the tasks, indexed by the real-time priorities.
+    lists: [LinkedList<TaskAdapter>; NR_PRIORITIES],
+    /// The bitmap of the non-empty lists.
+    bitmap: u128,
+}
+
+impl RealTimeRunQueue {
+    pub(super) fn new() -> Self {
+        Self {
+            lists: core::array::from_fn(|_| LinkedList::new(TaskAdapter::new())),
+            bitmap: 0,
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.bitmap == 0
+    }
+
+    /// Returns the highest real-time priority of the tasks in the queue.
+    fn highest_priority(&self) -> Option<u8> {
+        if self.is_empty() {
+            return None;
+        }
+        Some((u128::BITS - 1 - self.bitmap.leading_zeros()) as u8)
+    }
+
+    pub(super) fn push(&mut self, task: Arc<Task>) {
+        let priority = priority_of(&task);
+        self.lists[priority as usize].push_back(task);
+        self.bitmap |= 1 << priority;
+    }
+
+    /// Removes the first task with the highest real-time priority.
+    pub(super) fn pop(&mut self) -> Option<Arc<Task>> {
+        let priority = self.highest_priority()?;
+        let list = &mut self.lists[priority as usize];
+        let task = list.pop_front();
+        if list.is_empty() {
+            self.bitmap &= !(1 << priority);
+        }
+        task
+    }
+
+    /// Removes the first task with the highest real-time priority among the tasks
+    /// that satisfy `f`.
+    pub(super) fn pop_if(&mut self, f: impl Fn(&Task) -> bool) -> Option<Arc<Task>> {
+        for priority in (0..NR_PRIORITIES).rev() {
+            if self.bitmap & (1 << priority) == 0 {
+                continue;
+            }
+            let list = &mut self.lists[priority];
+            let mut cursor = list.front_mut();
+            while let Some(task) = cursor.get() {
+                if f(task) {
+                    let task = cursor.remove();
+                    if list.is_empty() {
+                        self.bitmap &= !(1 << priority);
+                    }
+                    return task;
+                }
+                cursor.move_next();
+            }
+        }
+        None
+    }
+
+    /// Charges a timer tick to the given real-time task, which is running on the current CPU.
+    ///
+    /// Returns whether the task should give up the CPU, either because a task with a higher
+    /// priority is waiting, or because it has used up its round-robin time slice and a task
+    /// with the same priority is waiting.
+    pub(super) fn tick(&self, task: &Task) -> bool {
+        let policy = policy_of(task);
+        if let SchedPolicy::RoundRobin(_) = policy
+            && let Some(thread) = thread_of(task)
+        {
+            let entity = thread.sched_entity();
+            entity.charge_slice(TICK_NS);
+            if entity.slice_runtime() >= ROUND_ROBIN_TIME_SLICE_NS {
+                entity.reset_slice_runtime();
+                return self.highest_priority() >= Some(priority_of(task));
+            }
+        }
+        self.should_preempt(task)
+    }
+
+    /// Tells whether the given task, which is running on the current CPU, should be
+    /// preempted by a task with a higher real-time priority in the queue.
+    pub(super) fn should_preempt(&self, task: &Task) -> bool {
+        self.highest_priority() > Some(priority_of(task))
+    }
+}
+
+/// Returns the real-time priority of a task, or 0 if the task is not a real-time one.
+fn priority_of(task: &Task) -> u8 {
+    policy_of(task)
+        .real_time_priority()
+        .map_or(0, |priority| priority.to_raw())
+}
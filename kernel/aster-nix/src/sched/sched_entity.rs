@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use aster_frame::{cpu::CpuSet, task::Task, timer::TIMER_FREQ, trap};
+use atomic::Atomic;
+
+use super::{
+    nice::{Nice, NICE_0_WEIGHT},
+    policy::SchedPolicy,
+};
+use crate::{prelude::*, process::posix_thread::PosixThreadExt, thread::Thread};
+
+/// The time that a timer tick stands for, in nanoseconds.
+pub(super) const TICK_NS: u64 = 1_000_000_000 / TIMER_FREQ;
+
+/// The load weight of a thread with the `Idle` policy, which is lower than
+/// that of any nice value.
+const IDLE_POLICY_WEIGHT: u64 = 3;
+
+/// The scheduling state of a thread.
+pub struct SchedEntity {
+    /// The scheduling policy.
+    policy: SpinLock<SchedPolicy>,
+    /// The nice value, which is kept in sync with the nice value of the process.
+    nice: Atomic<Nice>,
+    /// The virtual runtime in the fair scheduling class, in nanoseconds.
+    vruntime: AtomicU64,
+    /// The CPU whose fair run queue the virtual runtime is relative to,
+    /// or `u32::MAX` if the thread has never been enqueued.
+    cpu: AtomicU32,
+    /// The real runtime in the current time slice, in nanoseconds.
+    slice_runtime: AtomicU64,
+    /// The total real runtime, i.e., the CPU time of the thread, in nanoseconds.
+    runtime: AtomicU64,
+}
+
+impl SchedEntity {
+    pub fn new(policy: SchedPolicy) -> Self {
+        Self {
+            policy: SpinLock::new(policy),
+            nice: Atomic::new(Nice::default()),
+            vruntime: AtomicU64::new(0),
+            cpu: AtomicU32::new(u32::MAX),
+            slice_runtime: AtomicU64::new(0),
+            runtime: AtomicU64::new(0),
+        }
+    }
+
+    pub fn policy(&self) -> SchedPolicy {
+        *self.policy.lock_irq_disabled()
+    }
+
+    /// Sets the scheduling policy.
+    ///
+    /// If the thread is already in a run queue, the new policy takes effect
+    /// the next time it is enqueued.
+    pub fn set_policy(&self, policy: SchedPolicy) {
+        *self.policy.lock_irq_disabled() = policy;
+        self.reset_slice_runtime();
+    }
+
+    pub fn nice(&self) -> Nice {
+        self.nice.load(Ordering::Relaxed)
+    }
+
+    /// Sets the nice value, which takes effect from the next timer tick.
+    pub fn set_nice(&self, nice: Nice) {
+        self.nice.store(nice, Ordering::Relaxed);
+    }
+
+    /// Returns the load weight in the fair scheduling class.
+    pub(super) fn weight(&self) -> u64 {
+        match self.policy() {
+            SchedPolicy::Idle => IDLE_POLICY_WEIGHT,
+            _ => self.nice().weight() as u64,
+        }
+    }
+
+    pub(super) fn vruntime(&self) -> u64 {
+        self.vruntime.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_vruntime(&self, vruntime: u64) {
+        self.vruntime.store(vruntime, Ordering::Relaxed);
+    }
+
+    /// Records that the virtual runtime is now relative to the run queue of `cpu_id`,
+    /// and returns the CPU that it was relative to.
+    pub(super) fn swap_cpu(&self, cpu_id: u32) -> u32 {
+        self.cpu.swap(cpu_id, Ordering::Relaxed)
+    }
+
+    pub(super) fn slice_runtime(&self) -> u64 {
+        self.slice_runtime.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn reset_slice_runtime(&self) {
+        self.slice_runtime.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the CPU time of the thread.
+    pub fn runtime(&self) -> Duration {
+        Duration::from_nanos(self.runtime.load(Ordering::Relaxed))
+    }
+
+    /// Charges the real runtime `delta` to the entity in the fair scheduling class.
+    pub(super) fn charge(&self, delta: u64) {
+        let delta_vruntime = delta * NICE_0_WEIGHT as u64 / self.weight();
+        self.vruntime.fetch_add(delta_vruntime, Ordering::Relaxed);
+        self.charge_slice(delta);
+    }
+
+    /// Charges the real runtime `delta` to the current time slice only.
+    pub(super) fn charge_slice(&self, delta: u64) {
+        self.slice_runtime.fetch_add(delta, Ordering::Relaxed);
+    }
+}
+
+impl Default for SchedEntity {
+    fn default() -> Self {
+        Self::new(SchedPolicy::default())
+    }
+}
+
+/// Returns the thread of a task, if the task is created for a thread.
+pub(super) fn thread_of(task: &Task) -> Option<Arc<Thread>> {
+    task.data().downcast_ref::<Weak<Thread>>()?.upgrade()
+}
+
+/// Charges a timer tick to the CPU time of the thread of a task, which may expire
+/// the CPU-time timers of its process.
+///
+/// The tick is also charged to the user CPU time if it interrupts the user mode.
+pub(super) fn charge_cpu_time(task: &Task) {
+    let Some(thread) = thread_of(task) else {
+        return;
+    };
+    let entity = thread.sched_entity();
+    entity.runtime.fetch_add(TICK_NS, Ordering::Relaxed);
+    if let Some(posix_thread) = thread.as_posix_thread() {
+        posix_thread.charge_cpu_time(
+            thread.tid(),
+            entity.runtime(),
+            Duration::from_nanos(TICK_NS),
+            trap::interrupted_user_mode(),
+        );
+    }
+}
+
+/// Calls `f` with the set of CPUs that a task is allowed to run on.
+///
+/// The CPU affinity of a POSIX thread can be changed, while other tasks keep
+/// the CPU affinity that they are created with.
+pub(super) fn with_cpu_affinity<R>(task: &Task, f: impl FnOnce(&CpuSet) -> R) -> R {
+    if let Some(thread) = thread_of(task)
+        && let Some(posix_thread) = thread.as_posix_thread()
+    {
+        return f(&posix_thread.cpu_affinity().lock_irq_disabled());
+    }
+    f(task.cpu_affinity())
+}
+
+/// Tells whether a task is allowed to run on the given CPU.
+pub(super) fn is_allowed_on(task: &Task, cpu_id: u32) -> bool {
+    with_cpu_affinity(task, |cpu_affinity| cpu_affinity.contains(cpu_id))
+}
+
+/// Returns the scheduling policy of a task.
+///
+/// A task that is not created for a thread keeps the priority that it is created with.
+pub(super) fn policy_of(task: &Task) -> SchedPolicy {
+    match thread_of(task) {
+        Some(thread) => thread.sched_entity().policy(),
+        None => SchedPolicy::from(task.priority()),
+    }
+}
@@ -3,27 +3,23 @@
 use core::time::Duration;
 
 use super::{SyscallReturn, SYS_ALARM};
-use crate::{log_syscall_entry, prelude::*, process::posix_thread::PosixThreadExt};
+use crate::{log_syscall_entry, prelude::*, process::ITimerKind};
 
 pub fn sys_alarm(seconds: u32) -> Result<SyscallReturn> {
     log_syscall_entry!(SYS_ALARM);
     debug!("seconds = {}", seconds);
 
-    let current_thread = current_thread!();
-    let mut real_timer = {
-        let posix_thread = current_thread.as_posix_thread().unwrap();
-        posix_thread.real_timer().lock()
-    };
-
-    let remaining_secs = real_timer.remain().as_secs();
-
-    if seconds == 0 {
-        // Clear previous timer
-        real_timer.clear();
-        return Ok(SyscallReturn::Return(remaining_secs as _));
+    // The alarm shares the interval timer of `ITIMER_REAL`.
+    let current = current!();
+    let real_timer = current.timers().itimer(ITimerKind::Real, &current)?;
+    let (remain, _) =
+        real_timer.set_time(Duration::from_secs(seconds as u64), Duration::ZERO, false)?;
+
+    // Like Linux, the remaining seconds are rounded to the nearest, and a pending
+    // alarm never reports zero.
+    let mut remaining_secs = remain.as_secs();
+    if remain.subsec_micros() >= 500_000 || (remaining_secs == 0 && !remain.is_zero()) {
+        remaining_secs += 1;
     }
-
-    real_timer.set(Duration::from_secs(seconds as u64))?;
-
     Ok(SyscallReturn::Return(remaining_secs as _))
 }
@@ -0,0 +1,50 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_EVENTFD, SYS_EVENTFD2};
+use crate::{
+    fs::{eventfd::EventFile, file_table::FdFlags},
+    log_syscall_entry,
+    prelude::*,
+};
+
+pub fn sys_eventfd(init_val: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_EVENTFD);
+    debug!("init_val = {}", init_val);
+    do_eventfd(init_val, EventFdFlags::empty())
+}
+
+pub fn sys_eventfd2(init_val: u32, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_EVENTFD2);
+    let flags = EventFdFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!("init_val = {}, flags = {:?}", init_val, flags);
+    do_eventfd(init_val, flags)
+}
+
+fn do_eventfd(init_val: u32, flags: EventFdFlags) -> Result<SyscallReturn> {
+    let event_file = EventFile::new(
+        init_val as u64,
+        flags.contains(EventFdFlags::EFD_SEMAPHORE),
+        flags.contains(EventFdFlags::EFD_NONBLOCK),
+    );
+    let fd_flags = if flags.contains(EventFdFlags::EFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let current = current!();
+    let fd = current
+        .file_table()
+        .lock()
+        .insert(Arc::new(event_file), fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+bitflags! {
+    struct EventFdFlags: u32 {
+        const EFD_SEMAPHORE = 1;
+        const EFD_NONBLOCK = 1 << 11;
+        const EFD_CLOEXEC = 1 << 19;
+    }
+}
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::sync::atomic::Ordering;
+
 use aster_frame::cpu::UserContext;
 use aster_rights::WriteOp;
 
@@ -8,14 +10,15 @@ use crate::{
     fs::{
         file_table::FileDescripter,
         fs_resolver::{FsPath, AT_FDCWD},
-        utils::{Dentry, InodeType},
+        utils::{Dentry, InodeType, MountFlags},
     },
     log_syscall_entry,
     prelude::*,
     process::{
         check_executable_file, credentials_mut, load_program_to_vm,
         posix_thread::{PosixThreadExt, ThreadName},
-        Credentials, MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN,
+        ptrace::ptrace_exec_event,
+        Credentials, PersonalityFlags, MAX_ARGV_NUMBER, MAX_ARG_LEN, MAX_ENVP_NUMBER, MAX_ENV_LEN,
     },
     syscall::{SYS_EXECVE, SYS_EXECVEAT},
     util::{read_cstring_from_user, read_val_from_user},
@@ -106,17 +109,39 @@ fn do_execve(
 
     let current = current!();
 
+    // The POSIX timers are not preserved, while the interval timers are.
+    current.timers().delete_posix_timers();
+
     // Ensure that the file descriptors with the close-on-exec flag are closed.
     let closed_files = current.file_table().lock().close_files_on_exec();
     for file in closed_files {
         file.clean_for_close()?;
     }
 
+    // Like Linux, the personality flags that weaken the security are cleared for
+    // set-user-ID and set-group-ID programs.
+    let elf_mode = elf_file.mode()?;
+    if (elf_mode.has_set_uid() || elf_mode.has_set_gid()) && !is_on_nosuid_mount(&elf_file) {
+        current
+            .personality()
+            .fetch_and(!PersonalityFlags::CLEAR_ON_SETID.bits(), Ordering::Relaxed);
+    }
+    let personality =
+        PersonalityFlags::from_bits_truncate(current.personality().load(Ordering::Relaxed));
+
     debug!("load program to root vmar");
     let (new_executable_path, elf_load_info) = {
         let fs_resolver = &*current.fs().read();
         let process_vm = current.vm();
-        load_program_to_vm(process_vm, elf_file.clone(), argv, envp, fs_resolver, 1)?
+        load_program_to_vm(
+            process_vm,
+            elf_file.clone(),
+            argv,
+            envp,
+            fs_resolver,
+            personality,
+            1,
+        )?
     };
     debug!("load elf in execve succeeds");
 
@@ -139,6 +164,7 @@ fn do_execve(
     // set new user stack top
     context.set_rsp(elf_load_info.user_stack_top() as _);
     debug!("user stack top: 0x{:x}", elf_load_info.user_stack_top());
+    ptrace_exec_event();
     Ok(())
 }
 
@@ -181,7 +207,7 @@ fn read_cstring_vec(
 
 /// Sets uid for credentials as the same of uid of elf file if elf file has `set_uid` bit.
 fn set_uid_from_elf(credentials: &Credentials<WriteOp>, elf_file: &Arc<Dentry>) -> Result<()> {
-    if elf_file.mode()?.has_set_uid() {
+    if elf_file.mode()?.has_set_uid() && !is_on_nosuid_mount(elf_file) {
         let uid = elf_file.owner()?;
         credentials.set_euid(uid);
     }
@@ -193,7 +219,7 @@ fn set_uid_from_elf(credentials: &Credentials<WriteOp>, elf_file: &Arc<Dentry>)
 
 /// Sets gid for credentials as the same of gid of elf file if elf file has `set_gid` bit.
 fn set_gid_from_elf(credentials: &Credentials<WriteOp>, elf_file: &Arc<Dentry>) -> Result<()> {
-    if elf_file.mode()?.has_set_gid() {
+    if elf_file.mode()?.has_set_gid() && !is_on_nosuid_mount(elf_file) {
         let gid = elf_file.group()?;
         credentials.set_egid(gid);
     }
@@ -202,3 +228,9 @@ fn set_gid_from_elf(credentials: &Credentials<WriteOp>, elf_file: &Arc<Dentry>)
     credentials.reset_sgid();
     Ok(())
 }
+
+/// Returns whether the set-user-ID and set-group-ID bits of the elf file are ignored,
+/// because it is on a mount with the `nosuid` flag.
+fn is_on_nosuid_mount(elf_file: &Dentry) -> bool {
+    elf_file.mount_node().flags().contains(MountFlags::NOSUID)
+}
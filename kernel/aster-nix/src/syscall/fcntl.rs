@@ -3,11 +3,14 @@
 use super::{SyscallReturn, SYS_FCNTL};
 use crate::{
     fs::{
+        file_handle::FileLike,
         file_table::{FdFlags, FileDescripter},
-        utils::StatusFlags,
+        inode_handle::InodeHandle,
+        utils::{FileSeals, Inode, StatusFlags},
     },
     log_syscall_entry,
     prelude::*,
+    process::process_table,
 };
 
 pub fn sys_fcntl(fd: FileDescripter, cmd: i32, arg: u64) -> Result<SyscallReturn> {
@@ -82,9 +85,66 @@ pub fn sys_fcntl(fd: FileDescripter, cmd: i32, arg: u64) -> Result<SyscallReturn
             file.set_status_flags(new_status_flags)?;
             Ok(SyscallReturn::Return(0))
         }
+        FcntlCmd::F_ADD_SEALS => {
+            let seals = u32::try_from(arg)
+                .ok()
+                .and_then(FileSeals::from_bits)
+                .ok_or(Error::with_message(Errno::EINVAL, "invalid seals"))?;
+            let current = current!();
+            let file = {
+                let file_table = current.file_table().lock();
+                file_table.get_file(fd)?.clone()
+            };
+            if !file.access_mode().is_writable() {
+                return_errno_with_message!(Errno::EPERM, "the file is not opened for writing");
+            }
+            let inode = sealable_inode(file.as_ref())?;
+            inode.add_seals(seals, &|old_seals| {
+                if seals.contains(FileSeals::F_SEAL_WRITE)
+                    && !old_seals.contains(FileSeals::F_SEAL_WRITE)
+                    && is_mapped_writable(inode)
+                {
+                    return_errno_with_message!(Errno::EBUSY, "the file is mapped as writable");
+                }
+                Ok(())
+            })?;
+            Ok(SyscallReturn::Return(0))
+        }
+        FcntlCmd::F_GET_SEALS => {
+            let current = current!();
+            let file = {
+                let file_table = current.file_table().lock();
+                file_table.get_file(fd)?.clone()
+            };
+            let seals = sealable_inode(file.as_ref())?.seals()?;
+            Ok(SyscallReturn::Return(seals.bits() as _))
+        }
     }
 }
 
+/// Returns the inode of the file for the sealing operations.
+fn sealable_inode(file: &dyn FileLike) -> Result<&Arc<dyn Inode>> {
+    let inode_handle = file
+        .downcast_ref::<InodeHandle>()
+        .ok_or(Error::with_message(
+            Errno::EINVAL,
+            "the file does not support sealing",
+        ))?;
+    Ok(inode_handle.dentry().inode())
+}
+
+/// Returns whether the page cache of the inode is mapped as shared and writable
+/// by any process.
+fn is_mapped_writable(inode: &Arc<dyn Inode>) -> bool {
+    let Some(page_cache) = inode.page_cache() else {
+        return false;
+    };
+    let processes: Vec<_> = process_table::process_table().iter().cloned().collect();
+    processes
+        .iter()
+        .any(|process| process.root_vmar().is_vmo_mapped_writable(&page_cache))
+}
+
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[allow(non_camel_case_types)]
@@ -95,4 +155,6 @@ enum FcntlCmd {
     F_GETFL = 3,
     F_SETFL = 4,
     F_DUPFD_CLOEXEC = 1030,
+    F_ADD_SEALS = 1033,
+    F_GET_SEALS = 1034,
 }
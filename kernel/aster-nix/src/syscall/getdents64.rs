@@ -11,7 +11,7 @@ use crate::{
     },
     log_syscall_entry,
     prelude::*,
-    util::write_bytes_to_user,
+    util::{alloc_user_buffer, write_bytes_to_user},
 };
 
 pub fn sys_getdents64(
@@ -36,7 +36,7 @@ pub fn sys_getdents64(
     if inode_handle.dentry().type_() != InodeType::Dir {
         return_errno!(Errno::ENOTDIR);
     }
-    let mut buffer = vec![0u8; buf_len];
+    let mut buffer = alloc_user_buffer(buf_len)?;
     let mut reader = DirentBufferReader::<Dirent64>::new(&mut buffer);
     let _ = inode_handle.readdir(&mut reader)?;
     let read_len = reader.read_len();
@@ -2,7 +2,10 @@
 
 use super::SyscallReturn;
 use crate::{
-    device, log_syscall_entry, prelude::*, syscall::SYS_GETRANDOM, util::write_bytes_to_user,
+    device, log_syscall_entry,
+    prelude::*,
+    syscall::SYS_GETRANDOM,
+    util::{alloc_user_buffer, write_bytes_to_user},
 };
 
 pub fn sys_getrandom(buf: Vaddr, count: usize, flags: u32) -> Result<SyscallReturn> {
@@ -14,7 +17,7 @@ pub fn sys_getrandom(buf: Vaddr, count: usize, flags: u32) -> Result<SyscallRetu
     );
     // TODO: support nonblock flag.
     // Currently our getrandom implementation relies on x86-specific `rdrand` instruction, so it will never block.
-    let mut buffer = vec![0u8; count];
+    let mut buffer = alloc_user_buffer(count)?;
     let read_len = if flags.contains(GetRandomFlags::GRND_RANDOM) {
         device::Random::getrandom(&mut buffer)?
     } else {
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    SyscallReturn, SYS_INOTIFY_ADD_WATCH, SYS_INOTIFY_INIT, SYS_INOTIFY_INIT1, SYS_INOTIFY_RM_WATCH,
+};
+use crate::{
+    fs::{
+        file_table::{FdFlags, FileDescripter},
+        fs_resolver::{FsPath, AT_FDCWD},
+        inotify::{InotifyFile, InotifyFlags, WatchDescriptor},
+        utils::{FsEvents, InodeType, PATH_MAX},
+    },
+    log_syscall_entry,
+    prelude::*,
+    util::read_cstring_from_user,
+};
+
+pub fn sys_inotify_init() -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_INOTIFY_INIT);
+    do_inotify_init(InotifyInitFlags::empty())
+}
+
+pub fn sys_inotify_init1(flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_INOTIFY_INIT1);
+    let flags = InotifyInitFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!("flags = {:?}", flags);
+    do_inotify_init(flags)
+}
+
+fn do_inotify_init(flags: InotifyInitFlags) -> Result<SyscallReturn> {
+    let inotify_file = InotifyFile::new(flags.contains(InotifyInitFlags::IN_NONBLOCK));
+    let fd_flags = if flags.contains(InotifyInitFlags::IN_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let current = current!();
+    let fd = current.file_table().lock().insert(inotify_file, fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_inotify_add_watch(
+    fd: FileDescripter,
+    path_ptr: Vaddr,
+    mask: u32,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_INOTIFY_ADD_WATCH);
+    let path = read_cstring_from_user(path_ptr, PATH_MAX)?;
+    debug!("fd = {}, path = {:?}, mask = 0x{:x}", fd, path, mask);
+
+    let events = FsEvents::from_bits_truncate(mask);
+    let flags = InotifyFlags::from_bits_truncate(mask);
+    if flags.contains(InotifyFlags::IN_MASK_ADD | InotifyFlags::IN_MASK_CREATE) {
+        return_errno_with_message!(
+            Errno::EINVAL,
+            "IN_MASK_ADD and IN_MASK_CREATE cannot be both specified"
+        );
+    }
+
+    let current = current!();
+    let file = current.file_table().lock().get_file(fd)?.clone();
+    let inotify_file = file
+        .downcast_ref::<InotifyFile>()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the file is not an inotify file"))?;
+
+    let dentry = {
+        let path = path.to_string_lossy();
+        let fs_path = FsPath::new(AT_FDCWD, path.as_ref())?;
+        let fs = current.fs().read();
+        if flags.contains(InotifyFlags::IN_DONT_FOLLOW) {
+            fs.lookup_no_follow(&fs_path)?
+        } else {
+            fs.lookup(&fs_path)?
+        }
+    };
+    if flags.contains(InotifyFlags::IN_ONLYDIR) && dentry.type_() != InodeType::Dir {
+        return_errno_with_message!(Errno::ENOTDIR, "the path is not a directory");
+    }
+    if !dentry.mode()?.is_readable() {
+        return_errno_with_message!(Errno::EACCES, "the path is not readable");
+    }
+
+    let wd = inotify_file.add_watch(dentry, events, flags)?;
+    Ok(SyscallReturn::Return(wd as _))
+}
+
+pub fn sys_inotify_rm_watch(fd: FileDescripter, wd: WatchDescriptor) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_INOTIFY_RM_WATCH);
+    debug!("fd = {}, wd = {}", fd, wd);
+
+    let current = current!();
+    let file = current.file_table().lock().get_file(fd)?.clone();
+    let inotify_file = file
+        .downcast_ref::<InotifyFile>()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the file is not an inotify file"))?;
+    inotify_file.remove_watch(wd)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct InotifyInitFlags: u32 {
+        const IN_NONBLOCK = 1 << 11;
+        const IN_CLOEXEC = 1 << 19;
+    }
+}
@@ -1,7 +1,9 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use align_ext::AlignExt;
+
 use super::{SyscallReturn, SYS_MADVISE};
-use crate::{log_syscall_entry, prelude::*, util::read_bytes_from_user};
+use crate::{log_syscall_entry, prelude::*, vm::vmar::vm_mapping::MappingFlags};
 
 pub fn sys_madvise(start: Vaddr, len: usize, behavior: i32) -> Result<SyscallReturn> {
     log_syscall_entry!(SYS_MADVISE);
@@ -10,31 +12,81 @@ pub fn sys_madvise(start: Vaddr, len: usize, behavior: i32) -> Result<SyscallRet
         "start = 0x{:x}, len = 0x{:x}, behavior = {:?}",
         start, len, behavior
     );
+
+    if start % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the start address should be page aligned");
+    }
+    if len > isize::MAX as usize {
+        return_errno_with_message!(Errno::EINVAL, "the length is too large");
+    }
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+    let end = start
+        .checked_add(len.align_up(PAGE_SIZE))
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the range overflows"))?;
+    let range = start..end;
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
     match behavior {
+        // The access patterns are only hints, which do not change the semantics.
         MadviseBehavior::MADV_NORMAL
-        | MadviseBehavior::MADV_SEQUENTIAL
-        | MadviseBehavior::MADV_WILLNEED => {
-            // perform a read at first
-            let mut buffer = vec![0u8; len];
-            read_bytes_from_user(start, &mut buffer)?;
+        | MadviseBehavior::MADV_RANDOM
+        | MadviseBehavior::MADV_SEQUENTIAL => {}
+        MadviseBehavior::MADV_WILLNEED => root_vmar.readahead(range)?,
+        MadviseBehavior::MADV_DONTNEED | MadviseBehavior::MADV_FREE
+            if root_vmar.locked_size(&range) > 0 =>
+        {
+            return_errno_with_message!(Errno::EINVAL, "the pages are locked");
+        }
+        MadviseBehavior::MADV_DONTNEED | MadviseBehavior::MADV_DONTNEED_LOCKED => {
+            root_vmar.discard(range)?
+        }
+        MadviseBehavior::MADV_FREE => root_vmar.free(range)?,
+        MadviseBehavior::MADV_REMOVE => root_vmar.remove(range)?,
+        MadviseBehavior::MADV_DONTFORK => {
+            root_vmar.update_mapping_flags(range, MappingFlags::DONTFORK, MappingFlags::empty())?
+        }
+        MadviseBehavior::MADV_DOFORK => {
+            root_vmar.update_mapping_flags(range, MappingFlags::empty(), MappingFlags::DONTFORK)?
+        }
+        MadviseBehavior::MADV_WIPEONFORK => root_vmar.update_mapping_flags(
+            range,
+            MappingFlags::WIPEONFORK,
+            MappingFlags::empty(),
+        )?,
+        MadviseBehavior::MADV_KEEPONFORK => root_vmar.update_mapping_flags(
+            range,
+            MappingFlags::empty(),
+            MappingFlags::WIPEONFORK,
+        )?,
+        MadviseBehavior::MADV_HUGEPAGE => root_vmar.update_mapping_flags(
+            range,
+            MappingFlags::HUGEPAGE,
+            MappingFlags::NOHUGEPAGE,
+        )?,
+        MadviseBehavior::MADV_NOHUGEPAGE => root_vmar.update_mapping_flags(
+            range,
+            MappingFlags::NOHUGEPAGE,
+            MappingFlags::HUGEPAGE,
+        )?,
+        MadviseBehavior::MADV_COLD => root_vmar.deactivate(range)?,
+        MadviseBehavior::MADV_PAGEOUT => root_vmar.page_out(range)?,
+        MadviseBehavior::MADV_POPULATE_READ => root_vmar.populate(range, false)?,
+        MadviseBehavior::MADV_POPULATE_WRITE => root_vmar.populate(range, true)?,
+        // There are no core dumps yet.
+        MadviseBehavior::MADV_DONTDUMP | MadviseBehavior::MADV_DODUMP => {}
+        MadviseBehavior::MADV_MERGEABLE
+        | MadviseBehavior::MADV_UNMERGEABLE
+        | MadviseBehavior::MADV_HWPOISON
+        | MadviseBehavior::MADV_SOFT_OFFLINE => {
+            return_errno_with_message!(Errno::EINVAL, "the advice is not supported")
         }
-        MadviseBehavior::MADV_DONTNEED => madv_dontneed(start, len)?,
-        _ => todo!(),
     }
     Ok(SyscallReturn::Return(0))
 }
 
-fn madv_dontneed(start: Vaddr, len: usize) -> Result<()> {
-    debug_assert!(start % PAGE_SIZE == 0);
-    debug_assert!(len % PAGE_SIZE == 0);
-    let current = current!();
-    let root_vmar = current.root_vmar();
-    let advised_range = start..start + len;
-    // `destroy()` interface may require adjustment and replacement afterwards.
-    let _ = root_vmar.destroy(advised_range);
-    Ok(())
-}
-
 #[repr(i32)]
 #[derive(Debug, Clone, Copy, TryFromInt)]
 #[allow(non_camel_case_types)]
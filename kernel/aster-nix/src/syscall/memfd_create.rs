@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{constants::MAX_FILENAME_LEN, SyscallReturn, SYS_MEMFD_CREATE};
+use crate::{
+    fs::{file_table::FdFlags, ramfs::create_memfd},
+    log_syscall_entry,
+    prelude::*,
+    util::read_cstring_from_user,
+};
+
+/// The maximum length of the name of a memfd, excluding the prefix `memfd:`.
+const MFD_NAME_MAX_LEN: usize = 249;
+
+pub fn sys_memfd_create(name_addr: Vaddr, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MEMFD_CREATE);
+    let flags = MemfdFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown memfd flags"))?;
+    let name = read_cstring_from_user(name_addr, MAX_FILENAME_LEN)?;
+    debug!("name = {:?}, flags = {:?}", name, flags);
+
+    if flags.contains(MemfdFlags::MFD_HUGETLB) {
+        return_errno_with_message!(Errno::EINVAL, "huge pages are not supported by memfd");
+    }
+    if name.as_bytes().len() > MFD_NAME_MAX_LEN {
+        return_errno_with_message!(Errno::EINVAL, "the name is too long");
+    }
+
+    let memfd = create_memfd(
+        &name.to_string_lossy(),
+        flags.contains(MemfdFlags::MFD_ALLOW_SEALING),
+    )?;
+    let fd_flags = if flags.contains(MemfdFlags::MFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let current = current!();
+    let fd = current
+        .file_table()
+        .lock()
+        .insert(Arc::new(memfd), fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+bitflags! {
+    struct MemfdFlags: u32 {
+        const MFD_CLOEXEC       = 0x0001;
+        const MFD_ALLOW_SEALING = 0x0002;
+        const MFD_HUGETLB       = 0x0004;
+    }
+}
@@ -0,0 +1,36 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::{SyscallReturn, SYS_MINCORE};
+use crate::{log_syscall_entry, prelude::*, util::write_bytes_to_user};
+
+pub fn sys_mincore(addr: Vaddr, len: usize, vec_ptr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MINCORE);
+    debug!(
+        "addr = 0x{:x}, len = 0x{:x}, vec_ptr = 0x{:x}",
+        addr, len, vec_ptr
+    );
+
+    if addr % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the address is not page-aligned");
+    }
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+    let end = (len <= isize::MAX as usize)
+        .then(|| addr.checked_add(len.align_up(PAGE_SIZE)))
+        .flatten()
+        .ok_or_else(|| Error::with_message(Errno::ENOMEM, "the range overflows"))?;
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    let resident_pages = root_vmar.resident_pages(addr..end)?;
+    // The least significant bit of each byte is set if the page is resident.
+    let vec: Vec<u8> = resident_pages
+        .into_iter()
+        .map(|is_resident| is_resident as u8)
+        .collect();
+    write_bytes_to_user(vec_ptr, &vec)?;
+    Ok(SyscallReturn::Return(0))
+}
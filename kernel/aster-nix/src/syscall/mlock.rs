@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::ops::Range;
+
+use align_ext::AlignExt;
+
+use super::{SyscallReturn, SYS_MLOCK, SYS_MLOCK2, SYS_MLOCKALL, SYS_MUNLOCK, SYS_MUNLOCKALL};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    vm::vmar::{check_memlock_limit, process_locked_size, vm_mapping::MappingFlags},
+};
+
+pub fn sys_mlock(start: Vaddr, len: usize) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MLOCK);
+    debug!("start = 0x{:x}, len = 0x{:x}", start, len);
+
+    do_mlock(start, len, false)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_mlock2(start: Vaddr, len: usize, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MLOCK2);
+    let flags = Mlock2Flags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown mlock2 flags"))?;
+    debug!(
+        "start = 0x{:x}, len = 0x{:x}, flags = {:?}",
+        start, len, flags
+    );
+
+    do_mlock(start, len, flags.contains(Mlock2Flags::MLOCK_ONFAULT))?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_munlock(start: Vaddr, len: usize) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MUNLOCK);
+    debug!("start = 0x{:x}, len = 0x{:x}", start, len);
+
+    let Some(range) = lock_range(start, len)? else {
+        return Ok(SyscallReturn::Return(0));
+    };
+    current!().root_vmar().munlock(range)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_mlockall(flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MLOCKALL);
+    let flags = MlockallFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown mlockall flags"))?;
+    debug!("flags = {:?}", flags);
+
+    if !flags.intersects(MlockallFlags::MCL_CURRENT | MlockallFlags::MCL_FUTURE) {
+        return_errno_with_message!(Errno::EINVAL, "MCL_CURRENT or MCL_FUTURE is required");
+    }
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    let on_fault = flags.contains(MlockallFlags::MCL_ONFAULT);
+    if flags.contains(MlockallFlags::MCL_CURRENT) {
+        // All the mappings are locked, including the ones that are already locked.
+        let nr_bytes = root_vmar.mapped_size() - process_locked_size(&current);
+        check_memlock_limit(&current, nr_bytes)?;
+    } else {
+        check_memlock_limit(&current, 0)?;
+    }
+
+    let default_flags = match (flags.contains(MlockallFlags::MCL_FUTURE), on_fault) {
+        (true, true) => MappingFlags::LOCKED | MappingFlags::LOCKONFAULT,
+        (true, false) => MappingFlags::LOCKED,
+        (false, _) => MappingFlags::empty(),
+    };
+    root_vmar.set_default_mapping_flags(default_flags);
+    if flags.contains(MlockallFlags::MCL_CURRENT) {
+        root_vmar.mlock_all(on_fault)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_munlockall() -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MUNLOCKALL);
+
+    current!().root_vmar().munlock_all()?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn do_mlock(start: Vaddr, len: usize, on_fault: bool) -> Result<()> {
+    let Some(range) = lock_range(start, len)? else {
+        return Ok(());
+    };
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    let nr_bytes = range.len() - root_vmar.locked_size(&range);
+    check_memlock_limit(&current, nr_bytes)?;
+    root_vmar.mlock(range, on_fault)
+}
+
+/// Returns the page-aligned range of `mlock` or `munlock`, or `None` if it is empty.
+///
+/// Like Linux, the start address is rounded down and the end address is rounded up.
+fn lock_range(start: Vaddr, len: usize) -> Result<Option<Range<Vaddr>>> {
+    if len == 0 {
+        return Ok(None);
+    }
+    let end = start
+        .checked_add(len)
+        .filter(|end| *end <= isize::MAX as usize)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the range overflows"))?;
+    Ok(Some(start.align_down(PAGE_SIZE)..end.align_up(PAGE_SIZE)))
+}
+
+bitflags! {
+    struct Mlock2Flags: u32 {
+        const MLOCK_ONFAULT = 0x01;
+    }
+}
+
+bitflags! {
+    struct MlockallFlags: u32 {
+        const MCL_CURRENT = 1;
+        const MCL_FUTURE  = 2;
+        const MCL_ONFAULT = 4;
+    }
+}
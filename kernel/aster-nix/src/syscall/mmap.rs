@@ -3,18 +3,22 @@
 //! This mod defines mmap flags and the handler to syscall mmap
 
 use align_ext::AlignExt;
-use aster_frame::vm::VmPerm;
+use aster_frame::vm::{PageSize, VmPerm};
 use aster_rights::Rights;
 
 use super::SyscallReturn;
 use crate::{
-    fs::file_table::FileDescripter,
+    fs::{
+        file_table::FileDescripter,
+        utils::{FileSeals, MountFlags},
+    },
     log_syscall_entry,
     prelude::*,
     syscall::SYS_MMAP,
     vm::{
         perms::VmPerms,
-        vmo::{Vmo, VmoChildOptions, VmoOptions, VmoRightsOp},
+        vmar::{check_memlock_limit, vm_mapping::MappingFlags},
+        vmo::{Vmo, VmoChildOptions, VmoFlags, VmoOptions, VmoRightsOp},
     },
 };
 
@@ -53,57 +57,142 @@ fn do_sys_mmap(
         addr, len, vm_perm, option, fd, offset
     );
 
-    let len = len.align_up(PAGE_SIZE);
+    let len = match option.huge_page_size {
+        Some(page_size) => len.align_up(page_size.nbytes()),
+        None => len.align_up(PAGE_SIZE),
+    };
 
     if offset % PAGE_SIZE != 0 {
         return_errno_with_message!(Errno::EINVAL, "mmap only support page-aligned offset");
     }
     let perms = VmPerms::from(vm_perm);
 
-    let vmo = if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
+    let (vmo, vmo_offset) = if option.flags.contains(MMapFlags::MAP_ANONYMOUS) {
         if offset != 0 {
             return_errno_with_message!(Errno::EINVAL, "offset must be zero for anonymous mapping");
         }
-        alloc_anonyous_vmo(len)?
+        (alloc_anonyous_vmo(len)?, 0)
     } else {
-        alloc_filebacked_vmo(fd, len, offset, &option)?
+        if option.huge_page_size.is_some() {
+            return_errno_with_message!(Errno::EINVAL, "huge pages are not supported by the file");
+        }
+        alloc_filebacked_vmo(fd, len, offset, perms, &option)?
     };
 
     let current = current!();
     let root_vmar = current.root_vmar();
+    // The mapping locked by `mlockall(MCL_FUTURE)` is checked when it is created.
+    let is_locked = option.flags.contains(MMapFlags::MAP_LOCKED)
+        && !root_vmar
+            .default_mapping_flags()
+            .contains(MappingFlags::LOCKED);
+    if is_locked {
+        check_memlock_limit(&current, len).map_err(|err| match err.error() {
+            Errno::ENOMEM => {
+                Error::with_message(Errno::EAGAIN, "the locked memory exceeds RLIMIT_MEMLOCK")
+            }
+            _ => err,
+        })?;
+    }
     let vm_map_options = {
-        let mut options = root_vmar.new_map(vmo.to_dyn(), perms)?;
+        let is_shared = matches!(option.typ(), MMapType::Shared | MMapType::SharedValidate);
+        let mut options = root_vmar
+            .new_map(vmo.to_dyn(), perms)?
+            .vmo_offset(vmo_offset)
+            .size(len)
+            .is_shared(is_shared);
         let flags = option.flags;
+        if let Some(page_size) = option.huge_page_size {
+            options = options.align(page_size.nbytes()).page_size(page_size);
+        } else if flags.contains(MMapFlags::MAP_ANONYMOUS)
+            && !flags.contains(MMapFlags::MAP_FIXED)
+            && len >= PageSize::Size2M.nbytes()
+        {
+            // Large anonymous mappings are aligned so that they can be backed by
+            // transparent huge pages.
+            options = options.align(PageSize::Size2M.nbytes());
+        }
         if flags.contains(MMapFlags::MAP_FIXED) {
             options = options.offset(addr).can_overwrite(true);
         } else if flags.contains(MMapFlags::MAP_32BIT) {
             // TODO: support MAP_32BIT. MAP_32BIT requires the map range to be below 2GB
             warn!("MAP_32BIT is not supported");
+        } else if addr != 0 {
+            // The address is a hint, which also allows the mapping to be placed
+            // beyond the 47-bit user space with 5-level paging.
+            options = options.hint(addr);
         }
         options
     };
     let map_addr = vm_map_options.build()?;
     trace!("map range = 0x{:x} - 0x{:x}", map_addr, map_addr + len);
 
+    let map_range = map_addr..map_addr + len;
+    if option.flags.contains(MMapFlags::MAP_LOCKED) {
+        root_vmar.update_mapping_flags(
+            map_range.clone(),
+            MappingFlags::LOCKED,
+            MappingFlags::empty(),
+        )?;
+    }
+    // The pages locked by `MAP_LOCKED` are faulted in at once unless they are locked
+    // on faults. The ones locked by `mlockall(MCL_FUTURE)` have been faulted in.
+    let mapping_flags = root_vmar.get_vm_mapping(map_addr)?.flags();
+    let is_populated = (is_locked && !mapping_flags.contains(MappingFlags::LOCKONFAULT))
+        || (option.flags.contains(MMapFlags::MAP_POPULATE)
+            && !option.flags.contains(MMapFlags::MAP_NONBLOCK));
+    if is_populated {
+        // Like Linux, the failures to fault in the pages are ignored, since the
+        // mapping has been created.
+        let _ = root_vmar.prefault(map_range);
+    }
+
     Ok(map_addr)
 }
 
 fn alloc_anonyous_vmo(len: usize) -> Result<Vmo> {
-    let vmo_options: VmoOptions<Rights> = VmoOptions::new(len);
+    // The VMO is resizable so that the mapping can grow with `mremap`.
+    let vmo_options: VmoOptions<Rights> = VmoOptions::new(len).flags(VmoFlags::RESIZABLE);
     vmo_options.alloc()
 }
 
+/// Allocates the VMO for a file-backed mapping, returning the VMO and the offset
+/// of the mapping in the VMO.
 fn alloc_filebacked_vmo(
     fd: FileDescripter,
     len: usize,
     offset: usize,
+    perms: VmPerms,
     option: &MMapOptions,
-) -> Result<Vmo> {
+) -> Result<(Vmo, usize)> {
     let current = current!();
+    let is_file_writable = {
+        let file_table = current.file_table().lock();
+        file_table.get_file(fd)?.access_mode().is_writable()
+    };
+    let mut may_write = is_file_writable;
     let page_cache_vmo = {
         let fs_resolver = current.fs().read();
         let dentry = fs_resolver.lookup_from_fd(fd)?;
         let inode = dentry.inode();
+        if option.typ() != MMapType::Private && perms.contains(VmPerms::WRITE) && !is_file_writable
+        {
+            return_errno_with_message!(Errno::EACCES, "the file is not opened for writing");
+        }
+        // The content of a file sealed against writing cannot be modified through
+        // new shared mappings.
+        if inode.seals().is_ok_and(|seals| {
+            seals.intersects(FileSeals::F_SEAL_WRITE | FileSeals::F_SEAL_FUTURE_WRITE)
+        }) {
+            if option.typ() != MMapType::Private && perms.contains(VmPerms::WRITE) {
+                return_errno_with_message!(Errno::EPERM, "the file is sealed against writing");
+            }
+            may_write = false;
+        }
+        if perms.contains(VmPerms::EXEC) && dentry.mount_node().flags().contains(MountFlags::NOEXEC)
+        {
+            return_errno_with_message!(Errno::EPERM, "the file is on a noexec mount");
+        }
         inode
             .page_cache()
             .ok_or(Error::with_message(
@@ -115,11 +204,19 @@ fn alloc_filebacked_vmo(
 
     if option.typ() == MMapType::Private {
         // map private
-        VmoChildOptions::new_cow(page_cache_vmo, offset..(offset + len)).alloc()
+        let vmo = VmoChildOptions::new_cow(page_cache_vmo, offset..(offset + len)).alloc()?;
+        Ok((vmo, 0))
     } else {
         // map shared
-        // FIXME: map shared vmo can exceed parent range, but slice child cannot
-        VmoChildOptions::new_slice_rights(page_cache_vmo, offset..(offset + len)).alloc()
+        // The page cache is mapped directly, so that the updates through the mapping
+        // are visible to the file and can be written back with `msync`. If the file
+        // cannot be written, the mapping cannot be made writable with `mprotect` either.
+        let page_cache_vmo = if may_write {
+            page_cache_vmo
+        } else {
+            page_cache_vmo.restrict(Rights::all() - Rights::WRITE)
+        };
+        Ok((page_cache_vmo, offset))
     }
 }
 
@@ -132,6 +229,12 @@ fn alloc_filebacked_vmo(
 // The map type mask
 const MAP_TYPE: u32 = 0xf;
 
+// The bits that encode the log2 of the huge page size for `MAP_HUGETLB`
+const MAP_HUGE_SHIFT: u32 = 26;
+const MAP_HUGE_MASK: u32 = 0x3f;
+const MAP_HUGE_2MB: u32 = 21;
+const MAP_HUGE_1GB: u32 = 30;
+
 #[derive(Copy, Clone, PartialEq, Debug, TryFromInt)]
 #[repr(u8)]
 pub enum MMapType {
@@ -164,6 +267,8 @@ bitflags! {
 pub struct MMapOptions {
     typ: MMapType,
     flags: MMapFlags,
+    /// The size of the huge pages required by `MAP_HUGETLB`.
+    huge_page_size: Option<PageSize>,
 }
 
 impl TryFrom<u32> for MMapOptions {
@@ -173,11 +278,34 @@ impl TryFrom<u32> for MMapOptions {
         let typ_raw = (value & MAP_TYPE) as u8;
         let typ = MMapType::try_from(typ_raw)?;
 
-        let flags_raw = value & !MAP_TYPE;
+        let huge_raw = (value >> MAP_HUGE_SHIFT) & MAP_HUGE_MASK;
+        let flags_raw = value & !MAP_TYPE & !(MAP_HUGE_MASK << MAP_HUGE_SHIFT);
         let Some(flags) = MMapFlags::from_bits(flags_raw) else {
             return Err(Error::with_message(Errno::EINVAL, "unknown mmap flags"));
         };
-        Ok(MMapOptions { typ, flags })
+
+        let huge_page_size = if flags.contains(MMapFlags::MAP_HUGETLB) {
+            let page_size = match huge_raw {
+                // The default huge page size is 2 MiB.
+                0 | MAP_HUGE_2MB => PageSize::Size2M,
+                MAP_HUGE_1GB => PageSize::Size1G,
+                _ => return_errno_with_message!(Errno::EINVAL, "unsupported huge page size"),
+            };
+            if !page_size.is_supported() {
+                return_errno_with_message!(Errno::EINVAL, "unsupported huge page size");
+            }
+            Some(page_size)
+        } else if huge_raw != 0 {
+            return_errno_with_message!(Errno::EINVAL, "huge page size without MAP_HUGETLB");
+        } else {
+            None
+        };
+
+        Ok(MMapOptions {
+            typ,
+            flags,
+            huge_page_size,
+        })
     }
 }
 
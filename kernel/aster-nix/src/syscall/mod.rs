@@ -18,6 +18,10 @@ use self::{
 };
 use crate::{
     prelude::*,
+    process::{
+        ptrace::{ptrace_exit_stop, ptrace_syscall_enter, ptrace_syscall_exit},
+        TermStatus,
+    },
     syscall::{
         access::sys_access,
         arch_prctl::sys_arch_prctl,
@@ -32,6 +36,7 @@ use crate::{
         close::sys_close,
         dup::{sys_dup, sys_dup2},
         epoll::{sys_epoll_create, sys_epoll_create1, sys_epoll_ctl, sys_epoll_wait},
+        eventfd::{sys_eventfd, sys_eventfd2},
         execve::sys_execve,
         exit::sys_exit,
         exit_group::sys_exit_group,
@@ -50,21 +55,33 @@ use crate::{
         gettid::sys_gettid,
         gettimeofday::sys_gettimeofday,
         getuid::sys_getuid,
+        inotify::{
+            sys_inotify_add_watch, sys_inotify_init, sys_inotify_init1, sys_inotify_rm_watch,
+        },
         ioctl::sys_ioctl,
         kill::sys_kill,
         link::{sys_link, sys_linkat},
         lseek::sys_lseek,
         madvise::sys_madvise,
+        memfd_create::sys_memfd_create,
+        mincore::sys_mincore,
         mkdir::{sys_mkdir, sys_mkdirat},
+        mlock::{sys_mlock, sys_mlock2, sys_mlockall, sys_munlock, sys_munlockall},
         mmap::sys_mmap,
+        mount::sys_mount,
         mprotect::sys_mprotect,
+        mremap::sys_mremap,
+        msg::{sys_msgctl, sys_msgget, sys_msgrcv, sys_msgsnd},
+        msync::sys_msync,
         munmap::sys_munmap,
         open::{sys_open, sys_openat},
         pause::sys_pause,
+        personality::sys_personality,
         pipe::{sys_pipe, sys_pipe2},
         poll::sys_poll,
         prctl::sys_prctl,
         prlimit64::sys_prlimit64,
+        ptrace::sys_ptrace,
         read::sys_read,
         readlink::{sys_readlink, sys_readlinkat},
         rename::{sys_rename, sys_renameat},
@@ -72,22 +89,37 @@ use crate::{
         rt_sigaction::sys_rt_sigaction,
         rt_sigprocmask::sys_rt_sigprocmask,
         rt_sigreturn::sys_rt_sigreturn,
+        sched_affinity::{sys_sched_getaffinity, sys_sched_setaffinity},
+        sched_get_priority_max_min::{sys_sched_get_priority_max, sys_sched_get_priority_min},
+        sched_param::{sys_sched_getparam, sys_sched_setparam},
+        sched_rr_get_interval::sys_sched_rr_get_interval,
+        sched_scheduler::{sys_sched_getscheduler, sys_sched_setscheduler},
         sched_yield::sys_sched_yield,
         select::sys_select,
+        sem::{sys_semctl, sys_semget, sys_semop, sys_semtimedop},
         set_get_priority::{sys_get_priority, sys_set_priority},
         set_robust_list::sys_set_robust_list,
         set_tid_address::sys_set_tid_address,
+        setitimer::{sys_getitimer, sys_setitimer},
         setpgid::sys_setpgid,
+        shm::{sys_shmat, sys_shmctl, sys_shmdt, sys_shmget},
+        signalfd::{sys_signalfd, sys_signalfd4},
         stat::{sys_fstat, sys_fstatat, sys_lstat, sys_stat},
         statfs::{sys_fstatfs, sys_statfs},
+        swapon::{sys_swapoff, sys_swapon},
         symlink::{sys_symlink, sys_symlinkat},
         sync::sys_sync,
         tgkill::sys_tgkill,
         time::sys_time,
+        timer_create::{sys_timer_create, sys_timer_delete},
+        timer_settime::{sys_timer_getoverrun, sys_timer_gettime, sys_timer_settime},
+        timerfd::{sys_timerfd_create, sys_timerfd_gettime, sys_timerfd_settime},
         truncate::{sys_ftruncate, sys_truncate},
         umask::sys_umask,
+        umount::sys_umount2,
         uname::sys_uname,
         unlink::{sys_unlink, sys_unlinkat},
+        userfaultfd::sys_userfaultfd,
         utimens::sys_utimensat,
         wait4::sys_wait4,
         waitid::sys_waitid,
@@ -114,6 +146,7 @@ mod connect;
 mod constants;
 mod dup;
 mod epoll;
+mod eventfd;
 mod execve;
 mod exit;
 mod exit_group;
@@ -140,23 +173,33 @@ mod getsockopt;
 mod gettid;
 mod gettimeofday;
 mod getuid;
+mod inotify;
 mod ioctl;
 mod kill;
 mod link;
 mod listen;
 mod lseek;
 mod madvise;
+mod memfd_create;
+mod mincore;
 mod mkdir;
+mod mlock;
 mod mmap;
+mod mount;
 mod mprotect;
+mod mremap;
+mod msg;
+mod msync;
 mod munmap;
 mod open;
 mod pause;
+mod personality;
 mod pipe;
 mod poll;
 mod prctl;
 mod pread64;
 mod prlimit64;
+mod ptrace;
 mod read;
 mod readlink;
 mod recvfrom;
@@ -165,8 +208,14 @@ mod rmdir;
 mod rt_sigaction;
 mod rt_sigprocmask;
 mod rt_sigreturn;
+mod sched_affinity;
+mod sched_get_priority_max_min;
+mod sched_param;
+mod sched_rr_get_interval;
+mod sched_scheduler;
 mod sched_yield;
 mod select;
+mod sem;
 mod sendto;
 mod set_get_priority;
 mod set_robust_list;
@@ -175,6 +224,7 @@ mod setfsgid;
 mod setfsuid;
 mod setgid;
 mod setgroups;
+mod setitimer;
 mod setpgid;
 mod setregid;
 mod setresgid;
@@ -183,20 +233,28 @@ mod setreuid;
 mod setsid;
 mod setsockopt;
 mod setuid;
+mod shm;
 mod shutdown;
 mod sigaltstack;
+mod signalfd;
 mod socket;
 mod socketpair;
 mod stat;
 mod statfs;
+mod swapon;
 mod symlink;
 mod sync;
 mod tgkill;
 mod time;
+mod timer_create;
+mod timer_settime;
+mod timerfd;
 mod truncate;
 mod umask;
+mod umount;
 mod uname;
 mod unlink;
+mod userfaultfd;
 mod utimens;
 mod wait4;
 mod waitid;
@@ -257,11 +315,19 @@ define_syscall_nums!(
     SYS_PIPE = 22,
     SYS_SELECT = 23,
     SYS_SCHED_YIELD = 24,
+    SYS_MREMAP = 25,
+    SYS_MSYNC = 26,
+    SYS_MINCORE = 27,
     SYS_MADVISE = 28,
+    SYS_SHMGET = 29,
+    SYS_SHMAT = 30,
+    SYS_SHMCTL = 31,
     SYS_DUP = 32,
     SYS_DUP2 = 33,
     SYS_PAUSE = 34,
+    SYS_GETITIMER = 36,
     SYS_ALARM = 37,
+    SYS_SETITIMER = 38,
     SYS_GETPID = 39,
     SYS_SOCKET = 41,
     SYS_CONNECT = 42,
@@ -283,6 +349,14 @@ define_syscall_nums!(
     SYS_WAIT4 = 61,
     SYS_KILL = 62,
     SYS_UNAME = 63,
+    SYS_SEMGET = 64,
+    SYS_SEMOP = 65,
+    SYS_SEMCTL = 66,
+    SYS_SHMDT = 67,
+    SYS_MSGGET = 68,
+    SYS_MSGSND = 69,
+    SYS_MSGRCV = 70,
+    SYS_MSGCTL = 71,
     SYS_FCNTL = 72,
     SYS_FSYNC = 74,
     SYS_TRUNCATE = 76,
@@ -304,6 +378,7 @@ define_syscall_nums!(
     SYS_LCHOWN = 94,
     SYS_UMASK = 95,
     SYS_GETTIMEOFDAY = 96,
+    SYS_PTRACE = 101,
     SYS_GETUID = 102,
     SYS_GETGID = 104,
     SYS_SETUID = 105,
@@ -326,20 +401,44 @@ define_syscall_nums!(
     SYS_SETFSGID = 123,
     SYS_GETSID = 124,
     SYS_SIGALTSTACK = 131,
+    SYS_PERSONALITY = 135,
     SYS_STATFS = 137,
     SYS_FSTATFS = 138,
     SYS_GET_PRIORITY = 140,
     SYS_SET_PRIORITY = 141,
+    SYS_SCHED_SETPARAM = 142,
+    SYS_SCHED_GETPARAM = 143,
+    SYS_SCHED_SETSCHEDULER = 144,
+    SYS_SCHED_GETSCHEDULER = 145,
+    SYS_SCHED_GET_PRIORITY_MAX = 146,
+    SYS_SCHED_GET_PRIORITY_MIN = 147,
+    SYS_SCHED_RR_GET_INTERVAL = 148,
+    SYS_MLOCK = 149,
+    SYS_MUNLOCK = 150,
+    SYS_MLOCKALL = 151,
+    SYS_MUNLOCKALL = 152,
     SYS_PRCTL = 157,
     SYS_ARCH_PRCTL = 158,
     SYS_CHROOT = 161,
     SYS_SYNC = 162,
+    SYS_MOUNT = 165,
+    SYS_UMOUNT2 = 166,
+    SYS_SWAPON = 167,
+    SYS_SWAPOFF = 168,
     SYS_GETTID = 186,
     SYS_TIME = 201,
     SYS_FUTEX = 202,
+    SYS_SCHED_SETAFFINITY = 203,
+    SYS_SCHED_GETAFFINITY = 204,
     SYS_EPOLL_CREATE = 213,
+    SYS_SEMTIMEDOP = 220,
     SYS_GETDENTS64 = 217,
     SYS_SET_TID_ADDRESS = 218,
+    SYS_TIMER_CREATE = 222,
+    SYS_TIMER_SETTIME = 223,
+    SYS_TIMER_GETTIME = 224,
+    SYS_TIMER_GETOVERRUN = 225,
+    SYS_TIMER_DELETE = 226,
     SYS_CLOCK_GETTIME = 228,
     SYS_CLOCK_NANOSLEEP = 230,
     SYS_EXIT_GROUP = 231,
@@ -347,6 +446,9 @@ define_syscall_nums!(
     SYS_EPOLL_CTL = 233,
     SYS_TGKILL = 234,
     SYS_WAITID = 247,
+    SYS_INOTIFY_INIT = 253,
+    SYS_INOTIFY_ADD_WATCH = 254,
+    SYS_INOTIFY_RM_WATCH = 255,
     SYS_OPENAT = 257,
     SYS_MKDIRAT = 258,
     SYS_FCHOWNAT = 260,
@@ -359,11 +461,22 @@ define_syscall_nums!(
     SYS_FCHMODAT = 268,
     SYS_SET_ROBUST_LIST = 273,
     SYS_UTIMENSAT = 280,
+    SYS_SIGNALFD = 282,
+    SYS_TIMERFD_CREATE = 283,
+    SYS_EVENTFD = 284,
+    SYS_TIMERFD_SETTIME = 286,
+    SYS_TIMERFD_GETTIME = 287,
+    SYS_SIGNALFD4 = 289,
+    SYS_EVENTFD2 = 290,
     SYS_EPOLL_CREATE1 = 291,
     SYS_PIPE2 = 293,
+    SYS_INOTIFY_INIT1 = 294,
     SYS_PRLIMIT64 = 302,
     SYS_GETRANDOM = 318,
-    SYS_EXECVEAT = 322
+    SYS_MEMFD_CREATE = 319,
+    SYS_EXECVEAT = 322,
+    SYS_USERFAULTFD = 323,
+    SYS_MLOCK2 = 325
 );
 
 pub struct SyscallArgument {
@@ -398,22 +511,31 @@ impl SyscallArgument {
 }
 
 pub fn handle_syscall(context: &mut UserContext) {
-    let syscall_frame = SyscallArgument::new_from_context(context);
-    let syscall_return =
-        syscall_dispatch(syscall_frame.syscall_number, syscall_frame.args, context);
+    // The tracer of a traced thread may change the syscall or skip it.
+    if ptrace_syscall_enter(context).is_some() {
+        let syscall_frame = SyscallArgument::new_from_context(context);
+        if matches!(syscall_frame.syscall_number, SYS_EXIT | SYS_EXIT_GROUP) {
+            let term_status = TermStatus::Exited(syscall_frame.args[0] as _);
+            ptrace_exit_stop(term_status, context);
+        }
+
+        let syscall_return =
+            syscall_dispatch(syscall_frame.syscall_number, syscall_frame.args, context);
 
-    match syscall_return {
-        Ok(return_value) => {
-            if let SyscallReturn::Return(return_value) = return_value {
-                context.set_rax(return_value as usize);
+        match syscall_return {
+            Ok(return_value) => {
+                if let SyscallReturn::Return(return_value) = return_value {
+                    context.set_rax(return_value as usize);
+                }
+            }
+            Err(err) => {
+                debug!("syscall return error: {:?}", err);
+                let errno = err.error() as i32;
+                context.set_rax((-errno) as usize)
             }
-        }
-        Err(err) => {
-            debug!("syscall return error: {:?}", err);
-            let errno = err.error() as i32;
-            context.set_rax((-errno) as usize)
         }
     }
+    ptrace_syscall_exit(context);
 }
 
 pub fn syscall_dispatch(
@@ -445,11 +567,19 @@ pub fn syscall_dispatch(
         SYS_PIPE => syscall_handler!(1, sys_pipe, args),
         SYS_SELECT => syscall_handler!(5, sys_select, args),
         SYS_SCHED_YIELD => syscall_handler!(0, sys_sched_yield),
+        SYS_MREMAP => syscall_handler!(5, sys_mremap, args),
+        SYS_MSYNC => syscall_handler!(3, sys_msync, args),
+        SYS_MINCORE => syscall_handler!(3, sys_mincore, args),
         SYS_MADVISE => syscall_handler!(3, sys_madvise, args),
+        SYS_SHMGET => syscall_handler!(3, sys_shmget, args),
+        SYS_SHMAT => syscall_handler!(3, sys_shmat, args),
+        SYS_SHMCTL => syscall_handler!(3, sys_shmctl, args),
         SYS_DUP => syscall_handler!(1, sys_dup, args),
         SYS_DUP2 => syscall_handler!(2, sys_dup2, args),
         SYS_PAUSE => syscall_handler!(0, sys_pause),
+        SYS_GETITIMER => syscall_handler!(2, sys_getitimer, args),
         SYS_ALARM => syscall_handler!(1, sys_alarm, args),
+        SYS_SETITIMER => syscall_handler!(3, sys_setitimer, args),
         SYS_GETPID => syscall_handler!(0, sys_getpid),
         SYS_SOCKET => syscall_handler!(3, sys_socket, args),
         SYS_CONNECT => syscall_handler!(3, sys_connect, args),
@@ -471,6 +601,14 @@ pub fn syscall_dispatch(
         SYS_WAIT4 => syscall_handler!(3, sys_wait4, args),
         SYS_KILL => syscall_handler!(2, sys_kill, args),
         SYS_UNAME => syscall_handler!(1, sys_uname, args),
+        SYS_SEMGET => syscall_handler!(3, sys_semget, args),
+        SYS_SEMOP => syscall_handler!(3, sys_semop, args),
+        SYS_SEMCTL => syscall_handler!(4, sys_semctl, args),
+        SYS_SHMDT => syscall_handler!(1, sys_shmdt, args),
+        SYS_MSGGET => syscall_handler!(2, sys_msgget, args),
+        SYS_MSGSND => syscall_handler!(4, sys_msgsnd, args),
+        SYS_MSGRCV => syscall_handler!(5, sys_msgrcv, args),
+        SYS_MSGCTL => syscall_handler!(3, sys_msgctl, args),
         SYS_FCNTL => syscall_handler!(3, sys_fcntl, args),
         SYS_FSYNC => syscall_handler!(1, sys_fsync, args),
         SYS_TRUNCATE => syscall_handler!(2, sys_truncate, args),
@@ -492,6 +630,7 @@ pub fn syscall_dispatch(
         SYS_LCHOWN => syscall_handler!(3, sys_lchown, args),
         SYS_UMASK => syscall_handler!(1, sys_umask, args),
         SYS_GETTIMEOFDAY => syscall_handler!(1, sys_gettimeofday, args),
+        SYS_PTRACE => syscall_handler!(4, sys_ptrace, args),
         SYS_GETUID => syscall_handler!(0, sys_getuid),
         SYS_GETGID => syscall_handler!(0, sys_getgid),
         SYS_SETUID => syscall_handler!(1, sys_setuid, args),
@@ -514,20 +653,44 @@ pub fn syscall_dispatch(
         SYS_SETFSGID => syscall_handler!(1, sys_setfsgid, args),
         SYS_GETSID => syscall_handler!(1, sys_getsid, args),
         SYS_SIGALTSTACK => syscall_handler!(2, sys_sigaltstack, args),
+        SYS_PERSONALITY => syscall_handler!(1, sys_personality, args),
         SYS_STATFS => syscall_handler!(2, sys_statfs, args),
         SYS_FSTATFS => syscall_handler!(2, sys_fstatfs, args),
         SYS_GET_PRIORITY => syscall_handler!(2, sys_get_priority, args),
         SYS_SET_PRIORITY => syscall_handler!(3, sys_set_priority, args),
+        SYS_SCHED_SETPARAM => syscall_handler!(2, sys_sched_setparam, args),
+        SYS_SCHED_GETPARAM => syscall_handler!(2, sys_sched_getparam, args),
+        SYS_SCHED_SETSCHEDULER => syscall_handler!(3, sys_sched_setscheduler, args),
+        SYS_SCHED_GETSCHEDULER => syscall_handler!(1, sys_sched_getscheduler, args),
+        SYS_SCHED_GET_PRIORITY_MAX => syscall_handler!(1, sys_sched_get_priority_max, args),
+        SYS_SCHED_GET_PRIORITY_MIN => syscall_handler!(1, sys_sched_get_priority_min, args),
+        SYS_SCHED_RR_GET_INTERVAL => syscall_handler!(2, sys_sched_rr_get_interval, args),
+        SYS_MLOCK => syscall_handler!(2, sys_mlock, args),
+        SYS_MUNLOCK => syscall_handler!(2, sys_munlock, args),
+        SYS_MLOCKALL => syscall_handler!(1, sys_mlockall, args),
+        SYS_MUNLOCKALL => syscall_handler!(0, sys_munlockall),
         SYS_PRCTL => syscall_handler!(5, sys_prctl, args),
         SYS_ARCH_PRCTL => syscall_handler!(2, sys_arch_prctl, args, context),
         SYS_CHROOT => syscall_handler!(1, sys_chroot, args),
         SYS_SYNC => syscall_handler!(0, sys_sync),
+        SYS_MOUNT => syscall_handler!(5, sys_mount, args),
+        SYS_UMOUNT2 => syscall_handler!(2, sys_umount2, args),
+        SYS_SWAPON => syscall_handler!(2, sys_swapon, args),
+        SYS_SWAPOFF => syscall_handler!(1, sys_swapoff, args),
         SYS_GETTID => syscall_handler!(0, sys_gettid),
         SYS_TIME => syscall_handler!(1, sys_time, args),
         SYS_FUTEX => syscall_handler!(6, sys_futex, args),
+        SYS_SCHED_SETAFFINITY => syscall_handler!(3, sys_sched_setaffinity, args),
+        SYS_SCHED_GETAFFINITY => syscall_handler!(3, sys_sched_getaffinity, args),
         SYS_EPOLL_CREATE => syscall_handler!(1, sys_epoll_create, args),
+        SYS_SEMTIMEDOP => syscall_handler!(4, sys_semtimedop, args),
         SYS_GETDENTS64 => syscall_handler!(3, sys_getdents64, args),
         SYS_SET_TID_ADDRESS => syscall_handler!(1, sys_set_tid_address, args),
+        SYS_TIMER_CREATE => syscall_handler!(3, sys_timer_create, args),
+        SYS_TIMER_SETTIME => syscall_handler!(4, sys_timer_settime, args),
+        SYS_TIMER_GETTIME => syscall_handler!(2, sys_timer_gettime, args),
+        SYS_TIMER_GETOVERRUN => syscall_handler!(1, sys_timer_getoverrun, args),
+        SYS_TIMER_DELETE => syscall_handler!(1, sys_timer_delete, args),
         SYS_CLOCK_GETTIME => syscall_handler!(2, sys_clock_gettime, args),
         SYS_CLOCK_NANOSLEEP => syscall_handler!(4, sys_clock_nanosleep, args),
         SYS_EXIT_GROUP => syscall_handler!(1, sys_exit_group, args),
@@ -535,6 +698,9 @@ pub fn syscall_dispatch(
         SYS_EPOLL_CTL => syscall_handler!(4, sys_epoll_ctl, args),
         SYS_TGKILL => syscall_handler!(3, sys_tgkill, args),
         SYS_WAITID => syscall_handler!(5, sys_waitid, args),
+        SYS_INOTIFY_INIT => syscall_handler!(0, sys_inotify_init),
+        SYS_INOTIFY_ADD_WATCH => syscall_handler!(3, sys_inotify_add_watch, args),
+        SYS_INOTIFY_RM_WATCH => syscall_handler!(2, sys_inotify_rm_watch, args),
         SYS_OPENAT => syscall_handler!(4, sys_openat, args),
         SYS_MKDIRAT => syscall_handler!(3, sys_mkdirat, args),
         SYS_FCHOWNAT => syscall_handler!(5, sys_fchownat, args),
@@ -547,11 +713,22 @@ pub fn syscall_dispatch(
         SYS_FCHMODAT => syscall_handler!(3, sys_fchmodat, args),
         SYS_SET_ROBUST_LIST => syscall_handler!(2, sys_set_robust_list, args),
         SYS_UTIMENSAT => syscall_handler!(4, sys_utimensat, args),
+        SYS_SIGNALFD => syscall_handler!(3, sys_signalfd, args),
+        SYS_TIMERFD_CREATE => syscall_handler!(2, sys_timerfd_create, args),
+        SYS_EVENTFD => syscall_handler!(1, sys_eventfd, args),
+        SYS_TIMERFD_SETTIME => syscall_handler!(4, sys_timerfd_settime, args),
+        SYS_TIMERFD_GETTIME => syscall_handler!(2, sys_timerfd_gettime, args),
+        SYS_SIGNALFD4 => syscall_handler!(4, sys_signalfd4, args),
+        SYS_EVENTFD2 => syscall_handler!(2, sys_eventfd2, args),
         SYS_EPOLL_CREATE1 => syscall_handler!(1, sys_epoll_create1, args),
         SYS_PIPE2 => syscall_handler!(2, sys_pipe2, args),
+        SYS_INOTIFY_INIT1 => syscall_handler!(1, sys_inotify_init1, args),
         SYS_PRLIMIT64 => syscall_handler!(4, sys_prlimit64, args),
         SYS_GETRANDOM => syscall_handler!(3, sys_getrandom, args),
+        SYS_MEMFD_CREATE => syscall_handler!(2, sys_memfd_create, args),
         SYS_EXECVEAT => syscall_handler!(5, sys_execveat, args, context),
+        SYS_USERFAULTFD => syscall_handler!(1, sys_userfaultfd, args),
+        SYS_MLOCK2 => syscall_handler!(3, sys_mlock2, args),
         _ => {
             warn!("Unimplemented syscall number: {}", syscall_number);
             return_errno_with_message!(Errno::ENOSYS, "Syscall was unimplemented");
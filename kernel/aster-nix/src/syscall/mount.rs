@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_MOUNT};
+use crate::{
+    fs::{
+        fs_resolver::FsPath,
+        registry,
+        utils::{Dentry, MountFlags},
+    },
+    log_syscall_entry,
+    prelude::*,
+    process::credentials,
+    syscall::constants::MAX_FILENAME_LEN,
+    util::read_cstring_from_user,
+};
+
+/// The magic number in the upper 16 bits of the flags, which is required before
+/// Linux 2.4 and is ignored.
+const MS_MGC_MSK: u32 = 0xffff_0000;
+const MS_MGC_VAL: u32 = 0xc0ed_0000;
+
+pub fn sys_mount(
+    source_addr: Vaddr,
+    target_addr: Vaddr,
+    fs_type_addr: Vaddr,
+    flags: u64,
+    data_addr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MOUNT);
+    let target = read_cstring_from_user(target_addr, MAX_FILENAME_LEN)?;
+    let flags = {
+        let mut flags = flags as u32;
+        if flags & MS_MGC_MSK == MS_MGC_VAL {
+            flags &= !MS_MGC_MSK;
+        }
+        MsFlags::from_bits(flags)
+            .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported mount flags"))?
+    };
+    debug!("target = {:?}, flags = {:?}", target, flags);
+
+    if !credentials().euid().is_root() {
+        return_errno_with_message!(Errno::EPERM, "only root can mount file systems");
+    }
+
+    let target_dentry = lookup_dentry(target)?;
+    if flags.contains(MsFlags::MS_REMOUNT) {
+        do_remount(&target_dentry, flags)?;
+    } else if flags.contains(MsFlags::MS_BIND) {
+        let source = read_cstring_from_user(source_addr, MAX_FILENAME_LEN)?;
+        debug!("source = {:?}", source);
+        let source_dentry = lookup_dentry(source)?;
+        // Like Linux, the per-mount flags of a bind mount can only be changed by
+        // remounting it.
+        target_dentry.bind_mount(&source_dentry, MountFlags::empty())?;
+    } else {
+        let fs_type = read_cstring_from_user(fs_type_addr, MAX_FILENAME_LEN)?;
+        let source = if source_addr == 0 {
+            String::from("none")
+        } else {
+            read_cstring_from_user(source_addr, MAX_FILENAME_LEN)?
+                .to_string_lossy()
+                .into_owned()
+        };
+        let data = if data_addr == 0 {
+            String::new()
+        } else {
+            read_cstring_from_user(data_addr, PAGE_SIZE)?
+                .to_string_lossy()
+                .into_owned()
+        };
+        debug!(
+            "source = {:?}, fs_type = {:?}, data = {:?}",
+            source, fs_type, data
+        );
+
+        let fs = registry::create_fs(&fs_type.to_string_lossy(), &source, &data)?;
+        target_dentry.mount(fs, &source, flags.mount_flags())?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Changes the per-mount flags of the mount whose root dentry is `target_dentry`.
+fn do_remount(target_dentry: &Dentry, flags: MsFlags) -> Result<()> {
+    if !target_dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "the target is not a mount point");
+    }
+
+    let mount_node = target_dentry.mount_node();
+    if flags.contains(MsFlags::MS_RDONLY) {
+        // Flush the dirty data, which cannot be written after the mount becomes
+        // read-only.
+        mount_node.sync()?;
+    }
+    mount_node.set_flags(flags.mount_flags());
+    Ok(())
+}
+
+fn lookup_dentry(path: CString) -> Result<Arc<Dentry>> {
+    let path = path.to_string_lossy();
+    if path.is_empty() {
+        return_errno_with_message!(Errno::ENOENT, "path is empty");
+    }
+    let fs_path = FsPath::try_from(path.as_ref())?;
+    current!().fs().read().lookup(&fs_path)
+}
+
+bitflags! {
+    struct MsFlags: u32 {
+        const MS_RDONLY = 1 << 0;
+        const MS_NOSUID = 1 << 1;
+        const MS_NODEV = 1 << 2;
+        const MS_NOEXEC = 1 << 3;
+        const MS_SYNCHRONOUS = 1 << 4;
+        const MS_REMOUNT = 1 << 5;
+        const MS_NOATIME = 1 << 10;
+        const MS_NODIRATIME = 1 << 11;
+        const MS_BIND = 1 << 12;
+        const MS_REC = 1 << 14;
+        const MS_SILENT = 1 << 15;
+        const MS_RELATIME = 1 << 21;
+    }
+}
+
+impl MsFlags {
+    /// Returns the per-mount flags, ignoring the flags that are not supported.
+    fn mount_flags(&self) -> MountFlags {
+        MountFlags::from_bits_truncate(self.bits())
+    }
+}
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::{SyscallReturn, SYS_MREMAP};
+use crate::{log_syscall_entry, prelude::*};
+
+pub fn sys_mremap(
+    old_addr: Vaddr,
+    old_size: usize,
+    new_size: usize,
+    flags: i32,
+    new_addr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MREMAP);
+    let flags = MremapFlags::from_bits(flags as u32)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown mremap flags"))?;
+    debug!(
+        "old_addr = 0x{:x}, old_size = 0x{:x}, new_size = 0x{:x}, flags = {:?}, new_addr = 0x{:x}",
+        old_addr, old_size, new_size, flags, new_addr
+    );
+
+    if old_addr % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the old address is not page-aligned");
+    }
+    if flags.contains(MremapFlags::MREMAP_FIXED) && !flags.contains(MremapFlags::MREMAP_MAYMOVE) {
+        return_errno_with_message!(Errno::EINVAL, "MREMAP_FIXED requires MREMAP_MAYMOVE");
+    }
+    if flags.contains(MremapFlags::MREMAP_DONTUNMAP) {
+        return_errno_with_message!(Errno::EINVAL, "MREMAP_DONTUNMAP is not supported");
+    }
+    if old_size > isize::MAX as usize || new_size > isize::MAX as usize {
+        return_errno_with_message!(Errno::EINVAL, "the size is too large");
+    }
+    let old_size = old_size.align_up(PAGE_SIZE);
+    let new_size = new_size.align_up(PAGE_SIZE);
+    if new_size == 0 {
+        return_errno_with_message!(Errno::EINVAL, "the new size cannot be zero");
+    }
+    // TODO: Linux duplicates a shared mapping if the old size is zero.
+    if old_size == 0 {
+        return_errno_with_message!(Errno::EINVAL, "the old size cannot be zero");
+    }
+
+    let old_end = old_addr
+        .checked_add(old_size)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the old range overflows"))?;
+
+    let new_addr = if flags.contains(MremapFlags::MREMAP_FIXED) {
+        if new_addr % PAGE_SIZE != 0 {
+            return_errno_with_message!(Errno::EINVAL, "the new address is not page-aligned");
+        }
+        if new_addr.checked_add(new_size).is_none() {
+            return_errno_with_message!(Errno::EINVAL, "the new range overflows");
+        }
+        Some(new_addr)
+    } else {
+        None
+    };
+
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    let remapped_addr = root_vmar.remap(
+        old_addr..old_end,
+        new_size,
+        new_addr,
+        flags.contains(MremapFlags::MREMAP_MAYMOVE),
+    )?;
+    trace!(
+        "remapped range = 0x{:x} - 0x{:x}",
+        remapped_addr,
+        remapped_addr + new_size
+    );
+    Ok(SyscallReturn::Return(remapped_addr as _))
+}
+
+bitflags! {
+    struct MremapFlags: u32 {
+        const MREMAP_MAYMOVE   = 1 << 0;
+        const MREMAP_FIXED     = 1 << 1;
+        const MREMAP_DONTUNMAP = 1 << 2;
+    }
+}
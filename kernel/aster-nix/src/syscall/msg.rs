@@ -0,0 +1,100 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem;
+
+use super::{SyscallReturn, SYS_MSGCTL, SYS_MSGGET, SYS_MSGRCV, SYS_MSGSND};
+use crate::{
+    ipc::{
+        msg::{get_message_queue, msgget, Message, MsgFlags, MSGMAX},
+        IpcFlags, IpcId, IpcKey,
+    },
+    log_syscall_entry,
+    prelude::*,
+    util::{read_bytes_from_user, read_val_from_user, write_bytes_to_user, write_val_to_user},
+};
+
+pub fn sys_msgget(key: IpcKey, msgflg: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MSGGET);
+    debug!("key = {}, msgflg = 0o{:o}", key, msgflg);
+    let flags = IpcFlags::from_bits_truncate(msgflg);
+    let mode = (msgflg & 0o777) as u16;
+    let id = msgget(key, flags, mode)?;
+    Ok(SyscallReturn::Return(id as _))
+}
+
+/// The `msgbuf` structure starts with the type of the message, which is followed
+/// by the text of the message.
+const MTEXT_OFFSET: usize = mem::size_of::<i64>();
+
+pub fn sys_msgsnd(msqid: IpcId, msgp: Vaddr, msgsz: usize, msgflg: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MSGSND);
+    debug!(
+        "msqid = {}, msgp = 0x{:x}, msgsz = {}, msgflg = 0o{:o}",
+        msqid, msgp, msgsz, msgflg
+    );
+    let queue = get_message_queue(msqid)?;
+    if msgsz > MSGMAX {
+        return_errno_with_message!(Errno::EINVAL, "the message is too long");
+    }
+    let mtype: i64 = read_val_from_user(msgp)?;
+    let mut text = vec![0u8; msgsz];
+    read_bytes_from_user(msgp + MTEXT_OFFSET, &mut text)?;
+    let message = Message::new(mtype, text)?;
+
+    queue.send(message, MsgFlags::from_bits_truncate(msgflg))?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_msgrcv(
+    msqid: IpcId,
+    msgp: Vaddr,
+    msgsz: usize,
+    msgtyp: i64,
+    msgflg: u32,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MSGRCV);
+    debug!(
+        "msqid = {}, msgp = 0x{:x}, msgsz = {}, msgtyp = {}, msgflg = 0o{:o}",
+        msqid, msgp, msgsz, msgtyp, msgflg
+    );
+    if (msgsz as isize) < 0 {
+        return_errno_with_message!(Errno::EINVAL, "the message size is negative");
+    }
+    let queue = get_message_queue(msqid)?;
+    let message = queue.receive(msgsz, msgtyp, MsgFlags::from_bits_truncate(msgflg))?;
+
+    write_val_to_user(msgp, &message.mtype())?;
+    write_bytes_to_user(msgp + MTEXT_OFFSET, message.text())?;
+    Ok(SyscallReturn::Return(message.text().len() as _))
+}
+
+pub fn sys_msgctl(msqid: IpcId, cmd: i32, buf: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MSGCTL);
+    // The `IPC_64` flag is ignored since only the 64-bit structures are supported.
+    let cmd = MsgCtlCmd::try_from(cmd & !IPC_64)?;
+    debug!("msqid = {}, cmd = {:?}, buf = 0x{:x}", msqid, cmd, buf);
+    let queue = get_message_queue(msqid)?;
+    match cmd {
+        MsgCtlCmd::IPC_RMID => queue.remove()?,
+        MsgCtlCmd::IPC_SET => {
+            let msqid_ds = read_val_from_user(buf)?;
+            queue.set(&msqid_ds)?;
+        }
+        MsgCtlCmd::IPC_STAT => {
+            let msqid_ds = queue.stat()?;
+            write_val_to_user(buf, &msqid_ds)?;
+        }
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+const IPC_64: i32 = 0x100;
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[allow(non_camel_case_types)]
+enum MsgCtlCmd {
+    IPC_RMID = 0,
+    IPC_SET = 1,
+    IPC_STAT = 2,
+}
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use align_ext::AlignExt;
+
+use super::{SyscallReturn, SYS_MSYNC};
+use crate::{log_syscall_entry, prelude::*};
+
+pub fn sys_msync(addr: Vaddr, len: usize, flags: i32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_MSYNC);
+    let flags = MsyncFlags::from_bits(flags as u32)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown msync flags"))?;
+    debug!(
+        "addr = 0x{:x}, len = 0x{:x}, flags = {:?}",
+        addr, len, flags
+    );
+
+    if addr % PAGE_SIZE != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the address is not page-aligned");
+    }
+    if flags.contains(MsyncFlags::MS_ASYNC | MsyncFlags::MS_SYNC) {
+        return_errno_with_message!(Errno::EINVAL, "MS_ASYNC and MS_SYNC are exclusive");
+    }
+    if len == 0 {
+        return Ok(SyscallReturn::Return(0));
+    }
+    let end = (len <= isize::MAX as usize)
+        .then(|| addr.checked_add(len.align_up(PAGE_SIZE)))
+        .flatten()
+        .ok_or_else(|| Error::with_message(Errno::ENOMEM, "the range overflows"))?;
+
+    // There is no background writeback of dirty pages, so `MS_ASYNC` also writes them
+    // back right now. `MS_INVALIDATE` has nothing to do since the mappings of a file
+    // share its page cache, except that it fails on the locked pages like Linux.
+    let current = current!();
+    let root_vmar = current.root_vmar();
+    if flags.contains(MsyncFlags::MS_INVALIDATE) && root_vmar.locked_size(&(addr..end)) > 0 {
+        return_errno_with_message!(Errno::EBUSY, "MS_INVALIDATE is used on locked pages");
+    }
+    root_vmar.sync(addr..end)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+bitflags! {
+    struct MsyncFlags: u32 {
+        const MS_ASYNC      = 1 << 0;
+        const MS_INVALIDATE = 1 << 1;
+        const MS_SYNC       = 1 << 2;
+    }
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::sync::atomic::Ordering;
+
+use super::{SyscallReturn, SYS_PERSONALITY};
+use crate::{log_syscall_entry, prelude::*, process::PersonalityFlags};
+
+/// The special value to query the personality without changing it.
+const PERSONALITY_QUERY: u32 = 0xffff_ffff;
+
+pub fn sys_personality(persona: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_PERSONALITY);
+    debug!(
+        "persona = {:#x}, flags = {:?}",
+        persona,
+        PersonalityFlags::from_bits_truncate(persona)
+    );
+
+    let current = current!();
+    let old_persona = if persona == PERSONALITY_QUERY {
+        current.personality().load(Ordering::Relaxed)
+    } else {
+        current.personality().swap(persona, Ordering::Relaxed)
+    };
+    Ok(SyscallReturn::Return(old_persona as _))
+}
@@ -5,7 +5,7 @@ use crate::{
     fs::{file_table::FileDescripter, utils::SeekFrom},
     log_syscall_entry,
     prelude::*,
-    util::write_bytes_to_user,
+    util::{alloc_user_buffer, write_bytes_to_user},
 };
 
 pub fn sys_pread64(
@@ -28,7 +28,7 @@ pub fn sys_pread64(
     file.seek(seek_from)?;
 
     let read_len = {
-        let mut buffer = vec![0u8; count];
+        let mut buffer = alloc_user_buffer(count)?;
         let read_len = file.read(&mut buffer)?;
         write_bytes_to_user(buf_ptr, &buffer)?;
         read_len
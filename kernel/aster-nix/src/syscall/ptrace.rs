@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::mem;
+
+use aster_frame::vm::VmIo;
+
+use super::{SyscallReturn, SYS_PTRACE};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::{
+        ptrace::{
+            ptrace_attach, ptrace_detach, ptrace_event_msg, ptrace_interrupt, ptrace_kill,
+            ptrace_regs, ptrace_resume, ptrace_set_options, ptrace_set_regs, ptrace_siginfo,
+            ptrace_stopped_process, ptrace_traceme, PtraceOptions, PtraceRegs, PtraceResumeMode,
+        },
+        signal::sig_num::SigNum,
+    },
+    thread::{thread_table, Thread, Tid},
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_ptrace(request: u32, tid: Tid, addr: Vaddr, data: u64) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_PTRACE);
+    let request = PtraceRequest::try_from(request)
+        .map_err(|_| Error::with_message(Errno::EIO, "unsupported ptrace request"))?;
+    debug!(
+        "request = {:?}, tid = {}, addr = 0x{:x}, data = 0x{:x}",
+        request, tid, addr, data
+    );
+
+    if request == PtraceRequest::PTRACE_TRACEME {
+        ptrace_traceme()?;
+        return Ok(SyscallReturn::Return(0));
+    }
+
+    let tracee = thread_table::get_thread(tid)
+        .ok_or_else(|| Error::with_message(Errno::ESRCH, "the thread does not exist"))?;
+    match request {
+        PtraceRequest::PTRACE_TRACEME => unreachable!(),
+        PtraceRequest::PTRACE_ATTACH => ptrace_attach(tracee, PtraceOptions::empty(), false)?,
+        PtraceRequest::PTRACE_SEIZE => {
+            if addr != 0 {
+                return_errno_with_message!(Errno::EIO, "the address must be zero");
+            }
+            ptrace_attach(tracee, options_from(data)?, true)?
+        }
+        PtraceRequest::PTRACE_PEEKTEXT | PtraceRequest::PTRACE_PEEKDATA => {
+            let process = ptrace_stopped_process(&tracee)?;
+            let word: u64 = process.root_vmar().read_val(addr)?;
+            write_val_to_user(data as Vaddr, &word)?;
+        }
+        PtraceRequest::PTRACE_POKETEXT | PtraceRequest::PTRACE_POKEDATA => {
+            // Like Linux, the write is forced even if the memory is read-only,
+            // e.g., to insert a breakpoint into the text of the tracee.
+            let process = ptrace_stopped_process(&tracee)?;
+            process
+                .root_vmar()
+                .force_write_bytes(addr, &data.to_ne_bytes())?;
+        }
+        PtraceRequest::PTRACE_PEEKUSER => {
+            let word = peek_user(&tracee, addr)?;
+            write_val_to_user(data as Vaddr, &word)?;
+        }
+        PtraceRequest::PTRACE_POKEUSER => poke_user(&tracee, addr, data)?,
+        PtraceRequest::PTRACE_GETREGS => {
+            let regs = ptrace_regs(&tracee)?;
+            write_val_to_user(data as Vaddr, &regs)?;
+        }
+        PtraceRequest::PTRACE_SETREGS => {
+            let regs: PtraceRegs = read_val_from_user(data as Vaddr)?;
+            ptrace_set_regs(&tracee, &regs)?;
+        }
+        PtraceRequest::PTRACE_CONT => {
+            ptrace_resume(&tracee, PtraceResumeMode::Continue, signal_from(data)?)?
+        }
+        PtraceRequest::PTRACE_SYSCALL => {
+            ptrace_resume(&tracee, PtraceResumeMode::Syscall, signal_from(data)?)?
+        }
+        PtraceRequest::PTRACE_SINGLESTEP => {
+            ptrace_resume(&tracee, PtraceResumeMode::SingleStep, signal_from(data)?)?
+        }
+        PtraceRequest::PTRACE_KILL => ptrace_kill(&tracee)?,
+        PtraceRequest::PTRACE_DETACH => ptrace_detach(&tracee, signal_from(data)?)?,
+        PtraceRequest::PTRACE_SETOPTIONS => ptrace_set_options(&tracee, options_from(data)?)?,
+        PtraceRequest::PTRACE_GETEVENTMSG => {
+            let event_msg = ptrace_event_msg(&tracee)?;
+            write_val_to_user(data as Vaddr, &event_msg)?;
+        }
+        PtraceRequest::PTRACE_GETSIGINFO => {
+            let siginfo = ptrace_siginfo(&tracee)?;
+            write_val_to_user(data as Vaddr, &siginfo)?;
+        }
+        PtraceRequest::PTRACE_INTERRUPT => ptrace_interrupt(&tracee)?,
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+/// The size of `struct user` of Linux, which ends with the eight debug registers.
+const USER_SIZE: usize = 912;
+
+/// Reads a word at the offset in `struct user` of the tracee.
+///
+/// Only the general-purpose registers are supported. The other fields, e.g., the debug
+/// registers and the floating-point state, are always zero.
+fn peek_user(tracee: &Thread, offset: usize) -> Result<u64> {
+    if offset % mem::size_of::<u64>() != 0 || offset >= USER_SIZE {
+        return_errno_with_message!(Errno::EIO, "the offset is not supported");
+    }
+
+    if offset < mem::size_of::<PtraceRegs>() {
+        let regs = ptrace_regs(tracee)?;
+        let bytes = &regs.as_bytes()[offset..offset + mem::size_of::<u64>()];
+        return Ok(u64::from_ne_bytes(bytes.try_into().unwrap()));
+    }
+
+    Ok(0)
+}
+
+/// Writes a word at the offset in `struct user` of the tracee.
+///
+/// Only the general-purpose registers are supported.
+fn poke_user(tracee: &Thread, offset: usize, word: u64) -> Result<()> {
+    if offset % mem::size_of::<u64>() != 0 || offset >= mem::size_of::<PtraceRegs>() {
+        return_errno_with_message!(Errno::EIO, "the offset is not supported");
+    }
+
+    let mut regs = ptrace_regs(tracee)?;
+    regs.as_bytes_mut()[offset..offset + mem::size_of::<u64>()]
+        .copy_from_slice(&word.to_ne_bytes());
+    ptrace_set_regs(tracee, &regs)
+}
+
+fn signal_from(data: u64) -> Result<Option<SigNum>> {
+    if data == 0 {
+        return Ok(None);
+    }
+    let sig_num = u8::try_from(data)
+        .ok()
+        .and_then(|data| SigNum::try_from(data).ok())
+        .ok_or_else(|| Error::with_message(Errno::EIO, "invalid signal number"))?;
+    Ok(Some(sig_num))
+}
+
+fn options_from(data: u64) -> Result<PtraceOptions> {
+    u32::try_from(data)
+        .ok()
+        .and_then(PtraceOptions::from_bits)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid ptrace options"))
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[allow(non_camel_case_types)]
+enum PtraceRequest {
+    PTRACE_TRACEME = 0,
+    PTRACE_PEEKTEXT = 1,
+    PTRACE_PEEKDATA = 2,
+    PTRACE_PEEKUSER = 3,
+    PTRACE_POKETEXT = 4,
+    PTRACE_POKEDATA = 5,
+    PTRACE_POKEUSER = 6,
+    PTRACE_CONT = 7,
+    PTRACE_KILL = 8,
+    PTRACE_SINGLESTEP = 9,
+    PTRACE_GETREGS = 12,
+    PTRACE_SETREGS = 13,
+    PTRACE_ATTACH = 16,
+    PTRACE_DETACH = 17,
+    PTRACE_SYSCALL = 24,
+    PTRACE_SETOPTIONS = 0x4200,
+    PTRACE_GETEVENTMSG = 0x4201,
+    PTRACE_GETSIGINFO = 0x4202,
+    PTRACE_SEIZE = 0x4206,
+    PTRACE_INTERRUPT = 0x4207,
+}
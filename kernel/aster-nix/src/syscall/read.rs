@@ -2,7 +2,10 @@
 
 use super::{SyscallReturn, SYS_READ};
 use crate::{
-    fs::file_table::FileDescripter, log_syscall_entry, prelude::*, util::write_bytes_to_user,
+    fs::file_table::FileDescripter,
+    log_syscall_entry,
+    prelude::*,
+    util::{alloc_user_buffer, write_bytes_to_user},
 };
 
 pub fn sys_read(fd: FileDescripter, user_buf_addr: Vaddr, buf_len: usize) -> Result<SyscallReturn> {
@@ -14,7 +17,7 @@ pub fn sys_read(fd: FileDescripter, user_buf_addr: Vaddr, buf_len: usize) -> Res
     let current = current!();
     let file_table = current.file_table().lock();
     let file = file_table.get_file(fd)?;
-    let mut read_buf = vec![0u8; buf_len];
+    let mut read_buf = alloc_user_buffer(buf_len)?;
     let read_len = file.read(&mut read_buf)?;
     write_bytes_to_user(user_buf_addr, &read_buf)?;
     Ok(SyscallReturn::Return(read_len as _))
@@ -7,6 +7,7 @@ use crate::{
     net::socket::SendRecvFlags,
     prelude::*,
     util::{
+        alloc_user_buffer,
         net::{get_socket_from_fd, write_socket_addr_to_user},
         write_bytes_to_user,
     },
@@ -26,7 +27,7 @@ pub fn sys_recvfrom(
 
     let socket = get_socket_from_fd(sockfd)?;
 
-    let mut buffer = vec![0u8; len];
+    let mut buffer = alloc_user_buffer(len)?;
 
     let (recv_size, socket_addr) = socket.recvfrom(&mut buffer, flags)?;
     if buf != 0 {
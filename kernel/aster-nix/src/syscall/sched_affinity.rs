@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use aster_frame::cpu::{num_cpus, this_cpu, CpuSet};
+
+use super::{
+    sched_scheduler::{check_sched_perm, get_thread},
+    SyscallReturn, SYS_SCHED_GETAFFINITY, SYS_SCHED_SETAFFINITY,
+};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::posix_thread::PosixThreadExt,
+    thread::Thread,
+    util::{read_bytes_from_user, write_bytes_to_user},
+};
+
+pub fn sys_sched_setaffinity(tid: i32, len: usize, mask_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_SETAFFINITY);
+    debug!(
+        "tid = {}, len = {}, mask_addr = 0x{:x}",
+        tid, len, mask_addr
+    );
+
+    let thread = get_thread(tid)?;
+    let Some(posix_thread) = thread.as_posix_thread() else {
+        return_errno_with_message!(Errno::EINVAL, "not a posix thread");
+    };
+    check_sched_perm(&thread)?;
+
+    // The bits beyond the online CPUs are ignored.
+    let mut mask = vec![0u8; len.min(cpu_mask_size())];
+    read_bytes_from_user(mask_addr, &mut mask)?;
+    let mut cpu_affinity = CpuSet::new_empty();
+    for cpu_id in 0..num_cpus() {
+        let byte = mask.get(cpu_id as usize / 8).copied().unwrap_or(0);
+        if byte & (1 << (cpu_id % 8)) != 0 {
+            cpu_affinity.add(cpu_id);
+        }
+    }
+    if cpu_affinity.iter().next().is_none() {
+        return_errno_with_message!(Errno::EINVAL, "no online CPU is in the mask");
+    }
+
+    let is_allowed_here = cpu_affinity.contains(this_cpu());
+    *posix_thread.cpu_affinity().lock_irq_disabled() = cpu_affinity;
+
+    // Migrate the current thread at once if it is not allowed to run on the current CPU.
+    // Other threads are migrated the next time they are scheduled.
+    if Arc::ptr_eq(&thread, &current_thread!()) && !is_allowed_here {
+        Thread::yield_now();
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_sched_getaffinity(tid: i32, len: usize, mask_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_GETAFFINITY);
+    debug!(
+        "tid = {}, len = {}, mask_addr = 0x{:x}",
+        tid, len, mask_addr
+    );
+
+    let mask_size = cpu_mask_size();
+    if len < mask_size || len % core::mem::size_of::<u64>() != 0 {
+        return_errno_with_message!(Errno::EINVAL, "the mask is too small or misaligned");
+    }
+
+    let thread = get_thread(tid)?;
+    let mut mask = vec![0u8; mask_size];
+    match thread.as_posix_thread() {
+        Some(posix_thread) => {
+            let cpu_affinity = posix_thread.cpu_affinity().lock_irq_disabled();
+            for cpu_id in cpu_affinity.iter() {
+                mask[cpu_id / 8] |= 1 << (cpu_id % 8);
+            }
+        }
+        None => {
+            for cpu_id in thread.task().cpu_affinity().iter() {
+                mask[cpu_id / 8] |= 1 << (cpu_id % 8);
+            }
+        }
+    }
+    write_bytes_to_user(mask_addr, &mask)?;
+    Ok(SyscallReturn::Return(mask_size as _))
+}
+
+/// Returns the size of the CPU mask in bytes, which is the number of online
+/// CPUs rounded up to a multiple of the bits of `u64`.
+fn cpu_mask_size() -> usize {
+    (num_cpus() as usize).div_ceil(u64::BITS as usize) * core::mem::size_of::<u64>()
+}
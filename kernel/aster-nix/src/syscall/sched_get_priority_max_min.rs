@@ -0,0 +1,27 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    sched_scheduler::{SCHED_BATCH, SCHED_FIFO, SCHED_IDLE, SCHED_OTHER, SCHED_RR},
+    SyscallReturn, SYS_SCHED_GET_PRIORITY_MAX, SYS_SCHED_GET_PRIORITY_MIN,
+};
+use crate::{log_syscall_entry, prelude::*, sched::policy::RealTimePriority};
+
+pub fn sys_sched_get_priority_max(policy: i32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_GET_PRIORITY_MAX);
+    let priority = match policy {
+        SCHED_FIFO | SCHED_RR => RealTimePriority::MAX.to_raw(),
+        SCHED_OTHER | SCHED_BATCH | SCHED_IDLE => 0,
+        _ => return_errno_with_message!(Errno::EINVAL, "invalid scheduling policy"),
+    };
+    Ok(SyscallReturn::Return(priority as _))
+}
+
+pub fn sys_sched_get_priority_min(policy: i32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_GET_PRIORITY_MIN);
+    let priority = match policy {
+        SCHED_FIFO | SCHED_RR => RealTimePriority::MIN.to_raw(),
+        SCHED_OTHER | SCHED_BATCH | SCHED_IDLE => 0,
+        _ => return_errno_with_message!(Errno::EINVAL, "invalid scheduling policy"),
+    };
+    Ok(SyscallReturn::Return(priority as _))
+}
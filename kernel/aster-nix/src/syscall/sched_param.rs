@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{
+    sched_scheduler::{get_thread, read_sched_param, set_sched_policy, SchedParam},
+    SyscallReturn, SYS_SCHED_GETPARAM, SYS_SCHED_SETPARAM,
+};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    sched::policy::{RealTimePriority, SchedPolicy},
+    util::write_val_to_user,
+};
+
+pub fn sys_sched_setparam(tid: i32, param_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_SETPARAM);
+    debug!("tid = {}, param_addr = 0x{:x}", tid, param_addr);
+
+    let thread = get_thread(tid)?;
+    let param = read_sched_param(param_addr)?;
+    let priority = param.sched_priority;
+    let is_valid_real_time_priority = priority >= RealTimePriority::MIN.to_raw() as i32
+        && priority <= RealTimePriority::MAX.to_raw() as i32;
+    // Only the priority is changed, while the policy is kept.
+    let policy = match thread.sched_entity().policy() {
+        SchedPolicy::Fifo(_) if is_valid_real_time_priority => {
+            SchedPolicy::Fifo(RealTimePriority::new(priority as u8))
+        }
+        SchedPolicy::RoundRobin(_) if is_valid_real_time_priority => {
+            SchedPolicy::RoundRobin(RealTimePriority::new(priority as u8))
+        }
+        policy if !policy.is_real_time() && priority == 0 => policy,
+        _ => return_errno_with_message!(Errno::EINVAL, "invalid priority for the policy"),
+    };
+    set_sched_policy(&thread, policy)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_sched_getparam(tid: i32, param_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_GETPARAM);
+    debug!("tid = {}, param_addr = 0x{:x}", tid, param_addr);
+
+    if param_addr == 0 {
+        return_errno_with_message!(Errno::EINVAL, "param is NULL");
+    }
+    let thread = get_thread(tid)?;
+    let sched_priority = thread
+        .sched_entity()
+        .policy()
+        .real_time_priority()
+        .map_or(0, |priority| priority.to_raw() as i32);
+    write_val_to_user(param_addr, &SchedParam { sched_priority })?;
+    Ok(SyscallReturn::Return(0))
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{sched_scheduler::get_thread, SyscallReturn, SYS_SCHED_RR_GET_INTERVAL};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    sched::{policy::SchedPolicy, ROUND_ROBIN_TIME_SLICE_NS},
+    time::timespec_t,
+    util::write_val_to_user,
+};
+
+pub fn sys_sched_rr_get_interval(tid: i32, interval_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_RR_GET_INTERVAL);
+    debug!("tid = {}, interval_addr = 0x{:x}", tid, interval_addr);
+
+    let thread = get_thread(tid)?;
+    // Only the threads with the round-robin policy have fixed time slices.
+    let interval = match thread.sched_entity().policy() {
+        SchedPolicy::RoundRobin(_) => Duration::from_nanos(ROUND_ROBIN_TIME_SLICE_NS),
+        _ => Duration::ZERO,
+    };
+    write_val_to_user(interval_addr, &timespec_t::from(interval))?;
+    Ok(SyscallReturn::Return(0))
+}
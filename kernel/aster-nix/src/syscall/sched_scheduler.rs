@@ -0,0 +1,152 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_SCHED_GETSCHEDULER, SYS_SCHED_SETSCHEDULER};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::{credentials, posix_thread::PosixThreadExt, ResourceType},
+    sched::policy::{RealTimePriority, SchedPolicy},
+    thread::{thread_table, Thread, Tid},
+    util::read_val_from_user,
+};
+
+pub fn sys_sched_setscheduler(tid: i32, policy: i32, param_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_SETSCHEDULER);
+    debug!(
+        "tid = {}, policy = {}, param_addr = 0x{:x}",
+        tid, policy, param_addr
+    );
+
+    let thread = get_thread(tid)?;
+    let param = read_sched_param(param_addr)?;
+    let policy = SchedPolicy::try_from_raw(policy & !SCHED_RESET_ON_FORK, param.sched_priority)?;
+    set_sched_policy(&thread, policy)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_sched_getscheduler(tid: i32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SCHED_GETSCHEDULER);
+    debug!("tid = {}", tid);
+
+    let thread = get_thread(tid)?;
+    let policy = thread.sched_entity().policy();
+    Ok(SyscallReturn::Return(policy.to_raw() as _))
+}
+
+pub(super) const SCHED_OTHER: i32 = 0;
+pub(super) const SCHED_FIFO: i32 = 1;
+pub(super) const SCHED_RR: i32 = 2;
+pub(super) const SCHED_BATCH: i32 = 3;
+pub(super) const SCHED_IDLE: i32 = 5;
+
+/// The flag that can be ORed into the policy to not inherit the policy on fork,
+/// which is accepted but not supported yet.
+const SCHED_RESET_ON_FORK: i32 = 0x4000_0000;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod)]
+pub(super) struct SchedParam {
+    pub(super) sched_priority: i32,
+}
+
+impl SchedPolicy {
+    fn try_from_raw(policy: i32, priority: i32) -> Result<Self> {
+        let real_time_priority = || {
+            if priority < RealTimePriority::MIN.to_raw() as i32
+                || priority > RealTimePriority::MAX.to_raw() as i32
+            {
+                return_errno_with_message!(Errno::EINVAL, "invalid real-time priority");
+            }
+            Ok(RealTimePriority::new(priority as u8))
+        };
+        let policy = match policy {
+            SCHED_FIFO => Self::Fifo(real_time_priority()?),
+            SCHED_RR => Self::RoundRobin(real_time_priority()?),
+            SCHED_OTHER | SCHED_BATCH | SCHED_IDLE if priority != 0 => {
+                return_errno_with_message!(Errno::EINVAL, "non-real-time priority must be 0");
+            }
+            SCHED_OTHER => Self::Normal,
+            SCHED_BATCH => Self::Batch,
+            SCHED_IDLE => Self::Idle,
+            _ => return_errno_with_message!(Errno::EINVAL, "invalid scheduling policy"),
+        };
+        Ok(policy)
+    }
+
+    fn to_raw(self) -> i32 {
+        match self {
+            Self::Fifo(_) => SCHED_FIFO,
+            Self::RoundRobin(_) => SCHED_RR,
+            Self::Normal => SCHED_OTHER,
+            Self::Batch => SCHED_BATCH,
+            Self::Idle => SCHED_IDLE,
+        }
+    }
+}
+
+/// Gets the thread of the given TID, where 0 means the calling thread.
+pub(super) fn get_thread(tid: i32) -> Result<Arc<Thread>> {
+    if tid < 0 {
+        return_errno_with_message!(Errno::EINVAL, "negative tid");
+    }
+    if tid == 0 {
+        return Ok(current_thread!());
+    }
+    thread_table::get_thread(tid as Tid).ok_or(Error::with_message(
+        Errno::ESRCH,
+        "the thread does not exist",
+    ))
+}
+
+pub(super) fn read_sched_param(param_addr: Vaddr) -> Result<SchedParam> {
+    if param_addr == 0 {
+        return_errno_with_message!(Errno::EINVAL, "param is NULL");
+    }
+    read_val_from_user(param_addr)
+}
+
+/// Checks whether the current thread is allowed to change the scheduling attributes
+/// of the thread.
+///
+/// The current thread must either be privileged, or its effective user ID must equal
+/// the real or effective user ID of the thread.
+pub(super) fn check_sched_perm(thread: &Thread) -> Result<()> {
+    let euid = credentials().euid();
+    if euid.is_root() {
+        return Ok(());
+    }
+
+    if let Some(posix_thread) = thread.as_posix_thread() {
+        let target_credentials = posix_thread.credentials();
+        if euid == target_credentials.ruid() || euid == target_credentials.euid() {
+            return Ok(());
+        }
+    }
+
+    return_errno_with_message!(
+        Errno::EPERM,
+        "changing the scheduling attributes of the thread is not allowed"
+    );
+}
+
+/// Sets the scheduling policy of a thread, which requires the permission if
+/// the thread belongs to another user or the policy is a real-time one.
+pub(super) fn set_sched_policy(thread: &Thread, policy: SchedPolicy) -> Result<()> {
+    check_sched_perm(thread)?;
+    if let Some(priority) = policy.real_time_priority() {
+        let is_root = credentials().euid().is_root();
+        let rtprio_limit = current!()
+            .resource_limits()
+            .lock()
+            .get_rlimit(ResourceType::RLIMIT_RTPRIO)
+            .get_cur();
+        if !is_root && priority.to_raw() as u64 > rtprio_limit {
+            return_errno_with_message!(
+                Errno::EPERM,
+                "the real-time priority exceeds RLIMIT_RTPRIO"
+            );
+        }
+    }
+    thread.sched_entity().set_policy(policy);
+    Ok(())
+}
@@ -0,0 +1,153 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::{mem, time::Duration};
+
+use super::{SyscallReturn, SYS_SEMCTL, SYS_SEMGET, SYS_SEMOP, SYS_SEMTIMEDOP};
+use crate::{
+    ipc::{
+        sem::{get_semaphore_set, semget, SemBuf, SEMOPM},
+        IpcFlags, IpcId, IpcKey,
+    },
+    log_syscall_entry,
+    prelude::*,
+    time::timespec_t,
+    util::{read_bytes_from_user, read_val_from_user, write_bytes_to_user, write_val_to_user},
+};
+
+pub fn sys_semget(key: IpcKey, nsems: i32, semflg: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SEMGET);
+    debug!("key = {}, nsems = {}, semflg = 0o{:o}", key, nsems, semflg);
+    if nsems < 0 {
+        return_errno_with_message!(Errno::EINVAL, "the number of semaphores is negative");
+    }
+    let flags = IpcFlags::from_bits_truncate(semflg);
+    let mode = (semflg & 0o777) as u16;
+    let id = semget(key, nsems as usize, flags, mode)?;
+    Ok(SyscallReturn::Return(id as _))
+}
+
+pub fn sys_semop(semid: IpcId, sops: Vaddr, nsops: usize) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SEMOP);
+    do_semtimedop(semid, sops, nsops, None)
+}
+
+pub fn sys_semtimedop(
+    semid: IpcId,
+    sops: Vaddr,
+    nsops: usize,
+    timeout: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SEMTIMEDOP);
+    let timeout = if timeout == 0 {
+        None
+    } else {
+        let timespec: timespec_t = read_val_from_user(timeout)?;
+        if timespec.sec < 0 || !(0..1_000_000_000).contains(&timespec.nsec) {
+            return_errno_with_message!(Errno::EINVAL, "invalid timeout");
+        }
+        Some(Duration::from(timespec))
+    };
+    do_semtimedop(semid, sops, nsops, timeout)
+}
+
+fn do_semtimedop(
+    semid: IpcId,
+    sops: Vaddr,
+    nsops: usize,
+    timeout: Option<Duration>,
+) -> Result<SyscallReturn> {
+    debug!(
+        "semid = {}, sops = 0x{:x}, nsops = {}, timeout = {:?}",
+        semid, sops, nsops, timeout
+    );
+    if nsops == 0 {
+        return_errno_with_message!(Errno::EINVAL, "no operation is given");
+    }
+    if nsops > SEMOPM {
+        return_errno_with_message!(Errno::E2BIG, "too many operations");
+    }
+    let ops = (0..nsops)
+        .map(|i| read_val_from_user::<SemBuf>(sops + i * mem::size_of::<SemBuf>()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let set = get_semaphore_set(semid)?;
+    set.semop(&ops, timeout.as_ref())?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_semctl(semid: IpcId, semnum: i32, cmd: i32, arg: u64) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SEMCTL);
+    // The `IPC_64` flag is ignored since only the 64-bit structures are supported.
+    let cmd = SemCtlCmd::try_from(cmd & !IPC_64)?;
+    debug!(
+        "semid = {}, semnum = {}, cmd = {:?}, arg = 0x{:x}",
+        semid, semnum, cmd, arg
+    );
+    let set = get_semaphore_set(semid)?;
+    let semnum = usize::try_from(semnum)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid semaphore number"))?;
+    let addr = arg as Vaddr;
+
+    let res = match cmd {
+        SemCtlCmd::IPC_RMID => {
+            set.remove()?;
+            0
+        }
+        SemCtlCmd::IPC_SET => {
+            let semid_ds = read_val_from_user(addr)?;
+            set.set(&semid_ds)?;
+            0
+        }
+        SemCtlCmd::IPC_STAT => {
+            let semid_ds = set.stat()?;
+            write_val_to_user(addr, &semid_ds)?;
+            0
+        }
+        SemCtlCmd::GETPID => set.last_pid(semnum)? as isize,
+        SemCtlCmd::GETVAL => set.value(semnum)? as isize,
+        SemCtlCmd::GETNCNT => set.ncnt(semnum)? as isize,
+        SemCtlCmd::GETZCNT => set.zcnt(semnum)? as isize,
+        SemCtlCmd::GETALL => {
+            let bytes = set
+                .values()?
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect::<Vec<_>>();
+            write_bytes_to_user(addr, &bytes)?;
+            0
+        }
+        SemCtlCmd::SETVAL => {
+            set.set_value(semnum, arg as i32)?;
+            0
+        }
+        SemCtlCmd::SETALL => {
+            let mut bytes = vec![0u8; set.nsems() * mem::size_of::<u16>()];
+            read_bytes_from_user(addr, &mut bytes)?;
+            let values = bytes
+                .chunks_exact(mem::size_of::<u16>())
+                .map(|chunk| u16::from_ne_bytes([chunk[0], chunk[1]]))
+                .collect::<Vec<_>>();
+            set.set_values(&values)?;
+            0
+        }
+    };
+    Ok(SyscallReturn::Return(res))
+}
+
+const IPC_64: i32 = 0x100;
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[allow(non_camel_case_types)]
+enum SemCtlCmd {
+    IPC_RMID = 0,
+    IPC_SET = 1,
+    IPC_STAT = 2,
+    GETPID = 11,
+    GETVAL = 12,
+    GETALL = 13,
+    GETNCNT = 14,
+    GETZCNT = 15,
+    SETVAL = 16,
+    SETALL = 17,
+}
@@ -7,6 +7,7 @@ use crate::{
     net::socket::SendRecvFlags,
     prelude::*,
     util::{
+        alloc_user_buffer,
         net::{get_socket_from_fd, read_socket_addr_from_user},
         read_bytes_from_user,
     },
@@ -29,7 +30,7 @@ pub fn sys_sendto(
         Some(socket_addr)
     };
     debug!("sockfd = {sockfd}, buf = 0x{buf:x}, len = 0x{len:x}, flags = {flags:?}, socket_addr = {socket_addr:?}");
-    let mut buffer = vec![0u8; len];
+    let mut buffer = alloc_user_buffer(len)?;
     read_bytes_from_user(buf, &mut buffer)?;
 
     let socket = get_socket_from_fd(sockfd)?;
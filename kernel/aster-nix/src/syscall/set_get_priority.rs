@@ -32,6 +32,9 @@ pub fn sys_set_priority(which: i32, who: u32, prio: i32) -> Result<SyscallReturn
     let processes = get_processes(prio_target)?;
     for process in processes.iter() {
         process.nice().store(new_nice, Ordering::Relaxed);
+        for thread in process.threads().lock().iter() {
+            thread.sched_entity().set_nice(new_nice);
+        }
     }
 
     Ok(SyscallReturn::Return(0))
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{SyscallReturn, SYS_GETITIMER, SYS_SETITIMER};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::ITimerKind,
+    time::{itimerval_t, timeval_t},
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_setitimer(
+    which: i32,
+    new_value_ptr: Vaddr,
+    old_value_ptr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SETITIMER);
+    let kind = ITimerKind::try_from(which)?;
+    // Like Linux, a null `new_value` disarms the timer.
+    let new_value = if new_value_ptr != 0 {
+        read_val_from_user::<itimerval_t>(new_value_ptr)?
+    } else {
+        itimerval_t::default()
+    };
+    debug!(
+        "kind = {:?}, new_value = {:?}, old_value_ptr = 0x{:x}",
+        kind, new_value, old_value_ptr
+    );
+    let value = duration_from_timeval(new_value.it_value)?;
+    let interval = duration_from_timeval(new_value.it_interval)?;
+
+    let current = current!();
+    let itimer = current.timers().itimer(kind, &current)?;
+    let (old_value, old_interval) = itimer.set_time(value, interval, false)?;
+
+    if old_value_ptr != 0 {
+        let old_value = itimerval_t {
+            it_interval: timeval_t::from(old_interval),
+            it_value: timeval_t::from(old_value),
+        };
+        write_val_to_user(old_value_ptr, &old_value)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_getitimer(which: i32, curr_value_ptr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_GETITIMER);
+    let kind = ITimerKind::try_from(which)?;
+    debug!("kind = {:?}, curr_value_ptr = 0x{:x}", kind, curr_value_ptr);
+
+    let current = current!();
+    let itimer = current.timers().itimer(kind, &current)?;
+    let (value, interval) = itimer.get_time()?;
+    let curr_value = itimerval_t {
+        it_interval: timeval_t::from(interval),
+        it_value: timeval_t::from(value),
+    };
+    write_val_to_user(curr_value_ptr, &curr_value)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn duration_from_timeval(timeval: timeval_t) -> Result<Duration> {
+    if timeval.sec < 0 || !(0..1_000_000).contains(&timeval.usec) {
+        return_errno_with_message!(Errno::EINVAL, "invalid time");
+    }
+    Ok(Duration::from(timeval))
+}
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_SHMAT, SYS_SHMCTL, SYS_SHMDT, SYS_SHMGET};
+use crate::{
+    ipc::{
+        shm::{get_shm_segment, shmdt, shmget, ShmFlags},
+        IpcFlags, IpcId, IpcKey,
+    },
+    log_syscall_entry,
+    prelude::*,
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_shmget(key: IpcKey, size: usize, shmflg: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SHMGET);
+    debug!("key = {}, size = {}, shmflg = 0o{:o}", key, size, shmflg);
+    let flags = IpcFlags::from_bits_truncate(shmflg);
+    let mode = (shmflg & 0o777) as u16;
+    let id = shmget(key, size, flags, mode)?;
+    Ok(SyscallReturn::Return(id as _))
+}
+
+pub fn sys_shmat(shmid: IpcId, shmaddr: Vaddr, shmflg: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SHMAT);
+    debug!(
+        "shmid = {}, shmaddr = 0x{:x}, shmflg = 0o{:o}",
+        shmid, shmaddr, shmflg
+    );
+    let flags = ShmFlags::from_bits_truncate(shmflg);
+    let addr = get_shm_segment(shmid)?.attach(shmaddr, flags)?;
+    Ok(SyscallReturn::Return(addr as _))
+}
+
+pub fn sys_shmdt(shmaddr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SHMDT);
+    debug!("shmaddr = 0x{:x}", shmaddr);
+    shmdt(shmaddr)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_shmctl(shmid: IpcId, cmd: i32, buf: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SHMCTL);
+    // The `IPC_64` flag is ignored since only the 64-bit structures are supported.
+    let cmd = ShmCtlCmd::try_from(cmd & !IPC_64)?;
+    debug!("shmid = {}, cmd = {:?}, buf = 0x{:x}", shmid, cmd, buf);
+    let segment = get_shm_segment(shmid)?;
+    match cmd {
+        ShmCtlCmd::IPC_RMID => segment.remove()?,
+        ShmCtlCmd::IPC_SET => {
+            let shmid_ds = read_val_from_user(buf)?;
+            segment.set(&shmid_ds)?;
+        }
+        ShmCtlCmd::IPC_STAT => {
+            let shmid_ds = segment.stat()?;
+            write_val_to_user(buf, &shmid_ds)?;
+        }
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+const IPC_64: i32 = 0x100;
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromInt)]
+#[allow(non_camel_case_types)]
+enum ShmCtlCmd {
+    IPC_RMID = 0,
+    IPC_SET = 1,
+    IPC_STAT = 2,
+}
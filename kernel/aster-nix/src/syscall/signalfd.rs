@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_SIGNALFD, SYS_SIGNALFD4};
+use crate::{
+    fs::{
+        file_table::{FdFlags, FileDescripter},
+        signalfd::SignalFile,
+    },
+    log_syscall_entry,
+    prelude::*,
+    process::signal::{
+        c_types::sigset_t,
+        constants::{SIGKILL, SIGSTOP},
+        sig_mask::SigMask,
+    },
+    util::read_val_from_user,
+};
+
+pub fn sys_signalfd(fd: FileDescripter, mask_ptr: Vaddr, sizemask: usize) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SIGNALFD);
+    do_signalfd(fd, mask_ptr, sizemask, SignalFdFlags::empty())
+}
+
+pub fn sys_signalfd4(
+    fd: FileDescripter,
+    mask_ptr: Vaddr,
+    sizemask: usize,
+    flags: u32,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SIGNALFD4);
+    let flags = SignalFdFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    do_signalfd(fd, mask_ptr, sizemask, flags)
+}
+
+fn do_signalfd(
+    fd: FileDescripter,
+    mask_ptr: Vaddr,
+    sizemask: usize,
+    flags: SignalFdFlags,
+) -> Result<SyscallReturn> {
+    debug!(
+        "fd = {}, mask_ptr = 0x{:x}, sizemask = {}, flags = {:?}",
+        fd, mask_ptr, sizemask, flags
+    );
+    if sizemask != core::mem::size_of::<sigset_t>() {
+        return_errno_with_message!(Errno::EINVAL, "invalid size of the signal mask");
+    }
+
+    let mut mask = SigMask::from(read_val_from_user::<sigset_t>(mask_ptr)?);
+    // Like `sigprocmask`, `SIGKILL` and `SIGSTOP` are silently ignored.
+    mask.remove_signal(SIGKILL);
+    mask.remove_signal(SIGSTOP);
+
+    let current = current!();
+    let mut file_table = current.file_table().lock();
+    if fd != -1 {
+        let file = file_table.get_file(fd)?;
+        let Some(signal_file) = file.downcast_ref::<SignalFile>() else {
+            return_errno_with_message!(Errno::EINVAL, "the file is not a signalfd file");
+        };
+        signal_file.set_mask(mask);
+        return Ok(SyscallReturn::Return(fd as _));
+    }
+
+    let signal_file = SignalFile::new(mask, flags.contains(SignalFdFlags::SFD_NONBLOCK));
+    let fd_flags = if flags.contains(SignalFdFlags::SFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+    let fd = file_table.insert(signal_file, fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+bitflags! {
+    struct SignalFdFlags: u32 {
+        const SFD_NONBLOCK = 1 << 11;
+        const SFD_CLOEXEC = 1 << 19;
+    }
+}
@@ -6,7 +6,7 @@ use crate::{
         file_table::FileDescripter,
         fs_resolver::FsPath,
         inode_handle::InodeHandle,
-        utils::{SuperBlock, PATH_MAX},
+        utils::{Dentry, SuperBlock, PATH_MAX},
     },
     log_syscall_entry,
     prelude::*,
@@ -24,7 +24,7 @@ pub fn sys_statfs(path_ptr: Vaddr, statfs_buf_ptr: Vaddr) -> Result<SyscallRetur
         let fs_path = FsPath::try_from(path.as_ref())?;
         current.fs().read().lookup(&fs_path)?
     };
-    let statfs = Statfs::from(dentry.fs().sb());
+    let statfs = Statfs::new(&dentry);
     write_val_to_user(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -40,7 +40,7 @@ pub fn sys_fstatfs(fd: FileDescripter, statfs_buf_ptr: Vaddr) -> Result<SyscallR
         .downcast_ref::<InodeHandle>()
         .ok_or(Error::with_message(Errno::EBADF, "not inode"))?;
     let dentry = inode_handle.dentry();
-    let statfs = Statfs::from(dentry.fs().sb());
+    let statfs = Statfs::new(&dentry);
     write_val_to_user(statfs_buf_ptr, &statfs)?;
     Ok(SyscallReturn::Return(0))
 }
@@ -75,6 +75,20 @@ struct Statfs {
     f_spare: [u64; 4],
 }
 
+/// The flag indicating that `f_flags` is supported, which is the same as Linux.
+const ST_VALID: u64 = 0x0020;
+
+impl Statfs {
+    /// Returns the statistics of the file system that the dentry belongs to, with the
+    /// flags of the mount.
+    fn new(dentry: &Dentry) -> Self {
+        let mut statfs = Self::from(dentry.fs().sb());
+        // The values of the mount flags are the same as the corresponding `ST_*` flags.
+        statfs.f_flags |= dentry.mount_node().flags().bits() as u64 | ST_VALID;
+        statfs
+    }
+}
+
 impl From<SuperBlock> for Statfs {
     fn from(sb: SuperBlock) -> Self {
         Self {
@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_SWAPOFF, SYS_SWAPON};
+use crate::{
+    fs::{fs_resolver::FsPath, registry::open_block_device, utils::InodeType},
+    log_syscall_entry,
+    prelude::*,
+    process::credentials,
+    syscall::constants::MAX_FILENAME_LEN,
+    util::read_cstring_from_user,
+    vm::swap::{swap_off, swap_on, SwapBackend},
+};
+
+/// The flag to set the priority of the swap area.
+const SWAP_FLAG_PREFER: u32 = 0x8000;
+const SWAP_FLAG_PRIO_MASK: u32 = 0x7fff;
+/// The flags to discard the freed swap pages, which are ignored.
+const SWAP_FLAG_DISCARD: u32 = 0x10000;
+const SWAP_FLAG_DISCARD_ONCE: u32 = 0x20000;
+const SWAP_FLAG_DISCARD_PAGES: u32 = 0x40000;
+
+pub fn sys_swapon(path_addr: Vaddr, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SWAPON);
+    let path = read_cstring_from_user(path_addr, MAX_FILENAME_LEN)?;
+    debug!("path = {:?}, flags = 0x{:x}", path, flags);
+
+    let valid_flags = SWAP_FLAG_PREFER
+        | SWAP_FLAG_PRIO_MASK
+        | SWAP_FLAG_DISCARD
+        | SWAP_FLAG_DISCARD_ONCE
+        | SWAP_FLAG_DISCARD_PAGES;
+    if flags & !valid_flags != 0 {
+        return_errno_with_message!(Errno::EINVAL, "invalid swap flags");
+    }
+    let priority = if flags & SWAP_FLAG_PREFER != 0 {
+        Some((flags & SWAP_FLAG_PRIO_MASK) as i16)
+    } else {
+        None
+    };
+
+    let backend = lookup_swap_backend(path)?;
+    swap_on(backend, priority)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_swapoff(path_addr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_SWAPOFF);
+    let path = read_cstring_from_user(path_addr, MAX_FILENAME_LEN)?;
+    debug!("path = {:?}", path);
+
+    let backend = lookup_swap_backend(path)?;
+    swap_off(&backend)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn lookup_swap_backend(path: CString) -> Result<SwapBackend> {
+    if !credentials().euid().is_root() {
+        return_errno_with_message!(Errno::EPERM, "only root can configure swap areas");
+    }
+
+    let path = path.to_string_lossy();
+    if path.is_empty() {
+        return_errno_with_message!(Errno::ENOENT, "path is empty");
+    }
+    let dentry = {
+        let fs_path = FsPath::try_from(path.as_ref())?;
+        current!().fs().read().lookup(&fs_path)?
+    };
+    match dentry.type_() {
+        InodeType::File => Ok(SwapBackend::File(dentry.inode().clone())),
+        // Like the source of a mount, a block device is found by the name of its device file.
+        InodeType::BlockDevice => Ok(SwapBackend::BlockDevice(open_block_device(&path)?)),
+        _ => return_errno_with_message!(
+            Errno::EINVAL,
+            "the swap area must be a file or a block device"
+        ),
+    }
+}
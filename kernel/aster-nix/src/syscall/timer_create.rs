@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_TIMER_CREATE, SYS_TIMER_DELETE};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::{
+        posix_thread::PosixThreadExt,
+        signal::{constants::SIGALRM, sig_num::SigNum},
+        Process, TimerClock, TimerId, TimerNotify,
+    },
+    thread::{thread_table, Tid},
+    time::{clockid_t, ClockID},
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_timer_create(
+    clockid: clockid_t,
+    sigevent_ptr: Vaddr,
+    timer_id_ptr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMER_CREATE);
+    let clock_id = ClockID::try_from(clockid)?;
+    let sigevent = if sigevent_ptr != 0 {
+        Some(read_val_from_user::<sigevent_t>(sigevent_ptr)?)
+    } else {
+        None
+    };
+    debug!(
+        "clock_id = {:?}, sigevent = {:?}, timer_id_ptr = 0x{:x}",
+        clock_id, sigevent, timer_id_ptr
+    );
+
+    let clock = match clock_id {
+        ClockID::CLOCK_REALTIME | ClockID::CLOCK_MONOTONIC | ClockID::CLOCK_BOOTTIME => {
+            TimerClock::Wall(clock_id)
+        }
+        ClockID::CLOCK_PROCESS_CPUTIME_ID => TimerClock::ProcessCpu,
+        ClockID::CLOCK_THREAD_CPUTIME_ID => TimerClock::ThreadCpu(current_thread!().tid()),
+        _ => return_errno_with_message!(Errno::EINVAL, "the clock is not supported by timers"),
+    };
+
+    let current = current!();
+    let notify = match sigevent {
+        Some(sigevent) => Some(parse_sigevent(&sigevent, &current)?),
+        None => None,
+    };
+    let timer_id = current.timers().create_timer(
+        clock,
+        // By default, the timer sends `SIGALRM` to the process with its ID as the value.
+        |timer_id| {
+            notify.unwrap_or(TimerNotify::Signal {
+                signum: SIGALRM,
+                tid: None,
+                value: timer_id as u64,
+            })
+        },
+        &current,
+    )?;
+
+    if let Err(err) = write_val_to_user(timer_id_ptr, &timer_id) {
+        current.timers().delete_timer(timer_id)?;
+        return Err(err);
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_timer_delete(timer_id: TimerId) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMER_DELETE);
+    debug!("timer_id = {}", timer_id);
+
+    current!().timers().delete_timer(timer_id)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn parse_sigevent(sigevent: &sigevent_t, process: &Process) -> Result<TimerNotify> {
+    let notify = SigEventNotify::try_from(sigevent.sigev_notify)?;
+    let tid = match notify {
+        SigEventNotify::SIGEV_NONE => return Ok(TimerNotify::None),
+        SigEventNotify::SIGEV_SIGNAL => None,
+        SigEventNotify::SIGEV_THREAD_ID => {
+            // The thread must be in the same process.
+            let tid = sigevent.sigev_tid as Tid;
+            let is_in_process = thread_table::get_thread(tid)
+                .and_then(|thread| thread.as_posix_thread().map(|thread| thread.process()))
+                .is_some_and(|thread_process| thread_process.pid() == process.pid());
+            if !is_in_process {
+                return_errno_with_message!(Errno::EINVAL, "the thread is not in the process");
+            }
+            Some(tid)
+        }
+        // The notification by a new thread is implemented by the C library with `SIGEV_THREAD_ID`.
+        SigEventNotify::SIGEV_THREAD => {
+            return_errno_with_message!(Errno::EINVAL, "SIGEV_THREAD is not supported by the kernel")
+        }
+    };
+
+    let signum = u8::try_from(sigevent.sigev_signo)
+        .map_err(|_| Error::with_message(Errno::EINVAL, "invalid signal number"))
+        .and_then(SigNum::try_from)?;
+    Ok(TimerNotify::Signal {
+        signum,
+        tid,
+        value: sigevent.sigev_value,
+    })
+}
+
+/// The `sigevent` structure of Linux.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct sigevent_t {
+    sigev_value: u64,
+    sigev_signo: i32,
+    sigev_notify: i32,
+    /// The thread ID for `SIGEV_THREAD_ID`, which is in a union with other fields.
+    sigev_tid: i32,
+    _pad: [i32; 11],
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, TryFromInt)]
+#[repr(i32)]
+enum SigEventNotify {
+    SIGEV_SIGNAL = 0,
+    SIGEV_NONE = 1,
+    SIGEV_THREAD = 2,
+    SIGEV_THREAD_ID = 4,
+}
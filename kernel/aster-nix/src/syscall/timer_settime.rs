@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{SyscallReturn, SYS_TIMER_GETOVERRUN, SYS_TIMER_GETTIME, SYS_TIMER_SETTIME};
+use crate::{
+    log_syscall_entry,
+    prelude::*,
+    process::TimerId,
+    time::{itimerspec_t, timespec_t, TIMER_ABSTIME},
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_timer_settime(
+    timer_id: TimerId,
+    flags: i32,
+    new_value_ptr: Vaddr,
+    old_value_ptr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMER_SETTIME);
+    let new_value = read_val_from_user::<itimerspec_t>(new_value_ptr)?;
+    debug!(
+        "timer_id = {}, flags = {}, new_value = {:?}, old_value_ptr = 0x{:x}",
+        timer_id, flags, new_value, old_value_ptr
+    );
+    let value = duration_from_timespec(new_value.it_value)?;
+    let interval = duration_from_timespec(new_value.it_interval)?;
+
+    let timer = current!().timers().get_timer(timer_id)?;
+    let (old_value, old_interval) = timer.set_time(value, interval, flags & TIMER_ABSTIME != 0)?;
+
+    if old_value_ptr != 0 {
+        let old_value = itimerspec_t {
+            it_interval: timespec_t::from(old_interval),
+            it_value: timespec_t::from(old_value),
+        };
+        write_val_to_user(old_value_ptr, &old_value)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_timer_gettime(timer_id: TimerId, curr_value_ptr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMER_GETTIME);
+    debug!(
+        "timer_id = {}, curr_value_ptr = 0x{:x}",
+        timer_id, curr_value_ptr
+    );
+
+    let timer = current!().timers().get_timer(timer_id)?;
+    let (value, interval) = timer.get_time()?;
+    let curr_value = itimerspec_t {
+        it_interval: timespec_t::from(interval),
+        it_value: timespec_t::from(value),
+    };
+    write_val_to_user(curr_value_ptr, &curr_value)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_timer_getoverrun(timer_id: TimerId) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMER_GETOVERRUN);
+    debug!("timer_id = {}", timer_id);
+
+    let timer = current!().timers().get_timer(timer_id)?;
+    Ok(SyscallReturn::Return(timer.overrun() as _))
+}
+
+fn duration_from_timespec(timespec: timespec_t) -> Result<Duration> {
+    if timespec.sec < 0 || !(0..1_000_000_000).contains(&timespec.nsec) {
+        return_errno_with_message!(Errno::EINVAL, "invalid time");
+    }
+    Ok(Duration::from(timespec))
+}
@@ -0,0 +1,121 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use core::time::Duration;
+
+use super::{SyscallReturn, SYS_TIMERFD_CREATE, SYS_TIMERFD_GETTIME, SYS_TIMERFD_SETTIME};
+use crate::{
+    fs::{
+        file_handle::FileLike,
+        file_table::{FdFlags, FileDescripter},
+        timerfd::TimerFile,
+    },
+    log_syscall_entry,
+    prelude::*,
+    time::{clockid_t, itimerspec_t, timespec_t, ClockID},
+    util::{read_val_from_user, write_val_to_user},
+};
+
+pub fn sys_timerfd_create(clockid: clockid_t, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMERFD_CREATE);
+    let clock_id = ClockID::try_from(clockid)?;
+    let flags = TimerFdFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    debug!("clock_id = {:?}, flags = {:?}", clock_id, flags);
+
+    let timer_file = TimerFile::new(clock_id, flags.contains(TimerFdFlags::TFD_NONBLOCK))?;
+    let fd_flags = if flags.contains(TimerFdFlags::TFD_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let current = current!();
+    let fd = current
+        .file_table()
+        .lock()
+        .insert(Arc::new(timer_file), fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+pub fn sys_timerfd_settime(
+    fd: FileDescripter,
+    flags: u32,
+    new_value_ptr: Vaddr,
+    old_value_ptr: Vaddr,
+) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMERFD_SETTIME);
+    let flags = TimerSetFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "invalid flags"))?;
+    let new_value = read_val_from_user::<itimerspec_t>(new_value_ptr)?;
+    debug!(
+        "fd = {}, flags = {:?}, new_value = {:?}, old_value_ptr = 0x{:x}",
+        fd, flags, new_value, old_value_ptr
+    );
+    let value = duration_from_timespec(new_value.it_value)?;
+    let interval = duration_from_timespec(new_value.it_interval)?;
+
+    let file = get_file(fd)?;
+    let timer_file = as_timer_file(&file)?;
+    // `TFD_TIMER_CANCEL_ON_SET` is accepted but has no effect, since the
+    // realtime clock cannot be set yet.
+    let (old_value, old_interval) = timer_file.set_time(
+        value,
+        interval,
+        flags.contains(TimerSetFlags::TFD_TIMER_ABSTIME),
+    )?;
+
+    if old_value_ptr != 0 {
+        let old_value = itimerspec_t {
+            it_interval: timespec_t::from(old_interval),
+            it_value: timespec_t::from(old_value),
+        };
+        write_val_to_user(old_value_ptr, &old_value)?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+pub fn sys_timerfd_gettime(fd: FileDescripter, curr_value_ptr: Vaddr) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_TIMERFD_GETTIME);
+    debug!("fd = {}, curr_value_ptr = 0x{:x}", fd, curr_value_ptr);
+
+    let file = get_file(fd)?;
+    let (value, interval) = as_timer_file(&file)?.get_time()?;
+    let curr_value = itimerspec_t {
+        it_interval: timespec_t::from(interval),
+        it_value: timespec_t::from(value),
+    };
+    write_val_to_user(curr_value_ptr, &curr_value)?;
+    Ok(SyscallReturn::Return(0))
+}
+
+fn get_file(fd: FileDescripter) -> Result<Arc<dyn FileLike>> {
+    let current = current!();
+    let file_table = current.file_table().lock();
+    Ok(file_table.get_file(fd)?.clone())
+}
+
+fn as_timer_file(file: &Arc<dyn FileLike>) -> Result<&TimerFile> {
+    file.downcast_ref::<TimerFile>()
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "the file is not a timerfd file"))
+}
+
+fn duration_from_timespec(timespec: timespec_t) -> Result<Duration> {
+    if timespec.sec < 0 || !(0..1_000_000_000).contains(&timespec.nsec) {
+        return_errno_with_message!(Errno::EINVAL, "invalid time");
+    }
+    Ok(Duration::from(timespec))
+}
+
+bitflags! {
+    struct TimerFdFlags: u32 {
+        const TFD_NONBLOCK = 1 << 11;
+        const TFD_CLOEXEC = 1 << 19;
+    }
+}
+
+bitflags! {
+    struct TimerSetFlags: u32 {
+        const TFD_TIMER_ABSTIME = 1;
+        const TFD_TIMER_CANCEL_ON_SET = 1 << 1;
+    }
+}
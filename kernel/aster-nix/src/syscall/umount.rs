@@ -0,0 +1,87 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_UMOUNT2};
+use crate::{
+    fs::{fs_resolver::FsPath, inode_handle::InodeHandle, utils::MountNode},
+    log_syscall_entry,
+    prelude::*,
+    process::{credentials, process_table, Process},
+    syscall::constants::MAX_FILENAME_LEN,
+    util::read_cstring_from_user,
+};
+
+pub fn sys_umount2(target_addr: Vaddr, flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_UMOUNT2);
+    let target = read_cstring_from_user(target_addr, MAX_FILENAME_LEN)?;
+    let flags = UmountFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unsupported umount flags"))?;
+    debug!("target = {:?}, flags = {:?}", target, flags);
+
+    if !credentials().euid().is_root() {
+        return_errno_with_message!(Errno::EPERM, "only root can unmount file systems");
+    }
+
+    let target_dentry = {
+        let target = target.to_string_lossy();
+        if target.is_empty() {
+            return_errno_with_message!(Errno::ENOENT, "path is empty");
+        }
+        let fs_path = FsPath::try_from(target.as_ref())?;
+        let current = current!();
+        let fs = current.fs().read();
+        if flags.contains(UmountFlags::UMOUNT_NOFOLLOW) {
+            fs.lookup_no_follow(&fs_path)?
+        } else {
+            fs.lookup(&fs_path)?
+        }
+    };
+    if !target_dentry.is_root_of_mount() {
+        return_errno_with_message!(Errno::EINVAL, "the target is not a mount point");
+    }
+
+    // A lazy unmount detaches the mount with its child mounts at once, while the
+    // files that are opened in them are still accessible.
+    if !flags.contains(UmountFlags::MNT_DETACH) {
+        let mount_node = target_dentry.mount_node();
+        if mount_node.has_children() {
+            return_errno_with_message!(Errno::EBUSY, "the mount has child mounts");
+        }
+        if is_mount_in_use(&mount_node) {
+            return_errno_with_message!(Errno::EBUSY, "the mount is in use");
+        }
+        mount_node.sync()?;
+    }
+    target_dentry.umount()?;
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Returns whether any process uses the mount, i.e., opens a file in it or has its
+/// working or root directory in it.
+fn is_mount_in_use(mount_node: &Arc<MountNode>) -> bool {
+    let processes: Vec<Arc<Process>> = process_table::process_table().iter().cloned().collect();
+    processes.iter().any(|process| {
+        let fs = process.fs().read();
+        if fs.cwd().is_in_mount(mount_node) || fs.root().is_in_mount(mount_node) {
+            return true;
+        }
+        drop(fs);
+
+        process
+            .file_table()
+            .lock()
+            .fds_and_files()
+            .any(|(_, file)| {
+                file.downcast_ref::<InodeHandle>()
+                    .is_some_and(|inode_handle| inode_handle.dentry().is_in_mount(mount_node))
+            })
+    })
+}
+
+bitflags! {
+    struct UmountFlags: u32 {
+        /// Not supported. The mount is unmounted as usual.
+        const MNT_FORCE = 1 << 0;
+        const MNT_DETACH = 1 << 1;
+        const UMOUNT_NOFOLLOW = 1 << 3;
+    }
+}
@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MPL-2.0
+
+use super::{SyscallReturn, SYS_USERFAULTFD};
+use crate::{
+    fs::{file_table::FdFlags, userfaultfd::UserfaultFile},
+    log_syscall_entry,
+    prelude::*,
+    process::credentials,
+};
+
+pub fn sys_userfaultfd(flags: u32) -> Result<SyscallReturn> {
+    log_syscall_entry!(SYS_USERFAULTFD);
+    let flags = UserfaultFlags::from_bits(flags)
+        .ok_or_else(|| Error::with_message(Errno::EINVAL, "unknown userfaultfd flags"))?;
+    debug!("flags = {:?}", flags);
+
+    // Like Linux with `vm.unprivileged_userfaultfd` disabled, only privileged users
+    // can handle the faults in the kernel mode.
+    if !flags.contains(UserfaultFlags::UFFD_USER_MODE_ONLY) && !credentials().euid().is_root() {
+        return_errno_with_message!(Errno::EPERM, "handling kernel faults requires privileges");
+    }
+
+    let userfault_file = UserfaultFile::new(flags.contains(UserfaultFlags::O_NONBLOCK))?;
+    let fd_flags = if flags.contains(UserfaultFlags::O_CLOEXEC) {
+        FdFlags::CLOEXEC
+    } else {
+        FdFlags::empty()
+    };
+
+    let current = current!();
+    let fd = current
+        .file_table()
+        .lock()
+        .insert(Arc::new(userfault_file), fd_flags);
+    Ok(SyscallReturn::Return(fd as _))
+}
+
+bitflags! {
+    struct UserfaultFlags: u32 {
+        const UFFD_USER_MODE_ONLY = 1;
+        const O_NONBLOCK = 1 << 11;
+        const O_CLOEXEC = 1 << 19;
+    }
+}
@@ -4,7 +4,14 @@ use super::{SyscallReturn, SYS_WAITID};
 use crate::{
     log_syscall_entry,
     prelude::*,
-    process::{wait_child_exit, ProcessFilter, WaitOptions},
+    process::{
+        signal::{
+            c_types::siginfo_t,
+            constants::{CLD_EXITED, CLD_KILLED, CLD_TRAPPED, SIGCHLD},
+        },
+        wait_child_exit, Pid, ProcessFilter, WaitOptions,
+    },
+    util::write_val_to_user,
 };
 
 pub fn sys_waitid(
@@ -14,10 +21,27 @@ pub fn sys_waitid(
     options: u64,
     rusage_addr: u64,
 ) -> Result<SyscallReturn> {
-    // FIXME: what does infoq and rusage use for?
+    // FIXME: what does rusage use for?
     log_syscall_entry!(SYS_WAITID);
     let process_filter = ProcessFilter::from_which_and_id(which, upid);
     let wait_options = WaitOptions::from_bits(options as u32).expect("Unknown wait options");
-    let (exit_code, pid) = wait_child_exit(process_filter, wait_options)?;
-    Ok(SyscallReturn::Return(pid as _))
+    let (pid, status) = wait_child_exit(process_filter, wait_options)?;
+    if pid != 0 && infoq_addr != 0 {
+        write_val_to_user(infoq_addr as _, &siginfo_from_wait_status(pid, status as _))?;
+    }
+    Ok(SyscallReturn::Return(0))
+}
+
+/// Converts the status reported by `wait_child_exit` to the `siginfo_t` of `waitid`.
+fn siginfo_from_wait_status(pid: Pid, status: u32) -> siginfo_t {
+    let (code, si_status) = if status & 0x7f == 0x7f {
+        (CLD_TRAPPED, (status >> 8) & 0xff)
+    } else if status & 0x7f == 0 {
+        (CLD_EXITED, (status >> 8) & 0xff)
+    } else {
+        (CLD_KILLED, status & 0x7f)
+    };
+    let mut siginfo = siginfo_t::new(SIGCHLD, code);
+    siginfo.set_sigchld_fields(pid, si_status as i32);
+    siginfo
 }
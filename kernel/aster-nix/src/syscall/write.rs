@@ -2,8 +2,11 @@
 
 use super::SyscallReturn;
 use crate::{
-    fs::file_table::FileDescripter, log_syscall_entry, prelude::*, syscall::SYS_WRITE,
-    util::read_bytes_from_user,
+    fs::file_table::FileDescripter,
+    log_syscall_entry,
+    prelude::*,
+    syscall::SYS_WRITE,
+    util::{alloc_user_buffer, read_bytes_from_user},
 };
 
 const STDOUT: u64 = 1;
@@ -27,7 +30,7 @@ pub fn sys_write(
         return Ok(SyscallReturn::Return(0));
     }
 
-    let mut buffer = vec![0u8; user_buf_len];
+    let mut buffer = alloc_user_buffer(user_buf_len)?;
     read_bytes_from_user(user_buf_ptr, &mut buffer)?;
     debug!("write content = {:?}", buffer);
     let write_len = file.write(&buffer)?;
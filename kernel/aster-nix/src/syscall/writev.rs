@@ -6,7 +6,7 @@ use crate::{
     log_syscall_entry,
     prelude::*,
     syscall::SYS_WRITEV,
-    util::{read_bytes_from_user, read_val_from_user},
+    util::{alloc_user_buffer, read_bytes_from_user, read_val_from_user},
 };
 
 const IOVEC_MAX: usize = 256;
@@ -47,7 +47,7 @@ fn do_sys_writev(fd: FileDescripter, io_vec_ptr: Vaddr, io_vec_count: usize) ->
         let buffer = {
             let base = io_vec.base;
             let len = io_vec.len;
-            let mut buffer = vec![0u8; len];
+            let mut buffer = alloc_user_buffer(len)?;
             read_bytes_from_user(base, &mut buffer)?;
             buffer
         };
@@ -3,8 +3,14 @@
 use aster_frame::{cpu::*, vm::VmIo};
 
 use crate::{
-    prelude::*, process::signal::signals::fault::FaultSignal,
-    vm::page_fault_handler::PageFaultHandler,
+    prelude::*,
+    process::signal::signals::fault::FaultSignal,
+    thread::Thread,
+    vm::{
+        oom::out_of_memory,
+        page_fault_handler::PageFaultHandler,
+        reclaim::{reclaim_pages, RECLAIM_BATCH},
+    },
 };
 
 /// We can't handle most exceptions, just send self a fault signal before return to user space.
@@ -39,7 +45,25 @@ fn handle_page_fault(trap_info: &CpuExceptionInfo) {
         // If page is not present or due to write access, we should ask the vmar try to commit this page
         let current = current!();
         let root_vmar = current.root_vmar();
-        if let Err(e) = root_vmar.handle_page_fault(page_fault_addr, not_present, write) {
+        let mut result = root_vmar.handle_page_fault(page_fault_addr, not_present, write);
+        // Reclaim some pages directly if there are no free pages, and then retry.
+        if let Err(e) = &result
+            && e.error() == Errno::ENOMEM
+            && reclaim_pages(RECLAIM_BATCH) > 0
+        {
+            result = root_vmar.handle_page_fault(page_fault_addr, not_present, write);
+        }
+        // Kill a process to free memory if nothing can be reclaimed. The fault is
+        // handled again after returning to the user space, unless the current
+        // process is killed.
+        if let Err(e) = &result
+            && e.error() == Errno::ENOMEM
+            && out_of_memory()
+        {
+            Thread::yield_now();
+            return;
+        }
+        if let Err(e) = result {
             error!(
                 "page fault handler failed: addr: 0x{:x}, err: {:?}",
                 page_fault_addr, e
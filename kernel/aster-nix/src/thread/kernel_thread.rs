@@ -33,6 +33,7 @@ impl KernelThreadExt for Thread {
 
     fn new_kernel_thread(mut thread_options: ThreadOptions) -> Arc<Self> {
         let task_fn = thread_options.take_func();
+        let cpu_affinity = thread_options.cpu_affinity;
         let thread_fn = move || {
             task_fn();
             let current_thread = current_thread!();
@@ -44,6 +45,7 @@ impl KernelThreadExt for Thread {
             let weal_thread = thread_ref.clone();
             let task = TaskOptions::new(thread_fn)
                 .data(weal_thread)
+                .cpu_affinity(cpu_affinity)
                 .build()
                 .unwrap();
             let status = ThreadStatus::Init;
@@ -7,7 +7,10 @@ use core::sync::atomic::{AtomicU32, Ordering};
 use aster_frame::task::Task;
 
 use self::status::ThreadStatus;
-use crate::prelude::*;
+use crate::{
+    prelude::*,
+    sched::{policy::SchedPolicy, SchedEntity},
+};
 
 pub mod exception;
 pub mod kernel_thread;
@@ -32,6 +35,8 @@ pub struct Thread {
 
     // mutable part
     status: Mutex<ThreadStatus>,
+    /// Scheduling state
+    sched_entity: SchedEntity,
 }
 
 impl Thread {
@@ -42,11 +47,13 @@ impl Thread {
         data: impl Send + Sync + Any,
         status: ThreadStatus,
     ) -> Self {
+        let sched_entity = SchedEntity::new(SchedPolicy::from(task.priority()));
         Thread {
             tid,
             task,
             data: Box::new(data),
             status: Mutex::new(status),
+            sched_entity,
         }
     }
 
@@ -82,6 +89,10 @@ impl Thread {
         &self.status
     }
 
+    pub fn sched_entity(&self) -> &SchedEntity {
+        &self.sched_entity
+    }
+
     pub fn yield_now() {
         Task::yield_now()
     }
@@ -90,6 +101,10 @@ impl Thread {
         self.tid
     }
 
+    pub fn task(&self) -> &Arc<Task> {
+        &self.task
+    }
+
     // The return type must be borrowed box, otherwise the downcast_ref will fail
     #[allow(clippy::borrowed_box)]
     pub fn data(&self) -> &Box<dyn Send + Sync + Any> {
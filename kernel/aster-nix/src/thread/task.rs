@@ -30,6 +30,8 @@ pub fn create_new_user_task(user_space: Arc<UserSpace>, thread_ref: Weak<Thread>
             "[Task entry] rax = 0x{:x}",
             user_mode.context().syscall_ret()
         );
+        // A new thread that is traced should stop before it runs any user code.
+        handle_pending_signal(user_mode.context_mut()).unwrap();
         loop {
             let user_event = user_mode.execute();
             let context = user_mode.context_mut();
@@ -61,6 +61,13 @@ impl From<timespec_t> for Duration {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+pub struct itimerspec_t {
+    pub it_interval: timespec_t,
+    pub it_value: timespec_t,
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone, Pod)]
 pub struct timeval_t {
@@ -68,6 +75,13 @@ pub struct timeval_t {
     pub usec: suseconds_t,
 }
 
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, Pod)]
+pub struct itimerval_t {
+    pub it_interval: timeval_t,
+    pub it_value: timeval_t,
+}
+
 impl From<Duration> for timeval_t {
     fn from(duration: Duration) -> timeval_t {
         let sec = duration.as_secs() as time_t;
@@ -96,14 +110,8 @@ pub fn now_as_duration(clock_id: &ClockID) -> Result<Duration> {
             let now = SystemTime::now();
             now.duration_since(&SystemTime::UNIX_EPOCH)
         }
-        _ => {
-            warn!(
-                "unsupported clock_id: {:?}, treat it as CLOCK_REALTIME",
-                clock_id
-            );
-            let now = SystemTime::now();
-            now.duration_since(&SystemTime::UNIX_EPOCH)
-        }
+        ClockID::CLOCK_PROCESS_CPUTIME_ID => Ok(current!().timers().cpu_time()),
+        ClockID::CLOCK_THREAD_CPUTIME_ID => Ok(current_thread!().sched_entity().runtime()),
     }
 }
 
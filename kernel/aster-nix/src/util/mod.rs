@@ -35,6 +35,17 @@ pub fn write_bytes_to_user(dest: Vaddr, src: &[u8]) -> Result<()> {
     Ok(root_vmar.write_bytes(dest, src)?)
 }
 
+/// Allocates a zeroed buffer whose length is given by the user.
+///
+/// Unlike `vec![0u8; len]`, it fails with `ENOMEM` rather than panicking the kernel if
+/// the memory is exhausted.
+pub fn alloc_user_buffer(len: usize) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    buffer.try_reserve_exact(len)?;
+    buffer.resize(len, 0);
+    Ok(buffer)
+}
+
 /// Write `val` to the user space of the current process.
 pub fn write_val_to_user<T: Pod>(dest: Vaddr, val: &T) -> Result<()> {
     let current = current!();
@@ -16,7 +16,10 @@
 //! In Asterinas, VMARs and VMOs, as well as other capabilities, are implemented
 //! as zero-cost capabilities.
 
+pub mod oom;
 pub mod page_fault_handler;
 pub mod perms;
+pub mod reclaim;
+pub mod swap;
 pub mod vmar;
 pub mod vmo;
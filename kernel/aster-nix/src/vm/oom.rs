@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! The out-of-memory (OOM) killer.
+//!
+//! When the memory is exhausted and no pages can be reclaimed, a victim process
+//! is killed to free its memory. Like Linux, the victim is the one with the highest
+//! badness score, which is the resident set size adjusted by `oom_score_adj`.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use aster_frame::{
+    timer::read_monotonic_milli_seconds,
+    vm::{register_out_of_memory_handler, total_frames, OutOfMemoryAction},
+};
+
+use super::reclaim::{is_reclaimer_thread, request_out_of_memory};
+use crate::{
+    prelude::*,
+    process::{
+        posix_thread::PosixThreadExt,
+        process_table,
+        signal::{constants::SIGKILL, signals::kernel::KernelSignal},
+        Pid, Process,
+    },
+    thread::Thread,
+};
+
+/// The minimum `oom_score_adj`, with which a process is never killed.
+pub const OOM_SCORE_ADJ_MIN: i16 = -1000;
+/// The maximum `oom_score_adj`, with which a process is always killed first.
+pub const OOM_SCORE_ADJ_MAX: i16 = 1000;
+
+/// The init process is never killed.
+const INIT_PROCESS_PID: Pid = 1;
+
+/// The time in milliseconds that a victim is given to exit before another victim is chosen.
+const OOM_VICTIM_TIMEOUT_MS: u64 = 1000;
+
+static OOM_STATE: Mutex<OomState> = Mutex::new(OomState {
+    victim: Weak::new(),
+    killed_at_ms: 0,
+    stuck_victims: Vec::new(),
+});
+/// The PID of the last victim, which can be read without locking `OOM_STATE`.
+static OOM_VICTIM_PID: AtomicU32 = AtomicU32::new(0);
+
+struct OomState {
+    /// The last victim, which is given time to exit before another victim is chosen.
+    victim: Weak<Process>,
+    /// The time when the last victim is killed.
+    killed_at_ms: u64,
+    /// The victims that fail to exit in time, e.g., the ones waiting for the memory
+    /// held by others. They are not chosen again.
+    stuck_victims: Vec<Weak<Process>>,
+}
+
+/// Kills a process to free memory.
+///
+/// Returns whether a process is killed or is still exiting after being killed.
+/// If so, the allocation that fails may be retried later.
+pub fn out_of_memory() -> bool {
+    let mut state = OOM_STATE.lock();
+    if let Some(victim) = state.victim.upgrade()
+        && !victim.is_zombie()
+    {
+        if read_monotonic_milli_seconds() - state.killed_at_ms < OOM_VICTIM_TIMEOUT_MS {
+            return true;
+        }
+        warn!("OOM victim {} fails to exit in time", victim.pid());
+        state.stuck_victims.push(Arc::downgrade(&victim));
+    }
+    state
+        .stuck_victims
+        .retain(|victim| victim.upgrade().is_some_and(|victim| !victim.is_zombie()));
+
+    let processes: Vec<Arc<Process>> = process_table::process_table().iter().cloned().collect();
+    let Some((victim, badness)) = processes
+        .iter()
+        .filter(|process| {
+            !state
+                .stuck_victims
+                .iter()
+                .any(|victim| victim.as_ptr() == Arc::as_ptr(process))
+        })
+        .filter_map(|process| oom_badness(process).map(|badness| (process, badness)))
+        .max_by_key(|(_, badness)| *badness)
+    else {
+        error!("Out of memory and no killable processes");
+        return false;
+    };
+
+    error!(
+        "Out of memory: killed process {} ({}) total-rss: {}kB, oom_score_adj: {}, oom_score: {}",
+        victim.pid(),
+        victim.executable_path(),
+        rss_pages(victim) * PAGE_SIZE / 1024,
+        victim.oom_score_adj().load(Ordering::Relaxed),
+        oom_score(victim),
+    );
+    victim.enqueue_signal(KernelSignal::new(SIGKILL));
+    state.victim = Arc::downgrade(victim);
+    state.killed_at_ms = read_monotonic_milli_seconds();
+    OOM_VICTIM_PID.store(victim.pid(), Ordering::Relaxed);
+    true
+}
+
+/// Handles the failed allocations of the page frames and the kernel heap.
+pub fn init() {
+    register_out_of_memory_handler(handle_alloc_failure);
+}
+
+/// Asks the reclaimer thread to reclaim pages, or to kill a process if nothing can
+/// be reclaimed.
+///
+/// The victim itself uses the reserved memory instead, so that it can go on until
+/// it handles the kill signal and exits to free its memory. So does the reclaimer
+/// thread, which cannot wait for itself.
+fn handle_alloc_failure() -> OutOfMemoryAction {
+    let is_victim = Thread::current()
+        .as_posix_thread()
+        .is_some_and(|posix_thread| {
+            posix_thread.process().pid() == OOM_VICTIM_PID.load(Ordering::Relaxed)
+        });
+    if is_victim || is_reclaimer_thread() {
+        return OutOfMemoryAction::UseReserves;
+    }
+    request_out_of_memory();
+    OutOfMemoryAction::Retry
+}
+
+/// Returns the OOM score of the process shown in `/proc/[pid]/oom_score`,
+/// which is in the range of [0, 2000].
+pub fn oom_score(process: &Process) -> usize {
+    let Some(badness) = oom_badness(process) else {
+        return 0;
+    };
+    // Like Linux, the score is normalized by the total memory, and then shifted
+    // so that a negative badness is still shown as a non-negative score.
+    let total_pages = total_frames() as i64;
+    (badness * 1000 / total_pages + 1000).clamp(0, 2000) as usize
+}
+
+/// Returns the badness of the process, i.e., the number of pages that the process
+/// is accounted for. The process with the highest badness is killed first.
+///
+/// Returns `None` if the process cannot be killed.
+fn oom_badness(process: &Process) -> Option<i64> {
+    let oom_score_adj = process.oom_score_adj().load(Ordering::Relaxed);
+    if process.pid() == INIT_PROCESS_PID
+        || process.is_zombie()
+        || oom_score_adj == OOM_SCORE_ADJ_MIN
+    {
+        return None;
+    }
+
+    // Each unit of `oom_score_adj` is worth 0.1% of the total memory.
+    let total_pages = total_frames() as i64;
+    Some(rss_pages(process) as i64 + oom_score_adj as i64 * total_pages / 1000)
+}
+
+fn rss_pages(process: &Process) -> usize {
+    process.root_vmar().vm_space().nr_mapped_pages()
+}
@@ -0,0 +1,154 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Page reclamation.
+//!
+//! When the free frames drop below the low watermark of the frame allocator,
+//! the reclaimer thread is woken up to reclaim pages until the free frames reach
+//! the high watermark. If a page fault fails to allocate memory, pages are also
+//! reclaimed directly by the faulting thread (see `reclaim_pages`). If any other
+//! allocation fails, the reclaimer thread kills a process when nothing can be
+//! reclaimed (see `crate::vm::oom`).
+//!
+//! There are two kinds of reclaimable pages:
+//! * The clean pages in the page caches, which are dropped. The dirty ones are
+//! written back first. The page caches register themselves as `Shrinker`s.
+//! * The anonymous pages, which are written to the swap areas (see `crate::vm::swap`).
+//!
+//! Both kinds of pages are reclaimed in an approximate LRU order. The page caches
+//! keep their pages in LRU lists. The anonymous pages are scanned with the clock
+//! algorithm, i.e., a page is reclaimed only if its accessed bit, which is cleared
+//! in the last scan, is still unset.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use aster_frame::{
+    sync::WaitQueue,
+    vm::{free_frames, register_low_memory_handler, watermarks},
+};
+use spin::Once;
+
+use super::{oom::out_of_memory, swap::is_swap_enabled};
+use crate::{
+    prelude::*,
+    process::process_table,
+    thread::{
+        kernel_thread::{KernelThreadExt, ThreadOptions},
+        Thread, Tid,
+    },
+};
+
+/// The number of pages that are reclaimed in a batch.
+pub const RECLAIM_BATCH: usize = 32;
+
+static SHRINKERS: Mutex<Vec<Weak<dyn Shrinker>>> = Mutex::new(Vec::new());
+
+static RECLAIMER_WAIT_QUEUE: WaitQueue = WaitQueue::new();
+/// Whether the reclaimer thread is requested to reclaim pages.
+static RECLAIM_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// Whether an allocation has failed since the reclaimer thread last ran.
+static OUT_OF_MEMORY_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// The TID of the reclaimer thread.
+static RECLAIMER_TID: Once<Tid> = Once::new();
+
+/// An object that holds reclaimable pages, e.g., a page cache.
+pub trait Shrinker: Send + Sync {
+    /// Scans at most `nr_to_scan` pages that are least recently used, and
+    /// reclaims the ones that are not in use. Returns the number of reclaimed pages.
+    fn shrink(&self, nr_to_scan: usize) -> usize;
+}
+
+/// Registers a shrinker, which is unregistered automatically when it is dropped.
+pub fn register_shrinker(shrinker: Weak<dyn Shrinker>) {
+    let mut shrinkers = SHRINKERS.lock();
+    shrinkers.retain(|shrinker| shrinker.strong_count() > 0);
+    shrinkers.push(shrinker);
+}
+
+/// Reclaims at most `nr_to_reclaim` pages, returning the number of reclaimed pages.
+///
+/// The pages in the page caches are reclaimed first, since they are cheaper to reclaim
+/// than the anonymous pages.
+pub fn reclaim_pages(nr_to_reclaim: usize) -> usize {
+    let mut nr_reclaimed = shrink_page_caches(nr_to_reclaim);
+    if nr_reclaimed < nr_to_reclaim && is_swap_enabled() {
+        nr_reclaimed += reclaim_anonymous_pages(nr_to_reclaim - nr_reclaimed);
+    }
+    nr_reclaimed
+}
+
+fn shrink_page_caches(nr_to_reclaim: usize) -> usize {
+    let shrinkers: Vec<Arc<dyn Shrinker>> =
+        SHRINKERS.lock().iter().filter_map(Weak::upgrade).collect();
+
+    let mut nr_reclaimed = 0;
+    for shrinker in shrinkers {
+        if nr_reclaimed >= nr_to_reclaim {
+            break;
+        }
+        nr_reclaimed += shrinker.shrink(nr_to_reclaim - nr_reclaimed);
+    }
+    nr_reclaimed
+}
+
+fn reclaim_anonymous_pages(nr_to_reclaim: usize) -> usize {
+    let processes: Vec<_> = process_table::process_table().iter().cloned().collect();
+
+    let mut nr_reclaimed = 0;
+    for process in processes {
+        if nr_reclaimed >= nr_to_reclaim {
+            break;
+        }
+        nr_reclaimed += process
+            .root_vmar()
+            .reclaim_pages(nr_to_reclaim - nr_reclaimed);
+    }
+    nr_reclaimed
+}
+
+/// Asks the reclaimer thread to reclaim pages after an allocation fails, and to kill
+/// a process if nothing can be reclaimed.
+pub(super) fn request_out_of_memory() {
+    OUT_OF_MEMORY_REQUESTED.store(true, Ordering::Release);
+    RECLAIM_REQUESTED.store(true, Ordering::Release);
+    RECLAIMER_WAIT_QUEUE.wake_all();
+}
+
+/// Returns whether the current thread is the reclaimer thread, whose allocations
+/// cannot wait for the memory to be freed by itself.
+pub(super) fn is_reclaimer_thread() -> bool {
+    RECLAIMER_TID
+        .get()
+        .is_some_and(|tid| *tid == Thread::current().tid())
+}
+
+/// Starts the reclaimer thread, which is woken up by the frame allocator on low memory.
+pub fn init() {
+    register_low_memory_handler(|| {
+        RECLAIM_REQUESTED.store(true, Ordering::Release);
+        RECLAIMER_WAIT_QUEUE.wake_all();
+    });
+
+    let reclaimer = || loop {
+        RECLAIMER_WAIT_QUEUE.wait_until(|| {
+            RECLAIM_REQUESTED
+                .swap(false, Ordering::Acquire)
+                .then_some(())
+        });
+
+        let mut is_exhausted = false;
+        while free_frames() < watermarks().high {
+            if reclaim_pages(RECLAIM_BATCH) == 0 {
+                // Nothing can be reclaimed for now. Retry when the next allocation
+                // finds the memory low.
+                is_exhausted = true;
+                break;
+            }
+        }
+
+        if OUT_OF_MEMORY_REQUESTED.swap(false, Ordering::Acquire) && is_exhausted {
+            out_of_memory();
+        }
+    };
+    let reclaimer = Thread::spawn_kernel_thread(ThreadOptions::new(reclaimer));
+    RECLAIMER_TID.call_once(|| reclaimer.tid());
+}
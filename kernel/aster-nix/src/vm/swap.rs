@@ -0,0 +1,319 @@
+// SPDX-License-Identifier: MPL-2.0
+
+//! Swap areas.
+//!
+//! Under memory pressure, the anonymous pages that are not recently used are
+//! written to swap areas, so that their page frames can be reclaimed (see
+//! `crate::vm::reclaim`). A swap area is either a block device or a regular
+//! file, which starts with a header written by `mkswap`.
+
+use aster_block::{bio::BioStatus, id::Bid, BlockDevice};
+use aster_frame::vm::{VmAllocOptions, VmFrame, VmIo};
+
+use crate::{fs::utils::Inode, prelude::*};
+
+/// The swap areas in use, which are sorted by their priorities in descending order.
+static SWAP_AREAS: Mutex<Vec<Arc<SwapArea>>> = Mutex::new(Vec::new());
+
+/// The signature at the end of the header page of a swap area.
+const SWAP_SIGNATURE: &[u8] = b"SWAPSPACE2";
+/// The offset of `SwapHeader` in the header page.
+const SWAP_HEADER_OFFSET: usize = 1024;
+/// The maximum number of bad pages recorded in the header page.
+const MAX_SWAP_BADPAGES: usize = (PAGE_SIZE - SWAP_HEADER_OFFSET - 512 - SWAP_SIGNATURE.len()) / 4;
+
+/// The header of a swap area written by `mkswap`, following the boot block.
+#[derive(Debug, Clone, Copy, Pod)]
+#[repr(C)]
+struct SwapHeader {
+    version: u32,
+    /// The index of the last page in the swap area.
+    last_page: u32,
+    nr_badpages: u32,
+    uuid: [u8; 16],
+    volume_name: [u8; 16],
+    _padding: [u32; 117],
+}
+
+/// Returns whether there is any swap area in use.
+pub fn is_swap_enabled() -> bool {
+    !SWAP_AREAS.lock().is_empty()
+}
+
+/// Starts to use the device or the file as a swap area.
+///
+/// A lower priority is assigned to the swap area if `priority` is `None`.
+pub fn swap_on(backend: SwapBackend, priority: Option<i16>) -> Result<()> {
+    let mut swap_areas = SWAP_AREAS.lock();
+    if swap_areas.iter().any(|area| area.backend.is_same(&backend)) {
+        return_errno_with_message!(Errno::EBUSY, "the swap area is already in use");
+    }
+
+    let priority = priority.unwrap_or_else(|| {
+        // Like Linux, the default priorities start from -2 and decrease.
+        swap_areas
+            .iter()
+            .map(|area| area.priority)
+            .filter(|priority| *priority < 0)
+            .min()
+            .map_or(-2, |priority| priority.saturating_sub(1))
+    });
+    let area = Arc::new(SwapArea::new(backend, priority)?);
+    let pos = swap_areas.partition_point(|area| area.priority >= priority);
+    swap_areas.insert(pos, area);
+    Ok(())
+}
+
+/// Stops using the device or the file as a swap area.
+///
+/// The swapped pages in the swap area are read back into memory.
+pub fn swap_off(backend: &SwapBackend) -> Result<()> {
+    let area = {
+        let mut swap_areas = SWAP_AREAS.lock();
+        let Some(pos) = swap_areas
+            .iter()
+            .position(|area| area.backend.is_same(backend))
+        else {
+            return_errno_with_message!(Errno::EINVAL, "the swap area is not in use");
+        };
+        swap_areas.remove(pos)
+    };
+
+    if let Err(err) = area.swap_in_all() {
+        // The swap area is still in use by the pages that fail to be read back.
+        let mut swap_areas = SWAP_AREAS.lock();
+        let pos = swap_areas.partition_point(|other| other.priority >= area.priority);
+        swap_areas.insert(pos, area);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// The device or the file that backs a swap area.
+pub enum SwapBackend {
+    /// A block device, whose blocks are used as the slots.
+    BlockDevice(Arc<dyn BlockDevice>),
+    /// A regular file, whose pages are used as the slots.
+    File(Arc<dyn Inode>),
+}
+
+impl SwapBackend {
+    fn read_page(&self, idx: usize, frame: &VmFrame) -> Result<()> {
+        match self {
+            Self::BlockDevice(device) => {
+                match device.read_block_sync(Bid::new(idx as u64), frame)? {
+                    BioStatus::Complete => Ok(()),
+                    _ => return_errno_with_message!(Errno::EIO, "failed to read the swap area"),
+                }
+            }
+            Self::File(inode) => {
+                let mut buf = vec![0u8; PAGE_SIZE];
+                if inode.read_direct_at(idx * PAGE_SIZE, &mut buf)? != PAGE_SIZE {
+                    return_errno_with_message!(Errno::EIO, "the swap area is truncated");
+                }
+                frame.write_bytes(0, &buf)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_page(&self, idx: usize, frame: &VmFrame) -> Result<()> {
+        match self {
+            Self::BlockDevice(device) => {
+                match device.write_block_sync(Bid::new(idx as u64), frame)? {
+                    BioStatus::Complete => Ok(()),
+                    _ => return_errno_with_message!(Errno::EIO, "failed to write the swap area"),
+                }
+            }
+            Self::File(inode) => {
+                let mut buf = vec![0u8; PAGE_SIZE];
+                frame.read_bytes(0, &mut buf)?;
+                if inode.write_direct_at(idx * PAGE_SIZE, &buf)? != PAGE_SIZE {
+                    return_errno_with_message!(Errno::EIO, "failed to write the swap area");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::BlockDevice(device), Self::BlockDevice(other_device)) => {
+                Arc::ptr_eq(device, other_device)
+            }
+            (Self::File(inode), Self::File(other_inode)) => {
+                inode.ino() == other_inode.ino() && Arc::ptr_eq(&inode.fs(), &other_inode.fs())
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A swap area, which is divided into page-sized slots.
+struct SwapArea {
+    backend: SwapBackend,
+    priority: i16,
+    slots: Mutex<SwapSlots>,
+}
+
+struct SwapSlots {
+    /// The pages that are swapped to each slot. A slot is free if it is `None`.
+    /// The slots of the header and the bad pages are never free.
+    pages: Vec<Option<Weak<SwappedPage>>>,
+    nr_free: usize,
+    /// The slot to start searching for a free slot.
+    next: usize,
+}
+
+impl SwapArea {
+    fn new(backend: SwapBackend, priority: i16) -> Result<Self> {
+        let header_frame = VmAllocOptions::new(1).alloc_single()?;
+        backend.read_page(0, &header_frame)?;
+
+        let mut signature = [0u8; SWAP_SIGNATURE.len()];
+        header_frame.read_bytes(PAGE_SIZE - SWAP_SIGNATURE.len(), &mut signature)?;
+        let header: SwapHeader = header_frame.read_val(SWAP_HEADER_OFFSET)?;
+        if signature != SWAP_SIGNATURE || header.version != 1 || header.last_page == 0 {
+            return_errno_with_message!(Errno::EINVAL, "unable to find the swap signature");
+        }
+        let nr_badpages = header.nr_badpages as usize;
+        if nr_badpages > MAX_SWAP_BADPAGES {
+            return_errno_with_message!(Errno::EINVAL, "too many bad pages in the swap area");
+        }
+
+        let nr_slots = header.last_page as usize + 1;
+        let mut pages = vec![None; nr_slots];
+        pages[0] = Some(Weak::new());
+        for i in 0..nr_badpages {
+            let badpage: u32 = header_frame
+                .read_val(SWAP_HEADER_OFFSET + core::mem::size_of::<SwapHeader>() + i * 4)?;
+            if let Some(slot) = pages.get_mut(badpage as usize) {
+                *slot = Some(Weak::new());
+            }
+        }
+        let nr_free = pages.iter().filter(|slot| slot.is_none()).count();
+        if nr_free == 0 {
+            return_errno_with_message!(Errno::EINVAL, "the swap area is empty");
+        }
+
+        Ok(Self {
+            backend,
+            priority,
+            slots: Mutex::new(SwapSlots {
+                pages,
+                nr_free,
+                next: 1,
+            }),
+        })
+    }
+
+    /// Allocates a free slot for the swapped page.
+    fn alloc_slot(&self, page: &Arc<SwappedPage>) -> Option<usize> {
+        let mut slots = self.slots.lock();
+        if slots.nr_free == 0 {
+            return None;
+        }
+        let nr_slots = slots.pages.len();
+        let idx = (slots.next..nr_slots)
+            .chain(1..slots.next)
+            .find(|idx| slots.pages[*idx].is_none())
+            .unwrap();
+        slots.pages[idx] = Some(Arc::downgrade(page));
+        slots.nr_free -= 1;
+        slots.next = (idx + 1) % nr_slots;
+        Some(idx)
+    }
+
+    fn free_slot(&self, idx: usize) {
+        let mut slots = self.slots.lock();
+        debug_assert!(slots.pages[idx].is_some());
+        slots.pages[idx] = None;
+        slots.nr_free += 1;
+    }
+
+    /// Reads all the swapped pages in the swap area back into memory.
+    fn swap_in_all(&self) -> Result<()> {
+        let swapped_pages: Vec<Arc<SwappedPage>> = self
+            .slots
+            .lock()
+            .pages
+            .iter()
+            .filter_map(|slot| slot.as_ref().and_then(Weak::upgrade))
+            .collect();
+        for swapped_page in swapped_pages {
+            swapped_page.load()?;
+        }
+        Ok(())
+    }
+}
+
+/// The content of a page that is swapped out.
+///
+/// A swapped page may be shared by the VMOs that are copied-on-write from each other.
+/// When the last reference is dropped, its slot in the swap area is freed.
+pub struct SwappedPage {
+    location: Mutex<SwapLocation>,
+}
+
+enum SwapLocation {
+    /// The page is stored in the slot of the swap area.
+    Slot(Arc<SwapArea>, usize),
+    /// The page has been read back into memory since the swap area is turned off.
+    Memory(VmFrame),
+}
+
+impl SwappedPage {
+    /// Writes the page frame to a swap area with the highest priority, which has
+    /// free slots.
+    ///
+    /// If no swap area is available, `None` is returned.
+    pub fn swap_out(frame: &VmFrame) -> Result<Option<Arc<Self>>> {
+        let swapped_page = Arc::new(Self {
+            location: Mutex::new(SwapLocation::Memory(frame.clone())),
+        });
+        let Some((area, idx)) = SWAP_AREAS.lock().iter().find_map(|area| {
+            area.alloc_slot(&swapped_page)
+                .map(|idx| (area.clone(), idx))
+        }) else {
+            return Ok(None);
+        };
+
+        if let Err(err) = area.backend.write_page(idx, frame) {
+            area.free_slot(idx);
+            return Err(err);
+        }
+        *swapped_page.location.lock() = SwapLocation::Slot(area, idx);
+        Ok(Some(swapped_page))
+    }
+
+    /// Reads the content of the page into a new page frame.
+    pub fn swap_in(&self) -> Result<VmFrame> {
+        let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        match &*self.location.lock() {
+            SwapLocation::Slot(area, idx) => area.backend.read_page(*idx, &frame)?,
+            SwapLocation::Memory(page) => frame.copy_from_frame(page),
+        }
+        Ok(frame)
+    }
+
+    /// Reads the page back into memory and frees its slot in the swap area.
+    fn load(&self) -> Result<()> {
+        let mut location = self.location.lock();
+        let SwapLocation::Slot(area, idx) = &*location else {
+            return Ok(());
+        };
+        let frame = VmAllocOptions::new(1).uninit(true).alloc_single()?;
+        area.backend.read_page(*idx, &frame)?;
+        area.free_slot(*idx);
+        *location = SwapLocation::Memory(frame);
+        Ok(())
+    }
+}
+
+impl Drop for SwappedPage {
+    fn drop(&mut self) {
+        if let SwapLocation::Slot(area, idx) = &*self.location.lock() {
+            area.free_slot(*idx);
+        }
+    }
+}
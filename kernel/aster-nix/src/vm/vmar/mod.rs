@@ -11,15 +11,20 @@ pub mod vm_mapping;
 use core::ops::Range;
 
 use align_ext::AlignExt;
-use aster_frame::vm::{VmSpace, MAX_USERSPACE_VADDR};
+use aster_frame::vm::{max_userspace_vaddr, PageSize, VmSpace, MAX_USERSPACE_VADDR};
 use aster_rights::Rights;
 
 use self::{
     interval::{Interval, IntervalSet},
-    vm_mapping::VmMapping,
+    vm_mapping::{MappingFlags, VmMapping},
 };
 use super::page_fault_handler::PageFaultHandler;
-use crate::{prelude::*, vm::perms::VmPerms};
+use crate::{
+    fs::userfaultfd::{UffdRegisterMode, UserfaultCtx, UserfaultRegistration},
+    prelude::*,
+    process::{credentials, Process, ResourceType},
+    vm::{perms::VmPerms, vmo::Vmo},
+};
 
 /// Virtual Memory Address Regions (VMARs) are a type of capability that manages
 /// user address spaces.
@@ -93,6 +98,12 @@ impl<R> Vmar<R> {
     pub fn vm_space(&self) -> &VmSpace {
         self.0.vm_space()
     }
+
+    /// Returns the number of the capabilities that refer to the VMAR, e.g., the
+    /// ones held by the processes that share the VMAR.
+    pub fn num_refs(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
 }
 
 pub(super) struct Vmar_ {
@@ -117,6 +128,11 @@ struct VmarInner {
     vm_mappings: BTreeMap<Vaddr, Arc<VmMapping>>,
     /// Free regions that can be used for creating child vmar or mapping vmos
     free_regions: BTreeMap<Vaddr, FreeRegion>,
+    /// The flags of the mappings created later, e.g., `LOCKED` after `mlockall(MCL_FUTURE)`
+    default_mapping_flags: MappingFlags,
+    /// The address from which the free regions are searched for the children without
+    /// specified offsets. The lower free regions are only used if no higher ones fit.
+    mmap_base: Vaddr,
 }
 
 impl VmarInner {
@@ -126,12 +142,13 @@ impl VmarInner {
             child_vmar_s: BTreeMap::new(),
             vm_mappings: BTreeMap::new(),
             free_regions: BTreeMap::new(),
+            default_mapping_flags: MappingFlags::empty(),
+            mmap_base: 0,
         }
     }
 }
 
 const ROOT_VMAR_LOWEST_ADDR: Vaddr = 0x001_0000; // 64 KiB is the Linux configurable default
-const ROOT_VMAR_CAP_ADDR: Vaddr = MAX_USERSPACE_VADDR;
 
 impl Interval<usize> for Arc<Vmar_> {
     fn range(&self) -> Range<usize> {
@@ -164,15 +181,17 @@ impl Vmar_ {
 
     pub fn new_root() -> Arc<Self> {
         let mut free_regions = BTreeMap::new();
-        let root_region = FreeRegion::new(ROOT_VMAR_LOWEST_ADDR..ROOT_VMAR_CAP_ADDR);
+        let root_region = FreeRegion::new(ROOT_VMAR_LOWEST_ADDR..max_userspace_vaddr());
         free_regions.insert(root_region.start(), root_region);
         let vmar_inner = VmarInner {
             is_destroyed: false,
             child_vmar_s: BTreeMap::new(),
             vm_mappings: BTreeMap::new(),
             free_regions,
+            default_mapping_flags: MappingFlags::empty(),
+            mmap_base: ROOT_VMAR_LOWEST_ADDR,
         };
-        Vmar_::new(vmar_inner, VmSpace::new(), 0, ROOT_VMAR_CAP_ADDR, None)
+        Vmar_::new(vmar_inner, VmSpace::new(), 0, max_userspace_vaddr(), None)
     }
 
     fn is_root_vmar(&self) -> bool {
@@ -258,8 +277,16 @@ impl Vmar_ {
             return_errno_with_message!(Errno::EACCES, "page fault addr is not in current vmar");
         }
 
-        let inner = self.inner.lock();
-        if let Some(child_vmar) = inner.child_vmar_s.find_one(&page_fault_addr) {
+        // The lock is released before handling the fault, which may block until the fault
+        // is resolved by a userfaultfd handler.
+        let (child_vmar, vm_mapping) = {
+            let inner = self.inner.lock();
+            (
+                inner.child_vmar_s.find_one(&page_fault_addr).cloned(),
+                inner.vm_mappings.find_one(&page_fault_addr).cloned(),
+            )
+        };
+        if let Some(child_vmar) = child_vmar {
             debug_assert!(is_intersected(
                 &child_vmar.range(),
                 &(page_fault_addr..page_fault_addr + 1)
@@ -268,7 +295,7 @@ impl Vmar_ {
         }
 
         // FIXME: If multiple vmos are mapped to the addr, should we allow all vmos to handle page fault?
-        if let Some(vm_mapping) = inner.vm_mappings.find_one(&page_fault_addr) {
+        if let Some(vm_mapping) = vm_mapping {
             debug_assert!(is_intersected(
                 &vm_mapping.range(),
                 &(page_fault_addr..page_fault_addr + 1)
@@ -290,8 +317,10 @@ impl Vmar_ {
         inner.child_vmar_s.clear();
         inner.vm_mappings.clear();
         inner.free_regions.clear();
-        let root_region = FreeRegion::new(ROOT_VMAR_LOWEST_ADDR..ROOT_VMAR_CAP_ADDR);
+        let root_region = FreeRegion::new(ROOT_VMAR_LOWEST_ADDR..max_userspace_vaddr());
         inner.free_regions.insert(root_region.start(), root_region);
+        inner.default_mapping_flags = MappingFlags::empty();
+        inner.mmap_base = ROOT_VMAR_LOWEST_ADDR;
         Ok(())
     }
 
@@ -392,6 +421,500 @@ impl Vmar_ {
         Ok(())
     }
 
+    /// Resize the memory mapped in the range to the new size, and move it to another address
+    /// if needed. Returns the new address of the memory.
+    ///
+    /// The range must be contained in a single mapping. If `new_addr` is set, the memory is moved
+    /// there, overwriting any existing mappings. Otherwise, the memory is resized in place if
+    /// possible, or moved to a free region if `may_move` is true.
+    pub fn remap(
+        &self,
+        old_range: Range<usize>,
+        new_size: usize,
+        new_addr: Option<Vaddr>,
+        may_move: bool,
+    ) -> Result<Vaddr> {
+        debug_assert!(old_range.start % PAGE_SIZE == 0);
+        debug_assert!(old_range.end % PAGE_SIZE == 0);
+        debug_assert!(new_size % PAGE_SIZE == 0);
+
+        let vm_mapping = {
+            let inner = self.inner.lock();
+            let Some(vm_mapping) = inner.vm_mappings.find_one(&old_range.start) else {
+                return_errno_with_message!(Errno::EFAULT, "the remapped range is not mapped");
+            };
+            if vm_mapping.range().end < old_range.end {
+                return_errno_with_message!(
+                    Errno::EFAULT,
+                    "the remapped range is across multiple mappings"
+                );
+            }
+            vm_mapping.clone()
+        };
+        let old_size = old_range.len();
+
+        if let Some(new_addr) = new_addr {
+            let new_range = new_addr..(new_addr + new_size);
+            if is_intersected(&old_range, &new_range) {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "the new range overlaps with the remapped range"
+                );
+            }
+            return self.move_mapping(&vm_mapping, old_range, new_size, Some(new_addr));
+        }
+
+        if new_size <= old_size {
+            self.destroy((old_range.start + new_size)..old_range.end)?;
+            return Ok(old_range.start);
+        }
+
+        // Try to enlarge the mapping in place, which requires that the remapped range is
+        // at the end of the mapping and followed by a free region.
+        let enlarged_range = old_range.end..(old_range.start + new_size);
+        if vm_mapping.range().end == old_range.end
+            && self
+                .allocate_free_region_for_vmo(
+                    enlarged_range.len(),
+                    Some(old_range.end),
+                    PAGE_SIZE,
+                    false,
+                )
+                .is_ok()
+        {
+            let new_map_size = vm_mapping.map_size() + enlarged_range.len();
+            if let Err(err) = self.enlarge_mapping(&vm_mapping, new_map_size) {
+                self.free_range(enlarged_range);
+                return Err(err);
+            }
+            return Ok(old_range.start);
+        }
+
+        if !may_move {
+            return_errno_with_message!(Errno::ENOMEM, "the mapping cannot be enlarged in place");
+        }
+        self.move_mapping(&vm_mapping, old_range, new_size, None)
+    }
+
+    /// Move the memory mapped in the range to a new address and resize it.
+    fn move_mapping(
+        &self,
+        vm_mapping: &Arc<VmMapping>,
+        old_range: Range<usize>,
+        new_size: usize,
+        new_addr: Option<Vaddr>,
+    ) -> Result<Vaddr> {
+        let old_size = old_range.len();
+        let moved_range = old_range.start..(old_range.start + old_size.min(new_size));
+        let (new_addr, moved_mapping) = if let Some(new_addr) = new_addr {
+            // The moved mapping is cloned before overwriting the new range, which may trim
+            // the other parts of the original mapping.
+            let moved_mapping = vm_mapping.clone_moved(moved_range, new_addr)?;
+            self.allocate_free_region_for_vmo(new_size, Some(new_addr), PAGE_SIZE, true)?;
+            (new_addr, moved_mapping)
+        } else {
+            let new_addr = self.allocate_free_region_for_vmo(new_size, None, PAGE_SIZE, false)?;
+            match vm_mapping.clone_moved(moved_range, new_addr) {
+                Ok(moved_mapping) => (new_addr, moved_mapping),
+                Err(err) => {
+                    self.free_range(new_addr..(new_addr + new_size));
+                    return Err(err);
+                }
+            }
+        };
+        self.add_mapping(moved_mapping.clone());
+        if new_size > old_size {
+            self.enlarge_mapping(&moved_mapping, new_size)?;
+        }
+
+        self.destroy(old_range)?;
+        Ok(new_addr)
+    }
+
+    /// Enlarge the mapping to the new size, whose enlarged address range has been allocated.
+    fn enlarge_mapping(&self, vm_mapping: &Arc<VmMapping>, new_size: usize) -> Result<()> {
+        if let Some(extended_mapping) = vm_mapping.enlarge(new_size)? {
+            self.add_mapping(extended_mapping);
+        }
+        Ok(())
+    }
+
+    /// Return an allocated address range that is not used by any mappings back to the free regions.
+    fn free_range(&self, range: Range<usize>) {
+        let free_region = FreeRegion::new(range);
+        self.inner
+            .lock()
+            .free_regions
+            .insert(free_region.start(), free_region);
+        self.merge_continuous_regions();
+    }
+
+    /// Write back the dirty pages of the file-backed mappings within the range.
+    pub fn sync(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.sync(range))
+    }
+
+    /// Discard the pages within the range, which will be zero-filled or read from the files
+    /// again on the next access if the mappings are private.
+    pub fn discard(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.discard(range))
+    }
+
+    /// Free the pages of the private anonymous mappings within the range.
+    pub fn free(&self, range: Range<usize>) -> Result<()> {
+        for vm_mapping in self.find_mappings(&range)? {
+            if vm_mapping.is_shared() || vm_mapping.vmo().is_file_backed() {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "only private anonymous pages can be freed"
+                );
+            }
+        }
+        self.discard(range)
+    }
+
+    /// Remove the content of the shared anonymous mappings within the range.
+    pub fn remove(&self, range: Range<usize>) -> Result<()> {
+        for vm_mapping in self.find_mappings(&range)? {
+            if !vm_mapping.is_shared() {
+                return_errno_with_message!(Errno::EINVAL, "private pages cannot be removed");
+            }
+            if vm_mapping.vmo().is_file_backed() {
+                return_errno_with_message!(
+                    Errno::EOPNOTSUPP,
+                    "removing the pages of files is not supported"
+                );
+            }
+        }
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.zero_pages(range))
+    }
+
+    /// Set and clear the flags of the mappings within the range.
+    pub fn update_mapping_flags(
+        &self,
+        range: Range<usize>,
+        set_flags: MappingFlags,
+        clear_flags: MappingFlags,
+    ) -> Result<()> {
+        if set_flags.contains(MappingFlags::WIPEONFORK) {
+            for vm_mapping in self.find_mappings(&range)? {
+                if vm_mapping.is_shared() || vm_mapping.vmo().is_file_backed() {
+                    return_errno_with_message!(
+                        Errno::EINVAL,
+                        "only private anonymous pages can be wiped on fork"
+                    );
+                }
+            }
+        }
+        self.for_each_mapping(&range, |vm_mapping, range| {
+            vm_mapping.update_flags(range, set_flags, clear_flags)
+        })
+    }
+
+    /// Read the pages of the file-backed mappings within the range in advance.
+    pub fn readahead(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.readahead(range))
+    }
+
+    /// Deactivate the pages within the range, which are not likely to be accessed soon.
+    pub fn deactivate(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| {
+            vm_mapping.deactivate(range);
+            Ok(())
+        })
+    }
+
+    /// Page out the pages within the range.
+    pub fn page_out(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.page_out(range))
+    }
+
+    /// Populate the page tables for the pages within the range.
+    pub fn populate(&self, range: Range<usize>, write: bool) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| {
+            vm_mapping.populate(range, write)
+        })
+    }
+
+    /// Fault in the pages within the range, as if they were accessed by users.
+    pub fn prefault(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.prefault(range))
+    }
+
+    /// Lock the pages within the range in memory.
+    ///
+    /// The pages are faulted in at once, unless `on_fault` is true, in which case
+    /// they are locked when they are faulted in later.
+    pub fn mlock(&self, range: Range<usize>, on_fault: bool) -> Result<()> {
+        let (set_flags, clear_flags) = lock_flags(on_fault);
+        self.update_mapping_flags(range.clone(), set_flags, clear_flags)?;
+        if !on_fault {
+            self.prefault(range)?;
+        }
+        Ok(())
+    }
+
+    /// Unlock the pages within the range.
+    pub fn munlock(&self, range: Range<usize>) -> Result<()> {
+        self.update_mapping_flags(
+            range,
+            MappingFlags::empty(),
+            MappingFlags::LOCKED | MappingFlags::LOCKONFAULT,
+        )
+    }
+
+    /// Lock all the pages that are currently mapped in the vmar and its child vmars.
+    pub fn mlock_all(&self, on_fault: bool) -> Result<()> {
+        let (set_flags, clear_flags) = lock_flags(on_fault);
+        for vm_mapping in self.all_mappings() {
+            let range = vm_mapping.range();
+            vm_mapping.update_flags(range.clone(), set_flags, clear_flags)?;
+            if !on_fault {
+                // Like Linux, the pages that cannot be faulted in are skipped.
+                let _ = vm_mapping.prefault(range);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unlock all the pages in the vmar and its child vmars, and stop locking
+    /// the mappings created later.
+    pub fn munlock_all(&self) -> Result<()> {
+        self.set_default_mapping_flags(MappingFlags::empty());
+        for vm_mapping in self.all_mappings() {
+            vm_mapping.update_flags(
+                vm_mapping.range(),
+                MappingFlags::empty(),
+                MappingFlags::LOCKED | MappingFlags::LOCKONFAULT,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Return the size of the locked pages within the range in bytes.
+    /// The range is not required to be fully mapped.
+    pub fn locked_size(&self, range: &Range<usize>) -> usize {
+        self.all_mappings()
+            .iter()
+            .filter(|vm_mapping| {
+                vm_mapping.flags().contains(MappingFlags::LOCKED)
+                    && is_intersected(&vm_mapping.range(), range)
+            })
+            .map(|vm_mapping| get_intersected_range(&vm_mapping.range(), range).len())
+            .sum()
+    }
+
+    /// Return whether the vmo is mapped as shared and writable in the vmar or its child vmars.
+    ///
+    /// A mapping that is not writable for now but may be made writable with `mprotect`
+    /// is also considered writable.
+    pub fn is_vmo_mapped_writable<R>(&self, vmo: &Vmo<R>) -> bool {
+        self.all_mappings().iter().any(|vm_mapping| {
+            vm_mapping.is_shared() && vm_mapping.may_write() && vm_mapping.vmo().is_same(vmo)
+        })
+    }
+
+    /// Return the total size of the mappings in the vmar and its child vmars in bytes.
+    pub fn mapped_size(&self) -> usize {
+        self.all_mappings()
+            .iter()
+            .map(|vm_mapping| vm_mapping.map_size())
+            .sum()
+    }
+
+    /// Return the flags of the mappings that are created later.
+    pub fn default_mapping_flags(&self) -> MappingFlags {
+        self.inner.lock().default_mapping_flags
+    }
+
+    /// Set the flags of the mappings that are created later.
+    pub fn set_default_mapping_flags(&self, flags: MappingFlags) {
+        self.inner.lock().default_mapping_flags = flags;
+    }
+
+    /// Set the address from which the free regions are searched for the children
+    /// without specified offsets.
+    pub fn set_mmap_base(&self, mmap_base: Vaddr) {
+        self.inner.lock().mmap_base = mmap_base;
+    }
+
+    /// Register the mappings within the range with a userfaultfd.
+    ///
+    /// Only the anonymous mappings of base pages can be registered, and they cannot be
+    /// registered with another userfaultfd at the same time.
+    pub fn register_userfault(
+        &self,
+        range: Range<usize>,
+        registration: UserfaultRegistration,
+    ) -> Result<()> {
+        for vm_mapping in self.find_mappings(&range)? {
+            if vm_mapping.vmo().is_file_backed() || vm_mapping.page_size() != PageSize::Size4K {
+                return_errno_with_message!(
+                    Errno::EINVAL,
+                    "only anonymous mappings can be registered"
+                );
+            }
+            if let Some(other) = vm_mapping.userfault()
+                && let Some(other_ctx) = other.ctx()
+                && !registration.is_registered_with(&other_ctx)
+            {
+                return_errno_with_message!(
+                    Errno::EBUSY,
+                    "the range is registered with another userfaultfd"
+                );
+            }
+        }
+        self.for_each_mapping(&range, |vm_mapping, range| {
+            vm_mapping.set_userfault(range, Some(registration.clone()))
+        })
+    }
+
+    /// Unregister the mappings within the range from the userfaultfd.
+    /// The mappings registered with other userfaultfds are skipped.
+    pub fn unregister_userfault(&self, range: Range<usize>, ctx: &UserfaultCtx) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| {
+            if !vm_mapping
+                .userfault()
+                .is_some_and(|registration| registration.is_registered_with(ctx))
+            {
+                return Ok(());
+            }
+            vm_mapping.set_userfault(range, None)
+        })
+    }
+
+    /// Check that the mappings within the range are registered with the userfaultfd
+    /// in the mode.
+    pub fn check_userfault(
+        &self,
+        range: &Range<usize>,
+        ctx: &UserfaultCtx,
+        mode: UffdRegisterMode,
+    ) -> Result<()> {
+        for vm_mapping in self.find_mappings(range)? {
+            if !vm_mapping.userfault().is_some_and(|registration| {
+                registration.is_registered_with(ctx) && registration.mode().contains(mode)
+            }) {
+                return_errno_with_message!(Errno::ENOENT, "the range is not registered");
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill the missing page at the address, which should be registered with the userfaultfd.
+    pub fn fill_userfault_page(
+        &self,
+        page_addr: Vaddr,
+        ctx: &UserfaultCtx,
+        content: &[u8],
+    ) -> Result<()> {
+        let vm_mapping = self.get_vm_mapping(page_addr)?;
+        if !vm_mapping
+            .userfault()
+            .is_some_and(|registration| registration.is_registered_with(ctx))
+        {
+            return_errno_with_message!(Errno::ENOENT, "the page is not registered");
+        }
+        vm_mapping.fill_page(page_addr, content)
+    }
+
+    /// Make the pages within the range readonly in the page table.
+    pub fn write_protect(&self, range: Range<usize>) -> Result<()> {
+        self.for_each_mapping(&range, |vm_mapping, range| vm_mapping.write_protect(range))
+    }
+
+    /// Reclaim at most `nr_to_reclaim` pages that are not recently used in the vmar
+    /// and its child vmars. Return the number of reclaimed pages.
+    pub fn reclaim_pages(&self, nr_to_reclaim: usize) -> usize {
+        let (child_vmar_s, vm_mappings): (Vec<_>, Vec<_>) = {
+            let inner = self.inner.lock();
+            (
+                inner.child_vmar_s.values().cloned().collect(),
+                inner.vm_mappings.values().cloned().collect(),
+            )
+        };
+
+        let mut nr_reclaimed = 0;
+        for vm_mapping in vm_mappings {
+            if nr_reclaimed >= nr_to_reclaim {
+                return nr_reclaimed;
+            }
+            nr_reclaimed += vm_mapping.reclaim_pages(nr_to_reclaim - nr_reclaimed);
+        }
+        for child_vmar in child_vmar_s {
+            if nr_reclaimed >= nr_to_reclaim {
+                return nr_reclaimed;
+            }
+            nr_reclaimed += child_vmar.reclaim_pages(nr_to_reclaim - nr_reclaimed);
+        }
+        nr_reclaimed
+    }
+
+    /// Return whether each page within the range is resident in memory.
+    pub fn resident_pages(&self, range: Range<usize>) -> Result<Vec<bool>> {
+        let mut resident_pages = Vec::with_capacity(range.len() / PAGE_SIZE);
+        for vm_mapping in self.find_mappings(&range)? {
+            let intersected_range = get_intersected_range(&vm_mapping.range(), &range);
+            resident_pages.extend(vm_mapping.resident_pages(intersected_range));
+        }
+        Ok(resident_pages)
+    }
+
+    /// Apply the operation to each mapping within the range, which should be fully mapped.
+    ///
+    /// The operation is called with the intersected range of the mapping and the range.
+    /// It may modify the `vm_mappings` of the vmar since the mappings are collected first.
+    fn for_each_mapping(
+        &self,
+        range: &Range<usize>,
+        mut op: impl FnMut(&Arc<VmMapping>, Range<usize>) -> Result<()>,
+    ) -> Result<()> {
+        for vm_mapping in self.find_mappings(range)? {
+            let intersected_range = get_intersected_range(&vm_mapping.range(), range);
+            op(&vm_mapping, intersected_range)?;
+        }
+        Ok(())
+    }
+
+    /// Collect the mappings in the vmar and its child vmars.
+    fn all_mappings(&self) -> Vec<Arc<VmMapping>> {
+        let (child_vmar_s, mut vm_mappings): (Vec<_>, Vec<_>) = {
+            let inner = self.inner.lock();
+            (
+                inner.child_vmar_s.values().cloned().collect(),
+                inner.vm_mappings.values().cloned().collect(),
+            )
+        };
+        for child_vmar in child_vmar_s {
+            vm_mappings.extend(child_vmar.all_mappings());
+        }
+        vm_mappings
+    }
+
+    /// Find the mappings within the range, which should be fully mapped.
+    fn find_mappings(&self, range: &Range<usize>) -> Result<Vec<Arc<VmMapping>>> {
+        debug_assert!(range.start % PAGE_SIZE == 0);
+        debug_assert!(range.end % PAGE_SIZE == 0);
+
+        let inner = self.inner.lock();
+        let vm_mappings: Vec<Arc<VmMapping>> =
+            inner.vm_mappings.find(range).into_iter().cloned().collect();
+        // The mappings are ordered by their addresses, so there is a hole if one of them
+        // does not start right after the previous one.
+        let mut mapped_end = range.start;
+        for vm_mapping in &vm_mappings {
+            let vm_mapping_range = vm_mapping.range();
+            if vm_mapping_range.start > mapped_end {
+                break;
+            }
+            mapped_end = vm_mapping_range.end;
+        }
+        if mapped_end < range.end {
+            return_errno_with_message!(Errno::ENOMEM, "the range is not fully mapped");
+        }
+        Ok(vm_mappings)
+    }
+
     fn is_destroyed(&self) -> bool {
         self.inner.lock().is_destroyed
     }
@@ -418,30 +941,39 @@ impl Vmar_ {
         let read_start = self.base + offset;
         let read_end = buf.len() + read_start;
         let read_range = read_start..read_end;
-        // If the read range is in child vmar.
-        let inner = self.inner.lock();
-        for child_vmar_ in inner.child_vmar_s.find(&read_range) {
-            let child_vmar_range = child_vmar_.range();
-            if child_vmar_range.start <= read_start && read_end <= child_vmar_range.end {
-                let child_offset = read_start - child_vmar_range.start;
-                return child_vmar_.read(child_offset, buf);
-            }
-        }
 
-        // If the read range is in mapped vmo.
-        for vm_mapping in inner.vm_mappings.find(&read_range) {
-            let vm_mapping_range = vm_mapping.range();
-            if vm_mapping_range.start <= read_start && read_end <= vm_mapping_range.end {
-                let vm_mapping_offset = read_start - vm_mapping_range.start;
-                return vm_mapping.read_bytes(vm_mapping_offset, buf);
+        // The lock is released before the read, which may wait for the userfaultfd
+        // handler to resolve the faults (see `VmMapping::read_bytes`).
+        match self.find_accessed(&read_range) {
+            // If the read range is in child vmar.
+            Some(Accessed::ChildVmar(child_vmar_)) => {
+                let child_offset = read_start - child_vmar_.range().start;
+                child_vmar_.read(child_offset, buf)
+            }
+            // If the read range is in mapped vmo.
+            Some(Accessed::Mapping(vm_mapping)) => {
+                let vm_mapping_offset = read_start - vm_mapping.range().start;
+                vm_mapping.read_bytes(vm_mapping_offset, buf)
+            }
+            // FIXME: If the read range is across different vmos or child vmars, should we directly return error?
+            None => {
+                return_errno_with_message!(Errno::EACCES, "read range is not backed up by a vmo")
             }
         }
-
-        // FIXME: If the read range is across different vmos or child vmars, should we directly return error?
-        return_errno_with_message!(Errno::EACCES, "read range is not backed up by a vmo");
     }
 
     pub fn write(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.write_with(offset, buf, false)
+    }
+
+    /// Writes the bytes like `write`, but ignores the write permission of the mappings.
+    ///
+    /// See `VmMapping::force_write_bytes` for details.
+    pub fn force_write(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.write_with(offset, buf, true)
+    }
+
+    fn write_with(&self, offset: usize, buf: &[u8], force: bool) -> Result<()> {
         let write_start = self
             .base
             .checked_add(offset)
@@ -453,27 +985,50 @@ impl Vmar_ {
             .ok_or_else(|| Error::with_message(Errno::EFAULT, "Arithmetic Overflow"))?;
         let write_range = write_start..write_end;
 
-        // If the write range is in child vmar.
-        let inner = self.inner.lock();
-        for child_vmar_ in inner.child_vmar_s.find(&write_range) {
-            let child_vmar_range = child_vmar_.range();
-            if child_vmar_range.start <= write_start && write_end <= child_vmar_range.end {
-                let child_offset = write_start - child_vmar_range.start;
-                return child_vmar_.write(child_offset, buf);
+        // The lock is released before the write, which may wait for the userfaultfd
+        // handler to resolve the faults (see `VmMapping::write_bytes`).
+        match self.find_accessed(&write_range) {
+            // If the write range is in child vmar.
+            Some(Accessed::ChildVmar(child_vmar_)) => {
+                let child_offset = write_start - child_vmar_.range().start;
+                child_vmar_.write_with(child_offset, buf, force)
             }
-        }
-
-        // If the write range is in mapped vmo.
-        for vm_mapping in inner.vm_mappings.find(&write_range) {
-            let vm_mapping_range = vm_mapping.range();
-            if vm_mapping_range.start <= write_start && write_end <= vm_mapping_range.end {
-                let vm_mapping_offset = write_start - vm_mapping_range.start;
-                return vm_mapping.write_bytes(vm_mapping_offset, buf);
+            // If the write range is in mapped vmo.
+            Some(Accessed::Mapping(vm_mapping)) => {
+                let vm_mapping_offset = write_start - vm_mapping.range().start;
+                if force {
+                    vm_mapping.force_write_bytes(vm_mapping_offset, buf)
+                } else {
+                    vm_mapping.write_bytes(vm_mapping_offset, buf)
+                }
+            }
+            // FIXME: If the write range is across different vmos or child vmars, should we directly return error?
+            None => {
+                return_errno_with_message!(Errno::EACCES, "write range is not backed up by a vmo")
             }
         }
+    }
 
-        // FIXME: If the write range is across different vmos or child vmars, should we directly return error?
-        return_errno_with_message!(Errno::EACCES, "write range is not backed up by a vmo");
+    /// Finds the child vmar or the mapping that contains the whole accessed range.
+    fn find_accessed(&self, range: &Range<usize>) -> Option<Accessed> {
+        let contains = |item_range: Range<usize>| {
+            item_range.start <= range.start && range.end <= item_range.end
+        };
+        let inner = self.inner.lock();
+        if let Some(child_vmar_) = inner
+            .child_vmar_s
+            .find(range)
+            .into_iter()
+            .find(|child_vmar_| contains(child_vmar_.range()))
+        {
+            return Some(Accessed::ChildVmar(child_vmar_.clone()));
+        }
+        inner
+            .vm_mappings
+            .find(range)
+            .into_iter()
+            .find(|vm_mapping| contains(vm_mapping.range()))
+            .map(|vm_mapping| Accessed::Mapping(vm_mapping.clone()))
     }
 
     /// Allocate a child vmar_.
@@ -484,7 +1039,7 @@ impl Vmar_ {
         align: usize,
     ) -> Result<Arc<Vmar_>> {
         let (region_base, child_vmar_offset) =
-            self.find_free_region_for_child(child_vmar_offset, child_vmar_size, align)?;
+            self.find_free_region_for_child(child_vmar_offset, None, child_vmar_size, align)?;
         // This unwrap should never fails
         let free_region = self.inner.lock().free_regions.remove(&region_base).unwrap();
         let child_range = child_vmar_offset..(child_vmar_offset + child_vmar_size);
@@ -503,6 +1058,8 @@ impl Vmar_ {
             child_vmar_s: BTreeMap::new(),
             vm_mappings: BTreeMap::new(),
             free_regions: child_regions,
+            default_mapping_flags: self.default_mapping_flags(),
+            mmap_base: child_vmar_offset,
         };
         let child_vmar_ = Vmar_::new(
             child_vmar_inner,
@@ -520,9 +1077,14 @@ impl Vmar_ {
 
     /// Find a free region for child vmar or vmo.
     /// Returns (region base addr, child real offset).
+    ///
+    /// If `child_offset` is not set, the free region at `child_hint` is preferred.
+    /// Like Linux, the children of the root vmar are placed below
+    /// `MAX_USERSPACE_VADDR` unless the hint is beyond it.
     fn find_free_region_for_child(
         &self,
         child_offset: Option<Vaddr>,
+        child_hint: Option<Vaddr>,
         child_size: usize,
         align: usize,
     ) -> Result<(Vaddr, Vaddr)> {
@@ -540,16 +1102,46 @@ impl Vmar_ {
                 }
             }
         } else {
-            // Else, we find a free region that can satisfy the length and align requirement.
-            // Here, we use a simple brute-force algorithm to find the first free range that can satisfy.
-            // FIXME: A randomized algorithm may be more efficient.
-            for (region_base, free_region) in &inner.free_regions {
-                let region_start = free_region.start();
-                let region_end = free_region.end();
-                let child_vmar_real_start = region_start.align_up(align);
-                let child_vmar_real_end = child_vmar_real_start + child_size;
-                if region_start <= child_vmar_real_start && child_vmar_real_end <= region_end {
-                    return Ok((*region_base, child_vmar_real_start));
+            // Like Linux, the addresses beyond the 47-bit user space are only used if
+            // they are hinted.
+            let highest_end = if !self.is_root_vmar()
+                || child_hint.is_some_and(|hint| hint >= MAX_USERSPACE_VADDR)
+            {
+                Vaddr::MAX
+            } else {
+                MAX_USERSPACE_VADDR
+            };
+
+            // The hinted range is preferred if it is free, unless it crosses the end
+            // of the 47-bit user space when the hint is below it.
+            if let Some(hint) = child_hint
+                && hint % align == 0
+                && let Some(hint_end) = hint.checked_add(child_size)
+                && hint_end <= highest_end
+            {
+                let hint_range = hint..hint_end;
+                for free_region in inner.free_regions.find(&hint_range) {
+                    let free_region_range = free_region.range();
+                    if free_region_range.start <= hint_range.start
+                        && hint_range.end <= free_region_range.end
+                    {
+                        return Ok((free_region_range.start, hint));
+                    }
+                }
+            }
+
+            // Otherwise, we find a free region that can satisfy the length and align requirement.
+            // Here, we use a simple brute-force algorithm to find the first free range that can satisfy,
+            // starting from the mmap base, which may be randomized.
+            for lowest_start in [inner.mmap_base, 0] {
+                for (region_base, free_region) in &inner.free_regions {
+                    let region_start = free_region.start().max(lowest_start);
+                    let region_end = free_region.end().min(highest_end);
+                    let child_vmar_real_start = region_start.align_up(align);
+                    let child_vmar_real_end = child_vmar_real_start + child_size;
+                    if region_start <= child_vmar_real_start && child_vmar_real_end <= region_end {
+                        return Ok((*region_base, child_vmar_real_start));
+                    }
                 }
             }
         }
@@ -597,14 +1189,13 @@ impl Vmar_ {
 
     fn allocate_free_region_for_vmo(
         &self,
-        vmo_size: usize,
-        size: usize,
+        map_size: usize,
         offset: Option<usize>,
+        hint: Option<Vaddr>,
         align: usize,
         can_overwrite: bool,
     ) -> Result<Vaddr> {
-        trace!("allocate free region, vmo_size = 0x{:x}, map_size = 0x{:x}, offset = {:x?}, align = 0x{:x}, can_overwrite = {}", vmo_size, size, offset, align, can_overwrite);
-        let map_size = size.max(vmo_size);
+        trace!("allocate free region, map_size = 0x{:x}, offset = {:x?}, align = 0x{:x}, can_overwrite = {}", map_size, offset, align, can_overwrite);
 
         if can_overwrite {
             let mut inner = self.inner.lock();
@@ -638,7 +1229,7 @@ impl Vmar_ {
         } else {
             // Otherwise, the vmo in a single region.
             let (free_region_base, offset) =
-                self.find_free_region_for_child(offset, map_size, align)?;
+                self.find_free_region_for_child(offset, hint, map_size, align)?;
             let mut inner = self.inner.lock();
             let free_region = inner.free_regions.remove(&free_region_base).unwrap();
             let vmo_range = offset..(offset + map_size);
@@ -712,6 +1303,10 @@ impl Vmar_ {
         };
 
         let inner = self.inner.lock();
+        new_vmar_.inner.lock().mmap_base = inner.mmap_base;
+        // Like Linux, the memory locks are not inherited by the child, including the ones
+        // of the mappings created later (see `mlockall`).
+        new_vmar_.inner.lock().default_mapping_flags = MappingFlags::empty();
         // Clone free regions.
         for (free_region_base, free_region) in &inner.free_regions {
             new_vmar_
@@ -732,14 +1327,36 @@ impl Vmar_ {
         }
 
         // Clone vm mappings.
+        let mut has_dropped_mappings = false;
         for (vm_mapping_base, vm_mapping) in &inner.vm_mappings {
-            let new_mapping = Arc::new(vm_mapping.new_cow(&new_vmar_)?);
+            let flags = vm_mapping.flags();
+            // The pages inherited by the cloned `VmSpace` should be unmapped if the child
+            // does not share the content.
+            if flags.intersects(MappingFlags::DONTFORK | MappingFlags::WIPEONFORK) {
+                new_vmar_.vm_space().unmap(&vm_mapping.range())?;
+            }
+            // The mappings not inherited by the child become free regions.
+            if flags.contains(MappingFlags::DONTFORK) {
+                let free_region = FreeRegion::new(vm_mapping.range());
+                new_vmar_
+                    .inner
+                    .lock()
+                    .free_regions
+                    .insert(free_region.start(), free_region);
+                has_dropped_mappings = true;
+                continue;
+            }
+
+            let new_mapping = VmMapping::new_arc(vm_mapping.new_cow(&new_vmar_)?);
             new_vmar_
                 .inner
                 .lock()
                 .vm_mappings
                 .insert(*vm_mapping_base, new_mapping);
         }
+        if has_dropped_mappings {
+            new_vmar_.merge_continuous_regions();
+        }
         Ok(new_vmar_)
     }
 
@@ -777,6 +1394,247 @@ impl<R> Vmar<R> {
         self.check_rights(rights)?;
         self.0.get_vm_mapping(offset)
     }
+
+    /// Resize the memory mapped in the specified range, possibly moving it to
+    /// another address. Returns the new address of the memory.
+    ///
+    /// The range's start and end addresses and the new size must be page-aligned.
+    /// Also, the range must be contained in a single mapping.
+    ///
+    /// If `new_addr` is set, the memory is moved to the address, and any existing
+    /// mappings there are unmapped. Otherwise, the memory is resized in place if
+    /// possible, or moved to a free region if `may_move` is true.
+    ///
+    /// The content of the memory is preserved, and the enlarged part (if any) is
+    /// backed by the same source as the mapping, i.e., zeros or the file.
+    pub fn remap(
+        &self,
+        old_range: Range<usize>,
+        new_size: usize,
+        new_addr: Option<Vaddr>,
+        may_move: bool,
+    ) -> Result<Vaddr> {
+        self.0.remap(old_range, new_size, new_addr, may_move)
+    }
+
+    /// Write back the updates of the file-backed shared mappings within the
+    /// specified range to the files.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn sync(&self, range: Range<usize>) -> Result<()> {
+        self.0.sync(range)
+    }
+
+    /// Returns whether each page within the specified range is resident in
+    /// memory, i.e., committed in the mapped VMO.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn resident_pages(&self, range: Range<usize>) -> Result<Vec<bool>> {
+        self.0.resident_pages(range)
+    }
+
+    /// Reclaims at most `nr_to_reclaim` pages of the anonymous memory that are
+    /// not recently used, by swapping them out. Returns the number of reclaimed
+    /// pages.
+    pub fn reclaim_pages(&self, nr_to_reclaim: usize) -> usize {
+        self.0.reclaim_pages(nr_to_reclaim)
+    }
+
+    /// Discards the pages within the specified range.
+    ///
+    /// The pages of private mappings are dropped, so later accesses see zeros or
+    /// the content of the files. The pages of shared mappings are kept in the VMOs.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn discard(&self, range: Range<usize>) -> Result<()> {
+        self.0.discard(range)
+    }
+
+    /// Frees the pages within the specified range, which must be in private
+    /// anonymous mappings.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn free(&self, range: Range<usize>) -> Result<()> {
+        self.0.free(range)
+    }
+
+    /// Removes the content of the pages within the specified range, which must
+    /// be in shared anonymous mappings. The pages read as zeros afterwards.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn remove(&self, range: Range<usize>) -> Result<()> {
+        self.0.remove(range)
+    }
+
+    /// Sets and clears the flags of the mappings within the specified range.
+    /// The mappings are split if they are partly in the range.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn update_mapping_flags(
+        &self,
+        range: Range<usize>,
+        set_flags: MappingFlags,
+        clear_flags: MappingFlags,
+    ) -> Result<()> {
+        self.0.update_mapping_flags(range, set_flags, clear_flags)
+    }
+
+    /// Starts to read the pages of the file-backed mappings within the specified
+    /// range in the background.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn readahead(&self, range: Range<usize>) -> Result<()> {
+        self.0.readahead(range)
+    }
+
+    /// Deactivates the pages within the specified range, so that they are
+    /// reclaimed before other pages.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn deactivate(&self, range: Range<usize>) -> Result<()> {
+        self.0.deactivate(range)
+    }
+
+    /// Pages out the pages within the specified range, writing back the dirty
+    /// pages of the file-backed shared mappings first.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn page_out(&self, range: Range<usize>) -> Result<()> {
+        self.0.page_out(range)
+    }
+
+    /// Populates the page tables for the pages within the specified range,
+    /// as if they are read or written.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn populate(&self, range: Range<usize>, write: bool) -> Result<()> {
+        self.0.populate(range, write)
+    }
+
+    /// Faults in the pages within the specified range according to the permissions
+    /// of the mappings. The private writable pages are copied in advance.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn prefault(&self, range: Range<usize>) -> Result<()> {
+        self.0.prefault(range)
+    }
+
+    /// Locks the pages within the specified range in memory, so that they are
+    /// never reclaimed. If `on_fault` is false, the pages are faulted in at once.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn mlock(&self, range: Range<usize>, on_fault: bool) -> Result<()> {
+        self.0.mlock(range, on_fault)
+    }
+
+    /// Unlocks the pages within the specified range.
+    ///
+    /// The range's start and end addresses must be page-aligned.
+    /// Also, the range must be completely mapped.
+    pub fn munlock(&self, range: Range<usize>) -> Result<()> {
+        self.0.munlock(range)
+    }
+
+    /// Locks all the pages currently mapped in the VMAR.
+    pub fn mlock_all(&self, on_fault: bool) -> Result<()> {
+        self.0.mlock_all(on_fault)
+    }
+
+    /// Unlocks all the pages in the VMAR, and stops locking new mappings.
+    pub fn munlock_all(&self) -> Result<()> {
+        self.0.munlock_all()
+    }
+
+    /// Returns the size in bytes of the locked pages within the specified range.
+    pub fn locked_size(&self, range: &Range<usize>) -> usize {
+        self.0.locked_size(range)
+    }
+
+    /// Returns whether the VMO is mapped as shared and writable in the VMAR,
+    /// through which the VMO may be modified.
+    pub fn is_vmo_mapped_writable<R1>(&self, vmo: &Vmo<R1>) -> bool {
+        self.0.is_vmo_mapped_writable(vmo)
+    }
+
+    /// Returns the total size in bytes of the mappings in the VMAR.
+    pub fn mapped_size(&self) -> usize {
+        self.0.mapped_size()
+    }
+
+    /// Returns the flags that new mappings in the VMAR are created with.
+    pub fn default_mapping_flags(&self) -> MappingFlags {
+        self.0.default_mapping_flags()
+    }
+
+    /// Sets the flags that new mappings in the VMAR are created with, e.g.,
+    /// `MappingFlags::LOCKED` for `mlockall(MCL_FUTURE)`.
+    pub fn set_default_mapping_flags(&self, flags: MappingFlags) {
+        self.0.set_default_mapping_flags(flags)
+    }
+
+    /// Sets the lowest address preferred for the mappings without specified
+    /// addresses. It is randomized for address space layout randomization.
+    pub fn set_mmap_base(&self, mmap_base: Vaddr) {
+        self.0.set_mmap_base(mmap_base)
+    }
+
+    /// Registers the mappings within the specified range with a userfaultfd, which
+    /// handles the faults on the pages in user space.
+    ///
+    /// The range must be fully mapped with anonymous mappings.
+    pub fn register_userfault(
+        &self,
+        range: Range<usize>,
+        registration: UserfaultRegistration,
+    ) -> Result<()> {
+        self.0.register_userfault(range, registration)
+    }
+
+    /// Unregisters the mappings within the specified range from the userfaultfd.
+    pub fn unregister_userfault(&self, range: Range<usize>, ctx: &UserfaultCtx) -> Result<()> {
+        self.0.unregister_userfault(range, ctx)
+    }
+
+    /// Checks that the mappings within the specified range are registered with
+    /// the userfaultfd in the mode.
+    pub fn check_userfault(
+        &self,
+        range: &Range<usize>,
+        ctx: &UserfaultCtx,
+        mode: UffdRegisterMode,
+    ) -> Result<()> {
+        self.0.check_userfault(range, ctx, mode)
+    }
+
+    /// Fills the missing page at the specified address with the content, which
+    /// is mapped on the next fault.
+    ///
+    /// The page must be registered with the userfaultfd.
+    pub fn fill_userfault_page(
+        &self,
+        page_addr: Vaddr,
+        ctx: &UserfaultCtx,
+        content: &[u8],
+    ) -> Result<()> {
+        self.0.fill_userfault_page(page_addr, ctx, content)
+    }
+
+    /// Write-protects the pages within the specified range in the page table.
+    pub fn write_protect(&self, range: Range<usize>) -> Result<()> {
+        self.0.write_protect(range)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -831,6 +1689,54 @@ impl FreeRegion {
     }
 }
 
+/// Returns the flags to set and to clear for locking a mapping.
+fn lock_flags(on_fault: bool) -> (MappingFlags, MappingFlags) {
+    if on_fault {
+        (
+            MappingFlags::LOCKED | MappingFlags::LOCKONFAULT,
+            MappingFlags::empty(),
+        )
+    } else {
+        (MappingFlags::LOCKED, MappingFlags::LOCKONFAULT)
+    }
+}
+
+/// Checks whether the current process is allowed to lock `nr_bytes` more bytes
+/// of memory, according to `RLIMIT_MEMLOCK`.
+///
+/// The root user is not limited.
+pub fn check_memlock_limit(current: &Process, nr_bytes: usize) -> Result<()> {
+    if credentials().euid().is_root() {
+        return Ok(());
+    }
+
+    let limit = current
+        .resource_limits()
+        .lock()
+        .get_rlimit(ResourceType::RLIMIT_MEMLOCK)
+        .get_cur();
+    if limit == 0 {
+        return_errno_with_message!(Errno::EPERM, "locking memory is not permitted");
+    }
+    let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+    if process_locked_size(current).saturating_add(nr_bytes) > limit {
+        return_errno_with_message!(Errno::ENOMEM, "the locked memory exceeds RLIMIT_MEMLOCK");
+    }
+    Ok(())
+}
+
+/// Returns the size of the memory locked by the process in bytes.
+pub fn process_locked_size(process: &Process) -> usize {
+    let root_vmar = process.root_vmar();
+    root_vmar.locked_size(&(root_vmar.base()..root_vmar.base() + root_vmar.size()))
+}
+
+/// The child vmar or the mapping that is accessed by `Vmar_::read` or `Vmar_::write`.
+enum Accessed {
+    ChildVmar(Arc<Vmar_>),
+    Mapping(Arc<VmMapping>),
+}
+
 /// Determine whether two ranges are intersected.
 /// returns false if one of the ranges has a length of 0
 pub fn is_intersected(range1: &Range<usize>, range2: &Range<usize>) -> bool {
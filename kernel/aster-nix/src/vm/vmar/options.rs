@@ -135,7 +135,7 @@ impl<R> VmarChildOptions<R> {
 
 #[cfg(ktest)]
 mod test {
-    use aster_frame::vm::VmIo;
+    use aster_frame::vm::{PageSize, VmIo};
     use aster_rights::Full;
 
     use super::*;
@@ -215,4 +215,77 @@ mod test {
             .unwrap();
         root_vmar.handle_page_fault(OFFSET, true, false).unwrap();
     }
+
+    #[ktest]
+    fn split_transparent_huge_page() {
+        const OFFSET: usize = 0x4000_0000;
+        let huge_page_nbytes = PageSize::Size2M.nbytes();
+        let root_vmar = Vmar::<Full>::new_root();
+        let vmo = VmoOptions::<Full>::new(2 * huge_page_nbytes)
+            .alloc()
+            .unwrap()
+            .to_dyn();
+        root_vmar
+            .new_map(vmo, VmPerms::READ | VmPerms::WRITE)
+            .unwrap()
+            .offset(OFFSET)
+            .build()
+            .unwrap();
+
+        // A large anonymous mapping is backed by huge pages transparently, so the whole
+        // huge page is mapped at the first fault.
+        root_vmar.handle_page_fault(OFFSET, true, true).unwrap();
+        let vm_space = root_vmar.vm_space();
+        assert_eq!(vm_space.nr_mapped_pages(), PageSize::Size2M.nframes());
+        root_vmar.write_val(OFFSET + 3 * PAGE_SIZE, &100u8).unwrap();
+
+        // Unmapping a part of the huge page splits it.
+        root_vmar
+            .destroy(OFFSET + PAGE_SIZE..OFFSET + 2 * PAGE_SIZE)
+            .unwrap();
+        assert!(vm_space.is_mapped(OFFSET));
+        assert!(!vm_space.is_mapped(OFFSET + PAGE_SIZE));
+        assert!(vm_space.is_mapped(OFFSET + 2 * PAGE_SIZE));
+        assert_eq!(vm_space.nr_mapped_pages(), PageSize::Size2M.nframes() - 1);
+        assert!(root_vmar.read_val::<u8>(OFFSET + 3 * PAGE_SIZE).unwrap() == 100);
+
+        // So does protecting a part of the huge page.
+        root_vmar
+            .protect(VmPerms::READ, OFFSET..OFFSET + PAGE_SIZE)
+            .unwrap();
+        assert!(!vm_space.is_writable(OFFSET));
+        assert!(vm_space.is_writable(OFFSET + 2 * PAGE_SIZE));
+        assert!(vm_space.is_writable(OFFSET + huge_page_nbytes - PAGE_SIZE));
+    }
+
+    #[ktest]
+    fn map_hugetlb_page() {
+        const OFFSET: usize = 0x4000_0000;
+        let huge_page_nbytes = PageSize::Size2M.nbytes();
+        let root_vmar = Vmar::<Full>::new_root();
+        let vmo = VmoOptions::<Full>::new(huge_page_nbytes)
+            .alloc()
+            .unwrap()
+            .to_dyn();
+        root_vmar
+            .new_map(vmo, VmPerms::READ | VmPerms::WRITE)
+            .unwrap()
+            .offset(OFFSET)
+            .page_size(PageSize::Size2M)
+            .build()
+            .unwrap();
+
+        root_vmar
+            .handle_page_fault(OFFSET + huge_page_nbytes - 1, true, true)
+            .unwrap();
+        let vm_space = root_vmar.vm_space();
+        assert!(vm_space.is_mapped(OFFSET));
+        assert_eq!(vm_space.nr_mapped_pages(), PageSize::Size2M.nframes());
+
+        root_vmar
+            .destroy(OFFSET..OFFSET + huge_page_nbytes)
+            .unwrap();
+        assert!(!vm_space.is_mapped(OFFSET));
+        assert_eq!(vm_space.nr_mapped_pages(), 0);
+    }
 }
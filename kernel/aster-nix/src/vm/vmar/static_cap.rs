@@ -114,6 +114,19 @@ impl<R: TRights> Vmar<TRightSet<R>> {
         self.0.protect(perms, range)
     }
 
+    /// Writes the bytes at the offset even if the memory is mapped read-only,
+    /// as a debugger does to insert breakpoints into the text of a tracee.
+    ///
+    /// The written pages of private mappings are copied on write.
+    ///
+    /// # Access rights
+    ///
+    /// The method requires the Write right.
+    pub fn force_write_bytes(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        self.check_rights(Rights::WRITE)?;
+        self.0.force_write(offset, buf)
+    }
+
     /// clear all mappings and children vmars.
     /// After being cleared, this vmar will become an empty vmar
     pub fn clear(&self) -> Result<()> {
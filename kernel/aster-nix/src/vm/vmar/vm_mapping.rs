@@ -2,15 +2,22 @@
 
 use core::ops::Range;
 
-use aster_frame::vm::{VmFrame, VmFrameVec, VmIo, VmMapOptions, VmPerm, VmSpace};
+use align_ext::AlignExt;
+use aster_frame::vm::{PageSize, VmFrame, VmFrameVec, VmIo, VmMapOptions, VmPerm, VmSpace};
 
-use super::{interval::Interval, is_intersected, Vmar, Vmar_};
+use super::{check_memlock_limit, interval::Interval, is_intersected, Vmar, Vmar_};
 use crate::{
+    events::Observer,
+    fs::userfaultfd::{UffdRegisterMode, UserfaultRegistration},
+    ipc::shm::ShmAttachment,
     prelude::*,
     vm::{
         perms::VmPerms,
         vmar::Rights,
-        vmo::{get_page_idx_range, Vmo, VmoChildOptions, VmoRightsOp},
+        vmo::{
+            get_page_idx_range, Vmo, VmoChildOptions, VmoFlags, VmoOptions, VmoRightsOp,
+            VmoWriteback,
+        },
     },
 };
 
@@ -24,6 +31,10 @@ pub struct VmMapping {
     parent: Weak<Vmar_>,
     /// The mapped vmo. The mapped vmo is with dynamic capability.
     vmo: Vmo<Rights>,
+    /// Whether the mapping is shared with the child vmar when the vmar is forked.
+    is_shared: bool,
+    /// The attachment of the System V shared memory segment that is mapped, if any.
+    shm_attachment: Option<ShmAttachment>,
 }
 
 impl VmMapping {
@@ -34,6 +45,8 @@ impl VmMapping {
             inner: Mutex::new(inner),
             parent: self.parent.clone(),
             vmo,
+            is_shared: self.is_shared,
+            shm_attachment: self.shm_attachment.clone(),
         })
     }
 }
@@ -54,6 +67,38 @@ struct VmMappingInner {
     /// The permission of pages in the mapping.
     /// All pages within the same VmMapping have the same permission.
     perm: VmPerm,
+    /// The flags advised for the mapping.
+    flags: MappingFlags,
+    /// The size of the pages backing the mapping, which is larger than `PAGE_SIZE`
+    /// only for the mappings of huge pages required by users (e.g., `MAP_HUGETLB`).
+    page_size: PageSize,
+    /// The userfaultfd that the mapping is registered with, which handles the faults.
+    userfault: Option<UserfaultRegistration>,
+    /// Whether the huge pages used transparently have failed to be allocated, after which
+    /// the mapping is backed by base pages without trying huge pages again.
+    is_huge_page_failed: bool,
+}
+
+bitflags! {
+    /// The flags of a `VmMapping`, which are advised by users with `madvise`
+    /// or set by `mlock`.
+    pub struct MappingFlags: u32 {
+        /// The mapping is not inherited by the child vmar when the vmar is forked.
+        const DONTFORK    = 1 << 0;
+        /// The mapping is inherited as zero-filled memory by the child vmar when the vmar
+        /// is forked.
+        const WIPEONFORK  = 1 << 1;
+        /// The mapping is worth being backed by huge pages.
+        const HUGEPAGE    = 1 << 2;
+        /// The mapping is not worth being backed by huge pages.
+        const NOHUGEPAGE  = 1 << 3;
+        /// The pages of the mapping are locked in memory, i.e., they are never
+        /// reclaimed or swapped out.
+        const LOCKED      = 1 << 4;
+        /// The pages of the mapping are populated on faults instead of being
+        /// populated when the mapping is locked. Only valid with `LOCKED`.
+        const LOCKONFAULT = 1 << 5;
+    }
 }
 
 impl Interval<usize> for Arc<VmMapping> {
@@ -62,6 +107,28 @@ impl Interval<usize> for Arc<VmMapping> {
     }
 }
 
+impl Observer<VmoWriteback> for VmMapping {
+    /// Write-protect the mapped pages that are about to be written back, so that
+    /// the next writes fault and mark the pages dirty again.
+    fn on_events(&self, events: &VmoWriteback) {
+        let Some(vmar) = self.parent.upgrade() else {
+            return;
+        };
+        let vm_space = vmar.vm_space();
+        let inner = self.inner.lock();
+        if inner.is_destroyed {
+            return;
+        }
+        let perm = inner.perm - VmPerm::W;
+        for &page_idx in inner.mapped_pages.range(events.page_idx_range.clone()) {
+            let page_addr = inner.page_map_addr(page_idx);
+            if vm_space.is_writable(page_addr) {
+                let _ = vm_space.protect(&(page_addr..page_addr + PAGE_SIZE), perm);
+            }
+        }
+    }
+}
+
 impl VmMapping {
     pub fn build_mapping<R1, R2>(option: VmarMapOptions<R1, R2>) -> Result<Self> {
         let VmarMapOptions {
@@ -71,18 +138,16 @@ impl VmMapping {
             vmo_offset,
             size,
             offset,
+            hint,
             align,
             can_overwrite,
+            is_shared,
+            page_size,
+            shm_attachment,
         } = option;
         let Vmar(parent_vmar, _) = parent;
-        let vmo_size = vmo.size();
-        let map_to_addr = parent_vmar.allocate_free_region_for_vmo(
-            vmo_size,
-            size,
-            offset,
-            align,
-            can_overwrite,
-        )?;
+        let map_to_addr =
+            parent_vmar.allocate_free_region_for_vmo(size, offset, hint, align, can_overwrite)?;
         trace!(
             "build mapping, map_range = 0x{:x}- 0x{:x}",
             map_to_addr,
@@ -96,34 +161,43 @@ impl VmMapping {
             is_destroyed: false,
             mapped_pages: BTreeSet::new(),
             perm: VmPerm::from(perms),
+            flags: parent_vmar.default_mapping_flags(),
+            page_size,
+            userfault: None,
+            is_huge_page_failed: false,
         };
 
         Ok(Self {
             inner: Mutex::new(vm_mapping_inner),
             parent: Arc::downgrade(&parent_vmar),
             vmo: vmo.to_dyn(),
+            is_shared,
+            shm_attachment,
         })
     }
 
+    /// Wrap the mapping in an `Arc`.
+    ///
+    /// A shared mapping of a file is registered to the vmo, so that its pages are
+    /// write-protected when they are written back.
+    pub(super) fn new_arc(mapping: Self) -> Arc<Self> {
+        let mapping = Arc::new(mapping);
+        if mapping.is_shared && mapping.vmo.is_file_backed() && !mapping.vmo.is_cow_vmo() {
+            let observer: Weak<dyn Observer<VmoWriteback>> = Arc::downgrade(&mapping) as _;
+            mapping.vmo.register_writeback_observer(observer);
+        }
+        mapping
+    }
+
     /// Build a new VmMapping based on part of current `VmMapping`.
     /// The mapping range of the new mapping must be contained in the full mapping.
     ///
     /// Note: Since such new mappings will intersect with the current mapping,
     /// making sure that when adding the new mapping into a Vmar, the current mapping in the Vmar will be removed.
-    fn clone_partial(
-        &self,
-        range: Range<usize>,
-        new_perm: Option<VmPerm>,
-    ) -> Result<Arc<VmMapping>> {
-        let partial_mapping = Arc::new(self.try_clone()?);
-        // Adjust the mapping range and the permission.
-        {
-            let mut inner = partial_mapping.inner.lock();
-            inner.shrink_to(range);
-            if let Some(perm) = new_perm {
-                inner.perm = perm;
-            }
-        }
+    fn clone_partial(&self, range: Range<usize>) -> Result<Arc<VmMapping>> {
+        let partial_mapping = Self::new_arc(self.try_clone()?);
+        // Adjust the mapping range.
+        partial_mapping.inner.lock().shrink_to(range);
         Ok(partial_mapping)
     }
 
@@ -131,20 +205,48 @@ impl VmMapping {
         &self.vmo
     }
 
+    pub fn is_shared(&self) -> bool {
+        self.is_shared
+    }
+
+    pub fn shm_attachment(&self) -> Option<&ShmAttachment> {
+        self.shm_attachment.as_ref()
+    }
+
+    /// Returns whether the mapping is writable.
+    pub fn is_writable(&self) -> bool {
+        self.check_perm(&VmPerm::W).is_ok()
+    }
+
+    /// Returns whether the mapping is allowed to be writable, which is decided when
+    /// the mapping is created (like `VM_MAYWRITE` in Linux).
+    pub fn may_write(&self) -> bool {
+        self.vmo.rights().contains(Rights::WRITE)
+    }
+
+    pub fn flags(&self) -> MappingFlags {
+        self.inner.lock().flags
+    }
+
+    pub fn page_size(&self) -> PageSize {
+        self.inner.lock().page_size
+    }
+
+    /// Returns the userfaultfd registration of the mapping, if any.
+    pub fn userfault(&self) -> Option<UserfaultRegistration> {
+        self.inner.lock().userfault.clone()
+    }
+
     /// Set the entries in the page table associated with the current `VmMapping` to read-only.
     pub(super) fn set_pt_read_only(&self, vm_space: &VmSpace) -> Result<()> {
         let map_inner = self.inner.lock();
-        let mapped_addr = &map_inner.mapped_pages;
         let perm = map_inner.perm;
         if !perm.contains(VmPerm::W) {
             return Ok(());
         }
 
-        for page_idx in mapped_addr {
-            let map_addr = map_inner.page_map_addr(*page_idx);
-            vm_space.protect(&(map_addr..map_addr + PAGE_SIZE), perm - VmPerm::W)?;
-        }
-        Ok(())
+        // The whole range is protected at once to keep the huge pages in it.
+        vm_space.protect(&map_inner.range(), perm - VmPerm::W)
     }
 
     /// Add a new committed page and map it to vmspace. If copy on write is set, it's allowed to unmap the page at the same address.
@@ -192,6 +294,7 @@ impl VmMapping {
         self.check_page_idx_range(&page_idx_range)?;
         let read_perm = VmPerm::R;
         self.check_perm(&read_perm)?;
+        self.handle_userfaults(page_idx_range, false)?;
 
         self.vmo.read_bytes(vmo_read_offset, buf)?;
         Ok(())
@@ -204,6 +307,7 @@ impl VmMapping {
         self.check_page_idx_range(&page_idx_range)?;
         let write_perm = VmPerm::W;
         self.check_perm(&write_perm)?;
+        self.handle_userfaults(page_idx_range.clone(), true)?;
 
         let mut page_addr =
             self.map_to_addr() - self.vmo_offset() + page_idx_range.start * PAGE_SIZE;
@@ -225,6 +329,78 @@ impl VmMapping {
         Ok(())
     }
 
+    /// Reports the faults on the pages within the range to the userfaultfd that the
+    /// mapping is registered with, before the pages are accessed by the kernel on
+    /// behalf of the user space (e.g., with `process_vm_readv`).
+    ///
+    /// Like the faults from the user space, the missing pages and the write-protected
+    /// pages are resolved by the handler. An error is returned if they are not.
+    fn handle_userfaults(&self, page_idx_range: Range<usize>, write: bool) -> Result<()> {
+        let Some(registration) = self.userfault() else {
+            return Ok(());
+        };
+        let mode = registration.mode();
+        let map_base = self.map_to_addr() - self.vmo_offset();
+        for page_idx in page_idx_range {
+            let page_addr = map_base + page_idx * PAGE_SIZE;
+            // The faults are handled by the kernel after the userfaultfd is closed.
+            let Some(ctx) = registration.ctx() else {
+                return Ok(());
+            };
+            if mode.contains(UffdRegisterMode::MISSING) && !self.vmo.is_page_present(page_idx) {
+                ctx.handle_fault(page_addr, write, false)?;
+                if registration.ctx().is_some() && !self.vmo.is_page_present(page_idx) {
+                    return_errno_with_message!(Errno::EFAULT, "the missing page is not resolved");
+                }
+            }
+            if write && mode.contains(UffdRegisterMode::WP) && ctx.is_write_protected(page_addr) {
+                ctx.handle_fault(page_addr, true, true)?;
+                if ctx.is_write_protected(page_addr) {
+                    return_errno_with_message!(Errno::EFAULT, "the page is still write-protected");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the bytes at the offset even if the mapping is not writable, e.g., to insert
+    /// breakpoints into the text of a traced process.
+    ///
+    /// The written pages of a private mapping are copied on write, so that the write is not
+    /// seen by others, e.g., through the page cache of the mapped file. A shared mapping that
+    /// is not writable cannot be written.
+    pub fn force_write_bytes(&self, offset: usize, buf: &[u8]) -> Result<()> {
+        if self.is_writable() {
+            return self.write_bytes(offset, buf);
+        }
+        if self.is_shared {
+            return_errno_with_message!(Errno::EFAULT, "the shared mapping is not writable");
+        }
+
+        let vmo_write_range =
+            (self.vmo_offset() + offset)..(self.vmo_offset() + offset + buf.len());
+        let page_idx_range = get_page_idx_range(&vmo_write_range);
+        self.check_page_idx_range(&page_idx_range)?;
+
+        for page_idx in page_idx_range {
+            let page_range = (page_idx * PAGE_SIZE)..((page_idx + 1) * PAGE_SIZE);
+            let write_range = vmo_write_range.start.max(page_range.start)
+                ..vmo_write_range.end.min(page_range.end);
+            let frame = self.vmo.get_committed_frame(page_idx, true)?;
+            frame.write_bytes(
+                write_range.start - page_range.start,
+                &buf[(write_range.start - vmo_write_range.start)
+                    ..(write_range.end - vmo_write_range.start)],
+            )?;
+            // The page of a cow vmo may have been copied, so the copy is mapped in place of
+            // the page that is shared with the parent vmo.
+            if self.vmo.is_cow_vmo() {
+                self.map_one_page(page_idx, frame, true)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Unmap pages in the range
     pub fn unmap(&self, range: &Range<usize>, may_destroy: bool) -> Result<()> {
         let parent = self.parent.upgrade().unwrap();
@@ -256,14 +432,115 @@ impl VmMapping {
         let required_perm = if write { VmPerm::W } else { VmPerm::R };
         self.check_perm(&required_perm)?;
 
+        // The faults in a range registered with a userfaultfd are resolved by the
+        // user-space handler, after which the access is retried.
+        let mut is_write_protected = false;
+        if let Some(registration) = self.userfault()
+            && let Some(ctx) = registration.ctx()
+        {
+            let page_addr = page_fault_addr.align_down(PAGE_SIZE);
+            let mode = registration.mode();
+            if mode.contains(UffdRegisterMode::MISSING) && !self.vmo.is_page_present(page_idx) {
+                return ctx.handle_fault(page_addr, write, false);
+            }
+            is_write_protected =
+                mode.contains(UffdRegisterMode::WP) && ctx.is_write_protected(page_addr);
+            if is_write_protected && write {
+                return ctx.handle_fault(page_addr, true, true);
+            }
+        }
+
+        if let Some(page_size) = self.huge_page_size() {
+            if self.map_huge_page(page_fault_addr, page_size)? {
+                return Ok(());
+            }
+            // The huge pages required by users are not allowed to fall back to base pages.
+            if page_size == self.inner.lock().page_size {
+                return_errno_with_message!(Errno::ENOMEM, "no huge page is available");
+            }
+        }
+
         let frame = self.vmo.get_committed_frame(page_idx, write)?;
 
         // If read access to cow vmo triggers page fault, the map should be readonly.
         // If user next tries to write to the frame, another page fault will be triggered.
-        let is_readonly = self.vmo.is_cow_vmo() && !write;
+        // The same goes for file-backed vmo, whose pages are marked dirty on the write faults.
+        // A write-protected page is always readonly.
+        let is_readonly =
+            ((self.vmo.is_cow_vmo() || self.vmo.is_file_backed()) && !write) || is_write_protected;
         self.map_one_page(page_idx, frame, is_readonly)
     }
 
+    /// Return the size of the huge pages that should back the mapping, if any.
+    ///
+    /// The huge pages are either required by users, or used transparently for large
+    /// anonymous mappings unless advised otherwise or failed to be allocated before.
+    /// A vmo that requires COW is backed by base pages, since its pages are copied
+    /// one at a time. So is a mapping registered with a userfaultfd, whose pages are
+    /// filled one at a time.
+    fn huge_page_size(&self) -> Option<PageSize> {
+        if self.vmo.is_file_backed() || self.vmo.is_cow_vmo() {
+            return None;
+        }
+
+        let inner = self.inner.lock();
+        if inner.page_size != PageSize::Size4K {
+            return Some(inner.page_size);
+        }
+        if inner.userfault.is_some() {
+            return None;
+        }
+        let is_transparent = !inner.flags.contains(MappingFlags::NOHUGEPAGE)
+            && !inner.is_huge_page_failed
+            && inner.map_size >= PageSize::Size2M.nbytes()
+            && PageSize::Size2M.is_supported();
+        is_transparent.then_some(PageSize::Size2M)
+    }
+
+    /// Map the huge page that contains the address, returning whether the huge page
+    /// is mapped.
+    ///
+    /// The huge page cannot be mapped if it is not fully in the mapping and the vmo,
+    /// or if it cannot be backed by contiguous frames. Once the contiguous frames fail
+    /// to be allocated, which happens on every fault when the memory is fragmented,
+    /// the mapping stops using huge pages transparently.
+    fn map_huge_page(&self, addr: Vaddr, page_size: PageSize) -> Result<bool> {
+        let parent = self.parent.upgrade().unwrap();
+        let vm_space = parent.vm_space();
+        let mut inner = self.inner.lock();
+
+        let huge_addr = addr.align_down(page_size.nbytes());
+        let huge_range = huge_addr..huge_addr + page_size.nbytes();
+        let vmo_range = inner.vmo_range(&huge_range);
+        if huge_range.start < inner.map_to_addr
+            || huge_range.end > inner.map_to_addr + inner.map_size
+            || vmo_range.start % page_size.nbytes() != 0
+            || vmo_range.end > self.vmo.size()
+        {
+            return Ok(false);
+        }
+
+        // The huge page may have been mapped by an earlier fault, e.g., when populating pages.
+        let page_idx_range = get_page_idx_range(&vmo_range);
+        if inner.mapped_pages.range(page_idx_range.clone()).count() == page_idx_range.len()
+            && vm_space.is_mapped(huge_addr)
+        {
+            return Ok(true);
+        }
+
+        let frames = match self.vmo.commit_contiguous(vmo_range.clone()) {
+            Ok(Some(frames)) => frames,
+            Ok(None) => return Ok(false),
+            Err(err) if err.error() == Errno::ENOMEM => {
+                inner.is_huge_page_failed = true;
+                return Ok(false);
+            }
+            Err(err) => return Err(err),
+        };
+        inner.map_huge_page(vm_space, page_idx_range.start, frames, page_size)?;
+        Ok(true)
+    }
+
     /// Protect a specified range of pages in the mapping to the target perms.
     /// The VmMapping will split to maintain its property.
     ///
@@ -276,22 +553,338 @@ impl VmMapping {
             return Ok(());
         }
 
-        let rights = Rights::from(new_perms);
-        self.vmo().check_rights(rights)?;
+        if !self.vmo.rights().contains(Rights::from(new_perms)) {
+            return_errno_with_message!(Errno::EACCES, "the permissions are not allowed");
+        }
         // Protect permission for the perm in the VmMapping.
         self.protect_with_subdivision(&range, VmPerm::from(new_perms))?;
-        // Protect permission in the VmSpace.
+        // Protect permission in the VmSpace. The pages that trap writes (see `handle_page_fault`)
+        // stay readonly, and they will be remapped as writable on the next write faults.
+        let pt_perms =
+            if self.vmo.is_cow_vmo() || self.vmo.is_file_backed() || self.is_registered_for_wp() {
+                new_perms - VmPerms::WRITE
+            } else {
+                new_perms
+            };
         let vmar = self.parent.upgrade().unwrap();
         let vm_space = vmar.vm_space();
-        self.inner.lock().protect(vm_space, new_perms, range)?;
+        self.inner.lock().protect(vm_space, pt_perms, range)?;
 
         Ok(())
     }
 
-    pub(super) fn new_cow(&self, new_parent: &Arc<Vmar_>) -> Result<VmMapping> {
-        let VmMapping { inner, vmo, .. } = self;
+    /// Write back the dirty pages within the range to the file backing the mapping.
+    ///
+    /// Only shared mappings have updates to be written back. The mapped pages are
+    /// write-protected before the writeback (see `on_events`), so that the next writes
+    /// mark them dirty again.
+    pub(super) fn sync(&self, range: Range<usize>) -> Result<()> {
+        if !self.is_shared || !self.vmo.is_file_backed() {
+            return Ok(());
+        }
+
+        // The lock is released before the writeback, which notifies the mapping.
+        let vmo_range = self.inner.lock().vmo_range(&range);
+        self.vmo.sync(vmo_range)
+    }
+
+    /// Return whether each page within the range is resident in memory,
+    /// i.e., committed in the vmo.
+    pub(super) fn resident_pages(&self, range: Range<usize>) -> Vec<bool> {
+        let inner = self.inner.lock();
+        let vmo_size = self.vmo.size();
+        get_page_idx_range(&inner.vmo_range(&range))
+            .map(|page_idx| page_idx * PAGE_SIZE < vmo_size && self.vmo.is_page_committed(page_idx))
+            .collect()
+    }
+
+    /// Discard the pages within the range.
+    ///
+    /// The pages of a private mapping are decommitted from the vmo, so they will be
+    /// zero-filled or read from the file again on the next access. The pages of a
+    /// shared mapping are only unmapped, since their content is shared with others.
+    pub(super) fn discard(&self, range: Range<usize>) -> Result<()> {
+        let vmar = self.parent.upgrade().unwrap();
+        let vm_space = vmar.vm_space();
+        let mut inner = self.inner.lock();
+        inner.unmap(vm_space, &range, false)?;
+        if self.is_shared {
+            return Ok(());
+        }
+
+        let vmo_range = inner.vmo_range(&range);
+        let vmo_size = self.vmo.size();
+        if vmo_range.start < vmo_size {
+            self.vmo
+                .decommit(vmo_range.start..vmo_range.end.min(vmo_size))?;
+        }
+        Ok(())
+    }
+
+    /// Zero the committed pages within the range.
+    ///
+    /// The pages may also be mapped by other vmars, so they are zeroed in place rather
+    /// than decommitted from the vmo.
+    pub(super) fn zero_pages(&self, range: Range<usize>) -> Result<()> {
+        let inner = self.inner.lock();
+        let vmo_size = self.vmo.size();
+        for page_idx in get_page_idx_range(&inner.vmo_range(&range)) {
+            if page_idx * PAGE_SIZE < vmo_size && self.vmo.is_page_committed(page_idx) {
+                self.vmo
+                    .clear((page_idx * PAGE_SIZE)..((page_idx + 1) * PAGE_SIZE))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set and clear the flags of the mapping within the range.
+    /// The VmMapping will split to maintain its property.
+    pub(super) fn update_flags(
+        &self,
+        range: Range<usize>,
+        set_flags: MappingFlags,
+        clear_flags: MappingFlags,
+    ) -> Result<()> {
+        let old_flags = self.flags();
+        if (old_flags | set_flags) - clear_flags == old_flags {
+            return Ok(());
+        }
+        self.update_with_subdivision(&range, |inner| {
+            inner.flags = (inner.flags | set_flags) - clear_flags;
+        })
+    }
+
+    /// Register or unregister the mapping within the range with a userfaultfd.
+    /// The VmMapping will split to maintain its property.
+    pub(super) fn set_userfault(
+        &self,
+        range: Range<usize>,
+        registration: Option<UserfaultRegistration>,
+    ) -> Result<()> {
+        self.update_with_subdivision(&range, |inner| inner.userfault = registration)
+    }
+
+    /// Return whether the mapping is registered with a userfaultfd in the write-protect mode.
+    fn is_registered_for_wp(&self) -> bool {
+        self.userfault()
+            .is_some_and(|registration| registration.mode().contains(UffdRegisterMode::WP))
+    }
+
+    /// Fill the missing page at the address with the content, e.g., for `UFFDIO_COPY`.
+    ///
+    /// The page is mapped on the next fault.
+    pub(super) fn fill_page(&self, page_addr: Vaddr, content: &[u8]) -> Result<()> {
+        let vmo_offset = self.vmo_offset() + page_addr - self.map_to_addr();
+        if vmo_offset >= self.vmo.size() {
+            return_errno_with_message!(Errno::EFAULT, "the page is not backed up by the vmo");
+        }
+        if self.vmo.is_page_present(vmo_offset / PAGE_SIZE) {
+            return_errno_with_message!(Errno::EEXIST, "the page is already present");
+        }
+        self.vmo.write_bytes(vmo_offset, content)?;
+        Ok(())
+    }
+
+    /// Make the pages within the range readonly in the page table, so that the writes
+    /// to them trigger page faults.
+    pub(super) fn write_protect(&self, range: Range<usize>) -> Result<()> {
+        let vmar = self.parent.upgrade().unwrap();
+        let vm_space = vmar.vm_space();
+        let mut inner = self.inner.lock();
+        let perms = VmPerms::from(inner.perm) - VmPerms::WRITE;
+        inner.protect(vm_space, perms, range)
+    }
+
+    /// Read the pages within the range from the file backing the mapping in advance.
+    pub(super) fn readahead(&self, range: Range<usize>) -> Result<()> {
+        let vmo_range = self.inner.lock().vmo_range(&range);
+        self.vmo.readahead(vmo_range)
+    }
+
+    /// Hint that the pages within the range are not likely to be accessed in the near future.
+    pub(super) fn deactivate(&self, range: Range<usize>) {
+        let vmo_range = self.inner.lock().vmo_range(&range);
+        self.vmo.deactivate(vmo_range);
+    }
+
+    /// Populate the page table for the pages within the range by handling page faults.
+    pub(super) fn populate(&self, range: Range<usize>, write: bool) -> Result<()> {
+        let required_perm = if write { VmPerm::W } else { VmPerm::R };
+        if self.check_perm(&required_perm).is_err() {
+            return_errno_with_message!(Errno::EINVAL, "the mapping does not permit the access");
+        }
+        for page_addr in range.step_by(PAGE_SIZE) {
+            self.handle_page_fault(page_addr, true, write)
+                .map_err(|_| Error::with_message(Errno::EFAULT, "the page cannot be populated"))?;
+        }
+        Ok(())
+    }
+
+    /// Fault in the pages within the range as if they were accessed, e.g., for `mlock`
+    /// or `MAP_POPULATE`.
+    ///
+    /// The pages of a private writable mapping are faulted in for write, so that
+    /// they are copied in advance. The mapping without any access is skipped.
+    pub(super) fn prefault(&self, range: Range<usize>) -> Result<()> {
+        if self.check_perm(&VmPerm::R).is_err() {
+            return Ok(());
+        }
+        let write = !self.is_shared && self.check_perm(&VmPerm::W).is_ok();
+        self.populate(range, write)
+    }
+
+    /// Page out the pages within the range, so that they are the first to be reclaimed.
+    ///
+    /// The dirty pages of a shared file mapping are written back, and then all the pages
+    /// are unmapped and deactivated.
+    pub(super) fn page_out(&self, range: Range<usize>) -> Result<()> {
+        // The locked pages stay in memory.
+        if self.flags().contains(MappingFlags::LOCKED) {
+            return Ok(());
+        }
+        self.sync(range.clone())?;
 
-        let child_vmo = {
+        let vmar = self.parent.upgrade().unwrap();
+        let vm_space = vmar.vm_space();
+        let mut inner = self.inner.lock();
+        inner.unmap(vm_space, &range, false)?;
+        self.vmo.deactivate(inner.vmo_range(&range));
+        Ok(())
+    }
+
+    /// Reclaim at most `nr_to_reclaim` mapped pages that are not recently used, by
+    /// swapping them out. Return the number of reclaimed pages.
+    ///
+    /// The accessed bit of each mapped page is cleared when the page is scanned, so
+    /// a page is reclaimed only if it has not been accessed since the last scan.
+    pub(super) fn reclaim_pages(&self, nr_to_reclaim: usize) -> usize {
+        // The pages shared with a pager are reclaimed by the page cache.
+        if self.vmo.is_file_backed() && !self.vmo.is_cow_vmo() {
+            return 0;
+        }
+        let Some(vmar) = self.parent.upgrade() else {
+            return 0;
+        };
+        let vm_space = vmar.vm_space();
+
+        let mut inner = self.inner.lock();
+        // Huge pages are never swapped out.
+        if inner.page_size != PageSize::Size4K || inner.flags.contains(MappingFlags::LOCKED) {
+            return 0;
+        }
+        let mapped_pages: Vec<usize> = inner.mapped_pages.iter().copied().collect();
+        let mut nr_reclaimed = 0;
+        for page_idx in mapped_pages {
+            if nr_reclaimed >= nr_to_reclaim {
+                break;
+            }
+            let page_addr = inner.page_map_addr(page_idx);
+            if vm_space.clear_accessed(page_addr) != Some(false) {
+                continue;
+            }
+            // The page is mapped again on the next access if it cannot be swapped out.
+            if inner.unmap_one_page(vm_space, page_idx).is_err() {
+                continue;
+            }
+            if let Ok(true) = self.vmo.swap_out_page(page_idx) {
+                nr_reclaimed += 1;
+            }
+        }
+        nr_reclaimed
+    }
+
+    /// Build a new `VmMapping` that moves part of the current mapping to a new address.
+    /// The range of the part must be contained in the current mapping.
+    ///
+    /// The new mapping maps the same pages of the vmo, but none of them is mapped
+    /// in the page table yet.
+    pub(super) fn clone_moved(&self, range: Range<usize>, new_addr: Vaddr) -> Result<Arc<Self>> {
+        let moved_mapping = Self::new_arc(self.try_clone()?);
+        {
+            let mut inner = moved_mapping.inner.lock();
+            inner.shrink_to(range);
+            inner.map_to_addr = new_addr;
+            inner.mapped_pages.clear();
+        }
+        Ok(moved_mapping)
+    }
+
+    /// Enlarge the mapping to the new size. The enlarged address range must have been
+    /// allocated in the parent vmar.
+    ///
+    /// If the vmo cannot be enlarged together with the mapping, a new mapping that maps
+    /// the enlarged range is returned, which should be added to the parent vmar.
+    pub(super) fn enlarge(&self, new_size: usize) -> Result<Option<Arc<Self>>> {
+        let mut inner = self.inner.lock();
+        debug_assert!(new_size > inner.map_size);
+        let vmo_end = inner.vmo_offset + inner.map_size;
+
+        // A shared file mapping maps the page cache of the file, which covers the enlarged range.
+        if self.vmo.is_file_backed() && !self.vmo.is_cow_vmo() {
+            inner.map_size = new_size;
+            return Ok(None);
+        }
+
+        // The pages beyond the end of an anonymous vmo are not used by any other mappings,
+        // so the vmo can be resized if the mapping reaches its end.
+        if !self.vmo.is_file_backed()
+            && self.vmo.flags().contains(VmoFlags::RESIZABLE)
+            && vmo_end == self.vmo.size()
+        {
+            self.vmo.resize(inner.vmo_offset + new_size)?;
+            inner.map_size = new_size;
+            return Ok(None);
+        }
+
+        // Otherwise, the enlarged range is backed by a new vmo. For anonymous memory, the new vmo
+        // is zero-filled. For a private file mapping, the new vmo is copied-on-write from the
+        // following pages of the file.
+        let extended_size = new_size - inner.map_size;
+        let extended_vmo = if self.vmo.is_file_backed() {
+            VmoChildOptions::new_cow(self.vmo.dup()?, vmo_end..(vmo_end + extended_size)).alloc()?
+        } else {
+            VmoOptions::<Rights>::new(extended_size)
+                .flags(VmoFlags::RESIZABLE)
+                .alloc()?
+        };
+        let extended_inner = VmMappingInner {
+            vmo_offset: 0,
+            map_size: extended_size,
+            map_to_addr: inner.map_to_addr + inner.map_size,
+            is_destroyed: false,
+            mapped_pages: BTreeSet::new(),
+            perm: inner.perm,
+            flags: inner.flags,
+            page_size: inner.page_size,
+            userfault: inner.userfault.clone(),
+            is_huge_page_failed: inner.is_huge_page_failed,
+        };
+        Ok(Some(Arc::new(Self {
+            inner: Mutex::new(extended_inner),
+            parent: self.parent.clone(),
+            vmo: extended_vmo,
+            is_shared: self.is_shared,
+            shm_attachment: None,
+        })))
+    }
+
+    pub(super) fn new_cow(&self, new_parent: &Arc<Vmar_>) -> Result<VmMapping> {
+        let VmMapping {
+            inner,
+            vmo,
+            is_shared,
+            ..
+        } = self;
+
+        let inner_flags = self.inner.lock().flags;
+        // A shared mapping refers to the same vmo in the child vmar.
+        let child_vmo = if *is_shared {
+            vmo.dup()?
+        } else if inner_flags.contains(MappingFlags::WIPEONFORK) {
+            VmoOptions::<Rights>::new(vmo.size())
+                .flags(VmoFlags::RESIZABLE)
+                .alloc()?
+        } else {
             let parent_vmo = vmo.dup().unwrap();
             let vmo_size = parent_vmo.size();
             VmoChildOptions::new_cow(parent_vmo, 0..vmo_size).alloc()?
@@ -306,6 +899,12 @@ impl VmMapping {
                 is_destroyed: inner.is_destroyed,
                 mapped_pages: BTreeSet::new(),
                 perm: inner.perm,
+                // Like Linux, memory locks are not inherited by the child.
+                flags: inner.flags - (MappingFlags::LOCKED | MappingFlags::LOCKONFAULT),
+                page_size: inner.page_size,
+                // The faults in the child are handled by the kernel.
+                userfault: None,
+                is_huge_page_failed: false,
             }
         };
 
@@ -313,6 +912,9 @@ impl VmMapping {
             inner: Mutex::new(new_inner),
             parent: Arc::downgrade(new_parent),
             vmo: child_vmo,
+            is_shared: *is_shared,
+            // The child attaches the segment as well.
+            shm_attachment: self.shm_attachment.clone(),
         })
     }
 
@@ -322,8 +924,15 @@ impl VmMapping {
 
     /// Protect the current `VmMapping` to enforce new permissions within a specified range.
     ///
+    /// Generally, this function is only used in `protect()` method.
+    fn protect_with_subdivision(&self, intersect_range: &Range<usize>, perm: VmPerm) -> Result<()> {
+        self.update_with_subdivision(intersect_range, |inner| inner.perm = perm)
+    }
+
+    /// Update the properties (e.g., the permission) of the current `VmMapping` within a specified range.
+    ///
     /// Due to the property of `VmMapping`, this operation may require subdividing the current
-    /// `VmMapping`. In this condition, it will generate a new `VmMapping` with the specified `perm` to protect the
+    /// `VmMapping`. In this condition, it will generate a new `VmMapping` with the updated properties for the
     /// target range, as well as additional `VmMappings` to preserve the mappings in the remaining ranges.
     ///
     /// There are four conditions:
@@ -332,31 +941,33 @@ impl VmMapping {
     /// 3. |--------old perm--------| -> |-old-| + |-new-| + |-old-|
     /// 4. |--------old perm--------| -> |---------new perm--------|
     ///
-    /// Generally, this function is only used in `protect()` method.
     /// This method modifies the parent `Vmar` in the end if subdividing is required.
     /// It removes current mapping and add splitted mapping to the Vmar.
-    fn protect_with_subdivision(&self, intersect_range: &Range<usize>, perm: VmPerm) -> Result<()> {
+    fn update_with_subdivision(
+        &self,
+        intersect_range: &Range<usize>,
+        update: impl FnOnce(&mut VmMappingInner),
+    ) -> Result<()> {
         let mut additional_mappings = Vec::new();
         let range = self.range();
         // Condition 4, the `additional_mappings` will be empty.
         if range.start == intersect_range.start && range.end == intersect_range.end {
-            self.inner.lock().perm = perm;
+            update(&mut self.inner.lock());
             return Ok(());
         }
         // Condition 1 or 3, which needs an additional new VmMapping with range (range.start..intersect_range.start)
         if range.start < intersect_range.start {
-            let additional_left_mapping =
-                self.clone_partial(range.start..intersect_range.start, None)?;
+            let additional_left_mapping = self.clone_partial(range.start..intersect_range.start)?;
             additional_mappings.push(additional_left_mapping);
         }
         // Condition 2 or 3, which needs an additional new VmMapping with range (intersect_range.end..range.end).
         if range.end > intersect_range.end {
-            let additional_right_mapping =
-                self.clone_partial(intersect_range.end..range.end, None)?;
+            let additional_right_mapping = self.clone_partial(intersect_range.end..range.end)?;
             additional_mappings.push(additional_right_mapping);
         }
-        // The protected VmMapping must exist and its range is `intersect_range`.
-        let protected_mapping = self.clone_partial(intersect_range.clone(), Some(perm))?;
+        // The updated VmMapping must exist and its range is `intersect_range`.
+        let protected_mapping = self.clone_partial(intersect_range.clone())?;
+        update(&mut protected_mapping.inner.lock());
 
         // Begin to modify the `Vmar`.
         let vmar = self.parent.upgrade().unwrap();
@@ -415,7 +1026,7 @@ impl VmMapping {
         } else {
             if trim_range.end <= range.end {
                 // The trim range was totally inside the old mapping.
-                let another_mapping = Arc::new(self.try_clone()?);
+                let another_mapping = Self::new_arc(self.try_clone()?);
                 let another_map_to_addr = another_mapping.trim_left(trim_range.end)?;
                 mappings_to_append.insert(another_map_to_addr, another_mapping);
             } else {
@@ -464,7 +1075,6 @@ impl VmMappingInner {
         let vm_perm = {
             let mut perm = self.perm;
             if is_readonly {
-                debug_assert!(vmo.is_cow_vmo());
                 perm -= VmPerm::W;
             }
             perm
@@ -477,8 +1087,8 @@ impl VmMappingInner {
             options
         };
 
-        // Cow child allows unmapping the mapped page.
-        if vmo.is_cow_vmo() && vm_space.is_mapped(map_addr) {
+        // A page mapped as readonly (e.g., in a cow child) is remapped on write faults.
+        if vm_space.is_mapped(map_addr) {
             vm_space.unmap(&(map_addr..(map_addr + PAGE_SIZE))).unwrap();
         }
 
@@ -487,6 +1097,31 @@ impl VmMappingInner {
         Ok(())
     }
 
+    /// Map the contiguous frames as a huge page starting from the page index.
+    fn map_huge_page(
+        &mut self,
+        vm_space: &VmSpace,
+        start_page_idx: usize,
+        frames: VmFrameVec,
+        page_size: PageSize,
+    ) -> Result<()> {
+        let map_addr = self.page_map_addr(start_page_idx);
+        let vm_map_options = {
+            let mut options = VmMapOptions::new();
+            options.addr(Some(map_addr));
+            options.perm(self.perm);
+            options.page_size(page_size);
+            options
+        };
+
+        // Some of the pages may have been mapped as base pages.
+        vm_space.unmap(&(map_addr..(map_addr + page_size.nbytes())))?;
+        vm_space.map(frames, &vm_map_options)?;
+        self.mapped_pages
+            .extend(start_page_idx..start_page_idx + page_size.nframes());
+        Ok(())
+    }
+
     fn unmap_one_page(&mut self, vm_space: &VmSpace, page_idx: usize) -> Result<()> {
         let map_addr = self.page_map_addr(page_idx);
         let range = map_addr..(map_addr + PAGE_SIZE);
@@ -499,14 +1134,15 @@ impl VmMappingInner {
 
     /// Unmap pages in the range.
     fn unmap(&mut self, vm_space: &VmSpace, range: &Range<usize>, may_destroy: bool) -> Result<()> {
-        let map_to_addr = self.map_to_addr;
-        let vmo_map_range = (range.start - map_to_addr + self.vmo_offset)
-            ..(range.end - map_to_addr + self.vmo_offset);
-        let page_idx_range = get_page_idx_range(&vmo_map_range);
-        for page_idx in page_idx_range {
-            if self.mapped_pages.contains(&page_idx) {
-                self.unmap_one_page(vm_space, page_idx)?;
-            }
+        let map_range =
+            range.start.max(self.map_to_addr)..range.end.min(self.map_to_addr + self.map_size);
+        if map_range.start < map_range.end {
+            // The range is unmapped at once, which also covers the pages mapped as huge
+            // pages and the pages inherited from the parent vmar during forking.
+            vm_space.unmap(&map_range)?;
+            let page_idx_range = get_page_idx_range(&self.vmo_range(&map_range));
+            self.mapped_pages
+                .retain(|page_idx| !page_idx_range.contains(page_idx));
         }
         if may_destroy && *range == self.range() {
             self.is_destroyed = false;
@@ -518,6 +1154,12 @@ impl VmMappingInner {
         page_idx * PAGE_SIZE + self.map_to_addr - self.vmo_offset
     }
 
+    /// Return the range in the vmo that is mapped to the range of addresses.
+    fn vmo_range(&self, range: &Range<usize>) -> Range<usize> {
+        (range.start - self.map_to_addr + self.vmo_offset)
+            ..(range.end - self.map_to_addr + self.vmo_offset)
+    }
+
     pub(super) fn protect(
         &mut self,
         vm_space: &VmSpace,
@@ -526,18 +1168,8 @@ impl VmMappingInner {
     ) -> Result<()> {
         debug_assert!(range.start % PAGE_SIZE == 0);
         debug_assert!(range.end % PAGE_SIZE == 0);
-        let start_page = (range.start - self.map_to_addr + self.vmo_offset) / PAGE_SIZE;
-        let end_page = (range.end - self.map_to_addr + self.vmo_offset) / PAGE_SIZE;
-        let perm = VmPerm::from(perms);
-        for page_idx in start_page..end_page {
-            let page_addr = self.page_map_addr(page_idx);
-            if vm_space.is_mapped(page_addr) {
-                // If the page is already mapped, we will modify page table
-                let page_range = page_addr..(page_addr + PAGE_SIZE);
-                vm_space.protect(&page_range, perm)?;
-            }
-        }
-        Ok(())
+        // The range may contain unmapped pages, which are skipped by the VM space.
+        vm_space.protect(&range, VmPerm::from(perms))
     }
 
     /// Trim the mapping from left to a new address.
@@ -622,8 +1254,12 @@ pub struct VmarMapOptions<R1, R2> {
     vmo_offset: usize,
     size: usize,
     offset: Option<usize>,
+    hint: Option<Vaddr>,
     align: usize,
     can_overwrite: bool,
+    is_shared: bool,
+    page_size: PageSize,
+    shm_attachment: Option<ShmAttachment>,
 }
 
 impl<R1, R2> VmarMapOptions<R1, R2> {
@@ -642,8 +1278,12 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
             vmo_offset: 0,
             size,
             offset: None,
+            hint: None,
             align: PAGE_SIZE,
             can_overwrite: false,
+            is_shared: false,
+            page_size: PageSize::Size4K,
+            shm_attachment: None,
         }
     }
 
@@ -702,6 +1342,21 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
         self
     }
 
+    /// Sets the preferred offset of the mapping inside the VMAR.
+    ///
+    /// Unlike `offset`, the system will choose another offset if the hinted
+    /// range is not free or not aligned. If the hint is beyond
+    /// [`MAX_USERSPACE_VADDR`], the mapping may be placed beyond it as well,
+    /// which is only possible with 5-level paging.
+    ///
+    /// The option is ignored if `offset` is set.
+    ///
+    /// [`MAX_USERSPACE_VADDR`]: aster_frame::vm::MAX_USERSPACE_VADDR
+    pub fn hint(mut self, hint: Vaddr) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
     /// Sets whether the mapping can overwrite existing mappings.
     ///
     /// The default value is false.
@@ -713,6 +1368,40 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
         self
     }
 
+    /// Sets whether the mapping is shared with the child when the vmar is forked.
+    ///
+    /// A mapping that is not shared is copied-on-write to the child.
+    ///
+    /// The default value is false.
+    pub fn is_shared(mut self, is_shared: bool) -> Self {
+        self.is_shared = is_shared;
+        self
+    }
+
+    /// Sets the size of the pages backing the mapping.
+    ///
+    /// Unlike the huge pages used transparently, the mapping fails on page faults
+    /// if the huge pages of the size are not available. The size and the offset of
+    /// the mapping, as well as its alignment, must be multiples of the page size.
+    ///
+    /// The default value is `PageSize::Size4K`.
+    pub fn page_size(mut self, page_size: PageSize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Sets the attachment of the System V shared memory segment that is mapped.
+    ///
+    /// The attachment is held by the mapping and by the copies of the mapping, e.g.,
+    /// in the child vmar when the vmar is forked, so that they are all counted as
+    /// the attachments of the segment.
+    ///
+    /// The default value is `None`.
+    pub fn shm_attachment(mut self, shm_attachment: ShmAttachment) -> Self {
+        self.shm_attachment = Some(shm_attachment);
+        self
+    }
+
     /// Creates the mapping.
     ///
     /// All options will be checked at this point.
@@ -721,10 +1410,27 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
     pub fn build(self) -> Result<Vaddr> {
         self.check_options()?;
         let parent_vmar = self.parent.0.clone();
+        // Like `mlock`, the mappings locked by `mlockall(MCL_FUTURE)` are limited by
+        // `RLIMIT_MEMLOCK`, and their pages are faulted in unless they are locked on faults.
+        let lock_flags = parent_vmar.default_mapping_flags()
+            & (MappingFlags::LOCKED | MappingFlags::LOCKONFAULT);
+        if lock_flags.contains(MappingFlags::LOCKED) {
+            check_memlock_limit(&current!(), self.size).map_err(|err| match err.error() {
+                Errno::ENOMEM => {
+                    Error::with_message(Errno::EAGAIN, "the locked memory exceeds RLIMIT_MEMLOCK")
+                }
+                _ => err,
+            })?;
+        }
         let vmo_ = self.vmo.0.clone();
-        let vm_mapping = Arc::new(VmMapping::build_mapping(self)?);
+        let vm_mapping = VmMapping::new_arc(VmMapping::build_mapping(self)?);
         let map_to_addr = vm_mapping.map_to_addr();
-        parent_vmar.add_mapping(vm_mapping);
+        parent_vmar.add_mapping(vm_mapping.clone());
+        if lock_flags == MappingFlags::LOCKED {
+            // Like Linux, the failures to fault in the pages are ignored, since the
+            // mapping has been created.
+            let _ = vm_mapping.prefault(vm_mapping.range());
+        }
         Ok(map_to_addr)
     }
 
@@ -746,6 +1452,14 @@ impl<R1, R2> VmarMapOptions<R1, R2> {
                 return_errno_with_message!(Errno::EINVAL, "invalid offset");
             }
         }
+        // Check the page size.
+        let page_nbytes = self.page_size.nbytes();
+        if !self.page_size.is_supported()
+            || self.size % page_nbytes != 0
+            || self.align % page_nbytes != 0
+        {
+            return_errno_with_message!(Errno::EINVAL, "invalid page size");
+        }
         self.check_perms()?;
         self.check_overwrite()?;
         Ok(())
@@ -138,7 +138,7 @@ impl Vmo<Rights> {
 
     /// Restricts the access rights given the mask.
     pub fn restrict(mut self, mask: Rights) -> Self {
-        self.1 |= mask;
+        self.1 &= mask;
         self
     }
 
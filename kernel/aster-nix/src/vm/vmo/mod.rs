@@ -11,7 +11,11 @@ use aster_frame::{
 };
 use aster_rights::Rights;
 
-use crate::prelude::*;
+use super::swap::SwappedPage;
+use crate::{
+    events::{Events, Observer, Subject},
+    prelude::*,
+};
 
 mod dyn_cap;
 mod options;
@@ -192,8 +196,21 @@ pub(super) struct Vmo_ {
     page_idx_offset: usize,
     /// The virtual pages where the VMO resides.
     pages: Pages,
+    /// The pages that are swapped out, indexed in the same way as `pages`.
+    swapped_pages: Arc<Mutex<BTreeMap<usize, Arc<SwappedPage>>>>,
+    /// The mappings that are notified before the pages are written back.
+    writeback_subject: Subject<VmoWriteback>,
+}
+
+/// The event that the pages of a VMO are about to be written back to its pager.
+#[derive(Debug, Clone)]
+pub struct VmoWriteback {
+    /// The indices of the pages to write back.
+    pub page_idx_range: Range<usize>,
 }
 
+impl Events for VmoWriteback {}
+
 fn clone_page(page: &VmFrame) -> Result<VmFrame> {
     let new_page = VmAllocOptions::new(1).alloc_single()?;
     new_page.copy_from_frame(page);
@@ -211,12 +228,24 @@ impl Vmo_ {
     ///    obtain a page from the pager directly without the need to be set as exclusive.
     /// 3. For a File-backed VMO that requires triggering the COW mechanism, obtain a page
     ///    from the pager and then copy it. This page can be set as exclusive.
+    ///
+    /// If the page has been swapped out, it is read back into a new page, which can be set
+    /// as exclusive if the VMO requires COW.
     fn prepare_page(
         &self,
         page_idx: usize,
         is_cow_vmo: bool,
         will_write: bool,
     ) -> Result<(VmFrame, bool)> {
+        {
+            let mut swapped_pages = self.swapped_pages.lock();
+            if let Some(swapped_page) = swapped_pages.get(&page_idx) {
+                let page = swapped_page.swap_in()?;
+                swapped_pages.remove(&page_idx);
+                return Ok((page, is_cow_vmo));
+            }
+        }
+
         let (page, should_mark_exclusive) = match &self.pager {
             None => {
                 // Condition 1. The new anonymous page only need to be marked as `ExclusivePage`
@@ -276,13 +305,24 @@ impl Vmo_ {
     /// Commit the page corresponding to the target offset in the VMO and return that page.
     /// If the current offset has already been committed, the page will be returned directly.
     /// During the commit process, the Copy-On-Write (COW) mechanism may be triggered depending on the circumstances.
+    ///
+    /// A page committed for writing is about to be updated through memory mappings,
+    /// so the pager (if any) is notified of the update in advance.
     pub fn commit_page(&self, offset: usize, will_write: bool) -> Result<VmFrame> {
         let page_idx = offset / PAGE_SIZE + self.page_idx_offset;
-        self.pages.with(|pages, size| {
+        let (page, is_cow_vmo) = self.pages.with(|pages, size| {
             let is_cow_vmo = pages.is_marked(VmoMark::CowVmo);
             let mut cursor = pages.cursor_mut(page_idx as u64);
-            self.commit_with_cursor(&mut cursor, is_cow_vmo, will_write)
-        })
+            let page = self.commit_with_cursor(&mut cursor, is_cow_vmo, will_write)?;
+            Ok::<_, Error>((page, is_cow_vmo))
+        })?;
+        if will_write
+            && let Some(pager) = &self.pager
+            && !is_cow_vmo
+        {
+            pager.update_page(page_idx)?;
+        }
+        Ok(page)
     }
 
     /// Decommit the page corresponding to the target offset in the VMO.
@@ -291,6 +331,7 @@ impl Vmo_ {
         self.pages.with(|pages, size| {
             let is_cow_vmo = pages.is_marked(VmoMark::CowVmo);
             let mut cursor = pages.cursor_mut(page_idx as u64);
+            self.swapped_pages.lock().remove(&page_idx);
             if cursor.remove().is_some()
                 && let Some(pager) = &self.pager
                 && !is_cow_vmo
@@ -301,6 +342,62 @@ impl Vmo_ {
         })
     }
 
+    /// Swap out the page at the target index in pages, if the page is not used elsewhere,
+    /// e.g., mapped or shared with other VMOs.
+    ///
+    /// Only the pages that are not shared with a pager can be swapped out. Return whether
+    /// the page is swapped out.
+    pub fn swap_out_page(&self, page_idx: usize) -> Result<bool> {
+        if self.pager.is_some() && !self.is_cow_vmo() {
+            return Ok(false);
+        }
+
+        self.pages.with(|pages, size| {
+            let mut cursor = pages.cursor_mut(page_idx as u64);
+            let Some(page) = cursor.load() else {
+                return Ok(false);
+            };
+            // The only reference is held by `pages`.
+            if page.reference_count() != 1 {
+                return Ok(false);
+            }
+            let Some(swapped_page) = SwappedPage::swap_out(&page)? else {
+                return Ok(false);
+            };
+            cursor.remove();
+            self.swapped_pages.lock().insert(page_idx, swapped_page);
+            Ok(true)
+        })
+    }
+
+    /// Decommit the page at the target index in pages, which is shared with the pager, if
+    /// the page is not used elsewhere.
+    ///
+    /// The pager is responsible for writing back the page before the page is decommitted.
+    /// Return whether the page is decommitted.
+    pub fn decommit_unused_page(&self, page_idx: usize) -> Result<bool> {
+        let Some(pager) = &self.pager else {
+            return Ok(false);
+        };
+
+        self.pages.with(|pages, size| {
+            if pages.is_marked(VmoMark::CowVmo) {
+                return Ok(false);
+            }
+            let mut cursor = pages.cursor_mut(page_idx as u64);
+            let Some(page) = cursor.load() else {
+                return Ok(false);
+            };
+            // The references are held by `pages` and the pager.
+            if page.reference_count() != 2 {
+                return Ok(false);
+            }
+            cursor.remove();
+            pager.decommit_page(page_idx)?;
+            Ok(true)
+        })
+    }
+
     /// Commit a range of pages in the VMO, returns the pages in this range.
     pub fn commit(&self, range: Range<usize>, will_write: bool) -> Result<VmFrameVec> {
         self.pages.with(|pages, size| {
@@ -325,6 +422,77 @@ impl Vmo_ {
         })
     }
 
+    /// Commit a range of pages in the VMO with physically contiguous frames, so that the
+    /// range can be mapped with a huge page. The range must be aligned to its size, which
+    /// must be a power of two.
+    ///
+    /// Return `None` if the range cannot be backed by contiguous frames, i.e., the VMO is
+    /// file-backed or requires COW, or some pages in the range have been committed with
+    /// other frames or swapped out. Return `ENOMEM` if there are not enough contiguous
+    /// frames, which is not retried after reclaiming memory.
+    pub fn commit_contiguous(&self, range: Range<usize>) -> Result<Option<VmFrameVec>> {
+        debug_assert!(range.len().is_power_of_two());
+        debug_assert!(range.start % range.len() == 0);
+        if self.pager.is_some() {
+            return Ok(None);
+        }
+
+        self.pages.with(|pages, size| {
+            if range.end > size {
+                return_errno_with_message!(Errno::EINVAL, "operated range exceeds the vmo size");
+            }
+            if pages.is_marked(VmoMark::CowVmo) {
+                return Ok(None);
+            }
+
+            let nframes = range.len() / PAGE_SIZE;
+            let start_page_idx = (range.start / PAGE_SIZE + self.page_idx_offset) as u64;
+            let start_idx = start_page_idx as usize;
+            if self
+                .swapped_pages
+                .lock()
+                .range(start_idx..start_idx + nframes)
+                .next()
+                .is_some()
+            {
+                return Ok(None);
+            }
+            let mut committed_frames = VmFrameVec::new_with_capacity(nframes);
+            let mut cursor = pages.cursor_mut(start_page_idx);
+            for _ in 0..nframes {
+                if let Some(committed_page) = cursor.load() {
+                    committed_frames.push(committed_page.clone());
+                }
+                cursor.next();
+            }
+
+            // The pages may have been committed contiguously by an earlier call.
+            if committed_frames.len() == nframes {
+                let start_paddr = committed_frames.get(0).unwrap().start_paddr();
+                let is_contiguous = start_paddr % range.len() == 0
+                    && committed_frames
+                        .iter()
+                        .enumerate()
+                        .all(|(idx, frame)| frame.start_paddr() == start_paddr + idx * PAGE_SIZE);
+                return Ok(is_contiguous.then_some(committed_frames));
+            }
+            if !committed_frames.is_empty() {
+                return Ok(None);
+            }
+
+            let frames = VmAllocOptions::new(nframes)
+                .is_contiguous(true)
+                .no_retry(true)
+                .alloc()?;
+            let mut cursor = pages.cursor_mut(start_page_idx);
+            for frame in frames.iter() {
+                cursor.store(frame.clone());
+                cursor.next();
+            }
+            Ok(Some(frames))
+        })
+    }
+
     /// Decommit a range of pages in the VMO.
     pub fn decommit(&self, range: Range<usize>) -> Result<()> {
         self.pages.with(|pages, size| {
@@ -363,6 +531,43 @@ impl Vmo_ {
         Ok(())
     }
 
+    /// Write back the dirty pages within the target range to the pager.
+    ///
+    /// Only the VMO that shares pages with its pager has pages to write back.
+    pub fn sync(&self, range: Range<usize>) -> Result<()> {
+        let Some(pager) = &self.pager else {
+            return Ok(());
+        };
+        if self.is_cow_vmo() {
+            return Ok(());
+        }
+
+        pager.flush_pages(self.pager_page_idx_range(&range))
+    }
+
+    /// Ask the pager to prepare the pages within the target range in advance.
+    pub fn readahead(&self, range: Range<usize>) -> Result<()> {
+        match &self.pager {
+            Some(pager) => pager.readahead(self.pager_page_idx_range(&range)),
+            None => Ok(()),
+        }
+    }
+
+    /// Notify the pager that the pages within the target range are not likely to be
+    /// accessed in the near future.
+    pub fn deactivate(&self, range: Range<usize>) {
+        if let Some(pager) = &self.pager {
+            pager.deactivate_pages(self.pager_page_idx_range(&range));
+        }
+    }
+
+    /// Return the range of page indices in the pager that contains the target range.
+    fn pager_page_idx_range(&self, range: &Range<usize>) -> Range<usize> {
+        let raw_page_idx_range = get_page_idx_range(range);
+        (raw_page_idx_range.start + self.page_idx_offset)
+            ..(raw_page_idx_range.end + self.page_idx_offset)
+    }
+
     /// Clear the target range in current VMO.
     pub fn clear(&self, range: Range<usize>) -> Result<()> {
         let buffer = vec![0u8; range.end - range.start];
@@ -474,6 +679,20 @@ impl Vmo_ {
         }
     }
 
+    /// Clone the swapped pages to the child VMO.
+    ///
+    /// Like `pages`, the swapped pages are shared with a slice child, and are copied to a
+    /// COW child.
+    pub fn clone_swapped_pages_for_child(
+        &self,
+        child_type: ChildType,
+    ) -> Arc<Mutex<BTreeMap<usize, Arc<SwappedPage>>>> {
+        match child_type {
+            ChildType::Slice => self.swapped_pages.clone(),
+            ChildType::Cow => Arc::new(Mutex::new(self.swapped_pages.lock().clone())),
+        }
+    }
+
     /// Resize current VMO to target size.
     pub fn resize(&self, new_size: usize) -> Result<()> {
         assert!(self.flags.contains(VmoFlags::RESIZABLE));
@@ -504,6 +723,16 @@ impl Vmo_ {
         let page_idx_range = (raw_page_idx_range.start + self.page_idx_offset)
             ..(raw_page_idx_range.end + self.page_idx_offset);
         let is_cow_vmo = pages.is_marked(VmoMark::CowVmo);
+        {
+            let mut swapped_pages = self.swapped_pages.lock();
+            let swapped_idxs: Vec<usize> = swapped_pages
+                .range(page_idx_range.clone())
+                .map(|(idx, _)| *idx)
+                .collect();
+            for idx in swapped_idxs {
+                swapped_pages.remove(&idx);
+            }
+        }
         let mut cursor = pages.cursor_mut(page_idx_range.start as u64);
         for page_idx in page_idx_range {
             if cursor.remove().is_some()
@@ -518,6 +747,8 @@ impl Vmo_ {
     }
 
     /// Determine whether a page is committed.
+    ///
+    /// A page that is swapped out is not committed.
     pub fn is_page_committed(&self, page_idx: usize) -> bool {
         self.pages.with(|pages, size| {
             pages
@@ -526,11 +757,23 @@ impl Vmo_ {
         })
     }
 
+    /// Determine whether a page is swapped out.
+    pub fn is_page_swapped(&self, page_idx: usize) -> bool {
+        self.swapped_pages
+            .lock()
+            .contains_key(&(page_idx + self.page_idx_offset))
+    }
+
     /// Return the flags of current VMO.
     pub fn flags(&self) -> VmoFlags {
         self.flags
     }
 
+    /// Determine whether the VMO is backed by a pager.
+    pub fn is_file_backed(&self) -> bool {
+        self.pager.is_some()
+    }
+
     /// Determine whether the VMO is need COW mechanism.
     pub fn is_cow_vmo(&self) -> bool {
         self.pages
@@ -554,6 +797,11 @@ impl<R> Vmo<R> {
         self.0.is_page_committed(page_idx)
     }
 
+    /// Returns whether a page is present, i.e., it is committed or swapped out.
+    pub fn is_page_present(&self, page_idx: usize) -> bool {
+        self.0.is_page_committed(page_idx) || self.0.is_page_swapped(page_idx)
+    }
+
     pub fn get_committed_frame(&self, page_idx: usize, write_page: bool) -> Result<VmFrame> {
         self.0.commit_page(page_idx * PAGE_SIZE, write_page)
     }
@@ -561,6 +809,99 @@ impl<R> Vmo<R> {
     pub fn is_cow_vmo(&self) -> bool {
         self.0.is_cow_vmo()
     }
+
+    /// Commits the pages within the range (in bytes) with physically contiguous frames
+    /// and returns them, so that the range can be mapped with a huge page.
+    ///
+    /// The range must be aligned to its size, which must be a power of two. If the range
+    /// cannot be backed by contiguous frames, `None` is returned. If there are not enough
+    /// contiguous frames, `ENOMEM` is returned.
+    pub fn commit_contiguous(&self, range: Range<usize>) -> Result<Option<VmFrameVec>> {
+        self.0.commit_contiguous(range)
+    }
+
+    /// Returns whether the VMO is backed by a file, i.e., attached to a pager.
+    pub fn is_file_backed(&self) -> bool {
+        self.0.is_file_backed()
+    }
+
+    /// Writes back the dirty pages within the range (in bytes) to the pager.
+    ///
+    /// The range will be rounded down and up to page boundaries. A VMO that is
+    /// not backed by a file, or that is copied-on-write from a file, has nothing
+    /// to write back.
+    pub fn sync(&self, range: Range<usize>) -> Result<()> {
+        self.0.sync(range)
+    }
+
+    /// Registers an observer that is notified before the pages of the VMO are written back.
+    pub fn register_writeback_observer(&self, observer: Weak<dyn Observer<VmoWriteback>>) {
+        self.0.writeback_subject.register_observer(observer, ());
+    }
+
+    /// Notifies the observers that the pages within the range (in page indices) are about
+    /// to be written back.
+    ///
+    /// The mappings write-protect the pages, so that the next writes to them fault and
+    /// mark them dirty again.
+    pub fn notify_writeback(&self, page_idx_range: Range<usize>) {
+        self.0
+            .writeback_subject
+            .notify_observers(&VmoWriteback { page_idx_range });
+    }
+
+    /// Reads the pages within the range (in bytes) from the pager in advance.
+    ///
+    /// The reading is asynchronous. A VMO that is not backed by a file has
+    /// nothing to read.
+    pub fn readahead(&self, range: Range<usize>) -> Result<()> {
+        self.0.readahead(range)
+    }
+
+    /// Hints that the pages within the range (in bytes) are not likely to be
+    /// accessed in the near future, so that they are reclaimed first.
+    pub fn deactivate(&self, range: Range<usize>) {
+        self.0.deactivate(range)
+    }
+
+    /// Swaps out the page at the index, if the page is not mapped or shared.
+    ///
+    /// Returns whether the page is swapped out.
+    pub fn swap_out_page(&self, page_idx: usize) -> Result<bool> {
+        self.0.swap_out_page(page_idx + self.0.page_idx_offset())
+    }
+
+    /// Decommits the page at the index, which is shared with the pager, if the page
+    /// is not mapped.
+    ///
+    /// Returns whether the page is decommitted.
+    pub fn decommit_unused_page(&self, page_idx: usize) -> Result<bool> {
+        self.0
+            .decommit_unused_page(page_idx + self.0.page_idx_offset())
+    }
+
+    /// Creates a weak reference to the VMO, which does not keep it alive.
+    pub fn downgrade(&self) -> WeakVmo<R>
+    where
+        R: Clone,
+    {
+        WeakVmo(Arc::downgrade(&self.0), self.1.clone())
+    }
+
+    /// Returns whether the two capabilities refer to the same VMO.
+    pub fn is_same<R1>(&self, other: &Vmo<R1>) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A weak reference to a VMO, with the rights of the capability that it comes from.
+pub struct WeakVmo<R = Rights>(Weak<Vmo_>, R);
+
+impl<R: Clone> WeakVmo<R> {
+    /// Returns the VMO if it is still alive.
+    pub fn upgrade(&self) -> Option<Vmo<R>> {
+        self.0.upgrade().map(|vmo_| Vmo(vmo_, self.1.clone()))
+    }
 }
 
 /// get the page index range that contains the offset range of vmo
@@ -14,7 +14,7 @@ use aster_rights_proc::require;
 use typeflags_util::{SetExtend, SetExtendOp};
 
 use super::{Pager, Pages, Vmo, VmoFlags, VmoMark, VmoRightsOp};
-use crate::{prelude::*, vm::vmo::Vmo_};
+use crate::{events::Subject, prelude::*, vm::vmo::Vmo_};
 
 /// Options for allocating a root VMO.
 ///
@@ -137,6 +137,8 @@ fn alloc_vmo_(size: usize, flags: VmoFlags, pager: Option<Arc<dyn Pager>>) -> Re
         flags,
         page_idx_offset: 0,
         pages,
+        swapped_pages: Arc::new(Mutex::new(BTreeMap::new())),
+        writeback_subject: Subject::new(),
     })
 }
 
@@ -454,7 +456,9 @@ fn alloc_child_vmo_(
         pager: parent_vmo_.pager.clone(),
         flags: child_flags,
         pages: child_pages,
+        swapped_pages: parent_vmo_.clone_swapped_pages_for_child(child_type),
         page_idx_offset: parent_page_idx_offset + parent_vmo_.page_idx_offset(),
+        writeback_subject: Subject::new(),
     };
     Ok(new_vmo)
 }
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: MPL-2.0
 
+use core::ops::Range;
+
 use aster_frame::vm::VmFrame;
 
 use crate::prelude::*;
@@ -50,4 +52,23 @@ pub trait Pager: Send + Sync {
     /// such an assumption for its correctness; instead, it should simply ignore the
     /// call or return an error.
     fn decommit_page(&self, idx: usize) -> Result<()>;
+
+    /// Ask the pager to write back the dirty frames within a range of indices.
+    ///
+    /// The frames stay committed after being written back. Only the frames
+    /// that the pager has been notified of by `update_page` are dirty.
+    fn flush_pages(&self, idx_range: Range<usize>) -> Result<()>;
+
+    /// Ask the pager to prepare the frames within a range of indices in advance.
+    ///
+    /// The preparation is asynchronous, so the frames may still be unavailable
+    /// when this method returns. Later commits of the frames do not have to wait
+    /// for the preparation to complete.
+    fn readahead(&self, idx_range: Range<usize>) -> Result<()>;
+
+    /// Notify the pager that the frames within a range of indices are not likely
+    /// to be accessed in the near future.
+    ///
+    /// The pager can take these frames as the first ones to be freed.
+    fn deactivate_pages(&self, idx_range: Range<usize>);
 }
@@ -201,6 +201,9 @@ fn build_kernel_elf(
         &env_rustflags,
         &rustc_linker_script_arg,
         "-C relocation-model=static",
+        // Keep the relocations in the kernel ELF, so that the bzImage setup can
+        // move the kernel image for KASLR.
+        "-C link-arg=--emit-relocs",
         "-Z relro-level=off",
         // We do not really allow unwinding except for kernel testing. However, we need to specify
         // this to show backtraces when panicking.
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <poll.h>
+#include <stdint.h>
+#include <unistd.h>
+#include <sys/eventfd.h>
+
+#include "../network/test.h"
+
+FN_TEST(counter)
+{
+	uint64_t value;
+	int efd;
+
+	efd = TEST_SUCC(eventfd(0, EFD_NONBLOCK));
+	TEST_ERRNO(read(efd, &value, sizeof(value)), EAGAIN);
+
+	value = 3;
+	TEST_RES(write(efd, &value, sizeof(value)), _ret == sizeof(value));
+	TEST_RES(write(efd, &value, sizeof(value)), _ret == sizeof(value));
+	TEST_RES(read(efd, &value, sizeof(value)),
+		 _ret == sizeof(value) && value == 6);
+	TEST_ERRNO(read(efd, &value, sizeof(value)), EAGAIN);
+
+	value = UINT64_MAX;
+	TEST_ERRNO(write(efd, &value, sizeof(value)), EINVAL);
+	TEST_SUCC(close(efd));
+}
+END_TEST()
+
+FN_TEST(semaphore)
+{
+	struct pollfd pfd = { .events = POLLIN | POLLOUT };
+	uint64_t value;
+	int efd;
+
+	efd = TEST_SUCC(eventfd(2, EFD_NONBLOCK | EFD_SEMAPHORE));
+	pfd.fd = efd;
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && pfd.revents == (POLLIN | POLLOUT));
+
+	TEST_RES(read(efd, &value, sizeof(value)),
+		 _ret == sizeof(value) && value == 1);
+	TEST_RES(read(efd, &value, sizeof(value)),
+		 _ret == sizeof(value) && value == 1);
+	TEST_ERRNO(read(efd, &value, sizeof(value)), EAGAIN);
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && pfd.revents == POLLOUT);
+	TEST_SUCC(close(efd));
+}
+END_TEST()
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <fcntl.h>
+#include <limits.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/inotify.h>
+#include <sys/stat.h>
+
+#include "../network/test.h"
+
+#define DIR_PATH "/tmp/inotify_test"
+#define FILE_PATH DIR_PATH "/file"
+#define LINK_PATH DIR_PATH "/link"
+
+static int ifd;
+
+static char buf[sizeof(struct inotify_event) + NAME_MAX + 1]
+	__attribute__((aligned(__alignof__(struct inotify_event))));
+
+// Reads one event, returning its mask and copying its name if any.
+static long read_event(int wd, char *name)
+{
+	struct inotify_event *event = (struct inotify_event *)buf;
+	ssize_t len;
+
+	len = read(ifd, buf, sizeof(struct inotify_event) + NAME_MAX + 1);
+	if (len < (ssize_t)sizeof(struct inotify_event))
+		return -1;
+	if (event->wd != wd) {
+		errno = EINVAL;
+		return -1;
+	}
+	if (name != NULL)
+		strcpy(name, event->len > 0 ? event->name : "");
+	return event->mask;
+}
+
+FN_SETUP(inotify)
+{
+	ifd = CHECK(inotify_init1(IN_NONBLOCK));
+	CHECK(mkdir(DIR_PATH, 0755));
+}
+END_SETUP()
+
+FN_TEST(dir_events)
+{
+	char name[NAME_MAX + 1];
+	int wd, fd;
+
+	wd = TEST_SUCC(inotify_add_watch(ifd, DIR_PATH, IN_CREATE | IN_DELETE));
+
+	fd = TEST_SUCC(open(FILE_PATH, O_CREAT | O_WRONLY, 0644));
+	TEST_RES(read_event(wd, name),
+		 _ret == IN_CREATE && strcmp(name, "file") == 0);
+	TEST_SUCC(close(fd));
+	TEST_ERRNO(read_event(wd, name), EAGAIN);
+
+	TEST_SUCC(unlink(FILE_PATH));
+	TEST_RES(read_event(wd, name),
+		 _ret == IN_DELETE && strcmp(name, "file") == 0);
+
+	TEST_SUCC(inotify_rm_watch(ifd, wd));
+	TEST_RES(read_event(wd, NULL), _ret == IN_IGNORED);
+}
+END_TEST()
+
+FN_TEST(hard_link)
+{
+	int wd, fd;
+
+	fd = TEST_SUCC(open(FILE_PATH, O_CREAT | O_WRONLY, 0644));
+	TEST_SUCC(close(fd));
+	TEST_SUCC(link(FILE_PATH, LINK_PATH));
+
+	// The watch is on the inode, so it fires through the other link as well.
+	wd = TEST_SUCC(inotify_add_watch(ifd, FILE_PATH, IN_MODIFY));
+	TEST_RES(inotify_add_watch(ifd, LINK_PATH, IN_MODIFY), _ret == wd);
+
+	fd = TEST_SUCC(open(LINK_PATH, O_WRONLY));
+	TEST_RES(write(fd, "x", 1), _ret == 1);
+	TEST_RES(read_event(wd, NULL), _ret == IN_MODIFY);
+	TEST_SUCC(close(fd));
+
+	TEST_SUCC(unlink(LINK_PATH));
+	TEST_SUCC(unlink(FILE_PATH));
+	TEST_RES(read_event(wd, NULL), _ret == IN_IGNORED);
+}
+END_TEST()
+
+FN_TEST(cleanup)
+{
+	TEST_SUCC(close(ifd));
+	TEST_SUCC(rmdir(DIR_PATH));
+}
+END_TEST()
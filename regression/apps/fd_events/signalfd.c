@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <poll.h>
+#include <pthread.h>
+#include <signal.h>
+#include <unistd.h>
+#include <sys/signalfd.h>
+#include <sys/syscall.h>
+
+#include "../network/test.h"
+
+static int sfd;
+
+FN_SETUP(signalfd)
+{
+	sigset_t mask;
+
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR1);
+	sigaddset(&mask, SIGUSR2);
+	CHECK(sigprocmask(SIG_BLOCK, &mask, NULL));
+
+	sigdelset(&mask, SIGUSR2);
+	sfd = CHECK(signalfd(-1, &mask, SFD_NONBLOCK));
+}
+END_SETUP()
+
+FN_TEST(read_signal)
+{
+	struct signalfd_siginfo info;
+	struct pollfd pfd = { .fd = sfd, .events = POLLIN };
+
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0);
+	TEST_ERRNO(read(sfd, &info, sizeof(info)), EAGAIN);
+
+	TEST_SUCC(kill(getpid(), SIGUSR1));
+	TEST_RES(poll(&pfd, 1, 0), _ret == 1 && (pfd.revents & POLLIN));
+	TEST_RES(read(sfd, &info, sizeof(info)),
+		 _ret == sizeof(info) && info.ssi_signo == SIGUSR1 &&
+			 info.ssi_pid == getpid());
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(masked_signal)
+{
+	struct signalfd_siginfo info;
+	sigset_t mask;
+	int sig;
+
+	// The signals out of the mask are not read from the file.
+	TEST_SUCC(kill(getpid(), SIGUSR2));
+	TEST_ERRNO(read(sfd, &info, sizeof(info)), EAGAIN);
+
+	sigemptyset(&mask);
+	sigaddset(&mask, SIGUSR2);
+	TEST_RES(sigwait(&mask, &sig), _ret == 0 && sig == SIGUSR2);
+}
+END_TEST()
+
+static void *poll_and_read(void *arg)
+{
+	struct signalfd_siginfo info;
+	struct pollfd pfd = { .fd = sfd, .events = POLLIN };
+	long *res = arg;
+
+	// The signal is sent to this thread only.
+	syscall(SYS_tgkill, getpid(), syscall(SYS_gettid), SIGUSR1);
+
+	res[0] = poll(&pfd, 1, 0) == 1 && (pfd.revents & POLLIN);
+	res[1] = read(sfd, &info, sizeof(info)) == sizeof(info) &&
+		 info.ssi_signo == SIGUSR1;
+	return NULL;
+}
+
+FN_TEST(reader_thread)
+{
+	struct signalfd_siginfo info;
+	struct pollfd pfd = { .fd = sfd, .events = POLLIN };
+	long res[2] = { 0, 0 };
+	pthread_t thread;
+
+	// Like Linux, the signals of the thread that polls or reads the file are
+	// reported, not those of the thread that creates the file.
+	TEST_SUCC(pthread_create(&thread, NULL, poll_and_read, res));
+	TEST_SUCC(pthread_join(thread, NULL));
+	TEST_RES(res[0], _ret == 1);
+	TEST_RES(res[1], _ret == 1);
+
+	TEST_RES(poll(&pfd, 1, 0), _ret == 0);
+	TEST_ERRNO(read(sfd, &info, sizeof(info)), EAGAIN);
+}
+END_TEST()
@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <poll.h>
+#include <stdint.h>
+#include <time.h>
+#include <unistd.h>
+#include <sys/timerfd.h>
+
+#include "../network/test.h"
+
+static int tfd;
+
+FN_SETUP(timerfd)
+{
+	tfd = CHECK(timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK));
+}
+END_SETUP()
+
+FN_TEST(disarmed)
+{
+	struct itimerspec value;
+	uint64_t expirations;
+
+	TEST_RES(timerfd_gettime(tfd, &value),
+		 _ret == 0 && value.it_value.tv_sec == 0 &&
+			 value.it_value.tv_nsec == 0);
+	TEST_ERRNO(read(tfd, &expirations, sizeof(expirations)), EAGAIN);
+}
+END_TEST()
+
+FN_TEST(one_shot)
+{
+	struct itimerspec value = { .it_value = { .tv_nsec = 10 * 1000 * 1000 } };
+	struct pollfd pfd = { .fd = tfd, .events = POLLIN };
+	uint64_t expirations;
+
+	TEST_SUCC(timerfd_settime(tfd, 0, &value, NULL));
+	TEST_RES(poll(&pfd, 1, 1000), _ret == 1 && (pfd.revents & POLLIN));
+	TEST_RES(read(tfd, &expirations, sizeof(expirations)),
+		 _ret == sizeof(expirations) && expirations == 1);
+	TEST_ERRNO(read(tfd, &expirations, sizeof(expirations)), EAGAIN);
+	TEST_ERRNO(read(tfd, &expirations, sizeof(expirations) - 1), EINVAL);
+}
+END_TEST()
+
+FN_TEST(periodic)
+{
+	struct itimerspec value = {
+		.it_value = { .tv_nsec = 10 * 1000 * 1000 },
+		.it_interval = { .tv_nsec = 10 * 1000 * 1000 },
+	};
+	struct itimerspec old_value;
+	uint64_t expirations;
+
+	TEST_SUCC(timerfd_settime(tfd, 0, &value, NULL));
+	TEST_SUCC(usleep(100 * 1000));
+	TEST_RES(read(tfd, &expirations, sizeof(expirations)),
+		 _ret == sizeof(expirations) && expirations >= 2);
+
+	// Disarms the timer.
+	value.it_value.tv_nsec = 0;
+	TEST_RES(timerfd_settime(tfd, 0, &value, &old_value),
+		 _ret == 0 && old_value.it_interval.tv_nsec == 10 * 1000 * 1000);
+	TEST_SUCC(usleep(30 * 1000));
+	TEST_ERRNO(read(tfd, &expirations, sizeof(expirations)), EAGAIN);
+}
+END_TEST()
@@ -0,0 +1,135 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+
+#include <errno.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/ipc.h>
+#include <sys/msg.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#ifndef MSG_COPY
+#define MSG_COPY 040000
+#endif
+
+struct msgbuf_small {
+	long mtype;
+	char mtext[8];
+};
+
+static int msqid;
+
+static long num_msgs(void)
+{
+	struct msqid_ds ds;
+
+	if (msgctl(msqid, IPC_STAT, &ds) < 0)
+		return -1;
+	return ds.msg_qnum;
+}
+
+FN_SETUP(msgget)
+{
+	msqid = CHECK(msgget(IPC_PRIVATE, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+FN_TEST(send_receive)
+{
+	struct msgbuf_small msg1 = { .mtype = 1, .mtext = "first" };
+	struct msgbuf_small msg2 = { .mtype = 2, .mtext = "second" };
+	struct msgbuf_small buf;
+
+	TEST_SUCC(msgsnd(msqid, &msg1, sizeof(msg1.mtext), 0));
+	TEST_SUCC(msgsnd(msqid, &msg2, sizeof(msg2.mtext), 0));
+	TEST_RES(num_msgs(), _ret == 2);
+
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 2, 0),
+		 _ret == sizeof(buf.mtext) && buf.mtype == 2 &&
+			 strcmp(buf.mtext, "second") == 0);
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 0, 0),
+		 _ret == sizeof(buf.mtext) && buf.mtype == 1 &&
+			 strcmp(buf.mtext, "first") == 0);
+	TEST_ERRNO(msgrcv(msqid, &buf, sizeof(buf.mtext), 0, IPC_NOWAIT),
+		   ENOMSG);
+}
+END_TEST()
+
+FN_TEST(msg_copy)
+{
+	struct msgbuf_small msg1 = { .mtype = 1, .mtext = "first" };
+	struct msgbuf_small msg2 = { .mtype = 2, .mtext = "second" };
+	struct msgbuf_small buf;
+
+	TEST_SUCC(msgsnd(msqid, &msg1, sizeof(msg1.mtext), 0));
+	TEST_SUCC(msgsnd(msqid, &msg2, sizeof(msg2.mtext), 0));
+
+	// The message at the position is copied without being removed.
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 1,
+			MSG_COPY | IPC_NOWAIT),
+		 _ret == sizeof(buf.mtext) && buf.mtype == 2 &&
+			 strcmp(buf.mtext, "second") == 0);
+	TEST_RES(num_msgs(), _ret == 2);
+
+	TEST_ERRNO(msgrcv(msqid, &buf, sizeof(buf.mtext), 2,
+			  MSG_COPY | IPC_NOWAIT),
+		   ENOMSG);
+	TEST_ERRNO(msgrcv(msqid, &buf, sizeof(buf.mtext), 0, MSG_COPY),
+		   EINVAL);
+	TEST_ERRNO(msgrcv(msqid, &buf, sizeof(buf.mtext), 0,
+			  MSG_COPY | MSG_EXCEPT | IPC_NOWAIT),
+		   EINVAL);
+	TEST_ERRNO(msgrcv(msqid, &buf, 1, 0, MSG_COPY | IPC_NOWAIT), E2BIG);
+
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 0, 0),
+		 _ret == sizeof(buf.mtext) && buf.mtype == 1);
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 0, 0),
+		 _ret == sizeof(buf.mtext) && buf.mtype == 2);
+}
+END_TEST()
+
+FN_TEST(receive_wakeup)
+{
+	struct msgbuf_small msg = { .mtype = 3, .mtext = "third" };
+	struct msgbuf_small buf;
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// Give the parent a chance to block on the queue.
+		usleep(100 * 1000);
+		msgsnd(msqid, &msg, sizeof(msg.mtext), 0);
+		_exit(0);
+	}
+
+	TEST_RES(msgrcv(msqid, &buf, sizeof(buf.mtext), 3, 0),
+		 _ret == sizeof(buf.mtext) && strcmp(buf.mtext, "third") == 0);
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && WIFEXITED(status));
+}
+END_TEST()
+
+FN_TEST(remove)
+{
+	struct msgbuf_small buf;
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		if (msgrcv(msqid, &buf, sizeof(buf.mtext), 0, 0) < 0 &&
+		    errno == EIDRM)
+			_exit(0);
+		_exit(1);
+	}
+
+	usleep(100 * 1000);
+	TEST_SUCC(msgctl(msqid, IPC_RMID, NULL));
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+	TEST_ERRNO(msgctl(msqid, IPC_STAT, &(struct msqid_ds){}), EINVAL);
+}
+END_TEST()
@@ -0,0 +1,111 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <errno.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/ipc.h>
+#include <sys/sem.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+static int semid;
+
+static void handle_sigusr1(int sig)
+{
+	(void)sig;
+}
+
+FN_SETUP(semget)
+{
+	semid = CHECK(semget(IPC_PRIVATE, 2, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+FN_TEST(semop_nowait)
+{
+	struct sembuf op = { .sem_num = 0, .sem_op = -1, .sem_flg = IPC_NOWAIT };
+
+	TEST_RES(semctl(semid, 0, GETVAL), _ret == 0);
+	TEST_ERRNO(semop(semid, &op, 1), EAGAIN);
+
+	TEST_SUCC(semctl(semid, 0, SETVAL, 1));
+	TEST_SUCC(semop(semid, &op, 1));
+	TEST_RES(semctl(semid, 0, GETVAL), _ret == 0);
+	TEST_RES(semctl(semid, 0, GETPID), _ret == getpid());
+}
+END_TEST()
+
+FN_TEST(semop_wakeup)
+{
+	struct sembuf op = { .sem_num = 1, .sem_op = -1 };
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		// Wait for the parent to block on the semaphore.
+		while (semctl(semid, 1, GETNCNT) != 1)
+			usleep(1000);
+		semctl(semid, 1, SETVAL, 1);
+		_exit(0);
+	}
+
+	TEST_SUCC(semop(semid, &op, 1));
+	TEST_RES(semctl(semid, 1, GETNCNT), _ret == 0);
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && WIFEXITED(status));
+}
+END_TEST()
+
+FN_TEST(semop_interrupted)
+{
+	struct sembuf op = { .sem_num = 0, .sem_op = -1 };
+	struct sigaction sa = { .sa_handler = handle_sigusr1 };
+	pid_t pid;
+	int status;
+
+	// Each waiter is interrupted by its own signal only.
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		_exit(semop(semid, &op, 1) == 0 ? 0 : 1);
+	}
+
+	TEST_SUCC(sigaction(SIGUSR1, &sa, NULL));
+	if (TEST_SUCC(fork()) == 0) {
+		// Wait for both the parent and the sibling to block on the semaphore.
+		while (semctl(semid, 0, GETNCNT) != 2)
+			usleep(1000);
+		kill(getppid(), SIGUSR1);
+		_exit(0);
+	}
+	TEST_ERRNO(semop(semid, &op, 1), EINTR);
+	TEST_RES(wait(NULL), _ret > 0);
+
+	// The sibling keeps waiting until the semaphore is released.
+	TEST_RES(waitpid(pid, &status, WNOHANG), _ret == 0);
+	TEST_SUCC(semctl(semid, 0, SETVAL, 1));
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
+
+FN_TEST(remove)
+{
+	struct sembuf op = { .sem_num = 0, .sem_op = -1 };
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		_exit(semop(semid, &op, 1) < 0 && errno == EIDRM ? 0 : 1);
+	}
+
+	while (semctl(semid, 0, GETNCNT) != 1)
+		usleep(1000);
+	TEST_SUCC(semctl(semid, 0, IPC_RMID));
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+	TEST_ERRNO(semctl(semid, 0, GETVAL), EINVAL);
+}
+END_TEST()
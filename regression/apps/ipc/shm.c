@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <unistd.h>
+#include <sys/ipc.h>
+#include <sys/mman.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define SEG_SIZE 8192
+
+static int shmid;
+
+static unsigned long nattch(void)
+{
+	struct shmid_ds ds;
+
+	if (shmctl(shmid, IPC_STAT, &ds) < 0)
+		return -1;
+	return ds.shm_nattch;
+}
+
+FN_SETUP(shmget)
+{
+	shmid = CHECK(shmget(IPC_PRIVATE, SEG_SIZE, IPC_CREAT | 0600));
+}
+END_SETUP()
+
+FN_TEST(attach_detach)
+{
+	char *addr;
+
+	TEST_RES(nattch(), _ret == 0);
+
+	addr = (char *)TEST_RES((long)shmat(shmid, NULL, 0), _ret != -1);
+	TEST_RES(nattch(), _ret == 1);
+
+	addr[0] = 'a';
+	TEST_RES(shmdt(addr), _ret == 0);
+	TEST_RES(nattch(), _ret == 0);
+
+	addr = (char *)TEST_RES((long)shmat(shmid, NULL, SHM_RDONLY), _ret != -1);
+	TEST_RES(addr[0], _ret == 'a');
+	TEST_RES(shmdt(addr), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(munmap_detaches)
+{
+	char *addr;
+
+	addr = (char *)TEST_RES((long)shmat(shmid, NULL, 0), _ret != -1);
+	TEST_RES(nattch(), _ret == 1);
+
+	// Splitting the mapping makes two attachments, as in Linux.
+	TEST_SUCC(mprotect(addr, SEG_SIZE / 2, PROT_READ));
+	TEST_RES(nattch(), _ret == 2);
+
+	TEST_SUCC(munmap(addr, SEG_SIZE));
+	TEST_RES(nattch(), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(fork_and_exit)
+{
+	char *addr;
+	int pipefds[2];
+	char byte;
+	pid_t pid;
+	int status;
+
+	addr = (char *)TEST_RES((long)shmat(shmid, NULL, 0), _ret != -1);
+	TEST_SUCC(pipe(pipefds));
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0) {
+		close(pipefds[1]);
+		addr[1] = 'b';
+		// Wait for the parent to check the attachments.
+		read(pipefds[0], &byte, 1);
+		_exit(0);
+	}
+	close(pipefds[0]);
+
+	// The child inherits the attachment.
+	TEST_RES(nattch(), _ret == 2);
+
+	TEST_SUCC(write(pipefds[1], "x", 1));
+	TEST_RES(waitpid(pid, &status, 0), _ret == pid && WIFEXITED(status));
+	TEST_RES(addr[1], _ret == 'b');
+	TEST_RES(nattch(), _ret == 1);
+
+	TEST_SUCC(close(pipefds[1]));
+	TEST_SUCC(shmdt(addr));
+}
+END_TEST()
+
+FN_TEST(remove)
+{
+	char *addr;
+
+	addr = (char *)TEST_RES((long)shmat(shmid, NULL, 0), _ret != -1);
+
+	// The segment is kept until it is no longer attached.
+	TEST_SUCC(shmctl(shmid, IPC_RMID, NULL));
+	TEST_RES(nattch(), _ret == 1);
+
+	TEST_SUCC(shmdt(addr));
+	TEST_ERRNO(shmctl(shmid, IPC_STAT, &(struct shmid_ds){}), EINVAL);
+}
+END_TEST()
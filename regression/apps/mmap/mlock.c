@@ -0,0 +1,118 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define PAGE_SIZE 4096
+#define MAP_SIZE (4 * PAGE_SIZE)
+
+static int is_locked(void *addr)
+{
+	// Like Linux, `MS_INVALIDATE` fails on the locked pages.
+	if (msync(addr, PAGE_SIZE, MS_ASYNC | MS_INVALIDATE) == 0)
+		return 0;
+	if (errno != EBUSY)
+		return -1;
+	errno = 0;
+	return 1;
+}
+
+static int is_resident(void *addr, size_t len)
+{
+	unsigned char vec[MAP_SIZE / PAGE_SIZE];
+
+	if (mincore(addr, len, vec) < 0)
+		return -1;
+	for (size_t i = 0; i < len / PAGE_SIZE; ++i)
+		if (!(vec[i] & 1))
+			return 0;
+	return 1;
+}
+
+static void *map_anon(void)
+{
+	return mmap(NULL, MAP_SIZE, PROT_READ | PROT_WRITE,
+		    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+}
+
+FN_TEST(mlockall_future)
+{
+	char *addr;
+
+	TEST_SUCC(mlockall(MCL_FUTURE));
+	addr = (char *)TEST_RES((long)map_anon(), _ret != (long)MAP_FAILED);
+	TEST_RES(is_locked(addr), _ret == 1);
+	TEST_RES(is_resident(addr, MAP_SIZE), _ret == 1);
+
+	TEST_SUCC(munlockall());
+	TEST_RES(is_locked(addr), _ret == 0);
+	TEST_SUCC(munmap(addr, MAP_SIZE));
+}
+END_TEST()
+
+static int child_after_fork(char *parent_addr)
+{
+	char *addr;
+
+	// The memory locks are not inherited by the child.
+	if (is_locked(parent_addr) != 0)
+		return 1;
+	addr = map_anon();
+	if (addr == MAP_FAILED || is_locked(addr) != 0)
+		return 1;
+	return 0;
+}
+
+FN_TEST(fork_after_mlockall)
+{
+	char *addr;
+	pid_t pid;
+	int status;
+
+	TEST_SUCC(mlockall(MCL_CURRENT | MCL_FUTURE));
+	addr = (char *)TEST_RES((long)map_anon(), _ret != (long)MAP_FAILED);
+	TEST_RES(is_locked(addr), _ret == 1);
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(child_after_fork(addr));
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+
+	TEST_SUCC(munlockall());
+	TEST_SUCC(munmap(addr, MAP_SIZE));
+}
+END_TEST()
+
+static int child_with_memlock_limit(void)
+{
+	struct rlimit limit = { .rlim_cur = PAGE_SIZE, .rlim_max = PAGE_SIZE };
+
+	// Root is not limited by `RLIMIT_MEMLOCK`.
+	if (setresuid(65534, 65534, 65534) < 0 ||
+	    setrlimit(RLIMIT_MEMLOCK, &limit) < 0)
+		return 1;
+	if (mlockall(MCL_FUTURE) < 0)
+		return 1;
+	if (map_anon() != MAP_FAILED || errno != EAGAIN)
+		return 1;
+	return 0;
+}
+
+FN_TEST(mlockall_future_memlock_limit)
+{
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(child_with_memlock_limit());
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+}
+END_TEST()
@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+#include <fcntl.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/mman.h>
+
+#include "../network/test.h"
+
+#define FILE_PATH "/ext2/msync_test"
+#define PAGE_SIZE 4096
+#define FILE_SIZE (2 * PAGE_SIZE)
+
+static int fd;
+static char *addr;
+
+FN_SETUP(mmap)
+{
+	fd = CHECK(open(FILE_PATH, O_RDWR | O_CREAT | O_TRUNC, 0644));
+	CHECK(ftruncate(fd, FILE_SIZE));
+	addr = (char *)CHECK((long)mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE,
+					MAP_SHARED, fd, 0));
+}
+END_SETUP()
+
+static int check_file(char c)
+{
+	char buf[PAGE_SIZE];
+
+	if (pread(fd, buf, sizeof(buf), 0) != sizeof(buf))
+		return -1;
+	for (int i = 0; i < PAGE_SIZE; ++i)
+		if (buf[i] != c)
+			return -1;
+	return 0;
+}
+
+FN_TEST(write_after_msync)
+{
+	memset(addr, 'a', PAGE_SIZE);
+	TEST_SUCC(msync(addr, FILE_SIZE, MS_SYNC));
+	TEST_SUCC(check_file('a'));
+
+	// The page is written back again after it is written following the last writeback.
+	memset(addr, 'b', PAGE_SIZE);
+	TEST_SUCC(msync(addr, FILE_SIZE, MS_SYNC));
+	TEST_SUCC(check_file('b'));
+
+	memset(addr, 'c', PAGE_SIZE);
+	TEST_SUCC(fsync(fd));
+	TEST_SUCC(check_file('c'));
+
+	memset(addr, 'd', PAGE_SIZE);
+	TEST_SUCC(msync(addr, PAGE_SIZE, MS_ASYNC));
+	TEST_SUCC(check_file('d'));
+}
+END_TEST()
+
+FN_TEST(msync_invalid)
+{
+	TEST_ERRNO(msync(addr + 1, PAGE_SIZE, MS_SYNC), EINVAL);
+	TEST_ERRNO(msync(addr, PAGE_SIZE, MS_SYNC | MS_ASYNC), EINVAL);
+	TEST_ERRNO(msync(addr, SIZE_MAX & ~(PAGE_SIZE - 1), MS_SYNC), ENOMEM);
+	TEST_SUCC(msync(addr, 0, MS_SYNC));
+}
+END_TEST()
+
+FN_TEST(mincore_invalid)
+{
+	unsigned char vec[2];
+
+	TEST_ERRNO(mincore(addr + 1, PAGE_SIZE, vec), EINVAL);
+	TEST_ERRNO(mincore(addr, SIZE_MAX & ~(PAGE_SIZE - 1), vec), ENOMEM);
+	TEST_SUCC(mincore(addr, 0, NULL));
+	TEST_SUCC(mincore(addr, FILE_SIZE, vec));
+	TEST_RES(vec[0] & 1, _ret == 1);
+}
+END_TEST()
+
+FN_TEST(mremap_invalid)
+{
+	TEST_ERRNO((long)mremap(addr, PAGE_SIZE, SIZE_MAX - PAGE_SIZE + 2, 0),
+		   EINVAL);
+	TEST_ERRNO((long)mremap(addr, PAGE_SIZE, 2 * PAGE_SIZE,
+				MREMAP_MAYMOVE | MREMAP_FIXED,
+				(void *)(UINTPTR_MAX & ~(PAGE_SIZE - 1))),
+		   EINVAL);
+}
+END_TEST()
+
+FN_SETUP(cleanup)
+{
+	CHECK(munmap(addr, FILE_SIZE));
+	CHECK(close(fd));
+	CHECK(unlink(FILE_PATH));
+}
+END_SETUP()
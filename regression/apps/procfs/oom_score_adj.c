@@ -0,0 +1,95 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#define _GNU_SOURCE
+#include <fcntl.h>
+#include <signal.h>
+#include <unistd.h>
+#include <sys/wait.h>
+
+#include "../network/test.h"
+
+#define NOBODY_UID 65534
+
+static int write_oom_score_adj(pid_t pid, const char *value)
+{
+	char path[64];
+	int fd;
+	ssize_t len;
+
+	snprintf(path, sizeof(path), "/proc/%d/oom_score_adj", pid);
+	fd = open(path, O_WRONLY);
+	if (fd < 0)
+		return -1;
+	len = write(fd, value, strlen(value));
+	close(fd);
+	return len < 0 ? -1 : 0;
+}
+
+static int read_oom_score_adj(pid_t pid)
+{
+	char path[64];
+	char buf[16] = { 0 };
+	int fd;
+
+	snprintf(path, sizeof(path), "/proc/%d/oom_score_adj", pid);
+	fd = open(path, O_RDONLY);
+	if (fd < 0)
+		return -1;
+	if (read(fd, buf, sizeof(buf) - 1) < 0) {
+		close(fd);
+		return -1;
+	}
+	close(fd);
+	return atoi(buf);
+}
+
+static pid_t root_pid;
+
+FN_SETUP(spawn_root_process)
+{
+	root_pid = CHECK(fork());
+	if (root_pid == 0) {
+		pause();
+		_exit(0);
+	}
+}
+END_SETUP()
+
+static int child_as_nobody(void)
+{
+	if (setresuid(NOBODY_UID, NOBODY_UID, NOBODY_UID) < 0)
+		return 1;
+	// The process of another user cannot be changed. Like Linux, the open or
+	// the write fails with `EACCES`.
+	if (write_oom_score_adj(root_pid, "1000") == 0 || errno != EACCES)
+		return 1;
+	return 0;
+}
+
+FN_TEST(oom_score_adj_of_another_user)
+{
+	pid_t pid;
+	int status;
+
+	pid = TEST_SUCC(fork());
+	if (pid == 0)
+		_exit(child_as_nobody());
+	TEST_RES(waitpid(pid, &status, 0),
+		 _ret == pid && WIFEXITED(status) && WEXITSTATUS(status) == 0);
+	TEST_RES(read_oom_score_adj(root_pid), _ret == 0);
+}
+END_TEST()
+
+FN_TEST(oom_score_adj_of_root)
+{
+	TEST_SUCC(write_oom_score_adj(root_pid, "500"));
+	TEST_RES(read_oom_score_adj(root_pid), _ret == 500);
+}
+END_TEST()
+
+FN_SETUP(kill_root_process)
+{
+	CHECK(kill(root_pid, SIGKILL));
+	CHECK(waitpid(root_pid, NULL, 0));
+}
+END_SETUP()
@@ -8,7 +8,7 @@ SCRIPT_DIR=/regression
 cd ${SCRIPT_DIR}/..
 
 echo "Start process test......"
-tests="hello_world/hello_world fork/fork execve/execve fork_c/fork signal_c/signal_test pthread/pthread_test hello_pie/hello pty/open_pty getpid/getpid"
+tests="hello_world/hello_world fork/fork execve/execve fork_c/fork signal_c/itimer signal_c/signal_test pthread/pthread_test hello_pie/hello pty/open_pty getpid/getpid fd_events/eventfd fd_events/inotify fd_events/signalfd fd_events/timerfd ipc/msg ipc/sem ipc/shm mmap/msync mmap/mlock procfs/oom_score_adj"
 for testcase in ${tests}
 do 
     echo "Running test ${testcase}......"
@@ -0,0 +1,83 @@
+// SPDX-License-Identifier: MPL-2.0
+
+#include <fcntl.h>
+#include <signal.h>
+#include <unistd.h>
+#include <sys/time.h>
+
+#include "../network/test.h"
+
+static volatile sig_atomic_t num_sigvtalrm;
+static volatile sig_atomic_t num_sigprof;
+
+static void handle_signal(int sig)
+{
+	if (sig == SIGVTALRM)
+		num_sigvtalrm++;
+	else if (sig == SIGPROF)
+		num_sigprof++;
+}
+
+static int arm(int which, long usec)
+{
+	struct itimerval value = { .it_value = { .tv_usec = usec } };
+
+	return setitimer(which, &value, NULL);
+}
+
+FN_SETUP(handlers)
+{
+	struct sigaction sa = { .sa_handler = handle_signal };
+
+	CHECK(sigaction(SIGVTALRM, &sa, NULL));
+	CHECK(sigaction(SIGPROF, &sa, NULL));
+}
+END_SETUP()
+
+FN_TEST(getitimer)
+{
+	struct itimerval value;
+
+	TEST_SUCC(arm(ITIMER_VIRTUAL, 500 * 1000));
+	TEST_RES(getitimer(ITIMER_VIRTUAL, &value),
+		 _ret == 0 && value.it_value.tv_sec == 0 &&
+			 value.it_value.tv_usec > 0);
+	TEST_SUCC(arm(ITIMER_VIRTUAL, 0));
+	TEST_RES(getitimer(ITIMER_VIRTUAL, &value),
+		 _ret == 0 && value.it_value.tv_usec == 0);
+}
+END_TEST()
+
+FN_TEST(user_time)
+{
+	num_sigvtalrm = num_sigprof = 0;
+
+	// Both timers count the time spent in the user mode.
+	TEST_SUCC(arm(ITIMER_VIRTUAL, 50 * 1000));
+	TEST_SUCC(arm(ITIMER_PROF, 50 * 1000));
+	while (num_sigvtalrm == 0 || num_sigprof == 0)
+		;
+	TEST_RES(num_sigvtalrm, _ret == 1);
+	TEST_RES(num_sigprof, _ret == 1);
+}
+END_TEST()
+
+FN_TEST(system_time)
+{
+	static char buf[1 << 20];
+	int fd;
+
+	num_sigvtalrm = num_sigprof = 0;
+	fd = TEST_SUCC(open("/dev/zero", O_RDONLY));
+
+	// Only `ITIMER_PROF` counts the time spent in the kernel.
+	TEST_SUCC(arm(ITIMER_VIRTUAL, 100 * 1000));
+	TEST_SUCC(arm(ITIMER_PROF, 100 * 1000));
+	while (num_sigprof == 0)
+		read(fd, buf, sizeof(buf));
+	TEST_RES(num_sigvtalrm, _ret == 0);
+
+	TEST_SUCC(arm(ITIMER_VIRTUAL, 0));
+	TEST_SUCC(close(fd));
+}
+END_TEST()